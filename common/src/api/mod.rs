@@ -1,14 +1,20 @@
 use crate::auth;
 use crate::config::ConfigFile;
 use crate::errors::Error;
+use crate::http::TlsConfig;
 use crate::utils::zstd_compress;
 use anyhow::{Context, anyhow};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use log::debug;
 use reqwest::header::CONTENT_ENCODING;
-use reqwest::{RequestBuilder, Response};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::env;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
 use url::Url;
 
 pub mod v0;
@@ -17,14 +23,150 @@ pub mod v1;
 pub const AUTH_COOKIE_HEADER: &str = "X-Auth-Cookie";
 pub const WORKER_KEY_HEADER: &str = "X-Worker-Key";
 pub const SIGNUP_SECRET_HEADER: &str = "X-Signup-Secret";
+pub const WORKER_TOKEN_HEADER: &str = "X-Worker-Token";
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// The time of the daemon when it sent a response to a worker, in RFC 3339 format
+pub const SERVER_TIME_HEADER: &str = "X-Server-Time";
+
+/// The body of a failed api request
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    /// Generated by the daemon for every request and logged along with it
+    pub request_id: String,
+    pub error: String,
+}
+
+/// The body of a successful json response, if it was requested with `Accept: application/json; envelope=true`
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiEnvelope<T> {
+    /// Generated by the daemon for every request, same as the `X-Request-Id` header
+    pub request_id: String,
+    pub data: T,
+}
+
+impl ApiError {
+    /// Extract the message from an error body, older daemons send plain text
+    fn parse(body: &str) -> (String, Option<String>) {
+        match serde_json::from_str::<ApiError>(body) {
+            Ok(err) => (err.error, Some(err.request_id)),
+            Err(_) => (body.trim().to_string(), None),
+        }
+    }
+}
+
+/// A request the daemon, or a proxy in front of it, responded to with an error status
+#[derive(Debug)]
+pub struct ApiStatusError {
+    pub status: StatusCode,
+    description: String,
+}
+
+impl fmt::Display for ApiStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+impl std::error::Error for ApiStatusError {}
+
+fn describe_error(status: StatusCode, body: &str, request_id: Option<String>) -> Error {
+    let (message, id) = ApiError::parse(body);
+    let mut description = format!("Request failed with {status}");
+    if !message.is_empty() {
+        description.push_str(&format!(": {message}"));
+    }
+    if let Some(request_id) = id.or(request_id) {
+        description.push_str(&format!(" (request id {request_id})"));
+    }
+    Error::new(ApiStatusError {
+        status,
+        description,
+    })
+}
+
+/// The request never got an answer from the daemon, or a proxy in front of it reported the daemon as down. Unlike
+/// requests the daemon rejected, these are worth sending again later.
+pub fn is_unreachable(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            err.is_connect() || err.is_timeout() || err.is_request() || err.is_body()
+        } else if let Some(err) = cause.downcast_ref::<ApiStatusError>() {
+            matches!(
+                err.status,
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            )
+        } else {
+            false
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ClockObservation {
+    server_time: DateTime<Utc>,
+    local_time: DateTime<Utc>,
+    received: Instant,
+}
+
+/// The clock of the daemon, as of the last response that carried a [`SERVER_TIME_HEADER`]. Workers take their
+/// timestamps from it, so a drifting local clock doesn't affect the deadlines and durations of their builds.
+#[derive(Debug, Default)]
+pub struct ServerClock {
+    observed: Mutex<Option<ClockObservation>>,
+}
+
+impl ServerClock {
+    pub fn observe(&self, response: &Response) {
+        let server_time = response
+            .headers()
+            .get(SERVER_TIME_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok());
+        if let Some(server_time) = server_time {
+            self.observe_time(server_time.with_timezone(&Utc));
+        }
+    }
+
+    pub fn observe_time(&self, server_time: DateTime<Utc>) {
+        let observation = ClockObservation {
+            server_time,
+            local_time: Utc::now(),
+            received: Instant::now(),
+        };
+        *self.observed.lock().unwrap_or_else(PoisonError::into_inner) = Some(observation);
+    }
+
+    fn observation(&self) -> Option<ClockObservation> {
+        *self.observed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The current time of the daemon, `None` if it didn't send its time yet
+    pub fn now(&self) -> Option<DateTime<Utc>> {
+        let observation = self.observation()?;
+        let elapsed = chrono::Duration::from_std(observation.received.elapsed()).ok()?;
+        Some(observation.server_time + elapsed)
+    }
+
+    /// How far the local clock is ahead of the daemon, negative if it's behind. This includes the latency of the
+    /// response, which is negligible compared to a clock that is actually off.
+    pub fn skew(&self) -> Option<chrono::Duration> {
+        let observation = self.observation()?;
+        Some(observation.local_time - observation.server_time)
+    }
+}
 
 pub struct Client {
     endpoint: Url,
     client: crate::http::Client,
     is_default_endpoint: bool,
     auth_cookie: Option<String>,
+    bearer_token: Option<String>,
     worker_key: Option<String>,
+    worker_token: Option<String>,
     signup_secret: Option<String>,
+    server_clock: ServerClock,
 }
 
 impl Client {
@@ -59,17 +201,32 @@ impl Client {
             client,
             is_default_endpoint,
             auth_cookie,
+            bearer_token: None,
             worker_key: None,
+            worker_token: None,
             signup_secret: None,
+            server_clock: ServerClock::default(),
         })
     }
 
+    pub fn endpoint(&self) -> &str {
+        self.endpoint.as_str()
+    }
+
     pub fn with_auth_cookie(&mut self) -> anyhow::Result<&mut Self> {
-        if let Ok(cookie_path) = env::var("REBUILDERD_COOKIE_PATH") {
+        if let Ok(token) = env::var("REBUILDERD_TOKEN") {
+            debug!("Using bearer token from environment");
+            Ok(self.bearer_token(token))
+        } else if let Ok(cookie_path) = env::var("REBUILDERD_COOKIE_PATH") {
             debug!("Found cookie path in environment: {:?}", cookie_path);
             let auth_cookie =
                 auth::read_cookie_from_file(cookie_path).context("Failed to load auth cookie")?;
             Ok(self.auth_cookie(auth_cookie))
+        } else if let Some(auth_cookie) = auth::read_stored_cookie(self.endpoint.as_str())
+            .context("Failed to load stored credentials")?
+        {
+            debug!("Using stored credentials for {:?}", self.endpoint.as_str());
+            Ok(self.auth_cookie(auth_cookie))
         } else if self.is_default_endpoint {
             let auth_cookie = auth::find_auth_cookie().context("Failed to load auth cookie")?;
             Ok(self.auth_cookie(auth_cookie))
@@ -83,14 +240,38 @@ impl Client {
         self
     }
 
+    /// Authenticate with a token of the oidc provider configured in the daemon, instead of a cookie
+    pub fn bearer_token<I: Into<String>>(&mut self, token: I) -> &mut Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// Verify the daemon's certificate with a custom ca bundle or a pinned fingerprint
+    pub fn tls(&mut self, tls: &TlsConfig) -> anyhow::Result<&mut Self> {
+        self.client = tls
+            .apply(crate::http::Client::builder().zstd(true))?
+            .build()?;
+        Ok(self)
+    }
+
     pub fn worker_key<I: Into<String>>(&mut self, key: I) {
         self.worker_key = Some(key.into());
     }
 
+    /// Authenticate worker requests with a capability scoped token instead of the worker key
+    pub fn worker_token<I: Into<String>>(&mut self, token: I) {
+        self.worker_token = Some(token.into());
+    }
+
     pub fn signup_secret<I: Into<String>>(&mut self, secret: I) {
         self.signup_secret = Some(secret.into());
     }
 
+    /// The clock of the daemon, updated by the responses to popping and pinging jobs
+    pub fn server_clock(&self) -> &ServerClock {
+        &self.server_clock
+    }
+
     fn url_join(&self, route: &str) -> Url {
         let mut url = self.endpoint.clone();
         {
@@ -109,10 +290,18 @@ impl Client {
             req = req.header(AUTH_COOKIE_HEADER, auth_cookie);
         }
 
+        if let Some(bearer_token) = &self.bearer_token {
+            req = req.bearer_auth(bearer_token);
+        }
+
         if let Some(worker_key) = &self.worker_key {
             req = req.header(WORKER_KEY_HEADER, worker_key);
         }
 
+        if let Some(worker_token) = &self.worker_token {
+            req = req.header(WORKER_TOKEN_HEADER, worker_token);
+        }
+
         if let Some(signup_secret) = &self.signup_secret {
             req = req.header(SIGNUP_SECRET_HEADER, signup_secret);
         }
@@ -134,6 +323,13 @@ impl Client {
         self.authenticated(req)
     }
 
+    fn patch(&self, path: Cow<'static, str>) -> crate::http::RequestBuilder {
+        let url = self.url_join(&path);
+        debug!("Sending PATCH request to {}", url.as_str());
+        let req = self.client.patch(url);
+        self.authenticated(req)
+    }
+
     fn delete(&self, path: Cow<'static, str>) -> crate::http::RequestBuilder {
         let url = self.url_join(&path);
         debug!("Sending DELETE request to {}", url.as_str());
@@ -142,35 +338,141 @@ impl Client {
     }
 }
 
+#[async_trait]
+pub trait BodySizeResponse {
+    /// The daemon rejects bodies above its configured limit with 413, name the setting that needs to be raised
+    /// instead of only reporting the status code
+    async fn error_for_body_size(self, setting: &str) -> crate::errors::Result<Response>;
+}
+
+#[async_trait]
+impl BodySizeResponse for Response {
+    async fn error_for_body_size(self, setting: &str) -> crate::errors::Result<Response> {
+        if self.status() != StatusCode::PAYLOAD_TOO_LARGE {
+            return Ok(self);
+        }
+
+        let (message, _) = ApiError::parse(&self.text().await.unwrap_or_default());
+        Err(anyhow!(
+            "Request was rejected by the daemon as too large ({message}), the limit can be raised with {setting:?} in the [http] section of its configuration"
+        ))
+    }
+}
+
+#[async_trait]
+pub trait ApiResponse {
+    /// Like `error_for_status`, but the error includes the message and request id the daemon responded with
+    async fn error_for_api_status(self) -> crate::errors::Result<Response>;
+}
+
+#[async_trait]
+impl ApiResponse for Response {
+    async fn error_for_api_status(self) -> crate::errors::Result<Response> {
+        let status = self.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(self);
+        }
+
+        let request_id = self
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let body = self.text().await.unwrap_or_default();
+        Err(describe_error(status, &body, request_id))
+    }
+}
+
 #[async_trait]
 pub trait ZstdRequestBuilder {
+    /// Send the body zstd compressed. If the daemon, or a proxy in front of it, rejects the encoding with 415 the
+    /// request is sent again uncompressed.
     async fn send_encoded(self) -> crate::errors::Result<Response>;
 }
 
 #[async_trait]
 impl ZstdRequestBuilder for RequestBuilder {
     async fn send_encoded(self) -> crate::errors::Result<Response> {
-        if let Some(new_request) = self.try_clone() {
-            let mut request = self.build()?;
-
-            if let Some(body) = request.body_mut() {
-                if let Some(bytes) = body.as_bytes() {
-                    let encoded_body = zstd_compress(bytes).await?;
-
-                    new_request
-                        .body(encoded_body)
-                        .header(CONTENT_ENCODING, "zstd")
-                        .send()
-                        .await
-                        .map_err(Error::from)
-                } else {
-                    new_request.send().await.map_err(Error::from)
-                }
-            } else {
-                new_request.send().await.map_err(Error::from)
-            }
-        } else {
-            self.send().await.map_err(Error::from)
+        let (Some(encoded), Some(identity)) = (self.try_clone(), self.try_clone()) else {
+            return self.send().await.map_err(Error::from);
+        };
+
+        let request = self.build()?;
+        let Some(bytes) = request.body().and_then(|body| body.as_bytes()) else {
+            return identity.send().await.map_err(Error::from);
+        };
+
+        let encoded_body = zstd_compress(bytes).await?;
+        let res = encoded
+            .body(encoded_body)
+            .header(CONTENT_ENCODING, "zstd")
+            .send()
+            .await?;
+
+        if res.status() != StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            return Ok(res);
         }
+
+        debug!("Compressed request body was rejected, sending it again without compression");
+        identity.send().await.map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_clock() {
+        let clock = ServerClock::default();
+        assert!(clock.now().is_none());
+        assert!(clock.skew().is_none());
+
+        let server_time = Utc::now() - chrono::Duration::minutes(5);
+        clock.observe_time(server_time);
+        let skew = clock.skew().unwrap();
+        assert!(skew >= chrono::Duration::minutes(5));
+        assert!(skew < chrono::Duration::minutes(6));
+        let now = clock.now().unwrap();
+        assert!(now >= server_time);
+        assert!(now < server_time + chrono::Duration::minutes(1));
+    }
+
+    #[test]
+    fn test_describe_error() {
+        let err = describe_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            r#"{"request_id":"a1b2c3","error":"database is locked"}"#,
+            None,
+        );
+        assert_eq!(
+            err.to_string(),
+            "Request failed with 500 Internal Server Error: database is locked (request id a1b2c3)"
+        );
+
+        // older daemons respond with plain text
+        let err = describe_error(
+            StatusCode::NOT_FOUND,
+            "Not found\n",
+            Some("d4e5f6".to_string()),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Request failed with 404 Not Found: Not found (request id d4e5f6)"
+        );
+
+        let err = describe_error(StatusCode::FORBIDDEN, "", None);
+        assert_eq!(err.to_string(), "Request failed with 403 Forbidden");
+    }
+
+    #[test]
+    fn test_is_unreachable() {
+        let err = describe_error(StatusCode::BAD_GATEWAY, "", None);
+        assert!(is_unreachable(&err.context("Failed to report build")));
+
+        let err = describe_error(StatusCode::INTERNAL_SERVER_ERROR, "", None);
+        assert!(!is_unreachable(&err));
+
+        assert!(!is_unreachable(&anyhow!("Failed to parse response")));
     }
 }
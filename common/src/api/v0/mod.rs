@@ -1,9 +1,9 @@
 use crate::errors::*;
 use anyhow::bail;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Deref;
 use std::str::FromStr;
 
@@ -48,6 +48,83 @@ pub struct ListPkgs {
     pub architecture: Option<String>,
 }
 
+/// Look up the status of many packages at once, eg. to gate a release on all of them being reproducible
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PkgStatusBatchQuery {
+    pub names: Vec<String>,
+    pub distro: Option<String>,
+    pub suite: Option<String>,
+    pub architecture: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PkgStatusBatch {
+    /// Set if every requested name is known and all of its packages are reproducible
+    pub all_good: bool,
+    pub packages: Vec<PkgRelease>,
+    /// Requested names that didn't match any package
+    pub unknown: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PkgDiffQuery {
+    pub distro: Option<String>,
+    pub suite: Option<String>,
+    pub architecture: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PkgDiff {
+    pub name: String,
+    pub version: String,
+    pub distro: String,
+    pub suite: String,
+    pub architecture: String,
+    pub build_id: i32,
+    pub built_at: NaiveDateTime,
+    pub diffoscope: String,
+}
+
+/// A distro/suite/architecture combination rebuilderd has packages or a sync for
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SuiteInfo {
+    pub distro: String,
+    pub release: Option<String>,
+    pub suite: String,
+    pub architecture: String,
+    /// Binary packages that were part of the most recent sync
+    pub packages: i64,
+    pub last_sync: Option<NaiveDateTime>,
+    pub sync_source: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PkgArtifactsQuery {
+    pub distro: Option<String>,
+    pub suite: Option<String>,
+    pub architecture: Option<String>,
+}
+
+/// Everything needed to verify a rebuild independently of rebuilderd
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PkgArtifactChecksums {
+    pub name: String,
+    pub version: String,
+    pub distro: String,
+    pub suite: String,
+    pub architecture: String,
+    pub artifact_url: String,
+    /// The sha256 of the published artifact, as recorded during sync
+    pub checksum: Option<String>,
+    pub build_id: Option<i32>,
+    pub built_at: Option<NaiveDateTime>,
+    pub status: Status,
+    /// The sha256 of the rebuilt artifact, as reported by the worker
+    pub rebuilt_checksum: Option<String>,
+    /// The signed in-toto link of the rebuild, covering the rebuilt artifact
+    pub attestation_url: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QueueList {
     pub now: NaiveDateTime,
@@ -65,6 +142,33 @@ pub struct QueueItem {
     pub last_ping: Option<NaiveDateTime>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PkgBundleQuery {
+    pub distro: Option<String>,
+    pub suite: Option<String>,
+    pub architecture: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueuePositionQuery {
+    pub name: String,
+    pub distro: Option<String>,
+    pub suite: Option<String>,
+    pub architecture: Option<String>,
+}
+
+/// Where a queued package sits in the priority, queue date and popularity order
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuePosition {
+    pub item: QueueItem,
+    /// The number of pending jobs for the same backend and architecture that are going to be picked up first
+    pub ahead: i64,
+    /// The number of pending jobs that are picked up in random order together with this one
+    pub tied: i64,
+    /// A rough guess when a worker is going to pick up this job, based on the throughput of the last 24 hours
+    pub estimated_start: Option<NaiveDateTime>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListQueue {
     pub limit: Option<i64>,
@@ -247,6 +351,47 @@ pub struct SuiteStats {
     pub bad: usize,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StatsHistoryQuery {
+    pub distro: Option<String>,
+    pub suite: Option<String>,
+    /// Only return snapshots from this day on
+    pub since: Option<NaiveDate>,
+}
+
+/// The state of a suite at the end of a day, or the latest state for the current day
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsHistoryEntry {
+    pub date: NaiveDate,
+    pub distro: String,
+    pub release: Option<String>,
+    pub suite: Option<String>,
+    pub good: usize,
+    pub unknown: usize,
+    pub bad: usize,
+    /// Queued jobs that were dropped on this day because they expired
+    #[serde(default)]
+    pub expired: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WorkerStatsQuery {
+    /// Only count builds that finished at or after this time
+    pub since: Option<NaiveDateTime>,
+}
+
+/// The builds a worker reported, results that were shared with identical build inputs are only counted once
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkerStats {
+    pub builds: usize,
+    /// The share of builds that were GOOD, between 0 and 1
+    pub success_ratio: f64,
+    /// In seconds, builds without a start time are left out
+    pub average_duration: Option<f64>,
+    /// Number of builds for every status other than GOOD
+    pub failures: BTreeMap<String, usize>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PingRequest {
     pub queue_id: i32,
@@ -294,8 +439,8 @@ impl FromStr for Status {
         match s {
             "GOOD" => Ok(Status::Good),
             "BAD" => Ok(Status::Bad),
-            "UNKWN" => Ok(Status::Unknown),
-            "FAIL" => Ok(Status::Bad), // v0 had no concept of FAIL
+            "UNKWN" | "DOWNLOAD_FAILED" => Ok(Status::Unknown),
+            "FAIL" | "FLAKY" => Ok(Status::Bad), // v0 had no concept of FAIL or FLAKY
             _ => bail!("Unknown status: {:?}", s),
         }
     }
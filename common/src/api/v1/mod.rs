@@ -1,6 +1,6 @@
 mod models;
 
-use crate::api::{Client, ZstdRequestBuilder};
+use crate::api::{ApiResponse, BodySizeResponse, Client, ZstdRequestBuilder};
 use crate::errors::*;
 use async_trait::async_trait;
 pub use models::*;
@@ -40,6 +40,7 @@ use serde::{Deserialize, Serialize};
 ///
 /// ```
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
 #[cfg_attr(feature = "diesel", diesel(sql_type = Integer))]
 #[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
@@ -111,6 +112,18 @@ pub trait BuildRestApi {
     async fn get_build_artifact(&self, id: i32, artifact_id: i32) -> Result<RebuildArtifact>;
     async fn get_build_artifact_diffoscope(&self, id: i32, artifact_id: i32) -> Result<String>;
     async fn get_build_artifact_attestation(&self, id: i32, artifact_id: i32) -> Result<Vec<u8>>;
+    async fn get_build_bundle(&self, id: i32) -> Result<Vec<u8>>;
+    async fn get_verification_mismatches(
+        &self,
+        page: Option<&Page>,
+        origin_filter: Option<&OriginFilter>,
+    ) -> Result<ResultPage<VerificationMismatch>>;
+    async fn get_status_transitions(
+        &self,
+        page: Option<&Page>,
+        origin_filter: Option<&OriginFilter>,
+        transition_filter: Option<&TransitionFilter>,
+    ) -> Result<ResultPage<StatusTransition>>;
 }
 
 #[async_trait]
@@ -118,6 +131,13 @@ pub trait DashboardRestApi {
     async fn get_dashboard(&self, origin_filter: Option<&OriginFilter>) -> Result<DashboardState>;
 }
 
+#[async_trait]
+pub trait KeyRestApi {
+    async fn get_api_keys(&self, page: Option<&Page>) -> Result<ResultPage<ApiKey>>;
+    async fn issue_api_key(&self, request: IssueApiKeyRequest) -> Result<IssuedApiKey>;
+    async fn revoke_api_key(&self, id: i32) -> Result<()>;
+}
+
 #[async_trait]
 pub trait MetaRestApi {
     async fn get_distributions(&self) -> Result<Vec<String>>;
@@ -143,13 +163,28 @@ pub trait MetaRestApi {
         component: &str,
     ) -> Result<Vec<String>>;
 
+    async fn get_snapshot_generations(&self, distribution: &str)
+    -> Result<Vec<SnapshotGeneration>>;
+
     async fn get_public_keys(&self) -> Result<PublicKey>;
+    async fn get_schemas(&self) -> Result<Vec<String>>;
+    async fn get_schema(&self, name: &str) -> Result<serde_json::Value>;
 }
 
 #[async_trait]
 pub trait PackageRestApi {
     async fn submit_package_report(&self, report: &PackageReport) -> Result<()>;
 
+    /// Returns `false` if the daemon doesn't have the base revision of the delta on record, a full report needs to be
+    /// submitted instead
+    async fn submit_package_report_delta(&self, delta: &PackageReportDelta) -> Result<bool>;
+
+    async fn submit_package_popularity(&self, report: &PopularityReport) -> Result<()>;
+
+    async fn submit_build_durations(&self, report: &BuildDurationReport) -> Result<()>;
+
+    async fn submit_verdicts(&self, report: &VerdictReport) -> Result<()>;
+
     async fn get_source_packages(
         &self,
         page: Option<&Page>,
@@ -159,6 +194,10 @@ pub trait PackageRestApi {
 
     async fn get_source_package(&self, id: i32) -> Result<SourcePackage>;
 
+    async fn get_source_package_cross_rebuilds(&self, id: i32) -> Result<Vec<CrossRebuild>>;
+
+    async fn get_upstream_release(&self, name: &str, version: &str) -> Result<UpstreamRelease>;
+
     async fn get_binary_packages(
         &self,
         page: Option<&Page>,
@@ -167,6 +206,29 @@ pub trait PackageRestApi {
     ) -> Result<ResultPage<BinaryPackage>>;
 
     async fn get_binary_package(&self, id: i32) -> Result<BinaryPackage>;
+
+    async fn correct_binary_package(
+        &self,
+        id: i32,
+        correction: &BinaryPackageCorrection,
+    ) -> Result<BinaryPackage>;
+
+    async fn export_tracker(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+    ) -> Result<Vec<TrackerEntry>>;
+
+    async fn get_package_tags(&self, filter: Option<&PackageTagFilter>) -> Result<Vec<PackageTag>>;
+    async fn add_package_tag(&self, distribution: &str, name: &str, tag: &str) -> Result<()>;
+    async fn remove_package_tag(&self, distribution: &str, name: &str, tag: &str) -> Result<()>;
+
+    async fn annotate_package(
+        &self,
+        distribution: &str,
+        name: &str,
+        annotation: &PackageAnnotation,
+    ) -> Result<()>;
+    async fn remove_package_annotation(&self, distribution: &str, name: &str) -> Result<()>;
 }
 
 #[async_trait]
@@ -178,7 +240,20 @@ pub trait QueueRestApi {
         source_identity_filter: Option<&SourceIdentityFilter>,
     ) -> Result<ResultPage<QueuedJob>>;
 
-    async fn request_rebuild(&self, request: QueueJobRequest) -> Result<()>;
+    async fn get_queue_position(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+        source_identity_filter: Option<&SourceIdentityFilter>,
+    ) -> Result<Vec<QueuePosition>>;
+    async fn request_rebuild(&self, request: QueueJobRequest) -> Result<QueueJobResponse>;
+    /// Like `request_rebuild`, but reports the progress of large requeues while they are running
+    async fn request_rebuild_with_progress(
+        &self,
+        request: QueueJobRequest,
+        progress: &(dyn Fn(i64, i64) + Sync),
+    ) -> Result<QueueJobResponse>;
+    async fn request_bisect(&self, request: BisectRequest) -> Result<Bisect>;
+    async fn get_bisect(&self, id: i32) -> Result<Bisect>;
     async fn get_queued_job(&self, id: i32) -> Result<QueuedJob>;
     async fn drop_queued_job(&self, id: i32) -> Result<()>;
     async fn drop_queued_jobs(
@@ -186,8 +261,37 @@ pub trait QueueRestApi {
         origin_filter: Option<&OriginFilter>,
         source_identity_filter: Option<&SourceIdentityFilter>,
     ) -> Result<()>;
+    async fn assign_queued_jobs(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+        source_identity_filter: Option<&SourceIdentityFilter>,
+        request: AssignQueuedJobsRequest,
+    ) -> Result<AssignQueuedJobsResponse>;
+    /// Drop every job of the matching suites in one transaction, or the whole queue without a filter
+    async fn wipe_queue(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+        request: WipeQueueRequest,
+    ) -> Result<WipeQueueResponse>;
+    async fn export_queue(&self) -> Result<QueueSnapshot>;
+    async fn import_queue(&self, snapshot: &QueueSnapshot) -> Result<QueueImportResponse>;
     async fn request_work(&self, request: PopQueuedJobRequest) -> Result<JobAssignment>;
-    async fn ping_job(&self, id: i32) -> Result<()>;
+    /// Fails with [`JobAborted`] if an admin aborted the job
+    async fn ping_job(&self, id: i32) -> Result<PingJobResponse>;
+    /// Fails with [`JobAborted`] if an admin aborted the job
+    async fn ping_job_with_phase(
+        &self,
+        id: i32,
+        request: PingJobRequest,
+    ) -> Result<PingJobResponse>;
+}
+
+#[async_trait]
+pub trait SubscriptionRestApi {
+    async fn get_subscriptions(&self) -> Result<Vec<Subscription>>;
+    async fn subscribe(&self, request: SubscriptionRequest) -> Result<Subscription>;
+    async fn unsubscribe(&self, id: i32) -> Result<()>;
+    async fn confirm_subscription(&self, request: SubscriptionConfirmation) -> Result<()>;
 }
 
 #[async_trait]
@@ -196,6 +300,23 @@ pub trait WorkerRestApi {
     async fn register_worker(&self, request: RegisterWorkerRequest) -> Result<()>;
     async fn get_worker(&self, id: i32) -> Result<Worker>;
     async fn unregister_worker(&self, id: i32) -> Result<()>;
+    async fn drain_worker(&self, id: i32) -> Result<()>;
+    async fn resume_worker(&self, id: i32) -> Result<()>;
+    /// Kill the build the worker is currently running, the job is discarded
+    async fn abort_worker(&self, id: i32) -> Result<()>;
+    async fn trust_worker(&self, id: i32) -> Result<()>;
+    async fn untrust_worker(&self, id: i32) -> Result<()>;
+    /// Forget the pinned client certificate, the next one the worker presents is pinned instead
+    async fn reset_worker_fingerprint(&self, id: i32) -> Result<()>;
+    async fn get_worker_tokens(&self, id: i32) -> Result<Vec<WorkerToken>>;
+    async fn issue_worker_token(
+        &self,
+        id: i32,
+        request: IssueWorkerTokenRequest,
+    ) -> Result<IssuedWorkerToken>;
+    async fn revoke_worker_token(&self, id: i32, token_id: i32) -> Result<()>;
+    /// Authenticated with the current worker key, which also signs the request
+    async fn rotate_worker_key(&self, request: RotateWorkerKeyRequest) -> Result<RotatedWorkerKey>;
 }
 
 #[async_trait]
@@ -213,7 +334,8 @@ impl BuildRestApi for Client {
             .query(&source_identity_filter)
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -221,11 +343,14 @@ impl BuildRestApi for Client {
     }
 
     async fn submit_build_report(&self, request: RebuildReport) -> Result<()> {
-        self.post(Cow::Borrowed("api/v1/builds"))
+        self.post(Cow::Borrowed("api/v1/worker/builds"))
             .json(&request)
             .send_encoded()
             .await?
-            .error_for_status()?;
+            .error_for_body_size("report_body_size_limit")
+            .await?
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
@@ -235,7 +360,8 @@ impl BuildRestApi for Client {
             .get(Cow::Owned(format!("api/v1/builds/{id}")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -247,7 +373,8 @@ impl BuildRestApi for Client {
             .get(Cow::Owned(format!("api/v1/builds/{id}/log")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .text()
             .await?;
 
@@ -259,7 +386,8 @@ impl BuildRestApi for Client {
             .get(Cow::Owned(format!("api/v1/builds/{id}/artifacts")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -273,7 +401,8 @@ impl BuildRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -287,7 +416,8 @@ impl BuildRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .text()
             .await?;
 
@@ -301,12 +431,66 @@ impl BuildRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .bytes()
             .await?;
 
         Ok(Vec::from(data))
     }
+
+    async fn get_build_bundle(&self, id: i32) -> Result<Vec<u8>> {
+        let data = self
+            .get(Cow::Owned(format!("api/v1/builds/{id}/bundle.tar.gz")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .bytes()
+            .await?;
+
+        Ok(Vec::from(data))
+    }
+
+    async fn get_verification_mismatches(
+        &self,
+        page: Option<&Page>,
+        origin_filter: Option<&OriginFilter>,
+    ) -> Result<ResultPage<VerificationMismatch>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/builds/verification"))
+            .query(&page)
+            .query(&origin_filter)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn get_status_transitions(
+        &self,
+        page: Option<&Page>,
+        origin_filter: Option<&OriginFilter>,
+        transition_filter: Option<&TransitionFilter>,
+    ) -> Result<ResultPage<StatusTransition>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/builds/transitions"))
+            .query(&page)
+            .query(&origin_filter)
+            .query(&transition_filter)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
 }
 
 #[async_trait]
@@ -317,7 +501,8 @@ impl DashboardRestApi for Client {
             .query(&origin_filter)
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -325,6 +510,47 @@ impl DashboardRestApi for Client {
     }
 }
 
+#[async_trait]
+impl KeyRestApi for Client {
+    async fn get_api_keys(&self, page: Option<&Page>) -> Result<ResultPage<ApiKey>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/keys"))
+            .query(&page)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn issue_api_key(&self, request: IssueApiKeyRequest) -> Result<IssuedApiKey> {
+        let record = self
+            .post(Cow::Borrowed("api/v1/keys"))
+            .json(&request)
+            .send_encoded()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn revoke_api_key(&self, id: i32) -> Result<()> {
+        self.delete(Cow::Owned(format!("api/v1/keys/{id}")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl MetaRestApi for Client {
     async fn get_distributions(&self) -> Result<Vec<String>> {
@@ -332,7 +558,8 @@ impl MetaRestApi for Client {
             .get(Cow::Borrowed("api/v1/meta/distributions"))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -346,7 +573,8 @@ impl MetaRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -360,7 +588,8 @@ impl MetaRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -374,7 +603,8 @@ impl MetaRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -392,7 +622,8 @@ impl MetaRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -410,7 +641,8 @@ impl MetaRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -429,7 +661,25 @@ impl MetaRestApi for Client {
             )))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status().await?
+            .json()
+            .await?;
+
+        Ok(results)
+    }
+
+    async fn get_snapshot_generations(
+        &self,
+        distribution: &str,
+    ) -> Result<Vec<SnapshotGeneration>> {
+        let results = self
+            .get(Cow::Owned(format!(
+                "api/v1/meta/distributions/{distribution}/generations"
+            )))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -441,12 +691,39 @@ impl MetaRestApi for Client {
             .get(Cow::Borrowed("api/v1/meta/public-keys"))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
         Ok(public_key)
     }
+
+    async fn get_schemas(&self) -> Result<Vec<String>> {
+        let names = self
+            .get(Cow::Borrowed("api/v1/schema"))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(names)
+    }
+
+    async fn get_schema(&self, name: &str) -> Result<serde_json::Value> {
+        let schema = self
+            .get(Cow::Owned(format!("api/v1/schema/{name}")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(schema)
+    }
 }
 
 #[async_trait]
@@ -456,7 +733,66 @@ impl PackageRestApi for Client {
             .json(report)
             .send_encoded()
             .await?
-            .error_for_status()?;
+            .error_for_body_size("sync_body_size_limit")
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn submit_package_report_delta(&self, delta: &PackageReportDelta) -> Result<bool> {
+        let response = self
+            .post(Cow::Borrowed("api/v1/packages/delta"))
+            .json(delta)
+            .send_encoded()
+            .await?
+            .error_for_body_size("sync_body_size_limit")
+            .await?;
+
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            return Ok(false);
+        }
+        response.error_for_api_status().await?;
+
+        Ok(true)
+    }
+
+    async fn submit_package_popularity(&self, report: &PopularityReport) -> Result<()> {
+        self.post(Cow::Borrowed("api/v1/packages/popularity"))
+            .json(report)
+            .send_encoded()
+            .await?
+            .error_for_body_size("sync_body_size_limit")
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn submit_build_durations(&self, report: &BuildDurationReport) -> Result<()> {
+        self.post(Cow::Borrowed("api/v1/packages/durations"))
+            .json(report)
+            .send_encoded()
+            .await?
+            .error_for_body_size("sync_body_size_limit")
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn submit_verdicts(&self, report: &VerdictReport) -> Result<()> {
+        self.post(Cow::Borrowed("api/v1/packages/verdicts"))
+            .json(report)
+            .send_encoded()
+            .await?
+            .error_for_body_size("sync_body_size_limit")
+            .await?
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
@@ -474,7 +810,8 @@ impl PackageRestApi for Client {
             .query(&source_identity_filter)
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -486,7 +823,36 @@ impl PackageRestApi for Client {
             .get(Cow::Owned(format!("api/v1/packages/source/{id}")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn get_source_package_cross_rebuilds(&self, id: i32) -> Result<Vec<CrossRebuild>> {
+        let records = self
+            .get(Cow::Owned(format!("api/v1/packages/source/{id}/cross")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn get_upstream_release(&self, name: &str, version: &str) -> Result<UpstreamRelease> {
+        let record = self
+            .get(Cow::Owned(format!(
+                "api/v1/packages/upstream/{name}/{version}"
+            )))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -506,7 +872,8 @@ impl PackageRestApi for Client {
             .query(&binary_identity_filter)
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -518,12 +885,117 @@ impl PackageRestApi for Client {
             .get(Cow::Owned(format!("api/v1/packages/binary/{id}")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn correct_binary_package(
+        &self,
+        id: i32,
+        correction: &BinaryPackageCorrection,
+    ) -> Result<BinaryPackage> {
+        let record = self
+            .patch(Cow::Owned(format!("api/v1/packages/binary/{id}")))
+            .json(correction)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
         Ok(record)
     }
+
+    async fn export_tracker(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+    ) -> Result<Vec<TrackerEntry>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/packages/export/tracker"))
+            .query(&origin_filter)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn get_package_tags(&self, filter: Option<&PackageTagFilter>) -> Result<Vec<PackageTag>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/packages/tags"))
+            .query(&filter)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn add_package_tag(&self, distribution: &str, name: &str, tag: &str) -> Result<()> {
+        self.post(Cow::Owned(format!(
+            "api/v1/packages/tags/{distribution}/{name}/{tag}"
+        )))
+        .header("Content-Length", 0)
+        .send()
+        .await?
+        .error_for_api_status()
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_package_tag(&self, distribution: &str, name: &str, tag: &str) -> Result<()> {
+        self.delete(Cow::Owned(format!(
+            "api/v1/packages/tags/{distribution}/{name}/{tag}"
+        )))
+        .send()
+        .await?
+        .error_for_api_status()
+        .await?;
+
+        Ok(())
+    }
+
+    async fn annotate_package(
+        &self,
+        distribution: &str,
+        name: &str,
+        annotation: &PackageAnnotation,
+    ) -> Result<()> {
+        self.post(Cow::Owned(format!(
+            "api/v1/packages/annotations/{distribution}/{name}"
+        )))
+        .json(annotation)
+        .send()
+        .await?
+        .error_for_api_status()
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_package_annotation(&self, distribution: &str, name: &str) -> Result<()> {
+        self.delete(Cow::Owned(format!(
+            "api/v1/packages/annotations/{distribution}/{name}"
+        )))
+        .send()
+        .await?
+        .error_for_api_status()
+        .await?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -541,21 +1013,102 @@ impl QueueRestApi for Client {
             .query(&source_identity_filter)
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
         Ok(records)
     }
 
-    async fn request_rebuild(&self, request: QueueJobRequest) -> Result<()> {
-        self.post(Cow::Borrowed("api/v1/queue"))
+    async fn get_queue_position(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+        source_identity_filter: Option<&SourceIdentityFilter>,
+    ) -> Result<Vec<QueuePosition>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/queue/position"))
+            .query(&origin_filter)
+            .query(&source_identity_filter)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn request_rebuild(&self, request: QueueJobRequest) -> Result<QueueJobResponse> {
+        let response = self
+            .post(Cow::Borrowed("api/v1/queue"))
             .json(&request)
             .send_encoded()
             .await?
-            .error_for_status()?;
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
 
-        Ok(())
+        Ok(response)
+    }
+
+    async fn request_rebuild_with_progress(
+        &self,
+        request: QueueJobRequest,
+        progress: &(dyn Fn(i64, i64) + Sync),
+    ) -> Result<QueueJobResponse> {
+        let mut response = self
+            .post(Cow::Borrowed("api/v1/queue"))
+            .header("Accept", "application/x-ndjson")
+            .json(&request)
+            .send_encoded()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        let mut buf = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buf.extend_from_slice(&chunk);
+            while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                let line = buf.drain(..=pos).collect::<Vec<_>>();
+                match serde_json::from_slice::<QueueJobEvent>(&line)? {
+                    QueueJobEvent::Progress { processed, total } => progress(processed, total),
+                    QueueJobEvent::Done(response) => return Ok(response),
+                    QueueJobEvent::Error(err) => bail!("Failed to requeue packages: {err}"),
+                }
+            }
+        }
+
+        bail!("Response ended before the requeue was done")
+    }
+
+    async fn request_bisect(&self, request: BisectRequest) -> Result<Bisect> {
+        let record = self
+            .post(Cow::Borrowed("api/v1/bisects"))
+            .json(&request)
+            .send_encoded()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn get_bisect(&self, id: i32) -> Result<Bisect> {
+        let record = self
+            .get(Cow::Owned(format!("api/v1/bisects/{id}")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
     }
 
     async fn get_queued_job(&self, id: i32) -> Result<QueuedJob> {
@@ -563,7 +1116,8 @@ impl QueueRestApi for Client {
             .get(Cow::Owned(format!("api/v1/queue/{id}")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -574,7 +1128,8 @@ impl QueueRestApi for Client {
         self.delete(Cow::Owned(format!("api/v1/queue/{id}")))
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
@@ -589,31 +1144,182 @@ impl QueueRestApi for Client {
             .query(&source_identity_filter)
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
 
-    async fn request_work(&self, request: PopQueuedJobRequest) -> Result<JobAssignment> {
-        let record = self
-            .post(Cow::Borrowed("api/v1/queue/pop"))
+    async fn assign_queued_jobs(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+        source_identity_filter: Option<&SourceIdentityFilter>,
+        request: AssignQueuedJobsRequest,
+    ) -> Result<AssignQueuedJobsResponse> {
+        let response = self
+            .post(Cow::Borrowed("api/v1/queue/assign"))
+            .query(&origin_filter)
+            .query(&source_identity_filter)
+            .json(&request)
+            .send_encoded()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn wipe_queue(
+        &self,
+        origin_filter: Option<&OriginFilter>,
+        request: WipeQueueRequest,
+    ) -> Result<WipeQueueResponse> {
+        let response = self
+            .post(Cow::Borrowed("api/v1/queue/wipe"))
+            .query(&origin_filter)
             .json(&request)
             .send_encoded()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn export_queue(&self) -> Result<QueueSnapshot> {
+        let snapshot = self
+            .get(Cow::Borrowed("api/v1/queue/snapshot"))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
+        Ok(snapshot)
+    }
+
+    async fn import_queue(&self, snapshot: &QueueSnapshot) -> Result<QueueImportResponse> {
+        let response = self
+            .post(Cow::Borrowed("api/v1/queue/snapshot"))
+            .json(snapshot)
+            .send_encoded()
+            .await?
+            .error_for_body_size("sync_body_size_limit")
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn request_work(&self, request: PopQueuedJobRequest) -> Result<JobAssignment> {
+        let response = self
+            .post(Cow::Borrowed("api/v1/worker/jobs/pop"))
+            .json(&request)
+            .send_encoded()
+            .await?;
+        self.server_clock().observe(&response);
+        let record = response.error_for_api_status().await?.json().await?;
+
         Ok(record)
     }
 
-    async fn ping_job(&self, id: i32) -> Result<()> {
+    async fn ping_job(&self, id: i32) -> Result<PingJobResponse> {
         // nginx dies if proxying a request without a Content-Length header
-        self.post(Cow::Owned(format!("api/v1/queue/{id}/ping")))
+        let response = self
+            .post(Cow::Owned(format!("api/v1/worker/jobs/{id}/ping")))
             .header("Content-Length", 0)
             .send()
+            .await?;
+        self.server_clock().observe(&response);
+        if response.status() == reqwest::StatusCode::GONE {
+            return Err(Error::msg(JobAborted { id }));
+        }
+        let response = response.error_for_api_status().await?;
+        // older daemons answer without a body
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(PingJobResponse::default());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    async fn ping_job_with_phase(
+        &self,
+        id: i32,
+        request: PingJobRequest,
+    ) -> Result<PingJobResponse> {
+        let response = self
+            .post(Cow::Owned(format!("api/v1/worker/jobs/{id}/ping")))
+            .json(&request)
+            .send_encoded()
+            .await?;
+        self.server_clock().observe(&response);
+        if response.status() == reqwest::StatusCode::GONE {
+            return Err(Error::msg(JobAborted { id }));
+        }
+        let response = response.error_for_api_status().await?;
+        // older daemons answer without a body
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(PingJobResponse::default());
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl SubscriptionRestApi for Client {
+    async fn get_subscriptions(&self) -> Result<Vec<Subscription>> {
+        let records = self
+            .get(Cow::Borrowed("api/v1/subscriptions"))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn subscribe(&self, request: SubscriptionRequest) -> Result<Subscription> {
+        let record = self
+            .post(Cow::Borrowed("api/v1/subscriptions"))
+            .json(&request)
+            .send_encoded()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn unsubscribe(&self, id: i32) -> Result<()> {
+        self.delete(Cow::Owned(format!("api/v1/subscriptions/{id}")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+    async fn confirm_subscription(&self, request: SubscriptionConfirmation) -> Result<()> {
+        self.post(Cow::Borrowed("api/v1/subscriptions/confirm"))
+            .json(&request)
+            .send_encoded()
             .await?
-            .error_for_status()?;
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
@@ -627,7 +1333,8 @@ impl WorkerRestApi for Client {
             .query(&page)
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -639,7 +1346,8 @@ impl WorkerRestApi for Client {
             .json(&request)
             .send_encoded()
             .await?
-            .error_for_status()?;
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
@@ -649,7 +1357,8 @@ impl WorkerRestApi for Client {
             .get(Cow::Owned(format!("api/v1/workers/{id}")))
             .send()
             .await?
-            .error_for_status()?
+            .error_for_api_status()
+            .await?
             .json()
             .await?;
 
@@ -660,8 +1369,129 @@ impl WorkerRestApi for Client {
         self.delete(Cow::Owned(format!("api/v1/workers/{id}")))
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn drain_worker(&self, id: i32) -> Result<()> {
+        self.post(Cow::Owned(format!("api/v1/workers/{id}/drain")))
+            .header("Content-Length", 0)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn resume_worker(&self, id: i32) -> Result<()> {
+        self.post(Cow::Owned(format!("api/v1/workers/{id}/resume")))
+            .header("Content-Length", 0)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn abort_worker(&self, id: i32) -> Result<()> {
+        self.post(Cow::Owned(format!("api/v1/workers/{id}/abort")))
+            .header("Content-Length", 0)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn trust_worker(&self, id: i32) -> Result<()> {
+        self.post(Cow::Owned(format!("api/v1/workers/{id}/trust")))
+            .header("Content-Length", 0)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn untrust_worker(&self, id: i32) -> Result<()> {
+        self.post(Cow::Owned(format!("api/v1/workers/{id}/untrust")))
+            .header("Content-Length", 0)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reset_worker_fingerprint(&self, id: i32) -> Result<()> {
+        self.delete(Cow::Owned(format!("api/v1/workers/{id}/fingerprint")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_worker_tokens(&self, id: i32) -> Result<Vec<WorkerToken>> {
+        let records = self
+            .get(Cow::Owned(format!("api/v1/workers/{id}/tokens")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(records)
+    }
+
+    async fn issue_worker_token(
+        &self,
+        id: i32,
+        request: IssueWorkerTokenRequest,
+    ) -> Result<IssuedWorkerToken> {
+        let record = self
+            .post(Cow::Owned(format!("api/v1/workers/{id}/tokens")))
+            .json(&request)
+            .send_encoded()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn revoke_worker_token(&self, id: i32, token_id: i32) -> Result<()> {
+        self.delete(Cow::Owned(format!("api/v1/workers/{id}/tokens/{token_id}")))
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?;
 
         Ok(())
     }
+
+    async fn rotate_worker_key(&self, request: RotateWorkerKeyRequest) -> Result<RotatedWorkerKey> {
+        let rotated = self
+            .post(Cow::Borrowed("api/v1/worker/rotate-key"))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_api_status()
+            .await?
+            .json()
+            .await?;
+
+        Ok(rotated)
+    }
 }
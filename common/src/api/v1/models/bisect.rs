@@ -0,0 +1,217 @@
+use crate::api::v1::{BuildStatus, Priority};
+use chrono::NaiveDateTime;
+#[cfg(feature = "diesel")]
+use diesel::{
+    AsExpression, FromSqlRow, Queryable, deserialize::FromSql, serialize::Output, serialize::ToSql,
+    sql_types::Text, sqlite::Sqlite, sqlite::SqliteValue,
+};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// A change to the build environment. A bisect builds the package once without any of them, and then once per
+/// variation, so a verdict that differs from the baseline points at what the build is sensitive to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[serde(rename_all = "kebab-case")]
+pub enum BisectVariation {
+    /// The build environment of a regular rebuild
+    Baseline,
+    /// Build in a different directory
+    BuildPath,
+    /// Build with the clock set to a different point in time
+    Timestamp,
+    /// Build with a different locale
+    Locale,
+    /// Build on a single cpu
+    SingleCpu,
+}
+
+impl BisectVariation {
+    /// The order the variations are built in, the baseline always comes first
+    pub const ALL: [BisectVariation; 5] = [
+        BisectVariation::Baseline,
+        BisectVariation::BuildPath,
+        BisectVariation::Timestamp,
+        BisectVariation::Locale,
+        BisectVariation::SingleCpu,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            BisectVariation::Baseline => "baseline",
+            BisectVariation::BuildPath => "build-path",
+            BisectVariation::Timestamp => "timestamp",
+            BisectVariation::Locale => "locale",
+            BisectVariation::SingleCpu => "single-cpu",
+        }
+    }
+}
+
+impl fmt::Display for BisectVariation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BisectVariationParseError {
+    value: String,
+}
+
+impl fmt::Display for BisectVariationParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = &self.value;
+        write!(f, "could not parse \"{value}\" as a bisect variation")
+    }
+}
+
+impl Error for BisectVariationParseError {}
+
+impl TryFrom<&str> for BisectVariation {
+    type Error = BisectVariationParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        BisectVariation::ALL
+            .into_iter()
+            .find(|variation| variation.as_str() == value)
+            .ok_or_else(|| BisectVariationParseError {
+                value: value.to_string(),
+            })
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for BisectVariation {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(t.as_str().try_into()?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for BisectVariation {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BisectRequest {
+    /// The build input of this binary package is bisected
+    pub binary_package_id: i32,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(Queryable))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct BisectStep {
+    pub variation: BisectVariation,
+    /// `None` until the build of this variation was reported
+    pub status: Option<BuildStatus>,
+    pub built_at: Option<NaiveDateTime>,
+}
+
+impl BisectStep {
+    /// The variations that built with a different verdict than the baseline. This is empty unless the baseline was
+    /// either GOOD or BAD, a failed baseline doesn't tell anything about reproducibility.
+    pub fn flips(steps: &[BisectStep]) -> Vec<BisectVariation> {
+        let Some(baseline) = steps
+            .iter()
+            .find(|step| step.variation == BisectVariation::Baseline)
+            .and_then(|step| step.status.as_ref())
+        else {
+            return Vec::new();
+        };
+        if !matches!(baseline, BuildStatus::Good | BuildStatus::Bad) {
+            return Vec::new();
+        }
+
+        steps
+            .iter()
+            .filter(|step| step.variation != BisectVariation::Baseline)
+            .filter(|step| {
+                step.status
+                    .as_ref()
+                    .is_some_and(|status| status != baseline)
+            })
+            .map(|step| step.variation)
+            .collect()
+    }
+}
+
+/// Rebuilds of a single build input, one per [`BisectVariation`]. The steps are built one after another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bisect {
+    pub id: i32,
+    pub name: String,
+    pub version: String,
+    pub distribution: String,
+    pub architecture: String,
+    pub backend: String,
+    pub created_at: NaiveDateTime,
+    /// Set once every step was built
+    pub finished_at: Option<NaiveDateTime>,
+    pub steps: Vec<BisectStep>,
+    /// The variations that flipped the verdict of the baseline
+    pub flips: Vec<BisectVariation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(variation: BisectVariation, status: Option<BuildStatus>) -> BisectStep {
+        BisectStep {
+            variation,
+            status,
+            built_at: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_variations() {
+        for variation in BisectVariation::ALL {
+            assert_eq!(
+                BisectVariation::try_from(variation.as_str()).ok(),
+                Some(variation)
+            );
+        }
+        assert!(BisectVariation::try_from("kernel").is_err());
+    }
+
+    #[test]
+    fn test_flips() {
+        let steps = [
+            step(BisectVariation::Baseline, Some(BuildStatus::Good)),
+            step(BisectVariation::BuildPath, Some(BuildStatus::Bad)),
+            step(BisectVariation::Timestamp, Some(BuildStatus::Good)),
+            step(BisectVariation::Locale, Some(BuildStatus::Fail)),
+            step(BisectVariation::SingleCpu, None),
+        ];
+        assert_eq!(
+            BisectStep::flips(&steps),
+            [BisectVariation::BuildPath, BisectVariation::Locale]
+        );
+    }
+
+    #[test]
+    fn test_no_flips_without_verdict_of_baseline() {
+        let pending = [
+            step(BisectVariation::Baseline, None),
+            step(BisectVariation::BuildPath, Some(BuildStatus::Bad)),
+        ];
+        assert!(BisectStep::flips(&pending).is_empty());
+
+        let failed = [
+            step(BisectVariation::Baseline, Some(BuildStatus::Fail)),
+            step(BisectVariation::BuildPath, Some(BuildStatus::Good)),
+        ];
+        assert!(BisectStep::flips(&failed).is_empty());
+    }
+}
@@ -10,15 +10,20 @@ use std::fmt;
 use std::fmt::Formatter;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RebuildReport {
     pub queue_id: i32,
     pub built_at: NaiveDateTime,
     pub build_log: Vec<u8>,
     pub status: BuildStatus,
     pub artifacts: Vec<RebuildArtifactReport>,
+    /// How many bytes the worker downloaded for the build
+    #[serde(default)]
+    pub downloaded_bytes: Option<u64>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, clap::ValueEnum)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
 #[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
 #[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
@@ -35,6 +40,14 @@ pub enum BuildStatus {
     #[clap(name = "FAIL")]
     Fail,
 
+    #[serde(rename = "FLAKY")]
+    #[clap(name = "FLAKY")]
+    Flaky,
+
+    #[serde(rename = "DOWNLOAD_FAILED")]
+    #[clap(name = "DOWNLOAD_FAILED")]
+    DownloadFailed,
+
     #[serde(rename = "UNKWN")]
     #[clap(name = "UNKWN")]
     Unknown,
@@ -46,6 +59,8 @@ impl BuildStatus {
             BuildStatus::Good => "GOOD",
             BuildStatus::Bad => "BAD",
             BuildStatus::Fail => "FAIL",
+            BuildStatus::Flaky => "FLAKY",
+            BuildStatus::DownloadFailed => "DOWNLOAD_FAILED",
             BuildStatus::Unknown => "UNKWN",
         }
     }
@@ -73,6 +88,8 @@ impl TryFrom<&str> for BuildStatus {
             "GOOD" => Ok(BuildStatus::Good),
             "BAD" => Ok(BuildStatus::Bad),
             "FAIL" => Ok(BuildStatus::Fail),
+            "FLAKY" => Ok(BuildStatus::Flaky),
+            "DOWNLOAD_FAILED" => Ok(BuildStatus::DownloadFailed),
             "UNKWN" => Ok(BuildStatus::Unknown),
             _ => Err(BuildStatusParseError {
                 value: value.to_string(),
@@ -98,14 +115,130 @@ impl ToSql<Text, Sqlite> for BuildStatus {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RebuildArtifactReport {
     pub name: String,
     pub diffoscope: Option<Vec<u8>>,
     pub attestation: Option<Vec<u8>>,
     pub status: ArtifactStatus,
+    #[serde(default)]
+    pub diff_summary: Option<ArtifactDiffSummary>,
+    /// The sha256 of the rebuilt artifact, if the build produced one
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// The zstd compressed rebuilt artifact, uploaded if the daemon verifies the job itself
+    #[serde(default)]
+    pub content: Option<Vec<u8>>,
+}
+
+/// How a rebuilt artifact differs from the published one. Workers generate this even if diffoscope is disabled, it's
+/// cheap enough to include in every report of a BAD artifact. GOOD artifacts that are only reproducible modulo
+/// compression have one too.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct ArtifactDiffSummary {
+    /// The size of the published artifact in bytes
+    pub original_size: u64,
+    /// The size of the rebuilt artifact in bytes
+    pub rebuilt_size: u64,
+    /// The offset of the first byte that differs, the size of the shorter file if it's a prefix of the other one
+    pub first_difference: Option<u64>,
+    /// Files inside the archive that differ in content or metadata, or only exist in one of the artifacts. Paths in
+    /// nested archives are prefixed with the path of the archive, eg. `data.tar.gz/usr/bin/foo`.
+    #[serde(default)]
+    pub differing_files: Vec<String>,
+    /// More files differ than are listed
+    #[serde(default)]
+    pub truncated: bool,
+    /// The decompressed contents are identical, only the outer compression of the artifacts differs
+    #[serde(default)]
+    pub modulo_compression: bool,
+}
+
+impl ArtifactDiffSummary {
+    pub fn size_delta(&self) -> i64 {
+        self.rebuilt_size as i64 - self.original_size as i64
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for ArtifactDiffSummary {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&t)?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for ArtifactDiffSummary {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+/// A likely reason an artifact didn't reproduce, guessed by the daemon from the diffoscope output
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DiffCause {
+    /// The modification time in the header of a gzip file
+    #[serde(rename = "gzip-timestamp")]
+    #[clap(name = "gzip-timestamp")]
+    GzipTimestamp,
+
+    /// The directory the package was built in ended up in the artifact
+    #[serde(rename = "build-path")]
+    #[clap(name = "build-path")]
+    BuildPath,
+
+    /// The same files in a different order, eg. in an archive
+    #[serde(rename = "file-order")]
+    #[clap(name = "file-order")]
+    FileOrder,
+}
+
+impl DiffCause {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DiffCause::GzipTimestamp => "gzip-timestamp",
+            DiffCause::BuildPath => "build-path",
+            DiffCause::FileOrder => "file-order",
+        }
+    }
+}
+
+/// The labels of an artifact, stored as json list
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[serde(transparent)]
+pub struct LikelyCauses(pub Vec<DiffCause>);
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for LikelyCauses {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&t)?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for LikelyCauses {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(diesel::serialize::IsNull::No)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, clap::ValueEnum)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
 #[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
 #[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
@@ -194,6 +327,150 @@ pub struct Rebuild {
     pub started_at: Option<NaiveDateTime>,
     pub built_at: Option<NaiveDateTime>,
     pub status: Option<BuildStatus>,
+    /// Fingerprint of the environment of the worker that reported this build
+    #[serde(default)]
+    pub environment_fingerprint: Option<String>,
+    /// How many bytes the worker downloaded for the build, if it reported it
+    #[serde(default)]
+    pub downloaded_bytes: Option<i64>,
+    /// Why an admin queued the package for this rebuild, if they gave a reason
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A stored GOOD artifact whose rebuilt checksum doesn't match the published one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationMismatch {
+    pub build_id: i32,
+    pub artifact_id: i32,
+    /// The name of the artifact
+    pub artifact: String,
+    pub name: String,
+    pub version: String,
+    pub distribution: String,
+    pub architecture: String,
+    /// The worker that reported the artifact as GOOD, if it's still known
+    pub worker: Option<String>,
+    pub built_at: Option<NaiveDateTime>,
+    pub published_checksum: String,
+    /// Missing if the worker didn't report a checksum
+    pub rebuilt_checksum: Option<String>,
+}
+
+/// How a rebuild changed the status of a package between GOOD and BAD
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, clap::ValueEnum)]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub enum TransitionKind {
+    /// The same version was GOOD before, eg. until it was queued again to verify it. Nothing about the package changed,
+    /// so the build environment or the published artifacts may have been tampered with.
+    #[serde(rename = "regression")]
+    #[clap(name = "regression")]
+    Regression,
+
+    /// The previous version of the package was GOOD, the new one isn't
+    #[serde(rename = "new-version-unreproducible")]
+    #[clap(name = "new-version-unreproducible")]
+    NewVersionUnreproducible,
+
+    /// The same or the previous version was BAD before
+    #[serde(rename = "fixed")]
+    #[clap(name = "fixed")]
+    Fixed,
+}
+
+impl TransitionKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransitionKind::Regression => "regression",
+            TransitionKind::NewVersionUnreproducible => "new-version-unreproducible",
+            TransitionKind::Fixed => "fixed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransitionKindParseError {
+    value: String,
+}
+
+impl fmt::Display for TransitionKindParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let value = &self.value;
+        write!(f, "could not parse \"{value}\" as a transition kind")
+    }
+}
+
+impl Error for TransitionKindParseError {}
+
+impl TryFrom<&str> for TransitionKind {
+    type Error = TransitionKindParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "regression" => Ok(TransitionKind::Regression),
+            "new-version-unreproducible" => Ok(TransitionKind::NewVersionUnreproducible),
+            "fixed" => Ok(TransitionKind::Fixed),
+            _ => Err(TransitionKindParseError {
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for TransitionKind {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(t.as_str().try_into()?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for TransitionKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+/// A rebuild that changed the status of a package between GOOD and BAD
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(Queryable))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct StatusTransition {
+    pub id: i32,
+    pub build_id: i32,
+    pub kind: TransitionKind,
+    pub name: String,
+    pub version: String,
+    pub distribution: String,
+    pub release: Option<String>,
+    pub architecture: String,
+    /// The version the previous status was recorded for, the same as `version` unless a new version was rebuilt
+    pub previous_version: String,
+    pub previous_status: BuildStatus,
+    pub status: BuildStatus,
+    /// The worker that reported the new status, if it's still known
+    pub worker: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// The result of cross-compiling a package on a different architecture, kept apart from the native rebuilds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(Queryable))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct CrossRebuild {
+    pub id: i32,
+    /// The architecture of the package
+    pub architecture: String,
+    /// The architecture the package was built on
+    pub build_architecture: String,
+    pub backend: String,
+    pub started_at: Option<NaiveDateTime>,
+    pub built_at: NaiveDateTime,
+    pub status: BuildStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -205,4 +482,12 @@ pub struct RebuildArtifact {
     pub has_diffoscope: bool,
     pub has_attestation: bool,
     pub status: Option<ArtifactStatus>,
+    #[serde(default)]
+    pub diff_summary: Option<ArtifactDiffSummary>,
+    /// The sha256 of the rebuilt artifact, if the build produced one
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Likely reasons a BAD artifact didn't reproduce
+    #[serde(default)]
+    pub likely_causes: LikelyCauses,
 }
@@ -11,6 +11,8 @@ pub struct DashboardRebuildState {
     pub good: i64,
     pub bad: i64,
     pub fail: i64,
+    #[serde(default)]
+    pub flaky: i64,
     pub unknown: i64,
 }
 
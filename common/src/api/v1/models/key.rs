@@ -0,0 +1,171 @@
+use chrono::NaiveDateTime;
+#[cfg(feature = "diesel")]
+use diesel::{
+    AsExpression, FromSqlRow, Queryable, deserialize::FromSql, serialize::Output, serialize::ToSql,
+    sql_types::Text, sqlite::Sqlite, sqlite::SqliteValue,
+};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, clap::ValueEnum)]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub enum ApiKeyScope {
+    /// Full administrative access, equivalent to the auth cookie
+    #[serde(rename = "admin")]
+    #[clap(name = "admin")]
+    Admin,
+
+    /// Submitting package reports
+    #[serde(rename = "sync")]
+    #[clap(name = "sync")]
+    Sync,
+
+    /// Requesting rebuilds and dropping jobs from the queue, including wiping it
+    #[serde(rename = "queue")]
+    #[clap(name = "queue")]
+    Queue,
+
+    /// Triage: requesting rebuilds and bisects, pinning jobs, and tagging and annotating packages
+    #[serde(rename = "operator")]
+    #[clap(name = "operator")]
+    Operator,
+
+    /// Reading what requires authentication, eg. the queue export and the list of api keys
+    #[serde(rename = "read-only")]
+    #[clap(name = "read-only")]
+    ReadOnly,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ApiKeyScope::Admin => "admin",
+            ApiKeyScope::Sync => "sync",
+            ApiKeyScope::Queue => "queue",
+            ApiKeyScope::Operator => "operator",
+            ApiKeyScope::ReadOnly => "read-only",
+        }
+    }
+
+    /// Whether a key with this scope may be used for an action requiring the given scope. Admin includes everything
+    /// and operator includes read-only, the other scopes only permit their own actions. Routes that were opened up to
+    /// the operator and read-only roles keep accepting queue keys explicitly.
+    pub fn permits(&self, required: ApiKeyScope) -> bool {
+        match self {
+            ApiKeyScope::Admin => true,
+            ApiKeyScope::Operator => {
+                matches!(required, ApiKeyScope::Operator | ApiKeyScope::ReadOnly)
+            }
+            ApiKeyScope::Queue | ApiKeyScope::ReadOnly | ApiKeyScope::Sync => *self == required,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyScopeParseError {
+    value: String,
+}
+
+impl fmt::Display for ApiKeyScopeParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let value = &self.value;
+        write!(f, "could not parse \"{value}\" as an api key scope")
+    }
+}
+
+impl Error for ApiKeyScopeParseError {}
+
+impl TryFrom<&str> for ApiKeyScope {
+    type Error = ApiKeyScopeParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "admin" => Ok(ApiKeyScope::Admin),
+            "sync" => Ok(ApiKeyScope::Sync),
+            "queue" => Ok(ApiKeyScope::Queue),
+            "operator" => Ok(ApiKeyScope::Operator),
+            "read-only" => Ok(ApiKeyScope::ReadOnly),
+            _ => Err(ApiKeyScopeParseError {
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for ApiKeyScope {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(t.as_str().try_into()?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for ApiKeyScope {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssueApiKeyRequest {
+    pub name: String,
+    pub scope: ApiKeyScope,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(Queryable))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub scope: ApiKeyScope,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+/// Returned exactly once when a key is issued, the secret can't be retrieved afterwards.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedApiKey {
+    pub id: i32,
+    pub name: String,
+    pub scope: ApiKeyScope,
+    pub key: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles() {
+        assert!(ApiKeyScope::Admin.permits(ApiKeyScope::Sync));
+        assert!(ApiKeyScope::Queue.permits(ApiKeyScope::Queue));
+        assert!(!ApiKeyScope::Queue.permits(ApiKeyScope::Operator));
+        assert!(!ApiKeyScope::Queue.permits(ApiKeyScope::ReadOnly));
+        assert!(ApiKeyScope::Operator.permits(ApiKeyScope::ReadOnly));
+        assert!(!ApiKeyScope::Operator.permits(ApiKeyScope::Queue));
+        assert!(!ApiKeyScope::ReadOnly.permits(ApiKeyScope::Operator));
+        assert!(!ApiKeyScope::Sync.permits(ApiKeyScope::ReadOnly));
+        assert!(!ApiKeyScope::Queue.permits(ApiKeyScope::Admin));
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        for scope in [
+            ApiKeyScope::Admin,
+            ApiKeyScope::Sync,
+            ApiKeyScope::Queue,
+            ApiKeyScope::Operator,
+            ApiKeyScope::ReadOnly,
+        ] {
+            assert_eq!(ApiKeyScope::try_from(scope.as_str()).unwrap(), scope);
+        }
+        assert!(ApiKeyScope::try_from("root").is_err());
+    }
+}
@@ -1,6 +1,21 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PublicKey {
     pub current: Vec<String>,
 }
+
+/// A release of a snapshot distribution, eg. one timestamp of snapshot.debian.org
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotGeneration {
+    pub release: String,
+    pub first_synced_at: NaiveDateTime,
+    /// `None` if the distribution isn't configured as snapshot distribution anymore
+    pub expires_at: Option<NaiveDateTime>,
+    pub expired_at: Option<NaiveDateTime>,
+    /// Once the generation expired, these are the counts at the time it did
+    pub good: i64,
+    pub bad: i64,
+    pub unknown: i64,
+}
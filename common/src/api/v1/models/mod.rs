@@ -1,16 +1,22 @@
+mod bisect;
 mod build;
 mod dashboard;
+mod key;
 mod meta;
 mod package;
 mod queue;
+mod subscription;
 mod worker;
 
+pub use bisect::*;
 pub use build::*;
 pub use dashboard::*;
+pub use key::*;
 pub use meta::*;
 pub use package::*;
 pub use queue::*;
 use serde::{Deserialize, Serialize};
+pub use subscription::*;
 pub use worker::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,4 +64,22 @@ pub struct BinaryIdentityFilter {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FreshnessFilter {
     pub seen_only: Option<bool>,
+    /// Only packages that were (or weren't) dropped from their suite by a sync
+    pub removed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagFilter {
+    pub tag: Option<String>,
+}
+
+/// Only packages whose latest rebuild has an artifact labelled with this likely cause
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CauseFilter {
+    pub cause: Option<DiffCause>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransitionFilter {
+    pub kind: Option<TransitionKind>,
 }
@@ -1,32 +1,210 @@
-use crate::api::v1::{ArtifactStatus, BuildStatus};
+use crate::api::v1::{ArtifactStatus, BuildStatus, JobPayload, LikelyCauses};
 use chrono::NaiveDateTime;
 #[cfg(feature = "diesel")]
 use diesel::Queryable;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PackageReport {
     pub distribution: String,
     pub release: Option<String>,
     pub component: Option<String>,
     pub architecture: String,
+    /// Where the package list was fetched from, eg. the mirror of the sync profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
     pub packages: Vec<SourcePackageReport>,
 }
 
+impl PackageReport {
+    /// A digest over all packages of the report that doesn't depend on their order. The daemon remembers it for every
+    /// suite, so a later sync can send a [`PackageReportDelta`] against it.
+    pub fn revision(&self) -> String {
+        let mut packages = self.packages.iter().collect::<Vec<_>>();
+        packages.sort_by(|a, b| (&a.name, &a.version, &a.url).cmp(&(&b.name, &b.version, &b.url)));
+
+        let mut sha256 = Sha256::new();
+        for package in packages {
+            sha256.update(format!(
+                "{}\0{}\0{}\n",
+                package.name, package.version, package.url
+            ));
+            for artifact in &package.artifacts {
+                sha256.update(format!(
+                    "\t{}\0{}\0{}\0{}\0{}\n",
+                    artifact.name,
+                    artifact.version,
+                    artifact.architecture,
+                    artifact.url,
+                    artifact.checksum.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+
+        sha256
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    fn by_name(&self) -> BTreeMap<&str, Vec<&SourcePackageReport>> {
+        let mut packages = BTreeMap::<_, Vec<_>>::new();
+        for package in &self.packages {
+            packages
+                .entry(package.name.as_str())
+                .or_default()
+                .push(package);
+        }
+        for versions in packages.values_mut() {
+            versions.sort_by(|a, b| (&a.version, &a.url).cmp(&(&b.version, &b.url)));
+        }
+        packages
+    }
+
+    /// The changes from a report of the same suite that was submitted before. Packages are compared by name, if
+    /// anything about a package changed all of its versions are listed as updated.
+    pub fn delta_from(&self, base: &PackageReport) -> PackageReportDelta {
+        let before = base.by_name();
+        let after = self.by_name();
+
+        let mut added = Vec::new();
+        let mut updated = Vec::new();
+        for (name, versions) in &after {
+            match before.get(name) {
+                None => added.extend(versions.iter().map(|package| (*package).clone())),
+                Some(previous) if previous != versions => {
+                    updated.extend(versions.iter().map(|package| (*package).clone()))
+                }
+                Some(_) => (),
+            }
+        }
+
+        let removed = before
+            .keys()
+            .filter(|name| !after.contains_key(*name))
+            .map(|name| name.to_string())
+            .collect();
+
+        PackageReportDelta {
+            distribution: self.distribution.clone(),
+            release: self.release.clone(),
+            component: self.component.clone(),
+            architecture: self.architecture.clone(),
+            source: self.source.clone(),
+            base_revision: base.revision(),
+            revision: self.revision(),
+            added,
+            updated,
+            removed,
+        }
+    }
+}
+
+/// The changes to a suite since a full [`PackageReport`] that was submitted before, so frequent syncs of large suites
+/// don't need to send every package again
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PackageReportDelta {
+    pub distribution: String,
+    pub release: Option<String>,
+    pub component: Option<String>,
+    pub architecture: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// The revision of the report the delta was computed against, it needs to match what the daemon has on record
+    pub base_revision: String,
+    /// The revision of the full report this delta results in
+    pub revision: String,
+    /// Source packages that weren't part of the suite before
+    #[serde(default)]
+    pub added: Vec<SourcePackageReport>,
+    /// Source packages that changed, with all of their current versions
+    #[serde(default)]
+    pub updated: Vec<SourcePackageReport>,
+    /// Names of source packages that were dropped from the suite
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+impl PackageReportDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// How widely installed the binary packages of a distribution are, eg. from popcon or pkgstats. Jobs that were queued
+/// on the same day with the same priority are built in this order. Submitting a report replaces all previous scores of
+/// the distribution.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PopularityReport {
+    pub distribution: String,
+    /// Scores keyed by binary package name, higher is more popular
+    pub packages: BTreeMap<String, i64>,
+}
+
+/// How long builds are expected to take, eg. taken from the build logs of the distribution. Scheduling uses these until
+/// a worker built the package itself. Submitting a report replaces all previous hints of the distribution and
+/// architecture.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BuildDurationReport {
+    pub distribution: String,
+    pub architecture: String,
+    /// Durations in seconds, keyed by source package name
+    pub packages: BTreeMap<String, i64>,
+}
+
+/// Verdicts another rebuilderd instance reached for published artifacts. Syncs take them over instead of queueing a
+/// build, if the artifacts of a package were all verified by a rebuilder script our trusted workers run too.
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VerdictReport {
+    /// The instance the verdicts come from, recorded as their provenance
+    pub origin: String,
+    pub verdicts: Vec<ArtifactVerdictReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ArtifactVerdictReport {
+    /// The sha256 of the published artifact
+    pub checksum: String,
+    /// The sha256 of the rebuilder script that verified the artifact
+    pub script_version: String,
+    pub status: ArtifactStatus,
+    pub verified_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SourcePackageReport {
     pub name: String,
     pub version: String,
     pub url: String,
+    /// The files a worker needs for the build, if the sync knows the layout of the distribution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<JobPayload>,
+    /// Who maintains the package according to the distribution, eg. the `Maintainer` field of a debian source package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintainer: Option<String>,
     pub artifacts: Vec<BinaryPackageReport>,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct BinaryPackageReport {
     pub name: String,
     pub version: String,
     pub architecture: String,
     pub url: String,
+    /// The sha256 of the published artifact, workers verify their download against it
+    #[serde(default)]
+    pub checksum: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,6 +221,116 @@ pub struct SourcePackage {
     pub build_id: Option<i32>,
     pub last_seen: NaiveDateTime,
     pub seen_in_last_sync: bool,
+    /// Link to a bug report about the package's nondeterminism
+    #[serde(default)]
+    pub bug_url: Option<String>,
+    /// Free-form triage note
+    #[serde(default)]
+    pub note: Option<String>,
+    /// When a sync dropped this package from its suite
+    #[serde(default)]
+    pub removed_at: Option<NaiveDateTime>,
+    /// A rebuild is queued or running, `status` is the verdict of the previous rebuild until it's reported
+    #[serde(default)]
+    pub rebuilding: bool,
+    /// Where the sync that introduced this version fetched the package list from, eg. a mirror url
+    #[serde(default)]
+    pub synced_from: Option<String>,
+    /// When that sync was imported, unknown for packages imported before this was recorded
+    #[serde(default)]
+    pub synced_at: Option<NaiveDateTime>,
+    /// The revision of the suite the sync resulted in, see [`PackageReport::revision`]
+    #[serde(default)]
+    pub sync_revision: Option<String>,
+    /// Who maintains the package according to the distribution, subscriptions can follow a maintainer
+    #[serde(default)]
+    pub maintainer: Option<String>,
+}
+
+/// Triage information attached to all versions of a source package in a distribution, so known and reported
+/// nondeterminism can be told apart from new regressions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PackageAnnotation {
+    pub bug_url: Option<String>,
+    pub note: Option<String>,
+}
+
+/// The verdict of a rebuild in the vocabulary of tests.reproducible-builds.org
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrackerStatus {
+    #[serde(rename = "reproducible")]
+    Reproducible,
+    #[serde(rename = "FTBR")]
+    Unreproducible,
+    #[serde(rename = "FTBFS")]
+    FailedToBuild,
+    #[serde(rename = "E404")]
+    DownloadFailed,
+}
+
+impl TrackerStatus {
+    /// Packages that haven't been rebuilt yet are left out of the export, they have no verdict
+    pub fn from_build_status(status: &BuildStatus) -> Option<TrackerStatus> {
+        match status {
+            BuildStatus::Good => Some(TrackerStatus::Reproducible),
+            // a package that only reproduces sometimes isn't reproducible
+            BuildStatus::Bad | BuildStatus::Flaky => Some(TrackerStatus::Unreproducible),
+            BuildStatus::Fail => Some(TrackerStatus::FailedToBuild),
+            BuildStatus::DownloadFailed => Some(TrackerStatus::DownloadFailed),
+            BuildStatus::Unknown => None,
+        }
+    }
+}
+
+/// A source package in the format of `reproducible.json` on tests.reproducible-builds.org, which the Debian package
+/// tracker and usertag tooling consume
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackerEntry {
+    pub package: String,
+    pub version: String,
+    pub suite: String,
+    pub architecture: String,
+    pub status: TrackerStatus,
+    /// When the package was rebuilt, as `YYYY-MM-DD HH:MM` in UTC
+    pub build_date: String,
+}
+
+/// Corrections to a binary package record that a sync imported wrongly. Fields that are `None` are left as they are,
+/// the status is applied to the artifact of the latest rebuild.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BinaryPackageCorrection {
+    pub artifact_url: Option<String>,
+    pub version: Option<String>,
+    pub status: Option<ArtifactStatus>,
+}
+
+/// A free-form label attached to all versions of a source package in a distribution
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(Queryable))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct PackageTag {
+    pub id: i32,
+    pub distribution: String,
+    pub name: String,
+    pub tag: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageTagFilter {
+    pub distribution: Option<String>,
+    pub name: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// Source packages of the same upstream release, across all distributions that ship it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpstreamRelease {
+    pub name: String,
+    pub version: String,
+    /// Distributions with at least one GOOD rebuild of this release
+    pub reproducible_in: Vec<String>,
+    pub packages: Vec<SourcePackage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -64,4 +352,124 @@ pub struct BinaryPackage {
     pub attestation_log_id: Option<i32>,
     pub last_seen: NaiveDateTime,
     pub seen_in_last_sync: bool,
+    /// Link to a bug report about the source package's nondeterminism
+    #[serde(default)]
+    pub bug_url: Option<String>,
+    /// Free-form triage note of the source package
+    #[serde(default)]
+    pub note: Option<String>,
+    /// When a sync dropped the source package from its suite
+    #[serde(default)]
+    pub removed_at: Option<NaiveDateTime>,
+    /// A rebuild is queued or running, `status` is the verdict of the previous rebuild until it's reported
+    #[serde(default)]
+    pub rebuilding: bool,
+    /// Set on debug packages, the binary package they carry the debug symbols of
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    /// The combined verdict of the debug packages of this package, eg. the package is GOOD but its dbgsym is BAD
+    #[serde(default)]
+    pub debug_status: Option<ArtifactStatus>,
+    /// Where the sync that introduced the source package fetched the package list from, eg. a mirror url
+    #[serde(default)]
+    pub synced_from: Option<String>,
+    /// When that sync was imported, unknown for packages imported before this was recorded
+    #[serde(default)]
+    pub synced_at: Option<NaiveDateTime>,
+    /// The revision of the suite the sync resulted in, see [`PackageReport::revision`]
+    #[serde(default)]
+    pub sync_revision: Option<String>,
+    /// Likely reasons the package didn't reproduce in its latest rebuild
+    #[serde(default)]
+    pub likely_causes: Option<LikelyCauses>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str) -> SourcePackageReport {
+        SourcePackageReport {
+            name: name.to_string(),
+            version: version.to_string(),
+            url: format!("https://example.com/{name}-{version}.buildinfo"),
+            payload: None,
+            maintainer: None,
+            artifacts: vec![BinaryPackageReport {
+                name: name.to_string(),
+                version: version.to_string(),
+                architecture: "x86_64".to_string(),
+                url: format!("https://example.com/{name}-{version}.pkg"),
+                checksum: None,
+            }],
+        }
+    }
+
+    fn report(packages: Vec<SourcePackageReport>) -> PackageReport {
+        PackageReport {
+            distribution: "archlinux".to_string(),
+            release: None,
+            component: Some("core".to_string()),
+            architecture: "x86_64".to_string(),
+            source: None,
+            packages,
+        }
+    }
+
+    #[test]
+    fn test_revision_ignores_order() {
+        let a = report(vec![package("foo", "1"), package("bar", "1")]);
+        let b = report(vec![package("bar", "1"), package("foo", "1")]);
+        assert_eq!(a.revision(), b.revision());
+
+        let c = report(vec![package("bar", "1"), package("foo", "2")]);
+        assert_ne!(a.revision(), c.revision());
+    }
+
+    #[test]
+    fn test_delta_from() {
+        let base = report(vec![
+            package("foo", "1"),
+            package("bar", "1"),
+            package("baz", "1"),
+        ]);
+        let current = report(vec![
+            package("foo", "1"),
+            package("bar", "2"),
+            package("qux", "1"),
+        ]);
+
+        let delta = current.delta_from(&base);
+        assert_eq!(delta.base_revision, base.revision());
+        assert_eq!(delta.revision, current.revision());
+        assert_eq!(delta.added, vec![package("qux", "1")]);
+        assert_eq!(delta.updated, vec![package("bar", "2")]);
+        assert_eq!(delta.removed, vec!["baz".to_string()]);
+        assert!(!delta.is_empty());
+
+        assert!(current.delta_from(&current).is_empty());
+    }
+
+    #[test]
+    fn tracker_entry_matches_reproducible_json() {
+        let entry = TrackerEntry {
+            package: "curl".to_string(),
+            version: "8.5.0-2".to_string(),
+            suite: "unstable".to_string(),
+            architecture: "amd64".to_string(),
+            status: TrackerStatus::Unreproducible,
+            build_date: "2026-01-02 03:04".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            serde_json::json!({
+                "package": "curl",
+                "version": "8.5.0-2",
+                "suite": "unstable",
+                "architecture": "amd64",
+                "status": "FTBR",
+                "build_date": "2026-01-02 03:04",
+            })
+        );
+    }
 }
@@ -1,8 +1,13 @@
-use crate::api::v1::{BuildStatus, Priority};
+use crate::api::v1::{BisectVariation, BuildStatus, Priority};
 use chrono::{DateTime, NaiveDateTime, Utc};
 #[cfg(feature = "diesel")]
-use diesel::Queryable;
+use diesel::{
+    AsExpression, FromSqlRow, Queryable, deserialize::FromSql, serialize::Output, serialize::ToSql,
+    sql_types::Text, sqlite::Sqlite, sqlite::SqliteValue,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QueueJobRequest {
@@ -14,6 +19,87 @@ pub struct QueueJobRequest {
     pub architecture: Option<String>,
     pub status: Option<BuildStatus>,
     pub priority: Option<Priority>,
+    /// Only requeue packages whose latest rebuild was reported from a worker environment with this fingerprint
+    #[serde(default)]
+    pub built_with: Option<String>,
+    /// Only requeue packages whose latest rebuild finished before this point in time
+    #[serde(default)]
+    pub built_before: Option<NaiveDateTime>,
+    /// Only requeue source packages that ship a binary package with this name, all of their build inputs are requeued
+    #[serde(default)]
+    pub binary_name: Option<String>,
+    /// Build on this architecture instead of the one of the package, to verify the package can be cross-compiled
+    /// reproducibly
+    #[serde(default)]
+    pub build_architecture: Option<String>,
+    /// Why the packages are queued outside the normal schedule, kept in the build history of the rebuild
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueJobResponse {
+    /// The number of build inputs that were added to the queue
+    pub queued: i64,
+    /// The number of build inputs that were already queued and only had their priority updated
+    pub skipped: i64,
+}
+
+/// A line of the response to a requeue, it's streamed as ndjson to clients that accept `application/x-ndjson`
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueJobEvent {
+    /// How many of the matching build inputs were processed so far
+    Progress {
+        processed: i64,
+        total: i64,
+    },
+    Done(QueueJobResponse),
+    /// The requeue failed and nothing was queued
+    Error(String),
+}
+
+/// The full queue with jobs identified by their package instead of database ids, so it can be restored on a different
+/// instance
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub created_at: NaiveDateTime,
+    pub jobs: Vec<QueuedJob>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueImportResponse {
+    /// The number of jobs that were added to the queue
+    pub restored: i64,
+    /// The number of jobs that were already queued
+    pub skipped: i64,
+    /// The number of jobs for packages this instance doesn't know about
+    pub missing: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AssignQueuedJobsRequest {
+    /// The id of the worker the jobs are pinned to, `None` releases them to all workers again
+    pub worker: Option<i32>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssignQueuedJobsResponse {
+    /// The number of jobs that were pinned or released
+    pub assigned: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WipeQueueRequest {
+    /// Has to be set, so a missing filter doesn't empty the whole queue by accident
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WipeQueueResponse {
+    /// The number of jobs that were dropped
+    pub dropped: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,9 +107,45 @@ pub struct PopQueuedJobRequest {
     pub supported_backends: Vec<String>,
     pub architecture: String,
     pub supported_architectures: Vec<String>,
+    /// The mirror this worker prefers to download artifacts from, keyed by distribution
+    #[serde(default)]
+    pub mirrors: HashMap<String, String>,
+    /// The architectures this worker has cross toolchains for, packages for them are built on a supported architecture
+    #[serde(default)]
+    pub cross_architectures: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PingJobResponse {
+    /// An admin drained the worker, it finishes the current build but doesn't receive new jobs
+    #[serde(default)]
+    pub draining: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PingJobRequest {
+    /// The phase the rebuilder script reported last, if any
+    #[serde(default)]
+    pub phase: Option<String>,
+    /// The rebuilder script asked for this many seconds from now to finish the build
+    #[serde(default)]
+    pub extend: Option<u64>,
+}
+
+/// The daemon answered a ping with 410 Gone, an admin aborted the job. The build is killed and no result is reported.
+#[derive(Debug)]
+pub struct JobAborted {
+    pub id: i32,
+}
+
+impl fmt::Display for JobAborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Job {} was aborted by an admin", self.id)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "diesel", derive(Queryable))]
 #[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
 pub struct QueuedJob {
@@ -40,6 +162,32 @@ pub struct QueuedJob {
     pub priority: Priority,
     pub queued_at: NaiveDateTime,
     pub started_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub phase: Option<String>,
+    /// The number of failed attempts to rebuild this package
+    #[serde(default)]
+    pub attempts: i32,
+    /// Why the previous attempt failed, if there was one
+    #[serde(default)]
+    pub last_failure: Option<String>,
+    /// The name of the worker the job is currently assigned to
+    #[serde(default)]
+    pub worker: Option<String>,
+    /// The name of the worker the job is pinned to, no other worker picks it up
+    #[serde(default)]
+    pub pinned_worker: Option<String>,
+    /// The architecture the package is cross-compiled on, `None` for a native build
+    #[serde(default)]
+    pub build_architecture: Option<String>,
+    /// The job is handed back to the queue if it's still running at this point
+    #[serde(default)]
+    pub deadline: Option<NaiveDateTime>,
+    /// The job is a step of a bisect, the build environment is changed this way
+    #[serde(default)]
+    pub variation: Option<BisectVariation>,
+    /// Why an admin queued the package, if they gave a reason
+    #[serde(default)]
+    pub reason: Option<String>,
 }
 
 impl QueuedJob {
@@ -59,6 +207,7 @@ impl QueuedJob {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "diesel", derive(Queryable))]
 #[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
 pub struct QueuedJobArtifact {
@@ -66,12 +215,101 @@ pub struct QueuedJobArtifact {
     pub version: String,
     pub architecture: String,
     pub url: String,
+    /// The sha256 of the published artifact, if the sync recorded one
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+/// What a worker needs to download for a build, besides the published artifacts. The sync records this so workers don't
+/// have to guess the auxiliary files from the url of the build input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum JobPayload {
+    /// The .BUILDINFO of an Arch Linux package is embedded in the package itself
+    Archlinux {
+        package_url: String,
+        /// The commit of the packaging repository the package was built from, if the sync resolved it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        packaging_commit: Option<String>,
+    },
+    Debian {
+        buildinfo_url: String,
+        dsc_url: String,
+        /// The binary packages the source package builds, as listed in `Binary:` of the Sources index
+        binaries: Vec<String>,
+    },
+    /// Distributions without a dedicated schema, the build input is handed to the rebuilder script as is
+    Generic { url: String },
+}
+
+impl JobPayload {
+    /// The file that is passed to the rebuilder script
+    pub fn input_url(&self) -> &str {
+        match self {
+            JobPayload::Archlinux { package_url, .. } => package_url,
+            JobPayload::Debian { buildinfo_url, .. } => buildinfo_url,
+            JobPayload::Generic { url } => url,
+        }
+    }
+
+    /// Further files the rebuilder script expects next to the build input
+    pub fn auxiliary_urls(&self) -> Vec<&str> {
+        match self {
+            JobPayload::Debian { dsc_url, .. } => vec![dsc_url],
+            JobPayload::Archlinux { .. } | JobPayload::Generic { .. } => Vec::new(),
+        }
+    }
+
+    /// The revision of the packaging the build has to use, instead of whatever is current
+    pub fn packaging_commit(&self) -> Option<&str> {
+        match self {
+            JobPayload::Archlinux {
+                packaging_commit, ..
+            } => packaging_commit.as_deref(),
+            JobPayload::Debian { .. } | JobPayload::Generic { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for JobPayload {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&t)?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for JobPayload {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(diesel::serialize::IsNull::No)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct QueuedJobWithArtifacts {
     pub job: QueuedJob,
     pub artifacts: Vec<QueuedJobArtifact>,
+    /// Older daemons don't send a payload, the url of the job is the build input then
+    #[serde(default)]
+    pub payload: Option<JobPayload>,
+    /// The daemon verifies GOOD artifacts of this job itself and expects them to be uploaded with the report
+    #[serde(default)]
+    pub upload_artifacts: bool,
+}
+
+impl QueuedJobWithArtifacts {
+    pub fn input_payload(&self) -> JobPayload {
+        self.payload.clone().unwrap_or_else(|| JobPayload::Generic {
+            url: self.job.url.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -79,3 +317,15 @@ pub enum JobAssignment {
     Nothing,
     Rebuild(Box<QueuedJobWithArtifacts>),
 }
+
+/// Where a job sits in the priority, queue date and popularity order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuePosition {
+    pub job: QueuedJob,
+    /// The number of pending jobs for the same backend and architecture that are going to be picked up first
+    pub ahead: i64,
+    /// The number of pending jobs that are picked up in random order together with this one
+    pub tied: i64,
+    /// A rough guess when a worker is going to pick up this job, based on the throughput of the last 24 hours
+    pub estimated_start: Option<NaiveDateTime>,
+}
@@ -0,0 +1,39 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Ask to be notified whenever a source package changes between GOOD and BAD. Either a source package `name` or a
+/// `maintainer` is followed, and notifications are either posted to a `webhook` or mailed to an `email` address.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SubscriptionRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Follow every source package of this maintainer, as reported by the sync
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintainer: Option<String>,
+    /// Only notify about builds of this distribution, any distribution if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: i32,
+    pub name: Option<String>,
+    pub maintainer: Option<String>,
+    pub distribution: Option<String>,
+    pub webhook: Option<String>,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// Email subscriptions are only notified once the address confirmed the token that was mailed to it
+    pub confirmed: bool,
+}
+
+/// The token of a confirmation mail, it activates every subscription that is waiting for it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriptionConfirmation {
+    pub token: String,
+}
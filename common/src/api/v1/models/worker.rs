@@ -1,11 +1,79 @@
 use chrono::NaiveDateTime;
 #[cfg(feature = "diesel")]
-use diesel::Queryable;
+use diesel::{
+    AsExpression, FromSqlRow, Queryable, deserialize::FromSql, serialize::Output, serialize::ToSql,
+    sql_types::Text, sqlite::Sqlite, sqlite::SqliteValue,
+};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RegisterWorkerRequest {
     pub name: String,
+    #[serde(default)]
+    pub environment: Option<WorkerEnvironment>,
+    /// The persistent identity of the worker, re-registering with it doesn't create a second worker
+    #[serde(default)]
+    pub uuid: Option<String>,
+}
+
+/// A fingerprint of the environment a worker is running rebuilds in, collected by its startup self-test.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct WorkerEnvironment {
+    pub worker_version: String,
+    pub kernel: Option<String>,
+    /// sha256 of each configured rebuilder script, keyed by backend
+    #[serde(default)]
+    pub scripts: BTreeMap<String, String>,
+    /// first line of `<tool> --version` for each tool the worker depends on
+    #[serde(default)]
+    pub tools: BTreeMap<String, String>,
+}
+
+impl WorkerEnvironment {
+    /// A short digest over the whole environment, recorded with every rebuild so builds from an outdated toolchain can
+    /// be found again.
+    pub fn fingerprint(&self) -> String {
+        let mut sha256 = Sha256::new();
+        sha256.update(format!("worker={}\n", self.worker_version));
+        if let Some(kernel) = &self.kernel {
+            sha256.update(format!("kernel={kernel}\n"));
+        }
+        for (backend, digest) in &self.scripts {
+            sha256.update(format!("script:{backend}={digest}\n"));
+        }
+        for (tool, version) in &self.tools {
+            sha256.update(format!("tool:{tool}={version}\n"));
+        }
+
+        sha256.finalize()[..8]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for WorkerEnvironment {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&t)?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for WorkerEnvironment {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(diesel::serialize::IsNull::No)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,4 +86,139 @@ pub struct Worker {
     pub status: Option<String>,
     pub last_ping: NaiveDateTime,
     pub is_online: bool,
+    #[serde(default)]
+    pub environment: Option<WorkerEnvironment>,
+    /// Set while the worker finishes its current build but doesn't accept new jobs
+    #[serde(default)]
+    pub is_draining: bool,
+    /// Results of untrusted workers stay provisional until a trusted worker confirmed them
+    #[serde(default = "default_trusted")]
+    pub is_trusted: bool,
+    #[serde(default)]
+    pub uuid: Option<String>,
+    /// The sum of what the worker downloaded for the builds it reported
+    #[serde(default)]
+    pub downloaded_bytes: i64,
+    /// The client certificate the worker is pinned to
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+fn default_trusted() -> bool {
+    true
+}
+
+/// What a worker token may be used for. Workers authenticating with their worker key may do everything.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, clap::ValueEnum)]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = Text))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub enum WorkerCapability {
+    /// Picking up jobs from the queue and pinging them while they're being built
+    #[serde(rename = "pop")]
+    #[clap(name = "pop")]
+    Pop,
+
+    /// Submitting rebuild reports
+    #[serde(rename = "report")]
+    #[clap(name = "report")]
+    Report,
+}
+
+impl WorkerCapability {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WorkerCapability::Pop => "pop",
+            WorkerCapability::Report => "report",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerCapabilityParseError {
+    value: String,
+}
+
+impl fmt::Display for WorkerCapabilityParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let value = &self.value;
+        write!(f, "could not parse \"{value}\" as a worker capability")
+    }
+}
+
+impl Error for WorkerCapabilityParseError {}
+
+impl TryFrom<&str> for WorkerCapability {
+    type Error = WorkerCapabilityParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "pop" => Ok(WorkerCapability::Pop),
+            "report" => Ok(WorkerCapability::Report),
+            _ => Err(WorkerCapabilityParseError {
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl FromSql<Text, Sqlite> for WorkerCapability {
+    fn from_sql(bytes: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let t = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(t.as_str().try_into()?)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl ToSql<Text, Sqlite> for WorkerCapability {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssueWorkerTokenRequest {
+    pub capability: WorkerCapability,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "diesel", derive(Queryable))]
+#[cfg_attr(feature = "diesel", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct WorkerToken {
+    pub id: i32,
+    pub worker_id: i32,
+    pub capability: WorkerCapability,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+/// Returned exactly once when a token is issued, the secret can't be retrieved afterwards.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedWorkerToken {
+    pub id: i32,
+    pub worker_id: i32,
+    pub capability: WorkerCapability,
+    pub token: String,
+}
+
+/// The message a worker signs with its current key to move its identity to a new key
+pub fn key_rotation_message(current_key: &str, new_key: &str) -> String {
+    format!("rebuilderd worker key rotation\ncurrent={current_key}\nnew={new_key}\n")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateWorkerKeyRequest {
+    /// The new public key, encoded the same way as the worker key
+    pub new_key: String,
+    /// Hex encoded signature of the current key over [`key_rotation_message`]
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotatedWorkerKey {
+    pub worker_id: i32,
+    /// Until then the previous key is still accepted, so a running worker can be restarted with the new key
+    pub previous_key_expires_at: NaiveDateTime,
 }
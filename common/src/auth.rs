@@ -1,11 +1,19 @@
+use crate::api::v1::ApiKeyScope;
 use crate::errors::*;
-use serde::Deserialize;
+use data_encoding::BASE64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
 const SYSTEM_CONFIG_PATH: &str = "/etc/rebuilderd.conf";
 const SYSTEM_COOKIE_PATH: &str = "/var/lib/rebuilderd/auth-cookie";
+const CREDENTIALS_FILE: &str = "rebuilderd-credentials.toml";
 
 #[derive(Debug, Default, Deserialize)]
 pub struct Config {
@@ -16,6 +24,11 @@ pub struct Config {
 #[derive(Debug, Default, Clone, Deserialize)]
 pub struct AuthConfig {
     pub cookie: Option<String>,
+    #[serde(default)]
+    pub oidc: OidcConfig,
+    /// Administrators with their own secret and role, in addition to the auth cookie
+    #[serde(default, rename = "identity")]
+    pub identities: Vec<AdminIdentity>,
 }
 
 impl AuthConfig {
@@ -23,6 +36,43 @@ impl AuthConfig {
         if c.cookie.is_some() {
             self.cookie = c.cookie;
         }
+        self.oidc.update(c.oidc);
+        if !c.identities.is_empty() {
+            self.identities = c.identities;
+        }
+    }
+}
+
+/// A named administrator, the secret is presented like the auth cookie
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AdminIdentity {
+    pub name: String,
+    pub secret: String,
+    pub role: ApiKeyScope,
+}
+
+/// Accept bearer tokens issued by an OpenID Connect provider for administrative requests
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct OidcConfig {
+    /// The issuer url, the signing keys are discovered from its `.well-known/openid-configuration`
+    pub issuer: Option<String>,
+    /// Tokens need to be issued for this audience, usually the client id of rebuilderd
+    pub audience: Option<String>,
+    /// A claim that lists the api key scopes of a token, tokens are granted admin access if this isn't set
+    pub scope_claim: Option<String>,
+}
+
+impl OidcConfig {
+    pub fn update(&mut self, c: OidcConfig) {
+        if c.issuer.is_some() {
+            self.issuer = c.issuer;
+        }
+        if c.audience.is_some() {
+            self.audience = c.audience;
+        }
+        if c.scope_claim.is_some() {
+            self.scope_claim = c.scope_claim;
+        }
     }
 }
 
@@ -78,3 +128,135 @@ pub fn find_auth_cookie() -> Result<String> {
 
     bail!("Failed to find auth cookie anywhere")
 }
+
+/// Credentials stored by `rebuildctl auth login`, keyed by endpoint.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Credentials {
+    #[serde(default)]
+    pub endpoints: BTreeMap<String, StoredCredential>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredCredential {
+    pub cookie: String,
+}
+
+pub fn credentials_path() -> Result<PathBuf> {
+    let config_dir =
+        dirs_next::config_dir().ok_or_else(|| format_err!("Failed to find config dir"))?;
+    Ok(config_dir.join(CREDENTIALS_FILE))
+}
+
+impl Credentials {
+    /// Returns empty credentials if nothing was stored yet
+    pub fn load() -> Result<Credentials> {
+        let path = credentials_path()?;
+        let buf = match fs::read_to_string(&path) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Credentials::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| anyhow!("Failed to read credentials file {path:?}"));
+            }
+        };
+
+        debug!("Loading stored credentials from {path:?}");
+        let credentials = toml::from_str(&buf)
+            .with_context(|| anyhow!("Failed to parse credentials file {path:?}"))?;
+        Ok(credentials)
+    }
+
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = credentials_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let buf = toml::to_string(self).context("Failed to serialize credentials")?;
+
+        debug!("Writing stored credentials to {path:?}");
+        let mut file = OpenOptions::new()
+            .mode(0o600)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .context("Failed to open credentials file")?;
+        // the mode is only applied to newly created files, make sure older files get locked down too
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(buf.as_bytes())?;
+
+        Ok(path)
+    }
+}
+
+pub fn read_stored_cookie(endpoint: &str) -> Result<Option<String>> {
+    let credentials = Credentials::load()?;
+    Ok(credentials
+        .endpoints
+        .get(endpoint)
+        .map(|credential| credential.cookie.clone()))
+}
+
+/// The persistent identity of a worker, generated on its first run. The uuid stays the same when the key is rotated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerIdentity {
+    pub uuid: String,
+    /// The pkcs8 encoded ed25519 key of the worker, in base64
+    key: String,
+}
+
+impl WorkerIdentity {
+    pub fn new(pkcs8: &[u8]) -> WorkerIdentity {
+        WorkerIdentity {
+            uuid: Uuid::new_v4().to_string(),
+            key: BASE64.encode(pkcs8),
+        }
+    }
+
+    /// The same identity with a different key
+    pub fn with_key(&self, pkcs8: &[u8]) -> WorkerIdentity {
+        WorkerIdentity {
+            uuid: self.uuid.clone(),
+            key: BASE64.encode(pkcs8),
+        }
+    }
+
+    pub fn pkcs8(&self) -> Result<Vec<u8>> {
+        BASE64
+            .decode(self.key.as_bytes())
+            .context("Failed to decode worker key")
+    }
+
+    /// Returns `None` if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Option<WorkerIdentity>> {
+        let buf = match fs::read_to_string(path) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| anyhow!("Failed to read worker identity {path:?}"));
+            }
+        };
+
+        debug!("Loading worker identity from {path:?}");
+        let identity = toml::from_str(&buf)
+            .with_context(|| anyhow!("Failed to parse worker identity {path:?}"))?;
+        Ok(Some(identity))
+    }
+
+    /// Existing files are never overwritten, the key in them may still be needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let buf = toml::to_string(self).context("Failed to serialize worker identity")?;
+
+        debug!("Writing worker identity to {path:?}");
+        OpenOptions::new()
+            .mode(0o640)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .with_context(|| anyhow!("Failed to create worker identity {path:?}"))?
+            .write_all(buf.as_bytes())?;
+        Ok(())
+    }
+}
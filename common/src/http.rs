@@ -1,10 +1,468 @@
 use crate::errors::*;
-pub use reqwest::{Client, RequestBuilder};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, ClientBuilder};
+pub use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use url::{Host, Url};
 
-pub fn client() -> Result<Client> {
+/// How many downloads from the same host may run at once, unless configured otherwise
+pub const DEFAULT_MAX_CONCURRENT_PER_HOST: usize = 4;
+
+/// A client for requests to third-party servers, eg. distribution mirrors and webhooks
+pub fn client(outbound: &OutboundConfig) -> Result<Client> {
+    Client::builder()
+        .user_agent(outbound.user_agent())
+        .read_timeout(Duration::from_secs(60))
+        .build()
+        .map_err(Error::from)
+}
+
+/// Like [`client`], but response bodies are returned exactly as they were sent. Use this for files that are verified
+/// against a checksum or compared byte-for-byte.
+pub fn raw_client(outbound: &OutboundConfig) -> Result<Client> {
     Client::builder()
+        .user_agent(outbound.user_agent())
         .read_timeout(Duration::from_secs(60))
+        .no_zstd()
         .build()
         .map_err(Error::from)
 }
+
+/// Loopback, private, link-local and other addresses that can't be reached from the internet
+pub fn is_internal_address(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal_address(IpAddr::V4(v4)),
+            None => {
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Webhooks that anyone with an api key can register must not reach into the network of the operator. Addresses in
+/// the url are checked right away, hostnames are checked by [`webhook_client`] whenever they are resolved.
+pub fn check_webhook_url(url: &Url, allow_http: bool, allow_internal: bool) -> Result<()> {
+    match url.scheme() {
+        "https" => (),
+        "http" if allow_http => (),
+        _ => bail!("Webhook needs to be a https url"),
+    }
+
+    let internal = match url.host() {
+        Some(Host::Ipv4(addr)) => is_internal_address(IpAddr::V4(addr)),
+        Some(Host::Ipv6(addr)) => is_internal_address(IpAddr::V6(addr)),
+        Some(Host::Domain(_)) => false,
+        None => bail!("Webhook url has no host"),
+    };
+    if internal && !allow_internal {
+        bail!("Webhook points to an internal address");
+    }
+
+    Ok(())
+}
+
+/// Like [`check_webhook_url`], but also refuses hostnames that currently resolve to an internal address
+pub async fn check_webhook(url: &str, allow_http: bool, allow_internal: bool) -> Result<()> {
+    let url = url.parse::<Url>().context("Invalid webhook url")?;
+    check_webhook_url(&url, allow_http, allow_internal)?;
+
+    if !allow_internal && let Some(Host::Domain(domain)) = url.host() {
+        let port = url.port_or_known_default().unwrap_or(443);
+        let mut addrs = tokio::net::lookup_host((domain, port))
+            .await
+            .with_context(|| anyhow!("Failed to resolve webhook host {domain:?}"))?;
+        if addrs.any(|addr| is_internal_address(addr.ip())) {
+            bail!("Webhook host {domain:?} resolves to an internal address");
+        }
+    }
+
+    Ok(())
+}
+
+/// Only hands out the public addresses of a hostname
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs = tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|addr| !is_internal_address(addr.ip()))
+                .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(format!("{host:?} does not resolve to a public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Like [`client`], but for webhooks of subscribers. Redirects are not followed, and unless internal targets are
+/// allowed, hostnames that resolve to internal addresses can't be connected to.
+pub fn webhook_client(outbound: &OutboundConfig, allow_internal: bool) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(outbound.user_agent())
+        .read_timeout(Duration::from_secs(60))
+        .redirect(Policy::none());
+    if !allow_internal {
+        builder = builder.dns_resolver(PublicResolver);
+    }
+    builder.build().map_err(Error::from)
+}
+
+/// How rebuilderd identifies itself to the servers it fetches from, so mirror operators can tell whose traffic it is
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboundConfig {
+    /// Included in the User-Agent, eg. `rebuilderd.example.com`
+    pub instance_name: Option<String>,
+    /// Where the people running this instance can be reached, included in the User-Agent
+    pub contact_url: Option<String>,
+    /// How many downloads from the same host may run at once, `0` means no limit
+    pub max_concurrent_per_host: Option<usize>,
+}
+
+impl OutboundConfig {
+    pub fn update(&mut self, c: OutboundConfig) {
+        if c.instance_name.is_some() {
+            self.instance_name = c.instance_name;
+        }
+        if c.contact_url.is_some() {
+            self.contact_url = c.contact_url;
+        }
+        if c.max_concurrent_per_host.is_some() {
+            self.max_concurrent_per_host = c.max_concurrent_per_host;
+        }
+    }
+
+    /// eg. `rebuilderd/0.26.0 (+https://example.com/contact; rebuilderd.example.com)`
+    pub fn user_agent(&self) -> String {
+        let mut user_agent = format!("rebuilderd/{}", env!("CARGO_PKG_VERSION"));
+
+        let comments = [
+            self.contact_url.as_ref().map(|url| format!("+{url}")),
+            self.instance_name.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if !comments.is_empty() {
+            user_agent.push_str(&format!(" ({})", comments.join("; ")));
+        }
+
+        user_agent
+    }
+
+    pub fn host_limits(&self) -> HostLimits {
+        let max_per_host = self
+            .max_concurrent_per_host
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_PER_HOST);
+        HostLimits::new(Some(max_per_host).filter(|max| *max > 0))
+    }
+}
+
+/// Limits how many requests to the same host are in flight at once, clones share their limits
+#[derive(Debug, Default, Clone)]
+pub struct HostLimits {
+    max_per_host: Option<usize>,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl HostLimits {
+    pub fn new(max_per_host: Option<usize>) -> Self {
+        HostLimits {
+            max_per_host,
+            hosts: Default::default(),
+        }
+    }
+
+    /// Waits until a request to the host of this url may be sent, the request counts as in flight until the permit is
+    /// dropped
+    pub async fn acquire(&self, url: &Url) -> Result<Option<OwnedSemaphorePermit>> {
+        let (Some(max_per_host), Some(host)) = (self.max_per_host, url.host_str()) else {
+            return Ok(None);
+        };
+
+        let semaphore = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(max_per_host)))
+            .clone();
+        let permit = semaphore.acquire_owned().await?;
+        Ok(Some(permit))
+    }
+}
+
+/// How the certificate of a rebuilderd daemon is verified, instead of trusting the system roots
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Only trust certificate authorities from this PEM bundle
+    pub ca_bundle: Option<PathBuf>,
+    /// Only accept a server certificate with this sha256 fingerprint, in hex with optional colons. The certificate
+    /// isn't validated any further, this also works for self-signed certificates.
+    pub pinned_certificate: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if self.ca_bundle.is_some() && self.pinned_certificate.is_some() {
+            bail!("A pinned certificate can't be combined with a custom ca bundle");
+        }
+
+        if let Some(path) = &self.ca_bundle {
+            let pem =
+                fs::read(path).with_context(|| anyhow!("Failed to read ca bundle {path:?}"))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| anyhow!("Failed to parse ca bundle {path:?}"))?;
+            if certs.is_empty() {
+                bail!("Ca bundle {path:?} does not contain any certificates");
+            }
+            builder = builder.tls_certs_only(certs);
+        }
+
+        if let Some(fingerprint) = &self.pinned_certificate {
+            let verifier = PinnedCertificate::new(parse_fingerprint(fingerprint)?);
+            let config = rustls::ClientConfig::builder_with_provider(verifier.provider.clone())
+                .with_safe_default_protocol_versions()?
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth();
+            builder = builder.tls_backend_preconfigured(config);
+        }
+
+        Ok(builder)
+    }
+}
+
+fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32]> {
+    let hex = fingerprint.replace(':', "");
+    if hex.len() != 64 || !hex.is_ascii() {
+        bail!("Pinned certificate needs to be a sha256 fingerprint: {fingerprint:?}");
+    }
+
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).with_context(|| {
+            anyhow!("Pinned certificate needs to be a sha256 fingerprint: {fingerprint:?}")
+        })?;
+    }
+
+    Ok(bytes)
+}
+
+#[derive(Debug)]
+struct PinnedCertificate {
+    fingerprint: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertificate {
+    fn new(fingerprint: [u8; 32]) -> Self {
+        PinnedCertificate {
+            fingerprint,
+            provider: Arc::new(rustls::crypto::aws_lc_rs::default_provider()),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if Sha256::digest(end_entity).as_slice() == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_agent() {
+        let version = env!("CARGO_PKG_VERSION");
+        let mut outbound = OutboundConfig::default();
+        assert_eq!(outbound.user_agent(), format!("rebuilderd/{version}"));
+
+        outbound.instance_name = Some("rebuilderd.example.com".to_string());
+        assert_eq!(
+            outbound.user_agent(),
+            format!("rebuilderd/{version} (rebuilderd.example.com)")
+        );
+
+        outbound.contact_url = Some("https://example.com/contact".to_string());
+        assert_eq!(
+            outbound.user_agent(),
+            format!("rebuilderd/{version} (+https://example.com/contact; rebuilderd.example.com)")
+        );
+    }
+
+    #[test]
+    fn test_host_limits() {
+        let limits = HostLimits::new(Some(1));
+        let a = "https://mirror.example.com/a.deb".parse::<Url>().unwrap();
+        let b = "https://mirror.example.com/b.deb".parse::<Url>().unwrap();
+        let other = "https://other.example.com/c.deb".parse::<Url>().unwrap();
+
+        let permit = tokio_test::block_on(limits.acquire(&a)).unwrap();
+        assert!(permit.is_some());
+        assert!(
+            tokio_test::block_on(limits.acquire(&other))
+                .unwrap()
+                .is_some()
+        );
+
+        let mut blocked = tokio_test::task::spawn(limits.acquire(&b));
+        tokio_test::assert_pending!(blocked.poll());
+
+        drop(permit);
+        assert!(blocked.is_woken());
+        let permit = tokio_test::assert_ready!(blocked.poll()).unwrap();
+        assert!(permit.is_some());
+    }
+
+    #[test]
+    fn test_internal_addresses() {
+        for addr in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_internal_address(addr.parse().unwrap()), "{addr}");
+        }
+        for addr in [
+            "192.0.2.1",
+            "100.128.0.1",
+            "2001:db8::1",
+            "::ffff:192.0.2.1",
+        ] {
+            assert!(!is_internal_address(addr.parse().unwrap()), "{addr}");
+        }
+    }
+
+    #[test]
+    fn test_check_webhook_url() {
+        let url = |url: &str| url.parse::<Url>().unwrap();
+
+        assert!(check_webhook_url(&url("https://hooks.example.com/x"), false, false).is_ok());
+        assert!(check_webhook_url(&url("http://hooks.example.com/x"), false, false).is_err());
+        assert!(check_webhook_url(&url("http://hooks.example.com/x"), true, false).is_ok());
+        assert!(check_webhook_url(&url("file:///etc/passwd"), true, true).is_err());
+
+        assert!(check_webhook_url(&url("https://127.0.0.1/x"), false, false).is_err());
+        assert!(check_webhook_url(&url("https://[::1]/x"), false, false).is_err());
+        assert!(check_webhook_url(&url("https://169.254.169.254/x"), false, false).is_err());
+        assert!(check_webhook_url(&url("https://127.0.0.1/x"), false, true).is_ok());
+    }
+
+    #[test]
+    fn test_check_webhook_resolves_hostnames() {
+        assert!(tokio_test::block_on(check_webhook("https://localhost/x", false, false)).is_err());
+        assert!(tokio_test::block_on(check_webhook("https://localhost/x", false, true)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let fingerprint = parse_fingerprint(
+            "7A:3F:00:11:22:33:44:55:66:77:88:99:AA:BB:CC:DD:EE:FF:00:11:22:33:44:55:66:77:88:99:aa:bb:cc:dd",
+        )
+        .unwrap();
+        assert_eq!(fingerprint[0], 0x7a);
+        assert_eq!(fingerprint[1], 0x3f);
+        assert_eq!(fingerprint[31], 0xdd);
+    }
+
+    #[test]
+    fn test_parse_fingerprint_without_colons() {
+        let fingerprint = parse_fingerprint(&"ab".repeat(32)).unwrap();
+        assert_eq!(fingerprint, [0xab; 32]);
+    }
+
+    #[test]
+    fn test_parse_fingerprint_invalid() {
+        assert!(parse_fingerprint("abcd").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+        assert!(parse_fingerprint(&"ä".repeat(32)).is_err());
+    }
+}
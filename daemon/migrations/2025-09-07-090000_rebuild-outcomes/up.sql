@@ -0,0 +1,4 @@
+ALTER TABLE rebuilds
+    ADD COLUMN outcome TEXT;
+
+UPDATE rebuilds SET outcome = status;
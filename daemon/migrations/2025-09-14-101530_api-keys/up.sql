@@ -0,0 +1,11 @@
+CREATE TABLE api_keys
+(
+    id         INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name       TEXT     NOT NULL,
+    key_hash   TEXT     NOT NULL,
+    scope      TEXT     NOT NULL,
+    created_at DATETIME NOT NULL,
+    revoked_at DATETIME
+);
+
+CREATE UNIQUE INDEX api_keys_key_hash_idx ON api_keys (key_hash);
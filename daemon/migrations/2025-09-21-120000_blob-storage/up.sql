@@ -0,0 +1,58 @@
+PRAGMA foreign_keys= OFF;
+
+-- logs may now live in an external blob store, in which case only a reference is kept in the database
+CREATE TABLE _new_build_logs
+(
+    id        INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    build_log BLOB,
+    blob_key  TEXT,
+    CHECK (build_log IS NOT NULL OR blob_key IS NOT NULL)
+);
+
+INSERT INTO _new_build_logs (id, build_log, blob_key)
+SELECT id,
+       build_log,
+       NULL
+FROM build_logs;
+
+DROP TABLE build_logs;
+ALTER TABLE _new_build_logs
+    RENAME TO build_logs;
+
+CREATE TABLE _new_diffoscope_logs
+(
+    id             INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    diffoscope_log BLOB,
+    blob_key       TEXT,
+    CHECK (diffoscope_log IS NOT NULL OR blob_key IS NOT NULL)
+);
+
+INSERT INTO _new_diffoscope_logs (id, diffoscope_log, blob_key)
+SELECT id,
+       diffoscope_log,
+       NULL
+FROM diffoscope_logs;
+
+DROP TABLE diffoscope_logs;
+ALTER TABLE _new_diffoscope_logs
+    RENAME TO diffoscope_logs;
+
+CREATE TABLE _new_attestation_logs
+(
+    id              INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    attestation_log BLOB,
+    blob_key        TEXT,
+    CHECK (attestation_log IS NOT NULL OR blob_key IS NOT NULL)
+);
+
+INSERT INTO _new_attestation_logs (id, attestation_log, blob_key)
+SELECT id,
+       attestation_log,
+       NULL
+FROM attestation_logs;
+
+DROP TABLE attestation_logs;
+ALTER TABLE _new_attestation_logs
+    RENAME TO attestation_logs;
+
+PRAGMA foreign_keys= ON;
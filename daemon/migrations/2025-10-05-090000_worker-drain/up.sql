@@ -0,0 +1,2 @@
+ALTER TABLE workers
+    ADD COLUMN draining BOOLEAN NOT NULL DEFAULT 0;
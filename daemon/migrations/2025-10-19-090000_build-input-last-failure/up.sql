@@ -0,0 +1,2 @@
+ALTER TABLE build_inputs
+    ADD COLUMN last_failure TEXT;
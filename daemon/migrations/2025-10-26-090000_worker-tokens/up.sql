@@ -0,0 +1,12 @@
+CREATE TABLE worker_tokens
+(
+    id         INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    worker_id  INTEGER  NOT NULL REFERENCES workers (id) ON DELETE CASCADE,
+    token_hash TEXT     NOT NULL,
+    capability TEXT     NOT NULL,
+    created_at DATETIME NOT NULL,
+    revoked_at DATETIME
+);
+
+CREATE UNIQUE INDEX worker_tokens_token_hash_idx ON worker_tokens (token_hash);
+CREATE INDEX worker_tokens_worker_id_idx ON worker_tokens (worker_id);
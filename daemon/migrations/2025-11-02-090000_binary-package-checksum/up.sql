@@ -0,0 +1,2 @@
+ALTER TABLE binary_packages
+    ADD COLUMN checksum TEXT;
@@ -0,0 +1,11 @@
+CREATE TABLE package_tags
+(
+    id           INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    distribution TEXT     NOT NULL,
+    name         TEXT     NOT NULL,
+    tag          TEXT     NOT NULL,
+    created_at   DATETIME NOT NULL
+);
+
+CREATE UNIQUE INDEX package_tags_unique_idx ON package_tags (distribution, name, tag);
+CREATE INDEX package_tags_tag_idx ON package_tags (tag);
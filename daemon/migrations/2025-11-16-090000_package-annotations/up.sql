@@ -0,0 +1,11 @@
+CREATE TABLE package_annotations
+(
+    id           INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    distribution TEXT     NOT NULL,
+    name         TEXT     NOT NULL,
+    bug_url      TEXT,
+    note         TEXT,
+    updated_at   DATETIME NOT NULL
+);
+
+CREATE UNIQUE INDEX package_annotations_unique_idx ON package_annotations (distribution, name);
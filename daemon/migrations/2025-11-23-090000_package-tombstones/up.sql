@@ -0,0 +1,2 @@
+ALTER TABLE source_packages
+    ADD COLUMN removed_at DATETIME;
@@ -0,0 +1,2 @@
+ALTER TABLE rebuilds
+    ADD COLUMN environment_fingerprint TEXT;
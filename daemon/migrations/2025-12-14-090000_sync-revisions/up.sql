@@ -0,0 +1,12 @@
+CREATE TABLE sync_revisions
+(
+    id           INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    distribution TEXT     NOT NULL,
+    release      TEXT,
+    component    TEXT,
+    architecture TEXT     NOT NULL,
+    revision     TEXT     NOT NULL,
+    updated_at   DATETIME NOT NULL
+);
+
+CREATE INDEX sync_revisions_scope_idx ON sync_revisions (distribution, release, component, architecture);
@@ -0,0 +1,6 @@
+ALTER TABLE workers
+    ADD COLUMN previous_key TEXT;
+ALTER TABLE workers
+    ADD COLUMN previous_key_expires_at DATETIME;
+
+CREATE INDEX workers_previous_key_idx ON workers (previous_key);
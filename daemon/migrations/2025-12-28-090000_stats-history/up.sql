@@ -0,0 +1,13 @@
+CREATE TABLE stats_history
+(
+    id           INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    date         DATE    NOT NULL,
+    distribution TEXT    NOT NULL,
+    release      TEXT,
+    component    TEXT,
+    good         INTEGER NOT NULL,
+    bad          INTEGER NOT NULL,
+    unknown      INTEGER NOT NULL
+);
+
+CREATE INDEX stats_history_date_idx ON stats_history (date);
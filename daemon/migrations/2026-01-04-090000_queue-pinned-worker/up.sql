@@ -0,0 +1,4 @@
+ALTER TABLE queue
+    ADD COLUMN pinned_worker INTEGER REFERENCES workers(id) ON DELETE SET NULL;
+
+CREATE INDEX queue_pinned_worker_idx ON queue (pinned_worker);
@@ -0,0 +1,2 @@
+ALTER TABLE stats_history
+    ADD COLUMN expired INTEGER NOT NULL DEFAULT 0;
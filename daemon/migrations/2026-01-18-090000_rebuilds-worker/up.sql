@@ -0,0 +1,4 @@
+ALTER TABLE rebuilds
+    ADD COLUMN worker_id INTEGER REFERENCES workers(id) ON DELETE SET NULL;
+
+CREATE INDEX rebuilds_worker_id_idx ON rebuilds (worker_id);
@@ -0,0 +1,17 @@
+ALTER TABLE workers
+    ADD COLUMN trusted BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE provisional_rebuilds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    build_input_id INTEGER NOT NULL REFERENCES build_inputs(id) ON DELETE CASCADE,
+    worker_id INTEGER REFERENCES workers(id) ON DELETE SET NULL,
+    build_log_id INTEGER NOT NULL REFERENCES build_logs(id),
+    status TEXT NOT NULL,
+    started_at TIMESTAMP,
+    built_at TIMESTAMP NOT NULL,
+    confirmed BOOLEAN,
+    resolved_at TIMESTAMP
+);
+
+CREATE INDEX provisional_rebuilds_build_input_id_idx ON provisional_rebuilds (build_input_id);
+CREATE INDEX provisional_rebuilds_worker_id_idx ON provisional_rebuilds (worker_id);
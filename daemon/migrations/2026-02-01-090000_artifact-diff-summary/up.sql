@@ -0,0 +1,2 @@
+ALTER TABLE rebuild_artifacts
+    ADD COLUMN diff_summary TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE workers
+    ADD COLUMN uuid TEXT;
+
+CREATE UNIQUE INDEX workers_uuid_idx ON workers (uuid);
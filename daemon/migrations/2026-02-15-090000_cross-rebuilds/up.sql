@@ -0,0 +1,15 @@
+ALTER TABLE queue
+    ADD COLUMN build_architecture TEXT;
+
+CREATE TABLE cross_rebuilds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    build_input_id INTEGER NOT NULL REFERENCES build_inputs(id) ON DELETE CASCADE,
+    worker_id INTEGER REFERENCES workers(id) ON DELETE SET NULL,
+    build_log_id INTEGER NOT NULL REFERENCES build_logs(id),
+    build_architecture TEXT NOT NULL,
+    status TEXT NOT NULL,
+    started_at TIMESTAMP,
+    built_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX cross_rebuilds_build_input_id_idx ON cross_rebuilds (build_input_id);
@@ -0,0 +1,5 @@
+ALTER TABLE rebuilds
+    ADD COLUMN downloaded_bytes BIGINT;
+
+ALTER TABLE workers
+    ADD COLUMN downloaded_bytes BIGINT NOT NULL DEFAULT 0;
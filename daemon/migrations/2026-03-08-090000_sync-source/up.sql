@@ -0,0 +1,2 @@
+ALTER TABLE sync_revisions
+    ADD COLUMN source TEXT;
@@ -0,0 +1,11 @@
+ALTER TABLE build_inputs
+    ADD COLUMN popularity BIGINT;
+
+CREATE TABLE package_popularity (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    distribution TEXT NOT NULL,
+    name TEXT NOT NULL,
+    score BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX package_popularity_name_idx ON package_popularity (distribution, name);
@@ -0,0 +1,4 @@
+ALTER TABLE workers
+    ADD COLUMN fingerprint TEXT;
+ALTER TABLE workers
+    ADD COLUMN fingerprint_mismatch_at TIMESTAMP;
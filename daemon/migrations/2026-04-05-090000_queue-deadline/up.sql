@@ -0,0 +1,2 @@
+ALTER TABLE queue
+    ADD COLUMN deadline TIMESTAMP;
@@ -0,0 +1,2 @@
+ALTER TABLE build_inputs
+    ADD COLUMN payload TEXT;
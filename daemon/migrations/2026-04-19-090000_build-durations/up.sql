@@ -0,0 +1,10 @@
+CREATE TABLE build_durations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    distribution TEXT NOT NULL,
+    name TEXT NOT NULL,
+    architecture TEXT NOT NULL,
+    seconds BIGINT NOT NULL,
+    measured BOOLEAN NOT NULL
+);
+
+CREATE UNIQUE INDEX build_durations_name_idx ON build_durations (distribution, name, architecture);
@@ -0,0 +1,2 @@
+ALTER TABLE queue
+    ADD COLUMN aborted_at TIMESTAMP;
@@ -0,0 +1,11 @@
+CREATE TABLE artifact_verdicts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    checksum TEXT NOT NULL,
+    script_version TEXT NOT NULL,
+    status TEXT NOT NULL,
+    rebuild_artifact_id INTEGER REFERENCES rebuild_artifacts(id) ON DELETE CASCADE,
+    origin TEXT,
+    verified_at TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX artifact_verdicts_key_idx ON artifact_verdicts (checksum, script_version);
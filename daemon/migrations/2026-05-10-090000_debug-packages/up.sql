@@ -0,0 +1,2 @@
+ALTER TABLE binary_packages
+    ADD COLUMN parent_id INTEGER REFERENCES binary_packages(id) ON DELETE SET NULL;
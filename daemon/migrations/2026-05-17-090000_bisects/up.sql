@@ -0,0 +1,24 @@
+CREATE TABLE bisects (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    build_input_id INTEGER NOT NULL REFERENCES build_inputs(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX bisects_build_input_id_idx ON bisects (build_input_id);
+
+CREATE TABLE bisect_steps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    bisect_id INTEGER NOT NULL REFERENCES bisects(id) ON DELETE CASCADE,
+    variation TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    status TEXT,
+    build_log_id INTEGER REFERENCES build_logs(id),
+    worker_id INTEGER REFERENCES workers(id) ON DELETE SET NULL,
+    built_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX bisect_steps_position_idx ON bisect_steps (bisect_id, position);
+
+ALTER TABLE queue
+    ADD COLUMN bisect_step_id INTEGER REFERENCES bisect_steps(id) ON DELETE CASCADE;
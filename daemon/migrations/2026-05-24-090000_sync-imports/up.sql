@@ -0,0 +1,15 @@
+CREATE TABLE sync_imports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    distribution TEXT NOT NULL,
+    release TEXT,
+    component TEXT,
+    architecture TEXT NOT NULL,
+    source TEXT,
+    revision TEXT NOT NULL,
+    imported_at TIMESTAMP NOT NULL
+);
+
+ALTER TABLE build_inputs
+    ADD COLUMN sync_import_id INTEGER REFERENCES sync_imports(id) ON DELETE SET NULL;
+
+CREATE INDEX build_inputs_sync_import_id_idx ON build_inputs (sync_import_id);
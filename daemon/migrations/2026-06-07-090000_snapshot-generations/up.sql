@@ -0,0 +1,11 @@
+CREATE TABLE snapshot_generations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    distribution TEXT NOT NULL,
+    release TEXT NOT NULL,
+    first_synced_at TIMESTAMP NOT NULL,
+    expired_at TIMESTAMP,
+    good INTEGER,
+    bad INTEGER,
+    unknown INTEGER,
+    UNIQUE (distribution, release)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE queue
+    ADD COLUMN reason TEXT;
+
+ALTER TABLE rebuilds
+    ADD COLUMN reason TEXT;
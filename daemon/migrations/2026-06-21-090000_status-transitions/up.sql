@@ -0,0 +1,11 @@
+CREATE TABLE status_transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    rebuild_id INTEGER NOT NULL REFERENCES rebuilds(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    previous_version TEXT NOT NULL,
+    previous_status TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX status_transitions_rebuild_id_idx ON status_transitions (rebuild_id);
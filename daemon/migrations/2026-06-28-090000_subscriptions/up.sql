@@ -0,0 +1,22 @@
+ALTER TABLE source_packages ADD COLUMN maintainer TEXT;
+
+CREATE TABLE subscriptions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    api_key_id INTEGER NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    name TEXT,
+    maintainer TEXT,
+    distribution TEXT,
+    webhook TEXT,
+    email TEXT,
+    created_at TIMESTAMP NOT NULL,
+    -- email subscriptions are only notified once the token that was mailed to the address is confirmed
+    confirmation_token_hash TEXT,
+    confirmed_at TIMESTAMP,
+    CHECK ((name IS NULL) <> (maintainer IS NULL)),
+    CHECK ((webhook IS NULL) <> (email IS NULL))
+);
+
+CREATE INDEX subscriptions_api_key_id_idx ON subscriptions (api_key_id);
+CREATE INDEX subscriptions_name_idx ON subscriptions (name);
+CREATE INDEX subscriptions_maintainer_idx ON subscriptions (maintainer);
+CREATE INDEX subscriptions_confirmation_token_hash_idx ON subscriptions (confirmation_token_hash);
@@ -0,0 +1,321 @@
+//! Periodic checks for an instance that stopped making progress.
+//!
+//! Every rule that is enabled in the `[alerts]` section is evaluated on an interval. A notification is sent when a
+//! condition starts and another one once it's resolved, an ongoing condition doesn't repeat itself.
+use crate::config::LiveConfig;
+use crate::db::{self, Pool};
+use crate::notify::Notification;
+use crate::schema::*;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use rebuilderd_common::config::{AlertsConfig, PING_DEADLINE};
+use rebuilderd_common::errors::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    /// Identifies the condition, eg. `sync-age/archlinux/core`
+    pub key: String,
+    pub message: String,
+}
+
+/// distribution, release, component and when a package of the suite was last seen in a sync
+type SuiteLastSeen = (
+    String,
+    Option<String>,
+    Option<String>,
+    Option<NaiveDateTime>,
+);
+
+fn suite_name(distribution: &str, release: Option<&str>, component: Option<&str>) -> String {
+    [Some(distribution), release, component]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn queue_age_alert(waiting: i64, max_age: Duration) -> Option<Alert> {
+    (waiting > 0).then(|| Alert {
+        key: "queue-age".to_string(),
+        message: format!(
+            "{waiting} jobs have been waiting for a worker for more than {} hours",
+            max_age.num_hours()
+        ),
+    })
+}
+
+/// Architectures with due jobs that none of the workers is able to build, `None` is a worker that never asked for
+/// work yet
+fn missing_workers_alerts(due: &[String], workers: &[Option<String>]) -> Vec<Alert> {
+    let covered = workers
+        .iter()
+        .flatten()
+        .flat_map(|architectures| architectures.split(','))
+        .collect::<BTreeSet<_>>();
+
+    due.iter()
+        .filter(|architecture| !covered.contains(architecture.as_str()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|architecture| Alert {
+            key: format!("missing-workers/{architecture}"),
+            message: format!("Jobs for {architecture:?} are due but no online worker supports it"),
+        })
+        .collect()
+}
+
+fn sync_age_alerts(suites: &[SuiteLastSeen], deadline: NaiveDateTime) -> Vec<Alert> {
+    suites
+        .iter()
+        .filter_map(|(distribution, release, component, last_seen)| {
+            let last_seen = (*last_seen)?;
+            if last_seen >= deadline {
+                return None;
+            }
+            let suite = suite_name(distribution, release.as_deref(), component.as_deref());
+            Some(Alert {
+                key: format!("sync-age/{suite}"),
+                message: format!("Suite {suite} was last synced at {last_seen}"),
+            })
+        })
+        .collect()
+}
+
+/// Evaluate all enabled rules against the database
+pub fn check(
+    connection: &mut SqliteConnection,
+    config: &AlertsConfig,
+    now: NaiveDateTime,
+) -> Result<Vec<Alert>> {
+    let mut alerts = Vec::new();
+
+    if let Some(max_age) = config.queue_age() {
+        let deadline = now - max_age;
+        let waiting = queue::table
+            .inner_join(build_inputs::table)
+            .filter(queue::worker.is_null())
+            .filter(queue::queued_at.le(deadline))
+            .filter(
+                build_inputs::next_retry
+                    .is_null()
+                    .or(build_inputs::next_retry.le(deadline)),
+            )
+            .count()
+            .get_result::<i64>(connection)?;
+
+        alerts.extend(queue_age_alert(waiting, max_age));
+    }
+
+    if config.missing_workers() {
+        let due = queue::table
+            .inner_join(build_inputs::table)
+            .filter(queue::worker.is_null())
+            .filter(
+                build_inputs::next_retry
+                    .is_null()
+                    .or(build_inputs::next_retry.le(now)),
+            )
+            .select(build_inputs::architecture)
+            .distinct()
+            .load::<String>(connection)?;
+
+        let workers = workers::table
+            .filter(workers::online.eq(true))
+            .filter(workers::draining.eq(false))
+            .filter(workers::last_ping.ge(now - Duration::seconds(PING_DEADLINE)))
+            .select(workers::architectures)
+            .load::<Option<String>>(connection)?;
+
+        alerts.extend(missing_workers_alerts(&due, &workers));
+    }
+
+    if let Some(max_age) = config.sync_age() {
+        let suites = source_packages::table
+            .filter(source_packages::removed_at.is_null())
+            .group_by((
+                source_packages::distribution,
+                source_packages::release,
+                source_packages::component,
+            ))
+            .select((
+                source_packages::distribution,
+                source_packages::release,
+                source_packages::component,
+                diesel::dsl::max(source_packages::last_seen),
+            ))
+            .load::<SuiteLastSeen>(connection)?;
+
+        alerts.extend(sync_age_alerts(&suites, now - max_age));
+    }
+
+    Ok(alerts)
+}
+
+/// The conditions that are currently firing, keyed by [`Alert::key`]
+#[derive(Debug, Default)]
+pub struct State {
+    active: BTreeMap<String, Alert>,
+}
+
+impl State {
+    /// Compare the result of a check with the previous one and return what needs to be sent
+    pub fn update(&mut self, alerts: Vec<Alert>) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        let mut previous = std::mem::take(&mut self.active);
+
+        for alert in alerts {
+            if previous.remove(&alert.key).is_none() {
+                notifications.push(Notification::new(
+                    "alert.firing",
+                    &alert.key,
+                    alert.message.clone(),
+                ));
+            }
+            self.active.insert(alert.key.clone(), alert);
+        }
+
+        for (key, alert) in previous {
+            notifications.push(Notification::new(
+                "alert.resolved",
+                &key,
+                format!("Resolved: {}", alert.message),
+            ));
+        }
+
+        notifications
+    }
+}
+
+/// The rules are read from the current config on every check, so they can be enabled and changed with a reload
+pub async fn run(pool: Pool, config: Arc<LiveConfig>) {
+    let mut state = State::default();
+    let mut announced = None;
+
+    loop {
+        let current = config.current();
+        let interval = current.alerts.interval();
+
+        if !current.alerts.is_enabled() {
+            state = State::default();
+            announced = None;
+        } else {
+            if announced != Some(interval) {
+                info!("Checking alerting rules every {interval} seconds");
+                announced = Some(interval);
+            }
+
+            let alerts_config = current.alerts.clone();
+            let alerts = db::run(&pool, move |connection| {
+                check(connection, &alerts_config, Utc::now().naive_utc())
+            })
+            .await;
+
+            match alerts {
+                Ok(alerts) => {
+                    for notification in state.update(alerts) {
+                        current.notifier.send(&notification).await;
+                    }
+                }
+                Err(err) => error!("Failed to check alerting rules: {err:#}"),
+            }
+        }
+
+        actix_web::rt::time::sleep(std::time::Duration::from_secs(interval)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(key: &str, message: &str) -> Alert {
+        Alert {
+            key: key.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_queue_age_alert() {
+        assert_eq!(queue_age_alert(0, Duration::hours(24)), None);
+        assert_eq!(
+            queue_age_alert(3, Duration::hours(24)),
+            Some(alert(
+                "queue-age",
+                "3 jobs have been waiting for a worker for more than 24 hours"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_missing_workers_alerts() {
+        let due = vec![
+            "x86_64".to_string(),
+            "aarch64".to_string(),
+            "riscv64".to_string(),
+        ];
+        let workers = vec![
+            Some("amd64,x86_64".to_string()),
+            Some("aarch64,arm64".to_string()),
+            None,
+        ];
+        assert_eq!(
+            missing_workers_alerts(&due, &workers),
+            vec![alert(
+                "missing-workers/riscv64",
+                "Jobs for \"riscv64\" are due but no online worker supports it"
+            )]
+        );
+        assert_eq!(missing_workers_alerts(&due, &[]).len(), 3);
+    }
+
+    #[test]
+    fn test_sync_age_alerts() {
+        let deadline =
+            NaiveDateTime::parse_from_str("2025-12-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let suites = vec![
+            (
+                "archlinux".to_string(),
+                None,
+                Some("core".to_string()),
+                Some(deadline - Duration::days(1)),
+            ),
+            (
+                "archlinux".to_string(),
+                None,
+                Some("extra".to_string()),
+                Some(deadline + Duration::days(1)),
+            ),
+        ];
+        assert_eq!(
+            sync_age_alerts(&suites, deadline),
+            vec![alert(
+                "sync-age/archlinux/core",
+                "Suite archlinux/core was last synced at 2025-11-30 00:00:00"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_state_only_sends_transitions() {
+        let mut state = State::default();
+
+        let sent = state.update(vec![alert("queue-age", "3 jobs")]);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].event, "alert.firing");
+
+        // still firing, the message may change but nothing is sent again
+        let sent = state.update(vec![alert("queue-age", "5 jobs")]);
+        assert!(sent.is_empty());
+
+        let sent = state.update(vec![]);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].event, "alert.resolved");
+        assert_eq!(sent[0].key, "queue-age");
+        assert_eq!(sent[0].message, "Resolved: 5 jobs");
+
+        assert!(state.update(vec![]).is_empty());
+    }
+}
@@ -1,8 +1,25 @@
+use crate::config::{Config, LiveConfig};
+use crate::logging::AccessRecord;
 use crate::web;
-use actix_web::http::header::{AcceptEncoding, ContentEncoding, Encoding, Header};
-use actix_web::{HttpRequest, HttpResponse};
-use rebuilderd_common::errors::{Context, Error, format_err};
+use actix_web::body::{self, BoxBody, EitherBody, MessageBody};
+use actix_web::dev::{Extensions, ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::http::header::{
+    self, Accept, AcceptEncoding, ContentEncoding, Encoding, Header, HeaderName, HeaderValue,
+};
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, mime};
+use chrono::{SecondsFormat, Utc};
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::api::{
+    AUTH_COOKIE_HEADER, ApiEnvelope, ApiError, SIGNUP_SECRET_HEADER, WORKER_KEY_HEADER,
+    WORKER_TOKEN_HEADER,
+};
+use rebuilderd_common::errors::{self, Context, Error, format_err};
 use rebuilderd_common::utils::{is_zstd_compressed, zstd_decompress};
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::time::Instant;
 
 pub mod v0;
 pub mod v1;
@@ -55,3 +72,519 @@ pub fn header<'a>(req: &'a HttpRequest, key: &str) -> rebuilderd_common::errors:
 
     Ok(value)
 }
+
+/// The address of the client, taken from `real_ip_header` if the daemon is behind a reverse proxy
+pub fn client_ip(cfg: &Config, req: &HttpRequest) -> rebuilderd_common::errors::Result<IpAddr> {
+    if let Some(real_ip_header) = &cfg.real_ip_header {
+        let ip = header(req, real_ip_header).context("Failed to locate real ip header")?;
+        let ip = ip
+            .parse::<IpAddr>()
+            .context("Can't parse real ip header as ip address")?;
+        Ok(ip)
+    } else {
+        let ci = req
+            .peer_addr()
+            .ok_or_else(|| format_err!("Can't determine client ip"))?;
+        Ok(ci.ip())
+    }
+}
+
+/// Middleware that provides the config as it is at the start of the request, the handlers pick up a reloaded config
+/// without the app being rebuilt. It needs to wrap all other middleware that reads the config.
+pub async fn current_config(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(config) = req
+        .app_data::<web::Data<LiveConfig>>()
+        .map(|live| live.current())
+    {
+        let mut data = Extensions::new();
+        data.insert(web::Data::from(config));
+        req.add_data_container(Rc::new(data));
+    }
+    next.call(req).await
+}
+
+/// Middleware for the public listener. Anything that could modify state is rejected, and credentials are removed
+/// before routing so endpoints that need authentication can't be reached through this listener at all.
+pub async fn read_only(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        let res = HttpResponse::MethodNotAllowed().finish();
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+
+    let headers = req.headers_mut();
+    for header in [
+        AUTH_COOKIE_HEADER,
+        WORKER_KEY_HEADER,
+        WORKER_TOKEN_HEADER,
+        SIGNUP_SECRET_HEADER,
+    ] {
+        headers.remove(header);
+    }
+    headers.remove(header::AUTHORIZATION);
+
+    let res = next.call(req).await?;
+    Ok(res.map_into_left_body())
+}
+
+/// The request body encodings actix decodes before the body reaches an extractor
+const REQUEST_ENCODINGS: &[&str] = &["identity", "zstd", "gzip", "x-gzip", "deflate", "br"];
+
+/// Reject request bodies in an encoding that can't be decoded. Workers send large reports compressed, the 415 and the
+/// `Accept-Encoding` header tell them to retry without compression instead of failing with a confusing parse error.
+pub async fn request_encoding(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let encoding = req.headers().get(header::CONTENT_ENCODING).map(|value| {
+        value
+            .to_str()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    });
+
+    if let Some(encoding) = encoding
+        && !REQUEST_ENCODINGS.contains(&encoding.as_str())
+    {
+        let res = HttpResponse::UnsupportedMediaType()
+            .insert_header((header::ACCEPT_ENCODING, "zstd, gzip, deflate, br"))
+            .body(format!("Unsupported Content-Encoding: {encoding:?}"));
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+
+    let res = next.call(req).await?;
+    Ok(res.map_into_left_body())
+}
+
+/// Identifies a request in the logs of the daemon, handlers can read it from the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Who a request was authenticated as, recorded by the auth helpers for the access log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity(pub String);
+
+/// Replace the body of a failed api request with an [`ApiError`], the original body becomes the message
+async fn into_api_error(
+    res: ServiceResponse<BoxBody>,
+    request_id: &str,
+) -> ServiceResponse<BoxBody> {
+    let status = res.status();
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+
+    let body = body::to_bytes(body).await.unwrap_or_default();
+    let message = String::from_utf8_lossy(&body).trim().to_string();
+    let error = if message.is_empty() {
+        status.canonical_reason().unwrap_or_default().to_string()
+    } else {
+        message
+    };
+
+    let api_error = ApiError {
+        request_id: request_id.to_string(),
+        error,
+    };
+    let json = serde_json::to_string(&api_error).unwrap_or_default();
+
+    let mut res = res.set_body(BoxBody::new(json));
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    ServiceResponse::new(req, res)
+}
+
+/// Clients opt into [`ApiEnvelope`] responses with `Accept: application/json; envelope=true`
+fn wants_envelope(req: &ServiceRequest) -> bool {
+    Accept::parse(req).is_ok_and(|accept| {
+        accept.iter().any(|mime| {
+            mime.item.essence_str() == mime::APPLICATION_JSON.essence_str()
+                && mime.item.get_param("envelope").is_some_and(|v| v == "true")
+        })
+    })
+}
+
+/// Wrap the body of a successful json response in an [`ApiEnvelope`]. Compressed and non-json bodies, like logs or
+/// streams, are left as they are.
+async fn into_envelope(
+    res: ServiceResponse<BoxBody>,
+    request_id: &str,
+) -> ServiceResponse<BoxBody> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == mime::APPLICATION_JSON.essence_str());
+    if !is_json || res.headers().contains_key(header::CONTENT_ENCODING) {
+        return res;
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = body::to_bytes(body).await.unwrap_or_default();
+
+    let body = match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(data) => {
+            let envelope = ApiEnvelope {
+                request_id: request_id.to_string(),
+                data,
+            };
+            serde_json::to_vec(&envelope).map_or(body, Into::into)
+        }
+        Err(_) => body,
+    };
+
+    ServiceResponse::new(req, res.set_body(BoxBody::new(body)))
+}
+
+/// Middleware that tags every request with a generated id, which is returned in the `X-Request-Id` header and logged
+/// with the error if the request failed. Failed api requests respond with an [`ApiError`] that includes the id,
+/// successful ones only if the client asked for an [`ApiEnvelope`].
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let id = Alphanumeric.sample_string(&mut rand::rng(), 16);
+    req.extensions_mut().insert(RequestId(id.clone()));
+    let envelope = wants_envelope(&req);
+
+    // errors of handlers are already turned into responses, keeping a clone of the request around to do the same here
+    // would make routing panic
+    let res = next.call(req).await?.map_into_boxed_body();
+
+    let status = res.status();
+    if let Some(err) = res.response().error() {
+        if status.is_server_error() {
+            errors::error!("Request {id} to {:?} failed: {err}", res.request().path());
+        } else {
+            errors::debug!("Request {id} to {:?} failed: {err}", res.request().path());
+        }
+    }
+
+    let is_api = res.request().path().starts_with("/api/");
+    let mut res = if is_api && (status.is_client_error() || status.is_server_error()) {
+        into_api_error(res, &id).await
+    } else if is_api && envelope && status.is_success() {
+        into_envelope(res, &id).await
+    } else {
+        res
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+
+    Ok(res)
+}
+
+/// Middleware for the routes used by workers. Responses carry the time of the daemon, workers compare it with their
+/// own clock and use it for the timestamps they report.
+pub async fn server_time(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut res = next.call(req).await?;
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    if let Ok(value) = HeaderValue::from_str(&now) {
+        res.headers_mut()
+            .insert(HeaderName::from_static("x-server-time"), value);
+    }
+    Ok(res)
+}
+
+/// Middleware that writes a record of every request to the access log, if one is configured. It wraps
+/// [`request_id`], so failed requests already have been turned into a response and the id is known.
+pub async fn access_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(config) = req.app_data::<web::Data<Config>>().cloned() else {
+        return next.call(req).await;
+    };
+    let Some(sink) = &config.access_log else {
+        return next.call(req).await;
+    };
+
+    let started = Instant::now();
+    let res = next.call(req).await?;
+    let latency = started.elapsed();
+
+    {
+        let req = res.request();
+        let remote = if let Some(real_ip_header) = &config.real_ip_header {
+            header(req, real_ip_header).ok().map(String::from)
+        } else {
+            req.peer_addr().map(|addr| addr.ip().to_string())
+        };
+
+        let extensions = req.extensions();
+        sink.write(&AccessRecord {
+            time: Utc::now(),
+            request_id: extensions.get::<RequestId>().map(|id| id.0.as_str()),
+            remote,
+            method: req.method().as_str(),
+            path: req.path(),
+            status: res.status().as_u16(),
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            identity: extensions
+                .get::<Identity>()
+                .map(|identity| identity.0.as_str()),
+        });
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, test};
+    use rebuilderd_common::api::REQUEST_ID_HEADER;
+    use std::sync::Arc;
+
+    async fn echo_auth_cookie(req: HttpRequest) -> HttpResponse {
+        let cookie = header(&req, AUTH_COOKIE_HEADER).unwrap_or_default();
+        HttpResponse::Ok().body(cookie.to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_read_only_rejects_writes() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(read_only))
+                .route("/", actix_web::web::to(echo_auth_cookie)),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_read_only_removes_credentials() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(read_only))
+                .route("/", actix_web::web::to(echo_auth_cookie)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((AUTH_COOKIE_HEADER, "INSECURE"))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert!(body.is_empty());
+    }
+
+    async fn echo_real_ip_header(cfg: web::Data<Config>) -> HttpResponse {
+        HttpResponse::Ok().body(cfg.real_ip_header.clone().unwrap_or_default())
+    }
+
+    #[actix_web::test]
+    async fn test_current_config_after_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rebuilderd.conf");
+        let config =
+            crate::config::from_struct(Default::default(), "INSECURE".to_string()).unwrap();
+        let live = Arc::new(LiveConfig::new(config, Some(path.clone())));
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(current_config))
+                .app_data(web::Data::from(live.clone()))
+                .route("/", actix_web::web::to(echo_real_ip_header)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "");
+
+        std::fs::write(&path, "[http]\nreal_ip_header = \"X-Forwarded-For\"\n").unwrap();
+        live.reload().unwrap();
+        let req = test::TestRequest::get().uri("/").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "X-Forwarded-For");
+
+        // an invalid config is rejected and the previous one stays in place
+        std::fs::write(&path, "[http\n").unwrap();
+        assert!(live.reload().is_err());
+        let req = test::TestRequest::get().uri("/").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "X-Forwarded-For");
+    }
+
+    async fn echo_json(body: web::Json<serde_json::Value>) -> HttpResponse {
+        HttpResponse::Ok().json(body.into_inner())
+    }
+
+    #[actix_web::test]
+    async fn test_request_encoding() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(request_encoding))
+                .route("/", actix_web::web::post().to(echo_json)),
+        )
+        .await;
+
+        let body = rebuilderd_common::utils::zstd_compress(br#"{"status":"GOOD"}"#)
+            .await
+            .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/")
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .insert_header((header::CONTENT_ENCODING, "zstd"))
+            .set_payload(body)
+            .to_request();
+        let body = test::call_and_read_body_json::<_, _, serde_json::Value>(&app, req).await;
+        assert_eq!(body, serde_json::json!({"status": "GOOD"}));
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .insert_header((header::CONTENT_ENCODING, "compress"))
+            .set_payload("{}")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(res.headers().contains_key(header::ACCEPT_ENCODING));
+    }
+
+    async fn fail() -> web::Result<HttpResponse> {
+        Err(format_err!("database is locked").into())
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_wraps_api_errors() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(request_id))
+                .route("/api/v1/fail", actix_web::web::to(fail))
+                .route("/api/v0/fail", actix_web::web::to(fail))
+                .route(
+                    "/api/v1/forbidden",
+                    actix_web::web::to(|| async { HttpResponse::Forbidden().finish() }),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/v1/fail").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let id = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = test::read_body_json::<ApiError, _>(res).await;
+        assert_eq!(
+            body,
+            ApiError {
+                request_id: id,
+                error: "database is locked".to_string(),
+            }
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/forbidden")
+            .to_request();
+        let body = test::call_and_read_body_json::<_, _, ApiError>(&app, req).await;
+        assert_eq!(body.error, "Forbidden");
+
+        let req = test::TestRequest::get().uri("/api/v0/fail").to_request();
+        let body = test::call_and_read_body_json::<_, _, ApiError>(&app, req).await;
+        assert_eq!(body.error, "database is locked");
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_envelope() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(request_id))
+                .route(
+                    "/api/v1/json",
+                    actix_web::web::to(|| async {
+                        HttpResponse::Ok().json(serde_json::json!({"status": "GOOD"}))
+                    }),
+                )
+                .route(
+                    "/api/v1/log",
+                    actix_web::web::to(|| async { HttpResponse::Ok().body("build log") }),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/json")
+            .insert_header((header::ACCEPT, "application/json; envelope=true"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let id = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = test::read_body_json::<ApiEnvelope<serde_json::Value>, _>(res).await;
+        assert_eq!(
+            body,
+            ApiEnvelope {
+                request_id: id,
+                data: serde_json::json!({"status": "GOOD"}),
+            }
+        );
+
+        // without asking for it the body is left as it is
+        let req = test::TestRequest::get()
+            .uri("/api/v1/json")
+            .insert_header((header::ACCEPT, "application/json"))
+            .to_request();
+        let body = test::call_and_read_body_json::<_, _, serde_json::Value>(&app, req).await;
+        assert_eq!(body, serde_json::json!({"status": "GOOD"}));
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/log")
+            .insert_header((header::ACCEPT, "application/json; envelope=true"))
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "build log");
+    }
+
+    #[actix_web::test]
+    async fn test_server_time() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(server_time))
+                .route("/", actix_web::web::to(HttpResponse::NoContent)),
+        )
+        .await;
+
+        let before = Utc::now();
+        let req = test::TestRequest::post().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+        let value = res
+            .headers()
+            .get(rebuilderd_common::api::SERVER_TIME_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let server_time = chrono::DateTime::parse_from_rfc3339(value).unwrap();
+        assert!(server_time >= before - chrono::Duration::milliseconds(1));
+        assert!(server_time <= Utc::now());
+    }
+}
@@ -0,0 +1,322 @@
+use crate::api::v0::aliases::r1;
+use crate::api::v0::{filter_binary_packages_by, not_found};
+use crate::db::{self, Pool};
+use crate::schema::*;
+use crate::storage::Storage;
+use crate::web::{self, escape_html};
+use actix_web::http::header::{self, Accept, Header};
+use actix_web::{HttpRequest, HttpResponse, Responder, get};
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, NullableExpressionMethods, OptionalExtension};
+use diesel::{QueryDsl, RunQueryDsl};
+use rebuilderd_common::api::v0::*;
+use rebuilderd_common::errors::*;
+use rebuilderd_common::utils::{is_zstd_compressed, zstd_decompress};
+use std::fmt::Write;
+
+/// The plaintext variant is meant for terminals, larger diffs are cut off and need to be fetched as json
+const PLAINTEXT_LIMIT: usize = 1024 * 1024; // 1MB
+
+#[derive(Debug, PartialEq, Eq)]
+enum DiffFormat {
+    Json,
+    Html,
+    Text,
+}
+
+fn negotiate(req: &HttpRequest) -> DiffFormat {
+    let ranked = Accept::parse(req)
+        .map(|accept| accept.ranked())
+        .unwrap_or_default();
+
+    for mime in ranked {
+        match mime.essence_str() {
+            "application/json" => return DiffFormat::Json,
+            "text/html" => return DiffFormat::Html,
+            "text/plain" | "text/*" | "*/*" => return DiffFormat::Text,
+            _ => (),
+        }
+    }
+
+    DiffFormat::Text
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!(
+        "{}\n\n[diff truncated, showing {} of {} bytes]\n",
+        &text[..end],
+        end,
+        text.len()
+    )
+}
+
+/// Pick a css class for a line of diffoscope text output, nested sections are indented with `│ `
+fn classify(line: &str) -> Option<&'static str> {
+    if line.starts_with("--- ") || line.starts_with("+++ ") {
+        return Some("file");
+    }
+
+    let mut content = line;
+    while let Some(rest) = content.strip_prefix("│ ") {
+        content = rest;
+    }
+
+    let trimmed = content.trim_start();
+    if trimmed.starts_with("├──") || trimmed.starts_with("└──") {
+        Some("section")
+    } else if content.starts_with("@@") {
+        Some("hunk")
+    } else if content.starts_with('-') {
+        Some("del")
+    } else if content.starts_with('+') {
+        Some("add")
+    } else {
+        None
+    }
+}
+
+fn render_html(diff: &PkgDiff) -> String {
+    let title = escape_html(&format!(
+        "{} {} ({}/{}, {})",
+        diff.name, diff.version, diff.distro, diff.suite, diff.architecture
+    ));
+
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 1em 2em; }}
+pre {{ font-size: 13px; line-height: 1.3; }}
+.file {{ font-weight: bold; }}
+.section {{ color: #6f42c1; font-weight: bold; }}
+.hunk {{ color: #0969da; }}
+.del {{ background: #ffebe9; color: #82071e; }}
+.add {{ background: #dafbe1; color: #116329; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>Build #{} from {}</p>
+<pre>"#,
+        diff.build_id, diff.built_at
+    );
+
+    for line in diff.diffoscope.lines() {
+        let line_html = escape_html(line);
+        if let Some(class) = classify(line) {
+            let _ = writeln!(html, r#"<span class="{class}">{line_html}</span>"#);
+        } else {
+            let _ = writeln!(html, "{line_html}");
+        }
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+#[get("/pkgs/{name}/diff")]
+pub async fn get_pkg_diff(
+    req: HttpRequest,
+    name: web::Path<String>,
+    query: web::Query<PkgDiffQuery>,
+    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
+) -> web::Result<impl Responder> {
+    let name = name.into_inner();
+    let query = query.into_inner();
+
+    let found = db::run(&pool, move |connection| {
+        // the diff of the latest rebuild that produced one, same as the has_diffoscope flag of /pkgs/list
+        let Some(row) = filter_binary_packages_by(
+            Some(&name),
+            query.distro.as_deref(),
+            None,
+            query.suite.as_deref(),
+            query.architecture.as_deref(),
+            None,
+        )
+        .filter(rebuild_artifacts::diffoscope_log_id.is_not_null())
+        .select((
+            binary_packages::name,
+            binary_packages::version,
+            source_packages::distribution,
+            source_packages::component,
+            binary_packages::architecture,
+            r1.field(rebuilds::id).nullable(),
+            r1.field(rebuilds::built_at).nullable(),
+            rebuild_artifacts::diffoscope_log_id.nullable(),
+        ))
+        .order_by(r1.field(rebuilds::built_at).desc())
+        .first::<(
+            String,
+            String,
+            String,
+            Option<String>,
+            String,
+            Option<i32>,
+            Option<NaiveDateTime>,
+            Option<i32>,
+        )>(connection)
+        .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let (
+            name,
+            version,
+            distro,
+            suite,
+            architecture,
+            Some(build_id),
+            Some(built_at),
+            Some(diffoscope_log_id),
+        ) = row
+        else {
+            return Ok(None);
+        };
+
+        let (diffoscope, blob_key) = diffoscope_logs::table
+            .filter(diffoscope_logs::id.eq(diffoscope_log_id))
+            .select((diffoscope_logs::diffoscope_log, diffoscope_logs::blob_key))
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)?;
+
+        let diff = PkgDiff {
+            name,
+            version,
+            distro,
+            suite: suite.unwrap_or_default(),
+            architecture,
+            build_id,
+            built_at,
+            diffoscope: String::new(),
+        };
+
+        Ok(Some((diff, diffoscope, blob_key)))
+    })
+    .await?;
+
+    let Some((mut diff, diffoscope, blob_key)) = found else {
+        return Ok(not_found());
+    };
+
+    let Some(mut diffoscope) = storage.load(diffoscope, blob_key).await? else {
+        return Ok(not_found());
+    };
+
+    if is_zstd_compressed(&diffoscope) {
+        diffoscope = zstd_decompress(&diffoscope).await.map_err(Error::from)?;
+    }
+    diff.diffoscope = String::from_utf8_lossy(&diffoscope).into_owned();
+
+    let mut builder = HttpResponse::Ok();
+    builder.append_header((header::VARY, "Accept"));
+
+    let res = match negotiate(&req) {
+        DiffFormat::Json => builder.json(diff),
+        DiffFormat::Html => builder
+            .content_type("text/html; charset=utf-8")
+            .append_header(("X-Content-Type-Options", "nosniff"))
+            .append_header((
+                "Content-Security-Policy",
+                "default-src 'none'; style-src 'unsafe-inline'",
+            ))
+            .body(render_html(&diff)),
+        DiffFormat::Text => builder
+            .content_type("text/plain; charset=utf-8")
+            .append_header(("X-Content-Type-Options", "nosniff"))
+            .body(truncate(&diff.diffoscope, PLAINTEXT_LIMIT)),
+    };
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn request(accept: Option<&str>) -> HttpRequest {
+        let mut req = TestRequest::default();
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        req.to_http_request()
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&request(None)), DiffFormat::Text);
+        assert_eq!(negotiate(&request(Some("*/*"))), DiffFormat::Text);
+        assert_eq!(
+            negotiate(&request(Some("application/json"))),
+            DiffFormat::Json
+        );
+        assert_eq!(
+            negotiate(&request(Some(
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+            ))),
+            DiffFormat::Html
+        );
+        assert_eq!(
+            negotiate(&request(Some("text/html;q=0.5, application/json"))),
+            DiffFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(
+            truncate("hello world", 5),
+            "hello\n\n[diff truncated, showing 5 of 11 bytes]\n"
+        );
+        // never split a multi-byte character
+        assert_eq!(
+            truncate("a│b", 2),
+            "a\n\n[diff truncated, showing 1 of 5 bytes]\n"
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("--- a/foo.pkg"), Some("file"));
+        assert_eq!(classify("+++ b/foo.pkg"), Some("file"));
+        assert_eq!(classify("├── .BUILDINFO"), Some("section"));
+        assert_eq!(classify("│   ├── file list"), Some("section"));
+        assert_eq!(classify("│ @@ -1,3 +1,3 @@"), Some("hunk"));
+        assert_eq!(classify("│ │ -old"), Some("del"));
+        assert_eq!(classify("│ │ +new"), Some("add"));
+        assert_eq!(classify("│ │  -context"), None);
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let diff = PkgDiff {
+            name: "foo".to_string(),
+            version: "1.0".to_string(),
+            distro: "archlinux".to_string(),
+            suite: "core".to_string(),
+            architecture: "x86_64".to_string(),
+            build_id: 1,
+            built_at: NaiveDateTime::default(),
+            diffoscope: "│ -<script>\n│ +&amp;\n".to_string(),
+        };
+        let html = render_html(&diff);
+        assert!(html.contains(r#"<span class="del">│ -&lt;script&gt;</span>"#));
+        assert!(html.contains(r#"<span class="add">│ +&amp;amp;</span>"#));
+        assert!(!html.contains("<script>"));
+    }
+}
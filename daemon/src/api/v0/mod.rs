@@ -4,15 +4,19 @@ use diesel::{
 };
 mod auth;
 mod dashboard;
+mod diff;
+mod stats;
 
 use crate::api::forward_compressed_data;
 use crate::api::v0::aliases::{r1, r2};
+use crate::api::v1;
 use crate::attestation::{self};
 use crate::config::Config;
-use crate::db::Pool;
+use crate::db::{self, Pool};
 use crate::models;
 use crate::models::{BinaryPackage, BuildInput, Queued, SourcePackage};
 use crate::schema::*;
+use crate::storage::Storage;
 use crate::web;
 use actix_web::{HttpRequest, HttpResponse, Responder, get, http, post};
 use chrono::Duration;
@@ -20,13 +24,20 @@ use chrono::prelude::*;
 pub(crate) use dashboard::DashboardState;
 use diesel::dsl::auto_type;
 use diesel::{QueryDsl, RunQueryDsl};
+pub use diff::get_pkg_diff;
 use in_toto::crypto::PrivateKey;
 use rebuilderd_common::api::v0::*;
 use rebuilderd_common::config::PING_DEADLINE;
 use rebuilderd_common::errors::*;
+pub use stats::{get_stats_history, get_worker_stats};
+use std::collections::{BTreeMap, HashSet};
 use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
+/// Status queries are meant for CI pipelines checking their own packages, not for dumping the whole archive
+pub const MAX_STATUS_BATCH_NAMES: usize = 5000;
+const STATUS_BATCH_SIZE: usize = 500;
+
 fn forbidden() -> HttpResponse {
     HttpResponse::Forbidden().body("Authentication failed\n")
 }
@@ -66,25 +77,26 @@ pub async fn list_workers(
         return Ok(forbidden());
     }
 
-    let mut connection = pool.get().map_err(Error::from)?;
+    let workers = db::run(&pool, |connection| {
+        // mark stale workers as offline before returning any results
+        let now = Utc::now().naive_utc();
+        let deadline = now - Duration::seconds(PING_DEADLINE);
 
-    // mark stale workers as offline before returning any results
-    let now = Utc::now().naive_utc();
-    let deadline = now - Duration::seconds(PING_DEADLINE);
+        diesel::update(workers::table.filter(workers::last_ping.lt(deadline)))
+            .set((
+                workers::online.eq(false),
+                workers::status.eq(None as Option<String>),
+            ))
+            .execute(connection)?;
 
-    diesel::update(workers::table.filter(workers::last_ping.lt(deadline)))
-        .set((
-            workers::online.eq(false),
-            workers::status.eq(None as Option<String>),
-        ))
-        .execute(connection.as_mut())
-        .map_err(Error::from)?;
+        // grab online workers
+        let workers = workers::table
+            .filter(workers::online.eq(true))
+            .load::<models::Worker>(connection)?;
 
-    // grab online workers
-    let workers = workers::table
-        .filter(workers::online.eq(true))
-        .load::<models::Worker>(connection.as_mut())
-        .map_err(Error::from)?;
+        Ok(workers)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(workers))
 }
@@ -171,22 +183,55 @@ fn filter_binary_packages_by<'a>(
     query
 }
 
+type PkgReleaseRow = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<i32>,
+    Option<NaiveDateTime>,
+    Option<bool>,
+    Option<bool>,
+);
+
+fn into_pkg_release(d: PkgReleaseRow) -> Result<PkgRelease> {
+    Ok(PkgRelease {
+        name: d.0,
+        distro: d.1,
+        architecture: d.2,
+        version: d.3,
+        status: d.4.unwrap_or("UNKWN".to_string()).parse()?,
+        suite: d.5.unwrap_or_default(), // TODO: behaviour change, was always present, may not be now
+        artifact_url: d.6,
+        build_id: d.7,
+        built_at: d.8,
+        has_diffoscope: d.9.unwrap_or_default(),
+        has_attestation: d.10.unwrap_or_default(),
+    })
+}
+
 #[get("/pkgs/list")]
 pub async fn list_pkgs(
     req: HttpRequest,
     query: web::Query<ListPkgs>,
     pool: web::Data<Pool>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
     let mut builder = HttpResponse::Ok();
 
     // Set Last-Modified header to the most recent build package time
     // If If-Modified-Since header is set, compare it to the latest built time.
-    if let Some(latest_built_at) = rebuilds::table
-        .select(diesel::dsl::max(rebuilds::built_at))
-        .first(connection.as_mut())
-        .map_err(Error::from)?
-    {
+    let latest_built_at = db::run(&pool, |connection| {
+        let latest_built_at = rebuilds::table
+            .select(diesel::dsl::max(rebuilds::built_at))
+            .first::<Option<NaiveDateTime>>(connection)?;
+        Ok(latest_built_at)
+    })
+    .await?;
+
+    if let Some(latest_built_at) = latest_built_at {
         let latest_built_at = DateTime::from_naive_utc_and_offset(latest_built_at, Utc);
         if let Some(duration) = modified_since_duration(&req, latest_built_at)
             && duration.num_seconds() >= 0
@@ -198,68 +243,328 @@ pub async fn list_pkgs(
         builder.insert_header(http::header::LastModified(latest_built_at.into()));
     }
 
-    let data = filter_binary_packages_by(
-        query.name.as_deref(),
-        query.distro.as_deref(),
-        None,
-        query.suite.as_deref(),
-        query.architecture.as_deref(),
-        query.status.map(|s| s.to_string()).as_deref(),
-    )
-    .select((
-        binary_packages::name,
-        source_packages::distribution,
-        binary_packages::architecture,
-        binary_packages::version,
-        rebuild_artifacts::status.nullable(),
-        source_packages::component,
-        binary_packages::artifact_url,
-        r1.field(rebuilds::id).nullable(),
-        r1.field(rebuilds::built_at).nullable(),
-        rebuild_artifacts::diffoscope_log_id
-            .is_not_null()
-            .nullable(),
-        rebuild_artifacts::attestation_log_id
-            .is_not_null()
-            .nullable(),
-    ))
-    .get_results::<(
-        String,
-        String,
-        String,
-        String,
-        Option<String>,
-        Option<String>,
-        String,
-        Option<i32>,
-        Option<NaiveDateTime>,
-        Option<bool>,
-        Option<bool>,
-    )>(connection.as_mut())
-    .map_err(Error::from)?;
+    let query = query.into_inner();
+    let data = db::run(&pool, move |connection| {
+        let data = filter_binary_packages_by(
+            query.name.as_deref(),
+            query.distro.as_deref(),
+            None,
+            query.suite.as_deref(),
+            query.architecture.as_deref(),
+            query.status.map(|s| s.to_string()).as_deref(),
+        )
+        .select((
+            binary_packages::name,
+            source_packages::distribution,
+            binary_packages::architecture,
+            binary_packages::version,
+            rebuild_artifacts::status.nullable(),
+            source_packages::component,
+            binary_packages::artifact_url,
+            r1.field(rebuilds::id).nullable(),
+            r1.field(rebuilds::built_at).nullable(),
+            rebuild_artifacts::diffoscope_log_id
+                .is_not_null()
+                .nullable(),
+            rebuild_artifacts::attestation_log_id
+                .is_not_null()
+                .nullable(),
+        ))
+        .get_results::<PkgReleaseRow>(connection)?;
+        Ok(data)
+    })
+    .await?;
+
+    let mapped = data
+        .into_iter()
+        .map(into_pkg_release)
+        .collect::<Result<Vec<PkgRelease>>>()?;
+
+    Ok(builder.json(mapped))
+}
+
+/// The query string form of a status query, names are passed as repeated `pkg` parameters, eg.
+/// `?pkg=curl&pkg=xz&distro=debian`
+fn status_batch_query(query_string: &str) -> PkgStatusBatchQuery {
+    let mut query = PkgStatusBatchQuery {
+        names: Vec::new(),
+        distro: None,
+        suite: None,
+        architecture: None,
+    };
+    for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+        match key.as_ref() {
+            "pkg" => query.names.push(value.into_owned()),
+            "distro" => query.distro = Some(value.into_owned()),
+            "suite" => query.suite = Some(value.into_owned()),
+            "architecture" => query.architecture = Some(value.into_owned()),
+            _ => (),
+        }
+    }
+    query
+}
+
+/// Same as the POST form, but can be reached through the read-only public listener
+#[get("/pkgs/status-batch")]
+pub async fn get_pkg_status_batch(
+    req: HttpRequest,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    let query = status_batch_query(req.query_string());
+    pkg_status(query, &pool).await
+}
+
+#[post("/pkgs/status-batch")]
+pub async fn pkg_status_batch(
+    query: web::Json<PkgStatusBatchQuery>,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    pkg_status(query.into_inner(), &pool).await
+}
+
+async fn pkg_status(query: PkgStatusBatchQuery, pool: &Pool) -> web::Result<HttpResponse> {
+    let PkgStatusBatchQuery {
+        mut names,
+        distro,
+        suite,
+        architecture,
+    } = query;
+    if names.len() > MAX_STATUS_BATCH_NAMES {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Too many package names, at most {MAX_STATUS_BATCH_NAMES} can be queried at once\n"
+        )));
+    }
+
+    names.sort();
+    names.dedup();
+
+    let requested = names.clone();
+    let data = db::run(pool, move |connection| {
+        let mut data = Vec::new();
+        for chunk in requested.chunks(STATUS_BATCH_SIZE) {
+            let rows = filter_binary_packages_by(
+                None,
+                distro.as_deref(),
+                None,
+                suite.as_deref(),
+                architecture.as_deref(),
+                None,
+            )
+            .filter(binary_packages::name.eq_any(chunk))
+            .select((
+                binary_packages::name,
+                source_packages::distribution,
+                binary_packages::architecture,
+                binary_packages::version,
+                rebuild_artifacts::status.nullable(),
+                source_packages::component,
+                binary_packages::artifact_url,
+                r1.field(rebuilds::id).nullable(),
+                r1.field(rebuilds::built_at).nullable(),
+                rebuild_artifacts::diffoscope_log_id
+                    .is_not_null()
+                    .nullable(),
+                rebuild_artifacts::attestation_log_id
+                    .is_not_null()
+                    .nullable(),
+            ))
+            .get_results::<PkgReleaseRow>(connection)?;
+            data.extend(rows);
+        }
+        Ok(data)
+    })
+    .await?;
+
+    let packages = data
+        .into_iter()
+        .map(into_pkg_release)
+        .collect::<Result<Vec<PkgRelease>>>()?;
+
+    let known = packages
+        .iter()
+        .map(|pkg| pkg.name.as_str())
+        .collect::<HashSet<_>>();
+    let unknown = names
+        .into_iter()
+        .filter(|name| !known.contains(name.as_str()))
+        .collect::<Vec<_>>();
+
+    let all_good = unknown.is_empty() && packages.iter().all(|pkg| pkg.status == Status::Good);
+
+    Ok(HttpResponse::Ok().json(PkgStatusBatch {
+        all_good,
+        packages,
+        unknown,
+    }))
+}
+
+#[get("/pkgs/{name}/artifacts")]
+pub async fn get_pkg_artifacts(
+    name: web::Path<String>,
+    query: web::Query<PkgArtifactsQuery>,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    let name = name.into_inner();
+    let query = query.into_inner();
+
+    let data = db::run(&pool, move |connection| {
+        let data = filter_binary_packages_by(
+            Some(&name),
+            query.distro.as_deref(),
+            None,
+            query.suite.as_deref(),
+            query.architecture.as_deref(),
+            None,
+        )
+        .select((
+            binary_packages::name,
+            binary_packages::version,
+            source_packages::distribution,
+            source_packages::component,
+            binary_packages::architecture,
+            binary_packages::artifact_url,
+            binary_packages::checksum,
+            r1.field(rebuilds::id).nullable(),
+            r1.field(rebuilds::built_at).nullable(),
+            rebuild_artifacts::status.nullable(),
+            rebuild_artifacts::checksum.nullable(),
+            rebuild_artifacts::attestation_log_id
+                .is_not_null()
+                .nullable(),
+        ))
+        .get_results::<(
+            String,
+            String,
+            String,
+            Option<String>,
+            String,
+            String,
+            Option<String>,
+            Option<i32>,
+            Option<NaiveDateTime>,
+            Option<String>,
+            Option<String>,
+            Option<bool>,
+        )>(connection)?;
+        Ok(data)
+    })
+    .await?;
+
+    if data.is_empty() {
+        return Ok(not_found());
+    }
 
     let mapped = data
         .into_iter()
         .map(|d| {
-            let release = PkgRelease {
+            // rebuilt binaries are not kept, the attestation is the closest thing to a download
+            let attestation_url = match (d.7, d.11) {
+                (Some(build_id), Some(true)) => {
+                    Some(format!("/api/v0/builds/{build_id}/attestation"))
+                }
+                _ => None,
+            };
+
+            Ok(PkgArtifactChecksums {
                 name: d.0,
-                distro: d.1,
-                architecture: d.2,
-                version: d.3,
-                status: d.4.unwrap_or("UNKWN".to_string()).parse()?,
-                suite: d.5.unwrap_or_default(), // TODO: behaviour change, was always present, may not be now
-                artifact_url: d.6,
+                version: d.1,
+                distro: d.2,
+                suite: d.3.unwrap_or_default(),
+                architecture: d.4,
+                artifact_url: d.5,
+                checksum: d.6,
                 build_id: d.7,
                 built_at: d.8,
-                has_diffoscope: d.9.unwrap_or_default(),
-                has_attestation: d.10.unwrap_or_default(),
-            };
-
-            Ok(release)
+                status: d.9.unwrap_or("UNKWN".to_string()).parse()?,
+                rebuilt_checksum: d.10,
+                attestation_url,
+            })
         })
-        .collect::<Result<Vec<PkgRelease>>>()?;
+        .collect::<Result<Vec<PkgArtifactChecksums>>>()?;
 
-    Ok(builder.json(mapped))
+    Ok(HttpResponse::Ok().json(mapped))
+}
+
+#[get("/suites")]
+pub async fn list_suites(pool: web::Data<Pool>) -> web::Result<impl Responder> {
+    let suites = db::run(&pool, |connection| {
+        let counts = binary_packages::table
+            .inner_join(source_packages::table)
+            .inner_join(build_inputs::table)
+            .filter(source_packages::removed_at.is_null())
+            .group_by((
+                source_packages::distribution,
+                source_packages::release,
+                source_packages::component,
+                build_inputs::architecture,
+            ))
+            .select((
+                source_packages::distribution,
+                source_packages::release,
+                source_packages::component,
+                build_inputs::architecture,
+                diesel::dsl::count(binary_packages::id),
+            ))
+            .load::<(String, Option<String>, Option<String>, String, i64)>(connection)?;
+
+        let syncs = sync_revisions::table
+            .select((
+                sync_revisions::distribution,
+                sync_revisions::release,
+                sync_revisions::component,
+                sync_revisions::architecture,
+                sync_revisions::updated_at,
+                sync_revisions::source,
+            ))
+            .load::<(
+                String,
+                Option<String>,
+                Option<String>,
+                String,
+                NaiveDateTime,
+                Option<String>,
+            )>(connection)?;
+
+        // a suite may have been synced without any packages, or have packages from before syncs were recorded
+        let mut suites = BTreeMap::new();
+        for (distro, release, component, architecture, packages) in counts {
+            suites.insert(
+                (distro, release, component, architecture),
+                (packages, None, None),
+            );
+        }
+        for (distro, release, component, architecture, updated_at, source) in syncs {
+            let suite = suites
+                .entry((distro, release, component, architecture))
+                .or_insert((0, None, None));
+            suite.1 = Some(updated_at);
+            suite.2 = source;
+        }
+
+        let suites = suites
+            .into_iter()
+            .map(
+                |(
+                    (distro, release, component, architecture),
+                    (packages, last_sync, sync_source),
+                )| {
+                    SuiteInfo {
+                        distro,
+                        release,
+                        suite: component.unwrap_or_default(),
+                        architecture,
+                        packages,
+                        last_sync,
+                        sync_source,
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        Ok(suites)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(suites))
 }
 
 #[post("/queue/list")]
@@ -267,27 +572,117 @@ pub async fn list_queue(
     query: web::Json<ListQueue>,
     pool: web::Data<Pool>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let mut sql = queue::table
-        .order_by((queue::priority, queue::queued_at, queue::id))
-        .into_boxed();
-
-    if let Some(limit) = &query.limit {
-        sql = sql.limit(*limit);
-    }
+    let limit = query.limit;
+    let queue = db::run(&pool, move |connection| {
+        let mut sql = queue::table
+            .order_by((queue::priority, queue::queued_at, queue::id))
+            .into_boxed();
+
+        if let Some(limit) = limit {
+            sql = sql.limit(limit);
+        }
 
-    let queue = sql
-        .load::<Queued>(connection.as_mut())
-        .map_err(Error::from)?
-        .into_iter()
-        .map(|x| into_queue_item(x, connection.as_mut()))
-        .collect::<Result<Vec<QueueItem>>>()?;
+        sql.load::<Queued>(connection)?
+            .into_iter()
+            .map(|x| into_queue_item(x, connection))
+            .collect::<Result<Vec<QueueItem>>>()
+    })
+    .await?;
 
     let now = Utc::now().naive_utc();
     Ok(HttpResponse::Ok().json(QueueList { now, queue }))
 }
 
+/// The report bundle of the latest rebuild of a package, see `/api/v1/builds/{id}/bundle.tar.gz`
+#[get("/pkgs/{name}/bundle.tar.gz")]
+pub async fn get_pkg_bundle(
+    name: web::Path<String>,
+    query: web::Query<PkgBundleQuery>,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
+) -> web::Result<impl Responder> {
+    let name = name.into_inner();
+    let query = query.into_inner();
+
+    let build_id = db::run(&pool, move |connection| {
+        let build_id = filter_binary_packages_by(
+            Some(&name),
+            query.distro.as_deref(),
+            None,
+            query.suite.as_deref(),
+            query.architecture.as_deref(),
+            None,
+        )
+        .filter(r1.field(rebuilds::id).is_not_null())
+        .select(r1.field(rebuilds::id).nullable())
+        .order_by(r1.field(rebuilds::built_at).desc())
+        .first::<Option<i32>>(connection)
+        .optional()?
+        .flatten();
+        Ok(build_id)
+    })
+    .await?;
+
+    let Some(build_id) = build_id else {
+        return Ok(not_found());
+    };
+
+    v1::build_bundle(&cfg, &pool, &storage, build_id).await
+}
+
+#[get("/queue/position")]
+pub async fn get_queue_position(
+    query: web::Query<QueuePositionQuery>,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    let query = query.into_inner();
+
+    let positions = db::run(&pool, move |connection| {
+        let mut sql = queue::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(source_packages::name.eq(query.name))
+            .into_boxed();
+
+        if let Some(distro) = query.distro {
+            sql = sql.filter(source_packages::distribution.eq(distro));
+        }
+        if let Some(suite) = query.suite {
+            sql = sql.filter(source_packages::component.eq(suite));
+        }
+        if let Some(architecture) = query.architecture {
+            sql = sql.filter(build_inputs::architecture.eq(architecture));
+        }
+
+        let queued = sql
+            .order_by(queue::id)
+            .limit(v1::QUEUE_POSITION_LIMIT)
+            .select(Queued::as_select())
+            .load::<Queued>(connection)?;
+
+        let now = Utc::now();
+        queued
+            .into_iter()
+            .map(|queued| {
+                let job = v1::queue_base()
+                    .filter(queue::id.eq(queued.id))
+                    .get_result(connection)?;
+                let position = v1::get_job_position(connection, job, now)?;
+
+                Ok(QueuePosition {
+                    item: into_queue_item(queued, connection)?,
+                    ahead: position.ahead,
+                    tied: position.tied,
+                    estimated_start: position.estimated_start,
+                })
+            })
+            .collect::<Result<Vec<QueuePosition>>>()
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(positions))
+}
+
 pub fn into_queue_item(queued: Queued, connection: &mut SqliteConnection) -> Result<QueueItem> {
     let build_input = build_inputs::table
         .filter(build_inputs::id.eq(queued.build_input_id))
@@ -404,35 +799,29 @@ pub async fn ping_build(
     Ok(HttpResponse::NotImplemented())
 }
 
-#[post("/build/report")]
-pub async fn report_build(
-    _req: HttpRequest,
-    _cfg: web::Data<Config>,
-    _privkey: web::Data<Arc<PrivateKey>>,
-    _report: web::Json<BuildReport>,
-    _pool: web::Data<Pool>,
-) -> web::Result<impl Responder> {
-    // v0 is only available as a read-only API for backwards compatibility
-    Ok(HttpResponse::NotImplemented())
-}
-
 #[get("/builds/{id}/log")]
 pub async fn get_build_log(
     req: HttpRequest,
     id: web::Path<i32>,
     pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    // get log of the latest rebuild - v0 has no concept of multiple successful builds
-    let build_log = rebuild_artifacts::table
-        .filter(rebuild_artifacts::id.eq(id.into_inner()))
-        .inner_join(rebuilds::table.inner_join(build_logs::table))
-        .select(build_logs::build_log)
-        .order_by(rebuilds::built_at.desc())
-        .first::<Vec<u8>>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?;
+    let id = id.into_inner();
+    let (build_log, blob_key) = db::run(&pool, move |connection| {
+        // get log of the latest rebuild - v0 has no concept of multiple successful builds
+        let build_log = rebuild_artifacts::table
+            .filter(rebuild_artifacts::id.eq(id))
+            .inner_join(rebuilds::table.inner_join(build_logs::table))
+            .select((build_logs::build_log, build_logs::blob_key))
+            .order_by(rebuilds::built_at.desc())
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)
+            .optional()?
+            .unwrap_or_default();
+        Ok(build_log)
+    })
+    .await?;
+
+    let build_log = storage.load(build_log, blob_key).await?;
 
     if let Some(build_log) = build_log {
         forward_compressed_data(req, "text/plain; charset=utf-8", build_log).await
@@ -448,18 +837,27 @@ pub async fn get_attestation(
     _cfg: web::Data<Config>,
     _privkey: web::Data<Arc<PrivateKey>>,
     pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    // get log of the first artifact - v0 has no concept of separate attestation logs
-    let attestation = rebuild_artifacts::table
-        .filter(rebuild_artifacts::rebuild_id.eq(id.into_inner()))
-        .inner_join(attestation_logs::table)
-        .select(attestation_logs::attestation_log)
-        .order_by(rebuild_artifacts::id.asc())
-        .first::<Vec<u8>>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?;
+    let id = id.into_inner();
+    let (attestation, blob_key) = db::run(&pool, move |connection| {
+        // get log of the first artifact - v0 has no concept of separate attestation logs
+        let attestation = rebuild_artifacts::table
+            .filter(rebuild_artifacts::rebuild_id.eq(id))
+            .inner_join(attestation_logs::table)
+            .select((
+                attestation_logs::attestation_log,
+                attestation_logs::blob_key,
+            ))
+            .order_by(rebuild_artifacts::id.asc())
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)
+            .optional()?
+            .unwrap_or_default();
+        Ok(attestation)
+    })
+    .await?;
+
+    let attestation = storage.load(attestation, blob_key).await?;
 
     if let Some(attestation) = attestation {
         // v0 used to transparently sign attestations here, but for now v0 is entirely read-only
@@ -474,17 +872,23 @@ pub async fn get_diffoscope(
     req: HttpRequest,
     id: web::Path<i32>,
     pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let (diffoscope, blob_key) = db::run(&pool, move |connection| {
+        let diffoscope = rebuild_artifacts::table
+            .filter(rebuild_artifacts::rebuild_id.eq(id))
+            .inner_join(diffoscope_logs::table)
+            .select((diffoscope_logs::diffoscope_log, diffoscope_logs::blob_key))
+            .order_by(rebuild_artifacts::id.asc())
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)
+            .optional()?
+            .unwrap_or_default();
+        Ok(diffoscope)
+    })
+    .await?;
 
-    let diffoscope = rebuild_artifacts::table
-        .filter(rebuild_artifacts::rebuild_id.eq(id.into_inner()))
-        .inner_join(diffoscope_logs::table)
-        .select(diffoscope_logs::diffoscope_log)
-        .order_by(rebuild_artifacts::id.asc())
-        .first::<Vec<u8>>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?;
+    let diffoscope = storage.load(diffoscope, blob_key).await?;
 
     if let Some(diffoscope) = diffoscope {
         forward_compressed_data(req, "text/plain; charset=utf-8", diffoscope).await
@@ -498,17 +902,19 @@ pub async fn get_dashboard(
     pool: web::Data<Pool>,
     lock: web::Data<Arc<RwLock<DashboardState>>>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
     let stale = {
         let state = lock.read().unwrap();
         !state.is_fresh()
     };
 
     if stale {
-        let mut state = lock.write().unwrap();
-        debug!("Updating cached dashboard");
-        state.update(connection.as_mut())?;
+        let lock = lock.clone().into_inner();
+        db::run(&pool, move |connection| {
+            let mut state = lock.write().unwrap();
+            debug!("Updating cached dashboard");
+            state.update(connection)
+        })
+        .await?;
     }
 
     let state = lock.read().unwrap();
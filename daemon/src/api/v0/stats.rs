@@ -0,0 +1,299 @@
+use crate::api::v0::{auth, forbidden, not_found};
+use crate::config::Config;
+use crate::db::{self, Pool};
+use crate::schema::*;
+use crate::web;
+use actix_web::{HttpRequest, HttpResponse, Responder, get};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::dsl::{exists, select};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use rebuilderd_common::api::v0::*;
+use rebuilderd_common::errors::*;
+
+type StatsHistoryRow = (
+    NaiveDate,
+    String,
+    Option<String>,
+    Option<String>,
+    i32,
+    i32,
+    i32,
+    i32,
+);
+
+fn load_history(
+    connection: &mut SqliteConnection,
+    query: &StatsHistoryQuery,
+) -> Result<Vec<StatsHistoryEntry>> {
+    let mut sql = stats_history::table.into_boxed();
+
+    if let Some(distro) = &query.distro {
+        sql = sql.filter(stats_history::distribution.eq(distro));
+    }
+    if let Some(suite) = &query.suite {
+        sql = sql.filter(stats_history::component.eq(suite));
+    }
+    if let Some(since) = query.since {
+        sql = sql.filter(stats_history::date.ge(since));
+    }
+
+    let rows = sql
+        .select((
+            stats_history::date,
+            stats_history::distribution,
+            stats_history::release,
+            stats_history::component,
+            stats_history::good,
+            stats_history::bad,
+            stats_history::unknown,
+            stats_history::expired,
+        ))
+        .order_by((
+            stats_history::date,
+            stats_history::distribution,
+            stats_history::release,
+            stats_history::component,
+        ))
+        .load::<StatsHistoryRow>(connection)?;
+
+    let entries = rows
+        .into_iter()
+        .map(
+            |(date, distro, release, suite, good, bad, unknown, expired)| StatsHistoryEntry {
+                date,
+                distro,
+                release,
+                suite,
+                good: good as usize,
+                unknown: unknown as usize,
+                bad: bad as usize,
+                expired: expired as usize,
+            },
+        )
+        .collect();
+
+    Ok(entries)
+}
+
+#[get("/stats/history")]
+pub async fn get_stats_history(
+    query: web::Query<StatsHistoryQuery>,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    let query = query.into_inner();
+    let history = db::run(&pool, move |connection| load_history(connection, &query)).await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+/// build log id, status, start and end of a build a worker reported
+type WorkerBuild = (
+    i32,
+    Option<String>,
+    Option<NaiveDateTime>,
+    Option<NaiveDateTime>,
+);
+
+fn summarize(builds: &[WorkerBuild]) -> WorkerStats {
+    let mut stats = WorkerStats {
+        builds: builds.len(),
+        ..Default::default()
+    };
+
+    let mut good = 0;
+    let mut durations = Vec::new();
+    for (_, status, started_at, built_at) in builds {
+        match status.as_deref() {
+            Some("GOOD") => good += 1,
+            status => {
+                let status = status.unwrap_or("UNKWN").to_string();
+                *stats.failures.entry(status).or_default() += 1;
+            }
+        }
+
+        if let (Some(started_at), Some(built_at)) = (started_at, built_at) {
+            durations.push((*built_at - *started_at).num_milliseconds() as f64 / 1000.0);
+        }
+    }
+
+    if !builds.is_empty() {
+        stats.success_ratio = good as f64 / builds.len() as f64;
+    }
+    if !durations.is_empty() {
+        stats.average_duration = Some(durations.iter().sum::<f64>() / durations.len() as f64);
+    }
+
+    stats
+}
+
+fn load_worker_stats(
+    connection: &mut SqliteConnection,
+    worker_id: i32,
+    query: &WorkerStatsQuery,
+) -> Result<Option<WorkerStats>> {
+    let worker_exists = select(exists(workers::table.filter(workers::id.eq(worker_id))))
+        .get_result::<bool>(connection)?;
+    if !worker_exists {
+        return Ok(None);
+    }
+
+    let mut sql = rebuilds::table
+        .filter(rebuilds::worker_id.eq(worker_id))
+        .into_boxed();
+    if let Some(since) = query.since {
+        sql = sql.filter(rebuilds::built_at.ge(since));
+    }
+
+    // a result is recorded for every identical build input, but they all share the build log
+    let builds = sql
+        .select((
+            rebuilds::build_log_id,
+            rebuilds::status,
+            rebuilds::started_at,
+            rebuilds::built_at,
+        ))
+        .distinct()
+        .load::<WorkerBuild>(connection)?;
+
+    Ok(Some(summarize(&builds)))
+}
+
+#[get("/workers/{id}/stats")]
+pub async fn get_worker_stats(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    id: web::Path<i32>,
+    query: web::Query<WorkerStatsQuery>,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req).is_err() {
+        return Ok(forbidden());
+    }
+
+    let id = id.into_inner();
+    let query = query.into_inner();
+    let stats = db::run(&pool, move |connection| {
+        load_worker_stats(connection, id, &query)
+    })
+    .await?;
+
+    if let Some(stats) = stats {
+        Ok(HttpResponse::Ok().json(stats))
+    } else {
+        Ok(not_found())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NewStatsHistory;
+
+    fn snapshot(date: NaiveDate, component: &str, good: i32) -> NewStatsHistory {
+        NewStatsHistory {
+            date,
+            distribution: "archlinux".to_string(),
+            release: None,
+            component: Some(component.to_string()),
+            good,
+            bad: 1,
+            unknown: 0,
+            expired: 0,
+        }
+    }
+
+    #[test]
+    fn test_load_history() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let day1 = NaiveDate::from_ymd_opt(2025, 12, 27).unwrap();
+        let day2 = NaiveDate::from_ymd_opt(2025, 12, 28).unwrap();
+
+        NewStatsHistory::replace_day(
+            day1,
+            &[snapshot(day1, "core", 5), snapshot(day1, "extra", 50)],
+            &mut connection,
+        )
+        .unwrap();
+        NewStatsHistory::replace_day(day2, &[snapshot(day2, "core", 6)], &mut connection).unwrap();
+        // a later snapshot of the same day replaces the earlier one
+        NewStatsHistory::replace_day(day2, &[snapshot(day2, "core", 7)], &mut connection).unwrap();
+
+        let history = load_history(&mut connection, &StatsHistoryQuery::default()).unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| (entry.date, entry.suite.as_deref(), entry.good))
+                .collect::<Vec<_>>(),
+            vec![
+                (day1, Some("core"), 5),
+                (day1, Some("extra"), 50),
+                (day2, Some("core"), 7),
+            ]
+        );
+
+        let history = load_history(
+            &mut connection,
+            &StatsHistoryQuery {
+                distro: Some("archlinux".to_string()),
+                suite: Some("core".to_string()),
+                since: Some(day2),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            history,
+            vec![StatsHistoryEntry {
+                date: day2,
+                distro: "archlinux".to_string(),
+                release: None,
+                suite: Some("core".to_string()),
+                good: 7,
+                unknown: 0,
+                bad: 1,
+                expired: 0,
+            }]
+        );
+    }
+
+    fn build(log_id: i32, status: &str, duration: Option<i64>) -> WorkerBuild {
+        let built_at = NaiveDate::from_ymd_opt(2025, 12, 28)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        (
+            log_id,
+            Some(status.to_string()),
+            duration.map(|seconds| built_at - chrono::Duration::seconds(seconds)),
+            Some(built_at),
+        )
+    }
+
+    #[test]
+    fn test_summarize() {
+        let stats = summarize(&[
+            build(1, "GOOD", Some(60)),
+            build(2, "BAD", Some(120)),
+            build(3, "BAD", None),
+            build(4, "FAIL", Some(30)),
+        ]);
+        assert_eq!(
+            stats,
+            WorkerStats {
+                builds: 4,
+                success_ratio: 0.25,
+                average_duration: Some(70.0),
+                failures: [("BAD".to_string(), 2), ("FAIL".to_string(), 1)]
+                    .into_iter()
+                    .collect(),
+            }
+        );
+
+        assert_eq!(summarize(&[]), WorkerStats::default());
+    }
+
+    #[test]
+    fn test_load_worker_stats_unknown_worker() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let stats = load_worker_stats(&mut connection, 1, &WorkerStatsQuery::default()).unwrap();
+        assert_eq!(stats, None);
+    }
+}
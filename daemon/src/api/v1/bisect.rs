@@ -0,0 +1,166 @@
+use crate::api::v1::util::auth;
+use crate::config::Config;
+use crate::db::{self, Pool};
+use crate::models::{NewBisect, NewQueued};
+use crate::schema::{binary_packages, bisect_steps, bisects, build_inputs, queue, source_packages};
+use crate::web;
+use actix_web::{HttpRequest, HttpResponse, Responder, get, post};
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::{exists, select};
+use diesel::{
+    Connection, OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection, SqliteExpressionMethods,
+};
+use rebuilderd_common::api::v1::{ApiKeyScope, Bisect, BisectRequest, BisectStep, Priority};
+use rebuilderd_common::errors::*;
+
+enum Start {
+    UnknownPackage,
+    AlreadyQueued,
+    Started(i32),
+}
+
+fn start_bisect(connection: &mut SqliteConnection, request: BisectRequest) -> Result<Start> {
+    connection.transaction(|conn| {
+        let Some(build_input_id) = binary_packages::table
+            .filter(binary_packages::id.is(request.binary_package_id))
+            .select(binary_packages::build_input_id)
+            .get_result::<i32>(conn)
+            .optional()?
+        else {
+            return Ok(Start::UnknownPackage);
+        };
+
+        // the steps are built by a single queue entry, it can't share it with a regular rebuild
+        let queued = select(exists(
+            queue::table.filter(queue::build_input_id.is(build_input_id)),
+        ))
+        .get_result::<bool>(conn)?;
+        if queued {
+            return Ok(Start::AlreadyQueued);
+        }
+
+        let now = Utc::now().naive_utc();
+        let (bisect_id, first_step_id) = NewBisect {
+            build_input_id,
+            created_at: now,
+        }
+        .insert(conn)?;
+
+        NewQueued {
+            build_input_id,
+            priority: request.priority.unwrap_or(Priority::manual()),
+            queued_at: now,
+            build_architecture: None,
+            bisect_step_id: Some(first_step_id),
+            reason: None,
+        }
+        .upsert(conn)?;
+
+        Ok(Start::Started(bisect_id))
+    })
+}
+
+fn load_bisect(connection: &mut SqliteConnection, id: i32) -> Result<Option<Bisect>> {
+    let Some((name, version, distribution, architecture, backend, created_at, finished_at)) =
+        bisects::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(bisects::id.is(id))
+            .select((
+                source_packages::name,
+                source_packages::version,
+                source_packages::distribution,
+                build_inputs::architecture,
+                build_inputs::backend,
+                bisects::created_at,
+                bisects::finished_at,
+            ))
+            .get_result::<(
+                String,
+                String,
+                String,
+                String,
+                String,
+                NaiveDateTime,
+                Option<NaiveDateTime>,
+            )>(connection)
+            .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let steps = bisect_steps::table
+        .filter(bisect_steps::bisect_id.is(id))
+        .order_by(bisect_steps::position)
+        .select((
+            bisect_steps::variation,
+            bisect_steps::status,
+            bisect_steps::built_at,
+        ))
+        .load::<BisectStep>(connection)?;
+    let flips = BisectStep::flips(&steps);
+
+    Ok(Some(Bisect {
+        id,
+        name,
+        version,
+        distribution,
+        architecture,
+        backend,
+        created_at,
+        finished_at,
+        steps,
+        flips,
+    }))
+}
+
+#[post("")]
+pub async fn request_bisect(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: web::Json<BisectRequest>,
+) -> web::Result<impl Responder> {
+    if auth::admin_any(
+        &cfg,
+        &req,
+        &pool,
+        &[ApiKeyScope::Queue, ApiKeyScope::Operator],
+    )
+    .await
+    .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let request = request.into_inner();
+    let start = db::run(&pool, move |connection| start_bisect(connection, request)).await?;
+
+    match start {
+        Start::UnknownPackage => Ok(HttpResponse::NotFound().finish()),
+        Start::AlreadyQueued => Ok(HttpResponse::Conflict()
+            .body("The package is already queued, drop it from the queue first")),
+        Start::Started(id) => {
+            let bisect = db::run(&pool, move |connection| load_bisect(connection, id))
+                .await?
+                .context("Bisect disappeared after it was created")
+                .map_err(web::Error::from)?;
+            info!(
+                "Started bisect #{} of {} {}",
+                bisect.id, bisect.name, bisect.version
+            );
+            Ok(HttpResponse::Ok().json(bisect))
+        }
+    }
+}
+
+#[get("/{id}")]
+pub async fn get_bisect(pool: web::Data<Pool>, id: web::Path<i32>) -> web::Result<impl Responder> {
+    let id = id.into_inner();
+    let bisect = db::run(&pool, move |connection| load_bisect(connection, id)).await?;
+
+    if let Some(bisect) = bisect {
+        Ok(HttpResponse::Ok().json(bisect))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
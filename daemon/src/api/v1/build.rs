@@ -1,38 +1,47 @@
 use crate::api::forward_compressed_data;
-use crate::api::v1::util::auth;
-use crate::api::v1::util::filters::{IntoOriginFilter, IntoSourceIdentityFilter};
+use crate::api::v1::util::auth::AuthenticatedWorker;
+use crate::api::v1::util::bundle::{self, BundleFile, sanitize_filename};
+use crate::api::v1::util::filters::{IntoFilter, IntoOriginFilter, IntoSourceIdentityFilter};
 use crate::api::v1::util::friends::{
     get_build_input_friends, get_largest_retry_count_among_friends,
     mark_build_input_friends_as_non_retriable,
 };
+use crate::api::v1::util::json_schema::SchemaJson;
 use crate::api::v1::util::pagination::PaginateDsl;
 use crate::config::Config;
-use crate::db::Pool;
+use crate::db::{self, Pool};
 use crate::models::{
-    NewAttestationLog, NewBuildLog, NewDiffoscopeLog, NewQueued, NewRebuild, NewRebuildArtifact,
-    Queued,
+    BisectStepResult, NewArtifactVerdict, NewAttestationLog, NewBuildDuration, NewBuildLog,
+    NewCrossRebuild, NewDiffoscopeLog, NewProvisionalRebuild, NewQueued, NewRebuild,
+    NewRebuildArtifact, NewStatusTransition, Queued, Subscription,
 };
+use crate::notify::{Notification, Recipients};
 use crate::schema::{
-    attestation_logs, build_inputs, build_logs, diffoscope_logs, queue, rebuild_artifacts,
-    rebuilds, source_packages,
+    attestation_logs, binary_packages, build_inputs, build_logs, diffoscope_logs, queue,
+    rebuild_artifacts, rebuilds, source_packages, status_transitions, workers,
 };
-use crate::{attestation, web};
-use actix_web::{HttpRequest, HttpResponse, Responder, get, post};
-use chrono::{Duration, Utc};
+use crate::storage::{Storage, StoredBlob};
+use crate::{attestation, diff_causes, verification, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, get};
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::{
-    ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, NullableExpressionMethods,
+    OptionalExtension, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection,
     SqliteExpressionMethods, dsl::update,
 };
 use in_toto::crypto::PrivateKey;
 use rebuilderd_common::api;
 use rebuilderd_common::api::v1::{
-    BuildStatus, OriginFilter, Page, Priority, Rebuild, RebuildReport, ResultPage,
-    SourceIdentityFilter,
+    ArtifactStatus, BuildStatus, JobPayload, OriginFilter, Page, Priority, Rebuild, RebuildReport,
+    ResultPage, SourceIdentityFilter, StatusTransition, TagFilter, TransitionFilter,
+    TransitionKind, VerificationMismatch, WorkerCapability,
 };
-use rebuilderd_common::errors::Error;
+use rebuilderd_common::errors::{Error, Result, warn};
+use rebuilderd_common::http;
 use rebuilderd_common::utils::{is_zstd_compressed, zstd_compress};
-use std::collections::HashMap;
+use serde::Serialize;
 use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 
 #[diesel::dsl::auto_type]
@@ -52,6 +61,9 @@ fn builds_base() -> _ {
             rebuilds::started_at,
             rebuilds::built_at,
             rebuilds::status,
+            rebuilds::environment_fingerprint,
+            rebuilds::downloaded_bytes,
+            rebuilds::reason,
         ))
 }
 
@@ -61,232 +73,1017 @@ pub async fn get_builds(
     page: web::Query<Page>,
     origin_filter: web::Query<OriginFilter>,
     source_identity_filter: web::Query<SourceIdentityFilter>,
+    tag_filter: web::Query<TagFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let records = builds_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
-        .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
-        )
-        .paginate(page.into_inner())
-        .load::<Rebuild>(connection.as_mut())
-        .map_err(Error::from)?;
-
-    let total = builds_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
-        .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
-        )
-        .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+    let page = page.into_inner();
+    let origin_filter = origin_filter.into_inner();
+    let source_identity_filter = source_identity_filter.into_inner();
+    let tag_filter = tag_filter.into_inner();
+
+    let builds = db::run(&pool, move |connection| {
+        let records = builds_base()
+            .filter(
+                origin_filter
+                    .clone()
+                    .into_filter(build_inputs::architecture),
+            )
+            .filter(
+                source_identity_filter
+                    .clone()
+                    .into_filter(source_packages::name, source_packages::version),
+            )
+            .filter(tag_filter.clone().into_filter())
+            .paginate(page)
+            .load::<Rebuild>(connection)?;
+
+        let total = builds_base()
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .filter(
+                source_identity_filter.into_filter(source_packages::name, source_packages::version),
+            )
+            .filter(tag_filter.into_filter())
+            .count()
+            .get_result::<i64>(connection)?;
+
+        Ok(ResultPage { total, records })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(builds))
+}
+
+/// Decides whether a reported status should be recorded as FLAKY instead. A build input becomes flaky if its most
+/// recent outcomes (including the one being reported) flipped between GOOD and BAD at least `threshold` times. Flips
+/// are counted over the outcomes the workers reported, not the recorded status, so a package stays FLAKY only until it
+/// produced `threshold + 1` consistent results in a row.
+fn classify_flaky(
+    connection: &mut SqliteConnection,
+    build_input_id: i32,
+    status: BuildStatus,
+    threshold: i32,
+) -> QueryResult<BuildStatus> {
+    if threshold < 1 || !matches!(status, BuildStatus::Good | BuildStatus::Bad) {
+        return Ok(status);
+    }
+
+    let history = rebuilds::table
+        .filter(rebuilds::build_input_id.is(build_input_id))
+        .filter(rebuilds::outcome.eq_any(vec![BuildStatus::Good, BuildStatus::Bad]))
+        .order_by((rebuilds::built_at.desc(), rebuilds::id.desc()))
+        .limit(threshold as i64)
+        .select((rebuilds::status, rebuilds::outcome))
+        .load::<(Option<BuildStatus>, Option<BuildStatus>)>(connection)?;
+
+    let was_flaky = history
+        .first()
+        .is_some_and(|(status, _)| *status == Some(BuildStatus::Flaky));
+    let outcomes = std::iter::once(status.clone())
+        .chain(history.into_iter().filter_map(|(_, outcome)| outcome))
+        .collect::<Vec<_>>();
+    let flips = outcomes
+        .windows(2)
+        .filter(|pair| pair[0] != pair[1])
+        .count();
+
+    let flaky = if was_flaky {
+        flips > 0 || outcomes.len() <= threshold as usize
+    } else {
+        flips >= threshold as usize
+    };
+
+    if flaky {
+        Ok(BuildStatus::Flaky)
+    } else {
+        Ok(status)
+    }
+}
+
+/// The latest GOOD or BAD result of a build input and the version it was recorded for. FLAKY results are skipped, the
+/// package is compared with the last verdict it had before it started flipping.
+fn latest_verdict(
+    connection: &mut SqliteConnection,
+    build_input_id: i32,
+) -> QueryResult<Option<(String, BuildStatus)>> {
+    let verdict = rebuilds::table
+        .inner_join(build_inputs::table.inner_join(source_packages::table))
+        .filter(rebuilds::build_input_id.is(build_input_id))
+        .filter(rebuilds::status.eq_any(vec![BuildStatus::Good, BuildStatus::Bad]))
+        .order_by((rebuilds::built_at.desc(), rebuilds::id.desc()))
+        .select((source_packages::version, rebuilds::status))
+        .first::<(String, Option<BuildStatus>)>(connection)
+        .optional()?;
+
+    Ok(verdict.and_then(|(version, status)| Some((version, status?))))
+}
+
+/// Compares a GOOD or BAD result with the previous result of the same version, or with the last result of the
+/// previous version in the same suite if this version wasn't rebuilt before. Returns the kind of the transition, the
+/// previous version and its status.
+fn detect_transition(
+    connection: &mut SqliteConnection,
+    build_input_id: i32,
+    status: &BuildStatus,
+) -> QueryResult<Option<(TransitionKind, String, BuildStatus)>> {
+    if !matches!(status, BuildStatus::Good | BuildStatus::Bad) {
+        return Ok(None);
+    }
+
+    let (previous, same_version) =
+        if let Some(verdict) = latest_verdict(connection, build_input_id)? {
+            (Some(verdict), true)
+        } else {
+            let (name, version, distribution, release, architecture) = build_inputs::table
+                .inner_join(source_packages::table)
+                .filter(build_inputs::id.is(build_input_id))
+                .select((
+                    source_packages::name,
+                    source_packages::version,
+                    source_packages::distribution,
+                    source_packages::release,
+                    build_inputs::architecture,
+                ))
+                .get_result::<(String, String, String, Option<String>, String)>(connection)?;
+
+            // the most recently imported build input of an older version
+            let previous_input = build_inputs::table
+                .inner_join(source_packages::table)
+                .filter(source_packages::name.eq(name))
+                .filter(source_packages::version.ne(version))
+                .filter(source_packages::distribution.eq(distribution))
+                .filter(source_packages::release.is(release))
+                .filter(build_inputs::architecture.eq(architecture))
+                .filter(build_inputs::id.lt(build_input_id))
+                .order_by(build_inputs::id.desc())
+                .select(build_inputs::id)
+                .first::<i32>(connection)
+                .optional()?;
+
+            let previous = match previous_input {
+                Some(previous_input) => latest_verdict(connection, previous_input)?,
+                None => None,
+            };
+            (previous, false)
+        };
+
+    let Some((previous_version, previous_status)) = previous else {
+        return Ok(None);
+    };
+
+    let kind = match (&previous_status, status) {
+        (BuildStatus::Good, BuildStatus::Bad) if same_version => TransitionKind::Regression,
+        (BuildStatus::Good, BuildStatus::Bad) => TransitionKind::NewVersionUnreproducible,
+        (BuildStatus::Bad, BuildStatus::Good) => TransitionKind::Fixed,
+        _ => return Ok(None),
+    };
+
+    Ok(Some((kind, previous_version, previous_status)))
+}
+
+/// The duration of a completed build is the estimate for the next build of the package. Failed builds are left out,
+/// they may have stopped long before the build would have finished.
+fn record_build_duration(
+    connection: &mut SqliteConnection,
+    queued: &Queued,
+    status: &BuildStatus,
+    built_at: NaiveDateTime,
+) -> Result<()> {
+    if !matches!(
+        status,
+        BuildStatus::Good | BuildStatus::Bad | BuildStatus::Flaky
+    ) {
+        return Ok(());
+    }
+    let Some(started_at) = queued.started_at else {
+        return Ok(());
+    };
+
+    let (distribution, name, architecture) = build_inputs::table
+        .inner_join(source_packages::table)
+        .filter(build_inputs::id.eq(queued.build_input_id))
+        .select((
+            source_packages::distribution,
+            source_packages::name,
+            build_inputs::architecture,
+        ))
+        .get_result::<(String, String, String)>(connection)?;
+
+    NewBuildDuration {
+        distribution,
+        name,
+        architecture,
+        seconds: (built_at - started_at).num_seconds().max(0),
+        measured: true,
+    }
+    .upsert(connection)
+}
+
+/// Remember the verdicts of artifacts that were published with a checksum. A later sync of the same artifacts reuses
+/// them instead of queueing another build, as long as the rebuilder script didn't change in the meantime.
+fn record_artifact_verdicts(
+    connection: &mut SqliteConnection,
+    build_input_id: i32,
+    scripts: &BTreeMap<String, String>,
+    artifacts: &[(String, ArtifactStatus, i32)],
+    verified_at: NaiveDateTime,
+) -> Result<()> {
+    let backend = build_inputs::table
+        .filter(build_inputs::id.eq(build_input_id))
+        .select(build_inputs::backend)
+        .get_result::<String>(connection)?;
+    let Some(script_version) = scripts.get(&backend) else {
+        return Ok(());
+    };
+
+    let checksums = binary_packages::table
+        .filter(binary_packages::build_input_id.eq(build_input_id))
+        .filter(binary_packages::checksum.is_not_null())
+        .select((
+            binary_packages::name,
+            binary_packages::checksum.assume_not_null(),
+        ))
+        .load::<(String, String)>(connection)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    for (name, status, rebuild_artifact_id) in artifacts {
+        if !matches!(status, ArtifactStatus::Good | ArtifactStatus::Bad) {
+            continue;
+        }
+        let Some(checksum) = checksums.get(name) else {
+            continue;
+        };
+
+        NewArtifactVerdict {
+            checksum: checksum.clone(),
+            script_version: script_version.clone(),
+            status: status.as_str().to_string(),
+            rebuild_artifact_id: Some(*rebuild_artifact_id),
+            origin: None,
+            verified_at,
+        }
+        .upsert(connection)?;
+    }
+
+    Ok(())
+}
+
+/// Compress a log unless the worker already did, and hand it to the configured storage
+async fn store_log(storage: &Storage, kind: &str, log: Vec<u8>) -> Result<StoredBlob> {
+    let encoded = if is_zstd_compressed(&log) {
+        log
+    } else {
+        zstd_compress(&log[..]).await?
+    };
+    storage.store(kind, encoded).await
+}
+
+/// Blobs are written before the rows referencing them, they are removed again if the rows couldn't be written
+async fn discard_on_error<T>(storage: &Storage, keys: &[String], result: Result<T>) -> Result<T> {
+    if result.is_err() {
+        storage.discard(keys).await;
+    }
+    result
+}
+
+/// Notifications about a recorded transition, they are sent once the report is stored
+#[derive(Debug, Default)]
+struct TransitionNotifications {
+    /// A version that was reproduced before doesn't reproduce anymore, this is worth waking an operator up for
+    regression: Option<Notification>,
+    /// Sent to whoever subscribed to the package or its maintainer with an api key
+    subscribers: Option<(Recipients, Notification)>,
+}
+
+fn transition_notifications(
+    connection: &mut SqliteConnection,
+    build_input_id: i32,
+    transition: &(TransitionKind, String, BuildStatus),
+    status: &BuildStatus,
+    worker: &str,
+) -> Result<TransitionNotifications> {
+    let (kind, previous_version, previous_status) = transition;
+    let (name, version, distribution, release, architecture, maintainer) = build_inputs::table
+        .inner_join(source_packages::table)
+        .filter(build_inputs::id.is(build_input_id))
+        .select((
+            source_packages::name,
+            source_packages::version,
+            source_packages::distribution,
+            source_packages::release,
+            build_inputs::architecture,
+            source_packages::maintainer,
+        ))
+        .get_result::<(
+            String,
+            String,
+            String,
+            Option<String>,
+            String,
+            Option<String>,
+        )>(connection)?;
 
-    Ok(HttpResponse::Ok().json(ResultPage { total, records }))
+    let recipients =
+        Subscription::recipients(&name, maintainer.as_deref(), &distribution, connection)?;
+
+    let suite = match release {
+        Some(release) => format!("{distribution}/{release}"),
+        None => distribution,
+    };
+
+    let mut notifications = TransitionNotifications::default();
+    if *kind == TransitionKind::Regression {
+        notifications.regression = Some(Notification::new(
+            "package.regression",
+            &format!("regression/{suite}/{name}/{version}/{architecture}"),
+            format!(
+                "{name} {version} ({suite}, {architecture}) was GOOD before and is BAD now, reported by worker {worker:?}. The package didn't change, the build environment or the published artifacts may have been tampered with"
+            ),
+        ));
+    }
+
+    if !recipients.is_empty() {
+        let notification = Notification::new(
+            "package.status",
+            &format!("status/{suite}/{name}/{architecture}"),
+            format!(
+                "{name} {version} ({suite}, {architecture}) is {} now, {previous_version} was {} ({})",
+                status.as_str(),
+                previous_status.as_str(),
+                kind.as_str()
+            ),
+        );
+        notifications.subscribers = Some((recipients, notification));
+    }
+
+    Ok(notifications)
 }
 
-#[post("")]
 pub async fn submit_rebuild_report(
-    req: HttpRequest,
     cfg: web::Data<Config>,
     pool: web::Data<Pool>,
-    request: web::Json<RebuildReport>,
+    storage: web::Data<Storage>,
+    identity: web::ReqData<AuthenticatedWorker>,
+    request: SchemaJson<RebuildReport>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-    if auth::worker(&cfg, &req, connection.as_mut()).is_err() {
-        return Ok(HttpResponse::Forbidden());
+    let identity = identity.into_inner();
+    if !identity.permits(WorkerCapability::Report) {
+        return Ok(HttpResponse::Forbidden().finish());
     }
+    let worker = identity.worker;
+    let environment_fingerprint = worker
+        .environment
+        .as_ref()
+        .map(|environment| environment.fingerprint());
 
-    let report = request.into_inner();
-    let queued = queue::table
-        .filter(queue::id.is(report.queue_id))
-        .get_result::<Queued>(connection.as_mut())
-        .map_err(Error::from)?;
+    let mut report = request.into_inner();
+    let queue_id = report.queue_id;
+    let reported_status = report.status.clone();
+    let verification_config = cfg.verification.clone();
 
-    // figure out any other build inputs that should share this result (same input, backend, and arch). Will include the
-    // enqueued build ID as well, so no need to add it later.
-    let friends =
-        get_build_input_friends(connection.as_mut(), queued.build_input_id).map_err(Error::from)?;
+    // uploaded artifacts are hashed here, they are not kept around
+    let mut uploaded_checksums = HashMap::new();
+    for artifact in &mut report.artifacts {
+        let Some(content) = artifact.content.take() else {
+            continue;
+        };
+        match verification::uploaded_checksum(&content).await {
+            Ok(checksum) => {
+                uploaded_checksums.insert(artifact.name.clone(), checksum);
+            }
+            Err(err) => warn!(
+                "Worker {:?} uploaded an unusable artifact {:?}: {err:#}",
+                worker.name, artifact.name
+            ),
+        }
+    }
+    let reported_artifacts = report
+        .artifacts
+        .iter()
+        .map(|artifact| {
+            (
+                artifact.name.clone(),
+                artifact.status.clone(),
+                uploaded_checksums.get(&artifact.name).cloned(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let flaky_threshold = cfg.schedule.flaky_threshold();
+    let downloaded_bytes = report
+        .downloaded_bytes
+        .map(|bytes| i64::try_from(bytes).unwrap_or(i64::MAX));
+    let worker_id = worker.id;
 
-    let encoded_log = if is_zstd_compressed(&report.build_log) {
-        report.build_log
-    } else {
-        zstd_compress(&report.build_log[..])
-            .await
-            .map_err(Error::from)?
-    };
+    let (queued, status, friends, verified, contradicted) = db::run(&pool, move |connection| {
+        let queued = queue::table
+            .filter(queue::id.is(queue_id))
+            .get_result::<Queued>(connection)?;
 
-    let new_log = NewBuildLog {
-        build_log: encoded_log,
-    };
+        // artifacts of distributions that are compared byte for byte are hashed and checked against their published
+        // checksum, instead of taking the word of the worker
+        let distribution = build_inputs::table
+            .inner_join(source_packages::table)
+            .filter(build_inputs::id.is(queued.build_input_id))
+            .select(source_packages::distribution)
+            .get_result::<String>(connection)?;
+        let verified = verification_config.verifies(&distribution);
+        let contradicted = if verified {
+            let published = verification::published_checksums(connection, queued.build_input_id)?;
+            reported_artifacts
+                .into_iter()
+                .filter(|(name, status, checksum)| {
+                    verification::contradicts(
+                        published.get(name).map(String::as_str),
+                        status,
+                        checksum.as_deref(),
+                    )
+                })
+                .map(|(name, _, _)| name)
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+        let reported_status = if !contradicted.is_empty() && reported_status == BuildStatus::Good {
+            BuildStatus::Bad
+        } else {
+            reported_status
+        };
+
+        // every build counts towards the traffic of the worker, no matter how its result is recorded
+        if let Some(downloaded_bytes) = downloaded_bytes {
+            update(workers::table.filter(workers::id.is(worker_id)))
+                .set(workers::downloaded_bytes.eq(workers::downloaded_bytes + downloaded_bytes))
+                .execute(connection)?;
+        }
+
+        let status = if let Some(threshold) = flaky_threshold {
+            classify_flaky(
+                connection,
+                queued.build_input_id,
+                reported_status,
+                threshold,
+            )?
+        } else {
+            reported_status
+        };
+
+        // figure out any other build inputs that should share this result (same input, backend, and arch). Will
+        // include the enqueued build ID as well, so no need to add it later.
+        let friends = get_build_input_friends(connection, queued.build_input_id)?;
+
+        Ok((queued, status, friends, verified, contradicted))
+    })
+    .await?;
+
+    // only checksums the daemon computed itself are recorded for verified distributions
+    if verified {
+        for artifact in &mut report.artifacts {
+            artifact.checksum = uploaded_checksums.remove(&artifact.name);
+        }
+    }
+
+    if !contradicted.is_empty() {
+        warn!(
+            "Worker {:?} reported artifacts as GOOD that weren't uploaded or don't match their published checksum, recording them as BAD: {contradicted:?}",
+            worker.name
+        );
+        for artifact in &mut report.artifacts {
+            if contradicted.contains(&artifact.name) {
+                artifact.status = ArtifactStatus::Bad;
+            }
+        }
+        if report.status == BuildStatus::Good {
+            report.status = BuildStatus::Bad;
+        }
+    }
+
+    let stored_log = store_log(&storage, "build-logs", report.build_log).await?;
+    let mut stored_keys = stored_log.blob_key.iter().cloned().collect::<Vec<_>>();
+
+    // bisect steps are only recorded on the bisect, the same queue entry goes on with the next variation
+    if let Some(step_id) = queued.bisect_step_id {
+        let result = db::transaction(&pool, move |connection| {
+            let new_log = NewBuildLog {
+                build_log: stored_log.data,
+                blob_key: stored_log.blob_key,
+            };
+            let new_log_id = new_log.insert(connection)?;
+
+            let result = BisectStepResult {
+                status: report.status.as_str().to_string(),
+                build_log_id: new_log_id,
+                worker_id: Some(worker.id),
+                built_at: report.built_at,
+            };
+            if let Some(next_step_id) = result.record(step_id, connection)? {
+                update(queue::table.filter(queue::id.is(queued.id)))
+                    .set(queue::bisect_step_id.eq(next_step_id))
+                    .execute(connection)?;
+                queued.release(connection)
+            } else {
+                queued.delete(connection)
+            }
+        })
+        .await;
+        discard_on_error(&storage, &stored_keys, result).await?;
+
+        return Ok(HttpResponse::NoContent().finish());
+    }
 
-    let new_log_id = new_log.insert(connection.as_mut())?;
+    // cross builds are a separate verdict next to the native one, they don't touch the package status either
+    if let Some(build_architecture) = queued.build_architecture.clone() {
+        let result = db::transaction(&pool, move |connection| {
+            let new_log = NewBuildLog {
+                build_log: stored_log.data,
+                blob_key: stored_log.blob_key,
+            };
+            let new_log_id = new_log.insert(connection)?;
 
-    let mut artifact_logs: HashMap<&String, (Option<i32>, Option<i32>)> = HashMap::new();
+            let cross = NewCrossRebuild {
+                build_input_id: queued.build_input_id,
+                worker_id: Some(worker.id),
+                build_log_id: new_log_id,
+                build_architecture,
+                status: report.status.as_str().to_string(),
+                started_at: queued.started_at,
+                built_at: report.built_at,
+            };
+            cross.insert(connection)?;
 
-    for build_input_id in &friends {
-        let new_rebuild = NewRebuild {
-            build_input_id: *build_input_id,
-            started_at: queued.started_at,
-            built_at: Some(report.built_at),
-            build_log_id: new_log_id,
-            status: Some(report.status.as_str().to_string()),
+            queued.delete(connection)
+        })
+        .await;
+        discard_on_error(&storage, &stored_keys, result).await?;
+
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    // results of untrusted workers don't touch the package status, the job goes back to the queue so a trusted
+    // worker can confirm or contradict it
+    if !worker.trusted {
+        let result = db::transaction(&pool, move |connection| {
+            let new_log = NewBuildLog {
+                build_log: stored_log.data,
+                blob_key: stored_log.blob_key,
+            };
+            let new_log_id = new_log.insert(connection)?;
+
+            let provisional = NewProvisionalRebuild {
+                build_input_id: queued.build_input_id,
+                worker_id: Some(worker.id),
+                build_log_id: new_log_id,
+                status: status.as_str().to_string(),
+                started_at: queued.started_at,
+                built_at: report.built_at,
+            };
+            provisional.insert(connection)?;
+
+            queued.release(connection)
+        })
+        .await;
+        discard_on_error(&storage, &stored_keys, result).await?;
+
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    // artifact logs are stored once and shared between all friends
+    let mut stored_artifact_logs = HashMap::new();
+    let mut likely_causes = HashMap::new();
+    for artifact_report in &report.artifacts {
+        if stored_artifact_logs.contains_key(&artifact_report.name) {
+            continue;
+        }
+
+        if artifact_report.status == ArtifactStatus::Bad
+            && let Some(diffoscope) = &artifact_report.diffoscope
+        {
+            match diff_causes::classify_report(diffoscope).await {
+                Ok(causes) => {
+                    likely_causes.insert(artifact_report.name.clone(), causes);
+                }
+                Err(err) => warn!(
+                    "Failed to classify diffoscope output of {:?}: {err:#}",
+                    artifact_report.name
+                ),
+            }
+        }
+
+        let diffoscope = if let Some(diffoscope) = &artifact_report.diffoscope {
+            let result = store_log(&storage, "diffoscope-logs", diffoscope.clone()).await;
+            let stored = discard_on_error(&storage, &stored_keys, result).await?;
+            stored_keys.extend(stored.blob_key.clone());
+            Some(stored)
+        } else {
+            None
         };
 
-        let new_rebuild_id = new_rebuild.insert(connection.as_mut())?;
+        let attestation = if let Some(attestation) = &artifact_report.attestation {
+            let result = store_log(&storage, "attestation-logs", attestation.clone()).await;
+            let stored = discard_on_error(&storage, &stored_keys, result).await?;
+            stored_keys.extend(stored.blob_key.clone());
+            Some(stored)
+        } else {
+            None
+        };
 
-        for artifact_report in &report.artifacts {
-            let entry = artifact_logs.entry(&artifact_report.name);
+        stored_artifact_logs.insert(artifact_report.name.clone(), (diffoscope, attestation));
+    }
 
-            let logs = match entry {
-                Entry::Occupied(oc) => oc.into_mut(),
-                Entry::Vacant(vc) => {
-                    let encoded_diffoscope = if let Some(diffoscope) = &artifact_report.diffoscope {
-                        Some(if is_zstd_compressed(diffoscope) {
-                            diffoscope.clone()
-                        } else {
-                            zstd_compress(&diffoscope[..]).await.map_err(Error::from)?
-                        })
-                    } else {
-                        None::<Vec<u8>>
-                    };
-
-                    let encoded_attestation =
-                        if let Some(attestation) = &artifact_report.attestation {
-                            Some(if is_zstd_compressed(attestation) {
-                                attestation.clone()
-                            } else {
-                                zstd_compress(&attestation[..]).await.map_err(Error::from)?
-                            })
-                        } else {
-                            None::<Vec<u8>>
-                        };
+    let cfg = cfg.into_inner();
+    let notifier = cfg.notifier.clone();
+    let result = db::transaction(&pool, move |connection| {
+        let new_log = NewBuildLog {
+            build_log: stored_log.data,
+            blob_key: stored_log.blob_key,
+        };
+
+        let new_log_id = new_log.insert(connection)?;
 
-                    let new_diffoscope_id = if let Some(encoded_diffoscope) = encoded_diffoscope {
-                        let new_diffoscope_log = NewDiffoscopeLog {
-                            diffoscope_log: encoded_diffoscope.clone(),
+        // the status of the friends changes the same way, the transition is only recorded for the reported build input
+        let transition = detect_transition(connection, queued.build_input_id, &status)?;
+        let mut notifications = TransitionNotifications::default();
+
+        let mut artifact_logs: HashMap<&String, (Option<i32>, Option<i32>)> = HashMap::new();
+        let mut verified_artifacts = Vec::new();
+
+        for build_input_id in &friends {
+            let new_rebuild = NewRebuild {
+                build_input_id: *build_input_id,
+                started_at: queued.started_at,
+                built_at: Some(report.built_at),
+                build_log_id: new_log_id,
+                status: Some(status.as_str().to_string()),
+                outcome: Some(report.status.as_str().to_string()),
+                environment_fingerprint: environment_fingerprint.clone(),
+                worker_id: Some(worker.id),
+                downloaded_bytes,
+                reason: queued.reason.clone(),
+            };
+
+            let new_rebuild_id = new_rebuild.insert(connection)?;
+
+            if *build_input_id == queued.build_input_id
+                && let Some(transition) = &transition
+            {
+                let (kind, previous_version, previous_status) = transition;
+                NewStatusTransition {
+                    rebuild_id: new_rebuild_id,
+                    kind: kind.as_str().to_string(),
+                    previous_version: previous_version.clone(),
+                    previous_status: previous_status.as_str().to_string(),
+                    status: status.as_str().to_string(),
+                    created_at: Utc::now().naive_utc(),
+                }
+                .insert(connection)?;
+
+                notifications = transition_notifications(
+                    connection,
+                    *build_input_id,
+                    transition,
+                    &status,
+                    &worker.name,
+                )?;
+            }
+
+            for artifact_report in &report.artifacts {
+                let entry = artifact_logs.entry(&artifact_report.name);
+
+                let logs = match entry {
+                    Entry::Occupied(oc) => oc.into_mut(),
+                    Entry::Vacant(vc) => {
+                        let (diffoscope, attestation) = stored_artifact_logs
+                            .remove(&artifact_report.name)
+                            .unwrap_or_default();
+
+                        let new_diffoscope_id = if let Some(stored) = diffoscope {
+                            let new_diffoscope_log = NewDiffoscopeLog {
+                                diffoscope_log: stored.data,
+                                blob_key: stored.blob_key,
+                            };
+
+                            Some(new_diffoscope_log.insert(connection)?)
+                        } else {
+                            None::<i32>
                         };
 
-                        Some(new_diffoscope_log.insert(connection.as_mut())?)
-                    } else {
-                        None::<i32>
-                    };
+                        let new_attestation_id = if let Some(stored) = attestation {
+                            let new_attestation_log = NewAttestationLog {
+                                attestation_log: stored.data,
+                                blob_key: stored.blob_key,
+                            };
 
-                    let new_attestation_id = if let Some(encoded_attestation) = encoded_attestation
-                    {
-                        let new_attestation_log = NewAttestationLog {
-                            attestation_log: encoded_attestation.clone(),
+                            Some(new_attestation_log.insert(connection)?)
+                        } else {
+                            None::<i32>
                         };
 
-                        Some(new_attestation_log.insert(connection.as_mut())?)
-                    } else {
-                        None::<i32>
-                    };
+                        vc.insert((new_diffoscope_id, new_attestation_id))
+                    }
+                };
+
+                let new_rebuild_artifact = NewRebuildArtifact {
+                    rebuild_id: new_rebuild_id,
+                    name: artifact_report.name.clone(),
+                    diffoscope_log_id: logs.0,
+                    attestation_log_id: logs.1,
+                    status: Some(artifact_report.status.as_str().to_string()),
+                    diff_summary: artifact_report.diff_summary.clone(),
+                    checksum: artifact_report.checksum.clone(),
+                    likely_causes: likely_causes
+                        .get(&artifact_report.name)
+                        .cloned()
+                        .unwrap_or_default(),
+                };
 
-                    vc.insert((new_diffoscope_id, new_attestation_id))
+                let new_rebuild_artifact_id = new_rebuild_artifact.insert(connection)?;
+                if *build_input_id == queued.build_input_id {
+                    verified_artifacts.push((
+                        artifact_report.name.clone(),
+                        artifact_report.status.clone(),
+                        new_rebuild_artifact_id,
+                    ));
                 }
+            }
+        }
+
+        // a flaky package may be judged differently on the next attempt, and a worker that was caught lying doesn't
+        // get to hand out verdicts. There's nothing worth reusing in either case.
+        if status != BuildStatus::Flaky
+            && contradicted.is_empty()
+            && let Some(environment) = &worker.environment
+        {
+            record_artifact_verdicts(
+                connection,
+                queued.build_input_id,
+                &environment.scripts,
+                &verified_artifacts,
+                report.built_at,
+            )?;
+        }
+
+        record_build_duration(connection, &queued, &status, report.built_at)?;
+        queued.delete(connection)?;
+
+        NewProvisionalRebuild::resolve(
+            &friends,
+            status.as_str(),
+            Utc::now().naive_utc(),
+            connection,
+        )?;
+
+        if status == BuildStatus::Good {
+            update(build_inputs::table)
+                .filter(build_inputs::id.eq_any(&friends))
+                .set(build_inputs::last_failure.eq(None::<String>))
+                .execute(connection)?;
+        } else {
+            // increment retries and remember why this attempt failed
+            let last_failure = if contradicted.is_empty() {
+                format!("{} reported by worker {:?}", status.as_str(), worker.name)
+            } else {
+                format!(
+                    "GOOD reported by worker {:?}, but the checksums of {} don't match the published ones",
+                    worker.name,
+                    contradicted.join(", ")
+                )
             };
+            update(build_inputs::table)
+                .filter(build_inputs::id.eq_any(&friends))
+                .set((
+                    build_inputs::retries.eq(build_inputs::retries + 1),
+                    build_inputs::last_failure.eq(last_failure),
+                ))
+                .execute(connection)?;
+
+            let retry_count =
+                get_largest_retry_count_among_friends(connection, queued.build_input_id)?;
+
+            // bail if we have a max retry count set and requeueing this package would exceed it
+            if let Some(max_retries) = cfg.schedule.max_retries()
+                && retry_count >= max_retries
+            {
+                mark_build_input_friends_as_non_retriable(connection, queued.build_input_id)?;
+                return Ok(notifications);
+            }
 
-            let new_rebuild_artifact = NewRebuildArtifact {
-                rebuild_id: new_rebuild_id,
-                name: artifact_report.name.clone(),
-                diffoscope_log_id: logs.0,
-                attestation_log_id: logs.1,
-                status: Some(artifact_report.status.as_str().to_string()),
+            let now = Utc::now();
+            let then =
+                now + Duration::hours((retry_count + 1) as i64 * cfg.schedule.retry_delay_base());
+
+            update(build_inputs::table)
+                .filter(build_inputs::id.eq_any(friends))
+                .set(build_inputs::next_retry.eq(then.naive_utc()))
+                .execute(connection)?;
+
+            // only requeue this build ID, the retry is still done for the reason the package was queued
+            let new_queue = NewQueued {
+                build_input_id: queued.build_input_id,
+                priority: Priority::retry(),
+                queued_at: now.naive_utc(),
+                build_architecture: None,
+                bisect_step_id: None,
+                reason: queued.reason.clone(),
             };
 
-            new_rebuild_artifact.insert(connection.as_mut())?;
+            new_queue.upsert(connection)?;
         }
+
+        Ok(notifications)
+    })
+    .await;
+    let notifications = discard_on_error(&storage, &stored_keys, result).await?;
+
+    if let Some(notification) = notifications.regression {
+        notifier.send(&notification).await;
     }
+    if let Some((recipients, notification)) = notifications.subscribers {
+        notifier.send_to(&recipients, &notification).await;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Check the stored GOOD artifacts against their published checksums again, eg. after verification was enabled for a
+/// distribution or to find out what else a worker reported that was caught lying. Distributions that normalize their
+/// artifacts before comparing them are expected to show up here.
+#[get("/verification")]
+pub async fn get_verification_mismatches(
+    pool: web::Data<Pool>,
+    page: web::Query<Page>,
+    origin_filter: web::Query<OriginFilter>,
+) -> web::Result<impl Responder> {
+    let page = page.into_inner();
+    let origin_filter = origin_filter.into_inner();
 
-    queued.delete(connection.as_mut())?;
+    let mismatches = db::run(&pool, move |connection| {
+        let base = || {
+            rebuild_artifacts::table
+                .inner_join(
+                    rebuilds::table
+                        .inner_join(build_inputs::table.inner_join(source_packages::table)),
+                )
+                .inner_join(
+                    binary_packages::table.on(binary_packages::build_input_id
+                        .eq(rebuilds::build_input_id)
+                        .and(binary_packages::name.eq(rebuild_artifacts::name))),
+                )
+                .left_join(workers::table.on(rebuilds::worker_id.is(workers::id.nullable())))
+                .filter(
+                    origin_filter
+                        .clone()
+                        .into_filter(build_inputs::architecture),
+                )
+                .filter(rebuild_artifacts::status.eq(ArtifactStatus::Good.as_str()))
+                .filter(binary_packages::checksum.is_not_null())
+                .filter(
+                    rebuild_artifacts::checksum
+                        .is_null()
+                        .or(verification::lower(rebuild_artifacts::checksum)
+                            .ne(verification::lower(binary_packages::checksum))),
+                )
+                .into_boxed()
+        };
 
-    if report.status != BuildStatus::Good {
-        // increment retries
-        update(build_inputs::table)
-            .filter(build_inputs::id.eq_any(&friends))
-            .set(build_inputs::retries.eq(build_inputs::retries + 1))
-            .execute(connection.as_mut())
-            .map_err(Error::from)?;
+        // the artifact id comes first, it's the id the page is cut at
+        let records = base()
+            .select((
+                rebuild_artifacts::id,
+                rebuilds::id,
+                rebuild_artifacts::name,
+                source_packages::name,
+                source_packages::version,
+                source_packages::distribution,
+                build_inputs::architecture,
+                workers::name.nullable(),
+                rebuilds::built_at,
+                binary_packages::checksum.assume_not_null(),
+                rebuild_artifacts::checksum,
+            ))
+            .paginate(page)
+            .load::<(
+                i32,
+                i32,
+                String,
+                String,
+                String,
+                String,
+                String,
+                Option<String>,
+                Option<NaiveDateTime>,
+                String,
+                Option<String>,
+            )>(connection)?
+            .into_iter()
+            .map(
+                |(
+                    artifact_id,
+                    build_id,
+                    artifact,
+                    name,
+                    version,
+                    distribution,
+                    architecture,
+                    worker,
+                    built_at,
+                    published_checksum,
+                    rebuilt_checksum,
+                )| VerificationMismatch {
+                    build_id,
+                    artifact_id,
+                    artifact,
+                    name,
+                    version,
+                    distribution,
+                    architecture,
+                    worker,
+                    built_at,
+                    published_checksum,
+                    rebuilt_checksum,
+                },
+            )
+            .collect::<Vec<_>>();
 
-        let retry_count =
-            get_largest_retry_count_among_friends(connection.as_mut(), queued.build_input_id)
-                .map_err(Error::from)?;
+        let total = base().count().get_result::<i64>(connection)?;
 
-        // bail if we have a max retry count set and requeueing this package would exceed it
-        if let Some(max_retries) = cfg.schedule.max_retries()
-            && retry_count >= max_retries
-        {
-            mark_build_input_friends_as_non_retriable(connection.as_mut(), queued.build_input_id)
-                .map_err(Error::from)?;
+        Ok(ResultPage { total, records })
+    })
+    .await?;
 
-            return Ok(HttpResponse::NoContent());
-        }
+    Ok(HttpResponse::Ok().json(mismatches))
+}
+
+/// Rebuilds that changed the status of a package between GOOD and BAD
+#[get("/transitions")]
+pub async fn get_status_transitions(
+    pool: web::Data<Pool>,
+    page: web::Query<Page>,
+    origin_filter: web::Query<OriginFilter>,
+    transition_filter: web::Query<TransitionFilter>,
+) -> web::Result<impl Responder> {
+    let page = page.into_inner();
+    let origin_filter = origin_filter.into_inner();
+    let kind = transition_filter.into_inner().kind;
 
-        let now = Utc::now();
-        let then =
-            now + Duration::hours((retry_count + 1) as i64 * cfg.schedule.retry_delay_base());
-
-        update(build_inputs::table)
-            .filter(build_inputs::id.eq_any(friends))
-            .set(build_inputs::next_retry.eq(then.naive_utc()))
-            .execute(connection.as_mut())
-            .map_err(Error::from)?;
-
-        // only requeue this build ID
-        let new_queue = NewQueued {
-            build_input_id: queued.build_input_id,
-            priority: Priority::retry(),
-            queued_at: now.naive_utc(),
+    let transitions = db::run(&pool, move |connection| {
+        let base = || {
+            let mut query = status_transitions::table
+                .inner_join(
+                    rebuilds::table
+                        .inner_join(build_inputs::table.inner_join(source_packages::table)),
+                )
+                .left_join(workers::table.on(rebuilds::worker_id.is(workers::id.nullable())))
+                .filter(
+                    origin_filter
+                        .clone()
+                        .into_filter(build_inputs::architecture),
+                )
+                .into_boxed();
+            if let Some(kind) = &kind {
+                query = query.filter(status_transitions::kind.eq(kind.as_str().to_string()));
+            }
+            query
         };
 
-        new_queue.upsert(connection.as_mut())?;
-    }
+        let records = base()
+            .select((
+                status_transitions::id,
+                rebuilds::id,
+                status_transitions::kind,
+                source_packages::name,
+                source_packages::version,
+                source_packages::distribution,
+                source_packages::release,
+                build_inputs::architecture,
+                status_transitions::previous_version,
+                status_transitions::previous_status,
+                status_transitions::status,
+                workers::name.nullable(),
+                status_transitions::created_at,
+            ))
+            .paginate(page)
+            .load::<StatusTransition>(connection)?;
+
+        let total = base().count().get_result::<i64>(connection)?;
 
-    Ok(HttpResponse::NoContent())
+        Ok(ResultPage { total, records })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(transitions))
 }
 
 #[get("/{id}")]
 pub async fn get_build(pool: web::Data<Pool>, id: web::Path<i32>) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let record = db::run(&pool, move |connection| {
+        let record = builds_base()
+            .filter(rebuilds::id.is(id))
+            .get_result::<Rebuild>(connection)
+            .optional()?;
+        Ok(record)
+    })
+    .await?;
 
-    if let Some(record) = rebuilds::table
-        .inner_join(build_inputs::table.inner_join(source_packages::table))
-        .filter(rebuilds::id.is(*id))
-        .select((
-            rebuilds::id,
-            source_packages::name,
-            source_packages::version,
-            source_packages::distribution,
-            source_packages::release,
-            source_packages::component,
-            build_inputs::architecture,
-            build_inputs::backend,
-            build_inputs::retries,
-            rebuilds::started_at,
-            rebuilds::built_at,
-            rebuilds::status,
-        ))
-        .get_result::<Rebuild>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?
-    {
+    if let Some(record) = record {
         Ok(HttpResponse::Ok().json(record))
     } else {
         Ok(HttpResponse::NotFound().finish())
@@ -297,16 +1094,21 @@ pub async fn get_build(pool: web::Data<Pool>, id: web::Path<i32>) -> web::Result
 pub async fn get_build_log(
     req: HttpRequest,
     pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let (build_log, blob_key) = db::run(&pool, move |connection| {
+        let log = rebuilds::table
+            .filter(rebuilds::id.is(id))
+            .inner_join(build_logs::table)
+            .select((build_logs::build_log, build_logs::blob_key))
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)?;
+        Ok(log)
+    })
+    .await?;
 
-    let build_log = rebuilds::table
-        .filter(rebuilds::id.is(id.into_inner()))
-        .inner_join(build_logs::table)
-        .select(build_logs::build_log)
-        .first::<Vec<u8>>(connection.as_mut())
-        .map_err(Error::from)?;
+    let build_log = storage.load(build_log, blob_key).await?.unwrap_or_default();
 
     forward_compressed_data(req, "text/plain; charset=utf-8", build_log).await
 }
@@ -316,23 +1118,29 @@ pub async fn get_build_artifacts(
     pool: web::Data<Pool>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-    let records = rebuilds::table
-        .inner_join(
-            rebuild_artifacts::table
-                .left_join(diffoscope_logs::table)
-                .left_join(attestation_logs::table),
-        )
-        .filter(rebuilds::id.is(id.into_inner()))
-        .select((
-            rebuild_artifacts::id,
-            rebuild_artifacts::name,
-            diffoscope_logs::diffoscope_log.nullable().is_not_null(),
-            attestation_logs::attestation_log.nullable().is_not_null(),
-            rebuild_artifacts::status,
-        ))
-        .get_results::<api::v1::RebuildArtifact>(connection.as_mut())
-        .map_err(Error::from)?;
+    let id = id.into_inner();
+    let records = db::run(&pool, move |connection| {
+        let records = rebuilds::table
+            .inner_join(
+                rebuild_artifacts::table
+                    .left_join(diffoscope_logs::table)
+                    .left_join(attestation_logs::table),
+            )
+            .filter(rebuilds::id.is(id))
+            .select((
+                rebuild_artifacts::id,
+                rebuild_artifacts::name,
+                diffoscope_logs::id.nullable().is_not_null(),
+                attestation_logs::id.nullable().is_not_null(),
+                rebuild_artifacts::status,
+                rebuild_artifacts::diff_summary,
+                rebuild_artifacts::checksum,
+                rebuild_artifacts::likely_causes,
+            ))
+            .get_results::<api::v1::RebuildArtifact>(connection)?;
+        Ok(records)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(records))
 }
@@ -342,26 +1150,31 @@ pub async fn get_build_artifact(
     pool: web::Data<Pool>,
     path: web::Path<(i32, i32)>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let artifact = rebuilds::table
-        .inner_join(
-            rebuild_artifacts::table
-                .left_join(diffoscope_logs::table)
-                .left_join(attestation_logs::table),
-        )
-        .filter(rebuilds::id.is(path.0))
-        .filter(rebuild_artifacts::id.is(path.1))
-        .select((
-            rebuild_artifacts::id,
-            rebuild_artifacts::name,
-            diffoscope_logs::diffoscope_log.nullable().is_not_null(),
-            attestation_logs::attestation_log.nullable().is_not_null(),
-            rebuild_artifacts::status,
-        ))
-        .first::<api::v1::RebuildArtifact>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?;
+    let (id, artifact_id) = path.into_inner();
+    let artifact = db::run(&pool, move |connection| {
+        let artifact = rebuilds::table
+            .inner_join(
+                rebuild_artifacts::table
+                    .left_join(diffoscope_logs::table)
+                    .left_join(attestation_logs::table),
+            )
+            .filter(rebuilds::id.is(id))
+            .filter(rebuild_artifacts::id.is(artifact_id))
+            .select((
+                rebuild_artifacts::id,
+                rebuild_artifacts::name,
+                diffoscope_logs::id.nullable().is_not_null(),
+                attestation_logs::id.nullable().is_not_null(),
+                rebuild_artifacts::status,
+                rebuild_artifacts::diff_summary,
+                rebuild_artifacts::checksum,
+                rebuild_artifacts::likely_causes,
+            ))
+            .first::<api::v1::RebuildArtifact>(connection)
+            .optional()?;
+        Ok(artifact)
+    })
+    .await?;
 
     if let Some(artifact) = artifact {
         Ok(HttpResponse::Ok().json(artifact))
@@ -374,20 +1187,29 @@ pub async fn get_build_artifact(
 pub async fn get_build_artifact_diffoscope(
     req: HttpRequest,
     pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
     path: web::Path<(i32, i32)>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let diffoscope = rebuilds::table
-        .inner_join(rebuild_artifacts::table.left_join(diffoscope_logs::table))
-        .filter(rebuilds::id.is(path.0))
-        .filter(rebuild_artifacts::id.is(path.1))
-        .select(diffoscope_logs::diffoscope_log.nullable())
-        .first::<Option<Vec<u8>>>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?;
-
-    if let Some(diffoscope) = diffoscope.flatten() {
+    let (id, artifact_id) = path.into_inner();
+    let (diffoscope, blob_key) = db::run(&pool, move |connection| {
+        let diffoscope = rebuilds::table
+            .inner_join(rebuild_artifacts::table.left_join(diffoscope_logs::table))
+            .filter(rebuilds::id.is(id))
+            .filter(rebuild_artifacts::id.is(artifact_id))
+            .select((
+                diffoscope_logs::diffoscope_log.nullable(),
+                diffoscope_logs::blob_key.nullable(),
+            ))
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)
+            .optional()?
+            .unwrap_or_default();
+        Ok(diffoscope)
+    })
+    .await?;
+
+    let diffoscope = storage.load(diffoscope, blob_key).await?;
+
+    if let Some(diffoscope) = diffoscope {
         forward_compressed_data(req, "text/plain; charset=utf-8", diffoscope).await
     } else {
         Ok(HttpResponse::NotFound().finish())
@@ -398,22 +1220,29 @@ pub async fn get_build_artifact_diffoscope(
 pub async fn get_build_artifact_attestation(
     req: HttpRequest,
     pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
     path: web::Path<(i32, i32)>,
     cfg: web::Data<Config>,
     private_key: web::Data<Arc<PrivateKey>>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let attestation = rebuilds::table
-        .inner_join(rebuild_artifacts::table.left_join(attestation_logs::table))
-        .filter(rebuilds::id.is(path.0))
-        .filter(rebuild_artifacts::id.is(path.1))
-        .select(attestation_logs::attestation_log.nullable())
-        .first::<Option<Vec<u8>>>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?;
-
-    let Some(mut attestation) = attestation.flatten() else {
+    let (id, artifact_id) = path.into_inner();
+    let (attestation, blob_key) = db::run(&pool, move |connection| {
+        let attestation = rebuilds::table
+            .inner_join(rebuild_artifacts::table.left_join(attestation_logs::table))
+            .filter(rebuilds::id.is(id))
+            .filter(rebuild_artifacts::id.is(artifact_id))
+            .select((
+                attestation_logs::attestation_log.nullable(),
+                attestation_logs::blob_key.nullable(),
+            ))
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)
+            .optional()?
+            .unwrap_or_default();
+        Ok(attestation)
+    })
+    .await?;
+
+    let Some(mut attestation) = storage.load(attestation, blob_key.clone()).await? else {
         return Ok(HttpResponse::NotFound().finish());
     };
 
@@ -425,18 +1254,25 @@ pub async fn get_build_artifact_attestation(
         .await?;
 
         if has_new_signature {
-            let attestation_id = rebuild_artifacts::table
-                .filter(rebuild_artifacts::id.is(path.1))
-                .select(rebuild_artifacts::attestation_log_id.assume_not_null())
-                .get_result::<i32>(connection.as_mut())
-                .map_err(Error::from)?;
-
             // TODO: GET with side effects?
-            update(attestation_logs::table)
-                .filter(attestation_logs::id.is(attestation_id))
-                .set(attestation_logs::attestation_log.eq(bytes.clone()))
-                .execute(connection.as_mut())
-                .map_err(Error::from)?;
+            if let Some(blob_key) = &blob_key {
+                storage.replace(blob_key, bytes.clone()).await?;
+            } else {
+                let signed = bytes.clone();
+                db::run(&pool, move |connection| {
+                    let attestation_id = rebuild_artifacts::table
+                        .filter(rebuild_artifacts::id.is(artifact_id))
+                        .select(rebuild_artifacts::attestation_log_id.assume_not_null())
+                        .get_result::<i32>(connection)?;
+
+                    update(attestation_logs::table)
+                        .filter(attestation_logs::id.is(attestation_id))
+                        .set(attestation_logs::attestation_log.eq(Some(signed)))
+                        .execute(connection)?;
+                    Ok(())
+                })
+                .await?;
+            }
 
             attestation = bytes
         }
@@ -444,3 +1280,281 @@ pub async fn get_build_artifact_attestation(
 
     forward_compressed_data(req, "application/json; charset=utf-8", attestation).await
 }
+
+#[derive(Debug, Serialize)]
+struct BundleManifest {
+    build: Rebuild,
+    input_url: String,
+    payload: Option<JobPayload>,
+    artifacts: Vec<BundleArtifact>,
+}
+
+#[derive(Debug, Serialize)]
+struct BundleArtifact {
+    name: String,
+    version: String,
+    architecture: String,
+    url: String,
+    status: Option<ArtifactStatus>,
+    /// The sha256 of the published artifact, as recorded during sync
+    published_checksum: Option<String>,
+    /// The sha256 of the rebuilt artifact
+    rebuilt_checksum: Option<String>,
+}
+
+type BundleLog = (Option<Vec<u8>>, Option<String>);
+type BundleArtifactRecord = (
+    String,
+    Option<ArtifactStatus>,
+    Option<String>,
+    Option<i32>,
+    Option<i32>,
+);
+
+/// Everything the report bundle needs from the database, the logs are only loaded while the bundle is written
+struct BundleRecords {
+    build: Rebuild,
+    input_url: String,
+    payload: Option<JobPayload>,
+    build_log_id: i32,
+    artifacts: Vec<BundleArtifactRecord>,
+    binary_packages: Vec<(String, String, String, String, Option<String>)>,
+}
+
+fn load_bundle_records(
+    connection: &mut SqliteConnection,
+    id: i32,
+) -> Result<Option<BundleRecords>> {
+    let Some(build) = builds_base()
+        .filter(rebuilds::id.is(id))
+        .get_result::<Rebuild>(connection)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let (build_input_id, input_url, payload, build_log_id) = rebuilds::table
+        .inner_join(build_inputs::table)
+        .filter(rebuilds::id.is(id))
+        .select((
+            build_inputs::id,
+            build_inputs::url,
+            build_inputs::payload,
+            rebuilds::build_log_id,
+        ))
+        .get_result::<(i32, String, Option<JobPayload>, i32)>(connection)?;
+
+    let artifacts = rebuild_artifacts::table
+        .filter(rebuild_artifacts::rebuild_id.is(id))
+        .select((
+            rebuild_artifacts::name,
+            rebuild_artifacts::status,
+            rebuild_artifacts::checksum,
+            rebuild_artifacts::diffoscope_log_id,
+            rebuild_artifacts::attestation_log_id,
+        ))
+        .order_by(rebuild_artifacts::name)
+        .get_results::<BundleArtifactRecord>(connection)?;
+
+    let binary_packages = binary_packages::table
+        .filter(binary_packages::build_input_id.is(build_input_id))
+        .select((
+            binary_packages::name,
+            binary_packages::version,
+            binary_packages::architecture,
+            binary_packages::artifact_url,
+            binary_packages::checksum,
+        ))
+        .order_by(binary_packages::name)
+        .get_results::<(String, String, String, String, Option<String>)>(connection)?;
+
+    Ok(Some(BundleRecords {
+        build,
+        input_url,
+        payload,
+        build_log_id,
+        artifacts,
+        binary_packages,
+    }))
+}
+
+/// A bundle file that is read from the database and the configured storage once the bundle writer gets to it
+fn lazy_log<F>(pool: &Pool, storage: &Storage, path: String, query: F) -> BundleFile
+where
+    F: FnOnce(&mut SqliteConnection) -> QueryResult<BundleLog> + Send + 'static,
+{
+    let pool = pool.clone();
+    let storage = storage.clone();
+    BundleFile::lazy(path, async move {
+        let (data, blob_key) = db::run(&pool, move |connection| Ok(query(connection)?)).await?;
+        storage.load(data, blob_key).await
+    })
+}
+
+/// The buildinfo of debian packages is a small text file next to the package, the build input of other distributions
+/// is either embedded in the package or unknown to rebuilderd
+fn buildinfo(client: http::Client, payload: &Option<JobPayload>) -> Option<BundleFile> {
+    let Some(JobPayload::Debian { buildinfo_url, .. }) = payload else {
+        return None;
+    };
+    let buildinfo_url = buildinfo_url.clone();
+
+    Some(BundleFile::lazy("input.buildinfo", async move {
+        let fetched = async {
+            let response = client
+                .get(&buildinfo_url)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok::<_, Error>(response.bytes().await?.to_vec())
+        };
+        // the bundle is still useful without it, the url is in build.json
+        match fetched.await {
+            Ok(buildinfo) => Ok(Some(buildinfo)),
+            Err(err) => {
+                warn!("Failed to fetch buildinfo {buildinfo_url:?} for report bundle: {err:#}");
+                Ok(None)
+            }
+        }
+    }))
+}
+
+#[get("/{id}/bundle.tar.gz")]
+pub async fn get_build_bundle(
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    build_bundle(&cfg, &pool, &storage, id.into_inner()).await
+}
+
+/// Stream the report bundle of a build, also served by name on v0
+pub(crate) async fn build_bundle(
+    cfg: &Config,
+    pool: &Pool,
+    storage: &Storage,
+    id: i32,
+) -> web::Result<HttpResponse> {
+    let Some(BundleRecords {
+        build,
+        input_url,
+        payload,
+        build_log_id,
+        artifacts,
+        binary_packages,
+    }) = db::run(pool, move |connection| load_bundle_records(connection, id)).await?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut files = Vec::new();
+    files.push(lazy_log(
+        pool,
+        storage,
+        "build.log".to_string(),
+        move |connection| {
+            build_logs::table
+                .filter(build_logs::id.is(build_log_id))
+                .select((build_logs::build_log, build_logs::blob_key))
+                .get_result(connection)
+        },
+    ));
+
+    let client = http::client(&cfg.outbound)?;
+    files.extend(buildinfo(client, &payload));
+
+    let mut rebuilt = HashMap::new();
+    for (name, status, checksum, diffoscope_log_id, attestation_log_id) in artifacts {
+        let filename = sanitize_filename(&name);
+
+        if let Some(diffoscope_log_id) = diffoscope_log_id {
+            files.push(lazy_log(
+                pool,
+                storage,
+                format!("diffoscope/{filename}.txt"),
+                move |connection| {
+                    diffoscope_logs::table
+                        .filter(diffoscope_logs::id.is(diffoscope_log_id))
+                        .select((diffoscope_logs::diffoscope_log, diffoscope_logs::blob_key))
+                        .get_result(connection)
+                },
+            ));
+        }
+
+        if let Some(attestation_log_id) = attestation_log_id {
+            files.push(lazy_log(
+                pool,
+                storage,
+                format!("attestation/{filename}.json"),
+                move |connection| {
+                    attestation_logs::table
+                        .filter(attestation_logs::id.is(attestation_log_id))
+                        .select((
+                            attestation_logs::attestation_log,
+                            attestation_logs::blob_key,
+                        ))
+                        .get_result(connection)
+                },
+            ));
+        }
+
+        rebuilt.insert(name, (status, checksum));
+    }
+
+    let mut checksums = BTreeSet::new();
+    let artifacts = binary_packages
+        .into_iter()
+        .map(|(name, version, architecture, url, published_checksum)| {
+            let (status, rebuilt_checksum) = rebuilt.get(&name).cloned().unwrap_or_default();
+
+            let filename = sanitize_filename(&name);
+            if let Some(checksum) = &published_checksum {
+                checksums.insert(format!("{checksum}  published/{filename}\n"));
+            }
+            if let Some(checksum) = &rebuilt_checksum {
+                checksums.insert(format!("{checksum}  rebuilt/{filename}\n"));
+            }
+
+            BundleArtifact {
+                name,
+                version,
+                architecture,
+                url,
+                status,
+                published_checksum,
+                rebuilt_checksum,
+            }
+        })
+        .collect();
+
+    if !checksums.is_empty() {
+        files.push(BundleFile::new(
+            "SHA256SUMS",
+            checksums.into_iter().collect::<String>().into_bytes(),
+        ));
+    }
+
+    let prefix = sanitize_filename(&format!("{}-{}-build-{}", build.name, build.version, id));
+    let mtime = build
+        .built_at
+        .map(|built_at| built_at.and_utc().timestamp().max(0) as u64)
+        .unwrap_or_default();
+
+    let manifest = BundleManifest {
+        build,
+        input_url,
+        payload,
+        artifacts,
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(Error::from)?;
+    files.insert(0, BundleFile::new("build.json", manifest));
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .append_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{prefix}.tar.gz\""),
+        ))
+        .streaming(bundle::stream(prefix, mtime, files)))
+}
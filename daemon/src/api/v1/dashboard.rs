@@ -1,19 +1,19 @@
-use crate::db::Pool;
+use crate::db::{self, Pool};
 use crate::schema::{build_inputs, queue, rebuilds, source_packages};
 use crate::web;
 use actix_web::{HttpResponse, Responder, get};
 use chrono::Utc;
 use diesel::NullableExpressionMethods;
-use diesel::RunQueryDsl;
 use diesel::dsl::{case_when, sum};
 use diesel::sql_types::Integer;
 use diesel::sqlite::Sqlite;
 use diesel::{BoolExpressionMethods, JoinOnDsl, QueryDsl};
 use diesel::{ExpressionMethods, SqliteExpressionMethods};
+use diesel::{RunQueryDsl, SqliteConnection};
 use rebuilderd_common::api::v1::{
     DashboardJobState, DashboardRebuildState, DashboardState, OriginFilter,
 };
-use rebuilderd_common::errors::Error;
+use rebuilderd_common::errors::Result;
 
 use crate::api::v1::util::filters::IntoOriginFilter;
 use aliases::*;
@@ -39,8 +39,19 @@ pub async fn get_dashboard(
     pool: web::Data<Pool>,
     origin_filter: web::Query<OriginFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let origin_filter = origin_filter.into_inner();
+    let dashboard = db::run(&pool, move |connection| {
+        load_dashboard(connection, &origin_filter)
+    })
+    .await?;
 
+    Ok(HttpResponse::Ok().json(dashboard))
+}
+
+fn load_dashboard(
+    connection: &mut SqliteConnection,
+    origin_filter: &OriginFilter,
+) -> Result<DashboardState> {
     let mut sql = source_packages::table
         .inner_join(build_inputs::table)
         .left_join(r1.on(r1.field(rebuilds::build_input_id).is(build_inputs::id)))
@@ -59,7 +70,6 @@ pub async fn get_dashboard(
         .filter(
             origin_filter
                 .clone()
-                .into_inner()
                 .into_filter(build_inputs::architecture),
         )
         .into_boxed();
@@ -81,17 +91,28 @@ pub async fn get_dashboard(
                 case_when::<_, _, Integer>(r1.field(rebuilds::status).nullable().eq("FAIL"), 1)
                     .otherwise(0),
             ),
+            sum(
+                case_when::<_, _, Integer>(r1.field(rebuilds::status).nullable().eq("FLAKY"), 1)
+                    .otherwise(0),
+            ),
             sum(case_when::<_, _, Integer>(
                 r1.field(rebuilds::status)
                     .nullable()
                     .eq("UNKWN")
+                    // the inputs could not be fetched, nothing is known about reproducibility yet
+                    .or(r1.field(rebuilds::status).nullable().eq("DOWNLOAD_FAILED"))
                     .or(r1.field(rebuilds::status).nullable().is_null()),
                 1,
             )
             .otherwise(0)),
         ))
-        .get_result::<(Option<i64>, Option<i64>, Option<i64>, Option<i64>)>(connection.as_mut())
-        .map_err(Error::from)?;
+        .get_result::<(
+            Option<i64>,
+            Option<i64>,
+            Option<i64>,
+            Option<i64>,
+            Option<i64>,
+        )>(connection)?;
 
     let now = Utc::now();
 
@@ -99,19 +120,16 @@ pub async fn get_dashboard(
         .filter(
             origin_filter
                 .clone()
-                .into_inner()
                 .into_filter(build_inputs::architecture),
         )
         .filter(queue::worker.is_not_null())
         .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        .get_result::<i64>(connection)?;
 
     let available_jobs = queue_count_base()
         .filter(
             origin_filter
                 .clone()
-                .into_inner()
                 .into_filter(build_inputs::architecture),
         )
         .filter(queue::worker.is_null())
@@ -121,14 +139,12 @@ pub async fn get_dashboard(
                 .or(build_inputs::next_retry.le(now.naive_utc())),
         )
         .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        .get_result::<i64>(connection)?;
 
     let pending_jobs = queue_count_base()
         .filter(
             origin_filter
                 .clone()
-                .into_inner()
                 .into_filter(build_inputs::architecture),
         )
         .filter(queue::worker.is_null())
@@ -138,15 +154,15 @@ pub async fn get_dashboard(
                 .and(build_inputs::next_retry.gt(now.naive_utc())),
         )
         .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        .get_result::<i64>(connection)?;
 
     let dashboard = DashboardState {
         rebuilds: DashboardRebuildState {
             good: sums.0.unwrap_or(0),
             bad: sums.1.unwrap_or(0),
             fail: sums.2.unwrap_or(0),
-            unknown: sums.3.unwrap_or(0),
+            flaky: sums.3.unwrap_or(0),
+            unknown: sums.4.unwrap_or(0),
         },
         jobs: DashboardJobState {
             running: running_jobs,
@@ -155,5 +171,5 @@ pub async fn get_dashboard(
         },
     };
 
-    Ok(HttpResponse::Ok().json(dashboard))
+    Ok(dashboard)
 }
@@ -0,0 +1,120 @@
+use crate::api::v1::util::auth;
+use crate::api::v1::util::pagination::PaginateDsl;
+use crate::config::Config;
+use crate::db::{self, Pool};
+use crate::models::NewApiKey;
+use crate::schema::api_keys;
+use crate::secrets;
+use crate::web;
+use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, post};
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SqliteExpressionMethods};
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::api::v1::{
+    ApiKey, ApiKeyScope, IssueApiKeyRequest, IssuedApiKey, Page, ResultPage,
+};
+
+#[diesel::dsl::auto_type]
+fn api_keys_base() -> _ {
+    api_keys::table.select((
+        api_keys::id,
+        api_keys::name,
+        api_keys::scope,
+        api_keys::created_at,
+        api_keys::revoked_at,
+    ))
+}
+
+#[get("")]
+pub async fn get_api_keys(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    page: web::Query<Page>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::ReadOnly)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let page = page.into_inner();
+    let api_keys = db::run(&pool, move |connection| {
+        let records = api_keys_base().paginate(page).load::<ApiKey>(connection)?;
+        let total = api_keys_base().count().get_result::<i64>(connection)?;
+        Ok(ResultPage { total, records })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+#[post("")]
+pub async fn issue_api_key(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: web::Json<IssueApiKeyRequest>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let request = request.into_inner();
+    let key = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    let new_api_key = NewApiKey {
+        name: request.name,
+        key_hash: secrets::hash(&key),
+        scope: request.scope,
+        created_at: Utc::now().naive_utc(),
+    };
+
+    let (id, new_api_key) = db::run(&pool, move |connection| {
+        let id = new_api_key.insert(connection)?;
+        Ok((id, new_api_key))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(IssuedApiKey {
+        id,
+        name: new_api_key.name,
+        scope: new_api_key.scope,
+        key,
+    }))
+}
+
+#[delete("/{id}")]
+pub async fn revoke_api_key(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    let revoked_count = db::run(&pool, move |connection| {
+        let count = diesel::update(api_keys::table)
+            .filter(api_keys::id.is(id))
+            .filter(api_keys::revoked_at.is_null())
+            .set(api_keys::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(connection)?;
+        Ok(count)
+    })
+    .await?;
+
+    if revoked_count < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
@@ -1,12 +1,14 @@
 use crate::api::v1::util::filters::IntoFilter;
-use crate::db::Pool;
+use crate::config::Config;
+use crate::db::{self, Pool};
+use crate::models::SnapshotGenerationRow;
 use crate::schema::{build_inputs, source_packages};
-use crate::{attestation, web};
+use crate::{attestation, stats, web};
 use actix_web::{HttpResponse, Responder, get};
+use chrono::Utc;
 use diesel::{QueryDsl, RunQueryDsl, SqliteExpressionMethods};
 use in_toto::crypto::PrivateKey;
-use rebuilderd_common::api::v1::FreshnessFilter;
-use rebuilderd_common::errors::Error;
+use rebuilderd_common::api::v1::{FreshnessFilter, SnapshotGeneration};
 use serde_json::json;
 use std::sync::Arc;
 
@@ -21,14 +23,16 @@ pub async fn get_distributions(
     pool: web::Data<Pool>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let distributions = source_packages::table
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(source_packages::distribution)
-        .distinct()
-        .load::<String>(connection.as_mut())
-        .map_err(Error::from)?;
+    let freshness_filter = freshness_filter.into_inner();
+    let distributions = db::run(&pool, move |connection| {
+        let distributions = source_packages::table
+            .filter(freshness_filter.into_filter())
+            .select(source_packages::distribution)
+            .distinct()
+            .load::<String>(connection)?;
+        Ok(distributions)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distributions))
 }
@@ -39,15 +43,18 @@ pub async fn get_distribution_releases(
     distribution: web::Path<String>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let distribution_releases = source_packages::table
-        .filter(source_packages::distribution.is(distribution.into_inner()))
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(source_packages::release)
-        .distinct()
-        .load::<Option<String>>(connection.as_mut())
-        .map_err(Error::from)?;
+    let distribution = distribution.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let distribution_releases = db::run(&pool, move |connection| {
+        let releases = source_packages::table
+            .filter(source_packages::distribution.is(distribution))
+            .filter(freshness_filter.into_filter())
+            .select(source_packages::release)
+            .distinct()
+            .load::<Option<String>>(connection)?;
+        Ok(releases)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distribution_releases))
 }
@@ -58,16 +65,19 @@ pub async fn get_distribution_architectures(
     distribution: web::Path<String>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let distribution_architectures = source_packages::table
-        .inner_join(build_inputs::table)
-        .filter(source_packages::distribution.is(distribution.into_inner()))
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(build_inputs::architecture)
-        .distinct()
-        .load::<String>(connection.as_mut())
-        .map_err(Error::from)?;
+    let distribution = distribution.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let distribution_architectures = db::run(&pool, move |connection| {
+        let architectures = source_packages::table
+            .inner_join(build_inputs::table)
+            .filter(source_packages::distribution.is(distribution))
+            .filter(freshness_filter.into_filter())
+            .select(build_inputs::architecture)
+            .distinct()
+            .load::<String>(connection)?;
+        Ok(architectures)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distribution_architectures))
 }
@@ -78,15 +88,18 @@ pub async fn get_distribution_components(
     distribution: web::Path<String>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let distribution_components = source_packages::table
-        .filter(source_packages::distribution.is(distribution.into_inner()))
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(source_packages::component)
-        .distinct()
-        .load::<Option<String>>(connection.as_mut())
-        .map_err(Error::from)?;
+    let distribution = distribution.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let distribution_components = db::run(&pool, move |connection| {
+        let components = source_packages::table
+            .filter(source_packages::distribution.is(distribution))
+            .filter(freshness_filter.into_filter())
+            .select(source_packages::component)
+            .distinct()
+            .load::<Option<String>>(connection)?;
+        Ok(components)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distribution_components))
 }
@@ -97,18 +110,20 @@ pub async fn get_distribution_release_architectures(
     path: web::Path<(String, String)>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-    let release = derive_release(&path.1);
-
-    let distribution_release_architectures = source_packages::table
-        .inner_join(build_inputs::table)
-        .filter(source_packages::distribution.is(&path.0))
-        .filter(source_packages::release.is(release))
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(build_inputs::architecture)
-        .distinct()
-        .load::<String>(connection.as_mut())
-        .map_err(Error::from)?;
+    let (distribution, release) = path.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let distribution_release_architectures = db::run(&pool, move |connection| {
+        let architectures = source_packages::table
+            .inner_join(build_inputs::table)
+            .filter(source_packages::distribution.is(&distribution))
+            .filter(source_packages::release.is(derive_release(&release)))
+            .filter(freshness_filter.into_filter())
+            .select(build_inputs::architecture)
+            .distinct()
+            .load::<String>(connection)?;
+        Ok(architectures)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distribution_release_architectures))
 }
@@ -119,17 +134,19 @@ pub async fn get_distribution_release_components(
     path: web::Path<(String, String)>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-    let release = derive_release(&path.1);
-
-    let distribution_release_components = source_packages::table
-        .filter(source_packages::distribution.is(&path.0))
-        .filter(source_packages::release.is(release))
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(source_packages::component)
-        .distinct()
-        .load::<Option<String>>(connection.as_mut())
-        .map_err(Error::from)?;
+    let (distribution, release) = path.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let distribution_release_components = db::run(&pool, move |connection| {
+        let components = source_packages::table
+            .filter(source_packages::distribution.is(&distribution))
+            .filter(source_packages::release.is(derive_release(&release)))
+            .filter(freshness_filter.into_filter())
+            .select(source_packages::component)
+            .distinct()
+            .load::<Option<String>>(connection)?;
+        Ok(components)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distribution_release_components))
 }
@@ -140,23 +157,67 @@ pub async fn get_distribution_release_component_architectures(
     path: web::Path<(String, String, String)>,
     freshness_filter: web::Query<FreshnessFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-    let release = derive_release(&path.1);
-
-    let distribution_release_component_architectures = source_packages::table
-        .inner_join(build_inputs::table)
-        .filter(source_packages::distribution.is(&path.0))
-        .filter(source_packages::release.is(release))
-        .filter(source_packages::component.is(&path.2))
-        .filter(freshness_filter.into_inner().into_filter())
-        .select(build_inputs::architecture)
-        .distinct()
-        .load::<String>(connection.as_mut())
-        .map_err(Error::from)?;
+    let (distribution, release, component) = path.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let distribution_release_component_architectures = db::run(&pool, move |connection| {
+        let architectures = source_packages::table
+            .inner_join(build_inputs::table)
+            .filter(source_packages::distribution.is(&distribution))
+            .filter(source_packages::release.is(derive_release(&release)))
+            .filter(source_packages::component.is(&component))
+            .filter(freshness_filter.into_filter())
+            .select(build_inputs::architecture)
+            .distinct()
+            .load::<String>(connection)?;
+        Ok(architectures)
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(distribution_release_component_architectures))
 }
 
+/// Generations that didn't expire yet are counted like the daily stats snapshots
+#[get("/distributions/{distribution}/generations")]
+pub async fn get_snapshot_generations(
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    distribution: web::Path<String>,
+) -> web::Result<impl Responder> {
+    let distribution = distribution.into_inner();
+    let ttl = cfg.schedule.snapshot_ttl(&distribution);
+    let generations = db::run(&pool, move |connection| {
+        let rows = SnapshotGenerationRow::list(&distribution, connection)?;
+        let snapshots = if rows.iter().any(|row| row.expired_at.is_none()) {
+            stats::snapshot(connection, Utc::now().date_naive())?
+        } else {
+            Vec::new()
+        };
+
+        let generations = rows
+            .into_iter()
+            .map(|row| {
+                let (good, bad, unknown) = match (row.good, row.bad, row.unknown) {
+                    (Some(good), Some(bad), Some(unknown)) => (good, bad, unknown),
+                    _ => stats::count_release(&snapshots, &distribution, &row.release),
+                };
+                SnapshotGeneration {
+                    expires_at: ttl.map(|ttl| row.first_synced_at + ttl),
+                    release: row.release,
+                    first_synced_at: row.first_synced_at,
+                    expired_at: row.expired_at,
+                    good: good.into(),
+                    bad: bad.into(),
+                    unknown: unknown.into(),
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(generations)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(generations))
+}
+
 #[get("/public-keys")]
 pub async fn get_public_key(
     private_key: web::Data<Arc<PrivateKey>>,
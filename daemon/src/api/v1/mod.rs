@@ -1,14 +1,24 @@
+mod bisect;
 mod build;
 mod dashboard;
+mod key;
 mod meta;
 mod package;
 mod queue;
+mod schema;
+mod subscription;
 mod util;
 mod worker;
 
+pub use bisect::*;
 pub use build::*;
 pub use dashboard::*;
+pub use key::*;
 pub use meta::*;
 pub use package::*;
 pub use queue::*;
+pub use schema::*;
+pub use subscription::*;
 pub use worker::*;
+
+pub use util::auth::require_worker;
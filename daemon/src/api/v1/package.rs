@@ -1,42 +1,74 @@
 use crate::api::v1::util::auth;
 use crate::api::v1::util::filters::{
-    IntoBinaryIdentityFilter, IntoFilter, IntoOriginFilter, IntoSourceIdentityFilter,
+    IntoBinaryIdentityFilter, IntoCauseFilter, IntoFilter, IntoOriginFilter,
+    IntoSourceIdentityFilter,
 };
 use crate::api::v1::util::friends::{
-    build_input_friends, get_largest_retry_count_among_friends, has_queued_friend,
+    build_input_friends, get_largest_retry_count_among_friends,
     mark_build_input_friends_as_non_retriable,
 };
+use crate::api::v1::util::json_schema::SchemaJson;
 use crate::api::v1::util::pagination::PaginateDsl;
 use crate::config::Config;
-use crate::db::{Pool, SqliteConnectionWrap};
-use crate::models::{BuildInput, NewBinaryPackage, NewBuildInput, NewQueued, NewSourcePackage};
+use crate::db::{self, Pool};
+use crate::models::{
+    ArtifactVerdict, BuildInput, NewArtifactVerdict, NewBinaryPackage, NewBuildDuration,
+    NewBuildInput, NewBuildLog, NewPackageAnnotation, NewPackagePopularity, NewPackageTag,
+    NewQueued, NewRebuild, NewRebuildArtifact, NewSnapshotGeneration, NewSourcePackage,
+    NewSyncImport, NewSyncRevision, RebuildArtifact, SnapshotGenerationRow,
+    drop_unused_sync_import,
+};
 use crate::schema::{
-    binary_packages, build_inputs, queue, rebuild_artifacts, rebuilds, source_packages,
+    artifact_verdicts, binary_packages, build_durations, build_inputs, cross_rebuilds,
+    package_annotations, package_popularity, package_tags, queue, rebuild_artifacts, rebuilds,
+    source_packages, sync_imports, sync_revisions, workers,
 };
 use crate::web;
-use actix_web::{HttpRequest, HttpResponse, Responder, get, post};
+use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, post};
 use aliases::*;
-use chrono::{Duration, Utc};
-use diesel::dsl::{delete, exists, not, select, update};
-use diesel::r2d2::{ConnectionManager, PooledConnection};
-use diesel::sql_types::Integer;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::dsl::{delete, exists, select, sql, update};
+use diesel::expression::SqlLiteral;
+use diesel::sql_types::{Bool, Integer, Nullable, Text};
 use diesel::{
     BoolExpressionMethods, Connection, ExpressionMethods, JoinOnDsl, NullableExpressionMethods,
-    OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection, SqliteExpressionMethods,
+    OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper, SqliteConnection,
+    SqliteExpressionMethods, sql_query,
 };
 use rebuilderd_common::api::v1::{
-    BinaryIdentityFilter, BuildStatus, FreshnessFilter, OriginFilter, PackageReport, Page,
-    Priority, ResultPage, SourceIdentityFilter, SourcePackageReport,
+    ApiKeyScope, ArtifactStatus, BinaryIdentityFilter, BinaryPackageCorrection,
+    BuildDurationReport, BuildStatus, CauseFilter, CrossRebuild, FreshnessFilter, OriginFilter,
+    PackageAnnotation, PackageReport, PackageReportDelta, PackageTag, PackageTagFilter, Page,
+    PopularityReport, Priority, ResultPage, SourceIdentityFilter, SourcePackageReport, TagFilter,
+    TrackerEntry, TrackerStatus, UpstreamRelease, VerdictReport, WorkerEnvironment,
 };
-use rebuilderd_common::errors::Error;
+use rebuilderd_common::config::QueueOverflow;
+use rebuilderd_common::errors::{Error, info};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+const SYNC_BATCH_SIZE: usize = 500;
 
 mod aliases {
     diesel::alias!(crate::schema::rebuilds as r1: RebuildsAlias1, crate::schema::rebuilds as r2: RebuildsAlias2);
     diesel::alias!(crate::schema::source_packages as sp: SourcePackagesAlias);
 }
 
+/// Whether a rebuild of the build input or one of its friends is queued or running, the status of the previous rebuild
+/// is kept until the new one is reported. Cross builds don't affect the status and are ignored.
+const REBUILDING_SQL: &str = "EXISTS (
+    SELECT 1 FROM queue
+    INNER JOIN build_inputs AS friends ON friends.id = queue.build_input_id
+    WHERE queue.build_architecture IS NULL
+    AND friends.url = build_inputs.url
+    AND friends.backend = build_inputs.backend
+    AND friends.architecture = build_inputs.architecture
+)";
+
 #[diesel::dsl::auto_type]
 fn source_packages_base() -> _ {
+    let rebuilding: SqlLiteral<Bool> = sql(REBUILDING_SQL);
     source_packages::table
         .inner_join(build_inputs::table)
         .left_join(r1.on(r1.field(rebuilds::build_input_id).is(build_inputs::id)))
@@ -51,6 +83,14 @@ fn source_packages_base() -> _ {
                     )),
             )),
         )
+        .left_join(
+            package_annotations::table.on(package_annotations::distribution
+                .is(source_packages::distribution)
+                .and(package_annotations::name.is(source_packages::name))),
+        )
+        .left_join(
+            sync_imports::table.on(build_inputs::sync_import_id.is(sync_imports::id.nullable())),
+        )
         .filter(r2.field(rebuilds::id).is_null())
         .select((
             source_packages::id,
@@ -63,11 +103,39 @@ fn source_packages_base() -> _ {
             r1.field(rebuilds::id).nullable(),
             source_packages::last_seen,
             source_packages::seen_in_last_sync,
+            package_annotations::bug_url.nullable(),
+            package_annotations::note.nullable(),
+            source_packages::removed_at,
+            rebuilding,
+            sync_imports::source.nullable(),
+            sync_imports::imported_at.nullable(),
+            sync_imports::revision.nullable(),
+            source_packages::maintainer,
         ))
 }
 
+/// The combined verdict of the debug packages of a binary package in its latest rebuild: BAD if any of them didn't
+/// reproduce, GOOD if all of them did. `NULL` if the package has no debug packages or wasn't rebuilt yet.
+const DEBUG_STATUS_SQL: &str = "(
+    SELECT CASE
+        WHEN COUNT(*) = 0 OR r1.id IS NULL THEN NULL
+        WHEN SUM(debug_artifacts.status = 'BAD') > 0 THEN 'BAD'
+        WHEN SUM(debug_artifacts.status = 'GOOD') = COUNT(*) THEN 'GOOD'
+        ELSE 'UNKWN'
+    END
+    FROM binary_packages AS debug_packages
+    LEFT JOIN rebuild_artifacts AS debug_artifacts
+        ON debug_artifacts.rebuild_id = r1.id AND debug_artifacts.name = debug_packages.name
+    WHERE debug_packages.parent_id = binary_packages.id
+)";
+
+/// Suffixes distributions name the package with the debug symbols of a binary package with, eg. `foo-dbgsym`
+const DEBUG_PACKAGE_SUFFIXES: &[&str] = &["-dbgsym", "-debuginfo", "-debug"];
+
 #[diesel::dsl::auto_type]
 fn binary_packages_base() -> _ {
+    let rebuilding: SqlLiteral<Bool> = sql(REBUILDING_SQL);
+    let debug_status: SqlLiteral<Nullable<Text>> = sql(DEBUG_STATUS_SQL);
     binary_packages::table
         .inner_join(source_packages::table)
         .inner_join(build_inputs::table)
@@ -88,6 +156,14 @@ fn binary_packages_base() -> _ {
                     )),
             )),
         )
+        .left_join(
+            package_annotations::table.on(package_annotations::distribution
+                .is(source_packages::distribution)
+                .and(package_annotations::name.is(source_packages::name))),
+        )
+        .left_join(
+            sync_imports::table.on(build_inputs::sync_import_id.is(sync_imports::id.nullable())),
+        )
         .filter(r2.field(rebuilds::id).is_null())
         .select((
             binary_packages::id,
@@ -105,6 +181,16 @@ fn binary_packages_base() -> _ {
             rebuild_artifacts::attestation_log_id.nullable(),
             source_packages::last_seen,
             source_packages::seen_in_last_sync,
+            package_annotations::bug_url.nullable(),
+            package_annotations::note.nullable(),
+            source_packages::removed_at,
+            rebuilding,
+            binary_packages::parent_id,
+            debug_status,
+            sync_imports::source.nullable(),
+            sync_imports::imported_at.nullable(),
+            sync_imports::revision.nullable(),
+            rebuild_artifacts::likely_causes.nullable(),
         ))
 }
 
@@ -115,7 +201,7 @@ fn binary_packages_base() -> _ {
 /// runs, which will flip the flag back to true for seen packages.
 fn mark_scoped_packages_unseen(
     connection: &mut SqliteConnection,
-    report: &PackageReport,
+    scope: &SyncScope,
 ) -> Result<(), Error> {
     // mark all packages potentially affected by this report as unseen
     update(source_packages::table)
@@ -129,11 +215,11 @@ fn mark_scoped_packages_unseen(
                     )
                     .filter(
                         sp.field(source_packages::distribution)
-                            .is(&report.distribution),
+                            .is(scope.distribution),
                     )
-                    .filter(sp.field(source_packages::release).is(&report.release))
-                    .filter(sp.field(source_packages::component).is(&report.component))
-                    .filter(build_inputs::architecture.is(&report.architecture))
+                    .filter(sp.field(source_packages::release).is(scope.release))
+                    .filter(sp.field(source_packages::component).is(scope.component))
+                    .filter(build_inputs::architecture.is(scope.architecture))
                     .group_by(sp.field(source_packages::id))
                     .select(sp.field(source_packages::id)),
             ),
@@ -153,17 +239,17 @@ fn mark_scoped_packages_unseen(
 /// are unaffected, however.
 fn drop_unseen_scoped_jobs(
     connection: &mut SqliteConnection,
-    report: &PackageReport,
+    scope: &SyncScope,
 ) -> Result<(), Error> {
     delete(
         queue::table.filter(queue::worker.is_null()).filter(
             queue::build_input_id.eq_any(
                 build_inputs::table
                     .inner_join(source_packages::table)
-                    .filter(source_packages::distribution.is(&report.distribution))
-                    .filter(source_packages::release.is(&report.release))
-                    .filter(source_packages::component.is(&report.component))
-                    .filter(build_inputs::architecture.is(&report.architecture))
+                    .filter(source_packages::distribution.is(scope.distribution))
+                    .filter(source_packages::release.is(scope.release))
+                    .filter(source_packages::component.is(scope.component))
+                    .filter(build_inputs::architecture.is(scope.architecture))
                     .filter(source_packages::seen_in_last_sync.is(false))
                     .group_by(build_inputs::id)
                     .select(build_inputs::id),
@@ -176,146 +262,1148 @@ fn drop_unseen_scoped_jobs(
     Ok(())
 }
 
-#[post("")]
+/// Records when source packages potentially affected by the given report were
+/// dropped from their suite. The rows are kept as tombstones, so their last
+/// rebuild status remains available.
+///
+/// Packages that were only replaced by a new version are not considered
+/// removed. The timestamp is cleared again if a later sync has the package.
+fn mark_unseen_scoped_packages_removed(
+    connection: &mut SqliteConnection,
+    scope: &SyncScope,
+    now: NaiveDateTime,
+) -> Result<(), Error> {
+    update(source_packages::table)
+        .filter(
+            source_packages::id.eq_any(
+                build_inputs::table
+                    .filter(build_inputs::architecture.is(scope.architecture))
+                    .select(build_inputs::source_package_id),
+            ),
+        )
+        .filter(source_packages::distribution.is(scope.distribution))
+        .filter(source_packages::release.is(scope.release))
+        .filter(source_packages::component.is(scope.component))
+        .filter(source_packages::seen_in_last_sync.is(false))
+        .filter(source_packages::removed_at.is_null())
+        .filter(
+            source_packages::name.ne_all(
+                sp.filter(
+                    sp.field(source_packages::distribution)
+                        .is(scope.distribution),
+                )
+                .filter(sp.field(source_packages::release).is(scope.release))
+                .filter(sp.field(source_packages::component).is(scope.component))
+                .filter(sp.field(source_packages::seen_in_last_sync).is(true))
+                .select(sp.field(source_packages::name)),
+            ),
+        )
+        .set(source_packages::removed_at.eq(now))
+        .execute(connection)
+        .map_err(Error::from)?;
+
+    Ok(())
+}
+
+/// The suite and architecture a package sync is authoritative for
+struct SyncScope<'a> {
+    distribution: &'a String,
+    release: &'a Option<String>,
+    component: &'a Option<String>,
+    architecture: &'a String,
+}
+
+impl<'a> From<&'a PackageReport> for SyncScope<'a> {
+    fn from(report: &'a PackageReport) -> Self {
+        SyncScope {
+            distribution: &report.distribution,
+            release: &report.release,
+            component: &report.component,
+            architecture: &report.architecture,
+        }
+    }
+}
+
+impl<'a> From<&'a PackageReportDelta> for SyncScope<'a> {
+    fn from(delta: &'a PackageReportDelta) -> Self {
+        SyncScope {
+            distribution: &delta.distribution,
+            release: &delta.release,
+            component: &delta.component,
+            architecture: &delta.architecture,
+        }
+    }
+}
+
+fn get_sync_revision(
+    connection: &mut SqliteConnection,
+    scope: &SyncScope,
+) -> Result<Option<String>, Error> {
+    let revision = sync_revisions::table
+        .filter(sync_revisions::distribution.is(scope.distribution))
+        .filter(sync_revisions::release.is(scope.release))
+        .filter(sync_revisions::component.is(scope.component))
+        .filter(sync_revisions::architecture.is(scope.architecture))
+        .select(sync_revisions::revision)
+        .get_result::<String>(connection)
+        .optional()?;
+
+    Ok(revision)
+}
+
+/// Records where the package list of this sync came from, so build inputs it introduces can be traced back to the
+/// mirror state they were imported from
+fn record_sync_import(
+    connection: &mut SqliteConnection,
+    scope: &SyncScope,
+    revision: &str,
+    source: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<i32, Error> {
+    NewSyncImport {
+        distribution: scope.distribution.clone(),
+        release: scope.release.clone(),
+        component: scope.component.clone(),
+        architecture: scope.architecture.clone(),
+        source,
+        revision: revision.to_string(),
+        imported_at: now.naive_utc(),
+    }
+    .insert(connection)
+}
+
+fn set_sync_revision(
+    connection: &mut SqliteConnection,
+    scope: &SyncScope,
+    revision: String,
+    source: Option<String>,
+    now: NaiveDateTime,
+) -> Result<(), Error> {
+    NewSyncRevision {
+        distribution: scope.distribution.clone(),
+        release: scope.release.clone(),
+        component: scope.component.clone(),
+        architecture: scope.architecture.clone(),
+        revision,
+        updated_at: now,
+        source,
+    }
+    .replace(connection)
+}
+
+/// Marks all versions of the given source packages in the scope as unseen, before a delta sync upserts the versions
+/// that are still current
+fn mark_named_packages_unseen(
+    connection: &mut SqliteConnection,
+    scope: &SyncScope,
+    names: &[String],
+) -> Result<(), Error> {
+    update(source_packages::table)
+        .filter(
+            source_packages::id.eq_any(
+                build_inputs::table
+                    .filter(build_inputs::architecture.is(scope.architecture))
+                    .select(build_inputs::source_package_id),
+            ),
+        )
+        .filter(source_packages::distribution.is(scope.distribution))
+        .filter(source_packages::release.is(scope.release))
+        .filter(source_packages::component.is(scope.component))
+        .filter(source_packages::name.eq_any(names))
+        .set(source_packages::seen_in_last_sync.eq(false))
+        .execute(connection)
+        .map_err(Error::from)?;
+
+    Ok(())
+}
+
+/// Why a sync of a snapshot distribution is rejected
+enum SnapshotRejection {
+    MissingRelease,
+    Expired(String),
+}
+
+impl SnapshotRejection {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            SnapshotRejection::MissingRelease => HttpResponse::BadRequest()
+                .body("Snapshot distributions need a release that names the generation"),
+            SnapshotRejection::Expired(release) => HttpResponse::Conflict()
+                .body(format!("Snapshot generation {release:?} has expired")),
+        }
+    }
+}
+
+/// Every release of a snapshot distribution is a generation of the archive. An expired generation doesn't come back,
+/// its packages stay removed.
+fn check_snapshot_sync(
+    connection: &mut SqliteConnection,
+    cfg: &Config,
+    scope: &SyncScope,
+) -> Result<Option<SnapshotRejection>, Error> {
+    if cfg.schedule.snapshot_ttl(scope.distribution).is_none() {
+        return Ok(None);
+    }
+    let Some(release) = scope.release else {
+        return Ok(Some(SnapshotRejection::MissingRelease));
+    };
+
+    let generation = SnapshotGenerationRow::find(scope.distribution, release, connection)?;
+    if generation.is_some_and(|generation| generation.expired_at.is_some()) {
+        return Ok(Some(SnapshotRejection::Expired(release.clone())));
+    }
+
+    Ok(None)
+}
+
+/// The first sync of a generation starts its ttl
+fn record_snapshot_generation(
+    connection: &mut SqliteConnection,
+    cfg: &Config,
+    scope: &SyncScope,
+    now: NaiveDateTime,
+) -> Result<(), Error> {
+    if cfg.schedule.snapshot_ttl(scope.distribution).is_none() {
+        return Ok(());
+    }
+    let Some(release) = scope.release else {
+        return Ok(());
+    };
+
+    NewSnapshotGeneration {
+        distribution: scope.distribution.clone(),
+        release: release.clone(),
+        first_synced_at: now,
+    }
+    .insert_or_ignore(connection)
+}
+
 pub async fn submit_package_report(
     req: HttpRequest,
     cfg: web::Data<Config>,
     pool: web::Data<Pool>,
-    request: web::Json<PackageReport>,
+    request: SchemaJson<PackageReport>,
 ) -> web::Result<impl Responder> {
-    if auth::admin(&cfg, &req).is_err() {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Sync)
+        .await
+        .is_err()
+    {
         return Ok(HttpResponse::Forbidden().finish());
     }
 
-    let mut connection = pool.get().map_err(Error::from)?;
+    let report = request.into_inner();
+    let cfg = cfg.into_inner();
+    let rejection = db::run(&pool, move |connection| {
+        if let Some(rejection) = check_snapshot_sync(connection, &cfg, &SyncScope::from(&report))? {
+            return Ok(Some(rejection));
+        }
+        import_package_report(connection, &cfg, &report)?;
+        Ok(None)
+    })
+    .await?;
+
+    if let Some(rejection) = rejection {
+        return Ok(rejection.into_response());
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn submit_package_report_delta(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: SchemaJson<PackageReportDelta>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Sync)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let delta = request.into_inner();
+    let cfg = cfg.into_inner();
+    let applied = db::run(&pool, move |connection| {
+        if let Some(rejection) = check_snapshot_sync(connection, &cfg, &SyncScope::from(&delta))? {
+            return Ok(Err(rejection));
+        }
+        import_package_report_delta(connection, &cfg, &delta).map(Ok)
+    })
+    .await?;
+
+    match applied {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => {
+            Ok(HttpResponse::Conflict().body("Base revision does not match, submit a full report"))
+        }
+        Err(rejection) => Ok(rejection.into_response()),
+    }
+}
+
+pub async fn submit_package_popularity(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: SchemaJson<PopularityReport>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Sync)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
 
-    let now = Utc::now();
     let report = request.into_inner();
-    connection.transaction(|conn| {
-        mark_scoped_packages_unseen(conn.as_mut(), &report)?;
+    db::run(&pool, move |connection| {
+        import_popularity_report(connection, report)
+    })
+    .await?;
 
-        for package_report in &report.packages {
-            // check if this package already exists - this is used later to determine if we should copy over existing build
-            // results to this package.
-            let is_new_package = is_new_package(&report, conn, package_report)?;
+    Ok(HttpResponse::NoContent().finish())
+}
 
-            let new_source_package = NewSourcePackage {
-                name: package_report.name.clone(),
-                version: package_report.version.clone(),
+fn import_popularity_report(
+    connection: &mut SqliteConnection,
+    report: PopularityReport,
+) -> Result<(), Error> {
+    connection.transaction(|conn| {
+        delete(
+            package_popularity::table
+                .filter(package_popularity::distribution.eq(&report.distribution)),
+        )
+        .execute(conn)?;
+
+        let scores = report
+            .packages
+            .into_iter()
+            .map(|(name, score)| NewPackagePopularity {
                 distribution: report.distribution.clone(),
-                release: report.release.clone(),
-                component: report.component.clone(),
-                last_seen: now.naive_utc(),
-                seen_in_last_sync: true,
-            };
+                name,
+                score,
+            })
+            .collect::<Vec<_>>();
+        for chunk in scores.chunks(SYNC_BATCH_SIZE) {
+            NewPackagePopularity::insert_batch(chunk, conn)?;
+        }
 
-            let source_package = new_source_package.upsert(conn.as_mut())?;
+        let updated = sql_query(
+            "UPDATE build_inputs SET popularity = (
+                SELECT MAX(package_popularity.score) FROM binary_packages
+                INNER JOIN package_popularity ON package_popularity.name = binary_packages.name
+                WHERE binary_packages.build_input_id = build_inputs.id
+                AND package_popularity.distribution = ?1
+            )
+            WHERE source_package_id IN (SELECT id FROM source_packages WHERE distribution = ?1)",
+        )
+        .bind::<Text, _>(&report.distribution)
+        .execute(conn)?;
 
-            // None means we don't have a specific limitation on when the next retry (or first try, as the case may be)
-            // is. Any worker can pick it up, as long as it's eligible for build.
-            let next_retry = if cfg.schedule.initial_delay() != Duration::seconds(0) {
-                let delay_until = now + cfg.schedule.initial_delay();
-                Some(delay_until.naive_utc())
-            } else {
-                None
-            };
+        info!(
+            "Imported {} popularity scores of {:?}, updated {updated} build inputs",
+            scores.len(),
+            report.distribution
+        );
 
-            let new_build_input = NewBuildInput {
-                source_package_id: source_package.id,
-                url: package_report.url.clone(),
-                backend: report.distribution.clone(),
-                architecture: report.architecture.clone(),
-                retries: 0,
-                next_retry,
-            };
+        Ok::<(), Error>(())
+    })
+}
 
-            let build_input = new_build_input.upsert(conn.as_mut())?;
+pub async fn submit_build_durations(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: SchemaJson<BuildDurationReport>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Sync)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
 
-            for artifact_report in &package_report.artifacts {
-                let new_binary_package = NewBinaryPackage {
-                    source_package_id: source_package.id,
-                    build_input_id: build_input.id,
-                    name: artifact_report.name.clone(),
-                    version: artifact_report.version.clone(),
-                    architecture: report.architecture.clone(),
-                    artifact_url: artifact_report.url.clone(),
-                };
+    let report = request.into_inner();
+    db::run(&pool, move |connection| {
+        import_build_durations(connection, report)
+    })
+    .await?;
 
-                new_binary_package.upsert(conn.as_mut())?;
-            }
+    Ok(HttpResponse::NoContent().finish())
+}
 
-            if is_new_package {
-                // in order to avoid additional rebuilds in distributions that copy existing packages between releases, we
-                // want to also copy any results relevant to newly-imported versions. This only applies within a single
-                // build backend and matches on the URL of the input artifact and its architecture.
-                copy_existing_rebuilds(conn, &build_input)?;
-            }
+fn import_build_durations(
+    connection: &mut SqliteConnection,
+    report: BuildDurationReport,
+) -> Result<(), Error> {
+    connection.transaction(|conn| {
+        delete(
+            build_durations::table
+                .filter(build_durations::distribution.eq(&report.distribution))
+                .filter(build_durations::architecture.eq(&report.architecture))
+                .filter(build_durations::measured.eq(false)),
+        )
+        .execute(conn)?;
 
-            let current_status = get_current_rebuild_status(conn, &build_input)?;
-            let has_queued_friend = has_queued_friend(conn.as_mut(), build_input.id)?;
+        let hints = report
+            .packages
+            .into_iter()
+            .map(|(name, seconds)| NewBuildDuration {
+                distribution: report.distribution.clone(),
+                name,
+                architecture: report.architecture.clone(),
+                seconds,
+                measured: false,
+            })
+            .collect::<Vec<_>>();
+        for chunk in hints.chunks(SYNC_BATCH_SIZE) {
+            NewBuildDuration::insert_hints(chunk, conn)?;
+        }
 
-            if current_status != BuildStatus::Good && !has_queued_friend {
-                let retry_count =
-                    get_largest_retry_count_among_friends(conn.as_mut(), build_input.id)?;
+        info!(
+            "Imported {} build duration hints of {:?} on {:?}",
+            hints.len(),
+            report.distribution,
+            report.architecture
+        );
 
-                // bail if we have a max retry count set and requeueing this package would exceed it
-                if let Some(max_retries) = cfg.schedule.max_retries()
-                    && retry_count >= max_retries
-                {
-                    mark_build_input_friends_as_non_retriable(conn.as_mut(), build_input.id)?;
-                    continue;
-                }
+        Ok::<(), Error>(())
+    })
+}
 
-                let priority = match current_status {
-                    BuildStatus::Bad => Priority::retry(),
-                    _ => Priority::default(),
-                };
+pub async fn submit_verdicts(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: SchemaJson<VerdictReport>,
+) -> web::Result<impl Responder> {
+    // verdicts of peers mark packages as reproduced without a build, this needs more than a sync key
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
 
-                let new_queued_job = NewQueued {
-                    build_input_id: build_input.id,
-                    priority,
-                    queued_at: now.naive_utc(),
-                };
+    let report = request.into_inner();
+    db::run(&pool, move |connection| import_verdicts(connection, report)).await?;
 
-                new_queued_job.upsert(conn.as_mut())?;
-            }
-        }
+    Ok(HttpResponse::NoContent().finish())
+}
 
-        drop_unseen_scoped_jobs(conn.as_mut(), &report)?;
+fn import_verdicts(connection: &mut SqliteConnection, report: VerdictReport) -> Result<(), Error> {
+    let verdicts = report
+        .verdicts
+        .into_iter()
+        .filter(|verdict| matches!(verdict.status, ArtifactStatus::Good | ArtifactStatus::Bad))
+        .map(|verdict| NewArtifactVerdict {
+            checksum: verdict.checksum,
+            script_version: verdict.script_version,
+            status: verdict.status.as_str().to_string(),
+            rebuild_artifact_id: None,
+            origin: Some(report.origin.clone()),
+            verified_at: verdict.verified_at,
+        })
+        .collect::<Vec<_>>();
 
+    connection.transaction(|conn| {
+        for chunk in verdicts.chunks(SYNC_BATCH_SIZE) {
+            NewArtifactVerdict::insert_imported(chunk, conn)?;
+        }
         Ok::<(), Error>(())
     })?;
 
-    Ok(HttpResponse::NoContent().finish())
+    info!(
+        "Imported {} artifact verdicts from {:?}",
+        verdicts.len(),
+        report.origin
+    );
+
+    Ok(())
+}
+
+/// Build inputs are as popular as their most popular binary package, scores of new packages are looked up after
+/// every sync instead of waiting for the next popularity report
+fn apply_popularity(conn: &mut SqliteConnection, scope: &SyncScope) -> Result<(), Error> {
+    sql_query(
+        "UPDATE build_inputs SET popularity = (
+            SELECT MAX(package_popularity.score) FROM binary_packages
+            INNER JOIN package_popularity ON package_popularity.name = binary_packages.name
+            WHERE binary_packages.build_input_id = build_inputs.id
+            AND package_popularity.distribution = ?1
+        )
+        WHERE architecture = ?4 AND source_package_id IN (
+            SELECT id FROM source_packages WHERE distribution = ?1 AND release IS ?2 AND component IS ?3
+        )",
+    )
+    .bind::<Text, _>(scope.distribution)
+    .bind::<Nullable<Text>, _>(scope.release)
+    .bind::<Nullable<Text>, _>(scope.component)
+    .bind::<Text, _>(scope.architecture)
+    .execute(conn)?;
+
+    Ok(())
 }
 
-fn is_new_package(
+fn import_package_report(
+    connection: &mut SqliteConnection,
+    cfg: &Config,
     report: &PackageReport,
-    conn: &mut PooledConnection<ConnectionManager<SqliteConnectionWrap>>,
-    source_package_report: &SourcePackageReport,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let scope = SyncScope::from(report);
+    let revision = report.revision();
+    connection.transaction(|conn| {
+        record_snapshot_generation(conn, cfg, &scope, now.naive_utc())?;
+        mark_scoped_packages_unseen(conn, &scope)?;
+
+        let sync_import_id =
+            record_sync_import(conn, &scope, &revision, report.source.clone(), now)?;
+        let new_build_inputs =
+            import_source_packages(conn, cfg, &scope, &report.packages, sync_import_id, now)?;
+        drop_unused_sync_import(conn, sync_import_id)?;
+        apply_popularity(conn, &scope)?;
+
+        drop_unseen_scoped_jobs(conn, &scope)?;
+        enforce_queue_limit(conn, cfg, &scope, &new_build_inputs)?;
+        mark_unseen_scoped_packages_removed(conn, &scope, now.naive_utc())?;
+        set_sync_revision(
+            conn,
+            &scope,
+            revision,
+            report.source.clone(),
+            now.naive_utc(),
+        )?;
+
+        Ok::<(), Error>(())
+    })
+}
+
+/// Applies the delta if it's based on the revision of the last sync of the scope. Packages that aren't mentioned
+/// keep the state of the last sync.
+fn import_package_report_delta(
+    connection: &mut SqliteConnection,
+    cfg: &Config,
+    delta: &PackageReportDelta,
 ) -> Result<bool, Error> {
-    let is_new_package = select(not(exists(
-        source_packages::table
-            .filter(source_packages::name.is(&source_package_report.name))
-            .filter(source_packages::version.is(&source_package_report.version))
-            .filter(source_packages::distribution.is(&report.distribution))
-            .filter(source_packages::release.is(&report.release))
-            .filter(source_packages::component.is(&report.component)),
-    )))
-    .get_result::<bool>(conn.as_mut())?;
+    let now = Utc::now();
+    let scope = SyncScope::from(delta);
+    connection.transaction(|conn| {
+        if get_sync_revision(conn, &scope)?.as_ref() != Some(&delta.base_revision) {
+            return Ok(false);
+        }
+        record_snapshot_generation(conn, cfg, &scope, now.naive_utc())?;
+
+        let names = delta
+            .updated
+            .iter()
+            .map(|package| package.name.clone())
+            .chain(delta.removed.iter().cloned())
+            .collect::<Vec<_>>();
+        mark_named_packages_unseen(conn, &scope, &names)?;
+
+        let sync_import_id =
+            record_sync_import(conn, &scope, &delta.revision, delta.source.clone(), now)?;
+        let packages = delta.added.iter().chain(&delta.updated);
+        let new_build_inputs =
+            import_source_packages(conn, cfg, &scope, packages, sync_import_id, now)?;
+        drop_unused_sync_import(conn, sync_import_id)?;
+        apply_popularity(conn, &scope)?;
+
+        drop_unseen_scoped_jobs(conn, &scope)?;
+        enforce_queue_limit(conn, cfg, &scope, &new_build_inputs)?;
+        mark_unseen_scoped_packages_removed(conn, &scope, now.naive_utc())?;
+        set_sync_revision(
+            conn,
+            &scope,
+            delta.revision.clone(),
+            delta.source.clone(),
+            now.naive_utc(),
+        )?;
+
+        Ok::<bool, Error>(true)
+    })
+}
+
+/// The state of a sync scope before the import, loaded with a few queries up front so packages that didn't change
+/// since the last sync don't need any queries of their own
+struct ScopeIndex {
+    /// Maintainers of the source packages of the suite, keyed by name and version
+    source_packages: HashMap<(String, String), Option<String>>,
+    /// Build inputs of the scope architecture, keyed by name, version and url of their source package
+    build_inputs: HashMap<(String, String, String), BuildInput>,
+    /// Name, version and checksum of the binary packages of each build input
+    binary_packages: HashMap<i32, HashSet<(String, String, Option<String>)>>,
+    /// Status of the most recent rebuild of each build input
+    statuses: HashMap<i32, BuildStatus>,
+    /// Url and backend of build inputs of the scope architecture that have a queued job, friends share these
+    queued: HashSet<(String, String)>,
+    /// Digests of the rebuilder script the trusted workers currently run for the distribution
+    script_versions: Vec<String>,
+}
+
+impl ScopeIndex {
+    fn load(connection: &mut SqliteConnection, scope: &SyncScope) -> Result<Self, Error> {
+        let source_packages = source_packages::table
+            .filter(source_packages::distribution.is(scope.distribution))
+            .filter(source_packages::release.is(scope.release))
+            .filter(source_packages::component.is(scope.component))
+            .select((
+                source_packages::name,
+                source_packages::version,
+                source_packages::maintainer,
+            ))
+            .load::<(String, String, Option<String>)>(connection)?
+            .into_iter()
+            .map(|(name, version, maintainer)| ((name, version), maintainer))
+            .collect();
+
+        let build_inputs = build_inputs::table
+            .inner_join(source_packages::table)
+            .filter(source_packages::distribution.is(scope.distribution))
+            .filter(source_packages::release.is(scope.release))
+            .filter(source_packages::component.is(scope.component))
+            .filter(build_inputs::backend.is(scope.distribution))
+            .filter(build_inputs::architecture.is(scope.architecture))
+            .select((
+                source_packages::name,
+                source_packages::version,
+                BuildInput::as_select(),
+            ))
+            .load::<(String, String, BuildInput)>(connection)?
+            .into_iter()
+            .map(|(name, version, build_input)| {
+                ((name, version, build_input.url.clone()), build_input)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut binary_packages = HashMap::<_, HashSet<_>>::new();
+        for (build_input_id, name, version, checksum) in binary_packages::table
+            .filter(binary_packages::architecture.is(scope.architecture))
+            .filter(
+                binary_packages::source_package_id.eq_any(
+                    source_packages::table
+                        .filter(source_packages::distribution.is(scope.distribution))
+                        .filter(source_packages::release.is(scope.release))
+                        .filter(source_packages::component.is(scope.component))
+                        .select(source_packages::id),
+                ),
+            )
+            .select((
+                binary_packages::build_input_id,
+                binary_packages::name,
+                binary_packages::version,
+                binary_packages::checksum,
+            ))
+            .load::<(i32, String, String, Option<String>)>(connection)?
+        {
+            binary_packages
+                .entry(build_input_id)
+                .or_default()
+                .insert((name, version, checksum));
+        }
+
+        // ordered by build time, so the most recent rebuild of a build input is the one that's kept
+        let mut statuses = HashMap::new();
+        for (build_input_id, status) in rebuilds::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(source_packages::distribution.is(scope.distribution))
+            .filter(source_packages::release.is(scope.release))
+            .filter(source_packages::component.is(scope.component))
+            .filter(build_inputs::architecture.is(scope.architecture))
+            .order_by(rebuilds::built_at)
+            .select((rebuilds::build_input_id, rebuilds::status))
+            .load::<(i32, Option<BuildStatus>)>(connection)?
+        {
+            statuses.insert(build_input_id, status.unwrap_or(BuildStatus::Unknown));
+        }
+
+        let queued = queue::table
+            .inner_join(build_inputs::table)
+            .filter(build_inputs::architecture.is(scope.architecture))
+            .select((build_inputs::url, build_inputs::backend))
+            .load::<(String, String)>(connection)?
+            .into_iter()
+            .collect();
+
+        let mut script_versions = workers::table
+            .filter(workers::trusted.eq(true))
+            .select(workers::environment)
+            .load::<Option<WorkerEnvironment>>(connection)?
+            .into_iter()
+            .flatten()
+            .filter_map(|environment| environment.scripts.get(scope.distribution).cloned())
+            .collect::<Vec<_>>();
+        script_versions.sort();
+        script_versions.dedup();
+
+        Ok(ScopeIndex {
+            source_packages,
+            build_inputs,
+            binary_packages,
+            statuses,
+            queued,
+            script_versions,
+        })
+    }
+
+    /// The build input of a package that is already known with the same url, maintainer and artifacts
+    fn unchanged(&self, package_report: &SourcePackageReport) -> Option<&BuildInput> {
+        let maintainer = self
+            .source_packages
+            .get(&(package_report.name.clone(), package_report.version.clone()))?;
+        if *maintainer != package_report.maintainer {
+            return None;
+        }
+
+        let key = (
+            package_report.name.clone(),
+            package_report.version.clone(),
+            package_report.url.clone(),
+        );
+        let build_input = self.build_inputs.get(&key)?;
+        let binary_packages = self.binary_packages.get(&build_input.id);
+
+        let unchanged = package_report.artifacts.iter().all(|artifact_report| {
+            binary_packages.is_some_and(|binary_packages| {
+                binary_packages.contains(&(
+                    artifact_report.name.clone(),
+                    artifact_report.version.clone(),
+                    artifact_report.checksum.clone(),
+                ))
+            })
+        });
+
+        unchanged.then_some(build_input)
+    }
+}
+
+fn import_source_packages<'a>(
+    conn: &mut SqliteConnection,
+    cfg: &Config,
+    scope: &SyncScope,
+    packages: impl IntoIterator<Item = &'a SourcePackageReport>,
+    sync_import_id: i32,
+    now: DateTime<Utc>,
+) -> Result<Vec<i32>, Error> {
+    let mut index = ScopeIndex::load(conn, scope)?;
+
+    let mut unchanged = Vec::new();
+    let mut new_queued_jobs = Vec::new();
+    for package_report in packages {
+        let build_input = match index.unchanged(package_report).cloned() {
+            Some(build_input) => {
+                unchanged.push(build_input.source_package_id);
+                build_input
+            }
+            None => import_source_package(
+                conn,
+                cfg,
+                scope,
+                &mut index,
+                package_report,
+                sync_import_id,
+                now,
+            )?,
+        };
+
+        if let Some(new_queued_job) = queue_build_input(conn, cfg, &mut index, &build_input, now)? {
+            new_queued_jobs.push(new_queued_job);
+        }
+    }
+
+    for chunk in unchanged.chunks(SYNC_BATCH_SIZE) {
+        update(source_packages::table)
+            .filter(source_packages::id.eq_any(chunk))
+            .set((
+                source_packages::last_seen.eq(now.naive_utc()),
+                source_packages::seen_in_last_sync.eq(true),
+                source_packages::removed_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(conn)?;
+    }
+
+    for chunk in new_queued_jobs.chunks(SYNC_BATCH_SIZE) {
+        NewQueued::upsert_batch(chunk, conn)?;
+    }
+
+    Ok(new_queued_jobs
+        .into_iter()
+        .map(|job| job.build_input_id)
+        .collect())
+}
+
+/// Trims the queue of the suite to its configured maximum length, after jobs of packages that left the suite were
+/// dropped. Running and pinned jobs are never dropped.
+fn enforce_queue_limit(
+    conn: &mut SqliteConnection,
+    cfg: &Config,
+    scope: &SyncScope,
+    new_build_inputs: &[i32],
+) -> Result<(), Error> {
+    let Some(max_len) = cfg
+        .schedule
+        .max_queue_len(scope.distribution, scope.release.as_deref())
+    else {
+        return Ok(());
+    };
+
+    let suite_queue = queue::table
+        .inner_join(build_inputs::table.inner_join(source_packages::table))
+        .filter(source_packages::distribution.is(scope.distribution))
+        .filter(source_packages::release.is(scope.release));
+
+    let len = suite_queue.count().get_result::<i64>(conn)? as usize;
+    if len <= max_len {
+        return Ok(());
+    }
+    let excess = len - max_len;
+
+    let candidates = suite_queue
+        .filter(queue::worker.is_null())
+        .filter(queue::pinned_worker.is_null())
+        .select((queue::id, queue::build_input_id))
+        .order_by((
+            queue::priority.desc(),
+            queue::queued_at.desc(),
+            queue::id.desc(),
+        ))
+        .load::<(i32, i32)>(conn)?;
+
+    let skip_only = cfg.schedule.queue_overflow() == QueueOverflow::Skip;
+    let new_build_inputs = new_build_inputs.iter().collect::<HashSet<_>>();
+    let dropped = candidates
+        .into_iter()
+        .filter(|(_, build_input_id)| !skip_only || new_build_inputs.contains(build_input_id))
+        .map(|(id, _)| id)
+        .take(excess)
+        .collect::<Vec<_>>();
+
+    for chunk in dropped.chunks(SYNC_BATCH_SIZE) {
+        delete(queue::table.filter(queue::id.eq_any(chunk))).execute(conn)?;
+    }
+
+    if !dropped.is_empty() {
+        info!(
+            "Queue of {}/{} is full ({len} of {max_len} jobs), dropped {} jobs",
+            scope.distribution,
+            scope.release.as_deref().unwrap_or("-"),
+            dropped.len()
+        );
+    }
+
+    Ok(())
+}
+
+fn import_source_package(
+    conn: &mut SqliteConnection,
+    cfg: &Config,
+    scope: &SyncScope,
+    index: &mut ScopeIndex,
+    package_report: &SourcePackageReport,
+    sync_import_id: i32,
+    now: DateTime<Utc>,
+) -> Result<BuildInput, Error> {
+    // check if this package already exists - this is used later to determine if we should copy over existing build
+    // results to this package.
+    let is_new_package = index
+        .source_packages
+        .insert(
+            (package_report.name.clone(), package_report.version.clone()),
+            package_report.maintainer.clone(),
+        )
+        .is_none();
+
+    let new_source_package = NewSourcePackage {
+        name: package_report.name.clone(),
+        version: package_report.version.clone(),
+        distribution: scope.distribution.clone(),
+        release: scope.release.clone(),
+        component: scope.component.clone(),
+        last_seen: now.naive_utc(),
+        seen_in_last_sync: true,
+        removed_at: None,
+        maintainer: package_report.maintainer.clone(),
+    };
+
+    let source_package = new_source_package.upsert(conn)?;
 
-    Ok(is_new_package)
+    // None means we don't have a specific limitation on when the next retry (or first try, as the case may be)
+    // is. Any worker can pick it up, as long as it's eligible for build.
+    let next_retry = if cfg.schedule.initial_delay() != Duration::seconds(0) {
+        let delay_until = now + cfg.schedule.initial_delay();
+        Some(delay_until.naive_utc())
+    } else {
+        None
+    };
+
+    let new_build_input = NewBuildInput {
+        source_package_id: source_package.id,
+        url: package_report.url.clone(),
+        backend: scope.distribution.clone(),
+        architecture: scope.architecture.clone(),
+        retries: 0,
+        next_retry,
+        payload: package_report.payload.clone(),
+        sync_import_id: Some(sync_import_id),
+    };
+
+    let build_input = new_build_input.upsert(conn)?;
+
+    for artifact_report in &package_report.artifacts {
+        let new_binary_package = NewBinaryPackage {
+            source_package_id: source_package.id,
+            build_input_id: build_input.id,
+            name: artifact_report.name.clone(),
+            version: artifact_report.version.clone(),
+            architecture: scope.architecture.clone(),
+            artifact_url: artifact_report.url.clone(),
+            checksum: artifact_report.checksum.clone(),
+        };
+
+        new_binary_package.upsert(conn)?;
+    }
+    link_debug_packages(conn, build_input.id)?;
+
+    if is_new_package {
+        // in order to avoid additional rebuilds in distributions that copy existing packages between releases, we
+        // want to also copy any results relevant to newly-imported versions. This only applies within a single
+        // build backend and matches on the URL of the input artifact and its architecture.
+        copy_existing_rebuilds(conn, &build_input)?;
+
+        let current_status = get_current_rebuild_status(conn, &build_input)?;
+        index.statuses.insert(build_input.id, current_status);
+    }
+
+    let current_status = index.statuses.get(&build_input.id);
+    if current_status.is_none_or(|status| *status == BuildStatus::Unknown)
+        && let Some(status) = reuse_artifact_verdicts(conn, index, &build_input)?
+    {
+        index.statuses.insert(build_input.id, status);
+    }
+
+    Ok(build_input)
+}
+
+/// Links debug packages to the binary package they carry the symbols of. Both come out of the same build, so they share
+/// the build input and are always rebuilt together.
+fn link_debug_packages(conn: &mut SqliteConnection, build_input_id: i32) -> Result<(), Error> {
+    let packages = binary_packages::table
+        .filter(binary_packages::build_input_id.eq(build_input_id))
+        .select((binary_packages::id, binary_packages::name))
+        .load::<(i32, String)>(conn)?;
+    let ids = packages
+        .iter()
+        .map(|(id, name)| (name.as_str(), *id))
+        .collect::<HashMap<_, _>>();
+
+    for (id, name) in &packages {
+        let parent_id = DEBUG_PACKAGE_SUFFIXES
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+            .and_then(|parent| ids.get(parent));
+        if let Some(parent_id) = parent_id {
+            update(binary_packages::table.filter(binary_packages::id.eq(id)))
+                .set(binary_packages::parent_id.eq(parent_id))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records a rebuild from the verdicts of identical artifacts, if every binary package of the build input was published
+/// with a checksum that was already verified by a rebuilder script the trusted workers still run. This saves building
+/// packages again that only moved to another release, or that a peer already verified. The build log names where each
+/// verdict came from.
+fn reuse_artifact_verdicts(
+    conn: &mut SqliteConnection,
+    index: &ScopeIndex,
+    build_input: &BuildInput,
+) -> Result<Option<BuildStatus>, Error> {
+    if index.script_versions.is_empty() {
+        return Ok(None);
+    }
+
+    let Some(artifacts) = binary_packages::table
+        .filter(binary_packages::build_input_id.eq(build_input.id))
+        .select((binary_packages::name, binary_packages::checksum))
+        .load::<(String, Option<String>)>(conn)?
+        .into_iter()
+        .map(|(name, checksum)| Some((name, checksum?)))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Ok(None);
+    };
+    if artifacts.is_empty() {
+        return Ok(None);
+    }
+
+    // our own verdicts win over the ones of peers, otherwise the most recent one is used
+    let checksums = artifacts
+        .iter()
+        .map(|(_, checksum)| checksum)
+        .collect::<Vec<_>>();
+    let rank = |verdict: &ArtifactVerdict| (verdict.origin.is_none(), verdict.verified_at);
+    let mut verdicts = HashMap::<String, ArtifactVerdict>::new();
+    for verdict in artifact_verdicts::table
+        .filter(artifact_verdicts::checksum.eq_any(checksums))
+        .filter(artifact_verdicts::script_version.eq_any(&index.script_versions))
+        .load::<ArtifactVerdict>(conn)?
+    {
+        match verdicts.entry(verdict.checksum.clone()) {
+            Entry::Occupied(mut entry) => {
+                if rank(&verdict) > rank(entry.get()) {
+                    entry.insert(verdict);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(verdict);
+            }
+        }
+    }
+
+    let Some(reused) = artifacts
+        .iter()
+        .map(|(name, checksum)| Some((name, verdicts.get(checksum)?)))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Ok(None);
+    };
+
+    let status = if reused
+        .iter()
+        .all(|(_, verdict)| verdict.status == ArtifactStatus::Good.as_str())
+    {
+        BuildStatus::Good
+    } else {
+        BuildStatus::Bad
+    };
+
+    let mut log =
+        "Not rebuilt, all artifacts were already verified by the same rebuilder script\n\n"
+            .to_string();
+    let mut sources = Vec::new();
+    for (name, verdict) in &reused {
+        let source = match verdict.rebuild_artifact_id {
+            Some(id) => Some(
+                rebuild_artifacts::table
+                    .filter(rebuild_artifacts::id.eq(id))
+                    .get_result::<RebuildArtifact>(conn)?,
+            ),
+            None => None,
+        };
+
+        let provenance = match (&source, &verdict.origin) {
+            (Some(source), _) => format!("rebuild #{}", source.rebuild_id),
+            (None, Some(origin)) => format!("reported by {origin}"),
+            (None, None) => "unknown origin".to_string(),
+        };
+        let _ = writeln!(
+            log,
+            "{name}: {} with rebuilder script {} on {}, {provenance}",
+            verdict.status, verdict.script_version, verdict.verified_at
+        );
+        sources.push(source);
+    }
+
+    let build_log_id = NewBuildLog {
+        build_log: Some(log.into_bytes()),
+        blob_key: None,
+    }
+    .insert(conn)?;
+
+    // the verdict is as old as the oldest build it was taken from
+    let built_at = reused.iter().map(|(_, verdict)| verdict.verified_at).min();
+    let rebuild_id = NewRebuild {
+        build_input_id: build_input.id,
+        started_at: None,
+        built_at,
+        build_log_id,
+        status: Some(status.as_str().to_string()),
+        outcome: Some(status.as_str().to_string()),
+        environment_fingerprint: None,
+        worker_id: None,
+        downloaded_bytes: None,
+        reason: None,
+    }
+    .insert(conn)?;
+
+    // logs and attestations of our own builds are shared, like between friends
+    for ((name, verdict), source) in reused.into_iter().zip(sources) {
+        NewRebuildArtifact {
+            rebuild_id,
+            name: name.clone(),
+            diffoscope_log_id: source.as_ref().and_then(|source| source.diffoscope_log_id),
+            attestation_log_id: source.as_ref().and_then(|source| source.attestation_log_id),
+            status: Some(verdict.status.clone()),
+            diff_summary: source
+                .as_ref()
+                .and_then(|source| source.diff_summary.clone()),
+            checksum: source.as_ref().and_then(|source| source.checksum.clone()),
+            likely_causes: source
+                .map(|source| source.likely_causes)
+                .unwrap_or_default(),
+        }
+        .insert(conn)?;
+    }
+
+    Ok(Some(status))
+}
+
+/// Decides if a build input needs to be rebuilt, the job is returned so it can be inserted in a batch with the rest of
+/// the sync
+fn queue_build_input(
+    conn: &mut SqliteConnection,
+    cfg: &Config,
+    index: &mut ScopeIndex,
+    build_input: &BuildInput,
+    now: DateTime<Utc>,
+) -> Result<Option<NewQueued>, Error> {
+    let current_status = index
+        .statuses
+        .get(&build_input.id)
+        .cloned()
+        .unwrap_or(BuildStatus::Unknown);
+    let friends = (build_input.url.clone(), build_input.backend.clone());
+
+    if current_status == BuildStatus::Good || index.queued.contains(&friends) {
+        return Ok(None);
+    }
+
+    // bail if we have a max retry count set and requeueing this package would exceed it
+    if let Some(max_retries) = cfg.schedule.max_retries() {
+        let retry_count = get_largest_retry_count_among_friends(conn, build_input.id)?;
+        if retry_count >= max_retries {
+            mark_build_input_friends_as_non_retriable(conn, build_input.id)?;
+            return Ok(None);
+        }
+    }
+
+    let priority = match current_status {
+        BuildStatus::Bad => Priority::retry(),
+        _ => Priority::default(),
+    };
+
+    index.queued.insert(friends);
+    Ok(Some(NewQueued {
+        build_input_id: build_input.id,
+        priority,
+        queued_at: now.naive_utc(),
+        build_architecture: None,
+        bisect_step_id: None,
+        reason: None,
+    }))
 }
 
 fn get_current_rebuild_status(
-    conn: &mut PooledConnection<ConnectionManager<SqliteConnectionWrap>>,
+    conn: &mut SqliteConnection,
     build_input: &BuildInput,
 ) -> Result<BuildStatus, Error> {
     let current_status = rebuilds::table
         .filter(rebuilds::build_input_id.is(&build_input.id))
         .select(rebuilds::status)
         .order_by(rebuilds::built_at.desc())
-        .get_result::<Option<BuildStatus>>(conn.as_mut())
+        .get_result::<Option<BuildStatus>>(conn)
         .optional()
         .map_err(Error::from)?
         .flatten()
@@ -325,7 +1413,7 @@ fn get_current_rebuild_status(
 }
 
 fn copy_existing_rebuilds(
-    connection: &mut PooledConnection<ConnectionManager<SqliteConnectionWrap>>,
+    connection: &mut SqliteConnection,
     build_input: &BuildInput,
 ) -> Result<(), Error> {
     // check if we have any existing rebuilds that match this package
@@ -333,7 +1421,7 @@ fn copy_existing_rebuilds(
         .filter(build_inputs::id.ne(build_input.id))
         .order_by(build_inputs::id)
         .limit(1)
-        .get_result::<i32>(connection.as_mut())
+        .get_result::<i32>(connection)
         .optional()
         .map_err(Error::from)?;
 
@@ -342,14 +1430,14 @@ fn copy_existing_rebuilds(
         let has_existing_rebuild = select(exists(
             rebuilds::table.filter(rebuilds::build_input_id.is(existing_build_input)),
         ))
-        .get_result::<bool>(connection.as_mut())
+        .get_result::<bool>(connection)
         .map_err(Error::from)?;
 
         if has_existing_rebuild {
             let existing_rebuild_ids = rebuilds::table
                 .filter(rebuilds::build_input_id.is(existing_build_input))
                 .select(rebuilds::id)
-                .get_results::<i32>(connection.as_mut())
+                .get_results::<i32>(connection)
                 .map_err(Error::from)?;
 
             for existing_rebuild_id in existing_rebuild_ids {
@@ -363,6 +1451,8 @@ fn copy_existing_rebuilds(
                                 rebuilds::built_at,
                                 rebuilds::build_log_id,
                                 rebuilds::status,
+                                rebuilds::environment_fingerprint,
+                                rebuilds::worker_id,
                             )),
                     )
                     .into_columns((
@@ -371,9 +1461,11 @@ fn copy_existing_rebuilds(
                         rebuilds::built_at,
                         rebuilds::build_log_id,
                         rebuilds::status,
+                        rebuilds::environment_fingerprint,
+                        rebuilds::worker_id,
                     ))
                     .returning(rebuilds::id)
-                    .get_result::<i32>(connection.as_mut())
+                    .get_result::<i32>(connection)
                     .map_err(Error::from)?;
 
                 // copy artifacts
@@ -396,7 +1488,7 @@ fn copy_existing_rebuilds(
                         rebuild_artifacts::attestation_log_id,
                         rebuild_artifacts::status,
                     ))
-                    .execute(connection.as_mut())
+                    .execute(connection)
                     .map_err(Error::from)?;
             }
         }
@@ -412,46 +1504,50 @@ pub async fn get_source_packages(
     origin_filter: web::Query<OriginFilter>,
     source_identity_filter: web::Query<SourceIdentityFilter>,
     freshness_filter: web::Query<FreshnessFilter>,
+    tag_filter: web::Query<TagFilter>,
+    cause_filter: web::Query<CauseFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let page = page.into_inner();
+    let origin_filter = origin_filter.into_inner();
+    let source_identity_filter = source_identity_filter.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let tag_filter = tag_filter.into_inner();
+    let cause_filter = cause_filter.into_inner();
 
-    let records = source_packages_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
-        .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
-        )
-        .filter(freshness_filter.clone().into_inner().into_filter())
-        .paginate(page.into_inner())
-        .load::<rebuilderd_common::api::v1::SourcePackage>(connection.as_mut())
-        .map_err(Error::from)?;
+    let source_packages = db::run(&pool, move |connection| {
+        let records = source_packages_base()
+            .filter(
+                origin_filter
+                    .clone()
+                    .into_filter(build_inputs::architecture),
+            )
+            .filter(
+                source_identity_filter
+                    .clone()
+                    .into_filter(source_packages::name, source_packages::version),
+            )
+            .filter(freshness_filter.clone().into_filter())
+            .filter(tag_filter.clone().into_filter())
+            .filter(cause_filter.clone().into_rebuild_filter())
+            .paginate(page)
+            .load::<rebuilderd_common::api::v1::SourcePackage>(connection)?;
 
-    let total = source_packages_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
-        .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
-        )
-        .filter(freshness_filter.into_inner().into_filter())
-        .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        let total = source_packages_base()
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .filter(
+                source_identity_filter.into_filter(source_packages::name, source_packages::version),
+            )
+            .filter(freshness_filter.into_filter())
+            .filter(tag_filter.into_filter())
+            .filter(cause_filter.into_rebuild_filter())
+            .count()
+            .get_result::<i64>(connection)?;
+
+        Ok(ResultPage { total, records })
+    })
+    .await?;
 
-    Ok(HttpResponse::Ok().json(ResultPage { total, records }))
+    Ok(HttpResponse::Ok().json(source_packages))
 }
 
 #[get("/source/{id}")]
@@ -459,20 +1555,119 @@ pub async fn get_source_package(
     pool: web::Data<Pool>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let record = db::run(&pool, move |connection| {
+        let record = source_packages_base()
+            .filter(source_packages::id.is(id))
+            .get_result::<rebuilderd_common::api::v1::SourcePackage>(connection)
+            .optional()?;
+        Ok(record)
+    })
+    .await?;
 
-    if let Some(record) = source_packages_base()
-        .filter(source_packages::id.is(id.into_inner()))
-        .get_result::<rebuilderd_common::api::v1::SourcePackage>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?
-    {
+    if let Some(record) = record {
         Ok(HttpResponse::Ok().json(record))
     } else {
         Ok(HttpResponse::NotFound().finish())
     }
 }
 
+/// Cross builds of a source package, newest first. They are a separate verdict and don't affect the package status.
+#[get("/source/{id}/cross")]
+pub async fn get_source_package_cross_rebuilds(
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    let id = id.into_inner();
+    let records = db::run(&pool, move |connection| {
+        let records = cross_rebuilds::table
+            .inner_join(build_inputs::table)
+            .filter(build_inputs::source_package_id.is(id))
+            .select((
+                cross_rebuilds::id,
+                build_inputs::architecture,
+                cross_rebuilds::build_architecture,
+                build_inputs::backend,
+                cross_rebuilds::started_at,
+                cross_rebuilds::built_at,
+                cross_rebuilds::status,
+            ))
+            .order_by((cross_rebuilds::built_at.desc(), cross_rebuilds::id.desc()))
+            .load::<CrossRebuild>(connection)?;
+        Ok(records)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(records))
+}
+
+/// Strips the distribution specific parts of a version so packages of the same upstream release can be matched across
+/// distributions. This removes a leading epoch (`1:2.0-1`) and a trailing packaging revision (`2.0-1`).
+fn upstream_version(version: &str) -> &str {
+    let version = match version.split_once(':') {
+        Some((epoch, rest)) if !epoch.is_empty() && epoch.bytes().all(|b| b.is_ascii_digit()) => {
+            rest
+        }
+        _ => version,
+    };
+
+    version
+        .rsplit_once('-')
+        .map(|(upstream, _revision)| upstream)
+        .unwrap_or(version)
+}
+
+#[get("/upstream/{name}/{version}")]
+pub async fn get_upstream_release(
+    pool: web::Data<Pool>,
+    path: web::Path<(String, String)>,
+) -> web::Result<impl Responder> {
+    let (name, version) = path.into_inner();
+
+    let packages = db::run(&pool, {
+        let name = name.clone();
+        move |connection| {
+            let records = source_packages_base()
+                .filter(source_packages::name.is(name))
+                .order_by((
+                    source_packages::distribution,
+                    source_packages::release,
+                    source_packages::component,
+                    source_packages::id,
+                ))
+                .load::<rebuilderd_common::api::v1::SourcePackage>(connection)?;
+            Ok(records)
+        }
+    })
+    .await?;
+
+    let packages = packages
+        .into_iter()
+        .filter(|package| {
+            package.version == version || upstream_version(&package.version) == version
+        })
+        .collect::<Vec<_>>();
+
+    if packages.is_empty() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    // packages are sorted by distribution, so duplicates are always next to each other
+    let mut reproducible_in = packages
+        .iter()
+        .filter(|package| package.status == Some(BuildStatus::Good))
+        .map(|package| package.distribution.clone())
+        .collect::<Vec<_>>();
+    reproducible_in.dedup();
+
+    Ok(HttpResponse::Ok().json(UpstreamRelease {
+        name,
+        version,
+        reproducible_in,
+        packages,
+    }))
+}
+
 #[get("/binary")]
 pub async fn get_binary_packages(
     pool: web::Data<Pool>,
@@ -480,44 +1675,52 @@ pub async fn get_binary_packages(
     origin_filter: web::Query<OriginFilter>,
     binary_identity_filter: web::Query<BinaryIdentityFilter>,
     freshness_filter: web::Query<FreshnessFilter>,
+    tag_filter: web::Query<TagFilter>,
+    cause_filter: web::Query<CauseFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let page = page.into_inner();
+    let origin_filter = origin_filter.into_inner();
+    let binary_identity_filter = binary_identity_filter.into_inner();
+    let freshness_filter = freshness_filter.into_inner();
+    let tag_filter = tag_filter.into_inner();
+    let cause_filter = cause_filter.into_inner();
 
-    let records = binary_packages_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(binary_packages::architecture),
-        )
-        .filter(binary_identity_filter.clone().into_inner().into_filter(
-            binary_packages::name,
-            binary_packages::version,
-            source_packages::name,
-        ))
-        .filter(freshness_filter.clone().into_inner().into_filter())
-        .paginate(page.into_inner())
-        .load::<rebuilderd_common::api::v1::BinaryPackage>(connection.as_mut())
-        .map_err(Error::from)?;
+    let binary_packages = db::run(&pool, move |connection| {
+        let records = binary_packages_base()
+            .filter(
+                origin_filter
+                    .clone()
+                    .into_filter(binary_packages::architecture),
+            )
+            .filter(binary_identity_filter.clone().into_filter(
+                binary_packages::name,
+                binary_packages::version,
+                source_packages::name,
+            ))
+            .filter(freshness_filter.clone().into_filter())
+            .filter(tag_filter.clone().into_filter())
+            .filter(cause_filter.clone().into_artifact_filter())
+            .paginate(page)
+            .load::<rebuilderd_common::api::v1::BinaryPackage>(connection)?;
 
-    let total = binary_packages_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(binary_packages::architecture),
-        )
-        .filter(freshness_filter.into_inner().into_filter())
-        .filter(binary_identity_filter.clone().into_inner().into_filter(
-            binary_packages::name,
-            binary_packages::version,
-            source_packages::name,
-        ))
-        .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        let total = binary_packages_base()
+            .filter(origin_filter.into_filter(binary_packages::architecture))
+            .filter(freshness_filter.into_filter())
+            .filter(tag_filter.into_filter())
+            .filter(cause_filter.into_artifact_filter())
+            .filter(binary_identity_filter.into_filter(
+                binary_packages::name,
+                binary_packages::version,
+                source_packages::name,
+            ))
+            .count()
+            .get_result::<i64>(connection)?;
 
-    Ok(HttpResponse::Ok().json(ResultPage { total, records }))
+        Ok(ResultPage { total, records })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(binary_packages))
 }
 
 #[get("/binary/{id}")]
@@ -525,16 +1728,453 @@ pub async fn get_binary_package(
     pool: web::Data<Pool>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let record = db::run(&pool, move |connection| {
+        let record = binary_packages_base()
+            .filter(binary_packages::id.is(id))
+            .get_result::<rebuilderd_common::api::v1::BinaryPackage>(connection)
+            .optional()?;
+        Ok(record)
+    })
+    .await?;
 
-    if let Some(record) = binary_packages_base()
-        .filter(binary_packages::id.is(id.into_inner()))
-        .get_result::<rebuilderd_common::api::v1::BinaryPackage>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?
-    {
+    if let Some(record) = record {
         Ok(HttpResponse::Ok().json(record))
     } else {
         Ok(HttpResponse::NotFound().finish())
     }
 }
+
+/// The latest verdict of every source package and architecture, in the format of tests.reproducible-builds.org.
+/// Debian calls the release a suite, the component takes its place for distributions that don't have releases.
+#[get("/export/tracker")]
+pub async fn export_tracker(
+    pool: web::Data<Pool>,
+    origin_filter: web::Query<OriginFilter>,
+) -> web::Result<impl Responder> {
+    let origin_filter = origin_filter.into_inner();
+
+    let records = db::run(&pool, move |connection| {
+        let records = source_packages::table
+            .inner_join(build_inputs::table)
+            .inner_join(r1.on(r1.field(rebuilds::build_input_id).is(build_inputs::id)))
+            .left_join(
+                r2.on(r2.field(rebuilds::build_input_id).is(build_inputs::id).and(
+                    r1.field(rebuilds::built_at)
+                        .lt(r2.field(rebuilds::built_at))
+                        .or(r1.fields(
+                            rebuilds::built_at
+                                .eq(r2.field(rebuilds::built_at))
+                                .and(r1.field(rebuilds::id).lt(r2.field(rebuilds::id))),
+                        )),
+                )),
+            )
+            .filter(r2.field(rebuilds::id).is_null())
+            .filter(source_packages::removed_at.is_null())
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .select((
+                source_packages::name,
+                source_packages::version,
+                source_packages::release,
+                source_packages::component,
+                build_inputs::architecture,
+                r1.field(rebuilds::status),
+                r1.field(rebuilds::built_at),
+            ))
+            .order_by((source_packages::name, build_inputs::architecture))
+            .load::<(
+                String,
+                String,
+                Option<String>,
+                Option<String>,
+                String,
+                Option<BuildStatus>,
+                Option<NaiveDateTime>,
+            )>(connection)?;
+        Ok(records)
+    })
+    .await?;
+
+    let entries = records
+        .into_iter()
+        .filter_map(
+            |(package, version, release, component, architecture, status, built_at)| {
+                let status = TrackerStatus::from_build_status(status.as_ref()?)?;
+                Some(TrackerEntry {
+                    package,
+                    version,
+                    suite: release.or(component).unwrap_or_default(),
+                    architecture,
+                    status,
+                    build_date: built_at
+                        .map(|built_at| built_at.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default(),
+                })
+            },
+        )
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+enum Correction {
+    NotFound,
+    NotRebuilt,
+    Applied {
+        before: Box<rebuilderd_common::api::v1::BinaryPackage>,
+        after: Box<rebuilderd_common::api::v1::BinaryPackage>,
+    },
+}
+
+/// Describes the fields that differ between two versions of a binary package record, for the audit log
+fn describe_correction(
+    before: &rebuilderd_common::api::v1::BinaryPackage,
+    after: &rebuilderd_common::api::v1::BinaryPackage,
+) -> String {
+    let mut changes = Vec::new();
+    if before.url != after.url {
+        changes.push(format!("artifact url {:?} -> {:?}", before.url, after.url));
+    }
+    if before.version != after.version {
+        changes.push(format!(
+            "version {:?} -> {:?}",
+            before.version, after.version
+        ));
+    }
+    if before.status != after.status {
+        changes.push(format!(
+            "status {:?} -> {:?}",
+            before.status.as_ref().map(ArtifactStatus::as_str),
+            after.status.as_ref().map(ArtifactStatus::as_str)
+        ));
+    }
+
+    if changes.is_empty() {
+        "no changes".to_string()
+    } else {
+        changes.join(", ")
+    }
+}
+
+/// Corrects a package that a sync imported wrongly, without waiting for the next full sync. The status is the verdict
+/// of the latest rebuild, so it can only be corrected once the package was rebuilt.
+pub async fn correct_binary_package(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+    request: web::Json<BinaryPackageCorrection>,
+) -> web::Result<impl Responder> {
+    let Ok(actor) = auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin).await else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let id = id.into_inner();
+    let correction = request.into_inner();
+
+    if let Some(artifact_url) = &correction.artifact_url
+        && artifact_url.is_empty()
+    {
+        return Ok(HttpResponse::BadRequest().body("Artifact url must not be empty"));
+    }
+    if let Some(version) = &correction.version
+        && version.is_empty()
+    {
+        return Ok(HttpResponse::BadRequest().body("Version must not be empty"));
+    }
+
+    let result = db::run(&pool, move |connection| {
+        connection.transaction(|conn| {
+            let Some(before) = binary_packages_base()
+                .filter(binary_packages::id.is(id))
+                .get_result::<rebuilderd_common::api::v1::BinaryPackage>(conn)
+                .optional()?
+            else {
+                return Ok(Correction::NotFound);
+            };
+
+            if let Some(status) = correction.status {
+                let Some(artifact_id) = before.artifact_id else {
+                    return Ok(Correction::NotRebuilt);
+                };
+                update(rebuild_artifacts::table)
+                    .filter(rebuild_artifacts::id.is(artifact_id))
+                    .set(rebuild_artifacts::status.eq(status))
+                    .execute(conn)?;
+            }
+
+            if let Some(artifact_url) = correction.artifact_url {
+                update(binary_packages::table)
+                    .filter(binary_packages::id.is(id))
+                    .set(binary_packages::artifact_url.eq(artifact_url))
+                    .execute(conn)?;
+            }
+
+            if let Some(version) = correction.version {
+                update(binary_packages::table)
+                    .filter(binary_packages::id.is(id))
+                    .set(binary_packages::version.eq(version))
+                    .execute(conn)?;
+            }
+
+            let after = binary_packages_base()
+                .filter(binary_packages::id.is(id))
+                .get_result::<rebuilderd_common::api::v1::BinaryPackage>(conn)?;
+
+            Ok(Correction::Applied {
+                before: Box::new(before),
+                after: Box::new(after),
+            })
+        })
+    })
+    .await?;
+
+    match result {
+        Correction::NotFound => Ok(HttpResponse::NotFound().finish()),
+        Correction::NotRebuilt => Ok(HttpResponse::Conflict()
+            .body("Package has not been rebuilt yet, there is no status to correct")),
+        Correction::Applied { before, after } => {
+            info!(
+                "{actor} corrected binary package {:?} (#{id}): {}",
+                after.name,
+                describe_correction(&before, &after)
+            );
+            Ok(HttpResponse::Ok().json(after))
+        }
+    }
+}
+
+const MAX_TAG_LEN: usize = 64;
+
+/// Tags are free-form, but end up in urls and on the command line, so they are limited to a conservative set of
+/// characters.
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[get("/tags")]
+pub async fn get_package_tags(
+    pool: web::Data<Pool>,
+    filter: web::Query<PackageTagFilter>,
+) -> web::Result<impl Responder> {
+    let filter = filter.into_inner();
+
+    let records = db::run(&pool, move |connection| {
+        let mut sql = package_tags::table
+            .select((
+                package_tags::id,
+                package_tags::distribution,
+                package_tags::name,
+                package_tags::tag,
+                package_tags::created_at,
+            ))
+            .order_by((
+                package_tags::distribution,
+                package_tags::name,
+                package_tags::tag,
+            ))
+            .into_boxed();
+
+        if let Some(distribution) = filter.distribution {
+            sql = sql.filter(package_tags::distribution.is(distribution));
+        }
+
+        if let Some(name) = filter.name {
+            sql = sql.filter(package_tags::name.is(name));
+        }
+
+        if let Some(tag) = filter.tag {
+            sql = sql.filter(package_tags::tag.is(tag));
+        }
+
+        let records = sql.load::<PackageTag>(connection)?;
+        Ok(records)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(records))
+}
+
+#[post("/tags/{distribution}/{name}/{tag}")]
+pub async fn add_package_tag(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    path: web::Path<(String, String, String)>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Operator)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let (distribution, name, tag) = path.into_inner();
+    if !is_valid_tag(&tag) {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Tags must be 1-{MAX_TAG_LEN} characters of a-z, A-Z, 0-9, '-', '_', '.' or ':'"
+        )));
+    }
+
+    let added = db::run(&pool, move |connection| {
+        let package_exists = select(exists(
+            source_packages::table
+                .filter(source_packages::distribution.is(&distribution))
+                .filter(source_packages::name.is(&name)),
+        ))
+        .get_result::<bool>(connection)?;
+
+        if !package_exists {
+            return Ok(false);
+        }
+
+        NewPackageTag {
+            distribution,
+            name,
+            tag,
+            created_at: Utc::now().naive_utc(),
+        }
+        .insert(connection)?;
+
+        Ok(true)
+    })
+    .await?;
+
+    if added {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[delete("/tags/{distribution}/{name}/{tag}")]
+pub async fn remove_package_tag(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    path: web::Path<(String, String, String)>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Operator)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let (distribution, name, tag) = path.into_inner();
+    let deleted = db::run(&pool, move |connection| {
+        let count = delete(
+            package_tags::table
+                .filter(package_tags::distribution.is(distribution))
+                .filter(package_tags::name.is(name))
+                .filter(package_tags::tag.is(tag)),
+        )
+        .execute(connection)?;
+        Ok(count)
+    })
+    .await?;
+
+    if deleted < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+/// Bug urls are rendered as links, so only plain web urls are accepted.
+fn is_valid_bug_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+#[post("/annotations/{distribution}/{name}")]
+pub async fn annotate_package(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    path: web::Path<(String, String)>,
+    request: web::Json<PackageAnnotation>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Operator)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let (distribution, name) = path.into_inner();
+    let request = request.into_inner();
+
+    if let Some(bug_url) = &request.bug_url
+        && !is_valid_bug_url(bug_url)
+    {
+        return Ok(HttpResponse::BadRequest().body("Bug url must be an http or https url"));
+    }
+
+    let annotated = db::run(&pool, move |connection| {
+        let package_exists = select(exists(
+            source_packages::table
+                .filter(source_packages::distribution.is(&distribution))
+                .filter(source_packages::name.is(&name)),
+        ))
+        .get_result::<bool>(connection)?;
+
+        if !package_exists {
+            return Ok(false);
+        }
+
+        NewPackageAnnotation {
+            distribution,
+            name,
+            bug_url: request.bug_url,
+            note: request.note,
+            updated_at: Utc::now().naive_utc(),
+        }
+        .upsert(connection)?;
+
+        Ok(true)
+    })
+    .await?;
+
+    if annotated {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[delete("/annotations/{distribution}/{name}")]
+pub async fn remove_package_annotation(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    path: web::Path<(String, String)>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Operator)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let (distribution, name) = path.into_inner();
+    let deleted = db::run(&pool, move |connection| {
+        let count = delete(
+            package_annotations::table
+                .filter(package_annotations::distribution.is(distribution))
+                .filter(package_annotations::name.is(name)),
+        )
+        .execute(connection)?;
+        Ok(count)
+    })
+    .await?;
+
+    if deleted < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
@@ -1,30 +1,72 @@
-use crate::api::v1::util::auth;
-use crate::api::v1::util::filters::{IntoOriginFilter, IntoSourceIdentityFilter};
-use crate::api::v1::util::friends::{build_input_friends, has_queued_friend};
+use crate::api::v1::util::auth::{self, AuthenticatedWorker};
+use crate::api::v1::util::filters::{IntoFilter, IntoOriginFilter, IntoSourceIdentityFilter};
+use crate::api::v1::util::friends::has_queued_friend;
+use crate::api::v1::util::mirrors;
 use crate::api::v1::util::pagination::PaginateDsl;
 use crate::config::Config;
-use crate::db::Pool;
-use crate::models::NewQueued;
-use crate::schema::{binary_packages, build_inputs, queue, rebuilds, source_packages, workers};
+use crate::db::{self, Pool};
+use crate::models::{NewQueued, Worker};
+use crate::scheduler::{FairScheduler, Pick, Suite};
+use crate::schema::{
+    binary_packages, bisect_steps, build_durations, build_inputs, provisional_rebuilds, queue,
+    rebuilds, source_packages, workers,
+};
 use crate::web;
+use actix_web::http::header;
+use actix_web::web::Bytes;
 use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, post};
-use chrono::{Duration, NaiveDateTime, Utc};
+use aliases::*;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::dsl::update;
-use diesel::{BoolExpressionMethods, JoinOnDsl};
-use diesel::{Connection, OptionalExtension, QueryDsl, RunQueryDsl};
-use diesel::{ExpressionMethods, SqliteExpressionMethods, define_sql_function};
+use diesel::{AggregateExpressionMethods, BoolExpressionMethods, JoinOnDsl};
+use diesel::{Connection, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use diesel::{
+    ExpressionMethods, NullableExpressionMethods, SqliteExpressionMethods, define_sql_function,
+};
 use rebuilderd_common::api::v1::{
-    BuildStatus, JobAssignment, OriginFilter, Page, PopQueuedJobRequest, Priority, QueueJobRequest,
-    QueuedJob, QueuedJobArtifact, QueuedJobWithArtifacts, ResultPage, SourceIdentityFilter,
+    ApiKeyScope, AssignQueuedJobsRequest, AssignQueuedJobsResponse, BuildStatus, JobAssignment,
+    JobPayload, OriginFilter, Page, PingJobRequest, PingJobResponse, PopQueuedJobRequest, Priority,
+    QueueImportResponse, QueueJobEvent, QueueJobRequest, QueueJobResponse, QueuePosition,
+    QueueSnapshot, QueuedJob, QueuedJobArtifact, QueuedJobWithArtifacts, ResultPage,
+    SourceIdentityFilter, TagFilter, WipeQueueRequest, WipeQueueResponse, WorkerCapability,
 };
-use rebuilderd_common::config::PING_DEADLINE;
+use rebuilderd_common::config::{JobOrder, PING_DEADLINE};
 use rebuilderd_common::errors::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+const REQUEUE_BATCH_SIZE: usize = 500;
+const WIPE_BATCH_SIZE: usize = 500;
+const REQUEUE_PROGRESS_INTERVAL: usize = 10_000;
+const NDJSON: &str = "application/x-ndjson";
+pub(crate) const QUEUE_POSITION_LIMIT: i64 = 100;
+const MAX_PHASE_LEN: usize = 64;
+const MAX_REASON_LEN: usize = 256;
+
+mod aliases {
+    diesel::alias!(crate::schema::rebuilds as r1: RebuildsAlias1, crate::schema::rebuilds as r2: RebuildsAlias2);
+    diesel::alias!(crate::schema::workers as pw: PinnedWorkersAlias);
+}
+
+/// Durations are recorded per source package, they carry over to new versions
+#[diesel::dsl::auto_type]
+fn estimated_duration() -> _ {
+    build_durations::distribution
+        .eq(source_packages::distribution)
+        .and(build_durations::name.eq(source_packages::name))
+        .and(build_durations::architecture.eq(build_inputs::architecture))
+}
 
 #[diesel::dsl::auto_type]
-fn queue_base() -> _ {
+pub(crate) fn queue_base() -> _ {
     queue::table
         .inner_join(build_inputs::table.inner_join(source_packages::table))
+        .left_join(workers::table.on(queue::worker.is(workers::id.nullable())))
+        .left_join(pw.on(queue::pinned_worker.is(pw.field(workers::id).nullable())))
+        .left_join(build_durations::table.on(estimated_duration()))
+        .left_join(bisect_steps::table.on(queue::bisect_step_id.is(bisect_steps::id.nullable())))
         .select((
             queue::id,
             source_packages::name,
@@ -39,6 +81,15 @@ fn queue_base() -> _ {
             queue::priority,
             queue::queued_at,
             queue::started_at,
+            queue::phase,
+            build_inputs::retries,
+            build_inputs::last_failure,
+            workers::name.nullable(),
+            pw.field(workers::name).nullable(),
+            queue::build_architecture,
+            queue::deadline,
+            bisect_steps::variation.nullable(),
+            queue::reason,
         ))
 }
 
@@ -48,49 +99,179 @@ pub async fn get_queued_jobs(
     page: web::Query<Page>,
     origin_filter: web::Query<OriginFilter>,
     source_identity_filter: web::Query<SourceIdentityFilter>,
+    tag_filter: web::Query<TagFilter>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let page = page.into_inner();
+    let origin_filter = origin_filter.into_inner();
+    let source_identity_filter = source_identity_filter.into_inner();
+    let tag_filter = tag_filter.into_inner();
 
-    let records = queue_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
-        .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
-        )
-        .order_by((
-            queue::priority,
-            diesel::dsl::date(queue::queued_at),
-            sqlite_random(),
-        ))
-        .paginate(page.into_inner())
-        .load::<QueuedJob>(connection.as_mut())
-        .map_err(Error::from)?;
+    let jobs = db::run(&pool, move |connection| {
+        let records = queue_base()
+            .filter(
+                origin_filter
+                    .clone()
+                    .into_filter(build_inputs::architecture),
+            )
+            .filter(
+                source_identity_filter
+                    .clone()
+                    .into_filter(source_packages::name, source_packages::version),
+            )
+            .filter(tag_filter.clone().into_filter())
+            .order_by((
+                queue::priority,
+                diesel::dsl::date(queue::queued_at),
+                build_inputs::popularity.desc(),
+                sqlite_random(),
+            ))
+            .paginate(page)
+            .load::<QueuedJob>(connection)?;
 
-    let total = queue_base()
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
+        let total = queue_base()
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .filter(
+                source_identity_filter.into_filter(source_packages::name, source_packages::version),
+            )
+            .filter(tag_filter.into_filter())
+            .count()
+            .get_result::<i64>(connection)?;
+
+        Ok(ResultPage { total, records })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+#[get("/position")]
+pub async fn get_queue_position(
+    pool: web::Data<Pool>,
+    origin_filter: web::Query<OriginFilter>,
+    source_identity_filter: web::Query<SourceIdentityFilter>,
+) -> web::Result<impl Responder> {
+    let origin_filter = origin_filter.into_inner();
+    let source_identity_filter = source_identity_filter.into_inner();
+
+    let records = db::run(&pool, move |connection| {
+        let jobs = queue_base()
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .filter(
+                source_identity_filter.into_filter(source_packages::name, source_packages::version),
+            )
+            .order_by(queue::id)
+            .limit(QUEUE_POSITION_LIMIT)
+            .load::<QueuedJob>(connection)?;
+
+        let now = Utc::now();
+        let mut records = Vec::new();
+        for job in jobs {
+            records.push(get_job_position(connection, job, now)?);
+        }
+        Ok(records)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(records))
+}
+
+/// Figure out how many jobs are going to be picked up before the given one, and estimate the start time from the number
+/// of rebuilds finished in the last 24 hours.
+///
+/// This only follows the priority, queue date and popularity order of `request_work`. The suite picked by the fair
+/// scheduler, `job_order = "interleave-durations"`, pinned workers and `excluded_packages` are not taken into account.
+pub(crate) fn get_job_position(
+    connection: &mut SqliteConnection,
+    job: QueuedJob,
+    now: DateTime<Utc>,
+) -> QueryResult<QueuePosition> {
+    if let Some(started_at) = job.started_at {
+        return Ok(QueuePosition {
+            job,
+            ahead: 0,
+            tied: 0,
+            estimated_start: Some(started_at),
+        });
+    }
+
+    let pending = || {
+        queue::table
+            .inner_join(build_inputs::table)
+            .filter(queue::worker.is_null())
+            .filter(queue::id.ne(job.id))
+            .filter(build_inputs::backend.eq(job.backend.clone()))
+            .filter(build_inputs::architecture.eq(job.architecture.clone()))
+    };
+
+    let popularity = queue::table
+        .inner_join(build_inputs::table)
+        .filter(queue::id.eq(job.id))
+        .select(build_inputs::popularity)
+        .get_result::<Option<i64>>(connection)?;
+
+    let earlier = pending()
         .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
+            queue::priority.lt(job.priority).or(queue::priority
+                .eq(job.priority)
+                .and(diesel::dsl::date(queue::queued_at).lt(diesel::dsl::date(job.queued_at)))),
         )
         .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        .get_result::<i64>(connection)?;
+
+    let same_day = || {
+        pending()
+            .filter(queue::priority.eq(job.priority))
+            .filter(diesel::dsl::date(queue::queued_at).eq(diesel::dsl::date(job.queued_at)))
+    };
 
-    Ok(HttpResponse::Ok().json(ResultPage { total, records }))
+    // jobs without a popularity score are sorted last
+    let more_popular = match popularity {
+        Some(popularity) => same_day()
+            .filter(build_inputs::popularity.gt(popularity))
+            .count()
+            .get_result::<i64>(connection)?,
+        None => same_day()
+            .filter(build_inputs::popularity.is_not_null())
+            .count()
+            .get_result::<i64>(connection)?,
+    };
+    let ahead = earlier + more_popular;
+
+    let tied = same_day()
+        .filter(build_inputs::popularity.is(popularity))
+        .count()
+        .get_result::<i64>(connection)?;
+
+    // every report creates exactly one build log, even if it's shared between multiple build inputs
+    let since = (now - Duration::hours(24)).naive_utc();
+    let finished = rebuilds::table
+        .inner_join(build_inputs::table)
+        .filter(rebuilds::built_at.gt(since))
+        .filter(build_inputs::backend.eq(job.backend.clone()))
+        .filter(build_inputs::architecture.eq(job.architecture.clone()))
+        .select(diesel::dsl::count(rebuilds::build_log_id).aggregate_distinct())
+        .get_result::<i64>(connection)?;
+
+    let estimated_start = if finished > 0 {
+        // on average, half of the tied jobs are picked up before this one
+        let waiting = ahead * 2 + tied;
+        let wait = Duration::seconds(waiting * 24 * 60 * 60 / (finished * 2));
+        let estimate = (now + wait).naive_utc();
+
+        Some(match job.next_retry {
+            Some(next_retry) if next_retry > estimate => next_retry,
+            _ => estimate,
+        })
+    } else {
+        None
+    };
+
+    Ok(QueuePosition {
+        job,
+        ahead,
+        tied,
+        estimated_start,
+    })
 }
 
 #[post("")]
@@ -100,14 +281,97 @@ pub async fn request_rebuild(
     pool: web::Data<Pool>,
     request: web::Json<QueueJobRequest>,
 ) -> web::Result<impl Responder> {
-    if auth::admin(&cfg, &req).is_err() {
-        return Ok(HttpResponse::Forbidden());
+    if auth::admin_any(
+        &cfg,
+        &req,
+        &pool,
+        &[ApiKeyScope::Queue, ApiKeyScope::Operator],
+    )
+    .await
+    .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
     }
 
-    let mut connection = pool.get().map_err(Error::from)?;
-
     let queue_request = request.into_inner();
+    if let Some(reason) = &queue_request.reason
+        && reason.chars().count() > MAX_REASON_LEN
+    {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Reason must not be longer than {MAX_REASON_LEN} characters"
+        )));
+    }
+
+    let accepts_ndjson = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON));
+    if !accepts_ndjson {
+        let response = db::run(&pool, move |connection| {
+            requeue(connection, queue_request, |_, _| ())
+        })
+        .await?;
+        log_requeue(&response);
+        return Ok(HttpResponse::Ok().json(response));
+    }
+
+    // large requeues take a while, the client is kept up to date. The requeue goes on if the client goes away.
+    let (tx, rx) = mpsc::unbounded_channel();
+    let pool = pool.clone();
+    actix_web::rt::spawn(async move {
+        let progress = tx.clone();
+        let result = db::run(&pool, move |connection| {
+            requeue(connection, queue_request, |processed, total| {
+                progress
+                    .send(QueueJobEvent::Progress {
+                        processed: processed as i64,
+                        total: total as i64,
+                    })
+                    .ok();
+            })
+        })
+        .await;
+
+        let event = match result {
+            Ok(response) => {
+                log_requeue(&response);
+                QueueJobEvent::Done(response)
+            }
+            Err(err) => {
+                warn!("Failed to requeue packages: {err:#}");
+                QueueJobEvent::Error(format!("{err:#}"))
+            }
+        };
+        tx.send(event).ok();
+    });
+
+    let events = futures_util::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        let line = serde_json::to_vec(&event).map(|mut line| {
+            line.push(b'\n');
+            Bytes::from(line)
+        });
+        Some((line.map_err(io::Error::other), rx))
+    });
+
+    Ok(HttpResponse::Ok().content_type(NDJSON).streaming(events))
+}
+
+fn log_requeue(response: &QueueJobResponse) {
+    info!(
+        "Requeued {} build inputs, skipped {} that were already queued",
+        response.queued, response.skipped
+    );
+}
 
+/// Queue the matching build inputs, `progress` is called with the number of processed and total build inputs every
+/// now and then
+fn requeue(
+    connection: &mut SqliteConnection,
+    queue_request: QueueJobRequest,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<QueueJobResponse> {
     let origin_filter = OriginFilter {
         distribution: queue_request.distribution,
         release: queue_request.release,
@@ -133,9 +397,20 @@ pub async fn request_rebuild(
                 .clone()
                 .into_filter(source_packages::name, source_packages::version),
         )
-        .select(build_inputs::id)
+        .select((
+            build_inputs::id,
+            build_inputs::url,
+            build_inputs::backend,
+            build_inputs::architecture,
+        ))
+        .distinct()
+        .order_by(build_inputs::id)
         .into_boxed();
 
+    if let Some(binary_name) = queue_request.binary_name {
+        sql = sql.filter(binary_packages::name.eq(binary_name));
+    }
+
     if let Some(status) = queue_request.status {
         if status == BuildStatus::Unknown {
             sql = sql.filter(rebuilds::status.is_null());
@@ -144,55 +419,325 @@ pub async fn request_rebuild(
         }
     }
 
-    let build_input_ids = sql
-        .get_results::<i32>(connection.as_mut())
-        .map_err(Error::from)?;
-
     let now = Utc::now();
-    for build_input_id in build_input_ids {
-        let next_retry = (now - Duration::minutes(1)).naive_utc();
-        let priority = queue_request.priority.unwrap_or(Priority::manual());
-        if has_queued_friend(connection.as_mut(), build_input_id)? {
-            // set the priority of the queued item
-            diesel::update(
-                queue::table
-                    .filter(queue::build_input_id.eq_any(build_input_friends(build_input_id))),
-            )
-            .set(queue::priority.eq(priority))
-            .execute(connection.as_mut())
+    let next_retry = (now - Duration::minutes(1)).naive_utc();
+    let priority = queue_request.priority.unwrap_or(Priority::manual());
+    let built_with = queue_request.built_with;
+    let built_before = queue_request.built_before;
+    let build_architecture = queue_request.build_architecture;
+    let reason = queue_request.reason;
+
+    connection.transaction::<QueueJobResponse, _, _>(|conn| {
+        let mut build_inputs = sql
+            .get_results::<(i32, String, String, String)>(conn)
             .map_err(Error::from)?;
 
-            // reset the next_retry where applicable
-            let friends_in_queue = queue::table
-                .filter(queue::build_input_id.eq_any(build_input_friends(build_input_id)))
-                .select(queue::build_input_id)
-                .load::<i32>(connection.as_mut())
-                .map_err(Error::from)?;
+        if built_with.is_some() || built_before.is_some() {
+            let matching = latest_rebuild_matches(conn, built_with, built_before)?;
+            build_inputs.retain(|(id, ..)| matching.contains(id));
+        }
+
+        // build inputs with the same url, backend and architecture are friends and share a single queue entry
+        let mut seen = HashSet::new();
+        build_inputs.retain(|(_, url, backend, architecture)| {
+            seen.insert((url.clone(), backend.clone(), architecture.clone()))
+        });
+
+        let total = build_inputs.len();
+        let mut response = QueueJobResponse::default();
+        let mut processed = 0;
+
+        for chunk in build_inputs.chunks(REQUEUE_BATCH_SIZE) {
+            let (queued, pending) = partition_queued(conn, chunk)?;
+
+            if !queued.is_empty() {
+                // set the priority of the queued items
+                diesel::update(queue::table.filter(queue::build_input_id.eq_any(&queued)))
+                    .set(queue::priority.eq(priority))
+                    .execute(conn)
+                    .map_err(Error::from)?;
+
+                // jobs that are already running are reported the way they were started
+                if let Some(build_architecture) = &build_architecture {
+                    diesel::update(
+                        queue::table
+                            .filter(queue::build_input_id.eq_any(&queued))
+                            .filter(queue::worker.is_null()),
+                    )
+                    .set(queue::build_architecture.eq(build_architecture))
+                    .execute(conn)
+                    .map_err(Error::from)?;
+                }
+
+                if let Some(reason) = &reason {
+                    diesel::update(
+                        queue::table
+                            .filter(queue::build_input_id.eq_any(&queued))
+                            .filter(queue::worker.is_null()),
+                    )
+                    .set(queue::reason.eq(reason))
+                    .execute(conn)
+                    .map_err(Error::from)?;
+                }
+
+                // reset the next_retry where applicable
+                diesel::update(build_inputs::table.filter(build_inputs::id.eq_any(&queued)))
+                    .set(build_inputs::next_retry.eq(next_retry))
+                    .execute(conn)
+                    .map_err(Error::from)?;
+            }
+
+            response.skipped += (chunk.len() - pending.len()) as i64;
+            response.queued += queue_build_inputs(
+                conn,
+                &pending,
+                priority,
+                now.naive_utc(),
+                next_retry,
+                build_architecture.as_deref(),
+                reason.as_deref(),
+            )?;
+
+            let before = processed;
+            processed += chunk.len();
+            if processed / REQUEUE_PROGRESS_INTERVAL > before / REQUEUE_PROGRESS_INTERVAL {
+                info!("Requeue in progress: {processed}/{total} build inputs processed");
+                progress(processed, total);
+            }
+        }
+
+        Ok::<QueueJobResponse, Error>(response)
+    })
+}
+
+/// Split a chunk of build inputs into the queue entries of those that already have a queued friend, and the build
+/// inputs that still need to be queued
+fn partition_queued(
+    connection: &mut SqliteConnection,
+    build_inputs: &[(i32, String, String, String)],
+) -> Result<(Vec<i32>, Vec<i32>)> {
+    let urls = build_inputs
+        .iter()
+        .map(|(_, url, ..)| url.as_str())
+        .collect::<HashSet<_>>();
+
+    let queued_friends = queue::table
+        .inner_join(build_inputs::table)
+        .filter(build_inputs::url.eq_any(urls))
+        .select((
+            queue::build_input_id,
+            build_inputs::url,
+            build_inputs::backend,
+            build_inputs::architecture,
+        ))
+        .load::<(i32, String, String, String)>(connection)?;
+
+    let mut queued_by_friends = HashMap::<_, Vec<i32>>::new();
+    for (id, url, backend, architecture) in queued_friends {
+        queued_by_friends
+            .entry((url, backend, architecture))
+            .or_default()
+            .push(id);
+    }
+
+    let mut queued = Vec::new();
+    let mut pending = Vec::new();
+    for (id, url, backend, architecture) in build_inputs {
+        match queued_by_friends.remove(&(url.clone(), backend.clone(), architecture.clone())) {
+            Some(ids) => queued.extend(ids),
+            None => pending.push(*id),
+        }
+    }
+
+    Ok((queued, pending))
+}
 
-            diesel::update(build_inputs::table.filter(build_inputs::id.eq_any(friends_in_queue)))
-                .set(build_inputs::next_retry.eq(next_retry))
-                .execute(connection.as_mut())
+/// Build inputs whose latest rebuild was reported from the given environment, or finished before the given time. Older
+/// rebuilds are not considered, a package that has been rebuilt with a fixed toolchain since is left alone.
+fn latest_rebuild_matches(
+    connection: &mut SqliteConnection,
+    built_with: Option<String>,
+    built_before: Option<NaiveDateTime>,
+) -> Result<HashSet<i32>> {
+    let mut sql = build_inputs::table
+        .inner_join(r1.on(r1.field(rebuilds::build_input_id).is(build_inputs::id)))
+        .left_join(
+            r2.on(r2.field(rebuilds::build_input_id).is(build_inputs::id).and(
+                r1.field(rebuilds::built_at)
+                    .lt(r2.field(rebuilds::built_at))
+                    .or(r1.fields(
+                        rebuilds::built_at
+                            .eq(r2.field(rebuilds::built_at))
+                            .and(r1.field(rebuilds::id).lt(r2.field(rebuilds::id))),
+                    )),
+            )),
+        )
+        .filter(r2.field(rebuilds::id).is_null())
+        .select(build_inputs::id)
+        .into_boxed();
+
+    if let Some(built_with) = built_with {
+        sql = sql.filter(r1.field(rebuilds::environment_fingerprint).is(built_with));
+    }
+
+    if let Some(built_before) = built_before {
+        sql = sql.filter(r1.field(rebuilds::built_at).lt(built_before));
+    }
+
+    let matching = sql.load::<i32>(connection)?;
+    Ok(matching.into_iter().collect())
+}
+
+/// Reset the retry timer of the given build inputs and add them to the queue in a single batch.
+fn queue_build_inputs(
+    connection: &mut SqliteConnection,
+    build_input_ids: &[i32],
+    priority: Priority,
+    queued_at: NaiveDateTime,
+    next_retry: NaiveDateTime,
+    build_architecture: Option<&str>,
+    reason: Option<&str>,
+) -> Result<i64> {
+    if build_input_ids.is_empty() {
+        return Ok(0);
+    }
+
+    diesel::update(build_inputs::table)
+        .filter(build_inputs::id.eq_any(build_input_ids))
+        .set(build_inputs::next_retry.eq(next_retry))
+        .execute(connection)?;
+
+    let new_queued_jobs = build_input_ids
+        .iter()
+        .map(|build_input_id| NewQueued {
+            build_input_id: *build_input_id,
+            priority,
+            queued_at,
+            build_architecture: build_architecture.map(String::from),
+            bisect_step_id: None,
+            reason: reason.map(String::from),
+        })
+        .collect::<Vec<_>>();
+
+    let queued = NewQueued::upsert_batch(&new_queued_jobs, connection)?;
+    Ok(queued as i64)
+}
+
+#[get("/snapshot")]
+pub async fn export_queue(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    if auth::admin_any(
+        &cfg,
+        &req,
+        &pool,
+        &[ApiKeyScope::Queue, ApiKeyScope::ReadOnly],
+    )
+    .await
+    .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let jobs = db::run(&pool, move |connection| {
+        let jobs = queue_base()
+            .order_by(queue::id)
+            .load::<QueuedJob>(connection)?;
+        Ok(jobs)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(QueueSnapshot {
+        created_at: Utc::now().naive_utc(),
+        jobs,
+    }))
+}
+
+pub async fn import_queue(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: web::Json<QueueSnapshot>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Queue)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let snapshot = request.into_inner();
+    let response = db::run(&pool, move |connection| restore_queue(connection, snapshot)).await?;
+
+    info!(
+        "Restored {} jobs from queue snapshot, skipped {} that were already queued and {} for unknown packages",
+        response.restored, response.skipped, response.missing
+    );
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Jobs are matched to build inputs by their package, since database ids differ between instances. Jobs that were
+/// running when the snapshot was taken are restored as pending.
+fn restore_queue(
+    connection: &mut SqliteConnection,
+    snapshot: QueueSnapshot,
+) -> Result<QueueImportResponse> {
+    connection.transaction::<QueueImportResponse, _, _>(|conn| {
+        let mut response = QueueImportResponse::default();
+
+        for job in snapshot.jobs {
+            let build_input_id = build_inputs::table
+                .inner_join(source_packages::table)
+                .filter(source_packages::distribution.is(&job.distribution))
+                .filter(source_packages::release.is(&job.release))
+                .filter(source_packages::component.is(&job.component))
+                .filter(source_packages::name.is(&job.name))
+                .filter(source_packages::version.is(&job.version))
+                .filter(build_inputs::architecture.is(&job.architecture))
+                .filter(build_inputs::backend.is(&job.backend))
+                .select(build_inputs::id)
+                .order_by(build_inputs::id)
+                .first::<i32>(conn)
+                .optional()
                 .map_err(Error::from)?;
-            continue;
-        } else {
-            // no applicable queued item, set directly and upsert a new queued job
-            diesel::update(build_inputs::table)
-                .filter(build_inputs::id.eq(build_input_id))
-                .set(build_inputs::next_retry.eq(next_retry))
-                .execute(connection.as_mut())
+
+            let Some(build_input_id) = build_input_id else {
+                response.missing += 1;
+                continue;
+            };
+
+            if has_queued_friend(conn, build_input_id)? {
+                response.skipped += 1;
+                continue;
+            }
+
+            diesel::update(build_inputs::table.filter(build_inputs::id.is(build_input_id)))
+                .set((
+                    build_inputs::retries.eq(job.attempts),
+                    build_inputs::next_retry.eq(job.next_retry),
+                    build_inputs::last_failure.eq(job.last_failure),
+                ))
+                .execute(conn)
                 .map_err(Error::from)?;
 
-            let new_queued_job = NewQueued {
+            NewQueued {
                 build_input_id,
-                priority,
-                queued_at: now.naive_utc(),
-            };
+                priority: job.priority,
+                queued_at: job.queued_at,
+                build_architecture: job.build_architecture,
+                bisect_step_id: None,
+                reason: job.reason,
+            }
+            .upsert(conn)?;
 
-            new_queued_job.upsert(connection.as_mut())?;
+            response.restored += 1;
         }
-    }
 
-    Ok(HttpResponse::NoContent())
+        Ok::<QueueImportResponse, Error>(response)
+    })
 }
 
 #[delete("")]
@@ -203,50 +748,160 @@ pub async fn drop_queued_jobs(
     origin_filter: web::Query<OriginFilter>,
     source_identity_filter: web::Query<SourceIdentityFilter>,
 ) -> web::Result<impl Responder> {
-    if auth::admin(&cfg, &req).is_err() {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Queue)
+        .await
+        .is_err()
+    {
         return Ok(HttpResponse::Forbidden());
     }
 
-    let mut connection = pool.get().map_err(Error::from)?;
+    let origin_filter = origin_filter.into_inner();
+    let source_identity_filter = source_identity_filter.into_inner();
 
-    let ids = queue::table
-        .inner_join(build_inputs::table.inner_join(source_packages::table))
-        .filter(
-            origin_filter
-                .clone()
-                .into_inner()
-                .into_filter(build_inputs::architecture),
-        )
-        .filter(
-            source_identity_filter
-                .clone()
-                .into_inner()
-                .into_filter(source_packages::name, source_packages::version),
-        )
-        .select(queue::id)
-        .load::<i32>(connection.as_mut())
-        .map_err(Error::from)?;
+    db::run(&pool, move |connection| {
+        let ids = queue::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .filter(
+                source_identity_filter.into_filter(source_packages::name, source_packages::version),
+            )
+            .select(queue::id)
+            .load::<i32>(connection)?;
 
-    diesel::delete(queue::table.filter(queue::id.eq_any(ids)))
-        .execute(connection.as_mut())
-        .map_err(Error::from)?;
+        diesel::delete(queue::table.filter(queue::id.eq_any(ids))).execute(connection)?;
+        Ok(())
+    })
+    .await?;
 
     Ok(HttpResponse::NoContent())
 }
 
+/// Pin the matching jobs to a worker, eg. to reproduce a failure on a machine with shell access. A job that is already
+/// running is left alone, the pin applies the next time it's picked up.
+#[post("/assign")]
+pub async fn assign_queued_jobs(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    origin_filter: web::Query<OriginFilter>,
+    source_identity_filter: web::Query<SourceIdentityFilter>,
+    request: web::Json<AssignQueuedJobsRequest>,
+) -> web::Result<impl Responder> {
+    if auth::admin_any(
+        &cfg,
+        &req,
+        &pool,
+        &[ApiKeyScope::Queue, ApiKeyScope::Operator],
+    )
+    .await
+    .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let origin_filter = origin_filter.into_inner();
+    let source_identity_filter = source_identity_filter.into_inner();
+    let pinned_worker = request.into_inner().worker;
+
+    let assigned = db::run(&pool, move |connection| {
+        if let Some(worker) = pinned_worker {
+            let exists = workers::table
+                .filter(workers::id.is(worker))
+                .count()
+                .get_result::<i64>(connection)?;
+            if exists == 0 {
+                return Ok(None);
+            }
+        }
+
+        let ids = queue::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .filter(
+                source_identity_filter.into_filter(source_packages::name, source_packages::version),
+            )
+            .select(queue::id)
+            .load::<i32>(connection)?;
+
+        let count = diesel::update(queue::table.filter(queue::id.eq_any(ids)))
+            .set(queue::pinned_worker.eq(pinned_worker))
+            .execute(connection)?;
+        Ok(Some(count as i64))
+    })
+    .await?;
+
+    if let Some(assigned) = assigned {
+        Ok(HttpResponse::Ok().json(AssignQueuedJobsResponse { assigned }))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+fn wipe_queue_jobs(connection: &mut SqliteConnection, origin_filter: OriginFilter) -> Result<i64> {
+    connection.transaction(|conn| {
+        let ids = queue::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(origin_filter.into_filter(build_inputs::architecture))
+            .select(queue::id)
+            .load::<i32>(conn)?;
+
+        // a whole suite can exceed the number of bound parameters sqlite accepts in one statement
+        let mut dropped = 0;
+        for chunk in ids.chunks(WIPE_BATCH_SIZE) {
+            dropped +=
+                diesel::delete(queue::table.filter(queue::id.eq_any(chunk))).execute(conn)?;
+        }
+        Ok(dropped as i64)
+    })
+}
+
+/// Drop all jobs of the matching suites at once, either everything or nothing is dropped. Without a filter this
+/// empties the whole queue, so the request has to be confirmed explicitly.
+#[post("/wipe")]
+pub async fn wipe_queue(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    origin_filter: web::Query<OriginFilter>,
+    request: web::Json<WipeQueueRequest>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Queue)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    if !request.confirm {
+        return Ok(HttpResponse::BadRequest().body("Wiping the queue has to be confirmed"));
+    }
+
+    let origin_filter = origin_filter.into_inner();
+    let dropped = db::run(&pool, move |connection| {
+        wipe_queue_jobs(connection, origin_filter)
+    })
+    .await?;
+    info!("Wiped {dropped} jobs from the queue");
+
+    Ok(HttpResponse::Ok().json(WipeQueueResponse { dropped }))
+}
+
 #[get("/{id}")]
 pub async fn get_queued_job(
     pool: web::Data<Pool>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let record = db::run(&pool, move |connection| {
+        let record = queue_base()
+            .filter(source_packages::id.is(id))
+            .get_result::<QueuedJob>(connection)
+            .optional()?;
+        Ok(record)
+    })
+    .await?;
 
-    if let Some(record) = queue_base()
-        .filter(source_packages::id.is(id.into_inner()))
-        .get_result::<QueuedJob>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?
-    {
+    if let Some(record) = record {
         Ok(HttpResponse::Ok().json(record))
     } else {
         Ok(HttpResponse::NotFound().finish())
@@ -260,15 +915,19 @@ pub async fn drop_queued_job(
     pool: web::Data<Pool>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    if auth::admin(&cfg, &req).is_err() {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Queue)
+        .await
+        .is_err()
+    {
         return Ok(HttpResponse::Forbidden());
     }
 
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let dropped_jobs = diesel::delete(queue::table.filter(queue::id.is(id.into_inner())))
-        .execute(connection.as_mut())
-        .map_err(Error::from)?;
+    let id = id.into_inner();
+    let dropped_jobs = db::run(&pool, move |connection| {
+        let count = diesel::delete(queue::table.filter(queue::id.is(id))).execute(connection)?;
+        Ok(count)
+    })
+    .await?;
 
     if dropped_jobs < 1 {
         Ok(HttpResponse::NotFound())
@@ -277,42 +936,111 @@ pub async fn drop_queued_job(
     }
 }
 
-#[post("/{id}/ping")]
 pub async fn ping_job(
-    req: HttpRequest,
     cfg: web::Data<Config>,
     pool: web::Data<Pool>,
+    identity: web::ReqData<AuthenticatedWorker>,
     id: web::Path<i32>,
+    request: Option<web::Json<PingJobRequest>>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let check_worker = auth::worker(&cfg, &req, connection.as_mut());
-    if check_worker.is_err() {
+    let identity = identity.into_inner();
+    if !identity.permits(WorkerCapability::Pop) {
         return Ok(HttpResponse::Forbidden().finish());
     }
-
-    let worker = check_worker?;
+    let worker = identity.worker;
+    let draining = worker.draining;
 
     let now = Utc::now();
+    let id = id.into_inner();
+    // older workers don't send a body, don't clear the phase in that case
+    let request = request
+        .map(|request| request.into_inner())
+        .unwrap_or_default();
+    let phase = request.phase;
+    let max_build_deadline = cfg.schedule.max_build_deadline();
 
-    let affected_jobs = diesel::update(queue::table)
-        .set(queue::last_ping.eq(now.naive_utc()))
-        .filter(
-            queue::id
-                .is(id.into_inner())
-                .and(queue::worker.is(worker.id)),
+    let (aborted, affected_jobs) = db::run(&pool, move |connection| {
+        // aborted jobs are discarded without a result instead of going back to the queue
+        let aborted = diesel::delete(
+            queue::table
+                .filter(queue::id.is(id).and(queue::worker.is(worker.id)))
+                .filter(queue::aborted_at.is_not_null()),
         )
-        .execute(connection.as_mut())
-        .map_err(Error::from)?;
+        .execute(connection)?;
+        if aborted > 0 {
+            return Ok((true, 0));
+        }
+
+        let deadline = if let Some(extend) = request.extend
+            && let Some(max_build_deadline) = max_build_deadline
+        {
+            queue::table
+                .filter(queue::id.is(id).and(queue::worker.is(worker.id)))
+                .select((queue::started_at, queue::deadline))
+                .get_result::<(Option<NaiveDateTime>, Option<NaiveDateTime>)>(connection)
+                .optional()?
+                .and_then(|(started_at, deadline)| {
+                    extend_deadline(
+                        started_at?,
+                        deadline?,
+                        now.naive_utc(),
+                        extend,
+                        max_build_deadline,
+                    )
+                })
+        } else {
+            None
+        };
+
+        let count = diesel::update(queue::table)
+            .set((
+                queue::last_ping.eq(now.naive_utc()),
+                phase.map(|phase| queue::phase.eq(truncate_phase(phase))),
+                deadline.map(|deadline| queue::deadline.eq(deadline)),
+            ))
+            .filter(queue::id.is(id).and(queue::worker.is(worker.id)))
+            .execute(connection)?;
+        Ok((false, count))
+    })
+    .await?;
+
+    // tells the worker to kill the build and drop the job
+    if aborted {
+        return Ok(HttpResponse::Gone().finish());
+    }
 
     // schema does not allow for more than one record to match
     if affected_jobs < 1 {
         Ok(HttpResponse::NotFound().finish())
     } else {
-        Ok(HttpResponse::NoContent().finish())
+        Ok(HttpResponse::Ok().json(PingJobResponse { draining }))
     }
 }
 
+/// Rebuilder scripts may ask for more time, but never beyond the limit counted from when the job was picked up. Returns
+/// `None` if the current deadline is already later.
+fn extend_deadline(
+    started_at: NaiveDateTime,
+    deadline: NaiveDateTime,
+    now: NaiveDateTime,
+    extend: u64,
+    max_build_deadline: Duration,
+) -> Option<NaiveDateTime> {
+    let limit = started_at + max_build_deadline;
+    let extend = i64::try_from(extend).unwrap_or(i64::MAX);
+    let requested = now + Duration::seconds(extend.min(max_build_deadline.num_seconds()));
+    let extended = requested.min(limit);
+    (extended > deadline).then_some(extended)
+}
+
+/// Phases are reported by rebuilder scripts, keep them reasonably short
+fn truncate_phase(mut phase: String) -> String {
+    if let Some((idx, _)) = phase.char_indices().nth(MAX_PHASE_LEN) {
+        phase.truncate(idx);
+    }
+    phase
+}
+
 /// Standardizes architectures in the given list, expanding known aliases to other commonly-used architecture names.
 /// Rust's builtin architecture variables don't always line up with what distros use (x86_64 vs amd64, for instance), so
 /// we do some post-processing here.
@@ -342,70 +1070,345 @@ define_sql_function! {
     fn sqlite_random() -> Integer
 }
 
-#[post("/pop")]
 pub async fn request_work(
-    req: HttpRequest,
     cfg: web::Data<Config>,
     pool: web::Data<Pool>,
+    scheduler: web::Data<Arc<FairScheduler>>,
+    identity: web::ReqData<AuthenticatedWorker>,
     request: web::Json<PopQueuedJobRequest>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-
-    let check_worker = auth::worker(&cfg, &req, connection.as_mut());
-    let Ok(worker) = check_worker else {
+    let identity = identity.into_inner();
+    if !identity.permits(WorkerCapability::Pop) {
         return Ok(HttpResponse::Forbidden().finish());
+    }
+    let worker = identity.worker;
+
+    if worker.draining {
+        debug!(
+            "Worker {:?} is draining, not assigning new work",
+            worker.name
+        );
+        return Ok(HttpResponse::Ok().json(JobAssignment::Nothing));
+    }
+
+    let cfg = cfg.into_inner();
+    let scheduler = scheduler.into_inner();
+    let pop_request = request.into_inner();
+
+    let record = db::run(&pool, move |connection| {
+        pop_job(connection, &cfg, &scheduler, &worker, pop_request)
+    })
+    .await?;
+
+    if let Some(record) = record {
+        Ok(HttpResponse::Ok().json(JobAssignment::Rebuild(Box::new(record))))
+    } else {
+        Ok(HttpResponse::Ok().json(JobAssignment::Nothing))
+    }
+}
+
+/// Whether the next job should be a long build, so that at most half of the builds running on the architectures of
+/// the worker are long ones. Builds without an estimate count as short builds.
+fn prefer_long_build(
+    conn: &mut SqliteConnection,
+    cfg: &Config,
+    supported_architectures: &[String],
+) -> Result<bool> {
+    let running = || {
+        queue::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .left_join(build_durations::table.on(estimated_duration()))
+            .filter(queue::worker.is_not_null())
+            .filter(build_inputs::architecture.eq_any(supported_architectures))
     };
 
+    let total = running().count().get_result::<i64>(conn)?;
+    let long = running()
+        .filter(build_durations::seconds.ge(cfg.schedule.long_build_threshold()))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok((long + 1) * 2 <= total + 1)
+}
+
+/// What a worker is able to build, gathered once per request for work
+struct SchedulingContext<'a> {
+    worker: &'a Worker,
+    supported_architectures: Vec<String>,
+    /// Packages for these architectures are only built by this worker if a cross build was requested
+    cross_architectures: Vec<String>,
+    pop_request: PopQueuedJobRequest,
+    /// Provisional results that need to be confirmed by a trusted worker, another untrusted worker can't do that
+    awaiting_confirmation: Vec<i32>,
+    /// Packages that keep failing on this worker for reasons of its own are kept away from it
+    excluded_packages: &'a [String],
+}
+
+/// Pick the next unpinned job for a worker, suites with work at the most urgent priority take turns. The pick is
+/// charged by the caller once the job was handed out.
+fn schedule_job(
+    conn: &mut SqliteConnection,
+    cfg: &Config,
+    scheduler: &FairScheduler,
+    ctx: &SchedulingContext,
+) -> Result<Option<(QueuedJob, Pick)>> {
+    let SchedulingContext {
+        worker,
+        supported_architectures,
+        cross_architectures,
+        pop_request,
+        awaiting_confirmation,
+        excluded_packages,
+    } = ctx;
+
+    // find the suites that have work available at the most urgent priority
+    let candidates = queue::table
+        .inner_join(build_inputs::table.inner_join(source_packages::table))
+        .filter(queue::worker.is_null())
+        .filter(queue::pinned_worker.is_null())
+        .filter(
+            build_inputs::next_retry
+                .is_null()
+                .or(build_inputs::next_retry.le(diesel::dsl::now)),
+        )
+        .filter(
+            queue::build_architecture
+                .is_null()
+                .and(build_inputs::architecture.eq_any(supported_architectures))
+                .or(queue::build_architecture
+                    .eq_any(supported_architectures)
+                    .and(build_inputs::architecture.eq_any(cross_architectures))),
+        )
+        .filter(build_inputs::backend.eq_any(&pop_request.supported_backends))
+        .filter(queue::build_input_id.ne_all(awaiting_confirmation))
+        .filter(source_packages::name.ne_all(*excluded_packages))
+        .select((
+            queue::priority,
+            source_packages::distribution,
+            source_packages::release,
+        ))
+        .distinct()
+        .order_by((
+            queue::priority,
+            source_packages::distribution,
+            source_packages::release,
+        ))
+        .load::<(i32, String, Option<String>)>(conn)?;
+
+    let priority = candidates.first().map(|(priority, _, _)| *priority);
+    let candidates = candidates
+        .into_iter()
+        .filter(|(p, _, _)| Some(*p) == priority)
+        .map(|(_, distribution, release)| Suite {
+            distribution,
+            release,
+        })
+        .collect::<Vec<_>>();
+
+    let Some(pick) = scheduler.pick(&cfg.schedule, &candidates) else {
+        return Ok(None);
+    };
+    let suite = &pick.suite;
+    debug!("Picked suite for worker {:?}: {:?}", worker.name, suite);
+
+    let query = queue_base()
+        .filter(queue::worker.is_null())
+        .filter(queue::pinned_worker.is_null())
+        .filter(
+            build_inputs::next_retry
+                .is_null()
+                .or(build_inputs::next_retry.le(diesel::dsl::now)),
+        )
+        .filter(
+            queue::build_architecture
+                .is_null()
+                .and(build_inputs::architecture.eq_any(supported_architectures))
+                .or(queue::build_architecture
+                    .eq_any(supported_architectures)
+                    .and(build_inputs::architecture.eq_any(cross_architectures))),
+        )
+        .filter(build_inputs::backend.eq_any(&pop_request.supported_backends))
+        .filter(queue::build_input_id.ne_all(awaiting_confirmation))
+        .filter(source_packages::name.ne_all(*excluded_packages))
+        .filter(source_packages::distribution.eq(&suite.distribution))
+        .filter(source_packages::release.is(&suite.release))
+        .order_by(queue::priority)
+        .into_boxed();
+
+    let long_build = build_durations::seconds.ge(cfg.schedule.long_build_threshold());
+    let query = match cfg.schedule.job_order() {
+        JobOrder::Queued => query,
+        // sqlite sorts NULL first, jobs without an estimate are picked as short builds
+        JobOrder::InterleaveDurations => {
+            if prefer_long_build(conn, cfg, supported_architectures)? {
+                query.then_order_by(long_build.desc())
+            } else {
+                query.then_order_by(long_build.asc())
+            }
+        }
+    };
+
+    let record = query
+        .then_order_by(diesel::dsl::date(queue::queued_at))
+        .then_order_by(build_inputs::popularity.desc())
+        .then_order_by(sqlite_random())
+        .first::<QueuedJob>(conn)
+        .optional()?;
+
+    Ok(record.map(|record| (record, pick)))
+}
+
+fn pop_job(
+    connection: &mut SqliteConnection,
+    cfg: &Config,
+    scheduler: &FairScheduler,
+    worker: &Worker,
+    pop_request: PopQueuedJobRequest,
+) -> Result<Option<QueuedJobWithArtifacts>> {
     // clear any stale jobs before we consider available jobs in the queue
     let now = Utc::now();
     let then = now - Duration::seconds(PING_DEADLINE);
 
     debug!("Clearing stale jobs last pinged before {then:?}...");
+    // an aborted job whose worker stopped pinging is dropped, not handed to the next worker
+    diesel::delete(
+        queue::table.filter(queue::aborted_at.is_not_null()).filter(
+            queue::last_ping
+                .lt(then.naive_utc())
+                .or(queue::deadline.lt(now.naive_utc())),
+        ),
+    )
+    .execute(connection)?;
+
+    let stale = queue::table
+        .inner_join(workers::table.on(queue::worker.is(workers::id.nullable())))
+        .filter(
+            queue::last_ping
+                .is_not_null()
+                .and(queue::last_ping.lt(then.naive_utc())),
+        )
+        .select((queue::build_input_id, workers::name))
+        .load::<(i32, String)>(connection)?;
+
+    for (build_input_id, worker_name) in stale {
+        let last_failure = format!("worker {worker_name:?} stopped responding");
+        update(build_inputs::table.filter(build_inputs::id.is(build_input_id)))
+            .set(build_inputs::last_failure.eq(last_failure))
+            .execute(connection)?;
+    }
+
+    // jobs that are still pinged, but ran past their deadline
+    let overdue = queue::table
+        .inner_join(workers::table.on(queue::worker.is(workers::id.nullable())))
+        .filter(queue::deadline.lt(now.naive_utc()))
+        .select((queue::build_input_id, workers::name))
+        .load::<(i32, String)>(connection)?;
+
+    for (build_input_id, worker_name) in overdue {
+        let last_failure = format!("build on worker {worker_name:?} exceeded its deadline");
+        update(build_inputs::table.filter(build_inputs::id.is(build_input_id)))
+            .set(build_inputs::last_failure.eq(last_failure))
+            .execute(connection)?;
+    }
+
     update(
         queue::table.filter(
             queue::last_ping
                 .is_not_null()
-                .and(queue::last_ping.lt(then.naive_utc())),
+                .and(queue::last_ping.lt(then.naive_utc()))
+                .or(queue::deadline.lt(now.naive_utc())),
         ),
     )
     .set((
         queue::worker.eq(None::<i32>),
         queue::started_at.eq(None::<NaiveDateTime>),
         queue::last_ping.eq(None::<NaiveDateTime>),
+        queue::phase.eq(None::<String>),
+        queue::deadline.eq(None::<NaiveDateTime>),
     ))
-    .execute(connection.as_mut())
-    .map_err(Error::from)?;
+    .execute(connection)?;
 
     // see if we can dig up any available work for this worker
-    let pop_request = request.into_inner();
     let supported_architectures = standardize_architectures(&pop_request.supported_architectures);
+    let cross_architectures = standardize_architectures(&pop_request.cross_architectures);
+
+    // remember what the worker is able to build, this is used to detect architectures without any workers
+    let mut architectures = supported_architectures.clone();
+    architectures.sort();
+    update(workers::table.filter(workers::id.is(worker.id)))
+        .set(workers::architectures.eq(architectures.join(",")))
+        .execute(connection)?;
 
     debug!(
         "Trying to find work for worker {:?}... ({supported_architectures:?})",
         worker.name
     );
 
-    if let Some(record) =
-        connection.transaction::<Option<QueuedJobWithArtifacts>, _, _>(|conn| {
-            if let Some(record) = queue_base()
+    let awaiting_confirmation = if worker.trusted {
+        Vec::new()
+    } else {
+        provisional_rebuilds::table
+            .filter(provisional_rebuilds::resolved_at.is_null())
+            .select(provisional_rebuilds::build_input_id)
+            .distinct()
+            .load::<i32>(connection)?
+    };
+
+    let ctx = SchedulingContext {
+        worker,
+        supported_architectures,
+        cross_architectures,
+        pop_request,
+        awaiting_confirmation,
+        excluded_packages: cfg.schedule.excluded_packages(&worker.name),
+    };
+
+    let assigned =
+        connection.transaction::<Option<(QueuedJobWithArtifacts, Option<Pick>)>, _, _>(|conn| {
+            // jobs a worker claimed are unavailable to everyone else until it reports or stops pinging them
+            if let Some(max_jobs) = cfg.schedule.max_jobs(&worker.name) {
+                let claimed = queue::table
+                    .filter(queue::worker.is(worker.id))
+                    .count()
+                    .get_result::<i64>(conn)
+                    .map_err(Error::from)?;
+
+                if claimed >= max_jobs {
+                    debug!(
+                        "Worker {:?} already claimed {claimed} jobs, not assigning new work",
+                        worker.name
+                    );
+                    return Ok(None);
+                }
+            }
+
+            // jobs pinned to this worker skip the scheduler, and the retry delay
+            let record = if let Some(record) = queue_base()
                 .filter(queue::worker.is_null())
+                .filter(queue::pinned_worker.is(worker.id))
                 .filter(
-                    build_inputs::next_retry
+                    queue::build_architecture
                         .is_null()
-                        .or(build_inputs::next_retry.le(diesel::dsl::now)),
+                        .and(build_inputs::architecture.eq_any(&ctx.supported_architectures))
+                        .or(queue::build_architecture
+                            .eq_any(&ctx.supported_architectures)
+                            .and(build_inputs::architecture.eq_any(&ctx.cross_architectures))),
                 )
-                .filter(build_inputs::architecture.eq_any(supported_architectures))
-                .filter(build_inputs::backend.eq_any(pop_request.supported_backends))
-                .order_by((
-                    queue::priority,
-                    diesel::dsl::date(queue::queued_at),
-                    sqlite_random(),
-                ))
+                .filter(build_inputs::backend.eq_any(&ctx.pop_request.supported_backends))
+                .filter(queue::build_input_id.ne_all(&ctx.awaiting_confirmation))
+                .order_by((queue::priority, queue::queued_at))
                 .first::<QueuedJob>(conn)
                 .optional()
                 .map_err(Error::from)?
             {
-                let artifacts = queue::table
+                debug!("Found job pinned to worker {:?}", worker.name);
+                Some((record, None))
+            } else {
+                schedule_job(conn, cfg, scheduler, &ctx)?.map(|(record, pick)| (record, Some(pick)))
+            };
+
+            if let Some((record, pick)) = record {
+                let mut artifacts = queue::table
                     .filter(queue::id.is(record.id))
                     .inner_join(
                         binary_packages::table
@@ -416,9 +1419,27 @@ pub async fn request_work(
                         binary_packages::version,
                         binary_packages::architecture,
                         binary_packages::artifact_url,
+                        binary_packages::checksum,
                     ))
                     .get_results::<QueuedJobArtifact>(conn)
                     .map_err(Error::from)?;
+                mirrors::resolve_artifact_urls(
+                    &cfg.url_templates,
+                    &ctx.pop_request.mirrors,
+                    &record,
+                    &mut artifacts,
+                );
+
+                // build inputs imported before the sync reported payloads are handed out as is
+                let payload = queue::table
+                    .inner_join(build_inputs::table)
+                    .filter(queue::id.is(record.id))
+                    .select(build_inputs::payload)
+                    .get_result::<Option<JobPayload>>(conn)
+                    .map_err(Error::from)?
+                    .unwrap_or_else(|| JobPayload::Generic {
+                        url: record.url.clone(),
+                    });
 
                 let now = Utc::now().naive_utc();
                 let status = format!("working hard on {} {}", record.name, record.version);
@@ -433,6 +1454,9 @@ pub async fn request_work(
                         queue::started_at.eq(now),
                         queue::worker.eq(worker.id),
                         queue::last_ping.eq(now),
+                        queue::phase.eq(None::<String>),
+                        queue::deadline
+                            .eq(cfg.schedule.build_deadline().map(|deadline| now + deadline)),
                     ))
                     .execute(conn)
                     .map_err(Error::from)?;
@@ -447,10 +1471,14 @@ pub async fn request_work(
                     .execute(conn)
                     .map_err(Error::from)?;
 
-                Ok::<Option<QueuedJobWithArtifacts>, Error>(Some(QueuedJobWithArtifacts {
+                let upload_artifacts = cfg.verification.verifies(&record.distribution);
+                let job = QueuedJobWithArtifacts {
                     job: record,
                     artifacts,
-                }))
+                    payload: Some(payload),
+                    upload_artifacts,
+                };
+                Ok::<_, Error>(Some((job, pick)))
             } else {
                 debug!(
                     "Could not find any item in work queue for worker {:?}",
@@ -458,10 +1486,14 @@ pub async fn request_work(
                 );
                 Ok(None)
             }
-        })?
-    {
-        Ok(HttpResponse::Ok().json(JobAssignment::Rebuild(Box::new(record))))
-    } else {
-        Ok(HttpResponse::Ok().json(JobAssignment::Nothing))
+        })?;
+
+    // the suite only pays for the job once it's committed as assigned
+    let Some((job, pick)) = assigned else {
+        return Ok(None);
+    };
+    if let Some(pick) = pick {
+        scheduler.charge(&cfg.schedule, &pick);
     }
+    Ok(Some(job))
 }
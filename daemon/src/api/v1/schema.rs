@@ -0,0 +1,18 @@
+use crate::api::v1::util::json_schema::SCHEMAS;
+use crate::web;
+use actix_web::{HttpResponse, Responder, get};
+
+#[get("")]
+pub async fn get_schemas() -> web::Result<impl Responder> {
+    let names = SCHEMAS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(names))
+}
+
+#[get("/{name}")]
+pub async fn get_schema(name: web::Path<String>) -> web::Result<impl Responder> {
+    let Some((_, schema_for)) = SCHEMAS.iter().find(|(known, _)| *known == name.as_str()) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    Ok(HttpResponse::Ok().json(schema_for()))
+}
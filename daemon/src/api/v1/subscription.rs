@@ -0,0 +1,383 @@
+use crate::api::v1::util::auth;
+use crate::config::Config;
+use crate::db::{self, Pool};
+use crate::models::{NewSubscription, Subscription as SubscriptionRow};
+use crate::secrets;
+use crate::web;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::api::v1::{Subscription, SubscriptionConfirmation, SubscriptionRequest};
+
+fn into_subscription(row: SubscriptionRow) -> Subscription {
+    Subscription {
+        id: row.id,
+        name: row.name,
+        maintainer: row.maintainer,
+        distribution: row.distribution,
+        webhook: row.webhook,
+        email: row.email,
+        created_at: row.created_at,
+        confirmed: row.confirmed_at.is_some(),
+    }
+}
+
+/// Empty strings are treated like a missing field
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
+}
+
+pub async fn get_subscriptions(
+    req: HttpRequest,
+    pool: web::Data<Pool>,
+) -> web::Result<impl Responder> {
+    let Ok(api_key) = auth::api_key(&req, &pool).await else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let subscriptions = db::run(&pool, move |connection| {
+        SubscriptionRow::list(api_key.id, connection)
+    })
+    .await?;
+
+    let subscriptions = subscriptions
+        .into_iter()
+        .map(into_subscription)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(subscriptions))
+}
+
+pub async fn subscribe(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    request: web::Json<SubscriptionRequest>,
+) -> web::Result<impl Responder> {
+    let Ok(api_key) = auth::api_key(&req, &pool).await else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let request = request.into_inner();
+    let name = non_empty(request.name);
+    let maintainer = non_empty(request.maintainer);
+    let webhook = non_empty(request.webhook);
+    let email = non_empty(request.email);
+
+    if name.is_some() == maintainer.is_some() {
+        return Ok(HttpResponse::BadRequest()
+            .body("A subscription follows either a package name or a maintainer\n"));
+    }
+
+    let check = match (&webhook, &email) {
+        (Some(webhook), None) => cfg.notifier.check_subscriber_webhook(webhook).await,
+        (None, Some(email)) => cfg.notifier.check_subscriber_email(email),
+        _ => {
+            return Ok(HttpResponse::BadRequest()
+                .body("A subscription notifies either a webhook or an email address\n"));
+        }
+    };
+    if let Err(err) = check {
+        return Ok(HttpResponse::BadRequest().body(format!("{err:#}\n")));
+    }
+
+    // an address is only mailed once it confirmed the token, further subscriptions of the same key wait for the
+    // token that was already sent instead of mailing the address again
+    let now = Utc::now().naive_utc();
+    let (confirmation_token_hash, confirmed_at) = match &email {
+        None => (None, Some(now)),
+        Some(address) => {
+            let lookup = address.clone();
+            let (confirmed, pending) = db::run(&pool, move |connection| {
+                let confirmed = SubscriptionRow::is_confirmed(api_key.id, &lookup, connection)?;
+                let pending = SubscriptionRow::pending_token_hash(api_key.id, &lookup, connection)?;
+                Ok((confirmed, pending))
+            })
+            .await?;
+
+            if confirmed {
+                (None, Some(now))
+            } else if let Some(token_hash) = pending {
+                (Some(token_hash), None)
+            } else {
+                let token = Alphanumeric.sample_string(&mut rand::rng(), 32);
+                cfg.notifier.send_confirmation(address, &token).await?;
+                (Some(secrets::hash(&token)), None)
+            }
+        }
+    };
+
+    let subscription = db::run(&pool, move |connection| {
+        NewSubscription {
+            api_key_id: api_key.id,
+            name,
+            maintainer,
+            distribution: request.distribution,
+            webhook,
+            email,
+            created_at: now,
+            confirmation_token_hash,
+            confirmed_at,
+        }
+        .insert(connection)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(into_subscription(subscription)))
+}
+
+/// The token authenticates the request, the address it was mailed to doesn't need an api key
+pub async fn confirm_subscription(
+    pool: web::Data<Pool>,
+    request: web::Json<SubscriptionConfirmation>,
+) -> web::Result<impl Responder> {
+    let token_hash = secrets::hash(&request.token);
+    let confirmed = db::run(&pool, move |connection| {
+        SubscriptionRow::confirm(&token_hash, connection)
+    })
+    .await?;
+
+    if confirmed > 0 {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+pub async fn unsubscribe(
+    req: HttpRequest,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    let Ok(api_key) = auth::api_key(&req, &pool).await else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let id = id.into_inner();
+    let deleted = db::run(&pool, move |connection| {
+        SubscriptionRow::delete(id, api_key.id, connection)
+    })
+    .await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db;
+    use crate::models::{NewApiKey, NewSubscription, Subscription};
+    use crate::notify::Recipients;
+    use crate::secrets;
+    use chrono::Utc;
+    use diesel::SqliteConnection;
+    use rebuilderd_common::api::v1::ApiKeyScope;
+
+    fn api_key(connection: &mut SqliteConnection, key: &str) -> i32 {
+        NewApiKey {
+            name: key.to_string(),
+            key_hash: secrets::hash(key),
+            scope: ApiKeyScope::ReadOnly,
+            created_at: Utc::now().naive_utc(),
+        }
+        .insert(connection)
+        .unwrap()
+    }
+
+    fn subscribe(connection: &mut SqliteConnection, subscription: NewSubscription) -> i32 {
+        subscription.insert(connection).unwrap().id
+    }
+
+    fn webhook(api_key_id: i32, distribution: Option<&str>, webhook: &str) -> NewSubscription {
+        NewSubscription {
+            api_key_id,
+            name: Some("foo".to_string()),
+            maintainer: None,
+            distribution: distribution.map(String::from),
+            webhook: Some(webhook.to_string()),
+            email: None,
+            created_at: Utc::now().naive_utc(),
+            confirmation_token_hash: None,
+            confirmed_at: Some(Utc::now().naive_utc()),
+        }
+    }
+
+    fn email(api_key_id: i32, email: &str, token: &str) -> NewSubscription {
+        NewSubscription {
+            api_key_id,
+            name: Some("foo".to_string()),
+            maintainer: None,
+            distribution: None,
+            webhook: None,
+            email: Some(email.to_string()),
+            created_at: Utc::now().naive_utc(),
+            confirmation_token_hash: Some(secrets::hash(token)),
+            confirmed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_webhooks() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let alice = api_key(&mut connection, "alice");
+        let bob = api_key(&mut connection, "bob");
+
+        subscribe(
+            &mut connection,
+            webhook(alice, None, "https://alice.example/hook"),
+        );
+        subscribe(
+            &mut connection,
+            webhook(bob, Some("debian"), "https://bob.example/hook"),
+        );
+        subscribe(
+            &mut connection,
+            webhook(bob, Some("archlinux"), "https://bob.example/arch"),
+        );
+        // both keys share an endpoint, it's only notified once
+        subscribe(
+            &mut connection,
+            webhook(bob, None, "https://alice.example/hook"),
+        );
+
+        let recipients =
+            Subscription::recipients("foo", None, "archlinux", &mut connection).unwrap();
+        assert_eq!(
+            recipients.webhooks,
+            vec!["https://alice.example/hook", "https://bob.example/arch"]
+        );
+        assert_eq!(
+            Subscription::recipients("bar", None, "archlinux", &mut connection).unwrap(),
+            Recipients::default()
+        );
+        assert_eq!(
+            Subscription::active("foo", None, "archlinux", &mut connection)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_maintainer_subscriptions() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let alice = api_key(&mut connection, "alice");
+
+        subscribe(
+            &mut connection,
+            NewSubscription {
+                api_key_id: alice,
+                name: None,
+                maintainer: Some("Rust Maintainers <rust@example.com>".to_string()),
+                distribution: None,
+                webhook: None,
+                email: Some("alice@example.com".to_string()),
+                created_at: Utc::now().naive_utc(),
+                confirmation_token_hash: None,
+                confirmed_at: Some(Utc::now().naive_utc()),
+            },
+        );
+
+        let recipients = Subscription::recipients(
+            "foo",
+            Some("Rust Maintainers <rust@example.com>"),
+            "debian",
+            &mut connection,
+        )
+        .unwrap();
+        assert_eq!(
+            recipients,
+            Recipients {
+                webhooks: vec![],
+                emails: vec!["alice@example.com".to_string()],
+            }
+        );
+        assert!(
+            Subscription::recipients("foo", None, "debian", &mut connection)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            Subscription::active(
+                "foo",
+                Some("Perl Maintainers <perl@example.com>"),
+                "debian",
+                &mut connection
+            )
+            .unwrap()
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_unconfirmed_email_is_not_notified() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let alice = api_key(&mut connection, "alice");
+        let mallory = api_key(&mut connection, "mallory");
+
+        subscribe(
+            &mut connection,
+            email(alice, "alice@example.com", "token-a"),
+        );
+        subscribe(
+            &mut connection,
+            email(mallory, "victim@example.com", "token-m"),
+        );
+
+        assert!(
+            Subscription::recipients("foo", None, "debian", &mut connection)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            Subscription::active("foo", None, "debian", &mut connection)
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            Subscription::confirm(&secrets::hash("wrong"), &mut connection).unwrap(),
+            0
+        );
+        assert_eq!(
+            Subscription::confirm(&secrets::hash("token-a"), &mut connection).unwrap(),
+            1
+        );
+        // the token can only be used once
+        assert_eq!(
+            Subscription::confirm(&secrets::hash("token-a"), &mut connection).unwrap(),
+            0
+        );
+
+        let recipients = Subscription::recipients("foo", None, "debian", &mut connection).unwrap();
+        assert_eq!(recipients.emails, vec!["alice@example.com"]);
+        assert!(Subscription::is_confirmed(alice, "alice@example.com", &mut connection).unwrap());
+        assert_eq!(
+            Subscription::pending_token_hash(mallory, "victim@example.com", &mut connection)
+                .unwrap(),
+            Some(secrets::hash("token-m"))
+        );
+    }
+
+    #[test]
+    fn test_delete_only_own_subscriptions() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let alice = api_key(&mut connection, "alice");
+        let bob = api_key(&mut connection, "bob");
+        let id = subscribe(
+            &mut connection,
+            webhook(alice, None, "https://alice.example/hook"),
+        );
+
+        assert!(!Subscription::delete(id, bob, &mut connection).unwrap());
+        assert_eq!(Subscription::list(alice, &mut connection).unwrap().len(), 1);
+        assert!(Subscription::delete(id, alice, &mut connection).unwrap());
+        assert!(
+            Subscription::list(alice, &mut connection)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
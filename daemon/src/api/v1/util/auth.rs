@@ -1,30 +1,193 @@
 use crate::api;
 use crate::config::Config;
-use crate::models::Worker;
-use crate::schema::workers;
-use actix_web::HttpRequest;
-use diesel::QueryDsl;
-use diesel::SqliteExpressionMethods;
-use diesel::{RunQueryDsl, SqliteConnection};
+use crate::db::{self, Pool};
+use crate::models::{ApiKey, Worker, WorkerToken};
+use crate::notify::Notification;
+use crate::web;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use chrono::Utc;
+use data_encoding::BASE64;
+use in_toto::crypto::{PublicKey, Signature};
 use log::debug;
-use rebuilderd_common::api::{AUTH_COOKIE_HEADER, SIGNUP_SECRET_HEADER, WORKER_KEY_HEADER};
-use rebuilderd_common::errors::{Context, bail};
+use rebuilderd_common::api::v1::{ApiKeyScope, WorkerCapability, key_rotation_message};
+use rebuilderd_common::api::{
+    AUTH_COOKIE_HEADER, SIGNUP_SECRET_HEADER, WORKER_KEY_HEADER, WORKER_TOKEN_HEADER,
+};
+use rebuilderd_common::errors::{Context, bail, format_err};
+
+/// The token of an `Authorization: Bearer` header, if one was sent
+fn bearer_token(req: &HttpRequest) -> rebuilderd_common::errors::Result<Option<&str>> {
+    let Some(value) = req.headers().get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .context("Failed to decode authorization header")?;
+    let token = value
+        .strip_prefix("Bearer ")
+        .context("Only bearer tokens are supported for authorization")?;
+    Ok(Some(token.trim()))
+}
+
+/// Authenticates an administrative request. The auth cookie grants access to everything, while the secrets of
+/// configured identities and issued api keys are presented in the same header and are only accepted if their role
+/// covers the requested action. If an oidc issuer is configured, its tokens are accepted as bearer token. Returns who
+/// authenticated, for audit logging.
+pub async fn admin(
+    cfg: &Config,
+    req: &HttpRequest,
+    pool: &Pool,
+    scope: ApiKeyScope,
+) -> rebuilderd_common::errors::Result<String> {
+    admin_any(cfg, req, pool, &[scope]).await
+}
+
+/// Like `admin`, for routes that accept any of several scopes. Queue keys keep access to the queue routes that were
+/// opened up to the operator and read-only roles.
+pub async fn admin_any(
+    cfg: &Config,
+    req: &HttpRequest,
+    pool: &Pool,
+    scopes: &[ApiKeyScope],
+) -> rebuilderd_common::errors::Result<String> {
+    let actor = authenticate_admin(cfg, req, pool, scopes).await?;
+    identify(req, actor.clone());
+    Ok(actor)
+}
+
+fn scope_names(scopes: &[ApiKeyScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// Record who a request was authenticated as, it's picked up by the access log
+fn identify(req: &HttpRequest, identity: String) {
+    req.extensions_mut().insert(api::Identity(identity));
+}
+
+async fn authenticate_admin(
+    cfg: &Config,
+    req: &HttpRequest,
+    pool: &Pool,
+    scopes: &[ApiKeyScope],
+) -> rebuilderd_common::errors::Result<String> {
+    if let Some(oidc) = &cfg.oidc
+        && let Some(token) = bearer_token(req)?
+    {
+        let identity = oidc.verify(token).await?;
+        if !scopes.iter().any(|scope| identity.permits(*scope)) {
+            bail!(
+                "Token of {:?} is not allowed to perform {:?} actions",
+                identity.subject,
+                scope_names(scopes)
+            )
+        }
+
+        debug!(
+            "admin authenticated with oidc token of {:?}",
+            identity.subject
+        );
+        return Ok(format!("oidc subject {:?}", identity.subject));
+    }
 
-pub fn admin(cfg: &Config, req: &HttpRequest) -> rebuilderd_common::errors::Result<()> {
     let auth_cookie = api::header(req, AUTH_COOKIE_HEADER).context("Failed to get auth cookie")?;
 
-    if cfg.auth_cookie != auth_cookie {
+    if cfg.auth_cookie == auth_cookie {
+        return Ok("auth cookie".to_string());
+    }
+
+    if let Some(identity) = cfg
+        .identities
+        .iter()
+        .find(|identity| identity.secret == auth_cookie)
+    {
+        if !scopes.iter().any(|scope| identity.role.permits(*scope)) {
+            bail!(
+                "Identity {:?} is not allowed to perform {:?} actions",
+                identity.name,
+                scope_names(scopes)
+            )
+        }
+
+        debug!("admin authenticated as identity {:?}", identity.name);
+        return Ok(format!("identity {:?}", identity.name));
+    }
+
+    let auth_cookie = auth_cookie.to_string();
+    let api_key = db::run(pool, move |connection| {
+        ApiKey::find_active(&auth_cookie, connection)
+    })
+    .await?;
+
+    let Some(api_key) = api_key else {
         bail!("Wrong auth cookie")
+    };
+
+    if !scopes.iter().any(|scope| api_key.scope.permits(*scope)) {
+        bail!(
+            "Api key {:?} is not allowed to perform {:?} actions",
+            api_key.name,
+            scope_names(scopes)
+        )
     }
 
+    debug!("admin authenticated with api key {:?}", api_key.name);
+    Ok(format!("api key {:?}", api_key.name))
+}
+
+/// Subscriptions belong to an api key, so the auth cookie, identities and oidc tokens can't be used to manage them
+pub async fn api_key(req: &HttpRequest, pool: &Pool) -> rebuilderd_common::errors::Result<ApiKey> {
+    let auth_cookie = api::header(req, AUTH_COOKIE_HEADER)
+        .context("Failed to get auth cookie")?
+        .to_string();
+
+    let api_key = db::run(pool, move |connection| {
+        ApiKey::find_active(&auth_cookie, connection)
+    })
+    .await?
+    .context("Unknown api key")?;
+
+    if !api_key.scope.permits(ApiKeyScope::ReadOnly) {
+        bail!(
+            "Api key {:?} is not allowed to perform {:?} actions",
+            api_key.name,
+            ApiKeyScope::ReadOnly.as_str()
+        );
+    }
+
+    identify(req, format!("api key {:?}", api_key.name));
+    Ok(api_key)
+}
+
+/// Reject workers that connect from outside of the configured networks, a leaked key alone isn't enough then
+pub fn network(cfg: &Config, req: &HttpRequest) -> rebuilderd_common::errors::Result<()> {
+    if cfg.worker_networks.is_empty() {
+        return Ok(());
+    }
+    // requests on a unix socket have no peer address, the proxy in front of it has to pass on the client address
+    if cfg.real_ip_header.is_none() && req.peer_addr().is_none() {
+        bail!("Can't check the network of a worker on a unix socket without real_ip_header");
+    }
+    let ip = api::client_ip(cfg, req)?;
+    if !cfg.worker_networks.permits(ip) {
+        bail!("Worker address {ip} is not in an allowed network");
+    }
     Ok(())
 }
 
-pub fn worker(
+pub async fn worker(
     cfg: &Config,
     req: &HttpRequest,
-    connection: &mut SqliteConnection,
+    pool: &Pool,
 ) -> rebuilderd_common::errors::Result<Worker> {
+    network(cfg, req)?;
     let worker_key = api::header(req, WORKER_KEY_HEADER).context("Failed to get worker key")?;
 
     if !cfg.worker.authorized_workers.is_empty()
@@ -37,20 +200,208 @@ pub fn worker(
         bail!("Worker key is not on allow-list");
     }
 
-    let key_is_registered = diesel::dsl::select(diesel::dsl::exists(
-        workers::table.filter(workers::key.is(worker_key)),
-    ))
-    .get_result::<bool>(connection)?;
+    let worker_key = worker_key.to_string();
+    let worker = db::run(pool, move |connection| {
+        // a worker that rotated its key may keep using the previous one until it expires
+        let Some(worker_key) = Worker::resolve_key(&worker_key, connection)? else {
+            bail!("Worker is not registered")
+        };
 
-    if !key_is_registered {
-        bail!("Worker is not registered")
-    }
+        let worker = Worker::get_and_refresh(&worker_key, connection)?;
+        Ok(worker)
+    })
+    .await?;
 
-    let worker = Worker::get_and_refresh(worker_key, connection)?;
+    identify(req, format!("worker {:?}", worker.name));
     Ok(worker)
 }
 
+/// Check that a key rotation was signed by the current key of the worker, the signature is hex encoded
+pub fn verify_key_rotation(
+    current_key: &str,
+    new_key: &str,
+    signature: &str,
+) -> rebuilderd_common::errors::Result<()> {
+    let current = decode_worker_key(current_key).context("Failed to parse current worker key")?;
+    decode_worker_key(new_key).context("Failed to parse new worker key")?;
+
+    let signature = serde_json::from_value::<Signature>(serde_json::json!({
+        "keyid": current.key_id(),
+        "sig": signature,
+    }))
+    .context("Failed to parse signature")?;
+
+    let message = key_rotation_message(current_key, new_key);
+    current
+        .verify(message.as_bytes(), &signature)
+        .context("Key rotation signature is invalid")?;
+    Ok(())
+}
+
+fn decode_worker_key(key: &str) -> rebuilderd_common::errors::Result<PublicKey> {
+    let bytes = BASE64.decode(key.as_bytes())?;
+    let key = PublicKey::from_ed25519(bytes)?;
+    Ok(key)
+}
+
+/// The fingerprint of the client certificate, forwarded by a tls terminating proxy if `client_cert_header` is
+/// configured. The header is required then.
+pub fn client_fingerprint(
+    cfg: &Config,
+    req: &HttpRequest,
+) -> rebuilderd_common::errors::Result<Option<String>> {
+    let Some(name) = &cfg.worker.client_cert_header else {
+        return Ok(None);
+    };
+    let fingerprint =
+        api::header(req, name).context("Failed to get client certificate fingerprint")?;
+    // proxies differ in whether they separate the bytes with colons
+    Ok(Some(fingerprint.replace(':', "").to_lowercase()))
+}
+
+/// Trust on first use, the first client certificate a worker presents is pinned. A different certificate is rejected
+/// and reported to the admins, the worker key or token might have been stolen.
+pub async fn verify_fingerprint(
+    cfg: &Config,
+    req: &HttpRequest,
+    pool: &Pool,
+    worker: &Worker,
+) -> rebuilderd_common::errors::Result<()> {
+    let Some(fingerprint) = client_fingerprint(cfg, req)? else {
+        return Ok(());
+    };
+
+    let worker = worker.clone();
+    let mismatch = db::run(pool, move |connection| {
+        if worker.pin_fingerprint(&fingerprint, connection)? {
+            return Ok(None);
+        }
+        let notify = worker.record_fingerprint_mismatch(Utc::now().naive_utc(), connection)?;
+        Ok(Some((worker, fingerprint, notify)))
+    })
+    .await?;
+
+    let Some((worker, fingerprint, notify)) = mismatch else {
+        return Ok(());
+    };
+
+    if notify {
+        let notification = Notification::new(
+            "worker.fingerprint_mismatch",
+            &format!("worker-fingerprint/{}", worker.name),
+            format!(
+                "Worker {:?} presented client certificate {fingerprint:?} instead of the pinned {:?}, its credentials might have been stolen",
+                worker.name,
+                worker.fingerprint.as_deref().unwrap_or_default()
+            ),
+        );
+        cfg.notifier.send(&notification).await;
+    }
+
+    bail!(
+        "Worker {:?} presented a client certificate that doesn't match the pinned one",
+        worker.name
+    )
+}
+
+/// A worker that has been authenticated by [`require_worker`], handlers on the worker scope receive it through
+/// `web::ReqData`.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedWorker {
+    pub worker: Worker,
+    /// Set if the worker authenticated with a capability token instead of its worker key
+    pub capability: Option<WorkerCapability>,
+}
+
+impl AuthenticatedWorker {
+    pub fn permits(&self, capability: WorkerCapability) -> bool {
+        self.capability.is_none_or(|granted| granted == capability)
+    }
+}
+
+async fn worker_token(
+    cfg: &Config,
+    pool: &Pool,
+    req: &HttpRequest,
+) -> rebuilderd_common::errors::Result<Option<AuthenticatedWorker>> {
+    let Some(token) = req.headers().get(WORKER_TOKEN_HEADER) else {
+        return Ok(None);
+    };
+    network(cfg, req)?;
+    let token = token
+        .to_str()
+        .context("Failed to get worker token")?
+        .to_string();
+
+    let worker = db::run(pool, move |connection| {
+        let Some((token, worker)) = WorkerToken::find_active(&token, connection)? else {
+            bail!("Worker token is unknown or has been revoked")
+        };
+
+        let worker = Worker::get_and_refresh(&worker.key, connection)?;
+        Ok(AuthenticatedWorker {
+            worker,
+            capability: Some(token.capability),
+        })
+    })
+    .await?;
+
+    debug!(
+        "worker {:?} authenticated with {:?} token",
+        worker.worker.name, worker.capability
+    );
+    identify(req, format!("worker {:?}", worker.worker.name));
+    Ok(Some(worker))
+}
+
+/// Middleware for the worker scope. Requests are authenticated either with a capability token or with the worker
+/// key, and rejected before they reach a handler if neither is valid.
+pub async fn require_worker(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let cfg = req
+        .app_data::<web::Data<Config>>()
+        .cloned()
+        .ok_or_else(|| web::Error::from(format_err!("Config is not registered as app data")))?;
+    let pool = req.app_data::<web::Data<Pool>>().cloned().ok_or_else(|| {
+        web::Error::from(format_err!("Database pool is not registered as app data"))
+    })?;
+
+    let worker = match worker_token(&cfg, &pool, req.request()).await {
+        Ok(Some(worker)) => Ok(worker),
+        Ok(None) => worker(&cfg, req.request(), &pool)
+            .await
+            .map(|worker| AuthenticatedWorker {
+                worker,
+                capability: None,
+            }),
+        Err(err) => Err(err),
+    };
+
+    let worker = match worker {
+        Ok(worker) => verify_fingerprint(&cfg, req.request(), &pool, &worker.worker)
+            .await
+            .map(|_| worker),
+        Err(err) => Err(err),
+    };
+
+    match worker {
+        Ok(worker) => {
+            req.extensions_mut().insert(worker);
+            let res = next.call(req).await?;
+            Ok(res.map_into_left_body())
+        }
+        Err(err) => {
+            debug!("Rejecting worker request: {err:#}");
+            let res = HttpResponse::Forbidden().finish();
+            Ok(req.into_response(res).map_into_right_body())
+        }
+    }
+}
+
 pub fn signup(cfg: &Config, req: &HttpRequest) -> rebuilderd_common::errors::Result<()> {
+    network(cfg, req)?;
     let worker_key = api::header(req, WORKER_KEY_HEADER).context("Failed to get worker key")?;
 
     if !cfg.worker.authorized_workers.is_empty()
@@ -77,3 +428,103 @@ pub fn signup(cfg: &Config, req: &HttpRequest) -> rebuilderd_common::errors::Res
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use in_toto::crypto::{KeyType, PrivateKey, SignatureScheme};
+
+    fn keypair() -> (PrivateKey, String) {
+        let privkey = PrivateKey::new(KeyType::Ed25519).unwrap();
+        let privkey = PrivateKey::from_pkcs8(&privkey, SignatureScheme::Ed25519).unwrap();
+        let pubkey = BASE64.encode(privkey.public().as_bytes());
+        (privkey, pubkey)
+    }
+
+    fn sign(privkey: &PrivateKey, current_key: &str, new_key: &str) -> String {
+        let signature = privkey
+            .sign(key_rotation_message(current_key, new_key).as_bytes())
+            .unwrap();
+        let signature = serde_json::to_value(&signature).unwrap();
+        signature["sig"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_verify_key_rotation() {
+        let (current, current_key) = keypair();
+        let (_, new_key) = keypair();
+        let signature = sign(&current, &current_key, &new_key);
+        verify_key_rotation(&current_key, &new_key, &signature).unwrap();
+    }
+
+    #[test]
+    fn test_verify_key_rotation_signed_by_new_key() {
+        let (_, current_key) = keypair();
+        let (new, new_key) = keypair();
+        let signature = sign(&new, &current_key, &new_key);
+        verify_key_rotation(&current_key, &new_key, &signature).unwrap_err();
+    }
+
+    #[test]
+    fn test_verify_key_rotation_other_new_key() {
+        let (current, current_key) = keypair();
+        let (_, new_key) = keypair();
+        let (_, other_key) = keypair();
+        let signature = sign(&current, &current_key, &new_key);
+        verify_key_rotation(&current_key, &other_key, &signature).unwrap_err();
+    }
+
+    #[test]
+    fn test_verify_key_rotation_invalid_new_key() {
+        let (current, current_key) = keypair();
+        let signature = sign(&current, &current_key, "not a key");
+        verify_key_rotation(&current_key, "not a key", &signature).unwrap_err();
+    }
+
+    #[test]
+    fn test_pin_fingerprint() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let worker = crate::models::NewWorker {
+            key: "key".to_string(),
+            name: "build-01".to_string(),
+            address: "127.0.0.1".to_string(),
+            status: None,
+            last_ping: Utc::now().naive_utc(),
+            online: true,
+            environment: None,
+            trusted: true,
+            uuid: None,
+            fingerprint: None,
+        }
+        .upsert(&mut connection)
+        .unwrap();
+
+        assert!(worker.pin_fingerprint("aa", &mut connection).unwrap());
+        // the worker was loaded before the fingerprint got pinned
+        assert!(!worker.pin_fingerprint("bb", &mut connection).unwrap());
+
+        let worker = Worker::find_by_key("key", &mut connection)
+            .unwrap()
+            .unwrap();
+        assert!(worker.pin_fingerprint("aa", &mut connection).unwrap());
+        assert!(!worker.pin_fingerprint("bb", &mut connection).unwrap());
+
+        let now = Utc::now().naive_utc();
+        assert!(
+            worker
+                .record_fingerprint_mismatch(now, &mut connection)
+                .unwrap()
+        );
+        assert!(
+            !worker
+                .record_fingerprint_mismatch(now, &mut connection)
+                .unwrap()
+        );
+
+        Worker::reset_fingerprint(worker.id, &mut connection).unwrap();
+        let worker = Worker::find_by_key("key", &mut connection)
+            .unwrap()
+            .unwrap();
+        assert!(worker.pin_fingerprint("bb", &mut connection).unwrap());
+    }
+}
@@ -0,0 +1,136 @@
+use actix_web::web::Bytes;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use futures_util::Stream;
+use rebuilderd_common::errors::*;
+use rebuilderd_common::utils::is_zstd_compressed;
+use std::io::{self, BufWriter, Write};
+use std::pin::Pin;
+use tokio::sync::mpsc;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+type Load = Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>>> + Send>>;
+
+/// A file in a report bundle. The content is only loaded once the file is written, it may still be zstd compressed and
+/// files without content are left out.
+pub struct BundleFile {
+    pub path: String,
+    load: Load,
+}
+
+impl BundleFile {
+    pub fn new(path: impl Into<String>, data: Vec<u8>) -> Self {
+        Self::lazy(path, async move { Ok(Some(data)) })
+    }
+
+    pub fn lazy(
+        path: impl Into<String>,
+        load: impl Future<Output = Result<Option<Vec<u8>>>> + Send + 'static,
+    ) -> Self {
+        BundleFile {
+            path: path.into(),
+            load: Box::pin(load),
+        }
+    }
+}
+
+/// Forwards everything written to it to the response body
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Artifact names end up in paths inside of the archive, don't let them escape their directory
+pub fn sanitize_filename(name: &str) -> String {
+    let name = name.replace(['/', '\\'], "_");
+    if name.starts_with('.') {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+fn write_bundle<W: Write>(
+    writer: W,
+    prefix: &str,
+    mtime: u64,
+    files: impl Iterator<Item = io::Result<(String, Vec<u8>)>>,
+) -> io::Result<W> {
+    let gz = GzEncoder::new(writer, Compression::default());
+    let mut tar = tar::Builder::new(gz);
+
+    for file in files {
+        let (path, data) = file?;
+        let data = if is_zstd_compressed(&data) {
+            zstd::decode_all(data.as_slice())?
+        } else {
+            data
+        };
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, format!("{prefix}/{path}"), data.as_slice())?;
+    }
+
+    tar.into_inner()?.finish()
+}
+
+/// Generate a tar.gz in a background thread and stream it to the client while it's being written. The files are
+/// loaded one after another while the archive is written, so only one of them is kept in memory at a time.
+pub fn stream(
+    prefix: String,
+    mtime: u64,
+    files: Vec<BundleFile>,
+) -> impl Stream<Item = io::Result<Bytes>> {
+    let (tx, rx) = mpsc::channel(4);
+    let (file_tx, mut file_rx) = mpsc::channel(1);
+
+    actix_web::rt::spawn(async move {
+        for file in files {
+            let loaded = match file.load.await {
+                Ok(Some(data)) => Ok((file.path, data)),
+                Ok(None) => continue,
+                Err(err) => Err(io::Error::other(format!(
+                    "Failed to load {:?}: {err:#}",
+                    file.path
+                ))),
+            };
+            let failed = loaded.is_err();
+            // the writer is gone if the client went away
+            if file_tx.send(loaded).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    tokio::task::spawn_blocking(move || {
+        let writer = BufWriter::with_capacity(CHUNK_SIZE, ChannelWriter { tx: tx.clone() });
+        let files = std::iter::from_fn(|| file_rx.blocking_recv());
+        let result = write_bundle(writer, &prefix, mtime, files).and_then(|mut w| w.flush());
+        if let Err(err) = result {
+            warn!("Failed to generate report bundle: {err:#}");
+            tx.blocking_send(Err(err)).ok();
+        }
+    });
+
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        let item = rx.recv().await?;
+        Some((item, rx))
+    })
+}
@@ -1,14 +1,16 @@
-use crate::schema::source_packages;
+use crate::schema::{package_tags, source_packages};
 use diesel::backend::Backend;
+use diesel::dsl::{exists, sql};
 use diesel::expression::is_aggregate::No;
 use diesel::expression::{AsExpression, ValidGrouping};
 use diesel::query_builder::QueryFragment;
 use diesel::sql_types::{Bool, Text};
 use diesel::sqlite::Sqlite;
 use diesel::{BoolExpressionMethods, BoxableExpression, Expression, SelectableExpression};
-use diesel::{ExpressionMethods, SqliteExpressionMethods};
+use diesel::{ExpressionMethods, QueryDsl, SqliteExpressionMethods};
 use rebuilderd_common::api::v1::{
-    BinaryIdentityFilter, FreshnessFilter, OriginFilter, SourceIdentityFilter,
+    BinaryIdentityFilter, CauseFilter, DiffCause, FreshnessFilter, OriginFilter,
+    SourceIdentityFilter, TagFilter,
 };
 
 pub trait IntoSourceIdentityFilter<QS, DB>
@@ -259,15 +261,101 @@ where
 impl<T: 'static> IntoFilter<T, Sqlite> for FreshnessFilter
 where
     source_packages::seen_in_last_sync: SelectableExpression<T>,
+    source_packages::removed_at: SelectableExpression<T>,
 {
     type SqlType = Bool;
 
     type Output = Box<dyn BoxableExpression<T, Sqlite, SqlType = Self::SqlType>>;
 
     fn into_filter(self) -> Self::Output {
-        match self.seen_only {
+        let seen_is: Self::Output = match self.seen_only {
             Some(seen_only) => Box::new(source_packages::seen_in_last_sync.is(seen_only)),
             None => Box::new(AsExpression::<Bool>::as_expression(true)),
+        };
+
+        let removed_is: Self::Output = match self.removed {
+            Some(true) => Box::new(source_packages::removed_at.is_not_null()),
+            Some(false) => Box::new(source_packages::removed_at.is_null()),
+            None => Box::new(AsExpression::<Bool>::as_expression(true)),
+        };
+
+        Box::new(seen_is.and(removed_is))
+    }
+}
+
+/// Tags are attached by distribution and name, so they match every version of a package
+#[diesel::dsl::auto_type]
+fn tagged(tag: String) -> _ {
+    exists(
+        package_tags::table
+            .filter(package_tags::distribution.eq(source_packages::distribution))
+            .filter(package_tags::name.eq(source_packages::name))
+            .filter(package_tags::tag.eq(tag)),
+    )
+}
+
+impl<T: 'static> IntoFilter<T, Sqlite> for TagFilter
+where
+    tagged: BoxableExpression<T, Sqlite, SqlType = Bool>,
+{
+    type SqlType = Bool;
+
+    type Output = Box<dyn BoxableExpression<T, Sqlite, SqlType = Self::SqlType>>;
+
+    fn into_filter(self) -> Self::Output {
+        match self.tag {
+            Some(tag) => Box::new(tagged(tag)),
+            None => Box::new(AsExpression::<Bool>::as_expression(true)),
+        }
+    }
+}
+
+/// The causes of an artifact are stored as json list, matching the quoted label never matches part of another label.
+/// The labels are fixed strings, so they can be part of the sql.
+fn likely_causes_match(column: &str, cause: DiffCause) -> String {
+    format!("{column} LIKE '%\"{}\"%'", cause.as_str())
+}
+
+pub trait IntoCauseFilter<QS, DB>
+where
+    DB: Backend,
+{
+    type SqlType;
+
+    type Output;
+
+    /// Match the artifact joined as `rebuild_artifacts`, for lists of binary packages
+    fn into_artifact_filter(self) -> Self::Output;
+
+    /// Match any artifact of the latest rebuild joined as `r1`, for lists of source packages
+    fn into_rebuild_filter(self) -> Self::Output;
+}
+
+impl<T: 'static> IntoCauseFilter<T, Sqlite> for CauseFilter {
+    type SqlType = Bool;
+
+    type Output = Box<dyn BoxableExpression<T, Sqlite, SqlType = Self::SqlType>>;
+
+    fn into_artifact_filter(self) -> Self::Output {
+        match self.cause {
+            Some(cause) => Box::new(sql::<Bool>(&likely_causes_match(
+                "rebuild_artifacts.likely_causes",
+                cause,
+            ))),
+            None => Box::new(AsExpression::<Bool>::as_expression(true)),
+        }
+    }
+
+    fn into_rebuild_filter(self) -> Self::Output {
+        match self.cause {
+            Some(cause) => Box::new(sql::<Bool>(&format!(
+                "EXISTS (
+                    SELECT 1 FROM rebuild_artifacts AS labelled
+                    WHERE labelled.rebuild_id = r1.id AND {}
+                )",
+                likely_causes_match("labelled.likely_causes", cause)
+            ))),
+            None => Box::new(AsExpression::<Bool>::as_expression(true)),
         }
     }
 }
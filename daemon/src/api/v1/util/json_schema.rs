@@ -0,0 +1,190 @@
+use crate::config::Config;
+use crate::web;
+use actix_web::dev::Payload;
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use rebuilderd_common::api::v1::{
+    BuildDurationReport, PackageReport, PackageReportDelta, PopularityReport, QueuedJob,
+    QueuedJobWithArtifacts, RebuildReport, VerdictReport,
+};
+use rebuilderd_common::errors::*;
+use schemars::generate::SchemaSettings;
+use schemars::transform::{Transform, transform_subschemas};
+use schemars::{JsonSchema, Schema};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+type SchemaFn = fn() -> Value;
+
+/// The wire types that are published as JSON schema, by the name they're served under
+pub const SCHEMAS: &[(&str, SchemaFn)] = &[
+    ("build-duration-report", schema_for::<BuildDurationReport>),
+    ("package-report", schema_for::<PackageReport>),
+    ("package-report-delta", schema_for::<PackageReportDelta>),
+    ("popularity-report", schema_for::<PopularityReport>),
+    ("queued-job", schema_for::<QueuedJob>),
+    (
+        "queued-job-with-artifacts",
+        schema_for::<QueuedJobWithArtifacts>,
+    ),
+    ("rebuild-report", schema_for::<RebuildReport>),
+    ("verdict-report", schema_for::<VerdictReport>),
+];
+
+/// Objects only allow the fields we know about. Serde ignores everything else, so a typo in an optional field would
+/// otherwise go unnoticed.
+#[derive(Clone)]
+struct DenyUnknownFields;
+
+impl Transform for DenyUnknownFields {
+    fn transform(&mut self, schema: &mut Schema) {
+        if schema.get("properties").is_some() && schema.get("additionalProperties").is_none() {
+            schema.insert("additionalProperties".to_string(), Value::Bool(false));
+        }
+        transform_subschemas(self, schema);
+    }
+}
+
+pub fn schema_for<T: JsonSchema>() -> Value {
+    SchemaSettings::default()
+        .with_transform(DenyUnknownFields)
+        .into_generator()
+        .into_root_schema_for::<T>()
+        .to_value()
+}
+
+/// Everything about the value that doesn't match the schema of `T`, one line per violation
+fn schema_violations<T: JsonSchema>(value: &Value) -> Result<Vec<String>> {
+    let validator = jsonschema::validator_for(&schema_for::<T>())
+        .map_err(|err| anyhow!("Failed to compile json schema: {err}"))?;
+    let violations = validator
+        .iter_errors(value)
+        .map(|err| {
+            let path = err.instance_path.as_str();
+            let path = if path.is_empty() { "/" } else { path };
+            format!("{path}: {err}")
+        })
+        .collect();
+    Ok(violations)
+}
+
+/// A json body that is validated against the published schema of `T` if `strict_requests` is enabled. Violations are
+/// rejected with 400 and a list of everything that doesn't match. Otherwise this is the same as [`web::Json`].
+pub struct SchemaJson<T>(pub T);
+
+impl<T> SchemaJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + JsonSchema + 'static> FromRequest for SchemaJson<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let strict = req
+            .app_data::<web::Data<Config>>()
+            .is_some_and(|cfg| cfg.strict_requests);
+
+        if !strict {
+            let json = web::Json::<T>::from_request(req, payload);
+            return Box::pin(async move { Ok(SchemaJson(json.await?.into_inner())) });
+        }
+
+        // the body size limit of the route still applies, the value is only parsed once
+        let json = web::Json::<Value>::from_request(req, payload);
+        let path = req.path().to_string();
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+
+            let violations = schema_violations::<T>(&value).map_err(web::Error::from)?;
+            if !violations.is_empty() {
+                warn!(
+                    "Rejecting request to {path:?} with {} schema violations",
+                    violations.len()
+                );
+                let res = HttpResponse::BadRequest().body(violations.join("\n"));
+                return Err(InternalError::from_response(
+                    "Request body doesn't match the schema",
+                    res,
+                )
+                .into());
+            }
+
+            let request = serde_json::from_value(value).map_err(JsonPayloadError::Deserialize)?;
+            Ok(SchemaJson(request))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn package_report() -> Value {
+        json!({
+            "distribution": "archlinux",
+            "release": null,
+            "component": "core",
+            "architecture": "x86_64",
+            "packages": [{
+                "name": "zstd",
+                "version": "1.5.6-1",
+                "url": "https://geo.mirror.pkgbuild.com/core/os/x86_64/zstd-1.5.6-1-x86_64.pkg.tar.zst",
+                "payload": {
+                    "type": "archlinux",
+                    "package_url": "https://geo.mirror.pkgbuild.com/core/os/x86_64/zstd-1.5.6-1-x86_64.pkg.tar.zst",
+                },
+                "artifacts": [{
+                    "name": "zstd",
+                    "version": "1.5.6-1",
+                    "architecture": "x86_64",
+                    "url": "https://geo.mirror.pkgbuild.com/core/os/x86_64/zstd-1.5.6-1-x86_64.pkg.tar.zst",
+                }],
+            }],
+        })
+    }
+
+    #[test]
+    fn test_valid_package_report() {
+        let violations = schema_violations::<PackageReport>(&package_report()).unwrap();
+        assert_eq!(violations, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        let mut report = package_report();
+        report["packages"][0]["artifacts"][0]["checksm"] = json!("deadbeef");
+        let violations = schema_violations::<PackageReport>(&report).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].starts_with("/packages/0/artifacts/0: "));
+        assert!(violations[0].contains("checksm"));
+    }
+
+    #[test]
+    fn test_wrong_type_and_missing_field() {
+        let mut report = package_report();
+        report["architecture"] = json!(64);
+        report.as_object_mut().unwrap().remove("distribution");
+        let violations = schema_violations::<PackageReport>(&report).unwrap();
+        assert_eq!(violations.len(), 2);
+    }
+
+    #[test]
+    fn test_unknown_payload_type() {
+        let mut report = package_report();
+        report["packages"][0]["payload"]["type"] = json!("gentoo");
+        let violations = schema_violations::<PackageReport>(&report).unwrap();
+        assert!(!violations.is_empty());
+    }
+
+    #[test]
+    fn test_all_schemas_compile() {
+        for (name, schema_for) in SCHEMAS {
+            assert!(jsonschema::validator_for(&schema_for()).is_ok(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use rebuilderd_common::api::v1::{QueuedJob, QueuedJobArtifact};
+use rebuilderd_common::config::UrlTemplate;
+use rebuilderd_common::errors::*;
+use std::collections::HashMap;
+
+/// Templates are looked up the same way as schedule weights, `distribution/release` takes precedence over
+/// `distribution`
+fn find_template<'a>(
+    templates: &'a HashMap<String, UrlTemplate>,
+    job: &QueuedJob,
+) -> Option<&'a UrlTemplate> {
+    job.release
+        .as_ref()
+        .and_then(|release| templates.get(&format!("{}/{release}", job.distribution)))
+        .or_else(|| templates.get(&job.distribution))
+}
+
+fn render(template: &str, vars: &[(&str, Option<&str>)]) -> Result<String> {
+    let mut out = String::new();
+    let mut remaining = template;
+
+    while let Some((before, after)) = remaining.split_once('{') {
+        out.push_str(before);
+        let (name, after) = after
+            .split_once('}')
+            .with_context(|| anyhow!("Unterminated placeholder in url template: {template:?}"))?;
+
+        let Some((_, value)) = vars.iter().find(|(key, _)| *key == name) else {
+            bail!("Unknown placeholder in url template: {name:?}");
+        };
+        let Some(value) = value else {
+            bail!("No value for placeholder in url template: {name:?}");
+        };
+        out.push_str(value);
+        remaining = after;
+    }
+    out.push_str(remaining);
+
+    Ok(out)
+}
+
+fn resolve(
+    template: &UrlTemplate,
+    mirror: Option<&str>,
+    job: &QueuedJob,
+    artifact: &QueuedJobArtifact,
+) -> Result<String> {
+    let mirror = mirror
+        .or(template.mirror.as_deref())
+        .map(|mirror| mirror.trim_end_matches('/'));
+    let filename = artifact
+        .url
+        .rsplit_once('/')
+        .map(|(_, filename)| filename)
+        .filter(|filename| !filename.is_empty())
+        .with_context(|| anyhow!("Failed to detect filename of artifact: {:?}", artifact.url))?;
+
+    render(
+        &template.template,
+        &[
+            ("mirror", mirror),
+            ("distribution", Some(job.distribution.as_str())),
+            ("release", job.release.as_deref()),
+            ("repo", job.component.as_deref()),
+            ("arch", Some(job.architecture.as_str())),
+            ("filename", Some(filename)),
+        ],
+    )
+}
+
+/// Rewrite the artifact urls of a job with the url template of its suite, if one is configured. The urls recorded by
+/// the sync are kept if the template can't be resolved for an artifact.
+pub fn resolve_artifact_urls(
+    templates: &HashMap<String, UrlTemplate>,
+    mirrors: &HashMap<String, String>,
+    job: &QueuedJob,
+    artifacts: &mut [QueuedJobArtifact],
+) {
+    let Some(template) = find_template(templates, job) else {
+        return;
+    };
+    let mirror = mirrors.get(&job.distribution).map(String::as_str);
+
+    for artifact in artifacts {
+        match resolve(template, mirror, job, artifact) {
+            Ok(url) => artifact.url = url,
+            Err(err) => warn!(
+                "Failed to resolve url template for artifact {:?}: {err:#}",
+                artifact.name
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let url = render(
+            "{mirror}/{repo}/os/{arch}/{filename}",
+            &[
+                ("mirror", Some("https://geo.mirror.pkgbuild.com")),
+                ("repo", Some("core")),
+                ("arch", Some("x86_64")),
+                ("filename", Some("bash-5.2.026-2-x86_64.pkg.tar.zst")),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://geo.mirror.pkgbuild.com/core/os/x86_64/bash-5.2.026-2-x86_64.pkg.tar.zst"
+        );
+    }
+
+    #[test]
+    fn test_render_unknown_placeholder() {
+        assert!(render("{mirror}/{pool}", &[("mirror", Some("https://a"))]).is_err());
+    }
+
+    #[test]
+    fn test_render_missing_value() {
+        assert!(
+            render(
+                "{mirror}/{release}",
+                &[("mirror", Some("https://a")), ("release", None)]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_render_unterminated() {
+        assert!(render("{mirror", &[("mirror", Some("https://a"))]).is_err());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod auth;
+pub mod bundle;
 pub mod filters;
 pub mod friends;
+pub mod json_schema;
+pub mod mirrors;
 pub mod pagination;
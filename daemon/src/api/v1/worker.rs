@@ -1,18 +1,26 @@
-use crate::api::header;
-use crate::api::v1::util::auth;
+use crate::api::v1::util::auth::{self, AuthenticatedWorker};
 use crate::api::v1::util::pagination::PaginateDsl;
+use crate::api::{client_ip, header};
 use crate::config::Config;
-use crate::db::Pool;
-use crate::models::NewWorker;
-use crate::schema::workers;
+use crate::db::{self, Pool};
+use crate::models::{NewWorker, NewWorkerToken, Worker};
+use crate::schema::{build_inputs, queue, source_packages, worker_tokens, workers};
+use crate::secrets;
 use crate::web;
 use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, post};
 use chrono::Utc;
-use diesel::{OptionalExtension, QueryDsl, RunQueryDsl, SqliteExpressionMethods};
+use diesel::dsl::{exists, select, update};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SqliteConnection, SqliteExpressionMethods,
+};
+use rand::distr::{Alphanumeric, SampleString};
 use rebuilderd_common::api::WORKER_KEY_HEADER;
-use rebuilderd_common::api::v1::{Page, RegisterWorkerRequest, ResultPage};
-use rebuilderd_common::errors::{Context, Error, format_err};
-use std::net::IpAddr;
+use rebuilderd_common::api::v1::{
+    ApiKeyScope, IssueWorkerTokenRequest, IssuedWorkerToken, Page, RegisterWorkerRequest,
+    ResultPage, RotateWorkerKeyRequest, RotatedWorkerKey, WorkerToken,
+};
+use rebuilderd_common::errors::{Context, Result, info};
 
 #[diesel::dsl::auto_type]
 fn workers_base() -> _ {
@@ -23,6 +31,12 @@ fn workers_base() -> _ {
         workers::status,
         workers::last_ping,
         workers::online,
+        workers::environment,
+        workers::draining,
+        workers::trusted,
+        workers::uuid,
+        workers::downloaded_bytes,
+        workers::fingerprint,
     ))
 }
 
@@ -31,19 +45,19 @@ pub async fn get_workers(
     pool: web::Data<Pool>,
     page: web::Query<Page>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let page = page.into_inner();
+    let workers = db::run(&pool, move |connection| {
+        let records = workers_base()
+            .paginate(page)
+            .load::<rebuilderd_common::api::v1::Worker>(connection)?;
 
-    let records = workers_base()
-        .paginate(page.into_inner())
-        .load::<rebuilderd_common::api::v1::Worker>(connection.as_mut())
-        .map_err(Error::from)?;
+        let total = workers_base().count().get_result::<i64>(connection)?;
 
-    let total = workers_base()
-        .count()
-        .get_result::<i64>(connection.as_mut())
-        .map_err(Error::from)?;
+        Ok(ResultPage { total, records })
+    })
+    .await?;
 
-    Ok(HttpResponse::Ok().json(ResultPage { total, records }))
+    Ok(HttpResponse::Ok().json(workers))
 }
 
 #[post("")]
@@ -53,47 +67,146 @@ pub async fn register_worker(
     pool: web::Data<Pool>,
     request: web::Json<RegisterWorkerRequest>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
     if auth::signup(&cfg, &req).is_err() {
         return Ok(HttpResponse::Forbidden().finish());
     }
 
     let key = header(&req, WORKER_KEY_HEADER).context("Failed to get worker key")?;
-    let ip = if let Some(real_ip_header) = &cfg.real_ip_header {
-        let ip = header(&req, real_ip_header).context("Failed to locate real ip header")?;
-        ip.parse::<IpAddr>()
-            .context("Can't parse real ip header as ip address")?
+    // requests on a unix socket don't have a peer address, unless a proxy passes on the client address
+    let address = if cfg.real_ip_header.is_none() && req.peer_addr().is_none() {
+        "unix".to_string()
     } else {
-        let ci = req
-            .peer_addr()
-            .ok_or_else(|| format_err!("Can't determine client ip"))?;
-        ci.ip()
+        client_ip(&cfg, &req)?.to_string()
     };
 
+    // a worker that registers again has to present the client certificate it was pinned to
+    let existing = {
+        let key = key.to_string();
+        db::run(&pool, move |connection| {
+            let Some(key) = Worker::resolve_key(&key, connection)? else {
+                return Ok(None);
+            };
+            Worker::find_by_key(&key, connection)
+        })
+        .await?
+    };
+    if let Some(worker) = &existing
+        && auth::verify_fingerprint(&cfg, &req, &pool, worker)
+            .await
+            .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let fingerprint = auth::client_fingerprint(&cfg, &req)?;
+
+    let request = request.into_inner();
     let new_worker = NewWorker {
         key: key.to_string(),
-        name: request.name.clone(),
-        address: ip.to_string(),
+        name: request.name,
+        address,
         status: None,
         last_ping: Utc::now().naive_utc(),
         online: true,
+        environment: request.environment,
+        trusted: cfg.worker.trust_new_workers(),
+        uuid: request.uuid,
+        fingerprint,
     };
 
-    new_worker.upsert(connection.as_mut())?;
+    let registered = db::run(&pool, move |connection| {
+        let mut new_worker = new_worker;
+        // don't fork the identity of a worker that registers again with its previous key
+        if let Some(key) = Worker::resolve_key(&new_worker.key, connection)? {
+            new_worker.key = key;
+        }
+
+        // a new key for a known identity has to go through a key rotation, which is signed by the current key
+        if let Some(uuid) = &new_worker.uuid
+            && let Some(worker) = Worker::find_by_uuid(uuid, connection)?
+            && worker.key != new_worker.key
+        {
+            return Ok(false);
+        }
 
-    Ok(HttpResponse::NoContent().finish())
+        new_worker.upsert(connection)?;
+        Ok(true)
+    })
+    .await?;
+
+    if registered {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::Conflict().body("Worker uuid is already registered with a different key"))
+    }
+}
+
+/// Only the worker key itself can authorize a rotation, capability tokens are rejected.
+pub async fn rotate_worker_key(
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    identity: web::ReqData<AuthenticatedWorker>,
+    request: web::Json<RotateWorkerKeyRequest>,
+) -> web::Result<impl Responder> {
+    let identity = identity.into_inner();
+    if identity.capability.is_some() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let worker = identity.worker;
+    let request = request.into_inner();
+
+    if !cfg.worker.authorized_workers.is_empty()
+        && !cfg.worker.authorized_workers.contains(&request.new_key)
+    {
+        return Ok(HttpResponse::Forbidden().body("New worker key is not on allow-list"));
+    }
+
+    if let Err(err) = auth::verify_key_rotation(&worker.key, &request.new_key, &request.signature) {
+        return Ok(HttpResponse::BadRequest().body(format!("{err:#}")));
+    }
+
+    let expires_at = Utc::now().naive_utc() + cfg.worker.key_rotation_grace();
+    let rotated = db::run(&pool, move |connection| {
+        let key_in_use = select(exists(
+            workers::table.filter(
+                workers::key
+                    .is(&request.new_key)
+                    .or(workers::previous_key.is(&request.new_key)),
+            ),
+        ))
+        .get_result::<bool>(connection)?;
+
+        if key_in_use {
+            return Ok(None);
+        }
+
+        worker.rotate_key(&request.new_key, expires_at, connection)?;
+        Ok(Some(RotatedWorkerKey {
+            worker_id: worker.id,
+            previous_key_expires_at: expires_at,
+        }))
+    })
+    .await?;
+
+    if let Some(rotated) = rotated {
+        Ok(HttpResponse::Ok().json(rotated))
+    } else {
+        Ok(HttpResponse::Conflict().body("New worker key is already in use"))
+    }
 }
 
 #[get("/{id}")]
 pub async fn get_worker(pool: web::Data<Pool>, id: web::Path<i32>) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
+    let id = id.into_inner();
+    let record = db::run(&pool, move |connection| {
+        let record = workers_base()
+            .filter(workers::id.is(id))
+            .get_result::<rebuilderd_common::api::v1::Worker>(connection)
+            .optional()?;
+        Ok(record)
+    })
+    .await?;
 
-    if let Some(record) = workers_base()
-        .filter(workers::id.is(id.into_inner()))
-        .get_result::<rebuilderd_common::api::v1::Worker>(connection.as_mut())
-        .optional()
-        .map_err(Error::from)?
-    {
+    if let Some(record) = record {
         Ok(HttpResponse::Ok().json(record))
     } else {
         Ok(HttpResponse::NotFound().finish())
@@ -107,15 +220,18 @@ pub async fn unregister_worker(
     pool: web::Data<Pool>,
     id: web::Path<i32>,
 ) -> web::Result<impl Responder> {
-    let mut connection = pool.get().map_err(Error::from)?;
-    if auth::worker(&cfg, &req, connection.as_mut()).is_err() {
+    if auth::worker(&cfg, &req, &pool).await.is_err() {
         return Ok(HttpResponse::Forbidden().finish());
     }
 
-    let unregistered_count = diesel::delete(workers::table)
-        .filter(workers::id.is(id.into_inner()))
-        .execute(connection.as_mut())
-        .map_err(Error::from)?;
+    let id = id.into_inner();
+    let unregistered_count = db::run(&pool, move |connection| {
+        let count = diesel::delete(workers::table)
+            .filter(workers::id.is(id))
+            .execute(connection)?;
+        Ok(count)
+    })
+    .await?;
 
     if unregistered_count < 1 {
         Ok(HttpResponse::NotFound().finish())
@@ -123,3 +239,327 @@ pub async fn unregister_worker(
         Ok(HttpResponse::NoContent().finish())
     }
 }
+
+fn set_draining(connection: &mut SqliteConnection, id: i32, draining: bool) -> Result<usize> {
+    let updated = diesel::update(workers::table)
+        .filter(workers::id.is(id))
+        .set(workers::draining.eq(draining))
+        .execute(connection)?;
+    Ok(updated)
+}
+
+#[post("/{id}/drain")]
+pub async fn drain_worker(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    if db::run(&pool, move |connection| set_draining(connection, id, true)).await? < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+#[post("/{id}/resume")]
+pub async fn resume_worker(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    if db::run(&pool, move |connection| set_draining(connection, id, false)).await? < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+enum Abort {
+    UnknownWorker,
+    Idle,
+    Aborted { name: String, version: String },
+}
+
+/// Mark the build the worker started first, a job it prefetched while building is left alone. The worker learns about
+/// it on its next ping.
+fn abort_current_build(connection: &mut SqliteConnection, id: i32, actor: &str) -> Result<Abort> {
+    connection.transaction(|conn| {
+        let known =
+            select(exists(workers::table.filter(workers::id.is(id)))).get_result::<bool>(conn)?;
+        if !known {
+            return Ok(Abort::UnknownWorker);
+        }
+
+        let Some((queue_id, build_input_id, name, version)) = queue::table
+            .inner_join(build_inputs::table.inner_join(source_packages::table))
+            .filter(queue::worker.is(id))
+            .order_by(queue::started_at)
+            .select((
+                queue::id,
+                queue::build_input_id,
+                source_packages::name,
+                source_packages::version,
+            ))
+            .first::<(i32, i32, String, String)>(conn)
+            .optional()?
+        else {
+            return Ok(Abort::Idle);
+        };
+
+        update(queue::table.filter(queue::id.is(queue_id)))
+            .set(queue::aborted_at.eq(Utc::now().naive_utc()))
+            .execute(conn)?;
+        update(build_inputs::table.filter(build_inputs::id.is(build_input_id)))
+            .set(build_inputs::last_failure.eq(format!("build aborted by {actor}")))
+            .execute(conn)?;
+
+        Ok(Abort::Aborted { name, version })
+    })
+}
+
+pub async fn abort_worker(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    let Ok(actor) = auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin).await else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let id = id.into_inner();
+    let abort = {
+        let actor = actor.clone();
+        db::run(&pool, move |connection| {
+            abort_current_build(connection, id, &actor)
+        })
+        .await?
+    };
+
+    match abort {
+        Abort::UnknownWorker => Ok(HttpResponse::NotFound().finish()),
+        Abort::Idle => Ok(HttpResponse::Conflict().body("Worker is not building anything")),
+        Abort::Aborted { name, version } => {
+            info!("{actor} aborted the build of {name} {version} on worker #{id}");
+            Ok(HttpResponse::NoContent().finish())
+        }
+    }
+}
+
+fn set_trusted(connection: &mut SqliteConnection, id: i32, trusted: bool) -> Result<usize> {
+    let updated = diesel::update(workers::table)
+        .filter(workers::id.is(id))
+        .set(workers::trusted.eq(trusted))
+        .execute(connection)?;
+    Ok(updated)
+}
+
+#[post("/{id}/trust")]
+pub async fn trust_worker(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    if db::run(&pool, move |connection| set_trusted(connection, id, true)).await? < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+#[post("/{id}/untrust")]
+pub async fn untrust_worker(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    if db::run(&pool, move |connection| set_trusted(connection, id, false)).await? < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+#[delete("/{id}/fingerprint")]
+pub async fn reset_worker_fingerprint(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    if db::run(&pool, move |connection| {
+        Worker::reset_fingerprint(id, connection)
+    })
+    .await?
+        < 1
+    {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+#[diesel::dsl::auto_type]
+fn worker_tokens_base() -> _ {
+    worker_tokens::table.select((
+        worker_tokens::id,
+        worker_tokens::worker_id,
+        worker_tokens::capability,
+        worker_tokens::created_at,
+        worker_tokens::revoked_at,
+    ))
+}
+
+#[get("/{id}/tokens")]
+pub async fn get_worker_tokens(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::ReadOnly)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = id.into_inner();
+    let records = db::run(&pool, move |connection| {
+        let records = worker_tokens_base()
+            .filter(worker_tokens::worker_id.is(id))
+            .order_by(worker_tokens::id)
+            .load::<WorkerToken>(connection)?;
+        Ok(records)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(records))
+}
+
+#[post("/{id}/tokens")]
+pub async fn issue_worker_token(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+    request: web::Json<IssueWorkerTokenRequest>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let token = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    let new_token = NewWorkerToken {
+        worker_id: id.into_inner(),
+        token_hash: secrets::hash(&token),
+        capability: request.into_inner().capability,
+        created_at: Utc::now().naive_utc(),
+    };
+
+    let issued = db::run(&pool, move |connection| {
+        let worker_exists = select(exists(
+            workers::table.filter(workers::id.is(new_token.worker_id)),
+        ))
+        .get_result::<bool>(connection)?;
+
+        if !worker_exists {
+            return Ok(None);
+        }
+
+        let id = new_token.insert(connection)?;
+        Ok(Some(IssuedWorkerToken {
+            id,
+            worker_id: new_token.worker_id,
+            capability: new_token.capability,
+            token,
+        }))
+    })
+    .await?;
+
+    if let Some(issued) = issued {
+        Ok(HttpResponse::Ok().json(issued))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[delete("/{id}/tokens/{token_id}")]
+pub async fn revoke_worker_token(
+    req: HttpRequest,
+    cfg: web::Data<Config>,
+    pool: web::Data<Pool>,
+    path: web::Path<(i32, i32)>,
+) -> web::Result<impl Responder> {
+    if auth::admin(&cfg, &req, &pool, ApiKeyScope::Admin)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let (id, token_id) = path.into_inner();
+    let revoked_count = db::run(&pool, move |connection| {
+        let count = diesel::update(worker_tokens::table)
+            .filter(worker_tokens::id.is(token_id))
+            .filter(worker_tokens::worker_id.is(id))
+            .filter(worker_tokens::revoked_at.is_null())
+            .set(worker_tokens::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(connection)?;
+        Ok(count)
+    })
+    .await?;
+
+    if revoked_count < 1 {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
@@ -16,6 +16,12 @@ pub struct Args {
     /// Derive the public key from a private key file
     #[arg(long, group = "action")]
     pub derive_pubkey: Option<PathBuf>,
+    /// Run a queue drain against a throwaway in-memory daemon and exit
+    #[arg(long, group = "action")]
+    pub selftest: bool,
+    /// Don't apply pending database migrations on startup, refuse to start if there are any
+    #[arg(long)]
+    pub no_migrate: bool,
     /// Configuration file path
     #[arg(short, long)]
     pub config: Option<PathBuf>,
@@ -1,25 +1,56 @@
+use crate::logging::{LogSink, SlowQueries};
+use crate::networks::NetworkPolicy;
+use crate::notify;
+use crate::oidc;
 use rand::distr::{Alphanumeric, SampleString};
-use rebuilderd_common::auth;
-use rebuilderd_common::config::{ConfigFile, ScheduleConfig, WorkerConfig};
+use rebuilderd_common::auth::{self, AdminIdentity};
+use rebuilderd_common::config::{
+    AlertsConfig, ConfigFile, NotifyConfig, ScheduleConfig, StorageConfig, UrlTemplate,
+    VerificationConfig, WorkerConfig,
+};
 use rebuilderd_common::errors::*;
+use rebuilderd_common::http::OutboundConfig;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
 
-const DEFAULT_POST_BODY_SIZE_LIMIT: usize = 2_usize.pow(30); // 1 GB
+const DEFAULT_POST_BODY_SIZE_LIMIT: usize = 16 * 2_usize.pow(20); // 16 MB
+const DEFAULT_SYNC_BODY_SIZE_LIMIT: usize = 2_usize.pow(30); // 1 GB
+const DEFAULT_REPORT_BODY_SIZE_LIMIT: usize = 2_usize.pow(30); // 1 GB
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub auth_cookie: String,
+    /// Named administrators with a role, checked after the auth cookie
+    pub identities: Vec<AdminIdentity>,
     pub worker: WorkerConfig,
+    /// Parsed from the allowed and denied networks in [`WorkerConfig`]
+    pub worker_networks: NetworkPolicy,
     pub bind_addr: String,
+    pub public_bind_addr: Option<String>,
     pub real_ip_header: Option<String>,
     pub post_body_size_limit: usize,
+    pub sync_body_size_limit: usize,
+    pub report_body_size_limit: usize,
     pub transparently_sign_attestations: bool,
+    pub strict_requests: bool,
+    pub verification: VerificationConfig,
     pub schedule: ScheduleConfig,
+    pub storage: StorageConfig,
+    pub url_templates: HashMap<String, UrlTemplate>,
+    pub alerts: AlertsConfig,
+    pub notify: NotifyConfig,
+    pub outbound: OutboundConfig,
+    /// Structured records of every http request, if configured
+    pub access_log: Option<LogSink>,
+    pub slow_queries: Option<SlowQueries>,
+    pub oidc: Option<Arc<oidc::Verifier>>,
+    pub notifier: notify::Notifier,
 }
 
 pub fn from_struct(config: ConfigFile, auth_cookie: String) -> Result<Config> {
@@ -31,36 +62,165 @@ pub fn from_struct(config: ConfigFile, auth_cookie: String) -> Result<Config> {
         "127.0.0.1:8484".to_string()
     };
 
+    let oidc = oidc::Verifier::new(&config.auth.oidc, &config.outbound)
+        .context("Failed to setup oidc authentication")?
+        .map(Arc::new);
+
+    let notifier = notify::Notifier::new(&config.notify, &config.outbound)
+        .context("Failed to setup notifications")?;
+
+    let access_log = config
+        .logging
+        .access_log
+        .as_deref()
+        .map(|path| LogSink::open(Some(path), "rebuilderd::access"))
+        .transpose()
+        .context("Failed to setup access log")?;
+    let slow_queries =
+        SlowQueries::from_config(&config.logging).context("Failed to setup slow query log")?;
+    let worker_networks =
+        NetworkPolicy::from_config(&config.worker).context("Failed to parse worker networks")?;
+    check_identities(&config.auth.identities)?;
+
     Ok(Config {
         auth_cookie,
+        identities: config.auth.identities,
         worker: config.worker,
+        worker_networks,
         bind_addr,
+        public_bind_addr: config.http.public_bind_addr,
         real_ip_header: config.http.real_ip_header,
         post_body_size_limit: config
             .http
             .post_body_size_limit
             .unwrap_or(DEFAULT_POST_BODY_SIZE_LIMIT),
+        // an explicitly configured general limit used to cover these routes too
+        sync_body_size_limit: config
+            .http
+            .sync_body_size_limit
+            .or(config.http.post_body_size_limit)
+            .unwrap_or(DEFAULT_SYNC_BODY_SIZE_LIMIT),
+        report_body_size_limit: config
+            .http
+            .report_body_size_limit
+            .or(config.http.post_body_size_limit)
+            .unwrap_or(DEFAULT_REPORT_BODY_SIZE_LIMIT),
         transparently_sign_attestations: config
             .http
             .transparently_sign_attestations
             .unwrap_or(true),
+        strict_requests: config.http.strict_requests.unwrap_or(false),
+        verification: config.verification,
         schedule: config.schedule,
+        storage: config.storage,
+        url_templates: config.url_templates,
+        alerts: config.alerts,
+        notify: config.notify,
+        outbound: config.outbound,
+        access_log,
+        slow_queries,
+        oidc,
+        notifier,
     })
 }
 
-pub fn load(path: Option<&Path>) -> Result<Config> {
+/// Identities are told apart by their secret, and named in the audit log
+fn check_identities(identities: &[AdminIdentity]) -> Result<()> {
+    let mut names = HashSet::new();
+    let mut secrets = HashSet::new();
+    for identity in identities {
+        if identity.secret.is_empty() {
+            bail!("Secret of admin identity {:?} is empty", identity.name);
+        }
+        if !names.insert(&identity.name) {
+            bail!("Admin identity {:?} is configured twice", identity.name);
+        }
+        if !secrets.insert(&identity.secret) {
+            bail!(
+                "Admin identity {:?} uses the same secret as another identity",
+                identity.name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn read(path: Option<&Path>) -> Result<ConfigFile> {
     let config = if let Some(path) = path {
         let buf = fs::read_to_string(path).context("Failed to read config file")?;
         toml::from_str(&buf)?
     } else {
         ConfigFile::default()
     };
+    Ok(config)
+}
 
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let config = read(path)?;
     let auth_cookie = setup_auth_cookie().context("Failed to setup auth cookie")?;
-
     from_struct(config, auth_cookie)
 }
 
+/// The config of a running daemon, it's replaced as a whole when the config file is reloaded. A request keeps using
+/// the config it started with.
+#[derive(Debug)]
+pub struct LiveConfig {
+    path: Option<PathBuf>,
+    current: RwLock<Arc<Config>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: Config, path: Option<PathBuf>) -> Self {
+        LiveConfig {
+            path,
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Read the config file again, if it's invalid the current config stays in place. The listeners are bound on
+    /// startup, the auth cookie is kept so clients that already read it continue to work.
+    pub fn reload(&self) -> Result<()> {
+        let current = self.current();
+        let config = read(self.path.as_deref())?;
+        let config = from_struct(config, current.auth_cookie.clone())?;
+
+        for setting in restart_required(&current, &config) {
+            warn!("Changing {setting} requires a restart, keeping the previous value until then");
+        }
+
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+        Ok(())
+    }
+}
+
+/// Settings of the http listeners and the blob storage that can't be changed while they are in use
+fn restart_required(current: &Config, new: &Config) -> Vec<&'static str> {
+    let mut settings = Vec::new();
+    if current.bind_addr != new.bind_addr {
+        settings.push("http.bind_addr");
+    }
+    if current.public_bind_addr != new.public_bind_addr {
+        settings.push("http.public_bind_addr");
+    }
+    if current.post_body_size_limit != new.post_body_size_limit
+        || current.sync_body_size_limit != new.sync_body_size_limit
+        || current.report_body_size_limit != new.report_body_size_limit
+    {
+        settings.push("the http body size limits");
+    }
+    if current.storage != new.storage {
+        settings.push("storage");
+    }
+    settings
+}
+
 pub fn setup_auth_cookie() -> Result<String> {
     let cookie = if let Ok(cookie) = auth::find_auth_cookie() {
         debug!("Loaded cookie from filesystem");
@@ -0,0 +1,540 @@
+use crate::db::{self, Pool};
+use crate::models::Subscription;
+use crate::schema::{
+    binary_packages, build_inputs, build_logs, package_annotations, queue, rebuild_artifacts,
+    rebuilds, source_packages,
+};
+use crate::storage::Storage;
+use crate::web::{self, escape_html};
+use actix_web::{HttpResponse, Responder, get};
+use chrono::NaiveDateTime;
+use diesel::dsl::{exists, select};
+use diesel::{
+    ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection,
+    SqliteExpressionMethods,
+};
+use rebuilderd_common::api::v1::{ArtifactStatus, BuildStatus};
+use rebuilderd_common::errors::*;
+use rebuilderd_common::utils::{is_zstd_compressed, zstd_decompress};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// How many of the most recent builds are listed on a package page
+const HISTORY_LIMIT: i64 = 25;
+/// The end of the build log is usually where a build failed or the mismatching files are listed
+const LOG_EXCERPT_LINES: usize = 40;
+
+#[derive(Debug)]
+struct PageArtifact {
+    name: String,
+    version: String,
+    architecture: String,
+    status: Option<ArtifactStatus>,
+    build_id: Option<i32>,
+    artifact_id: Option<i32>,
+    has_diffoscope: bool,
+}
+
+#[derive(Debug)]
+struct PageBuild {
+    id: i32,
+    version: String,
+    architecture: String,
+    built_at: Option<NaiveDateTime>,
+    status: Option<BuildStatus>,
+}
+
+#[derive(Debug)]
+struct PageSubscription {
+    /// The source package or the maintainer that is followed
+    follows: String,
+    by_maintainer: bool,
+    email: bool,
+}
+
+#[derive(Debug)]
+struct PackagePage {
+    name: String,
+    distribution: String,
+    component: String,
+    version: String,
+    release: Option<String>,
+    last_seen: NaiveDateTime,
+    removed_at: Option<NaiveDateTime>,
+    maintainer: Option<String>,
+    bug_url: Option<String>,
+    note: Option<String>,
+    rebuilding: bool,
+    /// Subscriptions that are notified when the status changes, the addresses and webhooks they notify are private
+    subscriptions: Vec<PageSubscription>,
+    artifacts: Vec<PageArtifact>,
+    history: Vec<PageBuild>,
+    /// The id of the latest build and the end of its log
+    log_excerpt: Option<(i32, String)>,
+}
+
+type BuildLog = (i32, Option<Vec<u8>>, Option<String>);
+
+/// The most recent version of the package in the suite, the status of its binary packages and the builds of every
+/// version. The build log of the latest build is returned separately, it may have to be loaded from blob storage.
+fn load_package_page(
+    connection: &mut SqliteConnection,
+    distribution: &str,
+    component: &str,
+    name: &str,
+) -> Result<Option<(PackagePage, Option<BuildLog>)>> {
+    let Some((source_package_id, version, release, last_seen, removed_at, maintainer)) =
+        source_packages::table
+            .filter(source_packages::distribution.eq(distribution))
+            .filter(source_packages::component.is(component))
+            .filter(source_packages::name.eq(name))
+            .order_by((
+                source_packages::seen_in_last_sync.desc(),
+                source_packages::last_seen.desc(),
+                source_packages::id.desc(),
+            ))
+            .select((
+                source_packages::id,
+                source_packages::version,
+                source_packages::release,
+                source_packages::last_seen,
+                source_packages::removed_at,
+                source_packages::maintainer,
+            ))
+            .first::<(
+                i32,
+                String,
+                Option<String>,
+                NaiveDateTime,
+                Option<NaiveDateTime>,
+                Option<String>,
+            )>(connection)
+            .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let (bug_url, note) = package_annotations::table
+        .filter(package_annotations::distribution.eq(distribution))
+        .filter(package_annotations::name.eq(name))
+        .select((package_annotations::bug_url, package_annotations::note))
+        .first::<(Option<String>, Option<String>)>(connection)
+        .optional()?
+        .unwrap_or_default();
+
+    let rebuilding = select(exists(
+        queue::table
+            .inner_join(build_inputs::table)
+            .filter(build_inputs::source_package_id.eq(source_package_id))
+            .filter(queue::build_architecture.is_null()),
+    ))
+    .get_result::<bool>(connection)?;
+
+    // the verdict of a binary package comes from the latest rebuild of its build input
+    let binaries = binary_packages::table
+        .filter(binary_packages::source_package_id.eq(source_package_id))
+        .order_by((binary_packages::architecture, binary_packages::name))
+        .select((
+            binary_packages::name,
+            binary_packages::version,
+            binary_packages::architecture,
+            binary_packages::build_input_id,
+        ))
+        .load::<(String, String, String, i32)>(connection)?;
+
+    let mut latest = HashMap::new();
+    for (_, _, _, build_input_id) in &binaries {
+        if latest.contains_key(build_input_id) {
+            continue;
+        }
+        let rebuild = rebuilds::table
+            .filter(rebuilds::build_input_id.eq(build_input_id))
+            .order_by((rebuilds::built_at.desc(), rebuilds::id.desc()))
+            .select(rebuilds::id)
+            .first::<i32>(connection)
+            .optional()?;
+        let artifacts = if let Some(rebuild_id) = rebuild {
+            rebuild_artifacts::table
+                .filter(rebuild_artifacts::rebuild_id.eq(rebuild_id))
+                .select((
+                    rebuild_artifacts::name,
+                    rebuild_artifacts::id,
+                    rebuild_artifacts::status,
+                    rebuild_artifacts::diffoscope_log_id.is_not_null(),
+                ))
+                .load::<(String, i32, Option<ArtifactStatus>, bool)>(connection)?
+                .into_iter()
+                .map(|(name, id, status, has_diffoscope)| (name, (id, status, has_diffoscope)))
+                .collect::<HashMap<_, _>>()
+        } else {
+            HashMap::new()
+        };
+        latest.insert(*build_input_id, (rebuild, artifacts));
+    }
+
+    let artifacts = binaries
+        .into_iter()
+        .map(|(name, version, architecture, build_input_id)| {
+            let (rebuild, artifacts) = &latest[&build_input_id];
+            let artifact = artifacts.get(&name);
+            PageArtifact {
+                status: artifact.and_then(|(_, status, _)| status.clone()),
+                build_id: *rebuild,
+                artifact_id: artifact.map(|(id, _, _)| *id),
+                has_diffoscope: artifact.is_some_and(|(_, _, has_diffoscope)| *has_diffoscope),
+                name,
+                version,
+                architecture,
+            }
+        })
+        .collect();
+
+    let history = rebuilds::table
+        .inner_join(build_inputs::table.inner_join(source_packages::table))
+        .filter(source_packages::distribution.eq(distribution))
+        .filter(source_packages::component.is(component))
+        .filter(source_packages::name.eq(name))
+        .order_by((rebuilds::built_at.desc(), rebuilds::id.desc()))
+        .limit(HISTORY_LIMIT)
+        .select((
+            rebuilds::id,
+            source_packages::version,
+            build_inputs::architecture,
+            rebuilds::built_at,
+            rebuilds::status,
+        ))
+        .load::<(
+            i32,
+            String,
+            String,
+            Option<NaiveDateTime>,
+            Option<BuildStatus>,
+        )>(connection)?
+        .into_iter()
+        .map(|(id, version, architecture, built_at, status)| PageBuild {
+            id,
+            version,
+            architecture,
+            built_at,
+            status,
+        })
+        .collect::<Vec<_>>();
+
+    let build_log = if let Some(build) = history.first() {
+        let (data, blob_key) = rebuilds::table
+            .inner_join(build_logs::table)
+            .filter(rebuilds::id.eq(build.id))
+            .select((build_logs::build_log, build_logs::blob_key))
+            .first::<(Option<Vec<u8>>, Option<String>)>(connection)?;
+        Some((build.id, data, blob_key))
+    } else {
+        None
+    };
+
+    let subscriptions =
+        Subscription::active(name, maintainer.as_deref(), distribution, connection)?
+            .into_iter()
+            .map(|subscription| PageSubscription {
+                by_maintainer: subscription.maintainer.is_some(),
+                follows: subscription
+                    .name
+                    .or(subscription.maintainer)
+                    .unwrap_or_default(),
+                email: subscription.email.is_some(),
+            })
+            .collect();
+
+    let page = PackagePage {
+        name: name.to_string(),
+        distribution: distribution.to_string(),
+        component: component.to_string(),
+        version,
+        release,
+        last_seen,
+        removed_at,
+        maintainer,
+        bug_url,
+        note,
+        rebuilding,
+        subscriptions,
+        artifacts,
+        history,
+        log_excerpt: None,
+    };
+    Ok(Some((page, build_log)))
+}
+
+fn tail(log: &str, lines: usize) -> &str {
+    let log = log.trim_end();
+    match log.rmatch_indices('\n').nth(lines.saturating_sub(1)) {
+        Some((idx, _)) => &log[idx + 1..],
+        None => log,
+    }
+}
+
+fn status_class(status: &str) -> &'static str {
+    match status {
+        "GOOD" => "good",
+        "BAD" => "bad",
+        "FAIL" => "fail",
+        _ => "unknown",
+    }
+}
+
+fn render_html(page: &PackagePage) -> String {
+    let title = escape_html(&format!(
+        "{} {} ({}/{})",
+        page.name, page.version, page.distribution, page.component
+    ));
+
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 1em 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ text-align: left; padding: 0.2em 1em 0.2em 0; }}
+pre {{ font-size: 13px; line-height: 1.3; background: #f6f8fa; padding: 1em; overflow-x: auto; }}
+.good {{ color: #116329; font-weight: bold; }}
+.bad {{ color: #82071e; font-weight: bold; }}
+.fail {{ color: #9a6700; font-weight: bold; }}
+.unknown {{ color: #57606a; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<ul>
+"#
+    );
+
+    if let Some(release) = &page.release {
+        let _ = writeln!(html, "<li>Release: {}</li>", escape_html(release));
+    }
+    let _ = writeln!(html, "<li>Last seen: {}</li>", page.last_seen);
+    if let Some(removed_at) = page.removed_at {
+        let _ = writeln!(html, "<li>Removed from the suite: {removed_at}</li>");
+    }
+    if let Some(maintainer) = &page.maintainer {
+        let _ = writeln!(html, "<li>Maintainer: {}</li>", escape_html(maintainer));
+    }
+    if page.rebuilding {
+        html.push_str("<li>A rebuild is queued</li>\n");
+    }
+    if let Some(bug_url) = &page.bug_url {
+        let bug_url = escape_html(bug_url);
+        // only link to urls that can't run script in the context of this page
+        if bug_url.starts_with("https://") || bug_url.starts_with("http://") {
+            let _ = writeln!(
+                html,
+                r#"<li>Bug report: <a href="{bug_url}">{bug_url}</a></li>"#
+            );
+        } else {
+            let _ = writeln!(html, "<li>Bug report: {bug_url}</li>");
+        }
+    }
+    if let Some(note) = &page.note {
+        let _ = writeln!(html, "<li>Note: {}</li>", escape_html(note));
+    }
+    html.push_str("</ul>\n");
+
+    html.push_str(
+        "<h2>Packages</h2>\n<table>\n<tr><th>Name</th><th>Version</th><th>Architecture</th><th>Status</th><th></th></tr>\n",
+    );
+    for artifact in &page.artifacts {
+        let status = artifact
+            .status
+            .as_ref()
+            .map_or("UNKNOWN", ArtifactStatus::as_str);
+        let links = match (artifact.build_id, artifact.artifact_id) {
+            (Some(build_id), Some(artifact_id)) if artifact.has_diffoscope => format!(
+                r#"<a href="/api/v1/builds/{build_id}/artifacts/{artifact_id}/diffoscope">diffoscope</a>"#
+            ),
+            _ => String::new(),
+        };
+        let _ = writeln!(
+            html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td class="{}">{status}</td><td>{links}</td></tr>"#,
+            escape_html(&artifact.name),
+            escape_html(&artifact.version),
+            escape_html(&artifact.architecture),
+            status_class(status),
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<h2>History</h2>\n<table>\n<tr><th>Build</th><th>Version</th><th>Architecture</th><th>Built at</th><th>Status</th><th></th></tr>\n",
+    );
+    for build in &page.history {
+        let status = build.status.as_ref().map_or("UNKNOWN", BuildStatus::as_str);
+        let built_at = build
+            .built_at
+            .map(|built_at| built_at.to_string())
+            .unwrap_or_default();
+        let _ = writeln!(
+            html,
+            r#"<tr><td>#{id}</td><td>{}</td><td>{}</td><td>{built_at}</td><td class="{}">{status}</td><td><a href="/api/v1/builds/{id}/log">log</a></td></tr>"#,
+            escape_html(&build.version),
+            escape_html(&build.architecture),
+            status_class(status),
+            id = build.id,
+        );
+    }
+    html.push_str("</table>\n");
+
+    if !page.subscriptions.is_empty() {
+        html.push_str(
+            "<h2>Subscriptions</h2>\n<table>\n<tr><th>Follows</th><th>Channel</th></tr>\n",
+        );
+        for subscription in &page.subscriptions {
+            let kind = if subscription.by_maintainer {
+                "Maintainer"
+            } else {
+                "Package"
+            };
+            let channel = if subscription.email {
+                "email"
+            } else {
+                "webhook"
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{kind} {}</td><td>{channel}</td></tr>",
+                escape_html(&subscription.follows),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if let Some((build_id, excerpt)) = &page.log_excerpt {
+        let _ = write!(
+            html,
+            "<h2>Build log of #{build_id}</h2>\n<p>The last {LOG_EXCERPT_LINES} lines, see the <a href=\"/api/v1/builds/{build_id}/log\">full log</a>.</p>\n<pre>{}</pre>\n",
+            escape_html(excerpt)
+        );
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// A page about a single source package that can be linked to, eg. from an upstream bug report
+#[get("/pkg/{distribution}/{component}/{name}")]
+pub async fn get_package_page(
+    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
+    path: web::Path<(String, String, String)>,
+) -> web::Result<impl Responder> {
+    let (distribution, component, name) = path.into_inner();
+
+    let found = db::run(&pool, move |connection| {
+        load_package_page(connection, &distribution, &component, &name)
+    })
+    .await?;
+    let Some((mut page, build_log)) = found else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    if let Some((build_id, data, blob_key)) = build_log
+        && let Some(mut log) = storage.load(data, blob_key).await?
+    {
+        if is_zstd_compressed(&log) {
+            log = zstd_decompress(&log).await.map_err(Error::from)?;
+        }
+        let log = String::from_utf8_lossy(&log);
+        page.log_excerpt = Some((build_id, tail(&log, LOG_EXCERPT_LINES).to_string()));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .append_header(("X-Content-Type-Options", "nosniff"))
+        .append_header((
+            "Content-Security-Policy",
+            "default-src 'none'; style-src 'unsafe-inline'",
+        ))
+        .body(render_html(&page)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> PackagePage {
+        PackagePage {
+            name: "rebuilderd".to_string(),
+            distribution: "archlinux".to_string(),
+            component: "extra".to_string(),
+            version: "0.26.0-1".to_string(),
+            release: None,
+            last_seen: NaiveDateTime::default(),
+            removed_at: None,
+            maintainer: Some("Rust Maintainers <rust@example.com>".to_string()),
+            bug_url: None,
+            note: None,
+            rebuilding: false,
+            subscriptions: vec![
+                PageSubscription {
+                    follows: "rebuilderd".to_string(),
+                    by_maintainer: false,
+                    email: false,
+                },
+                PageSubscription {
+                    follows: "Rust Maintainers <rust@example.com>".to_string(),
+                    by_maintainer: true,
+                    email: true,
+                },
+            ],
+            artifacts: vec![PageArtifact {
+                name: "rebuilderd".to_string(),
+                version: "0.26.0-1".to_string(),
+                architecture: "x86_64".to_string(),
+                status: Some(ArtifactStatus::Bad),
+                build_id: Some(7),
+                artifact_id: Some(9),
+                has_diffoscope: true,
+            }],
+            history: vec![PageBuild {
+                id: 7,
+                version: "0.26.0-1".to_string(),
+                architecture: "x86_64".to_string(),
+                built_at: None,
+                status: Some(BuildStatus::Bad),
+            }],
+            log_excerpt: Some((7, "<mismatch>".to_string())),
+        }
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(tail("a\nb\nc", 5), "a\nb\nc");
+        assert_eq!(tail("", 5), "");
+    }
+
+    #[test]
+    fn test_render_links() {
+        let html = render_html(&page());
+        assert!(html.contains(r#"href="/api/v1/builds/7/artifacts/9/diffoscope""#));
+        assert!(html.contains(r#"href="/api/v1/builds/7/log""#));
+        assert!(html.contains(r#"<td class="bad">BAD</td>"#));
+        assert!(html.contains("&lt;mismatch&gt;"));
+        assert!(html.contains("<tr><td>Package rebuilderd</td><td>webhook</td></tr>"));
+        assert!(html.contains(
+            "<tr><td>Maintainer Rust Maintainers &lt;rust@example.com&gt;</td><td>email</td></tr>"
+        ));
+        assert!(html.contains("<li>Maintainer: Rust Maintainers &lt;rust@example.com&gt;</li>"));
+    }
+
+    #[test]
+    fn test_render_bug_url() {
+        let mut page = page();
+        page.bug_url = Some("javascript:alert(1)".to_string());
+        assert!(!render_html(&page).contains("href=\"javascript:"));
+
+        page.bug_url = Some("https://bugs.example.com/1?a=1&b=2".to_string());
+        assert!(render_html(&page).contains(r#"href="https://bugs.example.com/1?a=1&amp;b=2""#));
+    }
+}
@@ -1,24 +1,80 @@
 use crate::code_migrations::code_migration;
+use crate::logging::SlowQueries;
 use diesel::connection::{
     CacheSize, Instrumentation, LoadConnection, SimpleConnection, TransactionManager,
 };
 use diesel::expression::QueryMetadata;
-use diesel::migration::Migration;
+use diesel::migration::{Migration, MigrationSource};
 use diesel::prelude::*;
 use diesel::query_builder::{Query, QueryFragment, QueryId};
-use diesel::r2d2::{self, ConnectionManager};
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
 use diesel::sql_query;
+use diesel::sqlite::Sqlite;
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 use rebuilderd_common::errors::*;
+use std::collections::HashSet;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 pub type Pool = r2d2::Pool<ConnectionManager<SqliteConnectionWrap>>;
 
 pub fn setup(url: &str) -> Result<SqliteConnection> {
+    open(url, true)
+}
+
+/// Connect to the database and apply the migrations that are embedded in this binary. With `migrate` disabled, pending
+/// migrations are an error instead.
+pub fn open(url: &str, migrate: bool) -> Result<SqliteConnection> {
     info!("Using database at {:?}", url);
     let mut connection = SqliteConnection::establish(url)?;
 
+    check_schema(&mut connection, migrate)?;
+    if migrate {
+        run_migrations(&mut connection)?;
+    }
+
+    Ok(connection)
+}
+
+/// A database that was migrated by a newer rebuilderd can't be used, the schema doesn't match the queries anymore
+fn check_schema(connection: &mut SqliteConnection, migrate: bool) -> Result<()> {
+    let known = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)
+        .map_err(|err| anyhow!("Failed to load embedded migrations: {err:#}"))?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect::<HashSet<_>>();
+
+    let applied = connection
+        .applied_migrations()
+        .map_err(|err| anyhow!("Failed to check for applied migrations: {err:#}"))?;
+    if let Some(unknown) = applied
+        .iter()
+        .map(|version| version.to_string())
+        .filter(|version| !known.contains(version))
+        .max()
+    {
+        bail!(
+            "Database schema is newer than this version of rebuilderd (unknown migration {unknown}), upgrade rebuilderd or restore a backup of the database"
+        );
+    }
+
+    if !migrate {
+        let pending = connection
+            .pending_migrations(MIGRATIONS)
+            .map_err(|err| anyhow!("Failed to check for pending migrations: {err:#}"))?;
+        if let Some(latest) = pending.last() {
+            bail!(
+                "Database schema is outdated, {} migrations up to {} are pending. Start rebuilderd without --no-migrate to apply them",
+                pending.len(),
+                latest.name().version()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_migrations(connection: &mut SqliteConnection) -> Result<()> {
     let mut database_schema_changed = false;
 
     loop {
@@ -35,7 +91,7 @@ pub fn setup(url: &str) -> Result<SqliteConnection> {
             next_migration.name().version()
         );
 
-        let version = code_migration::run_code_backed_migration(&mut connection, next_migration)
+        let version = code_migration::run_code_backed_migration(connection, next_migration)
             .map_err(|err| anyhow!("Failed to run pending migration: {err:#}"))?;
 
         info!("Applied database migration: {version}");
@@ -44,25 +100,58 @@ pub fn setup(url: &str) -> Result<SqliteConnection> {
 
     if database_schema_changed {
         info!("reclaiming disk space (this might take a while)");
-        sql_query("VACUUM;").execute(&mut connection)?;
+        sql_query("VACUUM;").execute(connection)?;
 
         info!("analyzing new schema and optimizing queries");
-        sql_query("ANALYZE;").execute(&mut connection)?;
+        sql_query("ANALYZE;").execute(connection)?;
     }
 
-    Ok(connection)
+    Ok(())
 }
 
-pub fn setup_pool(url: &str) -> Result<Pool> {
-    setup(url)?;
+pub fn setup_pool(url: &str, migrate: bool, slow_queries: Option<SlowQueries>) -> Result<Pool> {
+    open(url, migrate)?;
 
     let manager = ConnectionManager::<SqliteConnectionWrap>::new(url);
-    let pool = r2d2::Pool::builder()
-        .build(manager)
-        .context("Failed to create pool")?;
+    let mut builder = r2d2::Pool::builder();
+    if let Some(slow_queries) = slow_queries {
+        builder = builder.connection_customizer(Box::new(slow_queries));
+    }
+    let pool = builder.build(manager).context("Failed to create pool")?;
     Ok(pool)
 }
 
+impl CustomizeConnection<SqliteConnectionWrap, r2d2::Error> for SlowQueries {
+    fn on_acquire(&self, connection: &mut SqliteConnectionWrap) -> Result<(), r2d2::Error> {
+        connection.set_instrumentation(self.instrumentation());
+        Ok(())
+    }
+}
+
+/// Run database access on the blocking thread pool. Diesel is synchronous, running queries directly
+/// in a request handler would stall every other request that is scheduled on the same worker thread.
+pub async fn run<F, T>(pool: &Pool, f: F) -> Result<T>
+where
+    F: FnOnce(&mut SqliteConnection) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    actix_web::web::block(move || {
+        let mut connection = pool.get()?;
+        f(connection.as_mut())
+    })
+    .await?
+}
+
+/// Like [`run`], but nothing is written unless the closure succeeds
+pub async fn transaction<F, T>(pool: &Pool, f: F) -> Result<T>
+where
+    F: FnOnce(&mut SqliteConnection) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    run(pool, move |connection| connection.transaction(f)).await
+}
+
 pub struct SqliteConnectionWrap(SqliteConnection);
 
 impl LoadConnection for SqliteConnectionWrap {
@@ -165,3 +254,29 @@ impl Connection for SqliteConnectionWrap {
         self.0.set_prepared_statement_cache_size(size);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refuse_pending_migrations() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        let err = check_schema(&mut connection, false).unwrap_err();
+        assert!(err.to_string().contains("--no-migrate"));
+
+        run_migrations(&mut connection).unwrap();
+        check_schema(&mut connection, false).unwrap();
+    }
+
+    #[test]
+    fn test_refuse_newer_schema() {
+        let mut connection = setup(":memory:").unwrap();
+        sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES ('29990101090000')")
+            .execute(&mut connection)
+            .unwrap();
+
+        let err = check_schema(&mut connection, true).unwrap_err();
+        assert!(err.to_string().contains("29990101090000"));
+    }
+}
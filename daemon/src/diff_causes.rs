@@ -0,0 +1,210 @@
+use rebuilderd_common::api::v1::{DiffCause, LikelyCauses};
+use rebuilderd_common::errors::*;
+use rebuilderd_common::utils::{is_zstd_compressed, zstd_decompress};
+use std::collections::BTreeSet;
+
+/// Directories distributions build in, a differing line that mentions one of them most likely embeds the build path
+const BUILD_PATHS: &[&str] = &[
+    "/build/",
+    "/builddir/",
+    "/startdir/",
+    "/usr/src/packages/BUILD",
+    "/var/tmp/portage/",
+    "/tmp/",
+];
+
+/// A line of diffoscope text output, without the `│ ` markers of the sections it's nested in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Section(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+    Other,
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+    if line.starts_with("--- ") || line.starts_with("+++ ") {
+        return Line::Other;
+    }
+
+    let mut content = line;
+    while let Some(rest) = content.strip_prefix("│ ") {
+        content = rest;
+    }
+
+    let trimmed = content.trim_start();
+    if let Some(title) = trimmed
+        .strip_prefix("├── ")
+        .or_else(|| trimmed.strip_prefix("└── "))
+    {
+        Line::Section(title)
+    } else if let Some(removed) = content.strip_prefix('-') {
+        Line::Removed(removed)
+    } else if let Some(added) = content.strip_prefix('+') {
+        Line::Added(added)
+    } else {
+        Line::Other
+    }
+}
+
+/// Collects the lines of a file list section, the section is only about ordering if both sides list the same entries
+#[derive(Debug, Default)]
+struct FileList<'a> {
+    removed: Vec<&'a str>,
+    added: Vec<&'a str>,
+}
+
+impl FileList<'_> {
+    fn only_reordered(mut self) -> bool {
+        if self.removed.is_empty() {
+            return false;
+        }
+        self.removed.sort_unstable();
+        self.added.sort_unstable();
+        self.removed == self.added
+    }
+}
+
+/// Guess why an artifact didn't reproduce from the diffoscope text output. The heuristics only look at single lines,
+/// so they are cheap enough to run on every report, but a label is a hint and not a diagnosis.
+pub fn classify(diffoscope: &str) -> LikelyCauses {
+    let mut causes = BTreeSet::new();
+    let mut file_list = None::<FileList>;
+
+    for line in diffoscope.lines() {
+        // diffoscope notices some reorderings itself and adds a comment to the section
+        if line.contains("Ordering differences only") {
+            causes.insert(DiffCause::FileOrder);
+            continue;
+        }
+
+        let line = parse_line(line);
+        if let Line::Section(title) = line {
+            if let Some(list) = file_list.take()
+                && list.only_reordered()
+            {
+                causes.insert(DiffCause::FileOrder);
+            }
+            if title.ends_with("file list") {
+                file_list = Some(FileList::default());
+            }
+            continue;
+        }
+
+        let (Line::Removed(content) | Line::Added(content)) = line else {
+            continue;
+        };
+
+        if content.contains("gzip compressed data") && content.contains("last modified") {
+            causes.insert(DiffCause::GzipTimestamp);
+        }
+        if BUILD_PATHS.iter().any(|path| content.contains(path)) {
+            causes.insert(DiffCause::BuildPath);
+        }
+
+        if let Some(list) = &mut file_list {
+            match line {
+                Line::Removed(entry) => list.removed.push(entry),
+                Line::Added(entry) => list.added.push(entry),
+                _ => (),
+            }
+        }
+    }
+
+    if let Some(list) = file_list
+        && list.only_reordered()
+    {
+        causes.insert(DiffCause::FileOrder);
+    }
+
+    LikelyCauses(causes.into_iter().collect())
+}
+
+/// Workers may send their diffoscope output compressed
+pub async fn classify_report(diffoscope: &[u8]) -> Result<LikelyCauses> {
+    let causes = if is_zstd_compressed(diffoscope) {
+        let text = zstd_decompress(diffoscope)
+            .await
+            .context("Failed to decompress diffoscope output")?;
+        classify(&String::from_utf8_lossy(&text))
+    } else {
+        classify(&String::from_utf8_lossy(diffoscope))
+    };
+    Ok(causes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("--- a/foo.pkg"), Line::Other);
+        assert_eq!(parse_line("│   ├── file list"), Line::Section("file list"));
+        assert_eq!(parse_line("│ │ -old"), Line::Removed("old"));
+        assert_eq!(parse_line("│ │ +new"), Line::Added("new"));
+        assert_eq!(parse_line("│ │  -context"), Line::Other);
+    }
+
+    #[test]
+    fn test_gzip_timestamp() {
+        let diffoscope = "--- a/foo.tar.gz
++++ b/foo.tar.gz
+├── filetype from file(1)
+│ @@ -1 +1 @@
+│ -gzip compressed data, last modified: Mon Jan  1 00:00:00 2024, from Unix
+│ +gzip compressed data, last modified: Tue Jan  2 00:00:00 2024, from Unix
+";
+        assert_eq!(classify(diffoscope).0, [DiffCause::GzipTimestamp]);
+    }
+
+    #[test]
+    fn test_build_path() {
+        let diffoscope = "--- a/foo.deb
++++ b/foo.deb
+├── data.tar.xz
+│ ├── ./usr/bin/foo
+│ │ ├── strings --all --bytes=8 {}
+│ │ │ @@ -1 +1 @@
+│ │ │ -/build/foo-AbCdEf/src/main.c
+│ │ │ +/build/foo-XyZabc/src/main.c
+";
+        assert_eq!(classify(diffoscope).0, [DiffCause::BuildPath]);
+    }
+
+    #[test]
+    fn test_file_order() {
+        let diffoscope = "--- a/foo.pkg.tar.zst
++++ b/foo.pkg.tar.zst
+├── file list
+│ @@ -1,3 +1,3 @@
+│ --rw-r--r--   0 root root  12 2024-01-01 00:00:00.000000 usr/share/foo/a
+│  -rw-r--r--   0 root root  12 2024-01-01 00:00:00.000000 usr/share/foo/b
+│ +-rw-r--r--   0 root root  12 2024-01-01 00:00:00.000000 usr/share/foo/a
+├── usr/share/foo/c
+│ @@ -1 +1 @@
+│ -1
+│ +2
+";
+        assert_eq!(classify(diffoscope).0, [DiffCause::FileOrder]);
+    }
+
+    #[test]
+    fn test_file_list_with_changed_entries() {
+        let diffoscope = "├── file list
+│ @@ -1 +1 @@
+│ --rw-r--r--   0 root root  12 2024-01-01 00:00:00.000000 usr/share/foo/a
+│ +-rw-r--r--   0 root root  13 2024-01-01 00:00:00.000000 usr/share/foo/a
+";
+        assert!(classify(diffoscope).0.is_empty());
+    }
+
+    #[test]
+    fn test_ordering_differences_comment() {
+        let diffoscope = "│ ├── ./usr/lib/foo/index.json
+│ │┄ Ordering differences only
+│ │ │ @@ -1,2 +1,2 @@
+";
+        assert_eq!(classify(diffoscope).0, [DiffCause::FileOrder]);
+    }
+}
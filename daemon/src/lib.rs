@@ -1,79 +1,201 @@
-use crate::config::Config;
+use crate::config::{Config, LiveConfig};
 use actix_web::dev::Server;
-use actix_web::middleware::{Logger, TrailingSlash};
-use actix_web::web::{Data, JsonConfig, scope};
+use actix_web::middleware::{Logger, TrailingSlash, from_fn};
+use actix_web::web::{Data, JsonConfig, ServiceConfig, delete, get, patch, post, resource, scope};
 use actix_web::{App, HttpServer, middleware};
+use futures_util::future::try_join_all;
 use in_toto::crypto::PrivateKey;
 use rebuilderd_common::errors::*;
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 
+pub mod alerts;
 pub mod api;
 pub mod attestation;
 pub mod code_migrations;
 pub mod config;
+pub mod dashboard;
 pub mod db;
+pub mod diff_causes;
+pub mod logging;
+pub mod maintenance;
 pub mod models;
+pub mod networks;
+pub mod notify;
+pub mod oidc;
+pub mod reload;
+pub mod scheduler;
 pub mod schema;
+pub mod secrets;
+pub mod selftest;
+pub mod stats;
+pub mod storage;
+pub mod verification;
 pub mod web;
 
-pub fn build_server(
+/// The default format of the access log, with the id of the request appended
+const REQUEST_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{X-Request-Id}o"#;
+
+/// The part of the api that is served by a listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listener {
+    /// The full api, including administration and the worker endpoints
+    Admin,
+    /// Read-only requests, any credentials that are presented are ignored
+    Public,
+}
+
+/// The routes of workers, served under `/api/v0/worker` and `/api/v1/worker`
+fn worker_routes(cfg: &mut ServiceConfig, report_json_config: JsonConfig) {
+    cfg.route("/jobs/pop", post().to(api::v1::request_work))
+        .route("/jobs/{id}/ping", post().to(api::v1::ping_job))
+        .service(
+            resource("/builds")
+                .app_data(report_json_config)
+                .route(post().to(api::v1::submit_rebuild_report)),
+        )
+        .route("/rotate-key", post().to(api::v1::rotate_worker_key));
+}
+
+/// Bind a listener to its configured address, `unix:` addresses are bound as unix domain socket. The socket address
+/// is only returned for tcp listeners.
+pub fn build_listener(
     pool: db::Pool,
-    config: Config,
-    privkey: PrivateKey,
-) -> Result<(Server, SocketAddr)> {
-    let bind_addr = config.bind_addr.clone();
+    live_config: Arc<LiveConfig>,
+    privkey: Arc<PrivateKey>,
+    listener: Listener,
+) -> Result<(Server, Option<SocketAddr>)> {
+    // the listeners keep the settings they were started with, everything else is looked up for each request
+    let config = live_config.current();
+    let bind_addr = match listener {
+        Listener::Admin => config.bind_addr.clone(),
+        Listener::Public => config
+            .public_bind_addr
+            .clone()
+            .context("No address configured for the public listener")?,
+    };
 
-    let privkey = Arc::new(privkey);
+    let storage = storage::Storage::from_config(&config.storage)?;
+    let scheduler = Arc::new(scheduler::FairScheduler::default());
 
     let server = HttpServer::new(move || {
-        let json_config = JsonConfig::default().limit(config.post_body_size_limit);
+        let json_config = web::json_config(config.post_body_size_limit);
+        let sync_json_config = web::json_config(config.sync_body_size_limit);
+        let report_json_config = web::json_config(config.report_body_size_limit);
 
         let v0_dashboard_cache = Arc::new(RwLock::new(api::v0::DashboardState::new()));
 
         App::new()
-            .wrap(Logger::default())
+            .wrap(from_fn(api::request_id))
+            .wrap(from_fn(api::access_log))
+            .wrap(Logger::new(REQUEST_LOG_FORMAT))
             .wrap(middleware::Compress::default())
             .wrap(middleware::NormalizePath::new(TrailingSlash::Trim))
+            .wrap(from_fn(api::request_encoding))
+            .wrap(middleware::Condition::new(
+                listener == Listener::Public,
+                from_fn(api::read_only),
+            ))
+            .wrap(from_fn(api::current_config))
             .app_data(json_config)
             .app_data(Data::new(pool.clone()))
-            .app_data(Data::new(config.clone()))
+            .app_data(Data::from(live_config.clone()))
             .app_data(Data::new(privkey.clone()))
+            .app_data(Data::new(storage.clone()))
+            .app_data(Data::new(scheduler.clone()))
             .app_data(Data::new(v0_dashboard_cache.clone()))
             .service(
                 scope("/api")
                     .service(
                         scope("/v0")
                             .service(api::v0::list_workers)
+                            .service(api::v0::get_worker_stats)
+                            .route("/workers/{id}/abort", post().to(api::v1::abort_worker))
                             .service(api::v0::sync_work)
                             .service(api::v0::list_pkgs)
+                            .service(api::v0::get_pkg_status_batch)
+                            .service(api::v0::pkg_status_batch)
+                            .service(api::v0::list_suites)
                             .service(api::v0::list_queue)
+                            .service(api::v0::get_queue_position)
                             .service(api::v0::push_queue)
                             .service(api::v0::pop_queue)
                             .service(api::v0::drop_from_queue)
                             .service(api::v0::requeue_pkgbase)
                             .service(api::v0::ping_build)
-                            .service(api::v0::report_build)
+                            .service(
+                                resource("/build/report")
+                                    .wrap(from_fn(api::v1::require_worker))
+                                    .wrap(from_fn(api::server_time))
+                                    .app_data(report_json_config.clone())
+                                    .route(post().to(api::v1::submit_rebuild_report)),
+                            )
                             .service(api::v0::get_build_log)
                             .service(api::v0::get_attestation)
                             .service(api::v0::get_diffoscope)
+                            .service(api::v0::get_pkg_diff)
+                            .service(api::v0::get_pkg_artifacts)
+                            .service(api::v0::get_pkg_bundle)
+                            .route("/pkgs/{id}", patch().to(api::v1::correct_binary_package))
                             .service(api::v0::get_dashboard)
-                            .service(api::v0::get_public_key),
+                            .service(api::v0::get_stats_history)
+                            .service(api::v0::get_public_key)
+                            .route("/subscriptions", get().to(api::v1::get_subscriptions))
+                            .route("/subscriptions", post().to(api::v1::subscribe))
+                            .route(
+                                "/subscriptions/confirm",
+                                post().to(api::v1::confirm_subscription),
+                            )
+                            .route("/subscriptions/{id}", delete().to(api::v1::unsubscribe))
+                            .service(
+                                scope("/worker")
+                                    .wrap(from_fn(api::v1::require_worker))
+                                    .wrap(from_fn(api::server_time))
+                                    .configure(|cfg| {
+                                        worker_routes(cfg, report_json_config.clone())
+                                    }),
+                            )
+                            .service(
+                                scope("/schema")
+                                    .service(api::v1::get_schemas)
+                                    .service(api::v1::get_schema),
+                            ),
                     )
                     .service(
                         scope("/v1")
+                            .service(
+                                scope("/bisects")
+                                    .service(api::v1::request_bisect)
+                                    .service(api::v1::get_bisect),
+                            )
                             .service(
                                 scope("/builds")
                                     .service(api::v1::get_builds)
-                                    .service(api::v1::submit_rebuild_report)
+                                    // deprecated, workers submit reports to /worker/builds
+                                    .service(
+                                        resource("")
+                                            .wrap(from_fn(api::v1::require_worker))
+                                            .app_data(report_json_config.clone())
+                                            .route(post().to(api::v1::submit_rebuild_report)),
+                                    )
+                                    .service(api::v1::get_verification_mismatches)
+                                    .service(api::v1::get_status_transitions)
                                     .service(api::v1::get_build)
                                     .service(api::v1::get_build_log)
+                                    .service(api::v1::get_build_bundle)
                                     .service(api::v1::get_build_artifacts)
                                     .service(api::v1::get_build_artifact)
                                     .service(api::v1::get_build_artifact_diffoscope)
                                     .service(api::v1::get_build_artifact_attestation),
                             )
                             .service(scope("/dashboard").service(api::v1::get_dashboard))
+                            .service(
+                                scope("/keys")
+                                    .service(api::v1::get_api_keys)
+                                    .service(api::v1::issue_api_key)
+                                    .service(api::v1::revoke_api_key),
+                            )
                             .service(
                                 scope("/meta")
                                     .service(api::v1::get_distributions)
@@ -85,49 +207,175 @@ pub fn build_server(
                                     .service(
                                         api::v1::get_distribution_release_component_architectures,
                                     )
+                                    .service(api::v1::get_snapshot_generations)
                                     .service(api::v1::get_public_key),
                             )
                             .service(
                                 scope("/packages")
-                                    .service(api::v1::submit_package_report)
+                                    .service(
+                                        resource("")
+                                            .app_data(sync_json_config.clone())
+                                            .route(post().to(api::v1::submit_package_report)),
+                                    )
+                                    .service(
+                                        resource("/delta")
+                                            .app_data(sync_json_config.clone())
+                                            .route(post().to(api::v1::submit_package_report_delta)),
+                                    )
+                                    .service(
+                                        resource("/popularity")
+                                            .app_data(sync_json_config.clone())
+                                            .route(post().to(api::v1::submit_package_popularity)),
+                                    )
+                                    .service(
+                                        resource("/durations")
+                                            .app_data(sync_json_config.clone())
+                                            .route(post().to(api::v1::submit_build_durations)),
+                                    )
+                                    .service(
+                                        resource("/verdicts")
+                                            .app_data(sync_json_config.clone())
+                                            .route(post().to(api::v1::submit_verdicts)),
+                                    )
                                     .service(api::v1::get_source_packages)
                                     .service(api::v1::get_source_package)
+                                    .service(api::v1::get_source_package_cross_rebuilds)
                                     .service(api::v1::get_binary_packages)
-                                    .service(api::v1::get_binary_package),
+                                    .service(api::v1::get_binary_package)
+                                    .route(
+                                        "/binary/{id}",
+                                        patch().to(api::v1::correct_binary_package),
+                                    )
+                                    .service(api::v1::export_tracker)
+                                    .service(api::v1::get_upstream_release)
+                                    .service(api::v1::get_package_tags)
+                                    .service(api::v1::add_package_tag)
+                                    .service(api::v1::remove_package_tag)
+                                    .service(api::v1::annotate_package)
+                                    .service(api::v1::remove_package_annotation),
                             )
                             .service(
                                 scope("/queue")
                                     .service(api::v1::get_queued_jobs)
                                     .service(api::v1::request_rebuild)
+                                    .service(api::v1::get_queue_position)
+                                    .service(api::v1::export_queue)
+                                    .service(
+                                        resource("/snapshot")
+                                            .app_data(sync_json_config)
+                                            .route(post().to(api::v1::import_queue)),
+                                    )
+                                    .service(api::v1::assign_queued_jobs)
+                                    .service(api::v1::wipe_queue)
                                     .service(api::v1::get_queued_job)
                                     .service(api::v1::drop_queued_job)
                                     .service(api::v1::drop_queued_jobs)
-                                    .service(api::v1::ping_job)
-                                    .service(api::v1::request_work),
+                                    // deprecated, workers use the /worker/jobs routes
+                                    .service(
+                                        resource("/{id}/ping")
+                                            .wrap(from_fn(api::v1::require_worker))
+                                            .wrap(from_fn(api::server_time))
+                                            .route(post().to(api::v1::ping_job)),
+                                    )
+                                    .service(
+                                        resource("/pop")
+                                            .wrap(from_fn(api::v1::require_worker))
+                                            .wrap(from_fn(api::server_time))
+                                            .route(post().to(api::v1::request_work)),
+                                    ),
+                            )
+                            .service(
+                                scope("/schema")
+                                    .service(api::v1::get_schemas)
+                                    .service(api::v1::get_schema),
+                            )
+                            .service(
+                                scope("/subscriptions")
+                                    .route("", get().to(api::v1::get_subscriptions))
+                                    .route("", post().to(api::v1::subscribe))
+                                    .route("/confirm", post().to(api::v1::confirm_subscription))
+                                    .route("/{id}", delete().to(api::v1::unsubscribe)),
+                            )
+                            .service(
+                                scope("/worker")
+                                    .wrap(from_fn(api::v1::require_worker))
+                                    .wrap(from_fn(api::server_time))
+                                    .configure(|cfg| worker_routes(cfg, report_json_config)),
                             )
                             .service(
                                 scope("/workers")
                                     .service(api::v1::get_workers)
                                     .service(api::v1::register_worker)
                                     .service(api::v1::get_worker)
-                                    .service(api::v1::unregister_worker),
+                                    .service(api::v1::unregister_worker)
+                                    .service(api::v1::drain_worker)
+                                    .service(api::v1::resume_worker)
+                                    .route("/{id}/abort", post().to(api::v1::abort_worker))
+                                    .service(api::v1::trust_worker)
+                                    .service(api::v1::untrust_worker)
+                                    .service(api::v1::reset_worker_fingerprint)
+                                    .service(api::v1::get_worker_tokens)
+                                    .service(api::v1::issue_worker_token)
+                                    .service(api::v1::revoke_worker_token),
                             ),
                     ),
             )
-    })
-    .bind(&bind_addr)?;
-
-    let address = server
-        .addrs()
-        .first()
-        .copied()
-        .context("Failed to determine bind address")?;
+            .service(scope("/dashboard").service(dashboard::get_package_page))
+    });
+
+    let server = if let Some(path) = bind_addr.strip_prefix("unix:") {
+        server
+            .bind_uds(path)
+            .with_context(|| anyhow!("Failed to bind unix domain socket: {path:?}"))?
+    } else {
+        server
+            .bind(&bind_addr)
+            .with_context(|| anyhow!("Failed to bind to address: {bind_addr:?}"))?
+    };
+
+    let address = server.addrs().first().copied();
     Ok((server.run(), address))
 }
 
-pub async fn run_config(pool: db::Pool, config: Config, privkey: PrivateKey) -> Result<()> {
-    let (server, _) = build_server(pool, config, privkey)?;
+/// Bind the full api on the configured address, this needs to be a tcp address
+pub fn build_server(
+    pool: db::Pool,
+    config: Config,
+    privkey: PrivateKey,
+) -> Result<(Server, SocketAddr)> {
+    let config = Arc::new(LiveConfig::new(config, None));
+    let (server, address) = build_listener(pool, config, Arc::new(privkey), Listener::Admin)?;
+    let address = address.context("Failed to determine bind address")?;
+    Ok((server, address))
+}
+
+pub async fn run_config(pool: db::Pool, config: LiveConfig, privkey: PrivateKey) -> Result<()> {
+    let config = Arc::new(config);
+    let privkey = Arc::new(privkey);
+    // without a handler SIGHUP would terminate the daemon, listen for it before serving any requests
+    reload::listen(config.clone())?;
+
+    let mut listeners = vec![Listener::Admin];
+    if config.current().public_bind_addr.is_some() {
+        listeners.push(Listener::Public);
+    }
+
+    let mut servers = Vec::new();
+    for listener in listeners {
+        let (server, address) =
+            build_listener(pool.clone(), config.clone(), privkey.clone(), listener)?;
+
+        if let Some(address) = address {
+            info!("Listening on {address} ({listener:?})");
+        } else {
+            info!("Listening on unix domain socket ({listener:?})");
+        }
+        servers.push(server);
+    }
+
+    actix_web::rt::spawn(maintenance::run(pool.clone(), config.clone()));
+    actix_web::rt::spawn(alerts::run(pool, config));
 
-    server.await?;
+    try_join_all(servers).await?;
     Ok(())
 }
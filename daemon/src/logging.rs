@@ -0,0 +1,162 @@
+use chrono::{DateTime, Utc};
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use rebuilderd_common::config::LoggingConfig;
+use rebuilderd_common::errors::*;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Where structured log records go, either a file with one json object per line or the regular log
+#[derive(Debug, Clone)]
+pub enum LogSink {
+    /// Log with this target, so the records can be filtered with `RUST_LOG`
+    Log(&'static str),
+    File(Arc<Mutex<File>>),
+}
+
+impl LogSink {
+    pub fn open(path: Option<&Path>, target: &'static str) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(LogSink::Log(target));
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| anyhow!("Failed to open log file: {path:?}"))?;
+        Ok(LogSink::File(Arc::new(Mutex::new(file))))
+    }
+
+    pub fn write<T: Serialize>(&self, record: &T) {
+        let json = match serde_json::to_string(record) {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("Failed to serialize log record: {err:#}");
+                return;
+            }
+        };
+
+        match self {
+            LogSink::Log(target) => info!(target: *target, "{json}"),
+            LogSink::File(file) => {
+                let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Err(err) = writeln!(file, "{json}") {
+                    warn!("Failed to write log record: {err:#}");
+                }
+            }
+        }
+    }
+}
+
+/// A request to the http api, as written to the access log
+#[derive(Debug, Serialize)]
+pub struct AccessRecord<'a> {
+    pub time: DateTime<Utc>,
+    pub request_id: Option<&'a str>,
+    pub remote: Option<String>,
+    pub method: &'a str,
+    pub path: &'a str,
+    pub status: u16,
+    pub latency_ms: f64,
+    /// Who the request was authenticated as, `None` for anonymous requests
+    pub identity: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct SlowQueryRecord<'a> {
+    time: DateTime<Utc>,
+    duration_ms: f64,
+    query: &'a str,
+    failed: bool,
+}
+
+/// Settings of the slow query log, every pooled connection gets its own [`SlowQueryLog`] from them
+#[derive(Debug, Clone)]
+pub struct SlowQueries {
+    pub threshold: Duration,
+    pub sink: LogSink,
+}
+
+impl SlowQueries {
+    pub fn from_config(config: &LoggingConfig) -> Result<Option<Self>> {
+        let Some(threshold) = config.slow_query_ms else {
+            return Ok(None);
+        };
+        let sink = LogSink::open(config.slow_query_log.as_deref(), "rebuilderd::slow_query")?;
+        Ok(Some(SlowQueries {
+            threshold: Duration::from_millis(threshold),
+            sink,
+        }))
+    }
+
+    pub fn instrumentation(&self) -> SlowQueryLog {
+        SlowQueryLog {
+            threshold: self.threshold,
+            sink: self.sink.clone(),
+            started: None,
+        }
+    }
+}
+
+/// Times the queries of a connection and logs the ones that took longer than the threshold
+pub struct SlowQueryLog {
+    threshold: Duration,
+    sink: LogSink,
+    started: Option<Instant>,
+}
+
+impl Instrumentation for SlowQueryLog {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                let Some(started) = self.started.take() else {
+                    return;
+                };
+                let elapsed = started.elapsed();
+                if elapsed < self.threshold {
+                    return;
+                }
+
+                let query = query.to_string();
+                self.sink.write(&SlowQueryRecord {
+                    time: Utc::now(),
+                    duration_ms: elapsed.as_secs_f64() * 1000.0,
+                    query: without_binds(&query),
+                    failed: error.is_some(),
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The bound values may be secrets like api keys, only the sql is logged
+fn without_binds(query: &str) -> &str {
+    query
+        .split_once(" -- binds: ")
+        .map_or(query, |(sql, _)| sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_binds() {
+        assert_eq!(
+            without_binds(
+                r#"SELECT `api_keys`.`id` FROM `api_keys` WHERE `api_keys`.`key` = ? -- binds: ["secret"]"#
+            ),
+            "SELECT `api_keys`.`id` FROM `api_keys` WHERE `api_keys`.`key` = ?"
+        );
+        assert_eq!(
+            without_binds("PRAGMA foreign_keys = ON"),
+            "PRAGMA foreign_keys = ON"
+        );
+    }
+}
@@ -6,6 +6,7 @@ use env_logger::Env;
 use rebuilderd::attestation;
 use rebuilderd::config;
 use rebuilderd::db;
+use rebuilderd::selftest;
 use rebuilderd_common::errors::*;
 use std::fs;
 
@@ -24,6 +25,17 @@ async fn main() -> Result<()> {
     env_logger::init_from_env(Env::default().default_filter_or(logging));
 
     dotenvy::dotenv().ok();
+
+    // the selftest brings its own configuration, it must not depend on the local setup
+    if args.selftest {
+        let summary = selftest::run().await.context("Selftest failed")?;
+        println!(
+            "Selftest passed: {} packages synced, {} jobs drained ({} good, {} bad, {} failed)",
+            summary.packages, summary.jobs, summary.good, summary.bad, summary.fail
+        );
+        return Ok(());
+    }
+
     let config = config::load(args.config.as_deref())?;
     if args.check_config {
         println!("{:#?}", config);
@@ -44,12 +56,14 @@ async fn main() -> Result<()> {
         }
     } else {
         let privkey = attestation::load_or_create_privkey_pem(&args.signing_key)?;
-        let pool = db::setup_pool("rebuilderd.db")?;
-
-        let (server, address) = rebuilderd::build_server(pool, config, privkey)?;
+        let pool = db::setup_pool(
+            "rebuilderd.db",
+            !args.no_migrate,
+            config.slow_queries.clone(),
+        )?;
 
-        info!("Listening on {}", address);
-        server.await?;
+        let config = config::LiveConfig::new(config, args.config);
+        rebuilderd::run_config(pool, config, privkey).await?;
     }
     Ok(())
 }
@@ -0,0 +1,225 @@
+//! Periodic housekeeping of the database.
+//!
+//! Every hour queued jobs that are no longer worth building are dropped, generations of snapshot distributions that
+//! are past their ttl are expired and a snapshot of the reproducibility of each suite is recorded, see
+//! [`crate::stats`].
+use crate::config::LiveConfig;
+use crate::db::{self, Pool};
+use crate::models::SnapshotGenerationRow;
+use crate::schema::*;
+use crate::stats::{self, Suite};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection, SqliteExpressionMethods,
+};
+use rebuilderd_common::config::ScheduleConfig;
+use rebuilderd_common::errors::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const MAINTENANCE_INTERVAL: u64 = 3600; // seconds
+const EXPIRE_BATCH_SIZE: usize = 500;
+
+/// queue id, distribution, release and component
+type ExpirableJob = (i32, String, Option<String>, Option<String>);
+
+/// Jobs that are currently being built or pinned to a worker by an admin never expire
+#[diesel::dsl::auto_type]
+fn expirable_jobs() -> _ {
+    queue::table
+        .inner_join(build_inputs::table.inner_join(source_packages::table))
+        .filter(queue::worker.is_null())
+        .filter(queue::pinned_worker.is_null())
+        .select((
+            queue::id,
+            source_packages::distribution,
+            source_packages::release,
+            source_packages::component,
+        ))
+}
+
+/// Drop queued jobs that sat unclaimed for longer than the configured ttl, or whose version is no longer part of its
+/// suite. Returns the number of dropped jobs per suite.
+pub fn expire_queue(
+    connection: &mut SqliteConnection,
+    config: &ScheduleConfig,
+    now: NaiveDateTime,
+) -> Result<BTreeMap<Suite, i64>> {
+    let mut jobs = BTreeMap::new();
+
+    if let Some(ttl) = config.queue_ttl() {
+        let stale = expirable_jobs()
+            .filter(queue::queued_at.lt(now - ttl))
+            .load::<ExpirableJob>(connection)?;
+        jobs.extend(stale.into_iter().map(|(id, d, r, c)| (id, (d, r, c))));
+    }
+
+    if config.expire_superseded() {
+        let superseded = expirable_jobs()
+            .filter(source_packages::seen_in_last_sync.is(false))
+            .load::<ExpirableJob>(connection)?;
+        jobs.extend(superseded.into_iter().map(|(id, d, r, c)| (id, (d, r, c))));
+    }
+
+    let ids = jobs.keys().copied().collect::<Vec<_>>();
+    for chunk in ids.chunks(EXPIRE_BATCH_SIZE) {
+        diesel::delete(queue::table.filter(queue::id.eq_any(chunk))).execute(connection)?;
+    }
+
+    let mut expired = BTreeMap::new();
+    for suite in jobs.into_values() {
+        *expired.entry(suite).or_default() += 1;
+    }
+
+    if !ids.is_empty() {
+        info!("Dropped {} expired jobs from the queue", ids.len());
+    }
+
+    Ok(expired)
+}
+
+/// Remove all packages of snapshot generations that were first synced longer than their ttl ago, together with their
+/// queued jobs. The packages are kept as tombstones and the generation keeps the counts it ended with. Returns the
+/// number of dropped jobs per suite.
+pub fn expire_snapshot_generations(
+    connection: &mut SqliteConnection,
+    config: &ScheduleConfig,
+    now: NaiveDateTime,
+) -> Result<BTreeMap<Suite, i64>> {
+    let mut expired = BTreeMap::new();
+    let mut snapshots = None;
+
+    for distribution in config.snapshot_ttl_days.keys() {
+        let Some(ttl) = config.snapshot_ttl(distribution) else {
+            continue;
+        };
+
+        for generation in
+            SnapshotGenerationRow::list_expirable(distribution, now - ttl, connection)?
+        {
+            // the counts of all generations are taken before the first one is expired
+            if snapshots.is_none() {
+                snapshots = Some(stats::snapshot(connection, now.date())?);
+            }
+            let counts = stats::count_release(
+                snapshots.as_deref().unwrap_or_default(),
+                distribution,
+                &generation.release,
+            );
+
+            connection.transaction(|connection| {
+                let jobs = expirable_jobs()
+                    .filter(source_packages::distribution.eq(distribution))
+                    .filter(source_packages::release.eq(&generation.release))
+                    .load::<ExpirableJob>(connection)?;
+                let ids = jobs.iter().map(|(id, ..)| *id).collect::<Vec<_>>();
+                for chunk in ids.chunks(EXPIRE_BATCH_SIZE) {
+                    diesel::delete(queue::table.filter(queue::id.eq_any(chunk)))
+                        .execute(connection)?;
+                }
+                for (_, distribution, release, component) in jobs {
+                    *expired
+                        .entry((distribution, release, component))
+                        .or_default() += 1;
+                }
+
+                let packages = source_packages::table
+                    .filter(source_packages::distribution.eq(distribution))
+                    .filter(source_packages::release.eq(&generation.release));
+                diesel::update(packages)
+                    .set(source_packages::seen_in_last_sync.eq(false))
+                    .execute(connection)?;
+                diesel::update(packages.filter(source_packages::removed_at.is_null()))
+                    .set(source_packages::removed_at.eq(now))
+                    .execute(connection)?;
+
+                generation.mark_expired(now, counts, connection)
+            })?;
+
+            info!(
+                "Expired snapshot generation {:?} of {:?}",
+                generation.release, distribution
+            );
+        }
+    }
+
+    Ok(expired)
+}
+
+pub async fn run(pool: Pool, config: Arc<LiveConfig>) {
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(MAINTENANCE_INTERVAL));
+
+    loop {
+        interval.tick().await;
+
+        let config = config.current().schedule.clone();
+        let res = db::run(&pool, move |connection| {
+            let now = Utc::now();
+            let mut expired = expire_queue(connection, &config, now.naive_utc())?;
+            for (suite, count) in expire_snapshot_generations(connection, &config, now.naive_utc())?
+            {
+                *expired.entry(suite).or_default() += count;
+            }
+            stats::record(connection, now.date_naive(), &expired)
+        })
+        .await;
+
+        if let Err(err) = res {
+            error!("Failed to run database maintenance: {err:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NewSnapshotGeneration;
+
+    #[test]
+    fn test_expire_queue_empty_database() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let config = ScheduleConfig {
+            queue_ttl_days: Some(7),
+            expire_superseded: Some(true),
+            ..Default::default()
+        };
+
+        let expired = expire_queue(&mut connection, &config, Utc::now().naive_utc()).unwrap();
+        assert!(expired.is_empty());
+    }
+
+    #[test]
+    fn test_expire_snapshot_generations() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let config = ScheduleConfig {
+            snapshot_ttl_days: [("debian-snapshot".to_string(), 7)].into(),
+            ..Default::default()
+        };
+        let now = Utc::now().naive_utc();
+
+        for (release, age) in [("20250101T000000Z", 8), ("20250105T000000Z", 3)] {
+            NewSnapshotGeneration {
+                distribution: "debian-snapshot".to_string(),
+                release: release.to_string(),
+                first_synced_at: now - chrono::Duration::days(age),
+            }
+            .insert_or_ignore(&mut connection)
+            .unwrap();
+        }
+
+        let expired = expire_snapshot_generations(&mut connection, &config, now).unwrap();
+        assert!(expired.is_empty());
+
+        let generations = SnapshotGenerationRow::list("debian-snapshot", &mut connection).unwrap();
+        assert_eq!(generations[0].expired_at, Some(now));
+        assert_eq!(generations[0].good, Some(0));
+        assert_eq!(generations[1].expired_at, None);
+
+        // an expired generation is only expired once
+        let later = now + chrono::Duration::hours(1);
+        expire_snapshot_generations(&mut connection, &config, later).unwrap();
+        let generations = SnapshotGenerationRow::list("debian-snapshot", &mut connection).unwrap();
+        assert_eq!(generations[0].expired_at, Some(now));
+    }
+}
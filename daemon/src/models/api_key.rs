@@ -0,0 +1,54 @@
+use crate::schema::*;
+use crate::secrets;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::api::v1::ApiKeyScope;
+use rebuilderd_common::errors::*;
+
+#[derive(Identifiable, Queryable, Selectable, AsChangeset, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(table_name = api_keys)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    /// Only the hash of the key is stored, see `secrets::hash`
+    pub key_hash: String,
+    pub scope: ApiKeyScope,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl ApiKey {
+    /// Looks up a key that has not been revoked yet.
+    pub fn find_active(key: &str, connection: &mut SqliteConnection) -> Result<Option<ApiKey>> {
+        let api_key = api_keys::table
+            .filter(api_keys::key_hash.is(secrets::hash(key)))
+            .filter(api_keys::revoked_at.is_null())
+            .select(ApiKey::as_select())
+            .first::<ApiKey>(connection)
+            .optional()?;
+
+        Ok(api_key)
+    }
+}
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey {
+    pub name: String,
+    pub key_hash: String,
+    pub scope: ApiKeyScope,
+    pub created_at: NaiveDateTime,
+}
+
+impl NewApiKey {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<i32> {
+        let id = diesel::insert_into(api_keys::table)
+            .values(self)
+            .returning(api_keys::id)
+            .get_result::<i32>(connection)?;
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,63 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Identifiable, Queryable, Selectable, Clone, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(table_name = artifact_verdicts)]
+pub struct ArtifactVerdict {
+    pub id: i32,
+    /// The sha256 of the published artifact
+    pub checksum: String,
+    /// The sha256 of the rebuilder script that verified it
+    pub script_version: String,
+    pub status: String,
+    /// The artifact of our own rebuild the verdict was taken from
+    pub rebuild_artifact_id: Option<i32>,
+    /// The rebuilderd instance that reported the verdict, `None` if one of our workers verified it
+    pub origin: Option<String>,
+    pub verified_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(table_name = artifact_verdicts)]
+pub struct NewArtifactVerdict {
+    pub checksum: String,
+    pub script_version: String,
+    pub status: String,
+    pub rebuild_artifact_id: Option<i32>,
+    pub origin: Option<String>,
+    pub verified_at: NaiveDateTime,
+}
+
+impl NewArtifactVerdict {
+    /// Verdicts of peers never replace one of our own, or one that was imported before.
+    pub fn insert_imported(
+        items: &[NewArtifactVerdict],
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        diesel::insert_or_ignore_into(artifact_verdicts::table)
+            .values(items)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    /// A verdict of our own workers replaces whatever was known about the artifact before.
+    pub fn upsert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(artifact_verdicts::table)
+            .values(self)
+            .on_conflict((
+                artifact_verdicts::checksum,
+                artifact_verdicts::script_version,
+            ))
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
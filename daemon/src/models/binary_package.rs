@@ -20,6 +20,9 @@ pub struct BinaryPackage {
     pub version: String,
     pub architecture: String,
     pub artifact_url: String,
+    pub checksum: Option<String>,
+    /// The binary package this one carries the debug symbols of
+    pub parent_id: Option<i32>,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
@@ -31,6 +34,7 @@ pub struct NewBinaryPackage {
     pub version: String,
     pub architecture: String,
     pub artifact_url: String,
+    pub checksum: Option<String>,
 }
 
 impl NewBinaryPackage {
@@ -53,6 +57,7 @@ impl NewBinaryPackage {
                 name.eq(excluded(name)),
                 version.eq(excluded(version)),
                 architecture.eq(excluded(architecture)),
+                checksum.eq(excluded(checksum)),
             ))
             .returning(BinaryPackage::as_select())
             .get_result::<BinaryPackage>(connection)?;
@@ -0,0 +1,85 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::api::v1::BisectVariation;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = bisects)]
+pub struct NewBisect {
+    pub build_input_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl NewBisect {
+    /// Insert the bisect with a step for every variation, returns the id of the bisect and of its first step
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<(i32, i32)> {
+        let bisect_id = diesel::insert_into(bisects::table)
+            .values(self)
+            .returning(bisects::id)
+            .get_result::<i32>(connection)?;
+
+        let steps = BisectVariation::ALL
+            .iter()
+            .zip(0..)
+            .map(|(variation, position)| {
+                (
+                    bisect_steps::bisect_id.eq(bisect_id),
+                    bisect_steps::variation.eq(variation),
+                    bisect_steps::position.eq(position),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(bisect_steps::table)
+            .values(&steps)
+            .execute(connection)?;
+
+        let first_step_id = bisect_steps::table
+            .filter(bisect_steps::bisect_id.eq(bisect_id))
+            .order_by(bisect_steps::position)
+            .select(bisect_steps::id)
+            .first::<i32>(connection)?;
+
+        Ok((bisect_id, first_step_id))
+    }
+}
+
+#[derive(AsChangeset, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = bisect_steps)]
+pub struct BisectStepResult {
+    pub status: String,
+    pub build_log_id: i32,
+    pub worker_id: Option<i32>,
+    pub built_at: NaiveDateTime,
+}
+
+impl BisectStepResult {
+    /// Record the result of a step and return the id of the step that is built next. After the last step the bisect
+    /// is marked as finished and `None` is returned.
+    pub fn record(&self, step_id: i32, connection: &mut SqliteConnection) -> Result<Option<i32>> {
+        diesel::update(bisect_steps::table.filter(bisect_steps::id.eq(step_id)))
+            .set(self)
+            .execute(connection)?;
+
+        let (bisect_id, position) = bisect_steps::table
+            .filter(bisect_steps::id.eq(step_id))
+            .select((bisect_steps::bisect_id, bisect_steps::position))
+            .get_result::<(i32, i32)>(connection)?;
+
+        let next_step_id = bisect_steps::table
+            .filter(bisect_steps::bisect_id.eq(bisect_id))
+            .filter(bisect_steps::position.gt(position))
+            .order_by(bisect_steps::position)
+            .select(bisect_steps::id)
+            .first::<i32>(connection)
+            .optional()?;
+
+        if next_step_id.is_none() {
+            diesel::update(bisects::table.filter(bisects::id.eq(bisect_id)))
+                .set(bisects::finished_at.eq(self.built_at))
+                .execute(connection)?;
+        }
+
+        Ok(next_step_id)
+    }
+}
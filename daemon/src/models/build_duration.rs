@@ -0,0 +1,44 @@
+use crate::schema::*;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, AsChangeset, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(table_name = build_durations)]
+pub struct NewBuildDuration {
+    pub distribution: String,
+    pub name: String,
+    pub architecture: String,
+    pub seconds: i64,
+    pub measured: bool,
+}
+
+impl NewBuildDuration {
+    /// Imported hints only fill the gaps, a duration that was measured by one of our workers is kept.
+    pub fn insert_hints(
+        items: &[NewBuildDuration],
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        diesel::insert_or_ignore_into(build_durations::table)
+            .values(items)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    /// Replaces whatever was known about the build before, measured or imported.
+    pub fn upsert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(build_durations::table)
+            .values(self)
+            .on_conflict((
+                build_durations::distribution,
+                build_durations::name,
+                build_durations::architecture,
+            ))
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use diesel::{
     AsChangeset, Associations, Identifiable, Insertable, Queryable, RunQueryDsl, Selectable,
     SelectableHelper, SqliteConnection,
 };
+use rebuilderd_common::api::v1::JobPayload;
 use rebuilderd_common::errors::*;
 
 #[derive(
@@ -23,6 +24,11 @@ pub struct BuildInput {
     pub architecture: String,
     pub retries: i32,
     pub next_retry: Option<NaiveDateTime>,
+    pub last_failure: Option<String>,
+    pub popularity: Option<i64>,
+    pub payload: Option<JobPayload>,
+    /// The sync that introduced this build input, unknown for build inputs from before this was recorded
+    pub sync_import_id: Option<i32>,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
@@ -35,6 +41,8 @@ pub struct NewBuildInput {
     pub architecture: String,
     pub retries: i32,
     pub next_retry: Option<NaiveDateTime>,
+    pub payload: Option<JobPayload>,
+    pub sync_import_id: Option<i32>,
 }
 
 impl NewBuildInput {
@@ -50,6 +58,7 @@ impl NewBuildInput {
                 url.eq(diesel::upsert::excluded(url)),
                 backend.eq(diesel::upsert::excluded(backend)),
                 architecture.eq(diesel::upsert::excluded(architecture)),
+                payload.eq(diesel::upsert::excluded(payload)),
             ))
             .returning(BuildInput::as_select())
             .get_result::<BuildInput>(connection)?;
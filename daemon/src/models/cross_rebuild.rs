@@ -0,0 +1,25 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = cross_rebuilds)]
+pub struct NewCrossRebuild {
+    pub build_input_id: i32,
+    pub worker_id: Option<i32>,
+    pub build_log_id: i32,
+    pub build_architecture: String,
+    pub status: String,
+    pub started_at: Option<NaiveDateTime>,
+    pub built_at: NaiveDateTime,
+}
+
+impl NewCrossRebuild {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(cross_rebuilds::table)
+            .values(self)
+            .execute(connection)?;
+        Ok(())
+    }
+}
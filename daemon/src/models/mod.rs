@@ -5,6 +5,7 @@ macro_rules! import_models {
     };
 }
 
+import_models!(api_key);
 import_models!(rebuild);
 import_models!(rebuild_artifact);
 import_models!(binary_package);
@@ -12,3 +13,18 @@ import_models!(build_input);
 import_models!(source_package);
 import_models!(worker);
 import_models!(queue);
+import_models!(worker_token);
+import_models!(package_tag);
+import_models!(package_annotation);
+import_models!(package_popularity);
+import_models!(build_duration);
+import_models!(artifact_verdict);
+import_models!(sync_revision);
+import_models!(sync_import);
+import_models!(stats_history);
+import_models!(snapshot_generation);
+import_models!(provisional_rebuild);
+import_models!(cross_rebuild);
+import_models!(bisect);
+import_models!(status_transition);
+import_models!(subscription);
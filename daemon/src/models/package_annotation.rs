@@ -0,0 +1,31 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, AsChangeset, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(table_name = package_annotations)]
+pub struct NewPackageAnnotation {
+    pub distribution: String,
+    pub name: String,
+    pub bug_url: Option<String>,
+    pub note: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl NewPackageAnnotation {
+    /// Like tags, annotations are keyed by distribution and source package name and replace the previous annotation
+    /// of the package.
+    pub fn upsert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(package_annotations::table)
+            .values(self)
+            .on_conflict((package_annotations::distribution, package_annotations::name))
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use crate::schema::*;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = package_popularity)]
+pub struct NewPackagePopularity {
+    pub distribution: String,
+    pub name: String,
+    pub score: i64,
+}
+
+impl NewPackagePopularity {
+    pub fn insert_batch(
+        items: &[NewPackagePopularity],
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        diesel::insert_into(package_popularity::table)
+            .values(items)
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = package_tags)]
+pub struct NewPackageTag {
+    pub distribution: String,
+    pub name: String,
+    pub tag: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl NewPackageTag {
+    /// Tags are keyed by distribution and source package name, so they are kept when a new version is synced. Adding
+    /// a tag twice is not an error.
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(package_tags::table)
+            .values(self)
+            .on_conflict((
+                package_tags::distribution,
+                package_tags::name,
+                package_tags::tag,
+            ))
+            .do_nothing()
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
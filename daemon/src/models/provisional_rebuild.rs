@@ -0,0 +1,53 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = provisional_rebuilds)]
+pub struct NewProvisionalRebuild {
+    pub build_input_id: i32,
+    pub worker_id: Option<i32>,
+    pub build_log_id: i32,
+    pub status: String,
+    pub started_at: Option<NaiveDateTime>,
+    pub built_at: NaiveDateTime,
+}
+
+impl NewProvisionalRebuild {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(provisional_rebuilds::table)
+            .values(self)
+            .execute(connection)?;
+        Ok(())
+    }
+
+    /// A trusted worker reported a result for these build inputs, compare it with the provisional results that were
+    /// waiting for it
+    pub fn resolve(
+        build_input_ids: &[i32],
+        status: &str,
+        now: NaiveDateTime,
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        let pending = provisional_rebuilds::table
+            .filter(provisional_rebuilds::build_input_id.eq_any(build_input_ids))
+            .filter(provisional_rebuilds::resolved_at.is_null());
+
+        diesel::update(pending.clone().filter(provisional_rebuilds::status.eq(status)))
+            .set((
+                provisional_rebuilds::confirmed.eq(true),
+                provisional_rebuilds::resolved_at.eq(now),
+            ))
+            .execute(connection)?;
+
+        diesel::update(pending)
+            .set((
+                provisional_rebuilds::confirmed.eq(false),
+                provisional_rebuilds::resolved_at.eq(now),
+            ))
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
 use crate::schema::*;
 use chrono::prelude::*;
 use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Nullable, Text, Timestamp};
+use diesel::sqlite::Sqlite;
 use diesel::upsert::excluded;
 use rebuilderd_common::api::v1::Priority;
 use rebuilderd_common::errors::*;
 use serde::{Deserialize, Serialize};
 
+/// Rows per statement of a batch upsert, this stays below the 999 variables older sqlite versions allow
+const UPSERT_CHUNK_SIZE: usize = 100;
+
 #[derive(Identifiable, Queryable, Selectable, AsChangeset, Serialize, PartialEq, Eq, Debug)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(treat_none_as_null = true)]
@@ -18,6 +24,19 @@ pub struct Queued {
     pub started_at: Option<NaiveDateTime>,
     pub worker: Option<i32>,
     pub last_ping: Option<NaiveDateTime>,
+    pub phase: Option<String>,
+    /// Only this worker may pick up the job
+    pub pinned_worker: Option<i32>,
+    /// Build on this architecture instead of the one of the package, to verify a cross build
+    pub build_architecture: Option<String>,
+    /// The job is handed back to the queue if it's still running at this point, unless the deadline is extended
+    pub deadline: Option<NaiveDateTime>,
+    /// An admin asked the worker to kill the build, the job is discarded on its next ping
+    pub aborted_at: Option<NaiveDateTime>,
+    /// The job builds the steps of a bisect one after another, this is the current one
+    pub bisect_step_id: Option<i32>,
+    /// Why an admin queued the package outside the normal schedule, carried over to the rebuild
+    pub reason: Option<String>,
 }
 
 impl Queued {
@@ -26,6 +45,23 @@ impl Queued {
         diesel::delete(queue::table.filter(id.is(self.id))).execute(connection)?;
         Ok(())
     }
+
+    /// Hand the job back to the queue so another worker can pick it up
+    pub fn release(&self, connection: &mut SqliteConnection) -> Result<()> {
+        use crate::schema::queue::columns::*;
+        diesel::update(queue::table.filter(id.is(self.id)))
+            .set((
+                worker.eq(None::<i32>),
+                started_at.eq(None::<NaiveDateTime>),
+                last_ping.eq(None::<NaiveDateTime>),
+                phase.eq(None::<String>),
+                pinned_worker.eq(None::<i32>),
+                deadline.eq(None::<NaiveDateTime>),
+                aborted_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(connection)?;
+        Ok(())
+    }
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -34,6 +70,9 @@ pub struct NewQueued {
     pub build_input_id: i32,
     pub priority: Priority,
     pub queued_at: NaiveDateTime,
+    pub build_architecture: Option<String>,
+    pub bisect_step_id: Option<i32>,
+    pub reason: Option<String>,
 }
 
 impl NewQueued {
@@ -54,4 +93,34 @@ impl NewQueued {
 
         Ok(result)
     }
+
+    /// Insert many queue entries, callers are expected to run this in a transaction. Entries that already exist only get
+    /// their priority updated.
+    pub fn upsert_batch(items: &[NewQueued], connection: &mut SqliteConnection) -> Result<usize> {
+        // diesel doesn't support an upsert of multiple rows with sqlite, the statement is put together by hand
+        let mut count = 0;
+        for chunk in items.chunks(UPSERT_CHUNK_SIZE) {
+            let rows = vec!["(?, ?, ?, ?, ?, ?)"; chunk.len()].join(", ");
+            let mut query = sql_query(format!(
+                "INSERT INTO queue (build_input_id, priority, queued_at, build_architecture, bisect_step_id, reason)
+                VALUES {rows}
+                ON CONFLICT (build_input_id) DO UPDATE SET priority = excluded.priority"
+            ))
+            .into_boxed::<Sqlite>();
+
+            for item in chunk {
+                query = query
+                    .bind::<Integer, _>(item.build_input_id)
+                    .bind::<Integer, _>(item.priority)
+                    .bind::<Timestamp, _>(item.queued_at)
+                    .bind::<Nullable<Text>, _>(&item.build_architecture)
+                    .bind::<Nullable<Integer>, _>(item.bisect_step_id)
+                    .bind::<Nullable<Text>, _>(&item.reason);
+            }
+
+            count += query.execute(connection)?;
+        }
+
+        Ok(count)
+    }
 }
@@ -10,13 +10,15 @@ use rebuilderd_common::errors::*;
 #[diesel(table_name = build_logs)]
 pub struct BuildLog {
     pub id: i32,
-    pub build_log: Vec<u8>,
+    pub build_log: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = build_logs)]
 pub struct NewBuildLog {
-    pub build_log: Vec<u8>,
+    pub build_log: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
 }
 
 impl NewBuildLog {
@@ -42,6 +44,15 @@ pub struct Rebuild {
     pub built_at: Option<NaiveDateTime>,
     pub build_log_id: i32,
     pub status: Option<String>,
+    /// The GOOD or BAD result before it was classified as FLAKY, flakiness is detected from these
+    pub outcome: Option<String>,
+    pub environment_fingerprint: Option<String>,
+    /// The worker that reported the rebuild, `None` for rebuilds from before this was recorded
+    pub worker_id: Option<i32>,
+    /// How much the worker downloaded for the build, `None` if the worker didn't report it
+    pub downloaded_bytes: Option<i64>,
+    /// Why the package was queued for this rebuild, if an admin gave a reason
+    pub reason: Option<String>,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
@@ -52,6 +63,11 @@ pub struct NewRebuild {
     pub built_at: Option<NaiveDateTime>,
     pub build_log_id: i32,
     pub status: Option<String>,
+    pub outcome: Option<String>,
+    pub environment_fingerprint: Option<String>,
+    pub worker_id: Option<i32>,
+    pub downloaded_bytes: Option<i64>,
+    pub reason: Option<String>,
 }
 
 impl NewRebuild {
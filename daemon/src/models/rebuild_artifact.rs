@@ -1,6 +1,7 @@
 use crate::models::Rebuild;
 use crate::schema::*;
 use diesel::prelude::*;
+use rebuilderd_common::api::v1::{ArtifactDiffSummary, LikelyCauses};
 use rebuilderd_common::errors::*;
 
 #[derive(Identifiable, Queryable, AsChangeset, Clone, PartialEq, Eq, Debug)]
@@ -9,13 +10,15 @@ use rebuilderd_common::errors::*;
 #[diesel(table_name = diffoscope_logs)]
 pub struct DiffoscopeLog {
     pub id: i32,
-    pub diffoscope_log: Vec<u8>,
+    pub diffoscope_log: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = diffoscope_logs)]
 pub struct NewDiffoscopeLog {
-    pub diffoscope_log: Vec<u8>,
+    pub diffoscope_log: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
 }
 
 impl NewDiffoscopeLog {
@@ -35,13 +38,15 @@ impl NewDiffoscopeLog {
 #[diesel(table_name = attestation_logs)]
 pub struct AttestationLog {
     pub id: i32,
-    pub attestation_log: Vec<u8>,
+    pub attestation_log: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = attestation_logs)]
 pub struct NewAttestationLog {
-    pub attestation_log: Vec<u8>,
+    pub attestation_log: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
 }
 
 impl NewAttestationLog {
@@ -67,6 +72,9 @@ pub struct RebuildArtifact {
     pub diffoscope_log_id: Option<i32>,
     pub attestation_log_id: Option<i32>,
     pub status: Option<String>,
+    pub diff_summary: Option<ArtifactDiffSummary>,
+    pub checksum: Option<String>,
+    pub likely_causes: LikelyCauses,
 }
 
 #[derive(Insertable, PartialEq, Eq, Debug, Clone)]
@@ -78,14 +86,18 @@ pub struct NewRebuildArtifact {
     pub diffoscope_log_id: Option<i32>,
     pub attestation_log_id: Option<i32>,
     pub status: Option<String>,
+    pub diff_summary: Option<ArtifactDiffSummary>,
+    pub checksum: Option<String>,
+    pub likely_causes: LikelyCauses,
 }
 
 impl NewRebuildArtifact {
-    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<()> {
-        diesel::insert_into(rebuild_artifacts::table)
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<i32> {
+        let id = diesel::insert_into(rebuild_artifacts::table)
             .values(self)
-            .execute(connection)?;
+            .returning(rebuild_artifacts::id)
+            .get_result::<i32>(connection)?;
 
-        Ok(())
+        Ok(id)
     }
 }
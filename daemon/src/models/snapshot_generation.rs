@@ -0,0 +1,98 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Queryable, Selectable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = snapshot_generations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SnapshotGenerationRow {
+    pub id: i32,
+    pub distribution: String,
+    pub release: String,
+    pub first_synced_at: NaiveDateTime,
+    pub expired_at: Option<NaiveDateTime>,
+    pub good: Option<i32>,
+    pub bad: Option<i32>,
+    pub unknown: Option<i32>,
+}
+
+impl SnapshotGenerationRow {
+    pub fn find(
+        distribution: &str,
+        release: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<Option<Self>> {
+        let generation = snapshot_generations::table
+            .filter(snapshot_generations::distribution.eq(distribution))
+            .filter(snapshot_generations::release.eq(release))
+            .select(SnapshotGenerationRow::as_select())
+            .get_result(connection)
+            .optional()?;
+        Ok(generation)
+    }
+
+    /// Oldest generation first
+    pub fn list(distribution: &str, connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        let generations = snapshot_generations::table
+            .filter(snapshot_generations::distribution.eq(distribution))
+            .order_by((
+                snapshot_generations::first_synced_at,
+                snapshot_generations::id,
+            ))
+            .select(SnapshotGenerationRow::as_select())
+            .load(connection)?;
+        Ok(generations)
+    }
+
+    /// Generations that weren't expired yet and were first synced before the given time
+    pub fn list_expirable(
+        distribution: &str,
+        synced_before: NaiveDateTime,
+        connection: &mut SqliteConnection,
+    ) -> Result<Vec<Self>> {
+        let generations = snapshot_generations::table
+            .filter(snapshot_generations::distribution.eq(distribution))
+            .filter(snapshot_generations::expired_at.is_null())
+            .filter(snapshot_generations::first_synced_at.lt(synced_before))
+            .select(SnapshotGenerationRow::as_select())
+            .load(connection)?;
+        Ok(generations)
+    }
+
+    /// Keep the counts of the generation as they were when it expired
+    pub fn mark_expired(
+        &self,
+        now: NaiveDateTime,
+        (good, bad, unknown): (i32, i32, i32),
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        diesel::update(snapshot_generations::table.filter(snapshot_generations::id.eq(self.id)))
+            .set((
+                snapshot_generations::expired_at.eq(now),
+                snapshot_generations::good.eq(good),
+                snapshot_generations::bad.eq(bad),
+                snapshot_generations::unknown.eq(unknown),
+            ))
+            .execute(connection)?;
+        Ok(())
+    }
+}
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = snapshot_generations)]
+pub struct NewSnapshotGeneration {
+    pub distribution: String,
+    pub release: String,
+    pub first_synced_at: NaiveDateTime,
+}
+
+impl NewSnapshotGeneration {
+    /// The ttl starts with the first sync of a generation, later syncs don't extend it
+    pub fn insert_or_ignore(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_or_ignore_into(snapshot_generations::table)
+            .values(self)
+            .execute(connection)?;
+        Ok(())
+    }
+}
@@ -17,6 +17,10 @@ pub struct SourcePackage {
     pub component: Option<String>,
     pub last_seen: NaiveDateTime,
     pub seen_in_last_sync: bool,
+    /// Set once a sync dropped the package from its suite, the row is kept as tombstone
+    pub removed_at: Option<NaiveDateTime>,
+    /// Who maintains the package according to the distribution, if the sync reported it
+    pub maintainer: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset, PartialEq, Eq, Debug, Clone)]
@@ -32,6 +36,10 @@ pub struct NewSourcePackage {
     pub component: Option<String>,
     pub last_seen: NaiveDateTime,
     pub seen_in_last_sync: bool,
+    /// Set once a sync dropped the package from its suite, the row is kept as tombstone
+    pub removed_at: Option<NaiveDateTime>,
+    /// Who maintains the package according to the distribution, if the sync reported it
+    pub maintainer: Option<String>,
 }
 
 impl NewSourcePackage {
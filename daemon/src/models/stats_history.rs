@@ -0,0 +1,40 @@
+use crate::schema::*;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = stats_history)]
+pub struct NewStatsHistory {
+    pub date: NaiveDate,
+    pub distribution: String,
+    pub release: Option<String>,
+    pub component: Option<String>,
+    pub good: i32,
+    pub bad: i32,
+    pub unknown: i32,
+    /// Queued jobs that were dropped because they expired, accumulated over the day
+    pub expired: i32,
+}
+
+impl NewStatsHistory {
+    /// There's one snapshot per suite and day, a snapshot taken later on the same day replaces the earlier one
+    pub fn replace_day(
+        date: NaiveDate,
+        snapshots: &[NewStatsHistory],
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        connection.transaction(|connection| {
+            diesel::delete(stats_history::table.filter(stats_history::date.eq(date)))
+                .execute(connection)?;
+
+            if !snapshots.is_empty() {
+                diesel::insert_into(stats_history::table)
+                    .values(snapshots)
+                    .execute(connection)?;
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,24 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = status_transitions)]
+pub struct NewStatusTransition {
+    pub rebuild_id: i32,
+    pub kind: String,
+    pub previous_version: String,
+    pub previous_status: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl NewStatusTransition {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::insert_into(status_transitions::table)
+            .values(self)
+            .execute(connection)?;
+        Ok(())
+    }
+}
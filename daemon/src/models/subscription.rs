@@ -0,0 +1,175 @@
+use crate::notify::Recipients;
+use crate::schema::*;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Identifiable, Queryable, Selectable, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(table_name = subscriptions)]
+pub struct Subscription {
+    pub id: i32,
+    pub api_key_id: i32,
+    /// The source package that is followed, unset if a maintainer is followed instead
+    pub name: Option<String>,
+    /// Follows every source package of this maintainer
+    pub maintainer: Option<String>,
+    pub distribution: Option<String>,
+    pub webhook: Option<String>,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// Email subscriptions are inactive until the address confirmed the token that was mailed to it
+    pub confirmed_at: Option<NaiveDateTime>,
+}
+
+#[diesel::dsl::auto_type]
+fn subscribed_to<'a>(
+    name: &'a str,
+    maintainer: Option<&'a str>,
+    distribution: &'a str,
+) -> _ {
+    subscriptions::table
+        .inner_join(api_keys::table)
+        .filter(api_keys::revoked_at.is_null())
+        .filter(subscriptions::confirmed_at.is_not_null())
+        .filter(
+            subscriptions::name
+                .eq(name)
+                .or(subscriptions::maintainer.eq(maintainer)),
+        )
+        .filter(
+            subscriptions::distribution
+                .is_null()
+                .or(subscriptions::distribution.eq(distribution)),
+        )
+}
+
+impl Subscription {
+    pub fn list(api_key_id: i32, connection: &mut SqliteConnection) -> Result<Vec<Subscription>> {
+        let subscriptions = subscriptions::table
+            .filter(subscriptions::api_key_id.eq(api_key_id))
+            .order_by(subscriptions::id)
+            .select(Subscription::as_select())
+            .load(connection)?;
+        Ok(subscriptions)
+    }
+
+    /// Returns false if the key doesn't have a subscription with this id
+    pub fn delete(id: i32, api_key_id: i32, connection: &mut SqliteConnection) -> Result<bool> {
+        let deleted = diesel::delete(
+            subscriptions::table
+                .filter(subscriptions::id.eq(id))
+                .filter(subscriptions::api_key_id.eq(api_key_id)),
+        )
+        .execute(connection)?;
+        Ok(deleted > 0)
+    }
+
+    /// If the key already confirmed this email address with another subscription
+    pub fn is_confirmed(
+        api_key_id: i32,
+        email: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<bool> {
+        let confirmed = diesel::select(diesel::dsl::exists(
+            subscriptions::table
+                .filter(subscriptions::api_key_id.eq(api_key_id))
+                .filter(subscriptions::email.eq(email))
+                .filter(subscriptions::confirmed_at.is_not_null()),
+        ))
+        .get_result(connection)?;
+        Ok(confirmed)
+    }
+
+    /// The token that was already mailed to this address for another subscription of the key, if it's still pending
+    pub fn pending_token_hash(
+        api_key_id: i32,
+        email: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<Option<String>> {
+        let token_hash = subscriptions::table
+            .filter(subscriptions::api_key_id.eq(api_key_id))
+            .filter(subscriptions::email.eq(email))
+            .filter(subscriptions::confirmation_token_hash.is_not_null())
+            .select(subscriptions::confirmation_token_hash)
+            .first::<Option<String>>(connection)
+            .optional()?;
+        Ok(token_hash.flatten())
+    }
+
+    /// Activates every subscription that is waiting for this token, returns how many there were
+    pub fn confirm(token_hash: &str, connection: &mut SqliteConnection) -> Result<usize> {
+        let now = Utc::now().naive_utc();
+        let confirmed = diesel::update(
+            subscriptions::table.filter(subscriptions::confirmation_token_hash.eq(token_hash)),
+        )
+        .set((
+            subscriptions::confirmation_token_hash.eq(None::<String>),
+            subscriptions::confirmed_at.eq(now),
+        ))
+        .execute(connection)?;
+        Ok(confirmed)
+    }
+
+    /// Who subscribed to a source package, directly or through its maintainer. Subscriptions of revoked keys and email
+    /// addresses that weren't confirmed are skipped
+    pub fn recipients(
+        name: &str,
+        maintainer: Option<&str>,
+        distribution: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<Recipients> {
+        let mut recipients = Recipients::default();
+        for (webhook, email) in subscribed_to(name, maintainer, distribution)
+            .select((subscriptions::webhook, subscriptions::email))
+            .distinct()
+            .load::<(Option<String>, Option<String>)>(connection)?
+        {
+            recipients.webhooks.extend(webhook);
+            recipients.emails.extend(email);
+        }
+        recipients.webhooks.sort();
+        recipients.webhooks.dedup();
+        recipients.emails.sort();
+        recipients.emails.dedup();
+        Ok(recipients)
+    }
+
+    /// The active subscriptions of a source package in a distribution, directly or through its maintainer
+    pub fn active(
+        name: &str,
+        maintainer: Option<&str>,
+        distribution: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<Vec<Subscription>> {
+        let subscriptions = subscribed_to(name, maintainer, distribution)
+            .order_by(subscriptions::id)
+            .select(Subscription::as_select())
+            .load(connection)?;
+        Ok(subscriptions)
+    }
+}
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = subscriptions)]
+pub struct NewSubscription {
+    pub api_key_id: i32,
+    pub name: Option<String>,
+    pub maintainer: Option<String>,
+    pub distribution: Option<String>,
+    pub webhook: Option<String>,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub confirmation_token_hash: Option<String>,
+    pub confirmed_at: Option<NaiveDateTime>,
+}
+
+impl NewSubscription {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<Subscription> {
+        let subscription = diesel::insert_into(subscriptions::table)
+            .values(self)
+            .returning(Subscription::as_returning())
+            .get_result(connection)?;
+        Ok(subscription)
+    }
+}
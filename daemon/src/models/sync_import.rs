@@ -0,0 +1,41 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::dsl::{exists, not};
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+/// A sync of a suite and architecture, build inputs remember the import that introduced them
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = sync_imports)]
+pub struct NewSyncImport {
+    pub distribution: String,
+    pub release: Option<String>,
+    pub component: Option<String>,
+    pub architecture: String,
+    pub source: Option<String>,
+    pub revision: String,
+    pub imported_at: NaiveDateTime,
+}
+
+impl NewSyncImport {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<i32> {
+        let id = diesel::insert_into(sync_imports::table)
+            .values(self)
+            .returning(sync_imports::id)
+            .get_result::<i32>(connection)?;
+        Ok(id)
+    }
+}
+
+/// Most syncs don't introduce any new build inputs, there's no point in keeping a record of them
+pub fn drop_unused_sync_import(connection: &mut SqliteConnection, id: i32) -> Result<()> {
+    diesel::delete(
+        sync_imports::table
+            .filter(sync_imports::id.eq(id))
+            .filter(not(exists(
+                build_inputs::table.filter(build_inputs::sync_import_id.eq(id)),
+            ))),
+    )
+    .execute(connection)?;
+    Ok(())
+}
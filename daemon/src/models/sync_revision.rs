@@ -0,0 +1,36 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::errors::*;
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = sync_revisions)]
+pub struct NewSyncRevision {
+    pub distribution: String,
+    pub release: Option<String>,
+    pub component: Option<String>,
+    pub architecture: String,
+    pub revision: String,
+    pub updated_at: NaiveDateTime,
+    pub source: Option<String>,
+}
+
+impl NewSyncRevision {
+    /// There's only one revision per suite and architecture, the previous one is replaced
+    pub fn replace(&self, connection: &mut SqliteConnection) -> Result<()> {
+        diesel::delete(
+            sync_revisions::table
+                .filter(sync_revisions::distribution.is(&self.distribution))
+                .filter(sync_revisions::release.is(&self.release))
+                .filter(sync_revisions::component.is(&self.component))
+                .filter(sync_revisions::architecture.is(&self.architecture)),
+        )
+        .execute(connection)?;
+
+        diesel::insert_into(sync_revisions::table)
+            .values(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
@@ -2,10 +2,13 @@ use crate::schema::*;
 use chrono::prelude::*;
 use diesel::prelude::*;
 use diesel::upsert::excluded;
+use rebuilderd_common::api::v1::WorkerEnvironment;
 use rebuilderd_common::errors::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Identifiable, Queryable, AsChangeset, Selectable, Serialize, PartialEq, Eq, Debug)]
+#[derive(
+    Identifiable, Queryable, AsChangeset, Selectable, Serialize, Clone, PartialEq, Eq, Debug,
+)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(treat_none_as_null = true)]
 #[diesel(table_name = workers)]
@@ -17,9 +20,151 @@ pub struct Worker {
     pub status: Option<String>,
     pub last_ping: NaiveDateTime,
     pub online: bool,
+    #[serde(skip)]
+    pub environment: Option<WorkerEnvironment>,
+    pub draining: bool,
+    /// Comma separated, the architectures the worker asked for work with the last time
+    #[serde(skip)]
+    pub architectures: Option<String>,
+    /// The key before the last rotation, it's accepted until it expires
+    #[serde(skip)]
+    pub previous_key: Option<String>,
+    #[serde(skip)]
+    pub previous_key_expires_at: Option<NaiveDateTime>,
+    /// Results of untrusted workers are only recorded as provisional until a trusted worker confirms them
+    #[serde(skip)]
+    pub trusted: bool,
+    /// Generated by the worker on its first run and kept in its identity file, it doesn't change when the key is
+    /// rotated
+    pub uuid: Option<String>,
+    /// The sum of what the worker downloaded for the builds it reported
+    pub downloaded_bytes: i64,
+    /// The client certificate the worker presented on first contact, requests with a different one are rejected
+    pub fingerprint: Option<String>,
+    /// When a different client certificate was presented the last time
+    #[serde(skip)]
+    pub fingerprint_mismatch_at: Option<NaiveDateTime>,
 }
 
 impl Worker {
+    pub fn find_by_uuid(uuid: &str, connection: &mut SqliteConnection) -> Result<Option<Worker>> {
+        let worker = workers::table
+            .filter(workers::uuid.is(uuid))
+            .select(Worker::as_select())
+            .first(connection)
+            .optional()?;
+        Ok(worker)
+    }
+
+    /// Look up the current key of the worker that presented this key, either the key itself or a previous key that
+    /// was rotated within the grace period
+    pub fn resolve_key(key: &str, connection: &mut SqliteConnection) -> Result<Option<String>> {
+        let current = workers::table
+            .filter(workers::key.is(key))
+            .select(workers::key)
+            .first::<String>(connection)
+            .optional()?;
+        if current.is_some() {
+            return Ok(current);
+        }
+
+        let current = workers::table
+            .filter(workers::previous_key.is(key))
+            .filter(workers::previous_key_expires_at.gt(Utc::now().naive_utc()))
+            .select(workers::key)
+            .first::<String>(connection)
+            .optional()?;
+        Ok(current)
+    }
+
+    /// Move the identity of the worker to a new key, the current key is kept as previous key until `expires_at`
+    pub fn rotate_key(
+        &self,
+        new_key: &str,
+        expires_at: NaiveDateTime,
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        diesel::update(workers::table.filter(workers::id.is(self.id)))
+            .set((
+                workers::key.eq(new_key),
+                workers::previous_key.eq(&self.key),
+                workers::previous_key_expires_at.eq(expires_at),
+            ))
+            .execute(connection)?;
+        Ok(())
+    }
+
+    pub fn find_by_key(key: &str, connection: &mut SqliteConnection) -> Result<Option<Worker>> {
+        let worker = workers::table
+            .filter(workers::key.is(key))
+            .select(Worker::as_select())
+            .first(connection)
+            .optional()?;
+        Ok(worker)
+    }
+
+    /// Pin the fingerprint if the worker doesn't have one yet. Returns false if a different one is pinned.
+    pub fn pin_fingerprint(
+        &self,
+        fingerprint: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<bool> {
+        if let Some(pinned) = &self.fingerprint {
+            return Ok(pinned == fingerprint);
+        }
+
+        let updated = diesel::update(
+            workers::table
+                .filter(workers::id.is(self.id))
+                .filter(workers::fingerprint.is_null()),
+        )
+        .set(workers::fingerprint.eq(fingerprint))
+        .execute(connection)?;
+        if updated > 0 {
+            info!(
+                "Pinned client certificate {fingerprint:?} for worker {:?}",
+                self.name
+            );
+            return Ok(true);
+        }
+
+        // a concurrent request pinned a fingerprint first
+        let pinned = workers::table
+            .filter(workers::id.is(self.id))
+            .select(workers::fingerprint)
+            .first::<Option<String>>(connection)?;
+        Ok(pinned.as_deref() == Some(fingerprint))
+    }
+
+    /// Returns true if the admins should hear about the mismatch, repeated ones are only reported once per hour
+    pub fn record_fingerprint_mismatch(
+        &self,
+        now: NaiveDateTime,
+        connection: &mut SqliteConnection,
+    ) -> Result<bool> {
+        let updated = diesel::update(
+            workers::table.filter(workers::id.is(self.id)).filter(
+                workers::fingerprint_mismatch_at
+                    .is_null()
+                    .or(workers::fingerprint_mismatch_at.lt(now - chrono::Duration::hours(1))),
+            ),
+        )
+        .set(workers::fingerprint_mismatch_at.eq(now))
+        .execute(connection)?;
+        Ok(updated > 0)
+    }
+
+    /// Forget the pinned fingerprint, the next one the worker presents is pinned instead
+    pub fn reset_fingerprint(id: i32, connection: &mut SqliteConnection) -> Result<usize> {
+        let updated = diesel::update(workers::table.filter(workers::id.is(id)))
+            .set((
+                workers::fingerprint.eq(None::<String>),
+                workers::fingerprint_mismatch_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(connection)?;
+        Ok(updated)
+    }
+
     pub fn get_and_refresh(key: &str, connection: &mut SqliteConnection) -> Result<Worker> {
         let worker = diesel::update(workers::table.filter(workers::key.is(key)))
             .set((
@@ -43,6 +188,12 @@ pub struct NewWorker {
     pub status: Option<String>,
     pub last_ping: NaiveDateTime,
     pub online: bool,
+    pub environment: Option<WorkerEnvironment>,
+    /// Only set on the first registration, a worker that registers again keeps the trust level it had
+    pub trusted: bool,
+    pub uuid: Option<String>,
+    /// Only set on the first registration, it's pinned from then on
+    pub fingerprint: Option<String>,
 }
 
 impl NewWorker {
@@ -58,10 +209,21 @@ impl NewWorker {
                 workers::status.eq(&self.status),
                 workers::last_ping.eq(&self.last_ping),
                 workers::online.eq(&self.online),
+                workers::environment.eq(&self.environment),
             ))
             .returning(Worker::as_select())
             .get_result::<Worker>(connection)?;
 
+        // workers that predate identity files register without uuid, they keep the one they had
+        if self.uuid.is_none() || result.uuid == self.uuid {
+            return Ok(result);
+        }
+
+        let result = diesel::update(workers::table.filter(workers::id.is(result.id)))
+            .set(workers::uuid.eq(&self.uuid))
+            .returning(Worker::as_select())
+            .get_result::<Worker>(connection)?;
+
         Ok(result)
     }
 }
@@ -0,0 +1,59 @@
+use crate::models::Worker;
+use crate::schema::*;
+use crate::secrets;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rebuilderd_common::api::v1::WorkerCapability;
+use rebuilderd_common::errors::*;
+
+#[derive(Identifiable, Queryable, Selectable, AsChangeset, PartialEq, Eq, Debug)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(table_name = worker_tokens)]
+pub struct WorkerToken {
+    pub id: i32,
+    pub worker_id: i32,
+    /// Only the hash of the token is stored, like for api keys
+    pub token_hash: String,
+    pub capability: WorkerCapability,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl WorkerToken {
+    /// Looks up a token that has not been revoked yet, together with the worker it was issued for.
+    pub fn find_active(
+        token: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<Option<(WorkerToken, Worker)>> {
+        let record = worker_tokens::table
+            .inner_join(workers::table)
+            .filter(worker_tokens::token_hash.is(secrets::hash(token)))
+            .filter(worker_tokens::revoked_at.is_null())
+            .select((WorkerToken::as_select(), Worker::as_select()))
+            .first::<(WorkerToken, Worker)>(connection)
+            .optional()?;
+
+        Ok(record)
+    }
+}
+
+#[derive(Insertable, PartialEq, Eq, Debug)]
+#[diesel(table_name = worker_tokens)]
+pub struct NewWorkerToken {
+    pub worker_id: i32,
+    pub token_hash: String,
+    pub capability: WorkerCapability,
+    pub created_at: NaiveDateTime,
+}
+
+impl NewWorkerToken {
+    pub fn insert(&self, connection: &mut SqliteConnection) -> Result<i32> {
+        let id = diesel::insert_into(worker_tokens::table)
+            .values(self)
+            .returning(worker_tokens::id)
+            .get_result::<i32>(connection)?;
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,119 @@
+use ipnet::IpNet;
+use rebuilderd_common::config::WorkerConfig;
+use rebuilderd_common::errors::*;
+use std::net::IpAddr;
+
+/// The networks workers may connect from. A denied network always wins, and if any network is allowed, addresses
+/// outside of them are rejected too.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NetworkPolicy {
+    allowed: Vec<IpNet>,
+    denied: Vec<IpNet>,
+}
+
+/// Either a network in CIDR notation or a single address
+fn parse_network(network: &str) -> Result<IpNet> {
+    if let Ok(addr) = network.parse::<IpAddr>() {
+        return Ok(IpNet::from(addr));
+    }
+    network
+        .parse::<IpNet>()
+        .with_context(|| anyhow!("Invalid network: {network:?}"))
+}
+
+impl NetworkPolicy {
+    pub fn from_config(config: &WorkerConfig) -> Result<Self> {
+        let allowed = config
+            .allowed_networks
+            .iter()
+            .map(|network| parse_network(network))
+            .collect::<Result<_>>()?;
+        let denied = config
+            .denied_networks
+            .iter()
+            .map(|network| parse_network(network))
+            .collect::<Result<_>>()?;
+        Ok(NetworkPolicy { allowed, denied })
+    }
+
+    /// Nothing is configured, workers are accepted from any address
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty() && self.denied.is_empty()
+    }
+
+    pub fn permits(&self, addr: IpAddr) -> bool {
+        // an ipv4 client of a dual-stack socket shows up as ipv4-mapped ipv6 address
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            IpAddr::V4(_) => addr,
+        };
+
+        if self.denied.iter().any(|network| network.contains(&addr)) {
+            return false;
+        }
+        self.allowed.is_empty() || self.allowed.iter().any(|network| network.contains(&addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed: &[&str], denied: &[&str]) -> NetworkPolicy {
+        NetworkPolicy::from_config(&WorkerConfig {
+            allowed_networks: allowed.iter().map(|s| s.to_string()).collect(),
+            denied_networks: denied.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_permit_everything_by_default() {
+        let policy = policy(&[], &[]);
+        assert!(policy.is_empty());
+        assert!(policy.permits(ip("192.0.2.1")));
+        assert!(policy.permits(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn test_allowed_networks() {
+        let policy = policy(&["10.0.0.0/8", "2001:db8::/32", "192.0.2.7"], &[]);
+        assert!(policy.permits(ip("10.1.2.3")));
+        assert!(policy.permits(ip("2001:db8::1")));
+        assert!(policy.permits(ip("192.0.2.7")));
+        assert!(!policy.permits(ip("192.0.2.8")));
+        assert!(!policy.permits(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn test_denied_networks_win() {
+        let nested = policy(&["10.0.0.0/8"], &["10.66.0.0/16"]);
+        assert!(nested.permits(ip("10.1.2.3")));
+        assert!(!nested.permits(ip("10.66.1.1")));
+
+        let deny_only = policy(&[], &["198.51.100.0/24"]);
+        assert!(!deny_only.is_empty());
+        assert!(!deny_only.permits(ip("198.51.100.1")));
+        assert!(deny_only.permits(ip("192.0.2.1")));
+    }
+
+    #[test]
+    fn test_ipv4_mapped() {
+        let policy = policy(&["10.0.0.0/8"], &[]);
+        assert!(policy.permits(ip("::ffff:10.1.2.3")));
+    }
+
+    #[test]
+    fn test_invalid_network() {
+        let config = WorkerConfig {
+            allowed_networks: vec!["10.0.0.0/33".to_string()],
+            ..Default::default()
+        };
+        assert!(NetworkPolicy::from_config(&config).is_err());
+    }
+}
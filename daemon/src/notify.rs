@@ -0,0 +1,224 @@
+use chrono::{NaiveDateTime, Utc};
+use lettre::message::Mailbox;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use rebuilderd_common::config::NotifyConfig;
+use rebuilderd_common::errors::*;
+use rebuilderd_common::http::{self, OutboundConfig};
+use serde::Serialize;
+use std::fmt;
+
+/// Something an operator or a subscriber should hear about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    /// What kind of event this is, eg. `alert.firing`
+    pub event: String,
+    /// Identifies the condition the notification is about, events for the same condition share the key
+    pub key: String,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Notification {
+    pub fn new(event: &str, key: &str, message: String) -> Self {
+        Notification {
+            event: event.to_string(),
+            key: key.to_string(),
+            message,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// Where the notifications of subscribers are delivered to
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Recipients {
+    pub webhooks: Vec<String>,
+    pub emails: Vec<String>,
+}
+
+impl Recipients {
+    pub fn is_empty(&self) -> bool {
+        self.webhooks.is_empty() && self.emails.is_empty()
+    }
+}
+
+#[derive(Clone)]
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    fn new(smtp: &str, from: &str) -> Result<Self> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(smtp)
+            .context("Failed to parse smtp url")?
+            .build();
+        let from = from
+            .parse()
+            .with_context(|| anyhow!("Failed to parse mail sender: {from:?}"))?;
+        Ok(Mailer { transport, from })
+    }
+
+    async fn send(&self, to: &str, subject: &str, body: String) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(format!("[rebuilderd] {subject}"))
+            .body(body)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Mailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the smtp url may carry credentials
+        f.debug_struct("Mailer").field("from", &self.from).finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    client: http::Client,
+    webhooks: Vec<String>,
+    subscriber_client: http::Client,
+    mailer: Option<Mailer>,
+    allow_http_subscriptions: bool,
+    allow_internal_subscriptions: bool,
+}
+
+impl Notifier {
+    pub fn new(config: &NotifyConfig, outbound: &OutboundConfig) -> Result<Self> {
+        let allow_internal_subscriptions = config.allow_internal_subscriptions();
+        let mailer = match (&config.smtp, &config.mail_from) {
+            (Some(smtp), Some(from)) => Some(Mailer::new(smtp, from)?),
+            (Some(_), None) => bail!("Sending mail with smtp= needs a mail_from= sender"),
+            (None, _) => None,
+        };
+        Ok(Notifier {
+            client: http::client(outbound)?,
+            webhooks: config.webhooks.clone(),
+            subscriber_client: http::webhook_client(outbound, allow_internal_subscriptions)?,
+            mailer,
+            allow_http_subscriptions: config.allow_http_subscriptions(),
+            allow_internal_subscriptions,
+        })
+    }
+
+    async fn post(&self, url: &str, notification: &Notification) -> Result<()> {
+        self.client
+            .post(url)
+            .json(notification)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn deliver(&self, webhooks: &[String], notification: &Notification) {
+        for url in webhooks {
+            if let Err(err) = self.post(url, notification).await {
+                warn!("Failed to deliver notification to webhook {url:?}: {err:#}");
+            }
+        }
+    }
+
+    /// Notifications are always logged, a webhook that can't be reached is logged too but doesn't fail the caller
+    pub async fn send(&self, notification: &Notification) {
+        warn!("{}: {}", notification.event, notification.message);
+        self.deliver(&self.webhooks, notification).await;
+    }
+
+    async fn post_to_subscriber(&self, url: &str, notification: &Notification) -> Result<()> {
+        // subscriptions from before the config changed are checked again
+        http::check_webhook(
+            url,
+            self.allow_http_subscriptions,
+            self.allow_internal_subscriptions,
+        )
+        .await?;
+        self.subscriber_client
+            .post(url)
+            .json(notification)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Deliver to subscribers instead of the configured webhooks
+    pub async fn send_to(&self, recipients: &Recipients, notification: &Notification) {
+        info!(
+            "Notifying {} subscriber webhook(s) and {} email address(es) about {}: {}",
+            recipients.webhooks.len(),
+            recipients.emails.len(),
+            notification.event,
+            notification.message
+        );
+        for url in &recipients.webhooks {
+            if let Err(err) = self.post_to_subscriber(url, notification).await {
+                warn!("Failed to deliver notification to subscriber webhook {url:?}: {err:#}");
+            }
+        }
+
+        if recipients.emails.is_empty() {
+            return;
+        }
+        let Some(mailer) = &self.mailer else {
+            warn!(
+                "Dropping notification for {} email subscriber(s), smtp is not configured anymore",
+                recipients.emails.len()
+            );
+            return;
+        };
+        for email in &recipients.emails {
+            let body = format!("{}\n", notification.message);
+            if let Err(err) = mailer.send(email, &notification.key, body).await {
+                warn!("Failed to mail notification to subscriber {email:?}: {err:#}");
+            }
+        }
+    }
+
+    /// Mail the token that activates the email subscriptions of an address, so nobody can be subscribed without asking
+    pub async fn send_confirmation(&self, email: &str, token: &str) -> Result<()> {
+        let mailer = self
+            .mailer
+            .as_ref()
+            .context("This rebuilderd doesn't send mail")?;
+        let body = format!(
+            "Somebody subscribed this address to notifications of a rebuilderd.\n\
+             \n\
+             If this was you, confirm the subscription with:\n\
+             \n\
+             rebuildctl subscriptions confirm {token}\n\
+             \n\
+             Otherwise you can ignore this mail, nothing is sent to this address\n\
+             until the subscription is confirmed.\n"
+        );
+        mailer
+            .send(email, "Confirm your subscription", body)
+            .await
+            .with_context(|| anyhow!("Failed to mail confirmation to {email:?}"))
+    }
+
+    /// Refuses webhooks of new subscriptions that are not allowed by the config
+    pub async fn check_subscriber_webhook(&self, url: &str) -> Result<()> {
+        http::check_webhook(
+            url,
+            self.allow_http_subscriptions,
+            self.allow_internal_subscriptions,
+        )
+        .await
+    }
+
+    /// Refuses email subscriptions if the daemon can't send mail
+    pub fn check_subscriber_email(&self, email: &str) -> Result<()> {
+        if self.mailer.is_none() {
+            bail!("This rebuilderd doesn't send mail, subscribe with a webhook instead");
+        }
+        email
+            .parse::<Address>()
+            .with_context(|| anyhow!("Invalid email address: {email:?}"))?;
+        Ok(())
+    }
+}
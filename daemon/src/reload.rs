@@ -0,0 +1,20 @@
+use crate::config::LiveConfig;
+use actix_web::rt::signal::unix::{SignalKind, signal};
+use rebuilderd_common::errors::*;
+use std::sync::Arc;
+
+/// Reload the config file on SIGHUP, eg. to rotate credentials without dropping the connections of workers that are
+/// in the middle of submitting a report
+pub fn listen(config: Arc<LiveConfig>) -> Result<()> {
+    let mut hangup = signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+    actix_web::rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading config");
+            match config.reload() {
+                Ok(()) => info!("Reloaded config"),
+                Err(err) => error!("Failed to reload config, keeping the previous one: {err:#}"),
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use rebuilderd_common::config::ScheduleConfig;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Jobs are distributed fairly between suites, a suite is a distribution and release
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Suite {
+    pub distribution: String,
+    pub release: Option<String>,
+}
+
+/// Smooth weighted round-robin across the suites that currently have work available.
+///
+/// Every time a job is handed out, each candidate suite earns its weight in credit and
+/// the suite with the most credit is picked and pays back the sum of all weights. Over
+/// time every suite receives a share of the workers proportional to its weight, no matter
+/// how many jobs it has queued.
+#[derive(Debug, Default)]
+pub struct FairScheduler {
+    credits: Mutex<HashMap<Suite, i64>>,
+}
+
+/// The suite the next job is taken from. The credits only change once a job of it was actually handed out, see
+/// `FairScheduler::charge`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pick {
+    pub suite: Suite,
+    candidates: Vec<Suite>,
+}
+
+fn weights(config: &ScheduleConfig, candidates: &[Suite]) -> Vec<i64> {
+    let mut weights = candidates
+        .iter()
+        .map(|suite| i64::from(config.weight(&suite.distribution, suite.release.as_deref())))
+        .collect::<Vec<_>>();
+
+    // suites with a weight of 0 are only picked if nothing else is available
+    if weights.iter().all(|weight| *weight == 0) {
+        weights.iter_mut().for_each(|weight| *weight = 1);
+    }
+    weights
+}
+
+impl FairScheduler {
+    pub fn pick(&self, config: &ScheduleConfig, candidates: &[Suite]) -> Option<Pick> {
+        let weights = weights(config, candidates);
+        let credits = self.credits.lock().unwrap();
+
+        let mut best: Option<(&Suite, i64)> = None;
+        for (suite, weight) in candidates.iter().zip(weights) {
+            if weight == 0 {
+                continue;
+            }
+
+            let credit = credits.get(suite).copied().unwrap_or_default() + weight;
+            if best.is_none_or(|(_, max)| credit > max) {
+                best = Some((suite, credit));
+            }
+        }
+
+        let (suite, _) = best?;
+        Some(Pick {
+            suite: suite.clone(),
+            candidates: candidates.to_vec(),
+        })
+    }
+
+    /// Settle the credits of a pick after a job of the picked suite was handed out
+    pub fn charge(&self, config: &ScheduleConfig, pick: &Pick) {
+        let weights = weights(config, &pick.candidates);
+        let total = weights.iter().sum::<i64>();
+
+        let mut credits = self.credits.lock().unwrap();
+        // forget suites that ran out of work, they start from scratch once they return
+        credits.retain(|suite, _| pick.candidates.contains(suite));
+
+        for (suite, weight) in pick.candidates.iter().zip(weights) {
+            *credits.entry(suite.clone()).or_default() += weight;
+        }
+        if let Some(credit) = credits.get_mut(&pick.suite) {
+            *credit -= total;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suite(distribution: &str, release: &str) -> Suite {
+        Suite {
+            distribution: distribution.to_string(),
+            release: Some(release.to_string()),
+        }
+    }
+
+    fn picks(config: &ScheduleConfig, candidates: &[Suite], n: usize) -> Vec<Suite> {
+        let scheduler = FairScheduler::default();
+        (0..n)
+            .map(|_| {
+                let pick = scheduler.pick(config, candidates).unwrap();
+                scheduler.charge(config, &pick);
+                pick.suite
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_candidates() {
+        let scheduler = FairScheduler::default();
+        assert_eq!(scheduler.pick(&ScheduleConfig::default(), &[]), None);
+    }
+
+    #[test]
+    fn picks_without_a_job_are_not_charged() {
+        let a = suite("debian", "sid");
+        let b = suite("debian", "trixie");
+        let config = ScheduleConfig::default();
+        let candidates = [a.clone(), b.clone()];
+
+        let scheduler = FairScheduler::default();
+        let pick = scheduler.pick(&config, &candidates).unwrap();
+        assert_eq!(pick.suite, a);
+        assert_eq!(scheduler.pick(&config, &candidates).unwrap().suite, a);
+
+        scheduler.charge(&config, &pick);
+        assert_eq!(scheduler.pick(&config, &candidates).unwrap().suite, b);
+    }
+
+    #[test]
+    fn equal_weights_alternate() {
+        let a = suite("debian", "sid");
+        let b = suite("debian", "trixie");
+        let picks = picks(&ScheduleConfig::default(), &[a.clone(), b.clone()], 4);
+        assert_eq!(picks, vec![a.clone(), b.clone(), a, b]);
+    }
+
+    #[test]
+    fn weights_are_respected() {
+        let a = suite("archlinux", "core");
+        let b = suite("debian", "sid");
+        let mut config = ScheduleConfig::default();
+        config.weights.insert("archlinux".to_string(), 3);
+
+        let picks = picks(&config, &[a.clone(), b.clone()], 8);
+        assert_eq!(picks.iter().filter(|s| **s == a).count(), 6);
+        assert_eq!(picks.iter().filter(|s| **s == b).count(), 2);
+    }
+
+    #[test]
+    fn release_weight_overrides_distribution_weight() {
+        let a = suite("debian", "sid");
+        let b = suite("debian", "trixie");
+        let mut config = ScheduleConfig::default();
+        config.weights.insert("debian".to_string(), 0);
+        config.weights.insert("debian/trixie".to_string(), 1);
+
+        let picks = picks(&config, &[a, b.clone()], 3);
+        assert_eq!(picks, vec![b.clone(), b.clone(), b]);
+    }
+
+    #[test]
+    fn zero_weight_is_picked_if_nothing_else_is_available() {
+        let a = suite("debian", "sid");
+        let mut config = ScheduleConfig::default();
+        config.weights.insert("debian".to_string(), 0);
+
+        let scheduler = FairScheduler::default();
+        let pick = scheduler.pick(&config, std::slice::from_ref(&a)).unwrap();
+        assert_eq!(pick.suite, a);
+    }
+}
@@ -1,9 +1,33 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> Integer,
+        name -> Text,
+        key_hash -> Text,
+        scope -> Text,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    artifact_verdicts (id) {
+        id -> Integer,
+        checksum -> Text,
+        script_version -> Text,
+        status -> Text,
+        rebuild_artifact_id -> Nullable<Integer>,
+        origin -> Nullable<Text>,
+        verified_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     attestation_logs (id) {
         id -> Integer,
-        attestation_log -> Binary,
+        attestation_log -> Nullable<Binary>,
+        blob_key -> Nullable<Text>,
     }
 }
 
@@ -16,6 +40,30 @@ diesel::table! {
         version -> Text,
         architecture -> Text,
         artifact_url -> Text,
+        checksum -> Nullable<Text>,
+        parent_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    bisect_steps (id) {
+        id -> Integer,
+        bisect_id -> Integer,
+        variation -> Text,
+        position -> Integer,
+        status -> Nullable<Text>,
+        build_log_id -> Nullable<Integer>,
+        worker_id -> Nullable<Integer>,
+        built_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    bisects (id) {
+        id -> Integer,
+        build_input_id -> Integer,
+        created_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
     }
 }
 
@@ -28,20 +76,94 @@ diesel::table! {
         architecture -> Text,
         retries -> Integer,
         next_retry -> Nullable<Timestamp>,
+        last_failure -> Nullable<Text>,
+        popularity -> Nullable<BigInt>,
+        payload -> Nullable<Text>,
+        sync_import_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    build_durations (id) {
+        id -> Integer,
+        distribution -> Text,
+        name -> Text,
+        architecture -> Text,
+        seconds -> BigInt,
+        measured -> Bool,
     }
 }
 
 diesel::table! {
     build_logs (id) {
         id -> Integer,
-        build_log -> Binary,
+        build_log -> Nullable<Binary>,
+        blob_key -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    cross_rebuilds (id) {
+        id -> Integer,
+        build_input_id -> Integer,
+        worker_id -> Nullable<Integer>,
+        build_log_id -> Integer,
+        build_architecture -> Text,
+        status -> Text,
+        started_at -> Nullable<Timestamp>,
+        built_at -> Timestamp,
     }
 }
 
 diesel::table! {
     diffoscope_logs (id) {
         id -> Integer,
-        diffoscope_log -> Binary,
+        diffoscope_log -> Nullable<Binary>,
+        blob_key -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    package_annotations (id) {
+        id -> Integer,
+        distribution -> Text,
+        name -> Text,
+        bug_url -> Nullable<Text>,
+        note -> Nullable<Text>,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    package_popularity (id) {
+        id -> Integer,
+        distribution -> Text,
+        name -> Text,
+        score -> BigInt,
+    }
+}
+
+diesel::table! {
+    package_tags (id) {
+        id -> Integer,
+        distribution -> Text,
+        name -> Text,
+        tag -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    provisional_rebuilds (id) {
+        id -> Integer,
+        build_input_id -> Integer,
+        worker_id -> Nullable<Integer>,
+        build_log_id -> Integer,
+        status -> Text,
+        started_at -> Nullable<Timestamp>,
+        built_at -> Timestamp,
+        confirmed -> Nullable<Bool>,
+        resolved_at -> Nullable<Timestamp>,
     }
 }
 
@@ -54,6 +176,13 @@ diesel::table! {
         started_at -> Nullable<Timestamp>,
         worker -> Nullable<Integer>,
         last_ping -> Nullable<Timestamp>,
+        phase -> Nullable<Text>,
+        pinned_worker -> Nullable<Integer>,
+        build_architecture -> Nullable<Text>,
+        deadline -> Nullable<Timestamp>,
+        aborted_at -> Nullable<Timestamp>,
+        bisect_step_id -> Nullable<Integer>,
+        reason -> Nullable<Text>,
     }
 }
 
@@ -65,6 +194,9 @@ diesel::table! {
         diffoscope_log_id -> Nullable<Integer>,
         attestation_log_id -> Nullable<Integer>,
         status -> Nullable<Text>,
+        diff_summary -> Nullable<Text>,
+        checksum -> Nullable<Text>,
+        likely_causes -> Text,
     }
 }
 
@@ -76,6 +208,24 @@ diesel::table! {
         built_at -> Nullable<Timestamp>,
         build_log_id -> Integer,
         status -> Nullable<Text>,
+        outcome -> Nullable<Text>,
+        environment_fingerprint -> Nullable<Text>,
+        worker_id -> Nullable<Integer>,
+        downloaded_bytes -> Nullable<BigInt>,
+        reason -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    snapshot_generations (id) {
+        id -> Integer,
+        distribution -> Text,
+        release -> Text,
+        first_synced_at -> Timestamp,
+        expired_at -> Nullable<Timestamp>,
+        good -> Nullable<Integer>,
+        bad -> Nullable<Integer>,
+        unknown -> Nullable<Integer>,
     }
 }
 
@@ -88,7 +238,76 @@ diesel::table! {
         release -> Nullable<Text>,
         component -> Nullable<Text>,
         last_seen -> Timestamp,
-        seen_in_last_sync -> Bool
+        seen_in_last_sync -> Bool,
+        removed_at -> Nullable<Timestamp>,
+        maintainer -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    stats_history (id) {
+        id -> Integer,
+        date -> Date,
+        distribution -> Text,
+        release -> Nullable<Text>,
+        component -> Nullable<Text>,
+        good -> Integer,
+        bad -> Integer,
+        unknown -> Integer,
+        expired -> Integer,
+    }
+}
+
+diesel::table! {
+    status_transitions (id) {
+        id -> Integer,
+        rebuild_id -> Integer,
+        kind -> Text,
+        previous_version -> Text,
+        previous_status -> Text,
+        status -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    subscriptions (id) {
+        id -> Integer,
+        api_key_id -> Integer,
+        name -> Nullable<Text>,
+        maintainer -> Nullable<Text>,
+        distribution -> Nullable<Text>,
+        webhook -> Nullable<Text>,
+        email -> Nullable<Text>,
+        created_at -> Timestamp,
+        confirmation_token_hash -> Nullable<Text>,
+        confirmed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    sync_imports (id) {
+        id -> Integer,
+        distribution -> Text,
+        release -> Nullable<Text>,
+        component -> Nullable<Text>,
+        architecture -> Text,
+        source -> Nullable<Text>,
+        revision -> Text,
+        imported_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sync_revisions (id) {
+        id -> Integer,
+        distribution -> Text,
+        release -> Nullable<Text>,
+        component -> Nullable<Text>,
+        architecture -> Text,
+        revision -> Text,
+        updated_at -> Timestamp,
+        source -> Nullable<Text>,
     }
 }
 
@@ -101,28 +320,82 @@ diesel::table! {
         status -> Nullable<Text>,
         last_ping -> Timestamp,
         online -> Bool,
+        environment -> Nullable<Text>,
+        draining -> Bool,
+        architectures -> Nullable<Text>,
+        previous_key -> Nullable<Text>,
+        previous_key_expires_at -> Nullable<Timestamp>,
+        trusted -> Bool,
+        uuid -> Nullable<Text>,
+        downloaded_bytes -> BigInt,
+        fingerprint -> Nullable<Text>,
+        fingerprint_mismatch_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::table! {
+    worker_tokens (id) {
+        id -> Integer,
+        worker_id -> Integer,
+        token_hash -> Text,
+        capability -> Text,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::joinable!(artifact_verdicts -> rebuild_artifacts (rebuild_artifact_id));
 diesel::joinable!(binary_packages -> build_inputs (build_input_id));
 diesel::joinable!(binary_packages -> source_packages (source_package_id));
+diesel::joinable!(bisect_steps -> bisects (bisect_id));
+diesel::joinable!(bisects -> build_inputs (build_input_id));
 diesel::joinable!(build_inputs -> source_packages (source_package_id));
+diesel::joinable!(build_inputs -> sync_imports (sync_import_id));
+diesel::joinable!(cross_rebuilds -> build_inputs (build_input_id));
+diesel::joinable!(provisional_rebuilds -> build_inputs (build_input_id));
 diesel::joinable!(queue -> build_inputs (build_input_id));
 diesel::joinable!(rebuild_artifacts -> attestation_logs (attestation_log_id));
 diesel::joinable!(rebuild_artifacts -> diffoscope_logs (diffoscope_log_id));
 diesel::joinable!(rebuild_artifacts -> rebuilds (rebuild_id));
 diesel::joinable!(rebuilds -> build_inputs (build_input_id));
 diesel::joinable!(rebuilds -> build_logs (build_log_id));
+diesel::joinable!(status_transitions -> rebuilds (rebuild_id));
+diesel::joinable!(subscriptions -> api_keys (api_key_id));
+diesel::joinable!(worker_tokens -> workers (worker_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    artifact_verdicts,
     attestation_logs,
     binary_packages,
+    bisect_steps,
+    bisects,
     build_inputs,
+    build_durations,
     build_logs,
+    cross_rebuilds,
     diffoscope_logs,
+    package_annotations,
+    package_popularity,
+    package_tags,
+    provisional_rebuilds,
     queue,
     rebuild_artifacts,
     rebuilds,
+    snapshot_generations,
     source_packages,
+    stats_history,
+    status_transitions,
+    subscriptions,
+    sync_imports,
+    sync_revisions,
+    worker_tokens,
     workers,
 );
+
+diesel::allow_columns_to_appear_in_same_group_by_clause!(
+    source_packages::distribution,
+    source_packages::release,
+    source_packages::component,
+    build_inputs::architecture,
+);
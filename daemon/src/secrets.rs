@@ -0,0 +1,21 @@
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+
+/// Api keys and worker tokens are only stored as a hash, a leaked database or backup doesn't reveal any credentials.
+/// The secrets are random enough that a plain sha256 can't be brute forced.
+pub fn hash(secret: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(
+            hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
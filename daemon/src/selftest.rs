@@ -0,0 +1,302 @@
+//! End-to-end check of the queue lifecycle against a throwaway daemon.
+//!
+//! The harness starts the regular http server on an ephemeral port with an in-memory database, imports a scripted
+//! package sync and then acts as a worker that drains the queue: every job is popped, pinged and reported with a
+//! predetermined outcome. It's used by `rebuilderd --selftest` and by the `integration` test suite.
+use crate::attestation;
+use crate::config;
+use crate::db;
+use chrono::Utc;
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::api::Client;
+use rebuilderd_common::api::v1::{
+    ArtifactStatus, BinaryPackageReport, BuildRestApi, BuildStatus, JobAssignment, PackageReport,
+    PackageRestApi, PingJobRequest, PopQueuedJobRequest, QueueRestApi, QueuedJobWithArtifacts,
+    RebuildArtifactReport, RebuildReport, RegisterWorkerRequest, SourcePackageReport,
+    WorkerRestApi,
+};
+use rebuilderd_common::config::ConfigFile;
+use rebuilderd_common::errors::*;
+use std::collections::HashMap;
+
+const DISTRIBUTION: &str = "selftest";
+const ARCHITECTURE: &str = "x86_64";
+const WORKER_NAME: &str = "selftest-worker";
+const PHASE: &str = "build";
+
+/// The scripted sync, every source package is paired with the status the fake worker is going to report for it
+const PACKAGES: &[(&str, BuildStatus)] = &[
+    ("selftest-good", BuildStatus::Good),
+    ("selftest-bad", BuildStatus::Bad),
+    ("selftest-fail", BuildStatus::Fail),
+];
+
+/// Upper bound for the number of jobs the fake worker picks up, protects against a queue that never drains
+const MAX_JOBS: usize = 100;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub packages: usize,
+    pub jobs: usize,
+    pub good: usize,
+    pub bad: usize,
+    pub fail: usize,
+}
+
+fn random_secret() -> String {
+    Alphanumeric.sample_string(&mut rand::rng(), 32)
+}
+
+fn expected_status(name: &str) -> Result<BuildStatus> {
+    PACKAGES
+        .iter()
+        .find(|(package, _)| *package == name)
+        .map(|(_, status)| status.clone())
+        .with_context(|| anyhow!("Daemon assigned a package that was never synced: {name:?}"))
+}
+
+fn package_report() -> PackageReport {
+    let packages = PACKAGES
+        .iter()
+        .map(|(name, _)| SourcePackageReport {
+            name: name.to_string(),
+            version: "1".to_string(),
+            url: format!("https://selftest.invalid/{name}-1.buildinfo"),
+            payload: None,
+            maintainer: None,
+            artifacts: vec![BinaryPackageReport {
+                name: name.to_string(),
+                version: "1".to_string(),
+                architecture: ARCHITECTURE.to_string(),
+                url: format!("https://selftest.invalid/{name}-1.pkg"),
+                checksum: None,
+            }],
+        })
+        .collect();
+
+    PackageReport {
+        distribution: DISTRIBUTION.to_string(),
+        release: None,
+        component: None,
+        architecture: ARCHITECTURE.to_string(),
+        source: None,
+        packages,
+    }
+}
+
+fn rebuild_report(job: &QueuedJobWithArtifacts, status: BuildStatus) -> RebuildReport {
+    let artifact_status = match status {
+        BuildStatus::Good => ArtifactStatus::Good,
+        BuildStatus::Bad => ArtifactStatus::Bad,
+        _ => ArtifactStatus::Unknown,
+    };
+
+    let artifacts = if status == BuildStatus::Fail {
+        vec![]
+    } else {
+        job.artifacts
+            .iter()
+            .map(|artifact| RebuildArtifactReport {
+                name: artifact.name.clone(),
+                diffoscope: (artifact_status == ArtifactStatus::Bad)
+                    .then(|| b"selftest diffoscope".to_vec()),
+                attestation: None,
+                status: artifact_status.clone(),
+                diff_summary: None,
+                checksum: None,
+                content: None,
+            })
+            .collect()
+    };
+
+    RebuildReport {
+        queue_id: job.job.id,
+        built_at: Utc::now().naive_utc(),
+        build_log: format!("selftest build log for {}\n", job.job.name).into_bytes(),
+        status,
+        artifacts,
+        downloaded_bytes: None,
+    }
+}
+
+fn job_request() -> PopQueuedJobRequest {
+    PopQueuedJobRequest {
+        supported_backends: vec![DISTRIBUTION.to_string()],
+        architecture: ARCHITECTURE.to_string(),
+        supported_architectures: vec![ARCHITECTURE.to_string()],
+        mirrors: HashMap::new(),
+        cross_architectures: Vec::new(),
+    }
+}
+
+/// Drain the queue like a worker would, until the daemon has nothing left to assign
+async fn drain(client: &Client, summary: &mut Summary) -> Result<()> {
+    loop {
+        let job = match client.request_work(job_request()).await? {
+            JobAssignment::Nothing => return Ok(()),
+            JobAssignment::Rebuild(job) => job,
+        };
+
+        summary.jobs += 1;
+        if summary.jobs > MAX_JOBS {
+            bail!("Queue did not drain after {MAX_JOBS} jobs");
+        }
+
+        debug!("Selftest picked up job #{}: {:?}", job.job.id, job.job.name);
+        let status = expected_status(&job.job.name)?;
+
+        client
+            .ping_job_with_phase(
+                job.job.id,
+                PingJobRequest {
+                    phase: Some(PHASE.to_string()),
+                    extend: None,
+                },
+            )
+            .await
+            .context("Failed to ping job")?;
+
+        let queue = client.get_queued_jobs(None, None, None).await?;
+        let queued = queue
+            .records
+            .iter()
+            .find(|queued| queued.id == job.job.id)
+            .context("Job that is being built is missing from queue")?;
+        if queued.phase.as_deref() != Some(PHASE) {
+            bail!(
+                "Ping did not update the phase of job #{}: {:?}",
+                job.job.id,
+                queued.phase
+            );
+        }
+
+        client
+            .submit_build_report(rebuild_report(&job, status))
+            .await
+            .context("Failed to submit build report")?;
+    }
+}
+
+async fn verify(client: &Client, summary: &mut Summary) -> Result<()> {
+    let queue = client.get_queued_jobs(None, None, None).await?;
+    if queue.total != 0 {
+        bail!("Expected an empty queue, {} jobs are left", queue.total);
+    }
+
+    let builds = client.get_builds(None, None, None).await?;
+    for build in builds.records {
+        let expected = expected_status(&build.name)?;
+        if build.status.as_ref() != Some(&expected) {
+            bail!(
+                "Build of {:?} was recorded as {:?}, expected {:?}",
+                build.name,
+                build.status,
+                expected
+            );
+        }
+
+        match expected {
+            BuildStatus::Good => summary.good += 1,
+            BuildStatus::Bad => summary.bad += 1,
+            _ => summary.fail += 1,
+        }
+    }
+
+    if summary.good + summary.bad + summary.fail != PACKAGES.len() {
+        bail!(
+            "Expected {} builds to be recorded, found {}",
+            PACKAGES.len(),
+            summary.good + summary.bad + summary.fail
+        );
+    }
+
+    Ok(())
+}
+
+/// Run the full pop, ping and report lifecycle against a daemon with an in-memory database
+pub async fn run() -> Result<Summary> {
+    // every connection to this url shares the same database, it lives for as long as one connection is open
+    let url = format!(
+        "file:rebuilderd-selftest-{}?mode=memory&cache=shared",
+        random_secret()
+    );
+    let _keepalive = db::setup(&url)?;
+    let pool = db::setup_pool(&url, true, None)?;
+
+    let cookie = random_secret();
+    let signup_secret = random_secret();
+
+    let mut config_file = ConfigFile::default();
+    config_file.worker.signup_secret = Some(signup_secret.clone());
+    // reported failures are not retried, this keeps the queue state predictable
+    config_file.schedule.max_retries = Some(0);
+    config_file.schedule.initial_delay = Some(0);
+
+    let mut config = config::from_struct(config_file, cookie.clone())?;
+    config.bind_addr = "127.0.0.1:0".to_string();
+
+    let (privkey, _) = attestation::keygen_pem()?;
+    let privkey = attestation::pem_to_privkeys(privkey.as_bytes())?
+        .next()
+        .context("Generated key is empty")??;
+
+    let (server, address) = crate::build_server(pool, config, privkey)?;
+    let handle = server.handle();
+    let server = actix_web::rt::spawn(server);
+    debug!("Selftest daemon is listening on {address}");
+
+    let mut client = Client::new(ConfigFile::default(), Some(format!("http://{address}")))?;
+    client.auth_cookie(cookie);
+    client.signup_secret(signup_secret);
+    client.worker_key(random_secret());
+
+    let result = async {
+        let mut summary = Summary::default();
+
+        client
+            .submit_package_report(&package_report())
+            .await
+            .context("Failed to submit package sync")?;
+        summary.packages = PACKAGES.len();
+
+        client
+            .register_worker(RegisterWorkerRequest {
+                name: WORKER_NAME.to_string(),
+                environment: None,
+                uuid: None,
+            })
+            .await
+            .context("Failed to register worker")?;
+
+        drain(&client, &mut summary).await?;
+        verify(&client, &mut summary).await?;
+
+        Ok(summary)
+    }
+    .await;
+
+    handle.stop(true).await;
+    server.await??;
+
+    result
+}
+
+#[cfg(all(test, feature = "integration"))]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_selftest_drains_queue() {
+        let summary = run().await.unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                packages: 3,
+                jobs: 3,
+                good: 1,
+                bad: 1,
+                fail: 1,
+            }
+        );
+    }
+}
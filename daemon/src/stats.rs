@@ -0,0 +1,261 @@
+//! Daily snapshots of the reproducibility of each suite.
+//!
+//! The dashboard only knows the current state, the snapshots are kept so trends can be plotted over months. A
+//! snapshot is taken every hour and replaces the one of the same day, so the last one of a day is what's kept.
+use crate::models::NewStatsHistory;
+use crate::schema::*;
+use aliases::*;
+use chrono::NaiveDate;
+use diesel::dsl::{case_when, count, sum};
+use diesel::sql_types::Integer;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl,
+    RunQueryDsl, SqliteConnection, SqliteExpressionMethods,
+};
+use rebuilderd_common::errors::*;
+use std::collections::BTreeMap;
+
+mod aliases {
+    diesel::alias!(crate::schema::rebuilds as r1: RebuildsAlias1, crate::schema::rebuilds as r2: RebuildsAlias2);
+}
+
+/// distribution, release and component
+pub type Suite = (String, Option<String>, Option<String>);
+
+/// distribution, release, component, good, unknown and the number of build inputs
+type SuiteCounts = (
+    String,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+    i64,
+);
+
+fn into_snapshot(date: NaiveDate, counts: SuiteCounts) -> NewStatsHistory {
+    let (distribution, release, component, good, unknown, total) = counts;
+    let good = good.unwrap_or_default();
+    let unknown = unknown.unwrap_or_default();
+
+    NewStatsHistory {
+        date,
+        distribution,
+        release,
+        component,
+        good: good as i32,
+        // everything that was rebuilt but didn't reproduce, including failed and flaky builds
+        bad: (total - good - unknown) as i32,
+        unknown: unknown as i32,
+        expired: 0,
+    }
+}
+
+/// Expired jobs are gone from the queue once they're counted, so the counts of earlier snapshots of the same day are
+/// carried over. Suites that only had expired jobs still get a row.
+fn add_expired(
+    snapshots: &mut Vec<NewStatsHistory>,
+    date: NaiveDate,
+    mut expired: BTreeMap<Suite, i64>,
+) {
+    for snapshot in snapshots.iter_mut() {
+        let suite = (
+            snapshot.distribution.clone(),
+            snapshot.release.clone(),
+            snapshot.component.clone(),
+        );
+        snapshot.expired = expired.remove(&suite).unwrap_or_default() as i32;
+    }
+
+    for ((distribution, release, component), expired) in expired {
+        snapshots.push(NewStatsHistory {
+            date,
+            distribution,
+            release,
+            component,
+            good: 0,
+            bad: 0,
+            unknown: 0,
+            expired: expired as i32,
+        });
+    }
+}
+
+/// Count the status of the latest rebuild of every build input that is still part of a suite
+pub fn snapshot(
+    connection: &mut SqliteConnection,
+    date: NaiveDate,
+) -> Result<Vec<NewStatsHistory>> {
+    let counts = source_packages::table
+        .inner_join(build_inputs::table)
+        .left_join(r1.on(r1.field(rebuilds::build_input_id).is(build_inputs::id)))
+        .left_join(
+            r2.on(r2.field(rebuilds::build_input_id).is(build_inputs::id).and(
+                r1.field(rebuilds::built_at)
+                    .lt(r2.field(rebuilds::built_at))
+                    .or(r1.fields(
+                        rebuilds::built_at
+                            .eq(r2.field(rebuilds::built_at))
+                            .and(r1.field(rebuilds::id).lt(r2.field(rebuilds::id))),
+                    )),
+            )),
+        )
+        .filter(r2.field(rebuilds::id).is_null())
+        .filter(source_packages::seen_in_last_sync.is(true))
+        .group_by((
+            source_packages::distribution,
+            source_packages::release,
+            source_packages::component,
+        ))
+        .select((
+            source_packages::distribution,
+            source_packages::release,
+            source_packages::component,
+            sum(
+                case_when::<_, _, Integer>(r1.field(rebuilds::status).nullable().eq("GOOD"), 1)
+                    .otherwise(0),
+            ),
+            sum(case_when::<_, _, Integer>(
+                r1.field(rebuilds::status)
+                    .nullable()
+                    .eq("UNKWN")
+                    .or(r1.field(rebuilds::status).nullable().eq("DOWNLOAD_FAILED"))
+                    .or(r1.field(rebuilds::status).nullable().is_null()),
+                1,
+            )
+            .otherwise(0)),
+            count(build_inputs::id),
+        ))
+        .load::<SuiteCounts>(connection)?;
+
+    Ok(counts
+        .into_iter()
+        .map(|counts| into_snapshot(date, counts))
+        .collect())
+}
+
+/// The good, bad and unknown counts of all components of a release, eg. of a snapshot generation
+pub fn count_release(
+    snapshots: &[NewStatsHistory],
+    distribution: &str,
+    release: &str,
+) -> (i32, i32, i32) {
+    snapshots
+        .iter()
+        .filter(|snapshot| {
+            snapshot.distribution == distribution && snapshot.release.as_deref() == Some(release)
+        })
+        .fold((0, 0, 0), |(good, bad, unknown), snapshot| {
+            (
+                good + snapshot.good,
+                bad + snapshot.bad,
+                unknown + snapshot.unknown,
+            )
+        })
+}
+
+/// Take a snapshot of the given day, `expired` are the jobs that were dropped from the queue since the last one
+pub fn record(
+    connection: &mut SqliteConnection,
+    date: NaiveDate,
+    expired: &BTreeMap<Suite, i64>,
+) -> Result<()> {
+    let mut snapshots = snapshot(connection, date)?;
+
+    let mut total = expired.clone();
+    let counted = stats_history::table
+        .filter(stats_history::date.eq(date))
+        .select((
+            stats_history::distribution,
+            stats_history::release,
+            stats_history::component,
+            stats_history::expired,
+        ))
+        .load::<(String, Option<String>, Option<String>, i32)>(connection)?;
+    for (distribution, release, component, expired) in counted {
+        if expired > 0 {
+            *total.entry((distribution, release, component)).or_default() += i64::from(expired);
+        }
+    }
+    add_expired(&mut snapshots, date, total);
+
+    NewStatsHistory::replace_day(date, &snapshots, connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn test_into_snapshot() {
+        let date = NaiveDate::from_ymd_opt(2025, 12, 28).unwrap();
+        let snapshot = into_snapshot(
+            date,
+            (
+                "archlinux".to_string(),
+                None,
+                Some("core".to_string()),
+                Some(7),
+                Some(2),
+                12,
+            ),
+        );
+        assert_eq!(
+            snapshot,
+            NewStatsHistory {
+                date,
+                distribution: "archlinux".to_string(),
+                release: None,
+                component: Some("core".to_string()),
+                good: 7,
+                bad: 3,
+                unknown: 2,
+                expired: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_add_expired() {
+        let date = NaiveDate::from_ymd_opt(2025, 12, 28).unwrap();
+        let core = ("archlinux".to_string(), None, Some("core".to_string()));
+        let extra = ("archlinux".to_string(), None, Some("extra".to_string()));
+
+        let mut snapshots = vec![into_snapshot(
+            date,
+            (
+                core.0.clone(),
+                core.1.clone(),
+                core.2.clone(),
+                Some(1),
+                None,
+                1,
+            ),
+        )];
+        add_expired(
+            &mut snapshots,
+            date,
+            BTreeMap::from([(core, 3), (extra, 2)]),
+        );
+
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].good, 1);
+        assert_eq!(snapshots[0].expired, 3);
+        assert_eq!(snapshots[1].component.as_deref(), Some("extra"));
+        assert_eq!(snapshots[1].good, 0);
+        assert_eq!(snapshots[1].expired, 2);
+    }
+
+    #[test]
+    fn test_record_empty_database() {
+        let mut connection = db::setup(":memory:").unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 12, 28).unwrap();
+        record(&mut connection, date, &BTreeMap::new()).unwrap();
+
+        let count = stats_history::table
+            .count()
+            .get_result::<i64>(&mut connection)
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::storage::BlobStore;
+use async_trait::async_trait;
+use rebuilderd_common::errors::*;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: PathBuf) -> Self {
+        LocalStore { root }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!("Refusing to use invalid blob key: {key:?}");
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| anyhow!("Failed to create directory: {parent:?}"))?;
+        }
+
+        // write to a temporary file first so readers never observe a partial blob
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, data)
+            .await
+            .with_context(|| anyhow!("Failed to write blob: {temp:?}"))?;
+        fs::rename(&temp, &path)
+            .await
+            .with_context(|| anyhow!("Failed to move blob into place: {path:?}"))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path_for(key)?;
+        let data = fs::read(&path)
+            .await
+            .with_context(|| anyhow!("Failed to read blob: {path:?}"))?;
+        Ok(data)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path_for(key)?;
+        match fs::remove_file(&path).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(err).with_context(|| anyhow!("Failed to remove blob: {path:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_keys_escaping_the_root() {
+        let store = LocalStore::new(PathBuf::from("/var/lib/rebuilderd/blobs"));
+        assert!(store.path_for("../etc/passwd").is_err());
+        assert!(store.path_for("/etc/passwd").is_err());
+        assert!(store.path_for("logs/./abc.zst").is_ok());
+        assert_eq!(
+            store.path_for("logs/abc.zst").unwrap(),
+            PathBuf::from("/var/lib/rebuilderd/blobs/logs/abc.zst")
+        );
+    }
+
+    #[test]
+    fn deletes_blobs() {
+        let dir = std::env::temp_dir().join(format!("rebuilderd-blobs-{}", std::process::id()));
+        let store = LocalStore::new(dir.clone());
+
+        tokio_test::block_on(store.put("logs/abc.zst", b"hello".to_vec())).unwrap();
+        tokio_test::block_on(store.delete("logs/abc.zst")).unwrap();
+        assert!(tokio_test::block_on(store.get("logs/abc.zst")).is_err());
+
+        // the blob is already gone
+        tokio_test::block_on(store.delete("logs/abc.zst")).unwrap();
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
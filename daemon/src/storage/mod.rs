@@ -0,0 +1,123 @@
+use async_trait::async_trait;
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::config::{StorageBackend, StorageConfig};
+use rebuilderd_common::errors::*;
+use std::sync::Arc;
+
+pub mod local;
+#[cfg(feature = "s3")]
+pub mod s3;
+
+/// An external place to keep large blobs (build logs, diffoscope output, attestations) outside the database.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Removing a blob that doesn't exist is not an error
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// A blob that was either kept inline or written to the configured blob store.
+pub struct StoredBlob {
+    pub data: Option<Vec<u8>>,
+    pub blob_key: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct Storage {
+    store: Option<Arc<dyn BlobStore>>,
+}
+
+impl Storage {
+    pub fn from_config(config: &StorageConfig) -> Result<Self> {
+        let store: Option<Arc<dyn BlobStore>> = match config.backend() {
+            StorageBackend::Database => None,
+            StorageBackend::Local => {
+                let path = config
+                    .path
+                    .clone()
+                    .context("Local blob storage requires `storage.path` to be set")?;
+                Some(Arc::new(local::LocalStore::new(path)))
+            }
+            #[cfg(feature = "s3")]
+            StorageBackend::S3 => Some(Arc::new(s3::S3Store::new(config)?)),
+            #[cfg(not(feature = "s3"))]
+            StorageBackend::S3 => {
+                bail!(
+                    "S3 blob storage is configured, but rebuilderd was built without the `s3` feature"
+                )
+            }
+        };
+
+        Ok(Storage { store })
+    }
+
+    /// Write a blob to the external store, if one is configured. Otherwise the data is handed back to be stored
+    /// in the database.
+    pub async fn store(&self, kind: &str, data: Vec<u8>) -> Result<StoredBlob> {
+        let Some(store) = &self.store else {
+            return Ok(StoredBlob {
+                data: Some(data),
+                blob_key: None,
+            });
+        };
+
+        let key = format!(
+            "{kind}/{}.zst",
+            Alphanumeric.sample_string(&mut rand::rng(), 32)
+        );
+        store.put(&key, data).await?;
+
+        Ok(StoredBlob {
+            data: None,
+            blob_key: Some(key),
+        })
+    }
+
+    /// Replace the content of a blob that is already in the external store.
+    pub async fn replace(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let store = self.configured_store(key)?;
+        store.put(key, data).await
+    }
+
+    /// Remove blobs again that no database row is going to reference, eg. because writing the row failed. Failures
+    /// are only logged, a leftover blob takes up space but doesn't break anything.
+    pub async fn discard(&self, keys: &[String]) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        for key in keys {
+            if let Err(err) = store.delete(key).await {
+                warn!("Failed to remove unreferenced blob {key:?}: {err:#}");
+            }
+        }
+    }
+
+    /// Resolve a database row to the actual blob, fetching it from the external store if necessary.
+    pub async fn load(
+        &self,
+        data: Option<Vec<u8>>,
+        blob_key: Option<String>,
+    ) -> Result<Option<Vec<u8>>> {
+        if let Some(data) = data {
+            return Ok(Some(data));
+        }
+
+        if let Some(key) = blob_key {
+            let store = self.configured_store(&key)?;
+            return store.get(&key).await.map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn configured_store(&self, key: &str) -> Result<&Arc<dyn BlobStore>> {
+        self.store.as_ref().ok_or_else(|| {
+            format_err!(
+                "Blob {key:?} is kept in external storage, but no storage backend is configured"
+            )
+        })
+    }
+}
@@ -0,0 +1,103 @@
+use crate::storage::BlobStore;
+use async_trait::async_trait;
+use rebuilderd_common::config::StorageConfig;
+use rebuilderd_common::errors::*;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+
+pub struct S3Store {
+    bucket: Box<Bucket>,
+}
+
+impl S3Store {
+    pub fn new(config: &StorageConfig) -> Result<Self> {
+        let name = config
+            .bucket
+            .as_deref()
+            .context("S3 blob storage requires `storage.bucket` to be set")?;
+
+        let region = config
+            .region
+            .clone()
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let region = if let Some(endpoint) = &config.endpoint {
+            Region::Custom {
+                region,
+                endpoint: endpoint.clone(),
+            }
+        } else {
+            region.parse().context("Failed to parse S3 region")?
+        };
+
+        // fall back to the usual AWS_* environment variables and profiles if no keys are configured
+        let credentials = if config.access_key.is_some() || config.secret_key.is_some() {
+            Credentials::new(
+                config.access_key.as_deref(),
+                config.secret_key.as_deref(),
+                None,
+                None,
+                None,
+            )
+        } else {
+            Credentials::default()
+        }
+        .context("Failed to setup S3 credentials")?;
+
+        let mut bucket =
+            Bucket::new(name, region, credentials).context("Failed to setup S3 bucket")?;
+        if config.path_style.unwrap_or(false) {
+            bucket = bucket.with_path_style();
+        }
+
+        Ok(S3Store { bucket })
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3Store {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let response = self
+            .bucket
+            .put_object(key, &data)
+            .await
+            .with_context(|| anyhow!("Failed to upload blob: {key:?}"))?;
+
+        let status = response.status_code();
+        if !(200..300).contains(&status) {
+            bail!("Failed to upload blob {key:?}: S3 returned status {status}");
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let response = self
+            .bucket
+            .get_object(key)
+            .await
+            .with_context(|| anyhow!("Failed to download blob: {key:?}"))?;
+
+        let status = response.status_code();
+        if !(200..300).contains(&status) {
+            bail!("Failed to download blob {key:?}: S3 returned status {status}");
+        }
+
+        Ok(response.bytes().to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let response = self
+            .bucket
+            .delete_object(key)
+            .await
+            .with_context(|| anyhow!("Failed to delete blob: {key:?}"))?;
+
+        // deleting a missing object succeeds with 204 as well
+        let status = response.status_code();
+        if !(200..300).contains(&status) {
+            bail!("Failed to delete blob {key:?}: S3 returned status {status}");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::schema::binary_packages;
+use data_encoding::HEXLOWER;
+use diesel::sql_types::{Nullable, Text};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection, define_sql_function};
+use rebuilderd_common::api::v1::ArtifactStatus;
+use rebuilderd_common::errors::*;
+use rebuilderd_common::utils::zstd_decompress;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+define_sql_function! {
+    /// Checksums are compared case insensitively, syncs record them as they are published
+    fn lower(checksum: Nullable<Text>) -> Nullable<Text>
+}
+
+/// The published checksums of the artifacts of a build input, keyed by artifact name. Artifacts the sync didn't record
+/// a checksum for are left out.
+pub fn published_checksums(
+    connection: &mut SqliteConnection,
+    build_input_id: i32,
+) -> Result<HashMap<String, String>> {
+    let checksums = binary_packages::table
+        .filter(binary_packages::build_input_id.eq(build_input_id))
+        .filter(binary_packages::checksum.is_not_null())
+        .select((binary_packages::name, binary_packages::checksum))
+        .load::<(String, Option<String>)>(connection)?
+        .into_iter()
+        .filter_map(|(name, checksum)| Some((name, checksum?)))
+        .collect();
+    Ok(checksums)
+}
+
+/// Hash an artifact that was uploaded with a rebuild report. The checksum a worker reports is easily copied from the
+/// published one, only the artifact itself shows what was rebuilt.
+pub async fn uploaded_checksum(content: &[u8]) -> Result<String> {
+    let content = zstd_decompress(content)
+        .await
+        .context("Failed to decompress uploaded artifact")?;
+    Ok(HEXLOWER.encode(&Sha256::digest(&content)))
+}
+
+/// Whether an artifact a worker claims is GOOD can't be identical to the published one. Without a published checksum
+/// there's nothing to compare with, the worker is taken at its word.
+pub fn contradicts(
+    published: Option<&str>,
+    status: &ArtifactStatus,
+    rebuilt: Option<&str>,
+) -> bool {
+    if *status != ArtifactStatus::Good {
+        return false;
+    }
+    let Some(published) = published else {
+        return false;
+    };
+    !rebuilt.is_some_and(|rebuilt| rebuilt.eq_ignore_ascii_case(published))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rebuilderd_common::utils::zstd_compress;
+
+    const CHECKSUM: &str = "4355a46b19d348dc2f57c046f8ef63d4538ebb936000f3c9ee954a27460dd865";
+
+    #[test]
+    fn test_matching_checksum() {
+        assert!(!contradicts(
+            Some(CHECKSUM),
+            &ArtifactStatus::Good,
+            Some(CHECKSUM)
+        ));
+        assert!(!contradicts(
+            Some(CHECKSUM),
+            &ArtifactStatus::Good,
+            Some(&CHECKSUM.to_uppercase())
+        ));
+    }
+
+    #[test]
+    fn test_mismatching_checksum() {
+        let other = CHECKSUM.replace('4', "5");
+        assert!(contradicts(
+            Some(CHECKSUM),
+            &ArtifactStatus::Good,
+            Some(&other)
+        ));
+        // a GOOD artifact without a checksum can't be checked, that's not good enough either
+        assert!(contradicts(Some(CHECKSUM), &ArtifactStatus::Good, None));
+    }
+
+    #[actix_web::test]
+    async fn test_uploaded_checksum() {
+        let content = zstd_compress(b"foo").await.unwrap();
+        assert_eq!(
+            uploaded_checksum(&content).await.unwrap(),
+            "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae"
+        );
+        assert!(uploaded_checksum(b"foo").await.is_err());
+    }
+
+    #[test]
+    fn test_nothing_to_contradict() {
+        assert!(!contradicts(None, &ArtifactStatus::Good, None));
+        assert!(!contradicts(
+            Some(CHECKSUM),
+            &ArtifactStatus::Bad,
+            Some("0000")
+        ));
+        assert!(!contradicts(Some(CHECKSUM), &ArtifactStatus::Bad, None));
+    }
+}
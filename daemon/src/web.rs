@@ -1,7 +1,46 @@
-pub use actix_web::web::{Data, Json, JsonConfig, Path, Query, post, resource};
+use actix_web::HttpResponse;
+use actix_web::error::{InternalError, JsonPayloadError};
+pub use actix_web::web::{Data, Json, JsonConfig, Path, Query, ReqData, post, resource};
 use rebuilderd_common::errors;
 use std::fmt;
 
+/// Configure the json extractor of a route. Bodies above the limit are rejected with 413 and a message that names
+/// the limit, instead of failing like a body that couldn't be parsed.
+pub fn json_config(limit: usize) -> JsonConfig {
+    JsonConfig::default()
+        .limit(limit)
+        .error_handler(|err, req| {
+            let message = match &err {
+                JsonPayloadError::OverflowKnownLength { length, limit } => {
+                    format!("Request body of {length} bytes exceeds the limit of {limit} bytes")
+                }
+                JsonPayloadError::Overflow { limit } => {
+                    format!("Request body exceeds the limit of {limit} bytes")
+                }
+                _ => return err.into(),
+            };
+            errors::warn!("Rejecting request to {:?}: {message}", req.path());
+            let res = HttpResponse::PayloadTooLarge().body(message);
+            InternalError::from_response(err, res).into()
+        })
+}
+
+/// Escape text for use in html, both as element content and in quoted attributes
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 #[derive(Debug)]
 pub struct Error {
     err: rebuilderd_common::errors::Error,
@@ -17,9 +56,9 @@ impl fmt::Display for Error {
 
 impl actix_web::error::ResponseError for Error {}
 
+/// The error is logged by the `request_id` middleware, along with the id of the failed request
 impl From<errors::Error> for Error {
     fn from(err: errors::Error) -> Error {
-        errors::error!("error occurred in http handler: {err:#}");
         Error { err }
     }
 }
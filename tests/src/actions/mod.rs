@@ -1,14 +1,19 @@
 use crate::data::*;
 use rebuilderd_common::api::Client;
 use rebuilderd_common::api::v1::{
-    BuildRestApi, BuildStatus, JobAssignment, PackageRestApi, PopQueuedJobRequest, QueueJobRequest,
-    QueueRestApi, QueuedJobWithArtifacts, RegisterWorkerRequest, WorkerRestApi,
+    ApiKeyScope, BuildRestApi, BuildStatus, IssueApiKeyRequest, IssueWorkerTokenRequest,
+    IssuedApiKey, IssuedWorkerToken, JobAssignment, KeyRestApi, PackageRestApi,
+    PopQueuedJobRequest, QueueJobRequest, QueueRestApi, QueuedJobWithArtifacts,
+    RegisterWorkerRequest, WorkerCapability, WorkerRestApi,
 };
+use std::collections::HashMap;
 
 pub async fn register_worker(client: &Client) {
     client
         .register_worker(RegisterWorkerRequest {
             name: DUMMY_WORKER.to_string(),
+            environment: None,
+            uuid: None,
         })
         .await
         .unwrap();
@@ -18,6 +23,8 @@ pub async fn register_other_worker(client: &Client) {
     client
         .register_worker(RegisterWorkerRequest {
             name: DUMMY_OTHER_WORKER.to_string(),
+            environment: None,
+            uuid: None,
         })
         .await
         .unwrap();
@@ -63,6 +70,8 @@ pub async fn pick_up_job(client: &Client) -> QueuedJobWithArtifacts {
                 DUMMY_ARCHITECTURE.to_string(),
                 DUMMY_OTHER_ARCHITECTURE.to_string(),
             ],
+            mirrors: HashMap::new(),
+            cross_architectures: Vec::new(),
         })
         .await
         .unwrap()
@@ -118,7 +127,75 @@ pub async fn request_rebuild_of_all_bad_packages(client: &Client) {
             architecture: None,
             status: Some(BuildStatus::Bad),
             priority: None,
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await
         .unwrap();
 }
+
+pub async fn request_rebuild_of_all_packages(client: &Client) {
+    client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: None,
+            version: None,
+            architecture: None,
+            status: None,
+            priority: None,
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
+        })
+        .await
+        .unwrap();
+}
+
+pub async fn request_cross_rebuild_of_all_packages(client: &Client, build_architecture: &str) {
+    client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: None,
+            version: None,
+            architecture: None,
+            status: None,
+            priority: None,
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: Some(build_architecture.to_string()),
+            reason: None,
+        })
+        .await
+        .unwrap();
+}
+
+pub async fn issue_api_key(client: &Client, scope: ApiKeyScope) -> IssuedApiKey {
+    client
+        .issue_api_key(IssueApiKeyRequest {
+            name: DUMMY_API_KEY_NAME.to_string(),
+            scope,
+        })
+        .await
+        .unwrap()
+}
+
+pub async fn issue_worker_token(
+    client: &Client,
+    worker_id: i32,
+    capability: WorkerCapability,
+) -> IssuedWorkerToken {
+    client
+        .issue_worker_token(worker_id, IssueWorkerTokenRequest { capability })
+        .await
+        .unwrap()
+}
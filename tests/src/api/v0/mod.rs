@@ -0,0 +1,6 @@
+mod pkgs;
+mod queue;
+mod schema;
+mod subscriptions;
+mod worker;
+mod workers;
@@ -0,0 +1,195 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use flate2::read::GzDecoder;
+use rebuilderd::api::v0::MAX_STATUS_BATCH_NAMES;
+use rebuilderd_common::api::v0::{PkgStatusBatch, Status};
+use rebuilderd_common::api::v1::{BinaryPackageCorrection, PackageRestApi};
+use rebuilderd_common::api::{AUTH_COOKIE_HEADER, ApiError, REQUEST_ID_HEADER};
+use rebuilderd_common::config::ConfigFile;
+use rebuilderd_common::http::{self, OutboundConfig, RequestBuilder, StatusCode};
+use rstest::rstest;
+use std::io::Read;
+
+fn get_pkg_bundle(isolated_server: &IsolatedServer, name: &str) -> RequestBuilder {
+    let url = format!(
+        "{}/api/v0/pkgs/{name}/bundle.tar.gz",
+        isolated_server.client.endpoint().trim_end_matches('/')
+    );
+
+    http::client(&OutboundConfig::default()).unwrap().get(url)
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn bundles_latest_rebuild_of_package(mut isolated_server: IsolatedServer) {
+    setup::single_bad_rebuild(&isolated_server.client).await;
+
+    let bundle = get_pkg_bundle(&isolated_server, DUMMY_BINARY_PACKAGE)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    let mut archive = tar::Archive::new(GzDecoder::new(&bundle[..]));
+    let mut build_log = String::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.path().unwrap().ends_with("build.log") {
+            entry.read_to_string(&mut build_log).unwrap();
+        }
+    }
+    assert_eq!(DUMMY_BUILD_LOG, build_log);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn bundle_of_package_without_rebuild_is_not_found(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let res = get_pkg_bundle(&isolated_server, DUMMY_BINARY_PACKAGE)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(404, res.status().as_u16());
+    let request_id = res.headers()[REQUEST_ID_HEADER]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let err = res.json::<ApiError>().await.unwrap();
+    assert_eq!(request_id, err.request_id);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn corrects_package_on_v0(
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let url = format!(
+        "{}/api/v0/pkgs/1",
+        isolated_server.client.endpoint().trim_end_matches('/')
+    );
+    http::client(&OutboundConfig::default())
+        .unwrap()
+        .patch(url)
+        .header(AUTH_COOKIE_HEADER, config_file.auth.cookie.unwrap())
+        .json(&BinaryPackageCorrection {
+            version: Some("1.1".to_string()),
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let package = isolated_server.client.get_binary_package(1).await.unwrap();
+    assert_eq!(package.version, "1.1");
+    assert_eq!(package.name, DUMMY_BINARY_PACKAGE);
+
+    isolated_server.shutdown().await;
+}
+
+fn public_listener() -> ConfigFile {
+    config_with(|config| config.http.public_bind_addr = Some("127.0.0.200:0".to_string()))
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn status_batch_is_served_on_public_listener(
+    #[with(public_listener())] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let _config_file = config_file;
+    setup::single_good_rebuild(&isolated_server.client).await;
+
+    let url = format!(
+        "{}/api/v0/pkgs/status-batch?pkg={DUMMY_BINARY_PACKAGE}&pkg=does-not-exist&distro={DUMMY_DISTRIBUTION}",
+        isolated_server.public_endpoint().unwrap()
+    );
+    let batch = http::client(&OutboundConfig::default())
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<PkgStatusBatch>()
+        .await
+        .unwrap();
+
+    assert!(!batch.all_good);
+    assert_eq!(1, batch.packages.len());
+    assert_eq!(DUMMY_BINARY_PACKAGE, batch.packages[0].name);
+    assert_eq!(Status::Good, batch.packages[0].status);
+    assert_eq!(vec!["does-not-exist".to_string()], batch.unknown);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn status_batch_rejects_too_many_names(
+    #[with(public_listener())] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let _config_file = config_file;
+
+    let query = (0..=MAX_STATUS_BATCH_NAMES)
+        .map(|i| format!("pkg={i}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    let url = format!(
+        "{}/api/v0/pkgs/status-batch?{query}",
+        isolated_server.public_endpoint().unwrap()
+    );
+    let res = http::client(&OutboundConfig::default())
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn status_batch_post_is_rejected_on_public_listener(
+    #[with(public_listener())] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let _config_file = config_file;
+
+    let url = format!(
+        "{}/api/v0/pkgs/status-batch",
+        isolated_server.public_endpoint().unwrap()
+    );
+    let res = http::client(&OutboundConfig::default())
+        .unwrap()
+        .post(url)
+        .json(&serde_json::json!({ "names": [DUMMY_BINARY_PACKAGE] }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+
+    isolated_server.shutdown().await;
+}
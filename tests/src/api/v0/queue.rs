@@ -0,0 +1,58 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v0::QueuePosition;
+use rebuilderd_common::http::{self, OutboundConfig};
+use rstest::rstest;
+
+async fn get_queue_position(isolated_server: &IsolatedServer, query: &str) -> Vec<QueuePosition> {
+    let url = format!(
+        "{}/api/v0/queue/position?{query}",
+        isolated_server.client.endpoint().trim_end_matches('/')
+    );
+
+    http::client(&OutboundConfig::default())
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_position_of_queued_package(mut isolated_server: IsolatedServer) {
+    setup::build_ready_database(&isolated_server.client).await;
+
+    let results =
+        get_queue_position(&isolated_server, &format!("name={DUMMY_SOURCE_PACKAGE}")).await;
+
+    assert_eq!(1, results.len());
+    assert_eq!(DUMMY_SOURCE_PACKAGE, results[0].item.pkgbase.name);
+    assert_eq!(0, results[0].ahead);
+    assert_eq!(0, results[0].tied);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn filters_by_suite(mut isolated_server: IsolatedServer) {
+    setup::build_ready_database(&isolated_server.client).await;
+
+    let results = get_queue_position(
+        &isolated_server,
+        &format!("name={DUMMY_SOURCE_PACKAGE}&suite=does-not-exist"),
+    )
+    .await;
+
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,49 @@
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::http::{self, OutboundConfig, StatusCode};
+use rstest::rstest;
+use serde_json::json;
+
+async fn get(isolated_server: &IsolatedServer, path: &str) -> http::Response {
+    let url = format!(
+        "{}/api/v0/schema{path}",
+        isolated_server.client.endpoint().trim_end_matches('/')
+    );
+
+    http::client(&OutboundConfig::default())
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn lists_schemas_on_v0(mut isolated_server: IsolatedServer) {
+    let names = get(&isolated_server, "")
+        .await
+        .json::<Vec<String>>()
+        .await
+        .unwrap();
+
+    assert!(names.contains(&"package-report".to_string()));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_schema_on_v0(mut isolated_server: IsolatedServer) {
+    let schema = get(&isolated_server, "/package-report")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(json!("PackageReport"), schema["title"]);
+
+    let res = get(&isolated_server, "/suite-import").await;
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,73 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::AUTH_COOKIE_HEADER;
+use rebuilderd_common::api::v1::{
+    ApiKeyScope, Subscription, SubscriptionConfirmation, SubscriptionRequest,
+};
+use rebuilderd_common::http::{self, OutboundConfig, StatusCode};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn manages_subscriptions_on_v0(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+
+    let url = format!(
+        "{}/api/v0/subscriptions",
+        client.endpoint().trim_end_matches('/')
+    );
+    let http = http::client(&OutboundConfig::default()).unwrap();
+
+    let subscription = http
+        .post(&url)
+        .header(AUTH_COOKIE_HEADER, &issued.key)
+        .json(&SubscriptionRequest {
+            name: Some("xz".to_string()),
+            webhook: Some("https://192.0.2.1/hook".to_string()),
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Subscription>()
+        .await
+        .unwrap();
+    assert_eq!(Some("xz"), subscription.name.as_deref());
+
+    let subscriptions = http
+        .get(&url)
+        .header(AUTH_COOKIE_HEADER, &issued.key)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<Subscription>>()
+        .await
+        .unwrap();
+    assert_eq!(vec![subscription], subscriptions);
+
+    let res = http
+        .delete(format!("{url}/{}", subscriptions[0].id))
+        .header(AUTH_COOKIE_HEADER, &issued.key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+    let res = http
+        .post(format!("{url}/confirm"))
+        .json(&SubscriptionConfirmation {
+            token: "does-not-exist".to_string(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+    isolated_server.shutdown().await;
+}
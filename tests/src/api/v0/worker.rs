@@ -0,0 +1,100 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::WORKER_KEY_HEADER;
+use rebuilderd_common::api::v1::{BuildRestApi, BuildStatus, JobAssignment, PopQueuedJobRequest};
+use rebuilderd_common::config::ConfigFile;
+use rebuilderd_common::http::{self, OutboundConfig};
+use rebuilderd_common::utils::zstd_compress;
+use rstest::rstest;
+use std::collections::HashMap;
+
+const WORKER_KEY: &str = "v0-worker-key";
+
+#[rstest]
+#[tokio::test]
+pub async fn pops_job_on_v0(
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let _config_file = config_file;
+    isolated_server.client.worker_key(WORKER_KEY);
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let url = format!(
+        "{}/api/v0/worker/jobs/pop",
+        client.endpoint().trim_end_matches('/')
+    );
+    let request = PopQueuedJobRequest {
+        supported_backends: vec![DUMMY_BACKEND.to_string()],
+        architecture: DUMMY_ARCHITECTURE.to_string(),
+        supported_architectures: vec![DUMMY_ARCHITECTURE.to_string()],
+        mirrors: HashMap::new(),
+        cross_architectures: Vec::new(),
+    };
+
+    let http = http::client(&OutboundConfig::default()).unwrap();
+    let res = http.post(&url).json(&request).send().await.unwrap();
+    assert_eq!(403, res.status().as_u16());
+
+    let assignment = http
+        .post(&url)
+        .header(WORKER_KEY_HEADER, WORKER_KEY)
+        .json(&request)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<JobAssignment>()
+        .await
+        .unwrap();
+    let JobAssignment::Rebuild(job) = assignment else {
+        panic!("Expected a job assignment");
+    };
+    assert_eq!(DUMMY_SOURCE_PACKAGE, job.job.name);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn accepts_compressed_report_on_v0(mut isolated_server: IsolatedServer) {
+    isolated_server.client.worker_key(WORKER_KEY);
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    let job = pick_up_job(client).await;
+
+    let report = serde_json::to_vec(&good_rebuild_report(&job)).unwrap();
+    let res = http::client(&OutboundConfig::default())
+        .unwrap()
+        .post(format!(
+            "{}/api/v0/build/report",
+            client.endpoint().trim_end_matches('/')
+        ))
+        .header(WORKER_KEY_HEADER, WORKER_KEY)
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "zstd")
+        .body(zstd_compress(&report).await.unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(204, res.status().as_u16());
+
+    let build = client
+        .get_builds(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Good), build.status);
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,39 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::AUTH_COOKIE_HEADER;
+use rebuilderd_common::api::v1::{JobAborted, QueueRestApi};
+use rebuilderd_common::config::ConfigFile;
+use rebuilderd_common::http::{self, OutboundConfig};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn aborts_build_on_v0(
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    let job = pick_up_job(client).await;
+
+    let url = format!(
+        "{}/api/v0/workers/1/abort",
+        client.endpoint().trim_end_matches('/')
+    );
+    let res = http::client(&OutboundConfig::default())
+        .unwrap()
+        .post(url)
+        .header(AUTH_COOKIE_HEADER, config_file.auth.cookie.unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(204, res.status().as_u16());
+
+    let err = client.ping_job(job.job.id).await.unwrap_err();
+    assert!(err.downcast_ref::<JobAborted>().is_some());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,15 @@
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::QueueRestApi;
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_bisect_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.get_bisect(1).await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,2 @@
+mod get_bisect;
+mod request_bisect;
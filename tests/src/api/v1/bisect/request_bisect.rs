@@ -0,0 +1,152 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::Client;
+use rebuilderd_common::api::v1::{
+    BisectRequest, BisectVariation, BuildRestApi, BuildStatus, PackageRestApi, QueueRestApi,
+    RebuildReport,
+};
+use rstest::rstest;
+
+async fn binary_package_id(client: &Client) -> i32 {
+    client
+        .get_binary_packages(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap()
+        .id
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn steps_are_built_one_after_another(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    report_good_rebuild(client).await;
+
+    let bisect = client
+        .request_bisect(BisectRequest {
+            binary_package_id: binary_package_id(client).await,
+            priority: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(DUMMY_SOURCE_PACKAGE, bisect.name);
+    assert_eq!(None, bisect.finished_at);
+    assert_eq!(
+        BisectVariation::ALL.to_vec(),
+        bisect
+            .steps
+            .iter()
+            .map(|step| step.variation)
+            .collect::<Vec<_>>()
+    );
+    assert!(bisect.steps.iter().all(|step| step.status.is_none()));
+
+    for variation in BisectVariation::ALL {
+        let job = pick_up_job(client).await;
+        assert_eq!(Some(variation), job.job.variation);
+
+        let report = if variation == BisectVariation::Locale {
+            bad_rebuild_report(&job)
+        } else {
+            good_rebuild_report(&job)
+        };
+        client.submit_build_report(report).await.unwrap();
+    }
+
+    let queued = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(0, queued.total);
+
+    let bisect = client.get_bisect(bisect.id).await.unwrap();
+    assert!(bisect.finished_at.is_some());
+    assert_eq!(vec![BisectVariation::Locale], bisect.flips);
+    assert_eq!(Some(BuildStatus::Bad), bisect.steps[3].status);
+
+    // the bisect doesn't touch the verdict of the package
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Good), package.status);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn failed_step_goes_on_with_next_variation(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    report_good_rebuild(client).await;
+
+    let bisect = client
+        .request_bisect(BisectRequest {
+            binary_package_id: binary_package_id(client).await,
+            priority: None,
+        })
+        .await
+        .unwrap();
+
+    let job = pick_up_job(client).await;
+    client
+        .submit_build_report(RebuildReport {
+            status: BuildStatus::Fail,
+            ..good_rebuild_report(&job)
+        })
+        .await
+        .unwrap();
+
+    let job = pick_up_job(client).await;
+    assert_eq!(Some(BisectVariation::BuildPath), job.job.variation);
+
+    let bisect = client.get_bisect(bisect.id).await.unwrap();
+    assert_eq!(Some(BuildStatus::Fail), bisect.steps[0].status);
+    assert!(bisect.flips.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_package_is_already_queued(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let result = client
+        .request_bisect(BisectRequest {
+            binary_package_id: binary_package_id(client).await,
+            priority: None,
+        })
+        .await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_package_is_unknown(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client
+        .request_bisect(BisectRequest {
+            binary_package_id: 1,
+            priority: None,
+        })
+        .await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -3,7 +3,7 @@ use crate::data::*;
 use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
-use rebuilderd_common::api::v1::BuildRestApi;
+use rebuilderd_common::api::v1::{ArtifactDiffSummary, BuildRestApi, DiffCause, LikelyCauses};
 use rstest::rstest;
 
 #[rstest]
@@ -92,3 +92,65 @@ pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_diff_summary_of_bad_artifact(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+    register_worker(client).await;
+
+    let summary = ArtifactDiffSummary {
+        original_size: 1024,
+        rebuilt_size: 1030,
+        first_difference: Some(512),
+        differing_files: vec!["usr/lib/python3/foo/__pycache__/bar.pyc".to_string()],
+        truncated: false,
+        modulo_compression: false,
+    };
+
+    let job = pick_up_job(client).await;
+    let mut report = bad_rebuild_report(&job);
+    report.artifacts[0].diff_summary = Some(summary.clone());
+    client.submit_build_report(report).await.unwrap();
+
+    let results = client.get_build_artifacts(1).await.unwrap();
+
+    assert_eq!(1, results.len());
+    assert_eq!(Some(summary), results[0].diff_summary);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn labels_likely_causes_of_bad_artifact(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+    register_worker(client).await;
+
+    let diffoscope = "--- a/foo.tar.gz
++++ b/foo.tar.gz
+├── filetype from file(1)
+│ @@ -1 +1 @@
+│ -gzip compressed data, last modified: Mon Jan  1 00:00:00 2024, from Unix
+│ +gzip compressed data, last modified: Tue Jan  2 00:00:00 2024, from Unix
+";
+
+    let job = pick_up_job(client).await;
+    let mut report = bad_rebuild_report(&job);
+    report.artifacts[0].diffoscope = Some(diffoscope.as_bytes().to_vec());
+    client.submit_build_report(report).await.unwrap();
+
+    let results = client.get_build_artifacts(1).await.unwrap();
+
+    assert_eq!(1, results.len());
+    assert_eq!(
+        LikelyCauses(vec![DiffCause::GzipTimestamp]),
+        results[0].likely_causes
+    );
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,132 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use flate2::read::GzDecoder;
+use rebuilderd_common::api::v1::{BuildRestApi, PackageRestApi};
+use rstest::rstest;
+use std::collections::BTreeMap;
+use std::io::Read;
+
+fn unpack(bundle: &[u8]) -> BTreeMap<String, String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bundle));
+    let mut files = BTreeMap::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        files.insert(path, content);
+    }
+    files
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_result_for_empty_database(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.get_build_bundle(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn bundles_log_and_diffoscope_of_bad_build(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_bad_rebuild(client).await;
+
+    let bundle = client.get_build_bundle(1).await.unwrap();
+    let files = unpack(&bundle);
+
+    let prefix = format!(
+        "{}-{}-build-1",
+        DUMMY_SOURCE_PACKAGE, DUMMY_SOURCE_PACKAGE_VERSION
+    );
+    assert_eq!(
+        files
+            .get(&format!("{prefix}/build.log"))
+            .map(String::as_str),
+        Some(DUMMY_BUILD_LOG)
+    );
+    assert_eq!(
+        files
+            .get(&format!("{prefix}/diffoscope/{DUMMY_BINARY_PACKAGE}.txt"))
+            .map(String::as_str),
+        Some(DUMMY_DIFFOSCOPE)
+    );
+
+    let manifest =
+        serde_json::from_str::<serde_json::Value>(&files[&format!("{prefix}/build.json")]).unwrap();
+    assert_eq!(manifest["input_url"], DUMMY_SOURCE_PACKAGE_URL);
+    assert_eq!(manifest["artifacts"][0]["url"], DUMMY_BINARY_PACKAGE_URL);
+    assert_eq!(manifest["artifacts"][0]["status"], "BAD");
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn includes_attestation_of_good_build(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild_with_signed_attestation(client).await;
+
+    let bundle = client.get_build_bundle(1).await.unwrap();
+    let files = unpack(&bundle);
+
+    assert!(
+        files
+            .keys()
+            .any(|path| path.ends_with(&format!("/attestation/{DUMMY_BINARY_PACKAGE}.json")))
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn includes_published_and_rebuilt_checksums(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    let mut package_report = single_package_report();
+    package_report.packages[0].artifacts[0].checksum =
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&package_report).await.unwrap();
+
+    let rebuilt = DUMMY_BINARY_PACKAGE_CHECKSUM.replace('2', "3");
+    let job = pick_up_job(client).await;
+    let mut report = bad_rebuild_report(&job);
+    report.artifacts[0].checksum = Some(rebuilt.clone());
+    client.submit_build_report(report).await.unwrap();
+
+    let bundle = client.get_build_bundle(1).await.unwrap();
+    let files = unpack(&bundle);
+
+    let prefix = format!(
+        "{}-{}-build-1",
+        DUMMY_SOURCE_PACKAGE, DUMMY_SOURCE_PACKAGE_VERSION
+    );
+    assert_eq!(
+        files[&format!("{prefix}/SHA256SUMS")],
+        format!(
+            "{DUMMY_BINARY_PACKAGE_CHECKSUM}  published/{DUMMY_BINARY_PACKAGE}\n{rebuilt}  rebuilt/{DUMMY_BINARY_PACKAGE}\n"
+        )
+    );
+
+    let manifest =
+        serde_json::from_str::<serde_json::Value>(&files[&format!("{prefix}/build.json")]).unwrap();
+    assert_eq!(
+        manifest["artifacts"][0]["published_checksum"],
+        DUMMY_BINARY_PACKAGE_CHECKSUM
+    );
+    assert_eq!(manifest["artifacts"][0]["rebuilt_checksum"], rebuilt);
+
+    isolated_server.shutdown().await;
+}
@@ -3,7 +3,9 @@ use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
 use rebuilderd_common::api::v1::BuildRestApi;
+use rebuilderd_common::config::{ConfigFile, StorageBackend};
 use rstest::rstest;
+use tempfile::TempDir;
 
 #[rstest]
 #[tokio::test]
@@ -63,3 +65,31 @@ pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_result_from_local_blob_storage(
+    blob_dir: TempDir,
+    #[with(config_with(|config| {
+        config.storage.backend = Some(StorageBackend::Local);
+        config.storage.path = Some(blob_dir.path().to_path_buf());
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let _config_file = config_file;
+    let client = &isolated_server.client;
+
+    setup::single_bad_rebuild(client).await;
+
+    let result = client.get_build_log(1).await.unwrap();
+
+    assert_eq!(DUMMY_BUILD_LOG, result);
+
+    let stored_logs = std::fs::read_dir(blob_dir.path().join("build-logs"))
+        .unwrap()
+        .count();
+    assert_eq!(1, stored_logs);
+
+    isolated_server.shutdown().await;
+}
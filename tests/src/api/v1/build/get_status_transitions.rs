@@ -0,0 +1,160 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::Client;
+use rebuilderd_common::api::v1::{
+    BuildRestApi, BuildStatus, PackageRestApi, StatusTransition, TransitionFilter, TransitionKind,
+};
+use rebuilderd_common::config::ConfigFile;
+use rstest::rstest;
+
+async fn transitions(client: &Client, kind: Option<TransitionKind>) -> Vec<StatusTransition> {
+    client
+        .get_status_transitions(None, None, Some(&TransitionFilter { kind }))
+        .await
+        .unwrap()
+        .records
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_results_for_first_rebuild(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+
+    assert!(transitions(client, None).await.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_results_for_unchanged_status(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_good_rebuild(client).await;
+
+    assert!(transitions(client, None).await.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn records_regression_of_same_version(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_bad_rebuild(client).await;
+
+    let mut results = transitions(client, None).await;
+    assert_eq!(1, results.len());
+
+    let transition = results.pop().unwrap();
+    assert_eq!(TransitionKind::Regression, transition.kind);
+    assert_eq!(DUMMY_SOURCE_PACKAGE, transition.name);
+    assert_eq!(DUMMY_SOURCE_PACKAGE_VERSION, transition.version);
+    assert_eq!(DUMMY_SOURCE_PACKAGE_VERSION, transition.previous_version);
+    assert_eq!(BuildStatus::Good, transition.previous_status);
+    assert_eq!(BuildStatus::Bad, transition.status);
+    assert_eq!(Some(DUMMY_WORKER.to_string()), transition.worker);
+
+    let build = client.get_build(transition.build_id).await.unwrap();
+    assert_eq!(Some(BuildStatus::Bad), build.status);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn records_new_version_that_is_unreproducible(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+
+    let mut report = single_package_report();
+    report.packages[0].version = "2".to_string();
+    // a build input with the same url would share the result of the previous version
+    report.packages[0].url = "https://placeholder.org/foo-2.buildinfo.txt".to_string();
+    report.packages[0].artifacts[0].version = "2".to_string();
+    client.submit_package_report(&report).await.unwrap();
+    report_bad_rebuild(client).await;
+
+    let mut results = transitions(client, None).await;
+    assert_eq!(1, results.len());
+
+    let transition = results.pop().unwrap();
+    assert_eq!(TransitionKind::NewVersionUnreproducible, transition.kind);
+    assert_eq!("2", transition.version);
+    assert_eq!(DUMMY_SOURCE_PACKAGE_VERSION, transition.previous_version);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn filters_by_kind(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_bad_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_good_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_bad_rebuild(client).await;
+
+    let results = transitions(client, None).await;
+    assert_eq!(2, results.len());
+    assert_eq!(TransitionKind::Fixed, results[0].kind);
+    assert_eq!(TransitionKind::Regression, results[1].kind);
+
+    let results = transitions(client, Some(TransitionKind::Fixed)).await;
+    assert_eq!(1, results.len());
+    assert_eq!(TransitionKind::Fixed, results[0].kind);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn flaky_results_are_skipped_when_comparing(
+    #[with(config_with(|config| config.schedule.flaky_threshold = Some(2)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    setup::single_good_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_bad_rebuild(client).await;
+
+    // GOOD, BAD, GOOD flipped twice, the package is FLAKY until it is GOOD three times in a row
+    for _ in 0..3 {
+        request_rebuild_of_all_packages(client).await;
+        report_good_rebuild(client).await;
+    }
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Good), package.status);
+
+    let results = transitions(client, None).await;
+    assert_eq!(
+        vec![TransitionKind::Regression, TransitionKind::Fixed],
+        results.iter().map(|t| t.kind).collect::<Vec<_>>()
+    );
+    assert_eq!(BuildStatus::Bad, results[1].previous_status);
+
+    isolated_server.shutdown().await;
+}
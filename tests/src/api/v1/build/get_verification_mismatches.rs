@@ -0,0 +1,122 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::Client;
+use rebuilderd_common::api::v1::{
+    ArtifactStatus, BuildRestApi, OriginFilter, PackageRestApi, Page,
+};
+use rstest::rstest;
+
+async fn good_rebuild_with_checksum(client: &Client, checksum: &str) {
+    register_worker(client).await;
+    let mut package_report = single_package_report();
+    package_report.packages[0].artifacts[0].checksum =
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&package_report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+    let mut report = good_rebuild_report(&job);
+    report.artifacts[0].checksum = Some(checksum.to_string());
+    client.submit_build_report(report).await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_results_for_good_rebuild_without_published_checksum(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+
+    let results = client
+        .get_verification_mismatches(None, None)
+        .await
+        .unwrap()
+        .records;
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_results_for_matching_checksum(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    good_rebuild_with_checksum(client, DUMMY_BINARY_PACKAGE_CHECKSUM).await;
+
+    let results = client
+        .get_verification_mismatches(None, None)
+        .await
+        .unwrap()
+        .records;
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_good_artifact_with_mismatching_checksum(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let rebuilt = DUMMY_BINARY_PACKAGE_CHECKSUM.replace('2', "3");
+    good_rebuild_with_checksum(client, &rebuilt).await;
+
+    // without verification configured the claim of the worker is recorded as is
+    let package = client
+        .get_binary_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+
+    let mut results = client
+        .get_verification_mismatches(None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(1, results.len());
+
+    let mismatch = results.pop().unwrap();
+    assert_eq!(DUMMY_BINARY_PACKAGE, mismatch.artifact);
+    assert_eq!(DUMMY_SOURCE_PACKAGE, mismatch.name);
+    assert_eq!(DUMMY_DISTRIBUTION, mismatch.distribution);
+    assert_eq!(DUMMY_BINARY_PACKAGE_CHECKSUM, mismatch.published_checksum);
+    assert_eq!(Some(rebuilt), mismatch.rebuilt_checksum);
+    assert!(mismatch.worker.is_some());
+
+    let page = Page {
+        limit: None,
+        before: None,
+        after: Some(mismatch.artifact_id),
+        sort: None,
+        direction: None,
+    };
+    let results = client
+        .get_verification_mismatches(Some(&page), None)
+        .await
+        .unwrap();
+    assert_eq!(1, results.total);
+    assert!(results.records.is_empty());
+
+    let other = OriginFilter {
+        distribution: Some(DUMMY_OTHER_DISTRIBUTION.to_string()),
+        release: None,
+        component: None,
+        architecture: None,
+    };
+    let results = client
+        .get_verification_mismatches(None, Some(&other))
+        .await
+        .unwrap()
+        .records;
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
@@ -3,6 +3,9 @@ mod get_build_artifact;
 mod get_build_artifact_attestation;
 mod get_build_artifact_diffoscope;
 mod get_build_artifacts;
+mod get_build_bundle;
 mod get_build_log;
 mod get_builds;
+mod get_status_transitions;
+mod get_verification_mismatches;
 mod submit_rebuild_report;
@@ -1,4 +1,7 @@
-use crate::actions::{import_single_package, pick_up_job, register_worker, report_bad_rebuild};
+use crate::actions::{
+    import_single_package, pick_up_job, register_worker, report_bad_rebuild,
+    request_cross_rebuild_of_all_packages, request_rebuild_of_all_packages,
+};
 use crate::assertions::assert_job_matches_package;
 use crate::data::*;
 use crate::fixtures::server::IsolatedServer;
@@ -6,9 +9,11 @@ use crate::fixtures::*;
 use crate::setup;
 use chrono::Utc;
 use rebuilderd_common::api::v1::{
-    ArtifactStatus, BuildRestApi, BuildStatus, PackageRestApi, Priority, QueueRestApi,
+    ArtifactStatus, BuildRestApi, BuildStatus, JobAssignment, PackageRestApi, PopQueuedJobRequest,
+    Priority, QueueRestApi, RebuildReport, WorkerRestApi,
 };
 use rebuilderd_common::config::ConfigFile;
+use rebuilderd_common::utils::zstd_compress;
 use rstest::rstest;
 
 #[rstest]
@@ -47,6 +52,39 @@ pub async fn can_report_failed_rebuild(mut isolated_server: IsolatedServer) {
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn download_failure_is_recorded_and_requeued(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+    client
+        .submit_build_report(download_failed_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::DownloadFailed), package.status);
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(1, jobs.len());
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn source_package_is_marked_failed_after_failed_report(
@@ -242,7 +280,7 @@ pub async fn requeued_job_after_bad_report_has_correct_data(mut isolated_server:
 #[rstest]
 #[tokio::test]
 pub async fn package_is_not_requeued_if_max_retries_is_exceeded(
-    #[with(None, Some(1), None)] config_file: ConfigFile,
+    #[with(config_with(|config| config.schedule.max_retries = Some(1)))] config_file: ConfigFile,
     #[with(config_file.clone())] mut isolated_server: IsolatedServer,
 ) {
     let client = &isolated_server.client;
@@ -263,6 +301,91 @@ pub async fn package_is_not_requeued_if_max_retries_is_exceeded(
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn source_package_is_marked_flaky_after_alternating_reports(
+    #[with(config_with(|config| config.schedule.flaky_threshold = Some(2)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    setup::single_flaky_rebuild(client).await;
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    assert_eq!(Some(BuildStatus::Flaky), package.status);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn flaky_package_is_cleared_after_consistent_reports(
+    #[with(config_with(|config| config.schedule.flaky_threshold = Some(2)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    setup::single_flaky_rebuild(client).await;
+
+    // the flaky result was BAD, two more BAD results make threshold + 1 consistent results in a row
+    request_rebuild_of_all_packages(client).await;
+    report_bad_rebuild(client).await;
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Flaky), package.status);
+
+    request_rebuild_of_all_packages(client).await;
+    report_bad_rebuild(client).await;
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Bad), package.status);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn package_is_not_marked_flaky_without_threshold(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_flaky_rebuild(client).await;
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    assert_eq!(Some(BuildStatus::Bad), package.status);
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn can_report_good_rebuild(mut isolated_server: IsolatedServer) {
@@ -370,3 +493,228 @@ pub async fn can_report_good_rebuild_with_unsigned_attestation(
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn cross_build_is_recorded_apart_from_native_status(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    report_bad_rebuild(client).await;
+
+    request_cross_rebuild_of_all_packages(client, DUMMY_OTHER_ARCHITECTURE).await;
+    let job = client
+        .request_work(PopQueuedJobRequest {
+            supported_architectures: vec![DUMMY_OTHER_ARCHITECTURE.to_string()],
+            cross_architectures: vec![DUMMY_ARCHITECTURE.to_string()],
+            ..job_request()
+        })
+        .await
+        .unwrap();
+    let JobAssignment::Rebuild(job) = job else {
+        panic!("Expected a job to be assigned");
+    };
+    client
+        .submit_build_report(good_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Bad), package.status);
+
+    let cross = client
+        .get_source_package_cross_rebuilds(package.id)
+        .await
+        .unwrap();
+    assert_eq!(1, cross.len());
+    assert_eq!(BuildStatus::Good, cross[0].status);
+    assert_eq!(DUMMY_ARCHITECTURE, cross[0].architecture);
+    assert_eq!(DUMMY_OTHER_ARCHITECTURE, cross[0].build_architecture);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn downloaded_bytes_are_accounted_per_build_and_worker(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+    client
+        .submit_build_report(RebuildReport {
+            downloaded_bytes: Some(1024),
+            ..bad_rebuild_report(&job)
+        })
+        .await
+        .unwrap();
+
+    let build = client
+        .get_builds(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some(1024), build.downloaded_bytes);
+
+    let worker = client
+        .get_workers(None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(1024, worker.downloaded_bytes);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn good_report_with_mismatching_checksum_is_recorded_as_bad(
+    #[with(config_with(|config| {
+        config.verification.distributions = vec![DUMMY_DISTRIBUTION.to_string()];
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    let mut package_report = single_package_report();
+    package_report.packages[0].artifacts[0].checksum =
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&package_report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+    assert!(job.upload_artifacts);
+    let mut report = good_rebuild_report(&job);
+    // the reported checksum is copied from the published one, the uploaded artifact differs
+    report.artifacts[0].checksum = Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    report.artifacts[0].content = Some(zstd_compress(b"bar").await.unwrap());
+    client.submit_build_report(report).await.unwrap();
+
+    let build = client
+        .get_builds(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Bad), build.status);
+
+    let artifacts = client.get_build_artifacts(build.id).await.unwrap();
+    assert_eq!(Some(ArtifactStatus::Bad), artifacts[0].status);
+
+    let package = client
+        .get_binary_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(ArtifactStatus::Bad), package.status);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn good_report_with_matching_checksum_is_recorded_as_good(
+    #[with(config_with(|config| {
+        config.verification.distributions = vec![DUMMY_DISTRIBUTION.to_string()];
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    let mut package_report = single_package_report();
+    package_report.packages[0].artifacts[0].checksum =
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&package_report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+    let mut report = good_rebuild_report(&job);
+    // the dummy checksum is the sha256 of the artifact name
+    report.artifacts[0].content = Some(
+        zstd_compress(DUMMY_BINARY_PACKAGE.as_bytes())
+            .await
+            .unwrap(),
+    );
+    client.submit_build_report(report).await.unwrap();
+
+    let package = client
+        .get_binary_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+
+    let build = client
+        .get_builds(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    let artifacts = client.get_build_artifacts(build.id).await.unwrap();
+    assert_eq!(
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string()),
+        artifacts[0].checksum
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn good_report_without_uploaded_artifact_is_recorded_as_bad(
+    #[with(config_with(|config| {
+        config.verification.distributions = vec![DUMMY_DISTRIBUTION.to_string()];
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    let mut package_report = single_package_report();
+    package_report.packages[0].artifacts[0].checksum =
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&package_report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+    let mut report = good_rebuild_report(&job);
+    report.artifacts[0].checksum = Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_build_report(report).await.unwrap();
+
+    let package = client
+        .get_binary_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(ArtifactStatus::Bad), package.status);
+
+    isolated_server.shutdown().await;
+}
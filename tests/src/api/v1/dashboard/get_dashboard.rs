@@ -2,6 +2,7 @@ use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
 use rebuilderd_common::api::v1::DashboardRestApi;
+use rebuilderd_common::config::ConfigFile;
 use rstest::rstest;
 
 #[rstest]
@@ -177,3 +178,26 @@ pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_correct_sums_for_database_with_flaky_package(
+    #[with(config_with(|config| config.schedule.flaky_threshold = Some(2)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    setup::single_flaky_rebuild(client).await;
+
+    let result = client.get_dashboard(None).await.unwrap();
+
+    assert_eq!(0, result.rebuilds.bad);
+    assert_eq!(0, result.rebuilds.fail);
+    assert_eq!(1, result.rebuilds.flaky);
+    assert_eq!(0, result.rebuilds.good);
+    assert_eq!(0, result.rebuilds.unknown);
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,80 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{ApiKeyScope, KeyRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_results_for_empty_database(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let results = client.get_api_keys(None).await.unwrap().records;
+
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_issued_key_without_secret(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::Queue).await;
+
+    let results = client.get_api_keys(None).await.unwrap().records;
+
+    assert_eq!(1, results.len());
+    assert_eq!(issued.id, results[0].id);
+    assert_eq!(issued.name, results[0].name);
+    assert_eq!(ApiKeyScope::Queue, results[0].scope);
+    assert_eq!(None, results[0].revoked_at);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    // zero out key
+    client.auth_cookie("");
+    let result = client.get_api_keys(None).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_scoped_key_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::Queue).await;
+
+    client.auth_cookie(issued.key);
+    let result = client.get_api_keys(None).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn succeeds_if_read_only_key_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+
+    client.auth_cookie(issued.key);
+    let results = client.get_api_keys(None).await.unwrap().records;
+
+    assert_eq!(1, results.len());
+    assert_eq!(ApiKeyScope::ReadOnly, results[0].scope);
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,166 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{
+    ApiKeyScope, IssueApiKeyRequest, KeyRestApi, PackageRestApi, Priority, QueueJobRequest,
+    QueueRestApi,
+};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn can_issue_key(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let issued = client
+        .issue_api_key(IssueApiKeyRequest {
+            name: DUMMY_API_KEY_NAME.to_string(),
+            scope: ApiKeyScope::Sync,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(DUMMY_API_KEY_NAME, issued.name);
+    assert_eq!(ApiKeyScope::Sync, issued.scope);
+    assert!(!issued.key.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    // zero out key
+    client.auth_cookie("");
+    let result = client
+        .issue_api_key(IssueApiKeyRequest {
+            name: DUMMY_API_KEY_NAME.to_string(),
+            scope: ApiKeyScope::Admin,
+        })
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn sync_key_can_submit_package_reports(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::Sync).await;
+
+    client.auth_cookie(issued.key);
+    let result = client.submit_package_report(&single_package_report()).await;
+
+    assert!(result.is_ok());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn sync_key_can_not_drop_queued_jobs(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_single_package(client).await;
+    let issued = issue_api_key(client, ApiKeyScope::Sync).await;
+
+    client.auth_cookie(issued.key);
+    let result = client.drop_queued_jobs(None, None).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn admin_key_can_drop_queued_jobs(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_single_package(client).await;
+    let issued = issue_api_key(client, ApiKeyScope::Admin).await;
+
+    client.auth_cookie(issued.key);
+    client.drop_queued_jobs(None, None).await.unwrap();
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    assert!(jobs.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+fn rebuild_everything() -> QueueJobRequest {
+    QueueJobRequest {
+        distribution: None,
+        release: None,
+        component: None,
+        name: None,
+        version: None,
+        architecture: None,
+        status: None,
+        priority: Some(Priority::default()),
+        built_with: None,
+        built_before: None,
+        binary_name: None,
+        build_architecture: None,
+        reason: None,
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn operator_key_can_request_rebuilds(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_single_package(client).await;
+    let issued = issue_api_key(client, ApiKeyScope::Operator).await;
+
+    client.auth_cookie(issued.key);
+    client.request_rebuild(rebuild_everything()).await.unwrap();
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn operator_key_can_not_drop_queued_jobs(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_single_package(client).await;
+    let issued = issue_api_key(client, ApiKeyScope::Operator).await;
+
+    client.auth_cookie(issued.key);
+    let result = client.drop_queued_jobs(None, None).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn read_only_key_can_export_queue(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_single_package(client).await;
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+
+    client.auth_cookie(issued.key);
+    client.export_queue().await.unwrap();
+    let result = client.request_rebuild(rebuild_everything()).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
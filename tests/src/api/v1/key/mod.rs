@@ -0,0 +1,3 @@
+mod get_api_keys;
+mod issue_api_key;
+mod revoke_api_key;
@@ -0,0 +1,56 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{ApiKeyScope, KeyRestApi, PackageRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn can_revoke_key(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::Sync).await;
+
+    client.revoke_api_key(issued.id).await.unwrap();
+
+    let key = client
+        .get_api_keys(None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+
+    assert!(key.revoked_at.is_some());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn revoked_key_is_rejected(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::Sync).await;
+    client.revoke_api_key(issued.id).await.unwrap();
+
+    client.auth_cookie(issued.key);
+    let result = client.submit_package_report(&single_package_report()).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_404_for_unknown_key(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.revoke_api_key(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,86 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use chrono::Duration;
+use rebuilderd_common::api::v1::{MetaRestApi, PackageRestApi};
+use rebuilderd_common::config::ConfigFile;
+use rstest::rstest;
+use std::collections::HashMap;
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_generations_for_regular_distribution(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_imported_package(client).await;
+
+    let results = client
+        .get_snapshot_generations(DUMMY_DISTRIBUTION)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_synced_generation_of_snapshot_distribution(
+    #[with(config_with(|config| {
+        config.schedule.snapshot_ttl_days = HashMap::from([(DUMMY_DISTRIBUTION.to_string(), 7)]);
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    setup::single_imported_package(client).await;
+    // syncing the generation again doesn't extend its ttl
+    setup::single_imported_package(client).await;
+
+    let results = client
+        .get_snapshot_generations(DUMMY_DISTRIBUTION)
+        .await
+        .unwrap();
+    assert_eq!(1, results.len());
+
+    let generation = &results[0];
+    assert_eq!(DUMMY_RELEASE, generation.release);
+    assert_eq!(
+        Some(generation.first_synced_at + Duration::days(7)),
+        generation.expires_at
+    );
+    assert_eq!(None, generation.expired_at);
+    assert_eq!(0, generation.good);
+    assert_eq!(1, generation.unknown);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn rejects_snapshot_sync_without_release(
+    #[with(config_with(|config| {
+        config.schedule.snapshot_ttl_days = HashMap::from([(DUMMY_DISTRIBUTION.to_string(), 7)]);
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    let mut report = single_package_report();
+    report.release = None;
+    let result = client.submit_package_report(&report).await;
+    assert!(result.is_err());
+
+    let results = client
+        .get_snapshot_generations(DUMMY_DISTRIBUTION)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
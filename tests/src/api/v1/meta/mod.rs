@@ -6,3 +6,4 @@ mod get_distribution_release_components;
 mod get_distribution_releases;
 mod get_distributions;
 mod get_public_key;
+mod get_snapshot_generations;
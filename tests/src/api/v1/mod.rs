@@ -1,6 +1,10 @@
+mod bisect;
 mod build;
 mod dashboard;
+mod key;
 mod meta;
 mod package;
 mod queue;
+mod schema;
+mod subscription;
 mod worker;
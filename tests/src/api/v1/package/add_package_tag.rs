@@ -0,0 +1,104 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{PackageRestApi, PackageTagFilter};
+use rstest::rstest;
+
+const DUMMY_TAG: &str = "critical";
+
+#[rstest]
+#[tokio::test]
+pub async fn can_tag_package(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    isolated_server
+        .client
+        .add_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await
+        .unwrap();
+
+    let tags = isolated_server.client.get_package_tags(None).await.unwrap();
+
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].distribution, DUMMY_DISTRIBUTION);
+    assert_eq!(tags[0].name, DUMMY_SOURCE_PACKAGE);
+    assert_eq!(tags[0].tag, DUMMY_TAG);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn adding_existing_tag_is_not_an_error(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    for _ in 0..2 {
+        isolated_server
+            .client
+            .add_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+            .await
+            .unwrap();
+    }
+
+    let tags = isolated_server
+        .client
+        .get_package_tags(Some(&PackageTagFilter {
+            tag: Some(DUMMY_TAG.to_string()),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+    assert_eq!(tags.len(), 1);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_for_unknown_package(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .add_package_tag(DUMMY_OTHER_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_for_invalid_tag(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .add_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, "not a tag")
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    setup::single_imported_package(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client
+        .add_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
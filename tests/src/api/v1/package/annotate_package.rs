@@ -0,0 +1,180 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{PackageAnnotation, PackageRestApi};
+use rstest::rstest;
+
+const DUMMY_BUG_URL: &str = "https://bugs.example.com/1234";
+const DUMMY_NOTE: &str = "embeds build path";
+
+fn dummy_annotation() -> PackageAnnotation {
+    PackageAnnotation {
+        bug_url: Some(DUMMY_BUG_URL.to_string()),
+        note: Some(DUMMY_NOTE.to_string()),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn annotation_is_included_in_package_records(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    isolated_server
+        .client
+        .annotate_package(
+            DUMMY_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &dummy_annotation(),
+        )
+        .await
+        .unwrap();
+
+    let source_package = isolated_server.client.get_source_package(1).await.unwrap();
+
+    assert_eq!(source_package.bug_url.as_deref(), Some(DUMMY_BUG_URL));
+    assert_eq!(source_package.note.as_deref(), Some(DUMMY_NOTE));
+
+    let binary_packages = isolated_server
+        .client
+        .get_binary_packages(None, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(binary_packages.total, 1);
+    assert_eq!(
+        binary_packages.records[0].bug_url.as_deref(),
+        Some(DUMMY_BUG_URL)
+    );
+    assert_eq!(binary_packages.records[0].note.as_deref(), Some(DUMMY_NOTE));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn annotating_again_replaces_annotation(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    isolated_server
+        .client
+        .annotate_package(
+            DUMMY_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &dummy_annotation(),
+        )
+        .await
+        .unwrap();
+
+    isolated_server
+        .client
+        .annotate_package(
+            DUMMY_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &PackageAnnotation {
+                bug_url: None,
+                note: Some("fixed upstream".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+
+    let source_package = isolated_server.client.get_source_package(1).await.unwrap();
+
+    assert_eq!(source_package.bug_url, None);
+    assert_eq!(source_package.note.as_deref(), Some("fixed upstream"));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_remove_annotation(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    isolated_server
+        .client
+        .annotate_package(
+            DUMMY_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &dummy_annotation(),
+        )
+        .await
+        .unwrap();
+
+    isolated_server
+        .client
+        .remove_package_annotation(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE)
+        .await
+        .unwrap();
+
+    let source_package = isolated_server.client.get_source_package(1).await.unwrap();
+
+    assert_eq!(source_package.bug_url, None);
+    assert_eq!(source_package.note, None);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_for_unknown_package(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .annotate_package(
+            DUMMY_OTHER_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &dummy_annotation(),
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_for_invalid_bug_url(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .annotate_package(
+            DUMMY_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &PackageAnnotation {
+                bug_url: Some("javascript:alert(1)".to_string()),
+                note: None,
+            },
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    setup::single_imported_package(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client
+        .annotate_package(
+            DUMMY_DISTRIBUTION,
+            DUMMY_SOURCE_PACKAGE,
+            &dummy_annotation(),
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,134 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{ArtifactStatus, BinaryPackageCorrection, PackageRestApi};
+use rstest::rstest;
+
+const CORRECTED_URL: &str = "https://placeholder.org/foo-1.1.tar.zst";
+
+#[rstest]
+#[tokio::test]
+pub async fn corrects_url_and_version(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let package = isolated_server
+        .client
+        .correct_binary_package(
+            1,
+            &BinaryPackageCorrection {
+                artifact_url: Some(CORRECTED_URL.to_string()),
+                version: Some("1.1".to_string()),
+                status: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(package.url, CORRECTED_URL);
+    assert_eq!(package.version, "1.1");
+
+    let package = isolated_server.client.get_binary_package(1).await.unwrap();
+    assert_eq!(package.url, CORRECTED_URL);
+    assert_eq!(package.version, "1.1");
+    assert_eq!(package.name, DUMMY_BINARY_PACKAGE);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn corrects_status_of_latest_rebuild(mut isolated_server: IsolatedServer) {
+    setup::single_bad_rebuild(&isolated_server.client).await;
+
+    let package = isolated_server
+        .client
+        .correct_binary_package(
+            1,
+            &BinaryPackageCorrection {
+                status: Some(ArtifactStatus::Good),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(package.status, Some(ArtifactStatus::Good));
+    assert_eq!(package.url, DUMMY_BINARY_PACKAGE_URL);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn cannot_correct_status_without_rebuild(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .correct_binary_package(
+            1,
+            &BinaryPackageCorrection {
+                artifact_url: Some(CORRECTED_URL.to_string()),
+                status: Some(ArtifactStatus::Good),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    // the correction is applied as a whole or not at all
+    let package = isolated_server.client.get_binary_package(1).await.unwrap();
+    assert_eq!(package.url, DUMMY_BINARY_PACKAGE_URL);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_error_for_nonexistent_id(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .correct_binary_package(
+            99999,
+            &BinaryPackageCorrection {
+                version: Some("1.1".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn needs_authentication(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    setup::single_imported_package(client).await;
+
+    // zero out keys
+    client.auth_cookie("");
+    client.worker_key("");
+    client.signup_secret("");
+
+    let result = client
+        .correct_binary_package(
+            1,
+            &BinaryPackageCorrection {
+                version: Some("1.1".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,71 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{OriginFilter, PackageRestApi, TrackerStatus};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn packages_without_rebuild_are_left_out(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let entries = isolated_server.client.export_tracker(None).await.unwrap();
+
+    assert!(entries.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn exports_verdict_of_latest_rebuild(mut isolated_server: IsolatedServer) {
+    setup::single_bad_rebuild(&isolated_server.client).await;
+
+    let entries = isolated_server.client.export_tracker(None).await.unwrap();
+
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.package, DUMMY_SOURCE_PACKAGE);
+    assert_eq!(entry.version, DUMMY_SOURCE_PACKAGE_VERSION);
+    assert_eq!(entry.suite, DUMMY_RELEASE);
+    assert_eq!(entry.architecture, DUMMY_ARCHITECTURE);
+    assert_eq!(entry.status, TrackerStatus::Unreproducible);
+    assert!(!entry.build_date.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn reproducible_packages_are_exported(mut isolated_server: IsolatedServer) {
+    setup::single_good_rebuild(&isolated_server.client).await;
+
+    let entries = isolated_server.client.export_tracker(None).await.unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].status, TrackerStatus::Reproducible);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn export_can_be_filtered_by_distribution(mut isolated_server: IsolatedServer) {
+    setup::single_good_rebuild(&isolated_server.client).await;
+
+    let entries = isolated_server
+        .client
+        .export_tracker(Some(&OriginFilter {
+            distribution: Some("other".to_string()),
+            release: None,
+            component: None,
+            architecture: None,
+        }))
+        .await
+        .unwrap();
+
+    assert!(entries.is_empty());
+
+    isolated_server.shutdown().await;
+}
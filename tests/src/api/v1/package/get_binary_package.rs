@@ -1,7 +1,8 @@
+use crate::actions::*;
 use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
-use rebuilderd_common::api::v1::PackageRestApi;
+use rebuilderd_common::api::v1::{ArtifactStatus, PackageRestApi};
 use rstest::rstest;
 
 #[rstest]
@@ -56,3 +57,27 @@ pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn keeps_previous_status_while_rebuilding(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let package = client.get_binary_package(1).await.unwrap();
+    assert!(package.rebuilding);
+
+    report_good_rebuild(client).await;
+    let package = client.get_binary_package(1).await.unwrap();
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+    assert!(!package.rebuilding);
+
+    request_rebuild_of_all_packages(client).await;
+    let package = client.get_binary_package(1).await.unwrap();
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+    assert!(package.rebuilding);
+
+    isolated_server.shutdown().await;
+}
@@ -1,9 +1,11 @@
+use crate::actions::*;
 use crate::data::*;
 use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
 use rebuilderd_common::api::v1::{
-    BinaryIdentityFilter, OriginFilter, PackageReport, PackageRestApi, Page,
+    ArtifactStatus, BinaryIdentityFilter, BuildRestApi, OriginFilter, PackageReport,
+    PackageRestApi, Page,
 };
 use rstest::rstest;
 
@@ -270,3 +272,50 @@ pub async fn returns_result_for_matching_identity_filter(
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn links_debug_package_and_combines_its_verdict(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+    let debug_name = format!("{DUMMY_BINARY_PACKAGE}-dbgsym");
+
+    let mut report = single_package_report();
+    let mut debug_artifact = report.packages[0].artifacts[0].clone();
+    debug_artifact.name = debug_name.clone();
+    report.packages[0].artifacts.push(debug_artifact);
+
+    register_worker(client).await;
+    client.submit_package_report(&report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+    let mut rebuild_report = good_rebuild_report(&job);
+    for artifact in &mut rebuild_report.artifacts {
+        if artifact.name == debug_name {
+            artifact.status = ArtifactStatus::Bad;
+        }
+    }
+    client.submit_build_report(rebuild_report).await.unwrap();
+
+    let results = client
+        .get_binary_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap();
+
+    let package = results
+        .iter()
+        .find(|package| package.name == DUMMY_BINARY_PACKAGE)
+        .unwrap();
+    let debug_package = results
+        .iter()
+        .find(|package| package.name == debug_name)
+        .unwrap();
+
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+    assert_eq!(Some(ArtifactStatus::Bad), package.debug_status);
+    assert_eq!(None, package.parent_id);
+    assert_eq!(Some(package.id), debug_package.parent_id);
+    assert_eq!(None, debug_package.debug_status);
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,120 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{PackageReport, PackageRestApi};
+use rstest::rstest;
+
+fn package_report_from_different_distribution_with_version(version: &str) -> PackageReport {
+    let mut report = single_package_report_from_different_distribution();
+    for package in &mut report.packages {
+        package.version = version.to_string();
+    }
+    report
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_result_for_empty_database(mut isolated_server: IsolatedServer) {
+    let result = isolated_server
+        .client
+        .get_upstream_release(DUMMY_SOURCE_PACKAGE, DUMMY_SOURCE_PACKAGE_VERSION)
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_result_for_unknown_version(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .get_upstream_release(DUMMY_SOURCE_PACKAGE, "99")
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn matches_packages_across_distributions(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let other_version = format!("2:{DUMMY_SOURCE_PACKAGE_VERSION}-3");
+    isolated_server
+        .client
+        .submit_package_report(&package_report_from_different_distribution_with_version(
+            &other_version,
+        ))
+        .await
+        .unwrap();
+
+    let result = isolated_server
+        .client
+        .get_upstream_release(DUMMY_SOURCE_PACKAGE, DUMMY_SOURCE_PACKAGE_VERSION)
+        .await
+        .unwrap();
+
+    assert_eq!(result.name, DUMMY_SOURCE_PACKAGE);
+    assert_eq!(result.version, DUMMY_SOURCE_PACKAGE_VERSION);
+    assert!(result.reproducible_in.is_empty());
+    assert_eq!(result.packages.len(), 2);
+    assert_eq!(result.packages[0].distribution, DUMMY_DISTRIBUTION);
+    assert_eq!(result.packages[0].version, DUMMY_SOURCE_PACKAGE_VERSION);
+    assert_eq!(result.packages[1].distribution, DUMMY_OTHER_DISTRIBUTION);
+    assert_eq!(result.packages[1].version, other_version);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn lists_distributions_with_good_rebuilds(mut isolated_server: IsolatedServer) {
+    setup::single_good_rebuild(&isolated_server.client).await;
+
+    isolated_server
+        .client
+        .submit_package_report(&package_report_from_different_distribution_with_version(
+            &format!("{DUMMY_SOURCE_PACKAGE_VERSION}-1"),
+        ))
+        .await
+        .unwrap();
+
+    let result = isolated_server
+        .client
+        .get_upstream_release(DUMMY_SOURCE_PACKAGE, DUMMY_SOURCE_PACKAGE_VERSION)
+        .await
+        .unwrap();
+
+    assert_eq!(result.packages.len(), 2);
+    assert_eq!(result.reproducible_in, vec![DUMMY_DISTRIBUTION.to_string()]);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    setup::single_imported_package(client).await;
+
+    // zero out keys
+    client.auth_cookie("");
+    client.worker_key("");
+    client.signup_secret("");
+
+    let result = client
+        .get_upstream_release(DUMMY_SOURCE_PACKAGE, DUMMY_SOURCE_PACKAGE_VERSION)
+        .await;
+
+    assert!(result.is_ok());
+
+    isolated_server.shutdown().await;
+}
@@ -1,5 +1,15 @@
+mod add_package_tag;
+mod annotate_package;
+mod correct_binary_package;
+mod export_tracker;
 mod get_binary_package;
 mod get_binary_packages;
 mod get_source_package;
 mod get_source_packages;
+mod get_upstream_release;
+mod remove_package_tag;
+mod submit_build_durations;
+mod submit_package_popularity;
 mod submit_package_report;
+mod submit_package_report_delta;
+mod submit_verdicts;
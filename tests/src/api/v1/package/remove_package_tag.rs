@@ -0,0 +1,69 @@
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::PackageRestApi;
+use rstest::rstest;
+
+const DUMMY_TAG: &str = "critical";
+
+#[rstest]
+#[tokio::test]
+pub async fn can_remove_tag(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    isolated_server
+        .client
+        .add_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await
+        .unwrap();
+
+    isolated_server
+        .client
+        .remove_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await
+        .unwrap();
+
+    let tags = isolated_server.client.get_package_tags(None).await.unwrap();
+
+    assert!(tags.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_for_unknown_tag(mut isolated_server: IsolatedServer) {
+    setup::single_imported_package(&isolated_server.client).await;
+
+    let result = isolated_server
+        .client
+        .remove_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    setup::single_imported_package(client).await;
+    client
+        .add_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await
+        .unwrap();
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client
+        .remove_package_tag(DUMMY_DISTRIBUTION, DUMMY_SOURCE_PACKAGE, DUMMY_TAG)
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,56 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{BuildDurationReport, PackageRestApi};
+use rebuilderd_common::config::{ConfigFile, JobOrder};
+use rstest::rstest;
+use std::collections::BTreeMap;
+
+fn duration_report() -> BuildDurationReport {
+    BuildDurationReport {
+        distribution: DUMMY_DISTRIBUTION.to_string(),
+        architecture: DUMMY_ARCHITECTURE.to_string(),
+        packages: BTreeMap::from([
+            (DUMMY_SOURCE_PACKAGE.to_string(), 7200),
+            (DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE.to_string(), 60),
+        ]),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    // zero out key
+    client.auth_cookie("");
+    let result = client.submit_build_durations(&duration_report()).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn short_build_is_picked_while_nothing_is_running(
+    #[with(config_with(|config| config.schedule.job_order = Some(JobOrder::InterleaveDurations)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+    client
+        .submit_build_durations(&duration_report())
+        .await
+        .unwrap();
+
+    let job = pick_up_job(client).await;
+    assert_eq!(DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE, job.job.name);
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,67 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{PackageRestApi, PopularityReport};
+use rstest::rstest;
+use std::collections::BTreeMap;
+
+fn popularity_report() -> PopularityReport {
+    PopularityReport {
+        distribution: DUMMY_DISTRIBUTION.to_string(),
+        packages: BTreeMap::from([
+            (DUMMY_BINARY_PACKAGE.to_string(), 10),
+            (DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2.to_string(), 1000),
+        ]),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    // zero out key
+    client.auth_cookie("");
+    let result = client.submit_package_popularity(&popularity_report()).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn most_popular_package_is_built_first(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+    client
+        .submit_package_popularity(&popularity_report())
+        .await
+        .unwrap();
+
+    let job = pick_up_job(client).await;
+    assert_eq!(DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE, job.job.name);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn popularity_is_applied_to_packages_synced_later(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    client
+        .submit_package_popularity(&popularity_report())
+        .await
+        .unwrap();
+    import_multiple_packages(client).await;
+
+    let job = pick_up_job(client).await;
+    assert_eq!(DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE, job.job.name);
+
+    isolated_server.shutdown().await;
+}
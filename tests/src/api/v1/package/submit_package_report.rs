@@ -6,11 +6,12 @@ use crate::fixtures::*;
 use crate::setup;
 use chrono::Utc;
 use rebuilderd_common::api::v1::{
-    BuildRestApi, BuildStatus, OriginFilter, PackageReport, PackageRestApi, Priority, QueueRestApi,
-    SourceIdentityFilter,
+    ArtifactStatus, BinaryPackageReport, BuildRestApi, BuildStatus, JobPayload, OriginFilter,
+    PackageReport, PackageRestApi, Priority, QueueRestApi, SourceIdentityFilter,
 };
 use rebuilderd_common::config::ConfigFile;
 use rstest::rstest;
+use std::collections::HashMap;
 
 #[rstest]
 #[tokio::test]
@@ -26,6 +27,54 @@ pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: I
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn oversized_package_report_is_rejected_with_body_size_error(
+    #[with(config_with(|config| config.http.sync_body_size_limit = Some(64)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    let err = client
+        .submit_package_report(&multiple_package_report())
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("sync_body_size_limit"));
+
+    // other routes keep the general limit
+    register_worker(client).await;
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn reports_of_rebuildctl_and_workers_match_their_schema(
+    #[with(config_with(|config| config.http.strict_requests = Some(true)))] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    let mut report = single_package_report();
+    report.source = Some(DUMMY_SOURCE_PACKAGE_URL.to_string());
+    report.packages[0].payload = Some(JobPayload::Generic {
+        url: DUMMY_SOURCE_PACKAGE_URL.to_string(),
+    });
+    report.packages[0].artifacts[0].checksum = Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&report).await.unwrap();
+
+    register_worker(client).await;
+    report_good_rebuild(client).await;
+
+    let package = client.get_binary_package(1).await.unwrap();
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn can_submit_package_report_with_single_package(mut isolated_server: IsolatedServer) {
@@ -195,6 +244,43 @@ pub async fn job_is_queued_after_import_of_new_package(mut isolated_server: Isol
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn new_packages_are_not_queued_beyond_max_queue_len(
+    #[with(config_with(|config| {
+        config.schedule.max_queue_len = HashMap::from([(DUMMY_DISTRIBUTION.to_string(), 1)]);
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    client
+        .submit_package_report(&multiple_package_report())
+        .await
+        .unwrap();
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    assert_eq!(jobs.len(), 1);
+
+    // the skipped package is still known, it's queued once there is room
+    let source_packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    assert_eq!(source_packages.len(), 2);
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn queued_job_from_new_package_has_correct_data(mut isolated_server: IsolatedServer) {
@@ -322,7 +408,7 @@ pub async fn queued_job_from_reimported_package_has_correct_data(
 #[rstest]
 #[tokio::test]
 pub async fn initial_delay_sets_next_retry_correctly_for_new_packages(
-    #[with(None, None, Some(60))] config_file: ConfigFile,
+    #[with(config_with(|config| config.schedule.initial_delay = Some(60)))] config_file: ConfigFile,
     #[with(config_file.clone())] mut isolated_server: IsolatedServer,
 ) {
     let client = &isolated_server.client;
@@ -351,7 +437,7 @@ pub async fn initial_delay_sets_next_retry_correctly_for_new_packages(
 #[rstest]
 #[tokio::test]
 pub async fn initial_delay_does_not_affect_existing_packages(
-    #[with(None, None, Some(60))] config_file: ConfigFile,
+    #[with(config_with(|config| config.schedule.initial_delay = Some(60)))] config_file: ConfigFile,
     #[with(config_file.clone())] mut isolated_server: IsolatedServer,
 ) {
     let client = &isolated_server.client;
@@ -436,6 +522,38 @@ pub async fn importing_the_same_package_multiple_times_is_idempotent(
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn new_artifact_of_known_version_is_imported(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let mut report = single_package_report();
+    client.submit_package_report(&report).await.unwrap();
+
+    let artifact = BinaryPackageReport {
+        name: "foo-docs".to_string(),
+        ..report.packages[0].artifacts[0].clone()
+    };
+    report.packages[0].artifacts.push(artifact);
+    client.submit_package_report(&report).await.unwrap();
+
+    let binary_packages = client
+        .get_binary_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(2, binary_packages.len());
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(1, jobs.len());
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn friend_source_packages_are_imported_independently(
@@ -773,7 +891,7 @@ pub async fn package_is_not_queued_if_any_friend_is_already_queued(
     }, single_package_report_from_different_component())]
 #[tokio::test]
 pub async fn package_is_not_queued_if_any_friend_is_already_past_max_retries(
-    #[with(None, Some(1), None)] config_file: ConfigFile,
+    #[with(config_with(|config| config.schedule.max_retries = Some(1)))] config_file: ConfigFile,
     #[with(config_file.clone())] mut isolated_server: IsolatedServer,
     #[case] origin_filter: OriginFilter,
     #[case] extra_packages: PackageReport,
@@ -855,3 +973,85 @@ pub async fn enqueues_jobs_for_all_packages_in_sync(mut isolated_server: Isolate
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn package_dropped_from_sync_is_kept_as_tombstone(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+    import_single_package_with_multiple_artifacts(client).await;
+
+    let packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(2, packages.len());
+
+    let dropped = packages
+        .iter()
+        .find(|package| package.name == DUMMY_SOURCE_PACKAGE)
+        .unwrap();
+    assert!(!dropped.seen_in_last_sync);
+    assert!(dropped.removed_at.is_some());
+
+    let synced = packages
+        .iter()
+        .find(|package| package.name == DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE)
+        .unwrap();
+    assert!(synced.removed_at.is_none());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn package_replaced_by_new_version_is_not_marked_removed(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let mut report = single_package_report();
+    report.packages[0].version = "2".to_string();
+    client.submit_package_report(&report).await.unwrap();
+
+    let packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(2, packages.len());
+    assert!(packages.iter().all(|package| package.removed_at.is_none()));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn package_returning_to_sync_is_no_longer_marked_removed(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+    import_single_package_with_multiple_artifacts(client).await;
+    import_single_package(client).await;
+
+    let packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    let returned = packages
+        .iter()
+        .find(|package| package.name == DUMMY_SOURCE_PACKAGE)
+        .unwrap();
+    assert!(returned.seen_in_last_sync);
+    assert!(returned.removed_at.is_none());
+
+    isolated_server.shutdown().await;
+}
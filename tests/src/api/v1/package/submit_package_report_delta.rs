@@ -0,0 +1,176 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{PackageRestApi, QueueRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_single_package(client).await;
+
+    let delta = multiple_package_report().delta_from(&single_package_report());
+
+    // zero out key
+    client.auth_cookie("");
+    let result = client.submit_package_report_delta(&delta).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn rejects_delta_without_known_base_revision(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let delta = multiple_package_report().delta_from(&single_package_report());
+    let applied = client.submit_package_report_delta(&delta).await.unwrap();
+    assert!(!applied);
+
+    let packages = client.get_source_packages(None, None, None).await.unwrap();
+    assert_eq!(0, packages.total);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn rejects_delta_against_outdated_base_revision(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_multiple_packages(client).await;
+
+    let delta =
+        single_package_with_multiple_artifacts_report().delta_from(&single_package_report());
+    let applied = client.submit_package_report_delta(&delta).await.unwrap();
+    assert!(!applied);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn delta_adds_packages(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let delta = multiple_package_report().delta_from(&single_package_report());
+    assert_eq!(1, delta.added.len());
+    let applied = client.submit_package_report_delta(&delta).await.unwrap();
+    assert!(applied);
+
+    let packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(2, packages.len());
+    assert!(packages.iter().all(|package| package.seen_in_last_sync));
+
+    let queue = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(2, queue.total);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn delta_removes_packages(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_multiple_packages(client).await;
+
+    let delta =
+        single_package_with_multiple_artifacts_report().delta_from(&multiple_package_report());
+    assert_eq!(vec![DUMMY_SOURCE_PACKAGE.to_string()], delta.removed);
+    let applied = client.submit_package_report_delta(&delta).await.unwrap();
+    assert!(applied);
+
+    let packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    let dropped = packages
+        .iter()
+        .find(|package| package.name == DUMMY_SOURCE_PACKAGE)
+        .unwrap();
+    assert!(!dropped.seen_in_last_sync);
+    assert!(dropped.removed_at.is_some());
+
+    let kept = packages
+        .iter()
+        .find(|package| package.name == DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE)
+        .unwrap();
+    assert!(kept.seen_in_last_sync);
+    assert!(kept.removed_at.is_none());
+
+    let queue = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(1, queue.total);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn delta_replaces_updated_versions(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let mut report = single_package_report();
+    report.packages[0].version = "2".to_string();
+    report.packages[0].url = "https://placeholder.org/foo-2.buildinfo.txt".to_string();
+    let delta = report.delta_from(&single_package_report());
+    assert_eq!(1, delta.updated.len());
+    let applied = client.submit_package_report_delta(&delta).await.unwrap();
+    assert!(applied);
+
+    let packages = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(2, packages.len());
+    assert!(packages.iter().all(|package| package.removed_at.is_none()));
+
+    let current = packages
+        .iter()
+        .find(|package| package.version == "2")
+        .unwrap();
+    assert!(current.seen_in_last_sync);
+
+    let queue = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(1, queue.total);
+    assert_eq!("2", queue.records[0].version);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn deltas_can_be_chained(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let delta = multiple_package_report().delta_from(&single_package_report());
+    assert!(client.submit_package_report_delta(&delta).await.unwrap());
+
+    let delta =
+        single_package_with_multiple_artifacts_report().delta_from(&multiple_package_report());
+    assert!(client.submit_package_report_delta(&delta).await.unwrap());
+
+    // the first delta is no longer the current revision
+    let delta = multiple_package_report().delta_from(&single_package_report());
+    assert!(!client.submit_package_report_delta(&delta).await.unwrap());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,163 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use chrono::Utc;
+use rebuilderd_common::api::Client;
+use rebuilderd_common::api::v1::{
+    ArtifactStatus, ArtifactVerdictReport, BuildRestApi, BuildStatus, PackageReport,
+    PackageRestApi, QueueRestApi, RegisterWorkerRequest, VerdictReport, WorkerEnvironment,
+    WorkerRestApi,
+};
+use rstest::rstest;
+use std::collections::BTreeMap;
+
+const DUMMY_SCRIPT_VERSION: &str = "deadbeef";
+const DUMMY_PEER: &str = "https://rebuilderd.example.org";
+
+async fn register_worker_with_script(client: &Client, script_version: &str) {
+    client
+        .register_worker(RegisterWorkerRequest {
+            name: DUMMY_WORKER.to_string(),
+            environment: Some(WorkerEnvironment {
+                worker_version: "0.26.0".to_string(),
+                kernel: None,
+                scripts: BTreeMap::from([(DUMMY_BACKEND.to_string(), script_version.to_string())]),
+                tools: BTreeMap::new(),
+            }),
+            uuid: None,
+        })
+        .await
+        .unwrap();
+}
+
+fn package_report_with_checksum() -> PackageReport {
+    let mut report = single_package_report();
+    report.packages[0].artifacts[0].checksum = Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    report
+}
+
+fn verdict_report(status: ArtifactStatus) -> VerdictReport {
+    VerdictReport {
+        origin: DUMMY_PEER.to_string(),
+        verdicts: vec![ArtifactVerdictReport {
+            checksum: DUMMY_BINARY_PACKAGE_CHECKSUM.to_string(),
+            script_version: DUMMY_SCRIPT_VERSION.to_string(),
+            status,
+            verified_at: Utc::now().naive_utc(),
+        }],
+    }
+}
+
+async fn queue_len(client: &Client) -> usize {
+    client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .len()
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    // zero out key
+    client.auth_cookie("");
+    let result = client
+        .submit_verdicts(&verdict_report(ArtifactStatus::Good))
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn verdict_of_peer_is_reused_instead_of_queueing(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker_with_script(client, DUMMY_SCRIPT_VERSION).await;
+    client
+        .submit_verdicts(&verdict_report(ArtifactStatus::Good))
+        .await
+        .unwrap();
+    client
+        .submit_package_report(&package_report_with_checksum())
+        .await
+        .unwrap();
+
+    assert_eq!(0, queue_len(client).await);
+
+    let package = client.get_binary_package(1).await.unwrap();
+    assert_eq!(Some(ArtifactStatus::Good), package.status);
+
+    let log = client
+        .get_build_log(package.build_id.unwrap())
+        .await
+        .unwrap();
+    assert!(log.contains(DUMMY_PEER));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn verdict_of_other_script_version_is_ignored(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker_with_script(client, "cafebabe").await;
+    client
+        .submit_verdicts(&verdict_report(ArtifactStatus::Good))
+        .await
+        .unwrap();
+    client
+        .submit_package_report(&package_report_with_checksum())
+        .await
+        .unwrap();
+
+    assert_eq!(1, queue_len(client).await);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn own_verdict_is_reused_in_other_release(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker_with_script(client, DUMMY_SCRIPT_VERSION).await;
+    client
+        .submit_package_report(&package_report_with_checksum())
+        .await
+        .unwrap();
+    report_good_rebuild(client).await;
+
+    // same artifact, but published with a different build input url so the rebuild isn't shared between friends
+    let mut report = package_report_with_checksum();
+    report.release = Some(DUMMY_OTHER_RELEASE.to_string());
+    report.packages[0].url = DUMMY_OTHER_SOURCE_PACKAGE_URL.to_string();
+    client.submit_package_report(&report).await.unwrap();
+
+    assert_eq!(0, queue_len(client).await);
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .into_iter()
+        .find(|package| package.release.as_deref() == Some(DUMMY_OTHER_RELEASE))
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Good), package.status);
+
+    let log = client
+        .get_build_log(package.build_id.unwrap())
+        .await
+        .unwrap();
+    assert!(log.contains("rebuild #1"));
+
+    isolated_server.shutdown().await;
+}
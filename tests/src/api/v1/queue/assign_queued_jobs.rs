@@ -0,0 +1,113 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::api::v1::{AssignQueuedJobsRequest, JobAssignment, QueueRestApi};
+use rstest::rstest;
+
+fn pin_to(worker: Option<i32>) -> AssignQueuedJobsRequest {
+    AssignQueuedJobsRequest { worker }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn pinned_job_is_only_assigned_to_pinned_worker(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+
+    // the client acts as the other worker from here on
+    let worker_key = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    client.worker_key(worker_key);
+    register_other_worker(client).await;
+
+    import_single_package(client).await;
+
+    let response = client
+        .assign_queued_jobs(None, None, pin_to(Some(1)))
+        .await
+        .unwrap();
+    assert_eq!(response.assigned, 1);
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(jobs[0].pinned_worker.as_deref(), Some(DUMMY_WORKER));
+
+    client
+        .assign_queued_jobs(None, None, pin_to(Some(2)))
+        .await
+        .unwrap();
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Rebuild(_)));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn unpinned_job_is_assigned_to_any_worker(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+
+    let worker_key = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    client.worker_key(worker_key);
+    register_other_worker(client).await;
+
+    import_single_package(client).await;
+
+    client
+        .assign_queued_jobs(None, None, pin_to(Some(1)))
+        .await
+        .unwrap();
+    client
+        .assign_queued_jobs(None, None, pin_to(None))
+        .await
+        .unwrap();
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Rebuild(_)));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let result = client
+        .assign_queued_jobs(None, None, pin_to(Some(9999)))
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.assign_queued_jobs(None, None, pin_to(Some(1))).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,50 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::QueueRestApi;
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_empty_snapshot_for_empty_database(mut isolated_server: IsolatedServer) {
+    let snapshot = isolated_server.client.export_queue().await.unwrap();
+
+    assert!(snapshot.jobs.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn contains_all_queued_jobs(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_multiple_packages(client).await;
+
+    let snapshot = client.export_queue().await.unwrap();
+    let mut queue = client.get_queued_jobs(None, None, None).await.unwrap();
+
+    // the queue listing picks jobs of the same priority and day in random order
+    queue.records.sort_by_key(|job| job.id);
+
+    assert_eq!(snapshot.jobs.len() as i64, queue.total);
+    assert_eq!(snapshot.jobs, queue.records);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_multiple_packages(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.export_queue().await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,137 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{Priority, QueueJobRequest, QueueRestApi, SourceIdentityFilter};
+use rstest::rstest;
+
+fn source_identity_filter(name: &str) -> SourceIdentityFilter {
+    SourceIdentityFilter {
+        name: Some(name.to_string()),
+        version: None,
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_no_results_for_empty_database(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let results = client
+        .get_queue_position(None, Some(&source_identity_filter(DUMMY_SOURCE_PACKAGE)))
+        .await
+        .unwrap();
+
+    assert!(results.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_position_of_only_queued_package(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::build_ready_database(client).await;
+
+    let results = client
+        .get_queue_position(None, Some(&source_identity_filter(DUMMY_SOURCE_PACKAGE)))
+        .await
+        .unwrap();
+
+    assert_eq!(1, results.len());
+    assert_eq!(DUMMY_SOURCE_PACKAGE, results[0].job.name);
+    assert_eq!(0, results[0].ahead);
+    assert_eq!(0, results[0].tied);
+
+    // nothing was rebuilt yet, so there's no throughput to estimate from
+    assert!(results[0].estimated_start.is_none());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn jobs_with_higher_priority_are_ahead(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+
+    let results = client
+        .get_queue_position(None, Some(&source_identity_filter(DUMMY_SOURCE_PACKAGE)))
+        .await
+        .unwrap();
+
+    assert_eq!(0, results[0].ahead);
+    assert_eq!(1, results[0].tied);
+
+    client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: Some(DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE.to_string()),
+            version: None,
+            architecture: None,
+            status: None,
+            priority: Some(Priority::manual()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
+        })
+        .await
+        .unwrap();
+
+    let results = client
+        .get_queue_position(None, Some(&source_identity_filter(DUMMY_SOURCE_PACKAGE)))
+        .await
+        .unwrap();
+
+    assert_eq!(1, results[0].ahead);
+    assert_eq!(0, results[0].tied);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn estimates_start_time_from_recent_rebuilds(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+
+    let results = client
+        .get_queue_position(None, Some(&source_identity_filter(DUMMY_SOURCE_PACKAGE)))
+        .await
+        .unwrap();
+
+    assert!(results[0].estimated_start.is_some());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    setup::build_ready_database(client).await;
+
+    // zero out keys
+    client.auth_cookie("");
+    client.worker_key("");
+    client.signup_secret("");
+
+    let result = client
+        .get_queue_position(None, Some(&source_identity_filter(DUMMY_SOURCE_PACKAGE)))
+        .await;
+
+    assert!(result.is_ok());
+
+    isolated_server.shutdown().await;
+}
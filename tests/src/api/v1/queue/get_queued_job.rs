@@ -39,6 +39,22 @@ pub async fn returns_no_result_for_nonexistent_id(mut isolated_server: IsolatedS
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn error_includes_request_id(mut isolated_server: IsolatedServer) {
+    let err = isolated_server
+        .client
+        .get_queued_job(99999)
+        .await
+        .unwrap_err();
+
+    let message = err.to_string();
+    assert!(message.starts_with("Request failed with 404 Not Found"));
+    assert!(message.contains("(request id "));
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn does_not_need_authentication(mut isolated_server: IsolatedServer) {
@@ -275,3 +275,46 @@ pub async fn returns_result_for_matching_identity_filter(
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn shows_assigned_worker(mut isolated_server: IsolatedServer) {
+    setup::single_rebuild_in_progress(&isolated_server.client).await;
+
+    let results = isolated_server
+        .client
+        .get_queued_jobs(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap();
+
+    assert_eq!(1, results.len());
+    assert_eq!(Some(DUMMY_WORKER.to_string()), results[0].worker);
+    assert_eq!(0, results[0].attempts);
+    assert_eq!(None, results[0].last_failure);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn shows_attempts_and_last_failure(mut isolated_server: IsolatedServer) {
+    setup::single_failed_rebuild(&isolated_server.client).await;
+
+    let results = isolated_server
+        .client
+        .get_queued_jobs(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap();
+
+    assert_eq!(1, results.len());
+    assert_eq!(None, results[0].worker);
+    assert_eq!(1, results[0].attempts);
+
+    let last_failure = results[0].last_failure.as_deref().unwrap();
+    assert!(last_failure.contains("FAIL"));
+    assert!(last_failure.contains(DUMMY_WORKER));
+
+    isolated_server.shutdown().await;
+}
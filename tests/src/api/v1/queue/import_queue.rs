@@ -0,0 +1,161 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{Priority, QueueImportResponse, QueueRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn restores_dropped_queue(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_multiple_packages(client).await;
+
+    let snapshot = client.export_queue().await.unwrap();
+    client.drop_queued_jobs(None, None).await.unwrap();
+
+    let response = client.import_queue(&snapshot).await.unwrap();
+
+    assert_eq!(
+        response,
+        QueueImportResponse {
+            restored: snapshot.jobs.len() as i64,
+            skipped: 0,
+            missing: 0,
+        }
+    );
+
+    let mut jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    jobs.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    let mut expected_jobs = snapshot.jobs;
+    expected_jobs.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    assert_eq!(jobs.len(), expected_jobs.len());
+    for (job, expected) in jobs.iter().zip(&expected_jobs) {
+        assert_eq!(job.name, expected.name);
+        assert_eq!(job.version, expected.version);
+        assert_eq!(job.distribution, expected.distribution);
+        assert_eq!(job.priority, expected.priority);
+        assert_eq!(job.queued_at, expected.queued_at);
+    }
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn skips_jobs_that_are_already_queued(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_multiple_packages(client).await;
+
+    let snapshot = client.export_queue().await.unwrap();
+    let response = client.import_queue(&snapshot).await.unwrap();
+
+    assert_eq!(response.restored, 0);
+    assert_eq!(response.skipped, snapshot.jobs.len() as i64);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn skips_jobs_of_unknown_packages(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let mut snapshot = client.export_queue().await.unwrap();
+    client.drop_queued_jobs(None, None).await.unwrap();
+
+    snapshot.jobs[0].version = "99".to_string();
+    let response = client.import_queue(&snapshot).await.unwrap();
+
+    assert_eq!(
+        response,
+        QueueImportResponse {
+            restored: 0,
+            skipped: 0,
+            missing: 1,
+        }
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn restores_running_jobs_as_pending(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+    register_worker(client).await;
+    pick_up_job(client).await;
+
+    let snapshot = client.export_queue().await.unwrap();
+    assert!(snapshot.jobs[0].started_at.is_some());
+
+    client.drop_queued_jobs(None, None).await.unwrap();
+    client.import_queue(&snapshot).await.unwrap();
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].name, DUMMY_SOURCE_PACKAGE);
+    assert_eq!(jobs[0].started_at, None);
+    assert_eq!(jobs[0].worker, None);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn keeps_priority_of_jobs(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+
+    let mut snapshot = client.export_queue().await.unwrap();
+    client.drop_queued_jobs(None, None).await.unwrap();
+
+    snapshot.jobs[0].priority = Priority::manual();
+    client.import_queue(&snapshot).await.unwrap();
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+
+    assert_eq!(jobs[0].priority, Priority::manual());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    import_multiple_packages(client).await;
+    let snapshot = client.export_queue().await.unwrap();
+    client.drop_queued_jobs(None, None).await.unwrap();
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.import_queue(&snapshot).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
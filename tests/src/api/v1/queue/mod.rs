@@ -1,7 +1,11 @@
+mod assign_queued_jobs;
 mod drop_queued_job;
 mod drop_queued_jobs;
+mod export_queue;
+mod get_queue_position;
 mod get_queued_job;
 mod get_queued_jobs;
+mod import_queue;
 mod ping_job;
 mod request_rebuild;
 mod request_work;
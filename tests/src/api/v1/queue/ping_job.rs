@@ -1,7 +1,9 @@
 use crate::actions::*;
 use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
-use rebuilderd_common::api::v1::QueueRestApi;
+use chrono::Duration;
+use rebuilderd_common::api::v1::{PingJobRequest, QueueRestApi};
+use rebuilderd_common::config::ConfigFile;
 use rstest::rstest;
 
 #[rstest]
@@ -19,6 +21,87 @@ pub async fn can_ping_running_job(mut isolated_server: IsolatedServer) {
     isolated_server.shutdown().await;
 }
 
+#[rstest]
+#[tokio::test]
+pub async fn ping_reports_phase(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+
+    let request = PingJobRequest {
+        phase: Some("build".to_string()),
+        extend: None,
+    };
+    client
+        .ping_job_with_phase(job.job.id, request)
+        .await
+        .unwrap();
+
+    let queued = client.get_queued_job(job.job.id).await.unwrap();
+    assert_eq!(queued.phase.as_deref(), Some("build"));
+
+    // a ping without a phase keeps the last reported one
+    client.ping_job(job.job.id).await.unwrap();
+
+    let queued = client.get_queued_job(job.job.id).await.unwrap();
+    assert_eq!(queued.phase.as_deref(), Some("build"));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn ping_extends_deadline(
+    #[with(config_with(|config| {
+        config.schedule.build_deadline = Some(600);
+        config.schedule.max_build_deadline = Some(3600);
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+    let queued = client.get_queued_job(job.job.id).await.unwrap();
+    let started_at = queued.started_at.unwrap();
+    assert_eq!(queued.deadline, Some(started_at + Duration::seconds(600)));
+
+    let request = PingJobRequest {
+        phase: None,
+        extend: Some(1800),
+    };
+    client
+        .ping_job_with_phase(job.job.id, request)
+        .await
+        .unwrap();
+
+    let queued = client.get_queued_job(job.job.id).await.unwrap();
+    let deadline = queued.deadline.unwrap();
+    assert!(deadline > started_at + Duration::seconds(1700));
+
+    // extensions are capped, counted from when the job was picked up
+    let request = PingJobRequest {
+        phase: None,
+        extend: Some(86400),
+    };
+    client
+        .ping_job_with_phase(job.job.id, request)
+        .await
+        .unwrap();
+
+    let queued = client.get_queued_job(job.job.id).await.unwrap();
+    assert_eq!(queued.deadline, Some(started_at + Duration::seconds(3600)));
+
+    isolated_server.shutdown().await;
+}
+
 #[rstest]
 #[tokio::test]
 pub async fn can_not_ping_available_job(mut isolated_server: IsolatedServer) {
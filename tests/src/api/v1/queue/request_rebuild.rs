@@ -3,12 +3,15 @@ use crate::data::*;
 use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use rebuilderd_common::api::Client;
 use rebuilderd_common::api::v1::{
-    BuildStatus, PackageReport, PackageRestApi, Priority, QueueJobRequest, QueueRestApi,
+    BuildRestApi, BuildStatus, PackageReport, PackageRestApi, Priority, QueueJobRequest,
+    QueueJobResponse, QueueRestApi, RegisterWorkerRequest, WorkerEnvironment, WorkerRestApi,
 };
 use rebuilderd_common::config::ConfigFile;
 use rstest::rstest;
+use std::collections::BTreeMap;
 
 #[rstest]
 #[tokio::test]
@@ -29,6 +32,11 @@ pub async fn can_requeue_bad_packages(mut isolated_server: IsolatedServer) {
             architecture: None,
             status: Some(BuildStatus::Bad),
             priority: Some(Priority::default()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await
         .unwrap();
@@ -95,6 +103,11 @@ pub async fn can_update_job_priority(mut isolated_server: IsolatedServer) {
             architecture: None,
             status: None,
             priority: Some(Priority::manual()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await
         .unwrap();
@@ -129,6 +142,11 @@ pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: I
             architecture: None,
             status: None,
             priority: Some(Priority::manual()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await;
 
@@ -164,6 +182,11 @@ pub async fn does_not_requeue_friends(
             architecture: None,
             status: None,
             priority: Some(Priority::manual()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await
         .unwrap();
@@ -182,7 +205,7 @@ pub async fn does_not_requeue_friends(
 #[rstest]
 #[tokio::test]
 pub async fn can_requeue_package_beyond_max_retries(
-    #[with(None, Some(1), None)] config_file: ConfigFile,
+    #[with(config_with(|config| config.schedule.max_retries = Some(1)))] config_file: ConfigFile,
     #[with(config_file.clone())] mut isolated_server: IsolatedServer,
 ) {
     let client = &isolated_server.client;
@@ -213,6 +236,11 @@ pub async fn can_requeue_package_beyond_max_retries(
             architecture: None,
             status: None,
             priority: Some(Priority::manual()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await
         .unwrap();
@@ -227,3 +255,449 @@ pub async fn can_requeue_package_beyond_max_retries(
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn reports_number_of_queued_jobs(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+
+    let response = client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: None,
+            version: None,
+            architecture: None,
+            status: None,
+            priority: None,
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        QueueJobResponse {
+            queued: 1,
+            skipped: 0
+        },
+        response
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn reports_already_queued_jobs_as_skipped(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    // importing a package queues it right away
+    setup::build_ready_database(client).await;
+
+    let response = client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: None,
+            version: None,
+            architecture: None,
+            status: None,
+            priority: None,
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        QueueJobResponse {
+            queued: 0,
+            skipped: 1
+        },
+        response
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn updates_queued_and_queues_missing_packages_together(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+    report_good_rebuild(client).await;
+
+    let response = client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: None,
+            version: None,
+            architecture: None,
+            status: None,
+            priority: None,
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        QueueJobResponse {
+            queued: 1,
+            skipped: 1
+        },
+        response
+    );
+
+    let queue = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(2, queue.total);
+    for job in queue.records {
+        assert_eq!(Priority::manual(), job.priority);
+    }
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn streams_the_result_to_clients_asking_for_progress(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+
+    let response = client
+        .request_rebuild_with_progress(
+            QueueJobRequest {
+                distribution: None,
+                release: None,
+                component: None,
+                name: None,
+                version: None,
+                architecture: None,
+                status: None,
+                priority: None,
+                built_with: None,
+                built_before: None,
+                binary_name: None,
+                build_architecture: None,
+                reason: None,
+            },
+            &|_, _| {},
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        QueueJobResponse {
+            queued: 1,
+            skipped: 0
+        },
+        response
+    );
+
+    let queue = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(1, queue.total);
+
+    isolated_server.shutdown().await;
+}
+
+fn worker_environment() -> WorkerEnvironment {
+    WorkerEnvironment {
+        worker_version: "0.26.0".to_string(),
+        kernel: Some("6.1.0".to_string()),
+        scripts: BTreeMap::from([("debian".to_string(), "deadbeef".to_string())]),
+        tools: BTreeMap::from([("diffoscope".to_string(), "diffoscope 301".to_string())]),
+    }
+}
+
+async fn requeue_built_with(
+    client: &Client,
+    built_with: Option<String>,
+    built_before: Option<chrono::NaiveDateTime>,
+) -> QueueJobResponse {
+    client
+        .request_rebuild(QueueJobRequest {
+            distribution: None,
+            release: None,
+            component: None,
+            name: None,
+            version: None,
+            architecture: None,
+            status: None,
+            priority: None,
+            built_with,
+            built_before,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
+        })
+        .await
+        .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn records_environment_fingerprint_of_rebuild(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    client
+        .register_worker(RegisterWorkerRequest {
+            name: DUMMY_WORKER.to_string(),
+            environment: Some(worker_environment()),
+            uuid: None,
+        })
+        .await
+        .unwrap();
+    import_single_package(client).await;
+    report_good_rebuild(client).await;
+
+    let build = client.get_build(1).await.unwrap();
+
+    assert_eq!(
+        Some(worker_environment().fingerprint()),
+        build.environment_fingerprint
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_requeue_packages_built_with_environment(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    client
+        .register_worker(RegisterWorkerRequest {
+            name: DUMMY_WORKER.to_string(),
+            environment: Some(worker_environment()),
+            uuid: None,
+        })
+        .await
+        .unwrap();
+    import_single_package(client).await;
+    report_good_rebuild(client).await;
+
+    let response = requeue_built_with(client, Some("0000000000000000".to_string()), None).await;
+    assert_eq!(QueueJobResponse::default(), response);
+
+    let fingerprint = worker_environment().fingerprint();
+    let response = requeue_built_with(client, Some(fingerprint), None).await;
+    assert_eq!(
+        QueueJobResponse {
+            queued: 1,
+            skipped: 0
+        },
+        response
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_requeue_packages_built_before_date(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_good_rebuild(client).await;
+
+    let yesterday = (Utc::now() - Duration::days(1)).naive_utc();
+    let response = requeue_built_with(client, None, Some(yesterday)).await;
+    assert_eq!(QueueJobResponse::default(), response);
+
+    let tomorrow = (Utc::now() + Duration::days(1)).naive_utc();
+    let response = requeue_built_with(client, None, Some(tomorrow)).await;
+    assert_eq!(
+        QueueJobResponse {
+            queued: 1,
+            skipped: 0
+        },
+        response
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn does_not_requeue_unbuilt_packages_by_environment(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_imported_package(client).await;
+    client.drop_queued_jobs(None, None).await.unwrap();
+
+    let tomorrow = (Utc::now() + Duration::days(1)).naive_utc();
+    let response = requeue_built_with(client, None, Some(tomorrow)).await;
+    assert_eq!(QueueJobResponse::default(), response);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_requeue_source_package_by_binary_name(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_imported_package_with_multiple_artifacts(client).await;
+
+    let requeue = |binary_name: &str| QueueJobRequest {
+        distribution: None,
+        release: None,
+        component: None,
+        name: None,
+        version: None,
+        architecture: None,
+        status: None,
+        priority: None,
+        built_with: None,
+        built_before: None,
+        binary_name: Some(binary_name.to_string()),
+        build_architecture: None,
+        reason: None,
+    };
+
+    // the source package isn't a binary package of its own
+    let response = client
+        .request_rebuild(requeue(DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE))
+        .await
+        .unwrap();
+    assert_eq!(QueueJobResponse::default(), response);
+
+    let response = client
+        .request_rebuild(requeue(DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2))
+        .await
+        .unwrap();
+    assert_eq!(
+        QueueJobResponse {
+            queued: 0,
+            skipped: 1
+        },
+        response
+    );
+
+    isolated_server.shutdown().await;
+}
+
+fn requeue_with_reason(reason: &str) -> QueueJobRequest {
+    QueueJobRequest {
+        distribution: None,
+        release: None,
+        component: None,
+        name: None,
+        version: None,
+        architecture: None,
+        status: None,
+        priority: None,
+        built_with: None,
+        built_before: None,
+        binary_name: None,
+        build_architecture: None,
+        reason: Some(reason.to_string()),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn reason_is_kept_in_build_history(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    // the package is already queued by the import, it picks up the reason anyway
+    client
+        .request_rebuild(requeue_with_reason("glibc 2.39 rebuild"))
+        .await
+        .unwrap();
+
+    let job = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some("glibc 2.39 rebuild"), job.reason.as_deref());
+
+    report_good_rebuild(client).await;
+
+    let build = client
+        .get_builds(None, None, None)
+        .await
+        .unwrap()
+        .records
+        .pop()
+        .unwrap();
+    assert_eq!(Some("glibc 2.39 rebuild"), build.reason.as_deref());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn reason_is_recorded_on_queued_and_missing_packages(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+    report_good_rebuild(client).await;
+
+    let response = client
+        .request_rebuild(requeue_with_reason("openssl 3.5 rebuild"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        QueueJobResponse {
+            queued: 1,
+            skipped: 1
+        },
+        response
+    );
+
+    let queue = client.get_queued_jobs(None, None, None).await.unwrap();
+    assert_eq!(2, queue.total);
+    for job in queue.records {
+        assert_eq!(Some("openssl 3.5 rebuild"), job.reason.as_deref());
+    }
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn rejects_overly_long_reason(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    setup::single_imported_package(client).await;
+
+    let result = client
+        .request_rebuild(requeue_with_reason(&"x".repeat(257)))
+        .await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
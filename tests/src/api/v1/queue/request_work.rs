@@ -4,10 +4,12 @@ use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
 use crate::setup;
 use rebuilderd_common::api::v1::{
-    JobAssignment, PopQueuedJobRequest, Priority, QueueJobRequest, QueueRestApi,
+    JobAssignment, JobPayload, PackageRestApi, PopQueuedJobRequest, Priority, QueueJobRequest,
+    QueueRestApi,
 };
-use rebuilderd_common::config::ConfigFile;
+use rebuilderd_common::config::{ConfigFile, UrlTemplate, WorkerSchedule};
 use rstest::rstest;
+use std::collections::HashMap;
 
 #[rstest]
 #[tokio::test]
@@ -137,7 +139,7 @@ pub async fn worker_with_different_native_architecture_gets_work(
 #[rstest]
 #[tokio::test]
 pub async fn manually_queued_item_past_max_retries_is_available(
-    #[with(None, Some(1), None)] config_file: ConfigFile,
+    #[with(config_with(|config| config.schedule.max_retries = Some(1)))] config_file: ConfigFile,
     #[with(config_file.clone())] mut isolated_server: IsolatedServer,
 ) {
     let client = &isolated_server.client;
@@ -163,6 +165,11 @@ pub async fn manually_queued_item_past_max_retries_is_available(
             architecture: None,
             status: None,
             priority: Some(Priority::manual()),
+            built_with: None,
+            built_before: None,
+            binary_name: None,
+            build_architecture: None,
+            reason: None,
         })
         .await
         .unwrap();
@@ -172,3 +179,304 @@ pub async fn manually_queued_item_past_max_retries_is_available(
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn work_is_distributed_fairly_between_suites(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+    client
+        .submit_package_report(&other_package_report_from_different_release())
+        .await
+        .unwrap();
+
+    // the suite with fewer queued jobs still gets its turn before the larger one is drained
+    let first = pick_up_job(client).await;
+    let second = pick_up_job(client).await;
+
+    assert_ne!(first.job.release, second.job.release);
+
+    let third = pick_up_job(client).await;
+    assert_eq!(third.job.release, Some(DUMMY_RELEASE.to_string()));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn job_carries_checksum_recorded_at_sync(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+
+    let mut report = single_package_report();
+    report.packages[0].artifacts[0].checksum = Some(DUMMY_BINARY_PACKAGE_CHECKSUM.to_string());
+    client.submit_package_report(&report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+
+    assert_eq!(
+        job.artifacts[0].checksum.as_deref(),
+        Some(DUMMY_BINARY_PACKAGE_CHECKSUM)
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn job_without_reported_payload_gets_generic_payload(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    import_single_package(client).await;
+    register_worker(client).await;
+
+    let job = pick_up_job(client).await;
+
+    assert_eq!(
+        job.payload,
+        Some(JobPayload::Generic {
+            url: DUMMY_SOURCE_PACKAGE_URL.to_string()
+        })
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn job_carries_payload_recorded_at_sync(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+
+    let payload = JobPayload::Debian {
+        buildinfo_url:
+            "https://buildinfos.debian.net/buildinfo-pool/d/dummy/dummy_1.0_amd64.buildinfo"
+                .to_string(),
+        dsc_url: "https://deb.debian.org/debian/pool/main/d/dummy/dummy_1.0.dsc".to_string(),
+        binaries: vec![DUMMY_BINARY_PACKAGE.to_string()],
+    };
+    let mut report = single_package_report();
+    report.packages[0].payload = Some(payload.clone());
+    client.submit_package_report(&report).await.unwrap();
+
+    let job = pick_up_job(client).await;
+
+    assert_eq!(job.payload, Some(payload));
+
+    isolated_server.shutdown().await;
+}
+
+fn dummy_url_templates() -> HashMap<String, UrlTemplate> {
+    HashMap::from([(
+        DUMMY_DISTRIBUTION.to_string(),
+        UrlTemplate {
+            template: "{mirror}/{repo}/os/{arch}/{filename}".to_string(),
+            mirror: Some("https://mirror.example.com/".to_string()),
+        },
+    )])
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn artifact_urls_are_resolved_from_url_template(
+    #[with(config_with(|config| config.url_templates = dummy_url_templates()))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let JobAssignment::Rebuild(job) = client.request_work(job_request()).await.unwrap() else {
+        panic!("Expected a job to be assigned");
+    };
+
+    assert_eq!(1, job.artifacts.len());
+    assert_eq!(
+        format!(
+            "https://mirror.example.com/{DUMMY_COMPONENT}/os/{DUMMY_ARCHITECTURE}/foo-1.tar.zst"
+        ),
+        job.artifacts[0].url
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn artifact_urls_use_mirror_of_worker(
+    #[with(config_with(|config| config.url_templates = dummy_url_templates()))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = client
+        .request_work(PopQueuedJobRequest {
+            mirrors: HashMap::from([(
+                DUMMY_DISTRIBUTION.to_string(),
+                "https://nearby.example.com".to_string(),
+            )]),
+            ..job_request()
+        })
+        .await
+        .unwrap();
+    let JobAssignment::Rebuild(job) = job else {
+        panic!("Expected a job to be assigned");
+    };
+
+    assert_eq!(
+        format!(
+            "https://nearby.example.com/{DUMMY_COMPONENT}/os/{DUMMY_ARCHITECTURE}/foo-1.tar.zst"
+        ),
+        job.artifacts[0].url
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn artifact_urls_are_kept_without_url_template(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let JobAssignment::Rebuild(job) = client.request_work(job_request()).await.unwrap() else {
+        panic!("Expected a job to be assigned");
+    };
+
+    assert_eq!(DUMMY_BINARY_PACKAGE_URL, job.artifacts[0].url);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn cross_build_is_only_picked_up_by_worker_with_cross_toolchain(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    request_cross_rebuild_of_all_packages(client, DUMMY_OTHER_ARCHITECTURE).await;
+
+    // a native worker for the package architecture doesn't pick up the cross build
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    let job = client
+        .request_work(PopQueuedJobRequest {
+            supported_architectures: vec![DUMMY_OTHER_ARCHITECTURE.to_string()],
+            cross_architectures: vec![DUMMY_ARCHITECTURE.to_string()],
+            ..job_request()
+        })
+        .await
+        .unwrap();
+    let JobAssignment::Rebuild(job) = job else {
+        panic!("Expected a job to be assigned");
+    };
+
+    assert_eq!(DUMMY_ARCHITECTURE, job.job.architecture);
+    assert_eq!(
+        Some(DUMMY_OTHER_ARCHITECTURE.to_string()),
+        job.job.build_architecture
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn worker_does_not_claim_more_than_max_jobs(
+    #[with(config_with(|config| config.schedule.max_jobs_per_worker = Some(1)))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+
+    pick_up_job(client).await;
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn max_jobs_of_worker_take_precedence(
+    #[with(config_with(|config| {
+        config.schedule.max_jobs_per_worker = Some(1);
+        config.schedule.workers = HashMap::from([(
+            DUMMY_WORKER.to_string(),
+            WorkerSchedule {
+                max_jobs: Some(2),
+                ..Default::default()
+            },
+        )]);
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+
+    let first = pick_up_job(client).await;
+    let second = pick_up_job(client).await;
+
+    assert_ne!(first.job.name, second.job.name);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn excluded_package_is_not_scheduled_for_worker(
+    #[with(config_with(|config| {
+        config.schedule.workers = HashMap::from([(
+            DUMMY_WORKER.to_string(),
+            WorkerSchedule {
+                exclude_packages: vec![DUMMY_SOURCE_PACKAGE.to_string()],
+                ..Default::default()
+            },
+        )]);
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+    let _config_file = config_file;
+
+    register_worker(client).await;
+    import_multiple_packages(client).await;
+
+    let job = pick_up_job(client).await;
+    assert_eq!(DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE, job.job.name);
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    isolated_server.shutdown().await;
+}
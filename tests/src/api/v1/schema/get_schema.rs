@@ -0,0 +1,37 @@
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::MetaRestApi;
+use rstest::rstest;
+use serde_json::json;
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_schema_of_package_report(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let schema = client.get_schema("package-report").await.unwrap();
+
+    assert_eq!(json!("PackageReport"), schema["title"]);
+    assert_eq!(json!(false), schema["additionalProperties"]);
+    assert!(schema["properties"]["packages"].is_object());
+    assert!(
+        schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("distribution"))
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_for_unknown_schema(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.get_schema("suite-import").await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
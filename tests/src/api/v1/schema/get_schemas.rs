@@ -0,0 +1,20 @@
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::MetaRestApi;
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn lists_wire_types(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    // zero out key
+    client.auth_cookie("");
+    let names = client.get_schemas().await.unwrap();
+
+    assert!(names.contains(&"package-report".to_string()));
+    assert!(names.contains(&"rebuild-report".to_string()));
+    assert!(names.contains(&"queued-job".to_string()));
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,2 @@
+mod get_schema;
+mod get_schemas;
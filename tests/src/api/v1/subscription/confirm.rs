@@ -0,0 +1,175 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::smtp::{MailServer, mail_server};
+use crate::fixtures::*;
+use crate::setup;
+use rebuilderd_common::api::v1::{
+    ApiKeyScope, Subscription, SubscriptionConfirmation, SubscriptionRequest, SubscriptionRestApi,
+};
+use rebuilderd_common::config::ConfigFile;
+use rstest::rstest;
+
+const EMAIL: &str = "alice@example.com";
+
+fn mail_config(mail_server: &MailServer) -> ConfigFile {
+    config_with(|config| {
+        config.notify.smtp = Some(mail_server.smtp_url());
+        config.notify.mail_from = Some("rebuilderd <rebuilderd@example.com>".to_string());
+    })
+}
+
+fn confirmation_token(mail: &str) -> String {
+    mail.lines()
+        .find_map(|line| line.strip_prefix("rebuildctl subscriptions confirm "))
+        .expect("Expected a confirmation token in the mail")
+        .to_string()
+}
+
+/// Subscribes to the dummy package with an api key, the client is switched back to the auth cookie afterwards
+async fn subscribe_with_email(
+    isolated_server: &mut IsolatedServer,
+    config_file: &ConfigFile,
+) -> Subscription {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+    let subscription = client
+        .subscribe(SubscriptionRequest {
+            name: Some(DUMMY_SOURCE_PACKAGE.to_string()),
+            email: Some(EMAIL.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    client.auth_cookie(config_file.auth.cookie.clone().unwrap());
+
+    subscription
+}
+
+/// The package is BAD before, the good rebuild is a status change subscribers are notified about
+async fn fix_package(isolated_server: &IsolatedServer) {
+    request_rebuild_of_all_packages(&isolated_server.client).await;
+    report_good_rebuild(&isolated_server.client).await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn unconfirmed_address_gets_no_mail(
+    mail_server: MailServer,
+    #[with(mail_config(&mail_server))] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    setup::single_bad_rebuild(&isolated_server.client).await;
+
+    let subscription = subscribe_with_email(&mut isolated_server, &config_file).await;
+    assert!(!subscription.confirmed);
+    assert_eq!(1, mail_server.mails_to(EMAIL).len());
+
+    fix_package(&isolated_server).await;
+
+    // only the confirmation was mailed
+    let mails = mail_server.mails_to(EMAIL);
+    assert_eq!(1, mails.len());
+    assert!(mails[0].contains("Confirm your subscription"));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn confirmed_address_is_notified(
+    mail_server: MailServer,
+    #[with(mail_config(&mail_server))] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    setup::single_bad_rebuild(&isolated_server.client).await;
+
+    subscribe_with_email(&mut isolated_server, &config_file).await;
+    let token = confirmation_token(&mail_server.mails_to(EMAIL)[0]);
+    isolated_server
+        .client
+        .confirm_subscription(SubscriptionConfirmation { token })
+        .await
+        .unwrap();
+
+    fix_package(&isolated_server).await;
+
+    let mails = mail_server.mails_to(EMAIL);
+    assert_eq!(2, mails.len());
+    assert!(mails[1].contains("is GOOD now"));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn pending_address_is_not_mailed_again(
+    mail_server: MailServer,
+    #[with(mail_config(&mail_server))] config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &mut isolated_server.client;
+    let _config_file = config_file;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+    for name in ["xz", "openssl"] {
+        let subscription = client
+            .subscribe(SubscriptionRequest {
+                name: Some(name.to_string()),
+                email: Some(EMAIL.to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(!subscription.confirmed);
+    }
+
+    let mails = mail_server.mails_to(EMAIL);
+    assert_eq!(1, mails.len());
+
+    // one token confirms both subscriptions
+    let token = confirmation_token(&mails[0]);
+    client
+        .confirm_subscription(SubscriptionConfirmation { token })
+        .await
+        .unwrap();
+    let subscriptions = client.get_subscriptions().await.unwrap();
+    assert_eq!(2, subscriptions.len());
+    assert!(
+        subscriptions
+            .iter()
+            .all(|subscription| subscription.confirmed)
+    );
+
+    // the address is confirmed for this key, new subscriptions are active right away
+    let subscription = client
+        .subscribe(SubscriptionRequest {
+            maintainer: Some("Rust Maintainers <rust@example.com>".to_string()),
+            email: Some(EMAIL.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(subscription.confirmed);
+    assert_eq!(1, mail_server.mails_to(EMAIL).len());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_with_unknown_token(mut isolated_server: IsolatedServer) {
+    let result = isolated_server
+        .client
+        .confirm_subscription(SubscriptionConfirmation {
+            token: "does-not-exist".to_string(),
+        })
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
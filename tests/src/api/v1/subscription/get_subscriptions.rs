@@ -0,0 +1,54 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{ApiKeyScope, SubscriptionRequest, SubscriptionRestApi};
+use rstest::rstest;
+
+fn webhook_subscription(name: &str) -> SubscriptionRequest {
+    SubscriptionRequest {
+        name: Some(name.to_string()),
+        webhook: Some("https://192.0.2.1/hook".to_string()),
+        ..Default::default()
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn lists_only_own_subscriptions(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let alice = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    let bob = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+
+    client.auth_cookie(alice.key.clone());
+    let subscription = client.subscribe(webhook_subscription("xz")).await.unwrap();
+
+    client.auth_cookie(bob.key);
+    client
+        .subscribe(webhook_subscription("zstd"))
+        .await
+        .unwrap();
+    client.subscribe(webhook_subscription("lz4")).await.unwrap();
+    assert_eq!(2, client.get_subscriptions().await.unwrap().len());
+
+    client.auth_cookie(alice.key);
+    assert_eq!(
+        vec![subscription],
+        client.get_subscriptions().await.unwrap()
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_not_authenticated_with_api_key(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    client.auth_cookie("");
+    let result = client.get_subscriptions().await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
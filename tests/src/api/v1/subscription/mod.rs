@@ -0,0 +1,4 @@
+mod confirm;
+mod get_subscriptions;
+mod subscribe;
+mod unsubscribe;
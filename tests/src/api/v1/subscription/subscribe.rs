@@ -0,0 +1,185 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::smtp::{MailServer, mail_server};
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{ApiKeyScope, SubscriptionRequest, SubscriptionRestApi};
+use rebuilderd_common::config::ConfigFile;
+use rstest::rstest;
+
+const WEBHOOK: &str = "https://192.0.2.1/hook";
+
+#[rstest]
+#[tokio::test]
+pub async fn can_subscribe_to_package(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let subscription = client
+        .subscribe(SubscriptionRequest {
+            name: Some("xz".to_string()),
+            distribution: Some("debian".to_string()),
+            webhook: Some(WEBHOOK.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(Some("xz"), subscription.name.as_deref());
+    assert_eq!(None, subscription.maintainer);
+    assert_eq!(Some("debian"), subscription.distribution.as_deref());
+    assert_eq!(Some(WEBHOOK), subscription.webhook.as_deref());
+    assert!(subscription.confirmed);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_subscribe_to_maintainer(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let subscription = client
+        .subscribe(SubscriptionRequest {
+            maintainer: Some("Rust Maintainers <rust@example.com>".to_string()),
+            webhook: Some(WEBHOOK.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(None, subscription.name);
+    assert_eq!(
+        Some("Rust Maintainers <rust@example.com>"),
+        subscription.maintainer.as_deref()
+    );
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_not_authenticated_with_api_key(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    client.auth_cookie("");
+    let result = client
+        .subscribe(SubscriptionRequest {
+            name: Some("xz".to_string()),
+            webhook: Some(WEBHOOK.to_string()),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[case::nothing_followed(SubscriptionRequest {
+    webhook: Some(WEBHOOK.to_string()),
+    ..Default::default()
+})]
+#[case::name_and_maintainer(SubscriptionRequest {
+    name: Some("xz".to_string()),
+    maintainer: Some("Rust Maintainers <rust@example.com>".to_string()),
+    webhook: Some(WEBHOOK.to_string()),
+    ..Default::default()
+})]
+#[case::empty_name(SubscriptionRequest {
+    name: Some(String::new()),
+    webhook: Some(WEBHOOK.to_string()),
+    ..Default::default()
+})]
+#[case::no_target(SubscriptionRequest {
+    name: Some("xz".to_string()),
+    ..Default::default()
+})]
+#[case::internal_webhook(SubscriptionRequest {
+    name: Some("xz".to_string()),
+    webhook: Some("https://127.0.0.1/hook".to_string()),
+    ..Default::default()
+})]
+#[tokio::test]
+pub async fn rejects_invalid_subscriptions(
+    #[case] request: SubscriptionRequest,
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let result = client.subscribe(request).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn rejects_email_if_mail_is_not_configured(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let result = client
+        .subscribe(SubscriptionRequest {
+            name: Some("xz".to_string()),
+            email: Some("alice@example.com".to_string()),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_subscribe_with_email_if_mail_is_configured(
+    mail_server: MailServer,
+    #[with(config_with(|config| {
+        config.notify.smtp = Some(mail_server.smtp_url());
+        config.notify.mail_from = Some("rebuilderd <rebuilderd@example.com>".to_string());
+    }))]
+    config_file: ConfigFile,
+    #[with(config_file.clone())] mut isolated_server: IsolatedServer,
+) {
+    let client = &mut isolated_server.client;
+    let _config_file = config_file;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let subscription = client
+        .subscribe(SubscriptionRequest {
+            name: Some("xz".to_string()),
+            email: Some("alice@example.com".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(Some("alice@example.com"), subscription.email.as_deref());
+    assert!(!subscription.confirmed);
+    assert_eq!(1, mail_server.mails_to("alice@example.com").len());
+
+    let result = client
+        .subscribe(SubscriptionRequest {
+            name: Some("xz".to_string()),
+            email: Some("not an address".to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
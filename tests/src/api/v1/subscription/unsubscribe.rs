@@ -0,0 +1,64 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{ApiKeyScope, SubscriptionRequest, SubscriptionRestApi};
+use rstest::rstest;
+
+fn webhook_subscription() -> SubscriptionRequest {
+    SubscriptionRequest {
+        name: Some("xz".to_string()),
+        webhook: Some("https://192.0.2.1/hook".to_string()),
+        ..Default::default()
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_unsubscribe(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let subscription = client.subscribe(webhook_subscription()).await.unwrap();
+    client.unsubscribe(subscription.id).await.unwrap();
+
+    assert!(client.get_subscriptions().await.unwrap().is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn can_not_remove_subscriptions_of_other_keys(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let alice = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    let bob = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+
+    client.auth_cookie(alice.key.clone());
+    let subscription = client.subscribe(webhook_subscription()).await.unwrap();
+
+    client.auth_cookie(bob.key);
+    assert!(client.unsubscribe(subscription.id).await.is_err());
+
+    client.auth_cookie(alice.key);
+    assert_eq!(1, client.get_subscriptions().await.unwrap().len());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_404_for_unknown_subscription(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let issued = issue_api_key(client, ApiKeyScope::ReadOnly).await;
+    client.auth_cookie(issued.key);
+
+    let result = client.unsubscribe(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,71 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{JobAborted, QueueRestApi, WorkerRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn aborted_job_is_discarded_on_next_ping(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+    client.abort_worker(1).await.unwrap();
+
+    let err = client.ping_job(job.job.id).await.unwrap_err();
+    assert!(err.downcast_ref::<JobAborted>().is_some());
+
+    // the job is not handed to another worker
+    let result = client.get_queued_job(job.job.id).await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_is_idle(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let result = client.abort_worker(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.abort_worker(9999).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+    pick_up_job(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.abort_worker(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,115 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{
+    BuildRestApi, BuildStatus, JobAssignment, PackageRestApi, QueueRestApi, WorkerRestApi,
+};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn drained_worker_gets_no_work(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    client.drain_worker(1).await.unwrap();
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    let worker = client.get_worker(1).await.unwrap();
+    assert!(worker.is_draining);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn drained_worker_keeps_and_finishes_current_build(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+    let response = client.ping_job(job.job.id).await.unwrap();
+    assert!(!response.draining);
+
+    client.drain_worker(1).await.unwrap();
+
+    let response = client.ping_job(job.job.id).await.unwrap();
+    assert!(response.draining);
+
+    // the running job stays assigned to the worker instead of going back to the queue
+    let queued = client.get_queued_job(job.job.id).await.unwrap();
+    assert!(queued.started_at.is_some());
+
+    client
+        .submit_build_report(good_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Good), package.status);
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert!(jobs.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn draining_is_kept_when_worker_registers_again(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    client.drain_worker(1).await.unwrap();
+    register_worker(client).await;
+
+    let worker = client.get_worker(1).await.unwrap();
+    assert!(worker.is_draining);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.drain_worker(9999).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.drain_worker(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
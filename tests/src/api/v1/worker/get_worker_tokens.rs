@@ -0,0 +1,38 @@
+use crate::actions::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{WorkerCapability, WorkerRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn can_list_tokens(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    issue_worker_token(client, 1, WorkerCapability::Pop).await;
+    issue_worker_token(client, 1, WorkerCapability::Report).await;
+
+    let tokens = client.get_worker_tokens(1).await.unwrap();
+
+    assert_eq!(tokens.len(), 2);
+    assert!(tokens.iter().all(|token| token.revoked_at.is_none()));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.get_worker_tokens(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
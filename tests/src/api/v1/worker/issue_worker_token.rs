@@ -0,0 +1,127 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{
+    BuildRestApi, IssueWorkerTokenRequest, QueueRestApi, WorkerCapability, WorkerRestApi,
+};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn can_issue_token(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+
+    let issued = issue_worker_token(client, 1, WorkerCapability::Pop).await;
+
+    assert_eq!(issued.worker_id, 1);
+    assert_eq!(issued.capability, WorkerCapability::Pop);
+    assert!(!issued.token.is_empty());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn pop_token_can_request_work_but_not_report(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let issued = issue_worker_token(client, 1, WorkerCapability::Pop).await;
+    client.worker_token(issued.token);
+
+    let job = pick_up_job(client).await;
+    client.ping_job(job.job.id).await.unwrap();
+
+    let result = client.submit_build_report(good_rebuild_report(&job)).await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn report_token_can_report_but_not_request_work(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let job = pick_up_job(client).await;
+
+    let issued = issue_worker_token(client, 1, WorkerCapability::Report).await;
+    client.worker_token(issued.token);
+
+    let result = client.request_work(job_request()).await;
+    assert!(result.is_err());
+
+    client
+        .submit_build_report(good_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn unknown_token_is_rejected(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    // the worker key would be accepted, the invalid token must not fall back to it
+    client.worker_token("not-a-token");
+    let result = client.request_work(job_request()).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client
+        .issue_worker_token(
+            9999,
+            IssueWorkerTokenRequest {
+                capability: WorkerCapability::Pop,
+            },
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client
+        .issue_worker_token(
+            1,
+            IssueWorkerTokenRequest {
+                capability: WorkerCapability::Pop,
+            },
+        )
+        .await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
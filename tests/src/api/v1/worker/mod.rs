@@ -1,4 +1,13 @@
+mod abort_worker;
+mod drain_worker;
 mod get_worker;
+mod get_worker_tokens;
 mod get_workers;
+mod issue_worker_token;
 mod register_worker;
+mod resume_worker;
+mod revoke_worker_token;
+mod rotate_worker_key;
+mod trust_worker;
+mod unix_socket;
 mod unregister_worker;
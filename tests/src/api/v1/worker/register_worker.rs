@@ -1,8 +1,10 @@
 use crate::data::*;
 use crate::fixtures::server::IsolatedServer;
 use crate::fixtures::*;
-use rebuilderd_common::api::v1::{RegisterWorkerRequest, WorkerRestApi};
+use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd_common::api::v1::{RegisterWorkerRequest, WorkerEnvironment, WorkerRestApi};
 use rstest::rstest;
+use std::collections::BTreeMap;
 
 #[rstest]
 #[tokio::test]
@@ -12,6 +14,8 @@ pub async fn worker_can_sign_up(mut isolated_server: IsolatedServer) {
     client
         .register_worker(RegisterWorkerRequest {
             name: DUMMY_WORKER.to_string(),
+            environment: None,
+            uuid: None,
         })
         .await
         .unwrap();
@@ -29,6 +33,8 @@ pub async fn fails_if_no_signup_authentication_is_provided(mut isolated_server:
     let result = client
         .register_worker(RegisterWorkerRequest {
             name: DUMMY_WORKER.to_string(),
+            environment: None,
+            uuid: None,
         })
         .await;
 
@@ -36,3 +42,87 @@ pub async fn fails_if_no_signup_authentication_is_provided(mut isolated_server:
 
     isolated_server.shutdown().await;
 }
+
+#[rstest]
+#[tokio::test]
+pub async fn worker_environment_is_stored(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let environment = WorkerEnvironment {
+        worker_version: "0.26.0".to_string(),
+        kernel: Some("6.1.0".to_string()),
+        scripts: BTreeMap::from([("debian".to_string(), "deadbeef".to_string())]),
+        tools: BTreeMap::from([("diffoscope".to_string(), "diffoscope 301".to_string())]),
+    };
+
+    client
+        .register_worker(RegisterWorkerRequest {
+            name: DUMMY_WORKER.to_string(),
+            environment: Some(environment.clone()),
+            uuid: None,
+        })
+        .await
+        .unwrap();
+
+    let worker = client.get_worker(1).await.unwrap();
+
+    assert_eq!(Some(environment), worker.environment);
+
+    isolated_server.shutdown().await;
+}
+
+const DUMMY_WORKER_UUID: &str = "0b6a4bd6-1f4c-4d1e-9d4f-3f6c2a7e8b10";
+
+fn register_request_with_uuid() -> RegisterWorkerRequest {
+    RegisterWorkerRequest {
+        name: DUMMY_WORKER.to_string(),
+        environment: None,
+        uuid: Some(DUMMY_WORKER_UUID.to_string()),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn registering_again_with_the_same_uuid_keeps_one_worker(
+    mut isolated_server: IsolatedServer,
+) {
+    let client = &isolated_server.client;
+
+    client
+        .register_worker(register_request_with_uuid())
+        .await
+        .unwrap();
+    client
+        .register_worker(register_request_with_uuid())
+        .await
+        .unwrap();
+
+    let workers = client.get_workers(None).await.unwrap().records;
+
+    assert_eq!(workers.len(), 1);
+    assert_eq!(workers[0].uuid.as_deref(), Some(DUMMY_WORKER_UUID));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn known_uuid_with_a_different_key_is_rejected(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    client
+        .register_worker(register_request_with_uuid())
+        .await
+        .unwrap();
+
+    let worker_key = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    client.worker_key(worker_key);
+    let result = client.register_worker(register_request_with_uuid()).await;
+
+    assert!(result.is_err());
+
+    let workers = client.get_workers(None).await.unwrap().records;
+    assert_eq!(workers.len(), 1);
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,38 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{JobAssignment, QueueRestApi, WorkerRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn resumed_worker_gets_work_again(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    client.drain_worker(1).await.unwrap();
+    client.resume_worker(1).await.unwrap();
+
+    let worker = client.get_worker(1).await.unwrap();
+    assert!(!worker.is_draining);
+
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Rebuild(_)));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    let result = client.resume_worker(9999).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,55 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{QueueRestApi, WorkerCapability, WorkerRestApi};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn can_revoke_token(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    let issued = issue_worker_token(client, 1, WorkerCapability::Pop).await;
+
+    client.revoke_worker_token(1, issued.id).await.unwrap();
+
+    let token = client.get_worker_tokens(1).await.unwrap().pop().unwrap();
+    assert!(token.revoked_at.is_some());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn revoked_token_is_rejected(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    let issued = issue_worker_token(client, 1, WorkerCapability::Pop).await;
+    client.revoke_worker_token(1, issued.id).await.unwrap();
+
+    client.worker_token(issued.token);
+    let result = client.request_work(job_request()).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn returns_404_for_unknown_token(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+
+    let result = client.revoke_worker_token(1, 1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,144 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use data_encoding::BASE64;
+use in_toto::crypto::{KeyType, PrivateKey, SignatureScheme};
+use rebuilderd_common::api::v1::{
+    QueueRestApi, RotateWorkerKeyRequest, WorkerCapability, WorkerRestApi, key_rotation_message,
+};
+use rstest::rstest;
+
+fn keypair() -> (PrivateKey, String) {
+    let privkey = PrivateKey::new(KeyType::Ed25519).unwrap();
+    let privkey = PrivateKey::from_pkcs8(&privkey, SignatureScheme::Ed25519).unwrap();
+    let pubkey = BASE64.encode(privkey.public().as_bytes());
+    (privkey, pubkey)
+}
+
+fn rotation(privkey: &PrivateKey, current_key: &str, new_key: &str) -> RotateWorkerKeyRequest {
+    let signature = privkey
+        .sign(key_rotation_message(current_key, new_key).as_bytes())
+        .unwrap();
+    let signature = serde_json::to_value(&signature).unwrap();
+    RotateWorkerKeyRequest {
+        new_key: new_key.to_string(),
+        signature: signature["sig"].as_str().unwrap().to_string(),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn worker_keeps_identity_after_rotation(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let (current, current_key) = keypair();
+    let (_, new_key) = keypair();
+    client.worker_key(current_key.clone());
+    register_worker(client).await;
+
+    let rotated = client
+        .rotate_worker_key(rotation(&current, &current_key, &new_key))
+        .await
+        .unwrap();
+    assert_eq!(rotated.worker_id, 1);
+
+    client.worker_key(new_key);
+    import_single_package(client).await;
+    let job = pick_up_job(client).await;
+    client.ping_job(job.job.id).await.unwrap();
+
+    let workers = client.get_workers(None).await.unwrap();
+    assert_eq!(workers.total, 1);
+    assert_eq!(workers.records[0].name, DUMMY_WORKER);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn previous_key_is_accepted_during_grace_period(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let (current, current_key) = keypair();
+    let (_, new_key) = keypair();
+    client.worker_key(current_key.clone());
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    client
+        .rotate_worker_key(rotation(&current, &current_key, &new_key))
+        .await
+        .unwrap();
+
+    // still authenticated as the same worker with the old key
+    let job = pick_up_job(client).await;
+    client.worker_key(new_key);
+    client.ping_job(job.job.id).await.unwrap();
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn rotation_must_be_signed_by_current_key(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let (_, current_key) = keypair();
+    let (new, new_key) = keypair();
+    client.worker_key(current_key.clone());
+    register_worker(client).await;
+
+    let result = client
+        .rotate_worker_key(rotation(&new, &current_key, &new_key))
+        .await;
+    assert!(result.is_err());
+
+    client.worker_key(new_key);
+    let result = client.request_work(job_request()).await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn cannot_rotate_to_registered_key(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let (_, other_key) = keypair();
+    client.worker_key(other_key.clone());
+    register_other_worker(client).await;
+
+    let (current, current_key) = keypair();
+    client.worker_key(current_key.clone());
+    register_worker(client).await;
+
+    let result = client
+        .rotate_worker_key(rotation(&current, &current_key, &other_key))
+        .await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn token_cannot_rotate_key(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    let (current, current_key) = keypair();
+    let (_, new_key) = keypair();
+    client.worker_key(current_key.clone());
+    register_worker(client).await;
+
+    let issued = issue_worker_token(client, 1, WorkerCapability::Pop).await;
+    client.worker_token(issued.token);
+
+    let result = client
+        .rotate_worker_key(rotation(&current, &current_key, &new_key))
+        .await;
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
@@ -0,0 +1,113 @@
+use crate::actions::*;
+use crate::data::*;
+use crate::fixtures::server::IsolatedServer;
+use crate::fixtures::*;
+use rebuilderd_common::api::v1::{
+    BuildRestApi, BuildStatus, JobAssignment, PackageRestApi, QueueRestApi, WorkerRestApi,
+};
+use rstest::rstest;
+
+#[rstest]
+#[tokio::test]
+pub async fn untrusted_report_is_provisional(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    client.untrust_worker(1).await.unwrap();
+    let worker = client.get_worker(1).await.unwrap();
+    assert!(!worker.is_trusted);
+
+    let job = pick_up_job(client).await;
+    client
+        .submit_build_report(good_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_ne!(Some(BuildStatus::Good), package.status);
+
+    let jobs = client
+        .get_queued_jobs(None, None, None)
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(1, jobs.len());
+
+    // the provisional result needs to be confirmed by a trusted worker
+    let job = client.request_work(job_request()).await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn trusted_worker_confirms_provisional_result(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    register_worker(client).await;
+    import_single_package(client).await;
+
+    client.untrust_worker(1).await.unwrap();
+    let job = pick_up_job(client).await;
+    client
+        .submit_build_report(good_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    client.trust_worker(1).await.unwrap();
+    let worker = client.get_worker(1).await.unwrap();
+    assert!(worker.is_trusted);
+
+    let job = pick_up_job(client).await;
+    client
+        .submit_build_report(good_rebuild_report(&job))
+        .await
+        .unwrap();
+
+    let package = client
+        .get_source_packages(None, None, None)
+        .await
+        .map(|p| p.records)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(Some(BuildStatus::Good), package.status);
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_worker_does_not_exist(mut isolated_server: IsolatedServer) {
+    let client = &isolated_server.client;
+
+    assert!(client.trust_worker(9999).await.is_err());
+    assert!(client.untrust_worker(9999).await.is_err());
+
+    isolated_server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn fails_if_no_admin_authentication_is_provided(mut isolated_server: IsolatedServer) {
+    let client = &mut isolated_server.client;
+
+    register_worker(client).await;
+
+    // zero out cookie
+    client.auth_cookie("");
+    let result = client.untrust_worker(1).await;
+
+    assert!(result.is_err());
+
+    isolated_server.shutdown().await;
+}
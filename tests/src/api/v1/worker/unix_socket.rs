@@ -0,0 +1,169 @@
+use crate::data::*;
+use crate::fixtures::*;
+use actix_web::dev::ServerHandle;
+use in_toto::crypto::PrivateKey;
+use rebuilderd::Listener;
+use rebuilderd::config::LiveConfig;
+use rebuilderd::db;
+use rebuilderd_common::api::v1::{JobAssignment, RegisterWorkerRequest};
+use rebuilderd_common::api::{SIGNUP_SECRET_HEADER, WORKER_KEY_HEADER};
+use rebuilderd_common::config::ConfigFile;
+use reqwest::StatusCode;
+use rstest::rstest;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio_util::task::AbortOnDropHandle;
+
+const WORKER_KEY: &str = "unix-socket-worker";
+
+/// A daemon that only listens on a unix socket, like one with `bind_addr = "unix:..."` behind a reverse proxy
+struct UnixSocketServer {
+    handle: ServerHandle,
+    join: AbortOnDropHandle<io::Result<()>>,
+    client: reqwest::Client,
+    signup_secret: String,
+    _tmp_dir: TempDir,
+}
+
+impl UnixSocketServer {
+    async fn start(config_file: ConfigFile, private_key: PrivateKey) -> Self {
+        let tmp_dir = TempDir::new().unwrap();
+        let socket = tmp_dir.path().join("rebuilderd.sock");
+        let database_path = tmp_dir.path().join("rebuilderd.db");
+
+        let signup_secret = config_file.worker.signup_secret.clone().unwrap();
+        let mut config = rebuilderd::config::from_struct(
+            config_file.clone(),
+            config_file.auth.cookie.clone().unwrap(),
+        )
+        .unwrap();
+        config.bind_addr = format!("unix:{}", socket.display());
+
+        let pool = db::setup_pool(database_path.to_str().unwrap(), true, None).unwrap();
+        let (server, _) = rebuilderd::build_listener(
+            pool,
+            Arc::new(LiveConfig::new(config, None)),
+            Arc::new(private_key),
+            Listener::Admin,
+        )
+        .unwrap();
+        let handle = server.handle();
+        let join = AbortOnDropHandle::new(tokio::spawn(server));
+
+        for _ in 0..100 {
+            if tokio::net::UnixStream::connect(&socket).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let client = reqwest::Client::builder()
+            .unix_socket(socket)
+            .build()
+            .unwrap();
+
+        Self {
+            handle,
+            join,
+            client,
+            signup_secret,
+            _tmp_dir: tmp_dir,
+        }
+    }
+
+    async fn register_worker(&self, real_ip: Option<&str>) -> StatusCode {
+        let mut request = self
+            .client
+            .post("http://localhost/api/v1/workers")
+            .header(WORKER_KEY_HEADER, WORKER_KEY)
+            .header(SIGNUP_SECRET_HEADER, &self.signup_secret)
+            .json(&RegisterWorkerRequest {
+                name: DUMMY_WORKER.to_string(),
+                environment: None,
+                uuid: None,
+            });
+        if let Some(real_ip) = real_ip {
+            request = request.header("X-Real-IP", real_ip);
+        }
+        request.send().await.unwrap().status()
+    }
+
+    async fn request_work(&self, real_ip: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .client
+            .post("http://localhost/api/v1/worker/jobs/pop")
+            .header(WORKER_KEY_HEADER, WORKER_KEY)
+            .json(&job_request());
+        if let Some(real_ip) = real_ip {
+            request = request.header("X-Real-IP", real_ip);
+        }
+        request.send().await.unwrap()
+    }
+
+    async fn shutdown(self) {
+        self.handle.stop(false).await;
+        self.join.await.unwrap().unwrap();
+    }
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn worker_can_connect_over_unix_socket(config_file: ConfigFile, private_key: PrivateKey) {
+    let server = UnixSocketServer::start(config_file, private_key).await;
+
+    assert!(server.register_worker(None).await.is_success());
+
+    let response = server.request_work(None).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let job = response.json::<JobAssignment>().await.unwrap();
+    assert!(matches!(job, JobAssignment::Nothing));
+
+    server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn unix_socket_workers_are_rejected_by_network_policy(
+    #[with(config_with(|config| {
+        config.worker.allowed_networks = vec!["192.0.2.0/24".to_string()];
+    }))]
+    config_file: ConfigFile,
+    private_key: PrivateKey,
+) {
+    let server = UnixSocketServer::start(config_file, private_key).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, server.register_worker(None).await);
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        server.request_work(None).await.status()
+    );
+
+    server.shutdown().await;
+}
+
+#[rstest]
+#[tokio::test]
+pub async fn unix_socket_workers_are_checked_by_real_ip_header(
+    #[with(config_with(|config| {
+        config.http.real_ip_header = Some("X-Real-IP".to_string());
+        config.worker.allowed_networks = vec!["192.0.2.0/24".to_string()];
+    }))]
+    config_file: ConfigFile,
+    private_key: PrivateKey,
+) {
+    let server = UnixSocketServer::start(config_file, private_key).await;
+
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        server.register_worker(Some("198.51.100.1")).await
+    );
+    assert!(server.register_worker(Some("192.0.2.1")).await.is_success());
+    assert_eq!(
+        StatusCode::OK,
+        server.request_work(Some("192.0.2.1")).await.status()
+    );
+
+    server.shutdown().await;
+}
@@ -16,6 +16,9 @@ pub fn bad_rebuild_report(job: &QueuedJobWithArtifacts) -> RebuildReport {
             diffoscope: Some(DUMMY_DIFFOSCOPE.to_string().into_bytes()),
             status: ArtifactStatus::Bad,
             attestation: None,
+            diff_summary: None,
+            checksum: None,
+            content: None,
         });
     }
 
@@ -25,6 +28,7 @@ pub fn bad_rebuild_report(job: &QueuedJobWithArtifacts) -> RebuildReport {
         build_log: DUMMY_BUILD_LOG.to_string().into_bytes(),
         status: BuildStatus::Bad,
         artifacts,
+        downloaded_bytes: None,
     }
 }
 
@@ -35,6 +39,18 @@ pub fn failed_rebuild_report(job: &QueuedJobWithArtifacts) -> RebuildReport {
         build_log: DUMMY_BUILD_LOG.to_string().into_bytes(),
         status: BuildStatus::Fail,
         artifacts: vec![],
+        downloaded_bytes: None,
+    }
+}
+
+pub fn download_failed_rebuild_report(job: &QueuedJobWithArtifacts) -> RebuildReport {
+    RebuildReport {
+        queue_id: job.job.id,
+        built_at: Utc::now().naive_utc(),
+        build_log: DUMMY_BUILD_LOG.to_string().into_bytes(),
+        status: BuildStatus::DownloadFailed,
+        artifacts: vec![],
+        downloaded_bytes: None,
     }
 }
 
@@ -46,6 +62,9 @@ pub fn good_rebuild_report(job: &QueuedJobWithArtifacts) -> RebuildReport {
             diffoscope: None,
             status: ArtifactStatus::Good,
             attestation: None,
+            diff_summary: None,
+            checksum: None,
+            content: None,
         });
     }
 
@@ -55,6 +74,7 @@ pub fn good_rebuild_report(job: &QueuedJobWithArtifacts) -> RebuildReport {
         build_log: DUMMY_BUILD_LOG.to_string().into_bytes(),
         status: BuildStatus::Good,
         artifacts,
+        downloaded_bytes: None,
     }
 }
 
@@ -72,6 +92,9 @@ pub async fn good_rebuild_report_with_signed_attestation(
             diffoscope: None,
             status: ArtifactStatus::Good,
             attestation: Some(zstd_compress(attestation.as_bytes()).await.unwrap()),
+            diff_summary: None,
+            checksum: None,
+            content: None,
         });
     }
 
@@ -81,6 +104,7 @@ pub async fn good_rebuild_report_with_signed_attestation(
         build_log: DUMMY_BUILD_LOG.to_string().into_bytes(),
         status: BuildStatus::Good,
         artifacts,
+        downloaded_bytes: None,
     }
 }
 
@@ -98,6 +122,9 @@ pub async fn good_rebuild_report_with_unsigned_attestation(
             diffoscope: None,
             status: ArtifactStatus::Good,
             attestation: Some(zstd_compress(attestation.as_bytes()).await.unwrap()),
+            diff_summary: None,
+            checksum: None,
+            content: None,
         });
     }
 
@@ -107,5 +134,6 @@ pub async fn good_rebuild_report_with_unsigned_attestation(
         build_log: DUMMY_BUILD_LOG.to_string().into_bytes(),
         status: BuildStatus::Good,
         artifacts,
+        downloaded_bytes: None,
     }
 }
@@ -1,10 +1,13 @@
 use crate::data::{DUMMY_ARCHITECTURE, DUMMY_BACKEND};
 use rebuilderd_common::api::v1::PopQueuedJobRequest;
+use std::collections::HashMap;
 
 pub fn job_request() -> PopQueuedJobRequest {
     PopQueuedJobRequest {
         supported_backends: vec![DUMMY_BACKEND.to_string()],
         architecture: DUMMY_ARCHITECTURE.to_string(),
         supported_architectures: vec![DUMMY_ARCHITECTURE.to_string()],
+        mirrors: HashMap::new(),
+        cross_architectures: Vec::new(),
     }
 }
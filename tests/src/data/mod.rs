@@ -24,6 +24,7 @@ pub const DUMMY_BACKEND: &str = DUMMY_DISTRIBUTION;
 pub const DUMMY_OTHER_BACKEND: &str = DUMMY_OTHER_DISTRIBUTION;
 pub const DUMMY_WORKER: &str = "worker";
 pub const DUMMY_OTHER_WORKER: &str = "other-worker";
+pub const DUMMY_API_KEY_NAME: &str = "api-key";
 
 pub fn create_dummy_signed_attestation(input_name: &str, output_name: &str) -> String {
     let private_key = PrivateKey::from_pkcs8(
@@ -7,6 +7,8 @@ pub const DUMMY_SOURCE_PACKAGE_URL: &str = "https://placeholder.org/foo-1.buildi
 pub const DUMMY_BINARY_PACKAGE: &str = "foo";
 pub const DUMMY_BINARY_PACKAGE_VERSION: &str = "1";
 pub const DUMMY_BINARY_PACKAGE_URL: &str = "https://placeholder.org/foo-1.tar.zst";
+pub const DUMMY_BINARY_PACKAGE_CHECKSUM: &str =
+    "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
 
 pub fn single_package_report() -> PackageReport {
     PackageReport {
@@ -14,15 +16,19 @@ pub fn single_package_report() -> PackageReport {
         release: Some(DUMMY_RELEASE.to_string()),
         component: Some(DUMMY_COMPONENT.to_string()),
         architecture: DUMMY_ARCHITECTURE.to_string(),
+        source: None,
         packages: vec![SourcePackageReport {
             name: DUMMY_SOURCE_PACKAGE.to_string(),
             version: DUMMY_SOURCE_PACKAGE_VERSION.to_string(),
             url: DUMMY_SOURCE_PACKAGE_URL.to_string(),
+            payload: None,
+            maintainer: None,
             artifacts: vec![BinaryPackageReport {
                 name: DUMMY_BINARY_PACKAGE.to_string(),
                 version: DUMMY_BINARY_PACKAGE_VERSION.to_string(),
                 architecture: DUMMY_ARCHITECTURE.to_string(),
                 url: DUMMY_BINARY_PACKAGE_URL.to_string(),
+                checksum: None,
             }],
         }],
     }
@@ -46,22 +52,27 @@ pub fn single_package_with_multiple_artifacts_report() -> PackageReport {
         release: Some(DUMMY_RELEASE.to_string()),
         component: Some(DUMMY_COMPONENT.to_string()),
         architecture: DUMMY_ARCHITECTURE.to_string(),
+        source: None,
         packages: vec![SourcePackageReport {
             name: DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE.to_string(),
             version: DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE_VERSION.to_string(),
             url: DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE_URL.to_string(),
+            payload: None,
+            maintainer: None,
             artifacts: vec![
                 BinaryPackageReport {
                     name: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_1.to_string(),
                     version: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_1_VERSION.to_string(),
                     architecture: DUMMY_ARCHITECTURE.to_string(),
                     url: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_1_URL.to_string(),
+                    checksum: None,
                 },
                 BinaryPackageReport {
                     name: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2.to_string(),
                     version: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2_VERSION.to_string(),
                     architecture: DUMMY_ARCHITECTURE.to_string(),
                     url: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2_URL.to_string(),
+                    checksum: None,
                 },
             ],
         }],
@@ -82,6 +93,31 @@ pub fn single_package_report_from_different_release() -> PackageReport {
     }
 }
 
+pub const DUMMY_OTHER_SOURCE_PACKAGE: &str = "qux";
+pub const DUMMY_OTHER_SOURCE_PACKAGE_URL: &str = "https://placeholder.org/qux-1.buildinfo.txt";
+pub const DUMMY_OTHER_BINARY_PACKAGE_URL: &str = "https://placeholder.org/qux-1.tar.zst";
+
+pub fn other_package_report_from_different_release() -> PackageReport {
+    PackageReport {
+        release: Some(DUMMY_OTHER_RELEASE.to_string()),
+        packages: vec![SourcePackageReport {
+            name: DUMMY_OTHER_SOURCE_PACKAGE.to_string(),
+            version: DUMMY_SOURCE_PACKAGE_VERSION.to_string(),
+            url: DUMMY_OTHER_SOURCE_PACKAGE_URL.to_string(),
+            payload: None,
+            maintainer: None,
+            artifacts: vec![BinaryPackageReport {
+                name: DUMMY_OTHER_SOURCE_PACKAGE.to_string(),
+                version: DUMMY_SOURCE_PACKAGE_VERSION.to_string(),
+                architecture: DUMMY_ARCHITECTURE.to_string(),
+                url: DUMMY_OTHER_BINARY_PACKAGE_URL.to_string(),
+                checksum: None,
+            }],
+        }],
+        ..single_package_report()
+    }
+}
+
 pub fn single_package_report_from_different_component() -> PackageReport {
     PackageReport {
         component: Some(DUMMY_OTHER_COMPONENT.to_string()),
@@ -109,34 +145,42 @@ pub fn multiple_package_report() -> PackageReport {
         release: Some(DUMMY_RELEASE.to_string()),
         component: Some(DUMMY_COMPONENT.to_string()),
         architecture: DUMMY_ARCHITECTURE.to_string(),
+        source: None,
         packages: vec![
             SourcePackageReport {
                 name: DUMMY_SOURCE_PACKAGE.to_string(),
                 version: DUMMY_SOURCE_PACKAGE_VERSION.to_string(),
                 url: DUMMY_SOURCE_PACKAGE_URL.to_string(),
+                payload: None,
+                maintainer: None,
                 artifacts: vec![BinaryPackageReport {
                     name: DUMMY_BINARY_PACKAGE.to_string(),
                     version: DUMMY_BINARY_PACKAGE_VERSION.to_string(),
                     architecture: DUMMY_ARCHITECTURE.to_string(),
                     url: DUMMY_BINARY_PACKAGE_URL.to_string(),
+                    checksum: None,
                 }],
             },
             SourcePackageReport {
                 name: DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE.to_string(),
                 version: DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE_VERSION.to_string(),
                 url: DUMMY_MULTI_ARTIFACT_SOURCE_PACKAGE_URL.to_string(),
+                payload: None,
+                maintainer: None,
                 artifacts: vec![
                     BinaryPackageReport {
                         name: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_1.to_string(),
                         version: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_1_VERSION.to_string(),
                         architecture: DUMMY_ARCHITECTURE.to_string(),
                         url: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_1_URL.to_string(),
+                        checksum: None,
                     },
                     BinaryPackageReport {
                         name: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2.to_string(),
                         version: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2_VERSION.to_string(),
                         architecture: DUMMY_ARCHITECTURE.to_string(),
                         url: DUMMY_MULTI_ARTIFACT_BINARY_PACKAGE_2_URL.to_string(),
+                        checksum: None,
                     },
                 ],
             },
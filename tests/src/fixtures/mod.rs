@@ -1,15 +1,19 @@
 pub mod server;
+pub mod smtp;
 
 use crate::args::Args;
 use crate::fixtures::server::{IsolatedServer, ServerHolder};
 use clap::Parser;
 use in_toto::crypto::{KeyType, PrivateKey, SignatureScheme};
 use rand::distr::{Alphanumeric, SampleString};
+use rebuilderd::Listener;
+use rebuilderd::config::LiveConfig;
 use rebuilderd::db;
 use rebuilderd_common::api::Client;
 use rebuilderd_common::config::{ConfigFile, EndpointConfig};
 use rebuilderd_common::errors::info;
 use rstest::fixture;
+use std::sync::Arc;
 use tempfile::TempDir;
 
 #[fixture]
@@ -17,15 +21,19 @@ pub fn program_arguments() -> Args {
     Args::parse()
 }
 
+/// Builds a config file for the `config_file` fixture, e.g.
+/// `#[with(config_with(|config| config.schedule.max_retries = Some(1)))]`.
+pub fn config_with(change: impl FnOnce(&mut ConfigFile)) -> ConfigFile {
+    let mut config = ConfigFile::default();
+    change(&mut config);
+    config
+}
+
 #[fixture]
 pub fn config_file(
-    #[default(None)] retry_delay_base: Option<i64>,
-    #[default(None)] max_retries: Option<i32>,
-    #[default(None)] initial_delay: Option<i64>,
+    #[default(ConfigFile::default())] mut config: ConfigFile,
     program_arguments: Args,
 ) -> ConfigFile {
-    let mut config = ConfigFile::default();
-
     let cookie = program_arguments
         .cookie
         .unwrap_or(Alphanumeric.sample_string(&mut rand::rng(), 32));
@@ -48,13 +56,14 @@ pub fn config_file(
         },
     );
 
-    config.schedule.retry_delay_base = retry_delay_base;
-    config.schedule.max_retries = max_retries;
-    config.schedule.initial_delay = initial_delay;
-
     config
 }
 
+#[fixture]
+pub fn blob_dir() -> TempDir {
+    TempDir::new().unwrap()
+}
+
 #[fixture]
 pub fn private_key() -> PrivateKey {
     let privkey = PrivateKey::new(KeyType::Ed25519).expect("Failed to generate private key");
@@ -90,27 +99,46 @@ pub fn isolated_server(
     )
     .unwrap();
 
-    let (server, tmp_dir, endpoint) = if !program_arguments.no_daemon {
+    let (server, public_server, tmp_dir, endpoint) = if !program_arguments.no_daemon {
         let tmp_dir = TempDir::new().unwrap();
         let database_path = tmp_dir.path().join("rebuilderd.db");
 
-        let pool = db::setup_pool(database_path.to_str().unwrap()).unwrap();
+        let pool = db::setup_pool(database_path.to_str().unwrap(), true, None).unwrap();
+
+        let has_public_listener = config.public_bind_addr.is_some();
+        let config = Arc::new(LiveConfig::new(config, None));
+        let private_key = Arc::new(private_key);
 
-        let mut server = ServerHolder::new(pool.clone(), config, private_key).unwrap();
+        let mut server = ServerHolder::new(
+            pool.clone(),
+            config.clone(),
+            private_key.clone(),
+            Listener::Admin,
+        )
+        .unwrap();
         server.start().unwrap();
 
+        let public_server = if has_public_listener {
+            let mut server =
+                ServerHolder::new(pool, config, private_key, Listener::Public).unwrap();
+            server.start().unwrap();
+            Some(server)
+        } else {
+            None
+        };
+
         let endpoint = format!("http://{}", server.address);
-        (Some(server), Some(tmp_dir), endpoint)
+        (Some(server), public_server, Some(tmp_dir), endpoint)
     } else {
         let addr = program_arguments.bind_addr;
         let endpoint = program_arguments
             .endpoint
             .unwrap_or_else(|| format!("http://{}", addr));
 
-        (None, None, endpoint)
+        (None, None, None, endpoint)
     };
 
     let client = make_client(config_file, endpoint);
 
-    IsolatedServer::new(server, tmp_dir, public_key, client)
+    IsolatedServer::new(server, public_server, tmp_dir, public_key, client)
 }
@@ -1,11 +1,13 @@
 use actix_web::dev::{Server, ServerHandle};
 use in_toto::crypto::{PrivateKey, PublicKey};
-use rebuilderd::config::Config;
+use rebuilderd::Listener;
+use rebuilderd::config::LiveConfig;
 use rebuilderd::db::Pool;
 use rebuilderd_common::api::Client;
-use rebuilderd_common::errors::bail;
+use rebuilderd_common::errors::{Context, bail};
 use std::io;
 use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use tempfile::TempDir;
@@ -21,10 +23,12 @@ pub struct ServerHolder {
 impl ServerHolder {
     pub fn new(
         pool: Pool,
-        config: Config,
-        private_key: PrivateKey,
+        config: Arc<LiveConfig>,
+        private_key: Arc<PrivateKey>,
+        listener: Listener,
     ) -> rebuilderd_common::errors::Result<Self> {
-        let (server, address) = rebuilderd::build_server(pool, config, private_key)?;
+        let (server, address) = rebuilderd::build_listener(pool, config, private_key, listener)?;
+        let address = address.context("Failed to determine bind address")?;
 
         Ok(Self {
             server: Some(server),
@@ -75,6 +79,8 @@ impl Drop for ServerHolder {
 
 pub struct IsolatedServer {
     server: Option<ServerHolder>,
+    /// Only started if the config has a `public_bind_addr`
+    public_server: Option<ServerHolder>,
     pub _tmp_dir: Option<TempDir>,
     pub public_key: PublicKey,
     pub client: Client,
@@ -83,21 +89,32 @@ pub struct IsolatedServer {
 impl IsolatedServer {
     pub fn new(
         server: Option<ServerHolder>,
+        public_server: Option<ServerHolder>,
         tmp_dir: Option<TempDir>,
         public_key: PublicKey,
         client: Client,
     ) -> Self {
         Self {
             server,
+            public_server,
             _tmp_dir: tmp_dir,
             public_key,
             client,
         }
     }
 
+    /// The url of the read-only public listener
+    pub fn public_endpoint(&self) -> Option<String> {
+        let server = self.public_server.as_ref()?;
+        Some(format!("http://{}", server.address))
+    }
+
     pub async fn shutdown(&mut self) {
         if let Some(server) = self.server.take() {
             server.shutdown().await;
         }
+        if let Some(server) = self.public_server.take() {
+            server.shutdown().await;
+        }
     }
 }
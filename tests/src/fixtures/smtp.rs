@@ -0,0 +1,93 @@
+use rstest::fixture;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Accepts every mail that is sent to it and keeps the raw messages, so tests can check what was mailed
+#[derive(Clone)]
+pub struct MailServer {
+    addr: String,
+    mails: Arc<Mutex<Vec<String>>>,
+}
+
+impl MailServer {
+    pub fn smtp_url(&self) -> String {
+        format!("smtp://{}", self.addr)
+    }
+
+    /// The raw messages received so far, including their headers
+    pub fn mails(&self) -> Vec<String> {
+        self.mails.lock().unwrap().clone()
+    }
+
+    /// The mails that were sent to an address
+    pub fn mails_to(&self, address: &str) -> Vec<String> {
+        let recipient = format!("To: {address}");
+        self.mails()
+            .into_iter()
+            .filter(|mail| mail.lines().any(|line| line == recipient))
+            .collect()
+    }
+
+    fn serve(stream: TcpStream, mails: &Mutex<Vec<String>>) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        writer.write_all(b"220 localhost ESMTP\r\n")?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            let command = line.trim_end().to_ascii_uppercase();
+            if command.starts_with("DATA") {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")?;
+                let mut mail = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Ok(());
+                    }
+                    if line == ".\r\n" {
+                        break;
+                    }
+                    mail.push_str(line.trim_end_matches("\r\n"));
+                    mail.push('\n');
+                }
+                // the mail is recorded before it's acknowledged, the sender only returns once it's stored
+                mails.lock().unwrap().push(mail);
+                writer.write_all(b"250 OK\r\n")?;
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 Bye\r\n")?;
+                return Ok(());
+            } else {
+                writer.write_all(b"250 OK\r\n")?;
+            }
+        }
+    }
+}
+
+#[fixture]
+pub fn mail_server() -> MailServer {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let server = MailServer {
+        addr: listener.local_addr().unwrap().to_string(),
+        mails: Arc::default(),
+    };
+
+    let mails = server.mails.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let mails = mails.clone();
+            thread::spawn(move || MailServer::serve(stream, &mails));
+        }
+    });
+
+    server
+}
@@ -33,6 +33,17 @@ pub async fn single_good_rebuild(client: &Client) {
     report_good_rebuild(client).await;
 }
 
+/// Only results in a FLAKY package if the server is configured with a `flaky_threshold` of two or less.
+pub async fn single_flaky_rebuild(client: &Client) {
+    register_worker(client).await;
+    import_single_package(client).await;
+    report_bad_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_good_rebuild(client).await;
+    request_rebuild_of_all_packages(client).await;
+    report_bad_rebuild(client).await;
+}
+
 pub async fn single_failed_rebuild(client: &Client) {
     register_worker(client).await;
     import_single_package(client).await;
@@ -1,7 +1,8 @@
-use clap::{ArgAction, CommandFactory, Parser};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{ArgAction, ArgGroup, CommandFactory, Parser};
 use clap_complete::Shell;
 use glob::Pattern;
-use rebuilderd_common::api::v1::ArtifactStatus;
+use rebuilderd_common::api::v1::{ApiKeyScope, ArtifactStatus, TransitionKind, WorkerCapability};
 use rebuilderd_common::errors::*;
 use std::io;
 use std::path::PathBuf;
@@ -28,33 +29,91 @@ pub struct Args {
 #[derive(Debug, Parser)]
 pub enum SubCommand {
     /// Show worker status
-    Status,
+    Status(Status),
     /// Package related subcommands
     #[command(subcommand)]
     Pkgs(Pkgs),
     /// Queue related subcommands
     #[command(subcommand)]
     Queue(Queue),
+    /// Manage locally stored credentials
+    #[command(subcommand)]
+    Auth(Auth),
+    /// Manage scoped api keys on the daemon
+    #[command(subcommand)]
+    Keys(Keys),
+    /// Manage build status notifications for the current api key
+    #[command(subcommand)]
+    Subscriptions(Subscriptions),
+    /// Worker related subcommands
+    #[command(subcommand)]
+    Workers(Workers),
+    /// Browse packages that failed to reproduce and act on them interactively
+    Triage(Triage),
     /// Generate shell completions
     Completions(Completions),
 }
 
+#[derive(Debug, Parser)]
+pub struct Status {
+    /// Also show the environment fingerprint reported by each worker
+    #[arg(long)]
+    pub env: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct Triage {
+    /// Only show packages of this distro
+    #[arg(long)]
+    pub distro: Option<String>,
+    /// Only show packages of this suite
+    #[arg(long)]
+    pub suite: Option<String>,
+    /// Only show packages of this architecture
+    #[arg(long)]
+    pub architecture: Option<String>,
+}
+
 #[derive(Debug, Parser)]
 pub enum Pkgs {
     /// Sync package index
     Sync(PkgsSync),
     /// List known packages
     Ls(PkgsList),
+    /// Compare an upstream release across all distributions that ship it
+    Upstream(PkgsUpstream),
+    /// List GOOD artifacts whose rebuilt checksum doesn't match the published one
+    Verify(PkgsVerify),
+    /// List rebuilds that changed the status of a package between GOOD and BAD
+    Transitions(PkgsTransitions),
+    /// List tagged packages
+    Tags(PkgsTags),
+    /// Attach a tag to all versions of a package
+    Tag(PkgsTag),
+    /// Remove a tag from a package
+    Untag(PkgsTag),
+    /// Record a bug report or triage note for all versions of a package
+    Annotate(PkgsAnnotate),
+    /// Correct the artifact url, version or status of a binary package that a sync imported wrongly
+    Correct(PkgsCorrect),
+    /// Import estimated build durations, used by the interleave-durations job order
+    Durations(PkgsDurations),
     /// Sync package index with profile
     SyncProfile(PkgsSyncProfile),
     /// Read a package sync from stdin
     SyncStdin(PkgsSyncStdin),
+    /// Queue packages for another rebuild, eg. after a reproducibility fix landed in the toolchain
+    Requeue(PkgsRequeue),
     /// Access the build log of the last rebuild
     Log(PkgsLog),
     /// Access the diffoscope of the last rebuild (if there is any)
     Diffoscope(PkgsDiffoscope),
     /// Access the attestation of the last rebuild (if there is any)
     Attestation(PkgsAttestation),
+    /// Rebuild a package once per variation of the build environment, to find out what it is sensitive to
+    Bisect(PkgsBisect),
+    /// Show the builds of a bisect and the variations that changed the verdict
+    BisectResult(PkgsBisectResult),
 }
 
 #[derive(Debug, Parser)]
@@ -64,10 +123,17 @@ pub struct PkgsSyncProfile {
     pub profile: String,
     #[arg(long = "sync-config", default_value = "/etc/rebuilderd-sync.conf")]
     pub config_file: String,
+    /// Remember submitted package lists in this directory and only send the changes on the next sync
+    #[arg(long)]
+    pub delta_state: Option<PathBuf>,
 }
 
 #[derive(Debug, Parser)]
-pub struct PkgsSyncStdin {}
+pub struct PkgsSyncStdin {
+    /// Remember submitted package lists in this directory and only send the changes on the next sync
+    #[arg(long)]
+    pub delta_state: Option<PathBuf>,
+}
 
 #[derive(Debug, Parser)]
 pub struct PkgsSync {
@@ -98,13 +164,29 @@ pub struct PkgsSync {
 
     #[arg(long)]
     pub sync_method: Option<String>,
+
+    /// Remember submitted package lists in this directory and only send the changes on the next sync
+    #[arg(long)]
+    pub delta_state: Option<PathBuf>,
+
+    /// Import popularity scores from this url or path, popcon for debian and pkgstats for archlinux
+    #[arg(long)]
+    pub popularity: Option<String>,
+
+    /// Pin archlinux packages to the commit of their release tag in this gitlab group of packaging repositories, eg.
+    /// https://gitlab.archlinux.org/archlinux/packaging/packages
+    #[arg(long)]
+    pub packaging_repo: Option<String>,
 }
 
 #[derive(Debug, Parser)]
 pub struct PkgsFilter {
-    /// Filter packages matching this name
+    /// Filter binary packages matching this name
     #[arg(long)]
     pub name: Option<String>,
+    /// Filter all binary packages built from the source package with this name
+    #[arg(long)]
+    pub src_name: Option<String>,
     /// Filter packages matching this status
     #[arg(long)]
     pub status: Option<ArtifactStatus>,
@@ -127,6 +209,116 @@ pub struct PkgsList {
     pub json: bool,
 }
 
+#[derive(Debug, Parser)]
+pub struct PkgsUpstream {
+    /// The name of the source package
+    pub name: String,
+    /// The upstream version, epochs and packaging revisions are ignored when matching
+    pub version: String,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsVerify {
+    /// Only check artifacts of this distro
+    #[arg(long)]
+    pub distro: Option<String>,
+    /// Only check artifacts of this suite
+    #[arg(long)]
+    pub suite: Option<String>,
+    /// Only check artifacts of this architecture
+    #[arg(long)]
+    pub architecture: Option<String>,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsTransitions {
+    /// Only show packages of this distro
+    #[arg(long)]
+    pub distro: Option<String>,
+    /// Only show packages of this suite
+    #[arg(long)]
+    pub suite: Option<String>,
+    /// Only show packages of this architecture
+    #[arg(long)]
+    pub architecture: Option<String>,
+    /// Only show transitions of this kind
+    #[arg(long, value_enum)]
+    pub kind: Option<TransitionKind>,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsTags {
+    /// Filter tags of packages in this distro
+    #[arg(long)]
+    pub distro: Option<String>,
+    /// Filter tags of the package with this name
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Only list packages with this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsTag {
+    /// The distribution of the package
+    pub distro: String,
+    /// The name of the source package
+    pub name: String,
+    /// The tag, eg. "toolchain" or "needs-upstream-fix"
+    pub tag: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsAnnotate {
+    /// The distribution of the package
+    pub distro: String,
+    /// The name of the source package
+    pub name: String,
+    /// Link to the bug report about the package's nondeterminism
+    #[arg(long)]
+    pub bug_url: Option<String>,
+    /// Free-form triage note
+    #[arg(long)]
+    pub note: Option<String>,
+    /// Remove the annotation of the package
+    #[arg(long, conflicts_with_all = ["bug_url", "note"])]
+    pub clear: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsCorrect {
+    /// The id of the binary package, as listed by `pkgs ls --json`
+    pub id: i32,
+    /// The url the artifact is published at
+    #[arg(long)]
+    pub url: Option<String>,
+    /// The version of the binary package
+    #[arg(long)]
+    pub version: Option<String>,
+    /// The verdict of the latest rebuild
+    #[arg(long)]
+    pub status: Option<ArtifactStatus>,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsDurations {
+    /// The distribution the durations apply to
+    pub distro: String,
+    /// The architecture the durations were measured on
+    pub architecture: String,
+    /// Url or path of a list with one source package name and its build duration in seconds per line
+    pub path: String,
+}
+
 #[derive(Debug, Parser)]
 pub struct PkgsRequeue {
     #[command(flatten)]
@@ -134,9 +326,35 @@ pub struct PkgsRequeue {
     /// Requeue with given priority
     #[arg(long, default_value = "0")]
     pub priority: i32,
-    /// Reset the status back to UNKWN
+    /// Only requeue packages whose latest rebuild was done in the worker environment with this fingerprint (see
+    /// `status --env`), or finished before this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_parser = parse_built_with)]
+    pub only_built_with: Option<BuiltWith>,
+    /// Why the packages are rebuilt, shown in the queue and kept in the build history
     #[arg(long)]
-    pub reset: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuiltWith {
+    Environment(String),
+    Before(NaiveDateTime),
+}
+
+fn parse_built_with(s: &str) -> Result<BuiltWith> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(BuiltWith::Before(date.and_time(NaiveTime::MIN)));
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Ok(BuiltWith::Before(datetime.naive_utc()));
+    }
+
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(BuiltWith::Environment(s.to_ascii_lowercase()));
+    }
+
+    bail!("Expected an environment fingerprint or a date: {s:?}")
 }
 
 #[derive(Debug, Parser)]
@@ -145,6 +363,24 @@ pub struct PkgsLog {
     pub filter: PkgsFilter,
 }
 
+#[derive(Debug, Parser)]
+pub struct PkgsBisect {
+    #[command(flatten)]
+    pub filter: PkgsFilter,
+    #[arg(long)]
+    pub priority: Option<i32>,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PkgsBisectResult {
+    /// The id rebuildctl printed when the bisect was started
+    pub id: i32,
+    #[arg(long)]
+    pub json: bool,
+}
+
 #[derive(Debug, Parser)]
 pub struct PkgsDiffoscope {
     #[command(flatten)]
@@ -166,6 +402,14 @@ pub enum Queue {
     /// Drop packages from queue matching given filter
     #[command(name = "drop")]
     Delete(QueueDrop),
+    /// Show where a package sits in the queue and when it's expected to be rebuilt
+    Position(QueuePositionArgs),
+    /// Pin a package to a worker, no other worker is going to build it
+    Assign(QueueAssign),
+    /// Dump the full queue as json, eg. before maintenance
+    Export,
+    /// Restore jobs from a queue dump
+    Import(QueueImport),
 }
 
 #[derive(Debug, Parser)]
@@ -190,17 +434,233 @@ pub struct QueuePush {
     pub architecture: Option<String>,
     #[arg(long, default_value = "0")]
     pub priority: i32,
+    /// Cross-compile the package on this architecture to verify the result matches the native build
+    #[arg(long)]
+    pub cross_from: Option<String>,
+    /// Why the package is rebuilt, shown in the queue and kept in the build history
+    #[arg(long)]
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Parser)]
 pub struct QueueDrop {
-    pub distro: String,
-    pub suite: String,
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    pub distro: Option<String>,
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    pub suite: Option<String>,
     #[arg(long)]
     pub architecture: Option<String>,
 
+    /// The name of the source package, the jobs of all binary packages built from it are dropped
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    pub name: Option<String>,
+    #[arg(conflicts_with = "all")]
+    pub version: Option<String>,
+
+    /// Drop every queued job at once, or only the ones of a suite with --distro and --suite
+    #[arg(long)]
+    pub all: bool,
+    /// Only wipe the jobs of this distribution
+    #[arg(long = "distro", requires = "all")]
+    pub all_distro: Option<String>,
+    /// Only wipe the jobs of this suite
+    #[arg(long = "suite", requires = "all")]
+    pub all_suite: Option<String>,
+    /// Don't ask for confirmation before wiping the queue
+    #[arg(short = 'y', long)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct QueueAssign {
+    pub name: String,
+    /// The name of the worker that should build the package
+    #[arg(required_unless_present = "unpin")]
+    pub worker: Option<String>,
+
+    #[arg(long)]
+    pub version: Option<String>,
+    #[arg(long)]
+    pub distro: Option<String>,
+    #[arg(long)]
+    pub architecture: Option<String>,
+    /// Remove the pin so any worker may build the package again
+    #[arg(long, conflicts_with = "worker")]
+    pub unpin: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct QueueImport {
+    /// Path to the queue dump, reads from stdin if omitted
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct QueuePositionArgs {
     pub name: String,
     pub version: Option<String>,
+
+    #[arg(long)]
+    pub distro: Option<String>,
+    #[arg(long)]
+    pub architecture: Option<String>,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum Auth {
+    /// Store an auth cookie or api key for the selected endpoint
+    Login(AuthLogin),
+    /// Remove the stored credentials of the selected endpoint
+    Logout,
+}
+
+#[derive(Debug, Parser)]
+pub struct AuthLogin {
+    /// Read the secret from stdin without prompting
+    #[arg(long)]
+    pub stdin: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum Keys {
+    /// List issued api keys
+    Ls(KeysList),
+    /// Issue a new api key, the secret is only shown once
+    Issue(KeysIssue),
+    /// Revoke an api key
+    Revoke(KeysRevoke),
+}
+
+#[derive(Debug, Parser)]
+pub struct KeysList {
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct KeysIssue {
+    /// A name describing who or what is going to use this key
+    pub name: String,
+    /// Restrict the key to a set of actions
+    #[arg(long)]
+    pub scope: ApiKeyScope,
+}
+
+#[derive(Debug, Parser)]
+pub struct KeysRevoke {
+    pub id: i32,
+}
+
+#[derive(Debug, Parser)]
+pub enum Subscriptions {
+    /// List the subscriptions of the current api key
+    Ls(SubscriptionsList),
+    /// Get notified when the status of a package changes
+    Add(SubscriptionsAdd),
+    /// Remove a subscription
+    Rm(SubscriptionsRemove),
+    /// Confirm an email address with the token that was mailed to it
+    Confirm(SubscriptionsConfirm),
+}
+
+#[derive(Debug, Parser)]
+pub struct SubscriptionsList {
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("follow").required(true)))]
+#[command(group(ArgGroup::new("target").required(true)))]
+pub struct SubscriptionsAdd {
+    /// Follow a package by name
+    #[arg(long, group = "follow")]
+    pub name: Option<String>,
+    /// Follow every package of a maintainer, as reported by the sync
+    #[arg(long, group = "follow")]
+    pub maintainer: Option<String>,
+    /// Only follow packages of this distribution
+    #[arg(long)]
+    pub distro: Option<String>,
+    /// Post notifications to this url
+    #[arg(long, group = "target")]
+    pub webhook: Option<String>,
+    /// Send notifications to this email address
+    #[arg(long, group = "target")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct SubscriptionsRemove {
+    pub id: i32,
+}
+
+#[derive(Debug, Parser)]
+pub struct SubscriptionsConfirm {
+    pub token: String,
+}
+
+#[derive(Debug, Parser)]
+pub enum Workers {
+    /// Let a worker finish its current build but stop assigning new jobs to it
+    Drain(WorkerSelector),
+    /// Allow a drained worker to pick up new jobs again
+    Resume(WorkerSelector),
+    /// Kill the build a worker is currently running and discard the job
+    Abort(WorkerSelector),
+    /// Let the results of a worker change the status of packages
+    Trust(WorkerSelector),
+    /// Only record the results of a worker as provisional until a trusted worker confirmed them
+    Untrust(WorkerSelector),
+    /// Forget the pinned client certificate of a worker, eg. after it got a new one
+    ResetFingerprint(WorkerSelector),
+    /// List the capability tokens issued for a worker
+    Tokens(WorkerTokensList),
+    /// Issue a capability token for a worker, the secret is only shown once
+    IssueToken(WorkerTokenIssue),
+    /// Revoke a capability token of a worker
+    RevokeToken(WorkerTokenRevoke),
+    /// Replace the key of a worker, the old key keeps working until the grace period of the daemon is over
+    RotateKey(WorkerRotateKey),
+}
+
+#[derive(Debug, Parser)]
+pub struct WorkerSelector {
+    /// The name of the worker
+    pub name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct WorkerTokensList {
+    /// The name of the worker
+    pub name: String,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct WorkerTokenIssue {
+    /// The name of the worker
+    pub name: String,
+    /// Restrict the token to a single kind of worker request
+    #[arg(long)]
+    pub capability: WorkerCapability,
+}
+
+#[derive(Debug, Parser)]
+pub struct WorkerTokenRevoke {
+    /// The name of the worker
+    pub name: String,
+    pub id: i32,
+}
+
+#[derive(Debug, Parser)]
+pub struct WorkerRotateKey {
+    /// The identity file of the worker, run this in the working directory of the worker or pass the path explicitly
+    #[arg(long, alias = "key-file", default_value = "rebuilder.identity")]
+    pub identity_file: PathBuf,
 }
 
 #[derive(Debug, Parser)]
@@ -217,3 +677,49 @@ pub fn gen_completions(args: &Completions) -> Result<()> {
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_built_with_date() {
+        let built_with = parse_built_with("2025-11-01").unwrap();
+        assert_eq!(
+            built_with,
+            BuiltWith::Before(
+                NaiveDate::from_ymd_opt(2025, 11, 1)
+                    .unwrap()
+                    .and_time(NaiveTime::MIN)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_built_with_datetime() {
+        let built_with = parse_built_with("2025-11-01T12:00:00+01:00").unwrap();
+        assert_eq!(
+            built_with,
+            BuiltWith::Before(
+                NaiveDate::from_ymd_opt(2025, 11, 1)
+                    .unwrap()
+                    .and_hms_opt(11, 0, 0)
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_built_with_fingerprint() {
+        let built_with = parse_built_with("0A1b2c3d4e5f6789").unwrap();
+        assert_eq!(
+            built_with,
+            BuiltWith::Environment("0a1b2c3d4e5f6789".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_built_with_invalid() {
+        assert!(parse_built_with("last tuesday").is_err());
+    }
+}
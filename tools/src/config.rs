@@ -49,4 +49,8 @@ pub struct SyncProfile {
 
     #[serde(default)]
     pub excludes: Vec<String>,
+
+    pub popularity: Option<String>,
+
+    pub packaging_repo: Option<String>,
 }
@@ -4,7 +4,7 @@ use rebuilderd_common::errors::*;
 use std::io::Read;
 use xz2::read::XzDecoder;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CompressedWith {
     // .gz
     Gzip,
@@ -12,12 +12,27 @@ pub enum CompressedWith {
     Bzip2,
     // .xz
     Xz,
-    // .zstd
+    // .zst
     Zstd,
     Unknown,
 }
 
+/// Signatures of the supported formats, checked before falling back to the mime database. The database is read from
+/// the system and isn't necessarily installed where the sync runs.
+const MAGIC: &[(&[u8], CompressedWith)] = &[
+    (b"\x1f\x8b", CompressedWith::Gzip),
+    (b"BZh", CompressedWith::Bzip2),
+    (b"\xfd7zXZ\x00", CompressedWith::Xz),
+    (b"\x28\xb5\x2f\xfd", CompressedWith::Zstd),
+];
+
 pub fn detect_compression(bytes: &[u8]) -> CompressedWith {
+    for (magic, comp) in MAGIC {
+        if bytes.starts_with(magic) {
+            return comp.clone();
+        }
+    }
+
     let mime = tree_magic_mini::from_u8(bytes);
     debug!("Detected mimetype for possibly compressed data: {:?}", mime);
 
@@ -31,6 +46,40 @@ pub fn detect_compression(bytes: &[u8]) -> CompressedWith {
     }
 }
 
+pub fn compression_from_extension(path: &str) -> CompressedWith {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("gz") => CompressedWith::Gzip,
+        Some("bz2") => CompressedWith::Bzip2,
+        Some("xz") => CompressedWith::Xz,
+        Some("zst" | "zstd") => CompressedWith::Zstd,
+        _ => CompressedWith::Unknown,
+    }
+}
+
+/// Detects the compression of downloaded repo metadata. The content is authoritative, mirrors are free to serve a
+/// `.gz` file with `Content-Encoding: gzip` so it arrives decompressed, and some repos name their database without
+/// any extension at all.
+pub fn detect(path: &str, bytes: &[u8]) -> CompressedWith {
+    let detected = detect_compression(bytes);
+    let expected = compression_from_extension(path);
+
+    if detected == CompressedWith::Unknown && expected != CompressedWith::Unknown {
+        warn!(
+            "Expected {path:?} to be compressed with {expected:?}, but it doesn't look like it, reading it as-is"
+        );
+    } else if expected != CompressedWith::Unknown && detected != expected {
+        debug!("Detected {detected:?} compression for {path:?}, despite its file extension");
+    }
+
+    detected
+}
+
+/// Transparently decompresses repo metadata in any of the supported formats, see [`detect`]
+pub fn read<'a>(path: &str, bytes: &'a [u8]) -> Result<Box<dyn Read + 'a>> {
+    stream(detect(path, bytes), bytes)
+}
+
 pub fn stream<'a>(comp: CompressedWith, bytes: &'a [u8]) -> Result<Box<dyn Read + 'a>> {
     match comp {
         CompressedWith::Gzip => Ok(Box::new(GzDecoder::new(bytes))),
@@ -140,4 +189,49 @@ mod tests {
         stream(comp, &bytes).unwrap().read_to_end(&mut buf).unwrap();
         assert_eq!(buf, b"ohai\n");
     }
+
+    #[test]
+    fn detect_compression_from_extension() {
+        assert_eq!(
+            compression_from_extension(
+                "https://deb.debian.org/debian/dists/sid/main/source/Sources.xz"
+            ),
+            CompressedWith::Xz
+        );
+        assert_eq!(
+            compression_from_extension("core/os/x86_64/core.db.tar.zst"),
+            CompressedWith::Zstd
+        );
+        assert_eq!(
+            compression_from_extension("https://example.com/Packages.gz?revision=1"),
+            CompressedWith::Gzip
+        );
+        assert_eq!(
+            compression_from_extension("https://example.com/core.db"),
+            CompressedWith::Unknown
+        );
+    }
+
+    #[test]
+    fn detect_prefers_content_over_extension() {
+        // a mirror that decompressed the file during transfer
+        assert_eq!(
+            detect("Sources.gz", b"Package: foo\n"),
+            CompressedWith::Unknown
+        );
+
+        let bytes = BASE64.decode(b"KLUv/QRYKQAAb2hhaQpnBE++").unwrap();
+        assert_eq!(detect("core.db", &bytes), CompressedWith::Zstd);
+        assert_eq!(detect("Packages.xz", &bytes), CompressedWith::Zstd);
+    }
+
+    #[test]
+    fn read_uncompressed_with_compressed_extension() {
+        let mut buf = Vec::new();
+        read("Sources.xz", b"ohai\n")
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"ohai\n");
+    }
 }
@@ -11,6 +11,8 @@ impl Fancy for BuildStatus {
             BuildStatus::Good => format!("{:5}", self.as_str().green()),
             BuildStatus::Bad => format!("{:5}", self.as_str().red()),
             BuildStatus::Fail => format!("{:5}", self.as_str().red()),
+            BuildStatus::Flaky => format!("{:5}", self.as_str().magenta()),
+            BuildStatus::DownloadFailed => format!("{:5}", self.as_str().yellow()),
             BuildStatus::Unknown => format!("{:5}", self.as_str().yellow()),
         }
     }
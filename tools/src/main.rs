@@ -4,21 +4,32 @@ use crate::fancy::Fancy;
 use chrono::Utc;
 use clap::Parser;
 use colored::*;
+use data_encoding::BASE64;
 use env_logger::Env;
 use glob::Pattern;
+use in_toto::crypto::{KeyType, PrivateKey, SignatureScheme};
 use nom::AsBytes;
 use rebuilderd_common::api::Client;
 use rebuilderd_common::api::v1::{
-    ArtifactStatus, BinaryIdentityFilter, BinaryPackage, BuildRestApi, OriginFilter, PackageReport,
-    PackageRestApi, Page, Priority, QueueJobRequest, QueueRestApi, SourceIdentityFilter,
-    WorkerRestApi,
+    ArtifactStatus, AssignQueuedJobsRequest, BinaryIdentityFilter, BinaryPackage,
+    BinaryPackageCorrection, Bisect, BisectRequest, BuildDurationReport, BuildRestApi, BuildStatus,
+    IssueApiKeyRequest, IssueWorkerTokenRequest, KeyRestApi, OriginFilter, PackageAnnotation,
+    PackageReport, PackageRestApi, PackageTagFilter, Page, PopularityReport, Priority,
+    QueueJobRequest, QueueRestApi, QueueSnapshot, RotateWorkerKeyRequest, SortDirection,
+    SourceIdentityFilter, SubscriptionConfirmation, SubscriptionRequest, SubscriptionRestApi,
+    TransitionFilter, TransitionKind, WipeQueueRequest, Worker, WorkerRestApi,
+    key_rotation_message,
 };
+use rebuilderd_common::auth::{Credentials, StoredCredential, WorkerIdentity};
 use rebuilderd_common::errors::*;
-use rebuilderd_common::http;
+use rebuilderd_common::http::{self, OutboundConfig};
 use rebuilderd_common::utils;
 use serde::Serialize;
+use std::fs;
 use std::io;
+use std::io::IsTerminal;
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 use tokio::io::AsyncReadExt;
 
 pub mod args;
@@ -27,6 +38,7 @@ pub mod decompress;
 pub mod fancy;
 pub mod pager;
 pub mod schedule;
+pub mod triage;
 
 fn patterns_from(patterns: &[String]) -> Result<Vec<Pattern>> {
     patterns
@@ -42,19 +54,23 @@ fn print_json<S: Serialize>(x: &S) -> Result<()> {
     Ok(())
 }
 
-pub async fn sync(client: &Client, sync: PkgsSync) -> Result<()> {
+pub async fn sync(client: &Client, outbound: &OutboundConfig, sync: PkgsSync) -> Result<()> {
     let method = if let Some(method) = &sync.sync_method {
         method.as_str()
     } else {
         sync.distro.as_str()
     };
 
-    let http = http::client()?;
+    let http = http::client(outbound)?;
     let mut reports = match method {
         "archlinux" => schedule::archlinux::sync(&http, &sync).await?,
         "debian" => schedule::debian::sync(&http, &sync).await?,
         "fedora" => schedule::fedora::sync(&http, &sync).await?,
+        "gentoo" => schedule::gentoo::sync(&http, &sync).await?,
+        "homebrew" => schedule::homebrew::sync(&http, &sync).await?,
+        "openwrt" => schedule::openwrt::sync(&http, &sync).await?,
         "tails" => schedule::tails::sync(&http, &sync).await?,
+        "void" => schedule::void::sync(&http, &sync).await?,
         unknown => bail!(
             "No integrated sync for {:?}, use --sync-method or `pkgs sync-stdin` instead",
             unknown
@@ -62,19 +78,131 @@ pub async fn sync(client: &Client, sync: PkgsSync) -> Result<()> {
     };
 
     reports.sort_by(|a, b| a.distribution.cmp(&b.distribution));
+    for report in &mut reports {
+        report.source = Some(sync.source.clone());
+    }
 
     if sync.print_json {
         print_json(&reports)?;
     } else {
-        for report in reports {
-            submit_package_report(client, &report).await?;
+        for report in &reports {
+            submit_package_report(client, report, sync.delta_state.as_deref()).await?;
+        }
+
+        if method == "archlinux" {
+            drop_superseded_jobs(client, &reports).await?;
+        }
+
+        if let Some(popularity) = &sync.popularity {
+            submit_popularity(client, &http, &sync.distro, method, popularity).await?;
         }
     }
 
     Ok(())
 }
 
-pub async fn submit_package_report(client: &Client, sync: &PackageReport) -> Result<()> {
+async fn submit_popularity(
+    client: &Client,
+    http: &http::Client,
+    distro: &str,
+    method: &str,
+    path: &str,
+) -> Result<()> {
+    let bytes = schedule::fetch_url_or_path(http, path).await?;
+    let packages = match method {
+        "debian" => schedule::popularity::parse_popcon(&String::from_utf8_lossy(&bytes)),
+        "archlinux" => schedule::popularity::parse_pkgstats(&bytes)?,
+        unknown => bail!("Popularity scores are not supported for {:?}", unknown),
+    };
+
+    info!(
+        "Submitting popularity scores of {} packages...",
+        packages.len()
+    );
+    client
+        .submit_package_popularity(&PopularityReport {
+            distribution: distro.to_string(),
+            packages,
+        })
+        .await
+        .context("Failed to submit popularity scores")?;
+
+    Ok(())
+}
+
+/// The sync queues every version that isn't GOOD yet, drop the jobs of stable versions that testing already replaced
+async fn drop_superseded_jobs(client: &Client, reports: &[PackageReport]) -> Result<()> {
+    let superseded = schedule::archlinux::superseded(reports);
+    if superseded.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Dropping queued jobs of {} packages that are superseded by testing...",
+        superseded.len()
+    );
+    for pkg in superseded {
+        debug!(
+            "Dropping {} {} of {} ({})",
+            pkg.name, pkg.version, pkg.component, pkg.architecture
+        );
+        let origin_filter = OriginFilter {
+            distribution: Some(pkg.distribution),
+            release: None,
+            component: Some(pkg.component),
+            architecture: Some(pkg.architecture),
+        };
+        let source_identity_filter = SourceIdentityFilter {
+            name: Some(pkg.name),
+            version: Some(pkg.version),
+        };
+        client
+            .drop_queued_jobs(Some(&origin_filter), Some(&source_identity_filter))
+            .await
+            .context("Failed to drop superseded jobs")?;
+    }
+
+    Ok(())
+}
+
+/// Where the last package list that was submitted for a suite is kept, a delta sync is computed against it
+fn delta_state_path(dir: &Path, report: &PackageReport) -> PathBuf {
+    let mut name = report.distribution.clone();
+    for part in [&report.release, &report.component].into_iter().flatten() {
+        name.push('-');
+        name.push_str(part);
+    }
+    name.push('-');
+    name.push_str(&report.architecture);
+    dir.join(format!("{}.json", name.replace('/', "_")))
+}
+
+fn load_delta_state(path: &Path) -> Option<PackageReport> {
+    let buf = fs::read(path).ok()?;
+    match serde_json::from_slice(&buf) {
+        Ok(report) => Some(report),
+        Err(err) => {
+            warn!("Ignoring corrupt delta state {path:?}: {err:#}");
+            None
+        }
+    }
+}
+
+fn save_delta_state(path: &Path, report: &PackageReport) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| anyhow!("Failed to create delta state directory {parent:?}"))?;
+    }
+    let buf = serde_json::to_vec(report)?;
+    fs::write(path, buf).with_context(|| anyhow!("Failed to write delta state {path:?}"))?;
+    Ok(())
+}
+
+pub async fn submit_package_report(
+    client: &Client,
+    sync: &PackageReport,
+    delta_state: Option<&Path>,
+) -> Result<()> {
     let mut identity_string = "".to_owned();
     if let Some(release) = &sync.release {
         identity_string.push_str(format!("/{}", release).as_str())
@@ -89,6 +217,31 @@ pub async fn submit_package_report(client: &Client, sync: &PackageReport) -> Res
         sync.distribution, identity_string, sync.architecture
     );
 
+    let state_path = delta_state.map(|dir| delta_state_path(dir, sync));
+    if let Some(path) = &state_path
+        && let Some(previous) = load_delta_state(path)
+    {
+        let delta = sync.delta_from(&previous);
+        info!(
+            "Sending changes of {} to rebuilderd ({} added, {} updated, {} removed)...",
+            display_string,
+            delta.added.len(),
+            delta.updated.len(),
+            delta.removed.len()
+        );
+
+        let applied = client
+            .submit_package_report_delta(&delta)
+            .await
+            .context("Failed to send delta import to daemon")?;
+        if applied {
+            return save_delta_state(path, sync);
+        }
+        warn!(
+            "Daemon has a different revision of {display_string}, sending the full package list instead"
+        );
+    }
+
     info!(
         "Sending {} to rebuilderd ({} packages)...",
         display_string,
@@ -99,6 +252,10 @@ pub async fn submit_package_report(client: &Client, sync: &PackageReport) -> Res
         .submit_package_report(sync)
         .await
         .context("Failed to send import to daemon")?;
+
+    if let Some(path) = &state_path {
+        save_delta_state(path, sync)?;
+    }
     Ok(())
 }
 
@@ -113,7 +270,7 @@ async fn lookup_package(client: &Client, filter: PkgsFilter) -> Result<BinaryPac
     let binary_identity_filter = BinaryIdentityFilter {
         name: filter.name,
         version: None, // TODO: ls.filter.version
-        source_name: None,
+        source_name: filter.src_name,
     };
 
     let mut results = client
@@ -128,6 +285,156 @@ async fn lookup_package(client: &Client, filter: PkgsFilter) -> Result<BinaryPac
     Ok(results.records.pop().unwrap())
 }
 
+fn print_bisect(bisect: &Bisect) -> Result<()> {
+    let mut stdout = io::stdout();
+    let state = if bisect.finished_at.is_some() {
+        "finished"
+    } else {
+        "running"
+    };
+    writeln!(
+        stdout,
+        "Bisect #{} of {} {} ({}, {}, {})",
+        bisect.id,
+        bisect.name.bold(),
+        bisect.version.bold(),
+        bisect.distribution,
+        bisect.architecture,
+        state,
+    )?;
+
+    for step in &bisect.steps {
+        let status_str = step
+            .status
+            .as_ref()
+            .map(|status| status.fancy())
+            .unwrap_or_else(|| format!("{:5}", "-"));
+        writeln!(stdout, "[{}] {}", status_str.bold(), step.variation)?;
+    }
+
+    if bisect.flips.is_empty() {
+        writeln!(stdout, "No variation changed the verdict")?;
+    } else {
+        let flips = bisect
+            .flips
+            .iter()
+            .map(|variation| variation.as_str())
+            .collect::<Vec<_>>();
+        writeln!(stdout, "Changed the verdict: {}", flips.join(", "))?;
+    }
+
+    Ok(())
+}
+
+fn worker_key(identity: &WorkerIdentity) -> Result<(PrivateKey, String)> {
+    let privkey = PrivateKey::from_pkcs8(&identity.pkcs8()?, SignatureScheme::Ed25519)?;
+    let pubkey = BASE64.encode(privkey.public().as_bytes());
+    Ok((privkey, pubkey))
+}
+
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// The new key is written next to the current identity before the daemon is asked to switch, so it can't get lost if
+/// the request succeeds but the local files can't be swapped
+async fn rotate_worker_key(client: &mut Client, identity_file: &Path) -> Result<()> {
+    let identity = WorkerIdentity::load(identity_file)?
+        .with_context(|| anyhow!("No worker identity found at {identity_file:?}"))?;
+    let (current, current_key) = worker_key(&identity)?;
+
+    let new_file = path_with_suffix(identity_file, ".new");
+    let new_identity = identity.with_key(&PrivateKey::new(KeyType::Ed25519)?);
+    new_identity
+        .save(&new_file)
+        .context("Failed to write new worker key")?;
+    let (_, new_key) = worker_key(&new_identity)?;
+
+    let signature = current.sign(key_rotation_message(&current_key, &new_key).as_bytes())?;
+    let signature = serde_json::to_value(&signature)?;
+    let signature = signature["sig"]
+        .as_str()
+        .context("Signature is missing its value")?
+        .to_string();
+
+    client.worker_key(current_key);
+    let rotated = client
+        .rotate_worker_key(RotateWorkerKeyRequest {
+            new_key: new_key.clone(),
+            signature,
+        })
+        .await
+        .with_context(|| {
+            anyhow!("Failed to rotate worker key, the unused new key is at {new_file:?}")
+        })?;
+
+    let old_file = path_with_suffix(identity_file, ".old");
+    fs::rename(identity_file, &old_file)
+        .with_context(|| anyhow!("Failed to move {identity_file:?} to {old_file:?}"))?;
+    fs::rename(&new_file, identity_file)
+        .with_context(|| anyhow!("Failed to move {new_file:?} to {identity_file:?}"))?;
+
+    info!(
+        "Worker #{} is now using key {:?}, the previous key is accepted until {}",
+        rotated.worker_id,
+        new_key,
+        rotated.previous_key_expires_at.format("%Y-%m-%d %H:%M:%S")
+    );
+    info!("Restart the worker to pick up the new key, the previous one was kept at {old_file:?}");
+    Ok(())
+}
+
+/// Ask on the terminal before dropping a whole suite, or the entire queue
+fn confirm_wipe(filter: &OriginFilter) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("Refusing to wipe the queue without confirmation, use --yes");
+    }
+
+    let mut scope = Vec::new();
+    if let Some(distribution) = &filter.distribution {
+        scope.push(format!("distribution {distribution:?}"));
+    }
+    if let Some(component) = &filter.component {
+        scope.push(format!("suite {component:?}"));
+    }
+    if let Some(architecture) = &filter.architecture {
+        scope.push(format!("architecture {architecture:?}"));
+    }
+    if scope.is_empty() {
+        eprint!("Drop every job in the queue? [y/N] ");
+    } else {
+        eprint!("Drop every queued job of {}? [y/N] ", scope.join(", "));
+    }
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+async fn find_worker(client: &Client, name: &str) -> Result<Worker> {
+    let mut workers = client
+        .get_workers(None)
+        .await
+        .context("Failed to fetch workers")?
+        .records
+        .into_iter()
+        .filter(|worker| worker.name == name)
+        .collect::<Vec<_>>();
+
+    if workers.len() != 1 {
+        bail!(
+            "Worker lookup for {:?} returned {} results, expected exactly one",
+            name,
+            workers.len()
+        );
+    }
+
+    Ok(workers.pop().unwrap())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -147,10 +454,12 @@ async fn main() -> Result<()> {
 
     let config =
         rebuilderd_common::config::load(args.config).context("Failed to load config file")?;
+    let outbound = config.outbound.clone();
     let mut client = Client::new(config, args.endpoint)?;
+    let verbose = args.verbose;
 
     match args.subcommand {
-        SubCommand::Status => {
+        SubCommand::Status(args) => {
             let mut stdout = io::stdout();
             for worker in client.with_auth_cookie()?.get_workers(None).await?.records {
                 let label = format!("{} ({})", worker.name.green(), worker.address.yellow());
@@ -159,12 +468,53 @@ async fn main() -> Result<()> {
                 } else {
                     "idle".blue()
                 };
+                let status = if worker.is_draining {
+                    format!("{} {}", status, "(draining)".red())
+                } else {
+                    status.to_string()
+                };
+                let status = if worker.is_trusted {
+                    status
+                } else {
+                    format!("{} {}", status, "(untrusted)".yellow())
+                };
                 if writeln!(stdout, "{:-40} => {}", label, status).is_err() {
                     break;
                 }
+
+                if !args.env {
+                    continue;
+                }
+
+                let Some(env) = worker.environment else {
+                    if writeln!(stdout, "    {}", "no environment reported".dimmed()).is_err() {
+                        break;
+                    }
+                    continue;
+                };
+
+                let kernel = env.kernel.as_deref().unwrap_or("unknown");
+                let mut lines = vec![
+                    format!("fingerprint {}", env.fingerprint()),
+                    format!("worker {}, kernel {}", env.worker_version, kernel),
+                ];
+                for (backend, digest) in &env.scripts {
+                    lines.push(format!("script {}: sha256:{}", backend, digest));
+                }
+                for (tool, version) in &env.tools {
+                    lines.push(format!("tool {}: {}", tool, version));
+                }
+                if lines
+                    .iter()
+                    .any(|line| writeln!(stdout, "    {}", line.dimmed()).is_err())
+                {
+                    break;
+                }
             }
         }
-        SubCommand::Pkgs(Pkgs::Sync(args)) => sync(client.with_auth_cookie()?, args).await?,
+        SubCommand::Pkgs(Pkgs::Sync(args)) => {
+            sync(client.with_auth_cookie()?, &outbound, args).await?
+        }
         SubCommand::Pkgs(Pkgs::SyncProfile(args)) => {
             let mut config = SyncConfigFile::load(&args.config_file)?;
             let mut profile = config
@@ -172,8 +522,12 @@ async fn main() -> Result<()> {
                 .remove(&args.profile)
                 .ok_or_else(|| format_err!("Profile not found: {:?}", args.profile))?;
 
+            // the deprecated options are only read to migrate them
+            #[allow(deprecated)]
+            let (suite, architecture) = (profile.suite.take(), profile.architecture.take());
+
             // TODO: remove this after we've deprecated suite=
-            if let Some(suite) = profile.suite {
+            if let Some(suite) = suite {
                 warn!(
                     "Deprecated option in config: replace `suite = \"{}\"` with `components = [\"{}\"]`",
                     suite, suite
@@ -182,7 +536,7 @@ async fn main() -> Result<()> {
             }
 
             // TODO: remove this after we've deprecated architecture=
-            if let Some(arch) = profile.architecture {
+            if let Some(arch) = architecture {
                 warn!(
                     "Deprecated option in config: replace `architecture = \"{}\"` with `architectures = [\"{}\"]`",
                     arch, arch
@@ -192,6 +546,7 @@ async fn main() -> Result<()> {
 
             sync(
                 client.with_auth_cookie()?,
+                &outbound,
                 PkgsSync {
                     distro: profile.distro,
                     sync_method: profile.sync_method,
@@ -204,11 +559,14 @@ async fn main() -> Result<()> {
                     maintainers: profile.maintainers,
                     pkgs: patterns_from(&profile.pkgs)?,
                     excludes: patterns_from(&profile.excludes)?,
+                    delta_state: args.delta_state,
+                    popularity: profile.popularity,
+                    packaging_repo: profile.packaging_repo,
                 },
             )
             .await?;
         }
-        SubCommand::Pkgs(Pkgs::SyncStdin(_sync)) => {
+        SubCommand::Pkgs(Pkgs::SyncStdin(sync)) => {
             let mut stdin = tokio::io::stdin();
             let mut buf = Vec::new();
             stdin.read_to_end(&mut buf).await?;
@@ -216,7 +574,54 @@ async fn main() -> Result<()> {
             let report = serde_json::from_slice(&buf)
                 .context("Failed to deserialize pkg import from stdin")?;
 
-            submit_package_report(client.with_auth_cookie()?, &report).await?;
+            submit_package_report(
+                client.with_auth_cookie()?,
+                &report,
+                sync.delta_state.as_deref(),
+            )
+            .await?;
+        }
+        SubCommand::Pkgs(Pkgs::Requeue(requeue)) => {
+            let (built_with, built_before) = match requeue.only_built_with {
+                Some(BuiltWith::Environment(fingerprint)) => (Some(fingerprint), None),
+                Some(BuiltWith::Before(date)) => (None, Some(date)),
+                None => (None, None),
+            };
+
+            let status = requeue.filter.status.map(|status| match status {
+                ArtifactStatus::Good => BuildStatus::Good,
+                ArtifactStatus::Bad => BuildStatus::Bad,
+                ArtifactStatus::Unknown => BuildStatus::Unknown,
+            });
+
+            let response = client
+                .with_auth_cookie()?
+                .request_rebuild_with_progress(
+                    QueueJobRequest {
+                        distribution: requeue.filter.distro,
+                        release: None,
+                        component: requeue.filter.suite,
+                        name: requeue.filter.src_name,
+                        version: None,
+                        architecture: requeue.filter.architecture,
+                        status,
+                        priority: Some(Priority::from(requeue.priority)),
+                        built_with,
+                        built_before,
+                        binary_name: requeue.filter.name,
+                        build_architecture: None,
+                        reason: requeue.reason,
+                    },
+                    &|processed, total| {
+                        info!("Processed {processed}/{total} build inputs");
+                    },
+                )
+                .await?;
+
+            info!(
+                "Queued {} build inputs, {} were already queued",
+                response.queued, response.skipped
+            );
         }
         SubCommand::Pkgs(Pkgs::Ls(ls)) => {
             let origin_filter = OriginFilter {
@@ -229,7 +634,7 @@ async fn main() -> Result<()> {
             let binary_identity_filter = BinaryIdentityFilter {
                 name: ls.filter.name,
                 version: None, // TODO: ls.filter.version
-                source_name: None,
+                source_name: ls.filter.src_name,
             };
 
             let mut page = Page {
@@ -269,11 +674,15 @@ async fn main() -> Result<()> {
                 } else {
                     let mut stdout = io::stdout();
                     for package in results.records {
-                        let status_str = format!(
-                            "[{}]",
-                            package.status.unwrap_or(ArtifactStatus::Unknown).fancy()
-                        )
-                        .bold();
+                        let mut status_str = package
+                            .status
+                            .clone()
+                            .unwrap_or(ArtifactStatus::Unknown)
+                            .fancy();
+                        if package.rebuilding {
+                            status_str.push_str(" (rebuilding)");
+                        }
+                        let status_str = format!("[{status_str}]").bold();
 
                         let pkg_str =
                             format!("{} {}", package.name.bold(), package.version.bold(),);
@@ -295,10 +704,301 @@ async fn main() -> Result<()> {
                         {
                             break;
                         }
+
+                        if let Some(bug_url) = package.bug_url
+                            && writeln!(stdout, "        bug: {bug_url}").is_err()
+                        {
+                            break;
+                        }
+
+                        if let Some(note) = package.note
+                            && writeln!(stdout, "        note: {}", note.dimmed()).is_err()
+                        {
+                            break;
+                        }
+
+                        if let Some(removed_at) = package.removed_at
+                            && writeln!(stdout, "        removed: {removed_at}").is_err()
+                        {
+                            break;
+                        }
+
+                        // only worth a line if the debug symbols tell a different story
+                        if let Some(debug_status) = package.debug_status
+                            && package.status.as_ref() != Some(&debug_status)
+                            && writeln!(stdout, "        debug packages: {}", debug_status.fancy())
+                                .is_err()
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        SubCommand::Pkgs(Pkgs::Upstream(args)) => {
+            let release = client
+                .get_upstream_release(&args.name, &args.version)
+                .await
+                .context("Failed to fetch upstream release")?;
+
+            if args.json {
+                print_json(&release)?;
+            } else {
+                let mut stdout = io::stdout();
+                for package in &release.packages {
+                    let mut status_str = package
+                        .status
+                        .clone()
+                        .unwrap_or(BuildStatus::Unknown)
+                        .fancy();
+                    if package.rebuilding {
+                        status_str.push_str(" (rebuilding)");
+                    }
+                    let status_str = format!("[{status_str}]").bold();
+
+                    let info = format!(
+                        "{}, {}, {}",
+                        package.distribution,
+                        package.release.as_deref().unwrap_or("<none>"),
+                        package.component.as_deref().unwrap_or("<none>"),
+                    );
+
+                    writeln!(
+                        stdout,
+                        "{} {:-60} ({})",
+                        status_str,
+                        format!("{} {}", package.name.bold(), package.version.bold()),
+                        info,
+                    )?;
+                }
+
+                let reproducible_in = if release.reproducible_in.is_empty() {
+                    "<none>".to_string()
+                } else {
+                    release.reproducible_in.join(", ")
+                };
+                writeln!(
+                    stdout,
+                    "{} {} is reproducible in: {}",
+                    release.name, release.version, reproducible_in
+                )?;
+            }
+        }
+        SubCommand::Pkgs(Pkgs::Verify(args)) => {
+            let origin_filter = OriginFilter {
+                distribution: args.distro,
+                release: None,
+                component: args.suite,
+                architecture: args.architecture,
+            };
+
+            let mut mismatches = Vec::new();
+            let mut page = Page {
+                limit: Some(1000),
+                before: None,
+                after: None,
+                sort: None,
+                direction: None,
+            };
+            loop {
+                let records = client
+                    .get_verification_mismatches(Some(&page), Some(&origin_filter))
+                    .await
+                    .context("Failed to fetch verification mismatches")?
+                    .records;
+                let Some(last) = records.last() else {
+                    break;
+                };
+                page.after = Some(last.artifact_id);
+                mismatches.extend(records);
+            }
+
+            if args.json {
+                print_json(&mismatches)?;
+            } else {
+                let mut stdout = io::stdout();
+                for mismatch in &mismatches {
+                    writeln!(
+                        stdout,
+                        "{} {:-60} artifact {:?} of build #{} by {}",
+                        "[MISMATCH]".red().bold(),
+                        format!("{} {}", mismatch.name.bold(), mismatch.version.bold()),
+                        mismatch.artifact,
+                        mismatch.build_id,
+                        mismatch.worker.as_deref().unwrap_or("<unknown worker>"),
+                    )?;
+                    writeln!(
+                        stdout,
+                        "    published: {}, rebuilt: {}",
+                        mismatch.published_checksum,
+                        mismatch.rebuilt_checksum.as_deref().unwrap_or("<none>"),
+                    )?;
+                }
+            }
+        }
+        SubCommand::Pkgs(Pkgs::Transitions(args)) => {
+            let origin_filter = OriginFilter {
+                distribution: args.distro,
+                release: None,
+                component: args.suite,
+                architecture: args.architecture,
+            };
+            let transition_filter = TransitionFilter { kind: args.kind };
+
+            let page = Page {
+                limit: None,
+                before: None,
+                after: None,
+                sort: None,
+                direction: Some(SortDirection::Descending),
+            };
+
+            let transitions = client
+                .get_status_transitions(Some(&page), Some(&origin_filter), Some(&transition_filter))
+                .await
+                .context("Failed to fetch status transitions")?
+                .records;
+
+            if args.json {
+                print_json(&transitions)?;
+            } else {
+                let mut stdout = io::stdout();
+                for transition in &transitions {
+                    let label = match transition.kind {
+                        TransitionKind::Regression => "[REGRESSION]".red().bold(),
+                        TransitionKind::NewVersionUnreproducible => "[NEW BAD]   ".yellow(),
+                        TransitionKind::Fixed => "[FIXED]     ".green(),
+                    };
+                    writeln!(
+                        stdout,
+                        "{label} {:-60} {} -> {} in build #{} by {} at {}",
+                        format!("{} {}", transition.name.bold(), transition.version.bold()),
+                        transition.previous_status.as_str(),
+                        transition.status.as_str(),
+                        transition.build_id,
+                        transition.worker.as_deref().unwrap_or("<unknown worker>"),
+                        transition.created_at.format("%Y-%m-%d %H:%M"),
+                    )?;
+                    if transition.previous_version != transition.version {
+                        writeln!(
+                            stdout,
+                            "    previous version: {}",
+                            transition.previous_version
+                        )?;
                     }
                 }
             }
         }
+        SubCommand::Pkgs(Pkgs::Tags(args)) => {
+            let tags = client
+                .get_package_tags(Some(&PackageTagFilter {
+                    distribution: args.distro,
+                    name: args.name,
+                    tag: args.tag,
+                }))
+                .await?;
+
+            if args.json {
+                print_json(&tags)?;
+            } else {
+                let mut stdout = io::stdout();
+                for tag in tags {
+                    if writeln!(
+                        stdout,
+                        "{:-40} {} ({})",
+                        format!("{}/{}", tag.distribution, tag.name.bold()),
+                        tag.tag.cyan(),
+                        tag.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    )
+                    .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        SubCommand::Pkgs(Pkgs::Tag(args)) => {
+            let client = client.with_auth_cookie()?;
+            client
+                .add_package_tag(&args.distro, &args.name, &args.tag)
+                .await?;
+            info!("Tagged {}/{} with {:?}", args.distro, args.name, args.tag);
+        }
+        SubCommand::Pkgs(Pkgs::Untag(args)) => {
+            let client = client.with_auth_cookie()?;
+            client
+                .remove_package_tag(&args.distro, &args.name, &args.tag)
+                .await?;
+            info!(
+                "Removed tag {:?} from {}/{}",
+                args.tag, args.distro, args.name
+            );
+        }
+        SubCommand::Pkgs(Pkgs::Annotate(args)) => {
+            let client = client.with_auth_cookie()?;
+            if args.clear {
+                client
+                    .remove_package_annotation(&args.distro, &args.name)
+                    .await?;
+                info!("Removed annotation of {}/{}", args.distro, args.name);
+            } else {
+                if args.bug_url.is_none() && args.note.is_none() {
+                    bail!("Either --bug-url, --note or --clear is required");
+                }
+
+                client
+                    .annotate_package(
+                        &args.distro,
+                        &args.name,
+                        &PackageAnnotation {
+                            bug_url: args.bug_url,
+                            note: args.note,
+                        },
+                    )
+                    .await?;
+                info!("Annotated {}/{}", args.distro, args.name);
+            }
+        }
+        SubCommand::Pkgs(Pkgs::Correct(args)) => {
+            if args.url.is_none() && args.version.is_none() && args.status.is_none() {
+                bail!("Either --url, --version or --status is required");
+            }
+
+            let package = client
+                .with_auth_cookie()?
+                .correct_binary_package(
+                    args.id,
+                    &BinaryPackageCorrection {
+                        artifact_url: args.url,
+                        version: args.version,
+                        status: args.status,
+                    },
+                )
+                .await?;
+            info!(
+                "Corrected {} {} ({})",
+                package.name, package.version, package.url
+            );
+        }
+        SubCommand::Pkgs(Pkgs::Durations(args)) => {
+            let http = http::client(&outbound)?;
+            let bytes = schedule::fetch_url_or_path(&http, &args.path).await?;
+            let packages = schedule::durations::parse(&String::from_utf8_lossy(&bytes))?;
+
+            info!(
+                "Submitting build durations of {} packages...",
+                packages.len()
+            );
+            client
+                .with_auth_cookie()?
+                .submit_build_durations(&BuildDurationReport {
+                    distribution: args.distro,
+                    architecture: args.architecture,
+                    packages,
+                })
+                .await
+                .context("Failed to submit build durations")?;
+        }
         SubCommand::Pkgs(Pkgs::Log(args)) => {
             let package = lookup_package(&client, args.filter).await?;
             if package.build_id.is_none() {
@@ -344,6 +1044,34 @@ async fn main() -> Result<()> {
             io::stdout().write_all(attestation.as_bytes())?;
             io::stdout().write_all(b"\n")?;
         }
+        SubCommand::Pkgs(Pkgs::Bisect(args)) => {
+            let package = lookup_package(&client, args.filter).await?;
+            let bisect = client
+                .with_auth_cookie()?
+                .request_bisect(BisectRequest {
+                    binary_package_id: package.id,
+                    priority: args.priority.map(Priority::from),
+                })
+                .await?;
+
+            if args.json {
+                print_json(&bisect)?;
+            } else {
+                print_bisect(&bisect)?;
+            }
+        }
+        SubCommand::Pkgs(Pkgs::BisectResult(args)) => {
+            let bisect = client
+                .get_bisect(args.id)
+                .await
+                .context("Failed to fetch bisect")?;
+
+            if args.json {
+                print_json(&bisect)?;
+            } else {
+                print_bisect(&bisect)?;
+            }
+        }
         SubCommand::Queue(Queue::Ls(ls)) => {
             let mut page = Page {
                 limit: Some(1000),
@@ -388,11 +1116,16 @@ async fn main() -> Result<()> {
                             let secs = duration.num_seconds();
                             utils::secs_to_human(secs)
                         });
+                        let phase = job
+                            .phase
+                            .as_ref()
+                            .map(|phase| format!(" ({phase})"))
+                            .unwrap_or_default();
 
                         // Print the queue item
                         if writeln!(
                             stdout,
-                            "{} {:-60} {:>11} {:19} {:?} {:?} {:?} {:?}",
+                            "{} {:-60} {:>11} {:19} {:?} {:?} {:?} {:?}{}",
                             job.queued_at
                                 .format("%Y-%m-%d %H:%M:%S")
                                 .to_string()
@@ -404,17 +1137,33 @@ async fn main() -> Result<()> {
                             job.release,
                             job.component,
                             job.architecture,
+                            phase.cyan(),
                         )
                         .is_err()
                         {
                             break;
                         }
+
+                        if verbose > 0
+                            && writeln!(
+                                stdout,
+                                "    attempts: {}, worker: {}, pinned to: {}, last failure: {}, reason: {}",
+                                job.attempts,
+                                job.worker.as_deref().unwrap_or("<none>"),
+                                job.pinned_worker.as_deref().unwrap_or("<none>"),
+                                job.last_failure.as_deref().unwrap_or("<none>").yellow(),
+                                job.reason.as_deref().unwrap_or("<none>"),
+                            )
+                            .is_err()
+                        {
+                            break;
+                        }
                     }
                 }
             }
         }
         SubCommand::Queue(Queue::Push(push)) => {
-            client
+            let response = client
                 .with_auth_cookie()?
                 .request_rebuild(QueueJobRequest {
                     distribution: Some(push.distro),
@@ -425,19 +1174,144 @@ async fn main() -> Result<()> {
                     architecture: push.architecture,
                     status: None, // TODO: push.status
                     priority: Some(Priority::from(push.priority)),
+                    built_with: None,
+                    built_before: None,
+                    binary_name: None,
+                    build_architecture: push.cross_from,
+                    reason: push.reason,
                 })
                 .await?;
+
+            info!(
+                "Queued {} build inputs, {} were already queued",
+                response.queued, response.skipped
+            );
+        }
+        SubCommand::Queue(Queue::Position(args)) => {
+            let origin_filter = OriginFilter {
+                distribution: args.distro,
+                release: None,
+                component: None,
+                architecture: args.architecture,
+            };
+
+            let source_identity_filter = SourceIdentityFilter {
+                name: Some(args.name),
+                version: args.version,
+            };
+
+            let positions = client
+                .get_queue_position(Some(&origin_filter), Some(&source_identity_filter))
+                .await?;
+
+            if args.json {
+                print_json(&positions)?;
+            } else {
+                let mut stdout = io::stdout();
+                for position in positions {
+                    let job = &position.job;
+                    let pkg_str = format!("{} {}", job.name.bold(), job.version);
+
+                    let state = if job.started_at.is_some() {
+                        "building now".green().to_string()
+                    } else {
+                        let ahead = if position.tied > 0 {
+                            format!(
+                                "{} to {} jobs ahead",
+                                position.ahead,
+                                position.ahead + position.tied
+                            )
+                        } else {
+                            format!("{} jobs ahead", position.ahead)
+                        };
+
+                        let eta = position
+                            .estimated_start
+                            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_else(|| "unknown".to_string());
+
+                        format!("{}, estimated start: {}", ahead, eta.yellow())
+                    };
+
+                    if writeln!(
+                        stdout,
+                        "{:-60} {:?} {:?} => {}",
+                        pkg_str, job.distribution, job.architecture, state
+                    )
+                    .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        SubCommand::Queue(Queue::Assign(assign)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = if let Some(name) = &assign.worker {
+                Some(find_worker(client, name).await?)
+            } else {
+                None
+            };
+
+            let origin_filter = OriginFilter {
+                distribution: assign.distro,
+                release: None,
+                component: None,
+                architecture: assign.architecture,
+            };
+
+            let source_identity_filter = SourceIdentityFilter {
+                name: Some(assign.name),
+                version: assign.version,
+            };
+
+            let response = client
+                .assign_queued_jobs(
+                    Some(&origin_filter),
+                    Some(&source_identity_filter),
+                    AssignQueuedJobsRequest {
+                        worker: worker.as_ref().map(|worker| worker.id),
+                    },
+                )
+                .await?;
+
+            if let Some(worker) = worker {
+                info!(
+                    "Pinned {} jobs to worker {:?}",
+                    response.assigned, worker.name
+                );
+            } else {
+                info!("Released {} jobs to all workers", response.assigned);
+            }
+        }
+        SubCommand::Queue(Queue::Delete(push)) if push.all => {
+            let origin_filter = OriginFilter {
+                distribution: push.all_distro,
+                release: None,
+                component: push.all_suite,
+                architecture: push.architecture,
+            };
+
+            if !push.yes && !confirm_wipe(&origin_filter)? {
+                bail!("Aborted, the queue was not modified");
+            }
+
+            let response = client
+                .with_auth_cookie()?
+                .wipe_queue(Some(&origin_filter), WipeQueueRequest { confirm: true })
+                .await?;
+            info!("Dropped {} jobs from the queue", response.dropped);
         }
         SubCommand::Queue(Queue::Delete(push)) => {
             let origin_filter = OriginFilter {
-                distribution: Some(push.distro),
+                distribution: push.distro,
                 release: None, // TODO: ls.filter.release,
-                component: Some(push.suite),
+                component: push.suite,
                 architecture: push.architecture,
             };
 
             let source_identity_filter = SourceIdentityFilter {
-                name: Some(push.name),
+                name: push.name,
                 version: push.version,
             };
 
@@ -446,6 +1320,290 @@ async fn main() -> Result<()> {
                 .drop_queued_jobs(Some(&origin_filter), Some(&source_identity_filter))
                 .await?;
         }
+        SubCommand::Queue(Queue::Export) => {
+            let snapshot = client.with_auth_cookie()?.export_queue().await?;
+            print_json(&snapshot)?;
+        }
+        SubCommand::Queue(Queue::Import(import)) => {
+            let buf = if let Some(path) = &import.path {
+                fs::read(path)
+                    .with_context(|| anyhow!("Failed to read queue dump from {path:?}"))?
+            } else {
+                let mut buf = Vec::new();
+                tokio::io::stdin().read_to_end(&mut buf).await?;
+                buf
+            };
+
+            let snapshot = serde_json::from_slice::<QueueSnapshot>(&buf)
+                .context("Failed to deserialize queue dump")?;
+
+            let response = client.with_auth_cookie()?.import_queue(&snapshot).await?;
+
+            info!(
+                "Restored {} jobs, {} were already queued, {} belong to unknown packages",
+                response.restored, response.skipped, response.missing
+            );
+        }
+        SubCommand::Auth(Auth::Login(login)) => {
+            if !login.stdin && io::stdin().is_terminal() {
+                eprint!("Auth cookie or api key for {}: ", client.endpoint());
+                io::stderr().flush()?;
+            }
+
+            let mut secret = String::new();
+            io::stdin().read_line(&mut secret)?;
+            let secret = secret.trim();
+            if secret.is_empty() {
+                bail!("No auth cookie or api key given");
+            }
+
+            let mut credentials = Credentials::load()?;
+            credentials.endpoints.insert(
+                client.endpoint().to_string(),
+                StoredCredential {
+                    cookie: secret.to_string(),
+                },
+            );
+            let path = credentials.save()?;
+            info!("Stored credentials for {} in {:?}", client.endpoint(), path);
+        }
+        SubCommand::Auth(Auth::Logout) => {
+            let mut credentials = Credentials::load()?;
+            if credentials.endpoints.remove(client.endpoint()).is_some() {
+                let path = credentials.save()?;
+                info!(
+                    "Removed credentials for {} from {:?}",
+                    client.endpoint(),
+                    path
+                );
+            } else {
+                warn!("No stored credentials for {}", client.endpoint());
+            }
+        }
+        SubCommand::Keys(Keys::Ls(ls)) => {
+            let keys = client.with_auth_cookie()?.get_api_keys(None).await?.records;
+
+            if ls.json {
+                print_json(&keys)?;
+            } else {
+                let mut stdout = io::stdout();
+                for key in keys {
+                    let state = if let Some(revoked_at) = key.revoked_at {
+                        format!("revoked {}", revoked_at.format("%Y-%m-%d %H:%M:%S")).red()
+                    } else {
+                        "active".green()
+                    };
+
+                    if writeln!(
+                        stdout,
+                        "{:>5} {:-30} {:6} {} ({})",
+                        key.id,
+                        key.name.bold(),
+                        key.scope.as_str(),
+                        key.created_at.format("%Y-%m-%d %H:%M:%S"),
+                        state,
+                    )
+                    .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        SubCommand::Keys(Keys::Issue(issue)) => {
+            let key = client
+                .with_auth_cookie()?
+                .issue_api_key(IssueApiKeyRequest {
+                    name: issue.name,
+                    scope: issue.scope,
+                })
+                .await?;
+
+            info!(
+                "Issued api key #{} ({:?}) with scope {:?}",
+                key.id,
+                key.name,
+                key.scope.as_str()
+            );
+            println!("{}", key.key);
+        }
+        SubCommand::Keys(Keys::Revoke(revoke)) => {
+            client.with_auth_cookie()?.revoke_api_key(revoke.id).await?;
+        }
+        SubCommand::Subscriptions(Subscriptions::Ls(ls)) => {
+            let subscriptions = client.with_auth_cookie()?.get_subscriptions().await?;
+
+            if ls.json {
+                print_json(&subscriptions)?;
+            } else {
+                let mut stdout = io::stdout();
+                for subscription in subscriptions {
+                    let follows = match (&subscription.name, &subscription.maintainer) {
+                        (Some(name), _) => name.bold(),
+                        (None, Some(maintainer)) => format!("maintainer {maintainer}").bold(),
+                        (None, None) => "-".normal(),
+                    };
+                    let distribution = subscription.distribution.as_deref().unwrap_or("*");
+                    let target = subscription
+                        .webhook
+                        .as_deref()
+                        .or(subscription.email.as_deref())
+                        .unwrap_or("-");
+                    let pending = if subscription.confirmed {
+                        "".normal()
+                    } else {
+                        " (waiting for confirmation)".yellow()
+                    };
+
+                    if writeln!(
+                        stdout,
+                        "{:>5} {:-30} {:-12} {}{}",
+                        subscription.id, follows, distribution, target, pending,
+                    )
+                    .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        SubCommand::Subscriptions(Subscriptions::Add(add)) => {
+            let subscription = client
+                .with_auth_cookie()?
+                .subscribe(SubscriptionRequest {
+                    name: add.name,
+                    maintainer: add.maintainer,
+                    distribution: add.distro,
+                    webhook: add.webhook,
+                    email: add.email,
+                })
+                .await?;
+            info!("Added subscription #{}", subscription.id);
+            if !subscription.confirmed {
+                info!(
+                    "Notifications are sent once the address is confirmed with the token that was mailed to it"
+                );
+            }
+        }
+        SubCommand::Subscriptions(Subscriptions::Rm(rm)) => {
+            client.with_auth_cookie()?.unsubscribe(rm.id).await?;
+        }
+        SubCommand::Subscriptions(Subscriptions::Confirm(confirm)) => {
+            client
+                .confirm_subscription(SubscriptionConfirmation {
+                    token: confirm.token,
+                })
+                .await?;
+            info!("Confirmed subscription");
+        }
+        SubCommand::Workers(Workers::Drain(selector)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &selector.name).await?;
+            client.drain_worker(worker.id).await?;
+            info!(
+                "Worker {:?} is draining, it finishes its current build but won't receive new jobs",
+                worker.name
+            );
+        }
+        SubCommand::Workers(Workers::Resume(selector)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &selector.name).await?;
+            client.resume_worker(worker.id).await?;
+            info!("Worker {:?} accepts new jobs again", worker.name);
+        }
+        SubCommand::Workers(Workers::Abort(selector)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &selector.name).await?;
+            client.abort_worker(worker.id).await?;
+            info!(
+                "Worker {:?} kills its current build on its next ping",
+                worker.name
+            );
+        }
+        SubCommand::Workers(Workers::Trust(selector)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &selector.name).await?;
+            client.trust_worker(worker.id).await?;
+            info!("Results of worker {:?} are trusted", worker.name);
+        }
+        SubCommand::Workers(Workers::Untrust(selector)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &selector.name).await?;
+            client.untrust_worker(worker.id).await?;
+            info!(
+                "Results of worker {:?} are provisional until a trusted worker confirms them",
+                worker.name
+            );
+        }
+        SubCommand::Workers(Workers::ResetFingerprint(selector)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &selector.name).await?;
+            client.reset_worker_fingerprint(worker.id).await?;
+            info!(
+                "Worker {:?} is pinned to the next client certificate it presents",
+                worker.name
+            );
+        }
+        SubCommand::Workers(Workers::Tokens(ls)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &ls.name).await?;
+            let tokens = client.get_worker_tokens(worker.id).await?;
+
+            if ls.json {
+                print_json(&tokens)?;
+            } else {
+                let mut stdout = io::stdout();
+                for token in tokens {
+                    let state = if let Some(revoked_at) = token.revoked_at {
+                        format!("revoked {}", revoked_at.format("%Y-%m-%d %H:%M:%S")).red()
+                    } else {
+                        "active".green()
+                    };
+
+                    if writeln!(
+                        stdout,
+                        "{:>5} {:6} {} ({})",
+                        token.id,
+                        token.capability.as_str(),
+                        token.created_at.format("%Y-%m-%d %H:%M:%S"),
+                        state,
+                    )
+                    .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        SubCommand::Workers(Workers::IssueToken(issue)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &issue.name).await?;
+            let token = client
+                .issue_worker_token(
+                    worker.id,
+                    IssueWorkerTokenRequest {
+                        capability: issue.capability,
+                    },
+                )
+                .await?;
+
+            info!(
+                "Issued {:?} token #{} for worker {:?}",
+                token.capability.as_str(),
+                token.id,
+                worker.name
+            );
+            println!("{}", token.token);
+        }
+        SubCommand::Workers(Workers::RevokeToken(revoke)) => {
+            let client = client.with_auth_cookie()?;
+            let worker = find_worker(client, &revoke.name).await?;
+            client.revoke_worker_token(worker.id, revoke.id).await?;
+        }
+        SubCommand::Workers(Workers::RotateKey(rotate)) => {
+            rotate_worker_key(&mut client, &rotate.identity_file).await?;
+        }
+        SubCommand::Triage(args) => triage::run(client.with_auth_cookie()?, args).await?,
         SubCommand::Completions(completions) => args::gen_completions(&completions)?,
     }
 
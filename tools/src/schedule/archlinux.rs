@@ -2,13 +2,23 @@ use crate::args::PkgsSync;
 use crate::decompress;
 use crate::schedule::{Pkg, fetch_url_or_path};
 use nom::bytes::complete::take_till;
-use rebuilderd_common::api::v1::{BinaryPackageReport, PackageReport, SourcePackageReport};
+use rebuilderd_common::api::v1::{
+    BinaryPackageReport, JobPayload, PackageReport, SourcePackageReport,
+};
 use rebuilderd_common::errors::*;
 use rebuilderd_common::http;
-use std::collections::HashMap;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::prelude::*;
+use std::sync::LazyLock;
 use tar::{Archive, EntryType};
+use tokio::task::{JoinError, JoinSet};
+use url::Url;
+
+/// How many tags of the packaging repository are looked up at once
+const PACKAGING_LOOKUPS: usize = 8;
 
 fn mirror_to_url(mut mirror: &str, repo: &str, arch: &str, file: &str) -> Result<String> {
     let mut url = String::new();
@@ -36,6 +46,207 @@ fn mirror_to_url(mut mirror: &str, repo: &str, arch: &str, file: &str) -> Result
     Ok(url)
 }
 
+/// The stable repos a testing repo feeds into, once a package moves out of testing it replaces the version in there
+fn stable_repos(repo: &str) -> &'static [&'static str] {
+    match repo {
+        "core-testing" => &["core"],
+        "extra-testing" => &["extra"],
+        "multilib-testing" => &["multilib"],
+        // before the repository merge in 2023
+        "testing" => &["core", "extra"],
+        "community-testing" => &["community"],
+        _ => &[],
+    }
+}
+
+/// A version in a stable repo that is about to be replaced by the version in testing
+#[derive(Debug, PartialEq, Eq)]
+pub struct Superseded {
+    pub distribution: String,
+    pub component: String,
+    pub architecture: String,
+    pub name: String,
+    pub version: String,
+}
+
+/// Find the versions of the stable repos that a testing repo of the same sync has a different version of. Rebuilding
+/// them is wasted work, the result is outdated as soon as the package is moved out of testing.
+pub fn superseded(reports: &[PackageReport]) -> Vec<Superseded> {
+    let mut superseded = Vec::new();
+
+    for testing in reports {
+        let Some(testing_repo) = &testing.component else {
+            continue;
+        };
+
+        for stable_repo in stable_repos(testing_repo) {
+            let Some(stable) = reports.iter().find(|report| {
+                report.component.as_deref() == Some(*stable_repo)
+                    && report.architecture == testing.architecture
+            }) else {
+                continue;
+            };
+
+            let in_testing = testing
+                .packages
+                .iter()
+                .map(|pkg| (pkg.name.as_str(), pkg.version.as_str()))
+                .collect::<HashSet<_>>();
+            let names = in_testing
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<HashSet<_>>();
+
+            for pkg in &stable.packages {
+                if names.contains(pkg.name.as_str())
+                    && !in_testing.contains(&(pkg.name.as_str(), pkg.version.as_str()))
+                {
+                    superseded.push(Superseded {
+                        distribution: stable.distribution.clone(),
+                        component: stable_repo.to_string(),
+                        architecture: stable.architecture.clone(),
+                        name: pkg.name.clone(),
+                        version: pkg.version.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    superseded
+}
+
+/// The gitlab project of a package base. Gitlab doesn't allow every character that is valid in a pkgbase, this follows
+/// the conversion of devtools, eg. `libsigc++` becomes `libsigcplusplus`.
+fn gitlab_project_name(pkgbase: &str) -> String {
+    static REWRITES: LazyLock<[(Regex, &str); 4]> = LazyLock::new(|| {
+        [
+            (Regex::new(r"([a-zA-Z0-9]+)\+([a-zA-Z]+)").unwrap(), "$1-$2"),
+            (Regex::new(r"\+").unwrap(), "plus"),
+            (Regex::new(r"[^a-zA-Z0-9_\-\.]").unwrap(), "-"),
+            (Regex::new(r"[_\-]{2,}").unwrap(), "-"),
+        ]
+    });
+
+    let mut name = pkgbase.to_string();
+    for (re, replacement) in REWRITES.iter() {
+        name = re.replace_all(&name, *replacement).into_owned();
+    }
+    if name == "tree" {
+        name = "unix-tree".to_string();
+    }
+    name
+}
+
+/// The gitlab api url of the tag a release of a package base was published with. Tags can't contain the `:` of an
+/// epoch, it's replaced with `-`.
+fn packaging_tag_url(repo: &str, pkgbase: &str, version: &str) -> Result<Url> {
+    let mut url = Url::parse(repo).with_context(|| anyhow!("Invalid packaging repo: {repo:?}"))?;
+    let project = format!(
+        "{}/{}",
+        url.path().trim_matches('/'),
+        gitlab_project_name(pkgbase)
+    );
+    let tag = version.replace(':', "-");
+
+    url.set_path("/api/v4/projects");
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Packaging repo can't be used as base url: {repo:?}"))?
+        .push(&project)
+        .extend(["repository", "tags", &tag]);
+    Ok(url)
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabTag {
+    commit: GitlabCommit,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabCommit {
+    id: String,
+}
+
+async fn fetch_tag_commit(http: &http::Client, url: Url) -> Result<String> {
+    let tag = http
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<GitlabTag>()
+        .await?;
+    Ok(tag.commit.id)
+}
+
+type Lookup = (String, String, Result<String>);
+
+fn record_lookup(
+    commits: &mut HashMap<(String, String), String>,
+    joined: Result<Lookup, JoinError>,
+) -> Result<()> {
+    let (pkgbase, version, commit) = joined?;
+    match commit {
+        Ok(commit) => {
+            commits.insert((pkgbase, version), commit);
+        }
+        Err(err) => warn!("Failed to resolve packaging commit of {pkgbase} {version}: {err:#}"),
+    }
+    Ok(())
+}
+
+/// Pin the packages to the commit of the packaging repository their version was tagged at, so workers don't build
+/// from whatever the packaging looks like by the time the job is picked up. Packages that can't be resolved are
+/// imported without a commit.
+async fn resolve_packaging_commits(
+    http: &http::Client,
+    repo: &str,
+    reports: &mut [PackageReport],
+) -> Result<()> {
+    // the same package base shows up in every architecture it's built for
+    let releases = reports
+        .iter()
+        .flat_map(|report| &report.packages)
+        .map(|pkg| (pkg.name.clone(), pkg.version.clone()))
+        .collect::<BTreeSet<_>>();
+    info!(
+        "Resolving packaging commits of {} package bases...",
+        releases.len()
+    );
+
+    let mut commits = HashMap::new();
+    let mut lookups = JoinSet::new();
+    for (pkgbase, version) in releases {
+        if lookups.len() >= PACKAGING_LOOKUPS
+            && let Some(joined) = lookups.join_next().await
+        {
+            record_lookup(&mut commits, joined)?;
+        }
+
+        let url = packaging_tag_url(repo, &pkgbase, &version)?;
+        let http = http.clone();
+        lookups.spawn(async move {
+            let commit = fetch_tag_commit(&http, url).await;
+            (pkgbase, version, commit)
+        });
+    }
+    while let Some(joined) = lookups.join_next().await {
+        record_lookup(&mut commits, joined)?;
+    }
+
+    for pkg in reports.iter_mut().flat_map(|report| &mut report.packages) {
+        if let Some(JobPayload::Archlinux {
+            packaging_commit, ..
+        }) = &mut pkg.payload
+        {
+            *packaging_commit = commits
+                .get(&(pkg.name.clone(), pkg.version.clone()))
+                .cloned();
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct ArchPkg {
     pub name: String,
@@ -44,6 +255,7 @@ pub struct ArchPkg {
     pub version: String,
     pub architecture: String,
     pub packager: String,
+    pub sha256: Option<String>,
 }
 
 impl Pkg for ArchPkg {
@@ -64,6 +276,7 @@ pub struct NewPkg {
     version: Vec<String>,
     architecture: Vec<String>,
     packager: Vec<String>,
+    sha256: Vec<String>,
 }
 
 impl TryInto<ArchPkg> for NewPkg {
@@ -101,6 +314,7 @@ impl TryInto<ArchPkg> for NewPkg {
                 .first()
                 .ok_or_else(|| anyhow!("Missing packager field"))?
                 .to_string(),
+            sha256: self.sha256.into_iter().next(),
         })
     }
 }
@@ -137,6 +351,7 @@ pub fn extract_pkgs(bytes: &[u8]) -> Result<Vec<ArchPkg>> {
                     "%VERSION%" => pkg.version = values,
                     "%ARCH%" => pkg.architecture = values,
                     "%PACKAGER%" => pkg.packager = values,
+                    "%SHA256SUM%" => pkg.sha256 = values,
                     _ => (),
                 }
             }
@@ -169,6 +384,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                 release: None,
                 component: Some(component.clone()),
                 architecture: arch.clone(),
+                source: None,
                 packages: Vec::new(),
             };
 
@@ -186,6 +402,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                     version: pkg.version.clone(),
                     architecture: pkg.architecture,
                     url: url.clone(),
+                    checksum: pkg.sha256,
                 };
 
                 if let Some(group) = bases.get_mut(&pkg.base) {
@@ -196,6 +413,11 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                         name: pkg.base.clone(),
                         version: pkg.version.clone(),
                         url: url.clone(), // use first artifact's url as the source URL for now
+                        payload: Some(JobPayload::Archlinux {
+                            package_url: url.clone(),
+                            packaging_commit: None,
+                        }),
+                        maintainer: Some(pkg.packager.clone()),
                         artifacts: Vec::new(),
                     };
 
@@ -209,6 +431,10 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
         }
     }
 
+    if let Some(repo) = &sync.packaging_repo {
+        resolve_packaging_commits(http, repo, &mut reports).await?;
+    }
+
     Ok(reports)
 }
 
@@ -216,6 +442,81 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
 mod tests {
     use super::*;
 
+    fn report(component: &str, packages: &[(&str, &str)]) -> PackageReport {
+        PackageReport {
+            distribution: "archlinux".to_string(),
+            release: None,
+            component: Some(component.to_string()),
+            architecture: "x86_64".to_string(),
+            source: None,
+            packages: packages
+                .iter()
+                .map(|(name, version)| SourcePackageReport {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    url: format!("https://example.com/{name}-{version}.pkg.tar.zst"),
+                    payload: None,
+                    maintainer: None,
+                    artifacts: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_superseded_by_testing() {
+        let reports = vec![
+            report("core", &[("linux", "6.1-1"), ("glibc", "2.40-1")]),
+            report("core-testing", &[("linux", "6.2-1")]),
+            report("extra", &[("linux", "6.0-1"), ("rebuilderd", "0.2.1-1")]),
+            // the same version in both repos isn't superseded
+            report("multilib", &[("lib32-glibc", "2.40-1")]),
+            report("multilib-testing", &[("lib32-glibc", "2.40-1")]),
+        ];
+        assert_eq!(
+            superseded(&reports),
+            vec![Superseded {
+                distribution: "archlinux".to_string(),
+                component: "core".to_string(),
+                architecture: "x86_64".to_string(),
+                name: "linux".to_string(),
+                version: "6.1-1".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_superseded_needs_stable_repo() {
+        let reports = vec![report("extra-testing", &[("rebuilderd", "0.2.2-1")])];
+        assert_eq!(superseded(&reports), vec![]);
+    }
+
+    #[test]
+    fn test_gitlab_project_name() {
+        assert_eq!(gitlab_project_name("rebuilderd"), "rebuilderd");
+        assert_eq!(gitlab_project_name("libsigc++"), "libsigcplusplus");
+        assert_eq!(gitlab_project_name("dvd+rw-tools"), "dvd-rw-tools");
+        assert_eq!(
+            gitlab_project_name("python-ruamel.yaml"),
+            "python-ruamel.yaml"
+        );
+        assert_eq!(gitlab_project_name("tree"), "unix-tree");
+    }
+
+    #[test]
+    fn test_packaging_tag_url() {
+        let url = packaging_tag_url(
+            "https://gitlab.archlinux.org/archlinux/packaging/packages",
+            "libsigc++",
+            "1:3.6.0-1",
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://gitlab.archlinux.org/api/v4/projects/archlinux%2Fpackaging%2Fpackages%2Flibsigcplusplus/repository/tags/1-3.6.0-1"
+        );
+    }
+
     #[test]
     fn test_mirror_to_url() {
         let url = mirror_to_url(
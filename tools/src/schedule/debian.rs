@@ -1,15 +1,20 @@
 use crate::args::PkgsSync;
+use crate::decompress;
 use crate::schedule::{Pkg, fetch_url_or_path};
-use rebuilderd_common::api::v1::{BinaryPackageReport, PackageReport, SourcePackageReport};
+use rebuilderd_common::api::v1::{
+    BinaryPackageReport, JobPayload, PackageReport, SourcePackageReport,
+};
 use rebuilderd_common::errors::*;
 use rebuilderd_common::http;
 use std::collections::HashMap;
 use std::io::BufReader;
 use std::io::prelude::*;
-use xz2::read::XzDecoder;
 
 pub const BIN_NMU_PREFIX: &str = "+b";
 
+/// Archives publish their indexes in one or more of these formats, the first one that exists on the mirror is used
+const INDEX_EXTENSIONS: &[&str] = &[".xz", ".zst", ".gz", ""];
+
 #[derive(Debug, Default)]
 pub struct SourcePkgBucket {
     pkgs: HashMap<String, Vec<DebianSourcePkg>>,
@@ -88,6 +93,7 @@ pub struct DebianSourcePkg {
     pub version: String,
     pub directory: String,
     pub architecture: String,
+    pub maintainer: Option<String>,
     pub uploaders: Vec<String>,
 }
 
@@ -101,19 +107,44 @@ impl DebianSourcePkg {
         }
     }
 
-    fn buildinfo_url(&self, arch: &str) -> String {
-        let directory = self.buildinfo_path();
-        let version_without_epoch = if let Some((_epoch, version)) = self.version.split_once(':') {
+    fn version_without_epoch(&self) -> &str {
+        if let Some((_epoch, version)) = self.version.split_once(':') {
             version
         } else {
             &self.version
-        };
+        }
+    }
+
+    fn buildinfo_url(&self, arch: &str) -> String {
+        let directory = self.buildinfo_path();
         let buildinfo_url = format!(
             "https://buildinfos.debian.net/buildinfo-pool/{}/{}/{}_{}_{}.buildinfo",
-            directory, self.base, self.base, version_without_epoch, arch
+            directory,
+            self.base,
+            self.base,
+            self.version_without_epoch(),
+            arch
         );
         buildinfo_url
     }
+
+    fn dsc_url(&self, mirror: &str) -> String {
+        // binNMUs are built from the unmodified source package
+        let version = self.version_without_epoch();
+        let version = match version.rsplit_once(BIN_NMU_PREFIX) {
+            Some((version, num)) if num.parse::<u64>().is_ok() => version,
+            _ => version,
+        };
+        format!("{mirror}/{}/{}_{version}.dsc", self.directory, self.base)
+    }
+
+    fn payload(&self, mirror: &str, arch: &str) -> JobPayload {
+        JobPayload::Debian {
+            buildinfo_url: self.buildinfo_url(arch),
+            dsc_url: self.dsc_url(mirror),
+            binaries: self.binary.clone(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -147,6 +178,7 @@ pub struct NewPkg {
     directory: Option<String>,
     architecture: Option<String>,
     filename: Option<String>,
+    maintainer: Option<String>,
     uploaders: Vec<String>,
 }
 
@@ -172,6 +204,7 @@ impl AnyhowTryFrom<NewPkg> for DebianSourcePkg {
             architecture: new
                 .architecture
                 .ok_or_else(|| format_err!("Missing architecture field"))?,
+            maintainer: new.maintainer,
             uploaders: new.uploaders,
         })
     }
@@ -218,7 +251,8 @@ impl AnyhowTryFrom<NewPkg> for DebianBinPkg {
 }
 
 pub fn extract_pkgs_compressed<T: AnyhowTryFrom<NewPkg>>(bytes: &[u8]) -> Result<Vec<T>> {
-    let r = XzDecoder::new(bytes);
+    let comp = decompress::detect_compression(bytes);
+    let r = decompress::stream(comp, bytes)?;
     let r = BufReader::new(r);
     extract_pkgs_uncompressed(r)
 }
@@ -270,6 +304,7 @@ pub fn extract_pkgs_uncompressed<T: AnyhowTryFrom<NewPkg>, R: BufRead>(r: R) ->
                 "Directory" => pkg.directory = Some(b.to_string()),
                 "Filename" => pkg.filename = Some(b.to_string()),
                 "Architecture" => pkg.architecture = Some(b.to_string()),
+                "Maintainer" => pkg.maintainer = Some(b.to_string()),
                 "Uploaders" => {
                     let mut uploaders = Vec::new();
                     for uploader in b.split(", ") {
@@ -311,6 +346,7 @@ impl SyncState {
             release: Some(release.to_string()),
             component: Some(component.to_string()),
             architecture: architecture.to_string(),
+            source: None,
             packages: Vec::new(),
         })
     }
@@ -318,6 +354,7 @@ impl SyncState {
     fn get_mut_group(
         &mut self,
         src: &DebianSourcePkg,
+        source: &str,
         release: &str,
         component: &str,
         architecture: &str,
@@ -335,6 +372,8 @@ impl SyncState {
                     name: src.base.clone(),
                     version: src.version.clone(),
                     url: src.buildinfo_url(architecture),
+                    payload: Some(src.payload(source, architecture)),
+                    maintainer: src.maintainer.clone(),
                     artifacts: Vec::new(),
                 };
 
@@ -352,13 +391,14 @@ impl SyncState {
         release: &str,
         component: &str,
     ) {
-        let group = self.get_mut_group(src, release, component, &bin.architecture);
+        let group = self.get_mut_group(src, source, release, component, &bin.architecture);
         let url = format!("{}/{}", source, bin.filename);
         group.artifacts.push(BinaryPackageReport {
             name: bin.name,
             version: bin.version,
             architecture: bin.architecture.clone(),
             url,
+            checksum: None,
         });
     }
 
@@ -438,6 +478,24 @@ impl SyncState {
     }
 }
 
+/// Fetches an index like `Sources` in whichever compression format the archive provides
+async fn fetch_index(http: &http::Client, base_url: &str) -> Result<Vec<u8>> {
+    let mut error = None;
+    for ext in INDEX_EXTENSIONS {
+        let url = format!("{base_url}{ext}");
+        match fetch_url_or_path(http, &url).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) => {
+                debug!("Failed to fetch {url:?}: {err:#}");
+                // the error of the preferred format is the most useful one
+                error.get_or_insert(err);
+            }
+        }
+    }
+    let error = error.unwrap_or_else(|| anyhow!("No index formats to try"));
+    Err(error.context(format!("Failed to fetch index {base_url:?} in any format")))
+}
+
 pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageReport>> {
     let mut state = SyncState::new();
 
@@ -447,11 +505,11 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
         for component in &sync.components {
             // Downloading source package index
             let db_url = format!(
-                "{}/dists/{}/{}/source/Sources.xz",
+                "{}/dists/{}/{}/source/Sources",
                 sync.source, release, component
             );
 
-            let bytes = fetch_url_or_path(http, &db_url).await?;
+            let bytes = fetch_index(http, &db_url).await?;
 
             info!("Building map of all source packages");
             sources.import_compressed_source_package_file(&bytes)?;
@@ -460,23 +518,23 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                 for db_url in [
                     // Binary package index
                     format!(
-                        "{}/dists/{}/{}/binary-{}/Packages.xz",
+                        "{}/dists/{}/{}/binary-{}/Packages",
                         sync.source, release, component, arch
                     ),
                     // Binary installer package index
                     format!(
-                        "{}/dists/{}/{}/debian-installer/binary-{}/Packages.xz",
+                        "{}/dists/{}/{}/debian-installer/binary-{}/Packages",
                         sync.source, release, component, arch
                     ),
                 ] {
-                    match fetch_url_or_path(http, &db_url).await {
+                    match fetch_index(http, &db_url).await {
                         Ok(bytes) => {
                             state.import_compressed_binary_package_file(
                                 &bytes, &sources, release, component, sync,
                             )?;
                         }
                         Err(e) => {
-                            warn!("{}, skipping", e);
+                            warn!("{:#}, skipping", e);
                         }
                     }
                 }
@@ -492,6 +550,14 @@ mod tests {
     use super::*;
     use std::io::Cursor;
 
+    fn payload(buildinfo_url: &str, dsc_url: &str, binaries: &[&str]) -> Option<JobPayload> {
+        Some(JobPayload::Debian {
+            buildinfo_url: buildinfo_url.to_string(),
+            dsc_url: dsc_url.to_string(),
+            binaries: binaries.iter().map(|name| name.to_string()).collect(),
+        })
+    }
+
     #[test]
     fn test_sync_empty_release() {
         let mut state = SyncState::new();
@@ -512,6 +578,9 @@ mod tests {
                     pkgs: vec![],
                     excludes: vec![],
                     sync_method: None,
+                    delta_state: None,
+                    popularity: None,
+                    packaging_repo: None,
                 },
             )
             .unwrap();
@@ -522,6 +591,7 @@ mod tests {
                 release: Some("trixie-proposed-updates".to_string()),
                 component: Some("main".to_string()),
                 architecture: "amd64".to_string(),
+                source: None,
                 packages: vec![],
             }]
         );
@@ -700,6 +770,10 @@ Section: misc
                 version: "1:10.5.12-1".to_string(),
                 directory: "pool/main/m/mariadb-10.5".to_string(),
                 architecture: "any all".to_string(),
+                maintainer: Some(
+                    "Debian MySQL Maintainers <pkg-mysql-maint@lists.alioth.debian.org>"
+                        .to_string()
+                ),
                 uploaders: vec!["Otto Kekäläinen <otto@debian.org>".to_string()],
             }]
         );
@@ -738,6 +812,9 @@ Section: misc
             version: "1:10.5.12-1".to_string(),
             directory: "pool/main/m/mariadb-10.5".to_string(),
             architecture: "any all".to_string(),
+            maintainer: Some(
+                "Debian MySQL Maintainers <pkg-mysql-maint@lists.alioth.debian.org>".to_string(),
+            ),
             uploaders: vec!["Otto Kekäläinen <otto@debian.org>".to_string()],
         };
         let bin = DebianBinPkg {
@@ -760,17 +837,25 @@ Section: misc
             release: Some("sid".to_string()),
             component: Some("main".to_string()),
             architecture: "all".to_string(),
+            source: None,
             packages: vec![
                 SourcePackageReport {
                     name: "mariadb-10.5".to_string(),
                     version: "1:10.5.12-1".to_string(),
                     url: "https://buildinfos.debian.net/buildinfo-pool/m/mariadb-10.5/mariadb-10.5_10.5.12-1_all.buildinfo".to_string(),
+                    payload: Some(JobPayload::Debian {
+                        buildinfo_url: "https://buildinfos.debian.net/buildinfo-pool/m/mariadb-10.5/mariadb-10.5_10.5.12-1_all.buildinfo".to_string(),
+                        dsc_url: "https://deb.debian.org/debian/pool/main/m/mariadb-10.5/mariadb-10.5_10.5.12-1.dsc".to_string(),
+                        binaries: src.binary.clone(),
+                    }),
+                    maintainer: Some("Debian MySQL Maintainers <pkg-mysql-maint@lists.alioth.debian.org>".to_string()),
                     artifacts: vec![
                         BinaryPackageReport {
                             name: "mariadb-server".to_string(),
                             version: "1:10.5.12-1".to_string(),
                             architecture: "all".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/m/mariadb-10.5/mariadb-server_10.5.12-1_all.deb".to_string(),
+                            checksum: None,
                         }
                     ],
                 },
@@ -872,23 +957,32 @@ Section: misc
             release: Some("sid".to_string()),
             component: Some("main".to_string()),
             architecture: "amd64".to_string(),
+            source: None,
             packages: vec![
                 SourcePackageReport {
                     name: "rust-sniffglue".to_string(),
                     version: "0.14.0-2".to_string(),
                     url: "https://buildinfos.debian.net/buildinfo-pool/r/rust-sniffglue/rust-sniffglue_0.14.0-2_amd64.buildinfo".to_string(),
+                    payload: payload(
+                        "https://buildinfos.debian.net/buildinfo-pool/r/rust-sniffglue/rust-sniffglue_0.14.0-2_amd64.buildinfo",
+                        "https://deb.debian.org/debian/pool/main/r/rust-sniffglue/rust-sniffglue_0.14.0-2.dsc",
+                        &["librust-sniffglue-dev", "sniffglue"],
+                    ),
+                    maintainer: Some("Debian Rust Maintainers <pkg-rust-maintainers@alioth-lists.debian.net>".to_string()),
                     artifacts: vec![
                         BinaryPackageReport {
                             name: "librust-sniffglue-dev".to_string(),
                             version: "0.14.0-2".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/r/rust-sniffglue/librust-sniffglue-dev_0.14.0-2_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "sniffglue".to_string(),
                             version: "0.14.0-2".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/r/rust-sniffglue/sniffglue_0.14.0-2_amd64.deb".to_string(),
+                            checksum: None,
                         }
                     ],
                 },
@@ -1202,71 +1296,88 @@ Section: mail
             release: Some("sid".to_string()),
             component: Some("main".to_string()),
             architecture: "amd64".to_string(),
+            source: None,
             packages: vec![
                 SourcePackageReport {
                     name: "courier".to_string(),
                     version: "1.0.16-3+b1".to_string(),
                     url: "https://buildinfos.debian.net/buildinfo-pool/c/courier/courier_1.0.16-3+b1_amd64.buildinfo".to_string(),
+                    payload: payload(
+                        "https://buildinfos.debian.net/buildinfo-pool/c/courier/courier_1.0.16-3+b1_amd64.buildinfo",
+                        "https://deb.debian.org/debian/pool/main/c/courier/courier_1.0.16-3.dsc",
+                        &["courier-base", "courier-mlm", "courier-mta", "courier-faxmail", "courier-webadmin", "sqwebmail", "courier-pcp", "courier-pop", "courier-imap", "courier-ldap", "courier-doc"],
+                    ),
+                    maintainer: Some("Markus Wanner <markus@bluegap.ch>".to_string()),
                     artifacts: vec![
                         BinaryPackageReport {
                             name: "courier-base".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-base_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-faxmail".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-faxmail_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-imap".to_string(),
                             version: "5.0.13+1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-imap_5.0.13+1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-ldap".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-ldap_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-mlm".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-mlm_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-mta".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-mta_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-pcp".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-pcp_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-pop".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-pop_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "courier-webadmin".to_string(),
                             version: "1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-webadmin_1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                         BinaryPackageReport {
                             name: "sqwebmail".to_string(),
                             version: "6.0.5+1.0.16-3+b1".to_string(),
                             architecture: "amd64".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/sqwebmail_6.0.5+1.0.16-3+b1_amd64.deb".to_string(),
+                            checksum: None,
                         },
                     ],
                 },
@@ -1278,17 +1389,25 @@ Section: mail
             release: Some("sid".to_string()),
             component: Some("main".to_string()),
             architecture: "all".to_string(),
+            source: None,
             packages: vec![
                 SourcePackageReport {
                     name: "courier".to_string(),
                     version: "1.0.16-3".to_string(),
                     url: "https://buildinfos.debian.net/buildinfo-pool/c/courier/courier_1.0.16-3_all.buildinfo".to_string(),
+                    payload: payload(
+                        "https://buildinfos.debian.net/buildinfo-pool/c/courier/courier_1.0.16-3_all.buildinfo",
+                        "https://deb.debian.org/debian/pool/main/c/courier/courier_1.0.16-3.dsc",
+                        &["courier-base", "courier-mlm", "courier-mta", "courier-faxmail", "courier-webadmin", "sqwebmail", "courier-pcp", "courier-pop", "courier-imap", "courier-ldap", "courier-doc"],
+                    ),
+                    maintainer: Some("Markus Wanner <markus@bluegap.ch>".to_string()),
                     artifacts: vec![
                         BinaryPackageReport {
                             name: "courier-doc".to_string(),
                             version: "1.0.16-3".to_string(),
                             architecture: "all".to_string(),
                             url: "https://deb.debian.org/debian/pool/main/c/courier/courier-doc_1.0.16-3_all.deb".to_string(),
+                            checksum: None,
                         }
                     ],
                 },
@@ -1444,6 +1563,7 @@ Section: misc
                 version: "0.0.37-1".to_string(),
                 directory: "pool/main/b/buildlog-consultant".to_string(),
                 architecture: "any".to_string(),
+                maintainer: Some("Jelmer Vernooĳ <jelmer@debian.org>".to_string()),
                 uploaders: vec![],
             }
         );
@@ -1462,6 +1582,10 @@ Section: misc
                 version: "0.0.44-1+b1".to_string(),
                 directory: "pool/main/r/rust-buildlog-consultant".to_string(),
                 architecture: "any".to_string(),
+                maintainer: Some(
+                    "Debian Rust Maintainers <pkg-rust-maintainers@alioth-lists.debian.net>"
+                        .to_string()
+                ),
                 uploaders: vec!["Jelmer Vernooĳ <jelmer@debian.org>".to_string()],
             }
         );
@@ -1542,6 +1666,9 @@ SHA256: cc2081a6b2f6dcb82039b5097405b5836017a7bfc54a78eba36b656549e17c92
             pkgs: vec![],
             excludes: vec![],
             sync_method: None,
+            delta_state: None,
+            popularity: None,
+            packaging_repo: None,
         };
 
         // add the package list twice, to simulate importing sid and testing
@@ -1558,17 +1685,25 @@ SHA256: cc2081a6b2f6dcb82039b5097405b5836017a7bfc54a78eba36b656549e17c92
             release: Some("sid".to_string()),
             component: Some("main".to_string()),
             architecture: "amd64".to_string(),
+            source: None,
             packages: vec![
                 SourcePackageReport {
                     name: "rust-repro-env".to_string(),
                     version: "0.4.3-2".to_string(),
                     url: "https://buildinfos.debian.net/buildinfo-pool/r/rust-repro-env/rust-repro-env_0.4.3-2_amd64.buildinfo".to_string(),
+                    payload: payload(
+                        "https://buildinfos.debian.net/buildinfo-pool/r/rust-repro-env/rust-repro-env_0.4.3-2_amd64.buildinfo",
+                        "http://deb.debian.org/debian/pool/main/r/rust-repro-env/rust-repro-env_0.4.3-2.dsc",
+                        &["librust-repro-env-dev", "repro-env"],
+                    ),
+                    maintainer: Some("Debian Rust Maintainers <pkg-rust-maintainers@alioth-lists.debian.net>".to_string()),
                     artifacts: vec![
                         BinaryPackageReport {
                             name: "repro-env".to_string(),
                             version: "0.4.3-2".to_string(),
                             architecture: "amd64".to_string(),
                             url: "http://deb.debian.org/debian/pool/main/r/rust-repro-env/repro-env_0.4.3-2_amd64.deb".to_string(),
+                            checksum: None,
                         },
                     ]
                 }
@@ -1580,17 +1715,25 @@ SHA256: cc2081a6b2f6dcb82039b5097405b5836017a7bfc54a78eba36b656549e17c92
             release: Some("testing".to_string()),
             component: Some("main".to_string()),
             architecture: "amd64".to_string(),
+            source: None,
             packages: vec![
                 SourcePackageReport {
                     name: "rust-repro-env".to_string(),
                     version: "0.4.3-2".to_string(),
                     url: "https://buildinfos.debian.net/buildinfo-pool/r/rust-repro-env/rust-repro-env_0.4.3-2_amd64.buildinfo".to_string(),
+                    payload: payload(
+                        "https://buildinfos.debian.net/buildinfo-pool/r/rust-repro-env/rust-repro-env_0.4.3-2_amd64.buildinfo",
+                        "http://deb.debian.org/debian/pool/main/r/rust-repro-env/rust-repro-env_0.4.3-2.dsc",
+                        &["librust-repro-env-dev", "repro-env"],
+                    ),
+                    maintainer: Some("Debian Rust Maintainers <pkg-rust-maintainers@alioth-lists.debian.net>".to_string()),
                     artifacts: vec![
                         BinaryPackageReport {
                             name: "repro-env".to_string(),
                             version: "0.4.3-2".to_string(),
                             architecture: "amd64".to_string(),
                             url: "http://deb.debian.org/debian/pool/main/r/rust-repro-env/repro-env_0.4.3-2_amd64.deb".to_string(),
+                            checksum: None,
                         },
                     ]
                 }
@@ -1615,6 +1758,9 @@ SHA256: cc2081a6b2f6dcb82039b5097405b5836017a7bfc54a78eba36b656549e17c92
             pkgs: vec![],
             excludes: vec![],
             sync_method: None,
+            delta_state: None,
+            popularity: None,
+            packaging_repo: None,
         };
 
         // sid
@@ -1800,17 +1946,25 @@ SHA256: 89c378d37058ea2a6c5d4bb2c1d47c4810f7504bde9e4d8142ac9781ce9df002
                release: Some("sid".to_string()),
                component: Some("main".to_string()),
                architecture: "all".to_string(),
+               source: None,
                packages: vec![
                    SourcePackageReport {
                        name: "novnc".to_string(),
                        version: "1:1.6.0-2".to_string(),
                        url: "https://buildinfos.debian.net/buildinfo-pool/n/novnc/novnc_1.6.0-2_all.buildinfo".to_string(),
+                       payload: payload(
+                           "https://buildinfos.debian.net/buildinfo-pool/n/novnc/novnc_1.6.0-2_all.buildinfo",
+                           "http://deb.debian.org/debian/pool/main/n/novnc/novnc_1.6.0-2.dsc",
+                           &["novnc"],
+                       ),
+                       maintainer: Some("Debian OpenStack <team+openstack@tracker.debian.org>".to_string()),
                        artifacts: vec![
                            BinaryPackageReport {
                                name: "novnc".to_string(),
                                version: "1:1.6.0-2".to_string(),
                                architecture: "all".to_string(),
                                url: "http://deb.debian.org/debian/pool/main/n/novnc/novnc_1.6.0-2_all.deb".to_string(),
+                               checksum: None,
                            },
                        ]
                    },
@@ -1818,12 +1972,19 @@ SHA256: 89c378d37058ea2a6c5d4bb2c1d47c4810f7504bde9e4d8142ac9781ce9df002
                        name: "novnc".to_string(),
                        version: "1:1.6.0-1".to_string(),
                        url: "https://buildinfos.debian.net/buildinfo-pool/n/novnc/novnc_1.6.0-1_all.buildinfo".to_string(),
+                       payload: payload(
+                           "https://buildinfos.debian.net/buildinfo-pool/n/novnc/novnc_1.6.0-1_all.buildinfo",
+                           "http://deb.debian.org/debian/pool/main/n/novnc/novnc_1.6.0-1.dsc",
+                           &["novnc", "python3-novnc"],
+                       ),
+                       maintainer: Some("Debian OpenStack <team+openstack@tracker.debian.org>".to_string()),
                        artifacts: vec![
                            BinaryPackageReport {
                                name: "python3-novnc".to_string(),
                                version: "1:1.6.0-1".to_string(),
                                architecture: "all".to_string(),
                                url: "http://deb.debian.org/debian/pool/main/n/novnc/python3-novnc_1.6.0-1_all.deb".to_string(),
+                               checksum: None,
                            },
                        ]
                    },
@@ -1836,23 +1997,32 @@ SHA256: 89c378d37058ea2a6c5d4bb2c1d47c4810f7504bde9e4d8142ac9781ce9df002
                release: Some("testing".to_string()),
                component: Some("main".to_string()),
                architecture: "all".to_string(),
+               source: None,
                packages: vec![
                    SourcePackageReport {
                        name: "novnc".to_string(),
                        version: "1:1.6.0-1".to_string(),
                        url: "https://buildinfos.debian.net/buildinfo-pool/n/novnc/novnc_1.6.0-1_all.buildinfo".to_string(),
+                       payload: payload(
+                           "https://buildinfos.debian.net/buildinfo-pool/n/novnc/novnc_1.6.0-1_all.buildinfo",
+                           "http://deb.debian.org/debian/pool/main/n/novnc/novnc_1.6.0-1.dsc",
+                           &["novnc", "python3-novnc"],
+                       ),
+                       maintainer: Some("Debian OpenStack <team+openstack@tracker.debian.org>".to_string()),
                        artifacts: vec![
                            BinaryPackageReport {
                                name: "novnc".to_string(),
                                version: "1:1.6.0-1".to_string(),
                                architecture: "all".to_string(),
                                url: "http://deb.debian.org/debian/pool/main/n/novnc/novnc_1.6.0-1_all.deb".to_string(),
+                               checksum: None,
                            },
                            BinaryPackageReport {
                                name: "python3-novnc".to_string(),
                                version: "1:1.6.0-1".to_string(),
                                architecture: "all".to_string(),
                                url: "http://deb.debian.org/debian/pool/main/n/novnc/python3-novnc_1.6.0-1_all.deb".to_string(),
+                               checksum: None,
                            },
                        ]
                    },
@@ -1867,6 +2037,7 @@ SHA256: 89c378d37058ea2a6c5d4bb2c1d47c4810f7504bde9e4d8142ac9781ce9df002
                 release: Some("sid".to_string()),
                 component: Some("main".to_string()),
                 architecture: "amd64".to_string(),
+                source: None,
                 packages: vec![],
             },
         );
@@ -1882,6 +2053,7 @@ SHA256: 89c378d37058ea2a6c5d4bb2c1d47c4810f7504bde9e4d8142ac9781ce9df002
                 release: Some("testing".to_string()),
                 component: Some("main".to_string()),
                 architecture: "amd64".to_string(),
+                source: None,
                 packages: vec![],
             },
         );
@@ -0,0 +1,56 @@
+use rebuilderd_common::errors::*;
+use std::collections::BTreeMap;
+
+/// Parse a list of build durations, one source package name and the duration in seconds per line. Empty lines and
+/// lines starting with `#` are skipped.
+pub fn parse(text: &str) -> Result<BTreeMap<String, i64>> {
+    let mut packages = BTreeMap::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(seconds), None) = (fields.next(), fields.next(), fields.next())
+        else {
+            bail!("Invalid build duration in line {}: {:?}", idx + 1, line);
+        };
+        let seconds = seconds
+            .parse()
+            .with_context(|| anyhow!("Invalid build duration in line {}: {:?}", idx + 1, line))?;
+        packages.insert(name.to_string(), seconds);
+    }
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_durations() {
+        let text = "# name seconds
+linux 14400
+  zstd   95
+
+firefox 10800
+";
+        let packages = parse(text).unwrap();
+        assert_eq!(
+            packages,
+            BTreeMap::from([
+                ("firefox".to_string(), 10800),
+                ("linux".to_string(), 14400),
+                ("zstd".to_string(), 95),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_durations_invalid() {
+        assert!(parse("linux 4h\n").is_err());
+        assert!(parse("linux\n").is_err());
+        assert!(parse("linux 14400 x86_64\n").is_err());
+    }
+}
@@ -21,8 +21,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                 let bytes = fetch_url_or_path(http, &format!("{url}{location}")).await?;
                 info!("Parsing index ({} bytes)...", bytes.len());
 
-                let comp = decompress::detect_compression(&bytes);
-                let data = decompress::stream(comp, &bytes)?;
+                let data = decompress::read(&location, &bytes)?;
                 let packages = parse_package_index(data)?;
 
                 let mut report = PackageReport {
@@ -30,6 +29,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                     release: None,
                     component: Some(component.clone()),
                     architecture: arch.clone(),
+                    source: None,
                     packages: Vec::new(),
                 };
 
@@ -47,6 +47,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                         version,
                         architecture: pkg.arch,
                         url: url.clone(),
+                        checksum: None,
                     };
 
                     if let Some(group) = bases.get_mut(&pkg.format.sourcerpm) {
@@ -56,6 +57,8 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                             name: pkg.format.sourcerpm.clone(),
                             version: format!("{}-{}", pkg.version.ver, pkg.version.rel),
                             url: url.clone(), // use first artifact's url as the source URL for now
+                            payload: None,
+                            maintainer: None,
                             artifacts: Vec::new(),
                         };
 
@@ -0,0 +1,342 @@
+use crate::args::PkgsSync;
+use crate::decompress;
+use crate::schedule::{Pkg, fetch_url_or_path};
+use nom::bytes::complete::take_till;
+use rebuilderd_common::api::v1::{BinaryPackageReport, PackageReport, SourcePackageReport};
+use rebuilderd_common::errors::*;
+use rebuilderd_common::http;
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+fn mirror_to_url(mut mirror: &str, arch: &str, repo: &str, file: &str) -> Result<String> {
+    let mut url = String::new();
+
+    loop {
+        let (s, txt) = take_till::<_, _, ()>(|c| c == '$')(mirror).unwrap();
+        url.push_str(txt);
+        if s.is_empty() {
+            break;
+        }
+        let (s, var) = take_till::<_, _, ()>(|c| c == '/')(s).unwrap();
+        match var {
+            "$arch" => url.push_str(arch),
+            "$repo" => url.push_str(repo),
+            _ => bail!("Unrecognized variable: {:?}", var),
+        }
+        mirror = s;
+    }
+
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+    url.push_str(file);
+
+    Ok(url)
+}
+
+/// Split a category/package-version string into the package atom and its version, eg. `app-arch/zstd-1.5.5-r1`
+fn split_cpv(cpv: &str) -> Result<(&str, &str)> {
+    // the version starts after the last dash that is followed by a digit, revisions are part of the version
+    let mut rest = cpv;
+    while let Some((name, version)) = rest.rsplit_once('-') {
+        if version.starts_with(|c: char| c.is_ascii_digit()) {
+            let version = &cpv[name.len() + 1..];
+            if name.contains('/') && !name.ends_with('/') {
+                return Ok((name, version));
+            }
+            break;
+        }
+        rest = name;
+    }
+    bail!("Invalid package atom: {:?}", cpv)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GentooPkg {
+    /// The package atom without version, eg. `app-arch/zstd`
+    pub name: String,
+    pub version: String,
+    /// Binhosts can hold multiple builds of the same version with different settings
+    pub build_id: u64,
+    pub path: String,
+    /// The ebuild repository the package was built from, only `gentoo` packages can be rebuilt from a snapshot
+    pub repo: Option<String>,
+}
+
+impl Pkg for GentooPkg {
+    fn pkg_name(&self) -> &str {
+        &self.name
+    }
+
+    fn by_maintainer(&self, _maintainers: &[String]) -> bool {
+        // the index doesn't record maintainers, they are only listed in the metadata.xml of the ebuild repository
+        false
+    }
+}
+
+#[derive(Debug, Default)]
+struct NewPkg {
+    cpv: Option<String>,
+    build_id: Option<u64>,
+    path: Option<String>,
+    repo: Option<String>,
+}
+
+impl NewPkg {
+    fn is_empty(&self) -> bool {
+        self.cpv.is_none() && self.build_id.is_none() && self.path.is_none() && self.repo.is_none()
+    }
+}
+
+impl TryFrom<NewPkg> for GentooPkg {
+    type Error = Error;
+
+    fn try_from(pkg: NewPkg) -> Result<GentooPkg> {
+        let cpv = pkg.cpv.ok_or_else(|| anyhow!("Missing CPV field"))?;
+        let (name, version) = split_cpv(&cpv)?;
+        Ok(GentooPkg {
+            name: name.to_string(),
+            version: version.to_string(),
+            build_id: pkg.build_id.unwrap_or_default(),
+            // older binhosts used a fixed layout of xpak packages instead of recording the path
+            path: pkg.path.ok_or_else(|| {
+                anyhow!(
+                    "Missing PATH field for {:?}, only gpkg binhosts are supported",
+                    cpv
+                )
+            })?,
+            repo: pkg.repo,
+        })
+    }
+}
+
+pub fn extract_pkgs(bytes: &[u8]) -> Result<Vec<GentooPkg>> {
+    let comp = decompress::detect_compression(bytes);
+    let mut reader = decompress::stream(comp, bytes)?;
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .context("Failed to read package index")?;
+    extract_pkgs_uncompressed(&content)
+}
+
+/// The index starts with a header block about the binhost, followed by one block per binary package
+pub fn extract_pkgs_uncompressed(content: &str) -> Result<Vec<GentooPkg>> {
+    let mut blocks = content.split("\n\n");
+    blocks.next().context("Package index is empty")?;
+
+    let mut pkgs = Vec::new();
+    for block in blocks {
+        let mut pkg = NewPkg::default();
+        for line in block.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                bail!("Malformed line in package index: {:?}", line);
+            };
+            let value = value.trim().to_string();
+
+            match key {
+                "CPV" => pkg.cpv = Some(value),
+                "BUILD_ID" => {
+                    pkg.build_id = Some(
+                        value
+                            .parse()
+                            .with_context(|| anyhow!("Invalid BUILD_ID: {:?}", value))?,
+                    )
+                }
+                "PATH" => pkg.path = Some(value),
+                "REPO" => pkg.repo = Some(value),
+                _ => (),
+            }
+        }
+
+        if !pkg.is_empty() {
+            pkgs.push(pkg.try_into()?);
+        }
+    }
+
+    Ok(pkgs)
+}
+
+/// Only the most recent build of each version is rebuilt, earlier builds are usually superseded by a rebuild with
+/// different USE flags or dependencies
+fn latest_builds(pkgs: Vec<GentooPkg>) -> Vec<GentooPkg> {
+    let mut latest = HashMap::<(String, String), GentooPkg>::new();
+    for pkg in pkgs {
+        let key = (pkg.name.clone(), pkg.version.clone());
+        match latest.get(&key) {
+            Some(existing) if existing.build_id >= pkg.build_id => (),
+            _ => {
+                latest.insert(key, pkg);
+            }
+        }
+    }
+    let mut pkgs = latest.into_values().collect::<Vec<_>>();
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+    pkgs
+}
+
+pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageReport>> {
+    let mut reports = Vec::new();
+    for arch in &sync.architectures {
+        for repo in &sync.components {
+            let index = mirror_to_url(&sync.source, arch, repo, "Packages")?;
+            let bytes = fetch_url_or_path(http, &index).await?;
+
+            let mut report = PackageReport {
+                distribution: "gentoo".to_string(),
+                release: None,
+                component: Some(repo.clone()),
+                architecture: arch.clone(),
+                source: None,
+                packages: Vec::new(),
+            };
+
+            info!("Parsing index ({} bytes)...", bytes.len());
+            for pkg in latest_builds(extract_pkgs(&bytes)?) {
+                if !pkg.matches(sync) {
+                    continue;
+                }
+                if pkg.repo.as_deref().is_some_and(|repo| repo != "gentoo") {
+                    debug!(
+                        "Skipping {:?}, it was built from the {:?} repository",
+                        pkg.name, pkg.repo
+                    );
+                    continue;
+                }
+
+                let url = mirror_to_url(&sync.source, arch, repo, &pkg.path)?;
+                // every gpkg is built from exactly one ebuild, there are no split packages
+                report.packages.push(SourcePackageReport {
+                    name: pkg.name.clone(),
+                    version: pkg.version.clone(),
+                    url: url.clone(), // the rebuilder script reads the build settings from the package
+                    payload: None,
+                    maintainer: None,
+                    artifacts: vec![BinaryPackageReport {
+                        name: pkg.name,
+                        version: pkg.version,
+                        architecture: arch.clone(),
+                        url,
+                        checksum: None,
+                    }],
+                });
+            }
+
+            reports.push(report);
+        }
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = "ARCH: amd64
+CBUILD: x86_64-pc-linux-gnu
+CHOST: x86_64-pc-linux-gnu
+PACKAGES: 3
+PROFILE: default/linux/amd64/23.0
+TIMESTAMP: 1718000000
+VERSION: 0
+
+BUILD_ID: 1
+BUILD_TIME: 1717000000
+CPV: app-arch/zstd-1.5.5-r1
+DEFINED_PHASES: compile configure install prepare test
+EAPI: 8
+KEYWORDS: amd64
+MD5: 00000000000000000000000000000000
+PATH: app-arch/zstd/zstd-1.5.5-r1-1.gpkg.tar
+REPO: gentoo
+SHA1: 0000000000000000000000000000000000000000
+SIZE: 614400
+USE: abi_x86_64 amd64 elibc_glibc kernel_linux lzma zlib
+
+BUILD_ID: 2
+BUILD_TIME: 1717500000
+CPV: app-arch/zstd-1.5.5-r1
+PATH: app-arch/zstd/zstd-1.5.5-r1-2.gpkg.tar
+REPO: gentoo
+
+BUILD_ID: 1
+BUILD_TIME: 1717000000
+CPV: dev-lang/python-3.12.3-r1
+PATH: dev-lang/python/python-3.12.3-r1-1.gpkg.tar
+REPO: gentoo
+";
+
+    #[test]
+    fn test_mirror_to_url() {
+        let url = mirror_to_url(
+            "https://distfiles.gentoo.org/releases/$arch/binpackages/$repo",
+            "amd64",
+            "23.0/x86-64",
+            "Packages",
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://distfiles.gentoo.org/releases/amd64/binpackages/23.0/x86-64/Packages"
+        );
+    }
+
+    #[test]
+    fn test_split_cpv() {
+        assert_eq!(
+            split_cpv("app-arch/zstd-1.5.5-r1").unwrap(),
+            ("app-arch/zstd", "1.5.5-r1")
+        );
+        assert_eq!(
+            split_cpv("dev-libs/libxml2-compat-2.9.14").unwrap(),
+            ("dev-libs/libxml2-compat", "2.9.14")
+        );
+        assert_eq!(
+            split_cpv("media-libs/x264-0.0.20240513").unwrap(),
+            ("media-libs/x264", "0.0.20240513")
+        );
+        assert!(split_cpv("app-arch/zstd").is_err());
+        assert!(split_cpv("zstd-1.5.5").is_err());
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let pkgs = extract_pkgs_uncompressed(INDEX).unwrap();
+        assert_eq!(pkgs.len(), 3);
+        assert_eq!(
+            pkgs[0],
+            GentooPkg {
+                name: "app-arch/zstd".to_string(),
+                version: "1.5.5-r1".to_string(),
+                build_id: 1,
+                path: "app-arch/zstd/zstd-1.5.5-r1-1.gpkg.tar".to_string(),
+                repo: Some("gentoo".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_latest_builds() {
+        let pkgs = latest_builds(extract_pkgs_uncompressed(INDEX).unwrap());
+        assert_eq!(
+            pkgs.iter().map(|pkg| pkg.path.as_str()).collect::<Vec<_>>(),
+            [
+                "app-arch/zstd/zstd-1.5.5-r1-2.gpkg.tar",
+                "dev-lang/python/python-3.12.3-r1-1.gpkg.tar",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_index_without_path() {
+        let index = "VERSION: 0
+
+CPV: app-arch/zstd-1.5.5-r1
+";
+        assert!(extract_pkgs_uncompressed(index).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::args::PkgsSync;
+use crate::schedule::{Pkg, fetch_url_or_path};
+use rebuilderd_common::api::v1::{BinaryPackageReport, PackageReport, SourcePackageReport};
+use rebuilderd_common::errors::*;
+use rebuilderd_common::http;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Bottles that work on every os version and architecture are published with this tag
+const BOTTLE_TAG_ALL: &str = "all";
+
+/// The subset of the formula metadata of formulae.brew.sh that is needed to locate bottles
+#[derive(Debug, Deserialize)]
+struct Formula {
+    name: String,
+    versions: Versions,
+    #[serde(default)]
+    revision: u32,
+    /// Keyed by `stable`, empty if the formula has no bottles
+    #[serde(default)]
+    bottle: HashMap<String, Bottle>,
+    #[serde(default)]
+    disabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Versions {
+    stable: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bottle {
+    /// Keyed by bottle tag, eg. `arm64_sonoma`
+    files: HashMap<String, BottleFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BottleFile {
+    url: String,
+    sha256: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct HomebrewPkg {
+    name: String,
+    version: String,
+    url: String,
+    sha256: String,
+}
+
+impl Pkg for HomebrewPkg {
+    fn pkg_name(&self) -> &str {
+        &self.name
+    }
+
+    // formulae don't have maintainers
+    fn by_maintainer(&self, _maintainers: &[String]) -> bool {
+        false
+    }
+}
+
+impl Formula {
+    /// The version bottles are published with, eg. `1.24.5_1` if the formula was revised
+    fn pkg_version(&self) -> Option<String> {
+        let version = self.versions.stable.as_ref()?;
+        if self.revision > 0 {
+            Some(format!("{version}_{}", self.revision))
+        } else {
+            Some(version.clone())
+        }
+    }
+
+    fn bottle(&self, tag: &str) -> Option<HomebrewPkg> {
+        let files = &self.bottle.get("stable")?.files;
+        let file = files.get(tag).or_else(|| files.get(BOTTLE_TAG_ALL))?;
+
+        Some(HomebrewPkg {
+            name: self.name.clone(),
+            version: self.pkg_version()?,
+            url: file.url.clone(),
+            sha256: file.sha256.clone(),
+        })
+    }
+}
+
+fn parse_formulae(bytes: &[u8]) -> Result<Vec<Formula>> {
+    serde_json::from_slice(bytes).context("Failed to parse formula metadata")
+}
+
+/// The bottles of all formulae that are available for a bottle tag, formulae that are disabled are skipped
+fn extract_pkgs(formulae: &[Formula], tag: &str) -> Vec<HomebrewPkg> {
+    let mut pkgs = formulae
+        .iter()
+        .filter(|formula| !formula.disabled)
+        .filter_map(|formula| formula.bottle(tag))
+        .collect::<Vec<_>>();
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name));
+    pkgs
+}
+
+pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageReport>> {
+    let bytes = fetch_url_or_path(http, &sync.source).await?;
+    info!("Parsing formulae ({} bytes)...", bytes.len());
+    let formulae = parse_formulae(&bytes)?;
+
+    let mut reports = Vec::new();
+    for tag in &sync.architectures {
+        let mut report = PackageReport {
+            distribution: "homebrew".to_string(),
+            release: None,
+            component: None,
+            architecture: tag.clone(),
+            source: None,
+            packages: Vec::new(),
+        };
+
+        for pkg in extract_pkgs(&formulae, tag) {
+            if !pkg.matches(sync) {
+                continue;
+            }
+
+            let artifact = BinaryPackageReport {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+                architecture: tag.clone(),
+                url: pkg.url.clone(),
+                checksum: Some(pkg.sha256),
+            };
+
+            report.packages.push(SourcePackageReport {
+                name: pkg.name,
+                version: pkg.version,
+                url: pkg.url, // the rebuilder script reads the formula and commit from the receipt in the bottle
+                payload: None,
+                maintainer: None,
+                artifacts: vec![artifact],
+            });
+        }
+
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMULAE: &str = r#"[
+  {
+    "name": "wget",
+    "full_name": "wget",
+    "tap": "homebrew/core",
+    "versions": {"stable": "1.24.5", "head": "HEAD", "bottle": true},
+    "revision": 0,
+    "bottle": {
+      "stable": {
+        "rebuild": 0,
+        "root_url": "https://ghcr.io/v2/homebrew/core",
+        "files": {
+          "arm64_sonoma": {
+            "cellar": "/opt/homebrew/Cellar",
+            "url": "https://ghcr.io/v2/homebrew/core/wget/blobs/sha256:4d180cd4ead91a34e2c2672189fc366b87ae86e6caa3acbf4845b272f57c859a",
+            "sha256": "4d180cd4ead91a34e2c2672189fc366b87ae86e6caa3acbf4845b272f57c859a"
+          },
+          "sonoma": {
+            "cellar": "/usr/local/Cellar",
+            "url": "https://ghcr.io/v2/homebrew/core/wget/blobs/sha256:b4c0a4a8b2e6bb3b5f6b1d8b7c7d1f2e3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d",
+            "sha256": "b4c0a4a8b2e6bb3b5f6b1d8b7c7d1f2e3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d"
+          }
+        }
+      }
+    },
+    "disabled": false
+  },
+  {
+    "name": "ca-certificates",
+    "versions": {"stable": "2025-05-20", "bottle": true},
+    "revision": 1,
+    "bottle": {
+      "stable": {
+        "rebuild": 0,
+        "files": {
+          "all": {
+            "cellar": ":any_skip_relocation",
+            "url": "https://ghcr.io/v2/homebrew/core/ca-certificates/blobs/sha256:dda1100e7f994081a593d6a5e422451bfa20037e29667ed2b79f011ffc9288a9",
+            "sha256": "dda1100e7f994081a593d6a5e422451bfa20037e29667ed2b79f011ffc9288a9"
+          }
+        }
+      }
+    },
+    "disabled": false
+  },
+  {
+    "name": "old-tool",
+    "versions": {"stable": "1.0", "bottle": true},
+    "revision": 0,
+    "bottle": {
+      "stable": {
+        "files": {
+          "all": {
+            "cellar": ":any",
+            "url": "https://ghcr.io/v2/homebrew/core/old-tool/blobs/sha256:00",
+            "sha256": "00"
+          }
+        }
+      }
+    },
+    "disabled": true
+  },
+  {
+    "name": "no-bottle",
+    "versions": {"stable": "2.0", "bottle": false},
+    "revision": 0,
+    "bottle": {},
+    "disabled": false
+  }
+]"#;
+
+    #[test]
+    fn test_parse_formulae() {
+        let formulae = parse_formulae(FORMULAE.as_bytes()).unwrap();
+        let pkgs = extract_pkgs(&formulae, "arm64_sonoma");
+        assert_eq!(
+            pkgs,
+            vec![
+                HomebrewPkg {
+                    name: "ca-certificates".to_string(),
+                    version: "2025-05-20_1".to_string(),
+                    url: "https://ghcr.io/v2/homebrew/core/ca-certificates/blobs/sha256:dda1100e7f994081a593d6a5e422451bfa20037e29667ed2b79f011ffc9288a9".to_string(),
+                    sha256: "dda1100e7f994081a593d6a5e422451bfa20037e29667ed2b79f011ffc9288a9".to_string(),
+                },
+                HomebrewPkg {
+                    name: "wget".to_string(),
+                    version: "1.24.5".to_string(),
+                    url: "https://ghcr.io/v2/homebrew/core/wget/blobs/sha256:4d180cd4ead91a34e2c2672189fc366b87ae86e6caa3acbf4845b272f57c859a".to_string(),
+                    sha256: "4d180cd4ead91a34e2c2672189fc366b87ae86e6caa3acbf4845b272f57c859a".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_formulae_missing_tag() {
+        let formulae = parse_formulae(FORMULAE.as_bytes()).unwrap();
+        let pkgs = extract_pkgs(&formulae, "arm64_sequoia");
+        let names = pkgs.iter().map(|pkg| pkg.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["ca-certificates"]);
+    }
+}
@@ -47,8 +47,14 @@ pub trait Pkg {
 
 pub mod archlinux;
 pub mod debian;
+pub mod durations;
 pub mod fedora;
+pub mod gentoo;
+pub mod homebrew;
+pub mod openwrt;
+pub mod popularity;
 pub mod tails;
+pub mod void;
 
 #[cfg(test)]
 mod tests {
@@ -78,6 +84,9 @@ mod tests {
             maintainers: f.maintainers,
             pkgs: to_patterns(f.pkgs),
             excludes: to_patterns(f.excludes),
+            delta_state: None,
+            popularity: None,
+            packaging_repo: None,
         }
     }
 
@@ -89,6 +98,7 @@ mod tests {
             version: "0.2.1-1".to_string(),
             architecture: "x86_64".to_string(),
             packager: "kpcyrd <kpcyrd@archlinux.org>".to_string(),
+            sha256: None,
         }
     }
 
@@ -0,0 +1,296 @@
+use crate::args::PkgsSync;
+use crate::decompress;
+use crate::schedule::{Pkg, fetch_url_or_path};
+use nom::bytes::complete::take_till;
+use rebuilderd_common::api::v1::{BinaryPackageReport, PackageReport, SourcePackageReport};
+use rebuilderd_common::errors::*;
+use rebuilderd_common::http;
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+fn mirror_to_url(
+    mut mirror: &str,
+    release: &str,
+    arch: &str,
+    feed: &str,
+    file: &str,
+) -> Result<String> {
+    let mut url = String::new();
+
+    loop {
+        let (s, txt) = take_till::<_, _, ()>(|c| c == '$')(mirror).unwrap();
+        url.push_str(txt);
+        if s.is_empty() {
+            break;
+        }
+        let (s, var) = take_till::<_, _, ()>(|c| c == '/')(s).unwrap();
+        match var {
+            "$release" => url.push_str(release),
+            "$arch" => url.push_str(arch),
+            "$feed" => url.push_str(feed),
+            _ => bail!("Unrecognized variable: {:?}", var),
+        }
+        mirror = s;
+    }
+
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+    url.push_str(file);
+
+    Ok(url)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OpenwrtPkg {
+    pub name: String,
+    pub source_name: String,
+    pub version: String,
+    pub architecture: String,
+    pub filename: String,
+    pub maintainer: Option<String>,
+}
+
+impl Pkg for OpenwrtPkg {
+    fn pkg_name(&self) -> &str {
+        &self.name
+    }
+
+    fn by_maintainer(&self, maintainers: &[String]) -> bool {
+        let Some(maintainer) = &self.maintainer else {
+            return false;
+        };
+        maintainers.iter().any(|m| maintainer.starts_with(m))
+    }
+}
+
+#[derive(Debug, Default)]
+struct NewPkg {
+    name: Option<String>,
+    source_name: Option<String>,
+    version: Option<String>,
+    architecture: Option<String>,
+    filename: Option<String>,
+    maintainer: Option<String>,
+}
+
+impl NewPkg {
+    fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.source_name.is_none()
+            && self.version.is_none()
+            && self.architecture.is_none()
+            && self.filename.is_none()
+            && self.maintainer.is_none()
+    }
+}
+
+impl TryFrom<NewPkg> for OpenwrtPkg {
+    type Error = Error;
+
+    fn try_from(pkg: NewPkg) -> Result<OpenwrtPkg> {
+        let name = pkg.name.ok_or_else(|| anyhow!("Missing package field"))?;
+        Ok(OpenwrtPkg {
+            // older feeds don't set SourceName, the binary package name is the best guess then
+            source_name: pkg.source_name.unwrap_or_else(|| name.clone()),
+            name,
+            version: pkg
+                .version
+                .ok_or_else(|| anyhow!("Missing version field"))?,
+            architecture: pkg
+                .architecture
+                .ok_or_else(|| anyhow!("Missing architecture field"))?,
+            filename: pkg
+                .filename
+                .ok_or_else(|| anyhow!("Missing filename field"))?,
+            maintainer: pkg.maintainer,
+        })
+    }
+}
+
+pub fn extract_pkgs(bytes: &[u8]) -> Result<Vec<OpenwrtPkg>> {
+    let comp = decompress::detect_compression(bytes);
+    let mut reader = decompress::stream(comp, bytes)?;
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .context("Failed to read package index")?;
+    extract_pkgs_uncompressed(&content)
+}
+
+pub fn extract_pkgs_uncompressed(content: &str) -> Result<Vec<OpenwrtPkg>> {
+    let mut pkgs = Vec::new();
+    let mut pkg = NewPkg::default();
+
+    for line in content.lines() {
+        if line.is_empty() {
+            if !pkg.is_empty() {
+                pkgs.push(std::mem::take(&mut pkg).try_into()?);
+            }
+            continue;
+        }
+
+        // continuation lines only ever show up in the description
+        if line.starts_with([' ', '\t']) {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            bail!("Malformed line in package index: {:?}", line);
+        };
+        let value = value.trim().to_string();
+
+        match key {
+            "Package" => pkg.name = Some(value),
+            "SourceName" => pkg.source_name = Some(value),
+            "Version" => pkg.version = Some(value),
+            "Architecture" => pkg.architecture = Some(value),
+            "Filename" => pkg.filename = Some(value),
+            "Maintainer" => pkg.maintainer = Some(value),
+            _ => (),
+        }
+    }
+
+    if !pkg.is_empty() {
+        pkgs.push(pkg.try_into()?);
+    }
+
+    Ok(pkgs)
+}
+
+pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageReport>> {
+    let mut reports = Vec::new();
+    for release in &sync.releases {
+        for arch in &sync.architectures {
+            for feed in &sync.components {
+                let index = mirror_to_url(&sync.source, release, arch, feed, "Packages.gz")?;
+                let bytes = fetch_url_or_path(http, &index).await?;
+
+                let mut report = PackageReport {
+                    distribution: "openwrt".to_string(),
+                    release: Some(release.clone()),
+                    component: Some(feed.clone()),
+                    architecture: arch.clone(),
+                    source: None,
+                    packages: Vec::new(),
+                };
+
+                let mut sources: HashMap<_, SourcePackageReport> = HashMap::new();
+
+                info!("Parsing index ({} bytes)...", bytes.len());
+                for pkg in extract_pkgs(&bytes)? {
+                    if !pkg.matches(sync) {
+                        continue;
+                    }
+
+                    let url = mirror_to_url(&sync.source, release, arch, feed, &pkg.filename)?;
+                    let artifact = BinaryPackageReport {
+                        name: pkg.name,
+                        version: pkg.version.clone(),
+                        architecture: pkg.architecture,
+                        url: url.clone(),
+                        checksum: None,
+                    };
+
+                    if let Some(group) = sources.get_mut(&pkg.source_name) {
+                        group.artifacts.push(artifact);
+                    } else {
+                        let group = SourcePackageReport {
+                            name: pkg.source_name.clone(),
+                            version: pkg.version,
+                            url, // the rebuilder script locates the sdk from the first artifact's url
+                            payload: None,
+                            maintainer: None,
+                            artifacts: vec![artifact],
+                        };
+                        sources.insert(pkg.source_name, group);
+                    }
+                }
+
+                report.packages = sources.into_values().collect();
+                reports.push(report);
+            }
+        }
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_to_url() {
+        let url = mirror_to_url(
+            "https://downloads.openwrt.org/releases/$release/packages/$arch/$feed",
+            "23.05.5",
+            "mips_24kc",
+            "base",
+            "Packages.gz",
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://downloads.openwrt.org/releases/23.05.5/packages/mips_24kc/base/Packages.gz"
+        );
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let index = "Package: dropbear
+Version: 2022.82-6
+Depends: libc
+SourceName: dropbear
+License: MIT
+Section: net
+SourceDateEpoch: 1719247920
+Maintainer: Someone <someone@example.com>
+Architecture: mips_24kc
+Installed-Size: 102400
+Filename: dropbear_2022.82-6_mips_24kc.ipk
+Size: 103024
+SHA256sum: 0000000000000000000000000000000000000000000000000000000000000000
+Description:  A small SSH2 server/client designed for small memory
+ environments.
+
+Package: libuci20130104
+Version: 2023-08-10-5781664d-1
+SourceName: uci
+Architecture: mips_24kc
+Filename: libuci20130104_2023-08-10-5781664d-1_mips_24kc.ipk
+Description:  C library for the Unified Configuration Interface (UCI)
+";
+        let pkgs = extract_pkgs_uncompressed(index).unwrap();
+        assert_eq!(
+            pkgs,
+            vec![
+                OpenwrtPkg {
+                    name: "dropbear".to_string(),
+                    source_name: "dropbear".to_string(),
+                    version: "2022.82-6".to_string(),
+                    architecture: "mips_24kc".to_string(),
+                    filename: "dropbear_2022.82-6_mips_24kc.ipk".to_string(),
+                    maintainer: Some("Someone <someone@example.com>".to_string()),
+                },
+                OpenwrtPkg {
+                    name: "libuci20130104".to_string(),
+                    source_name: "uci".to_string(),
+                    version: "2023-08-10-5781664d-1".to_string(),
+                    architecture: "mips_24kc".to_string(),
+                    filename: "libuci20130104_2023-08-10-5781664d-1_mips_24kc.ipk".to_string(),
+                    maintainer: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_index_missing_version() {
+        let index = "Package: dropbear
+Architecture: mips_24kc
+Filename: dropbear_2022.82-6_mips_24kc.ipk
+";
+        assert!(extract_pkgs_uncompressed(index).is_err());
+    }
+}
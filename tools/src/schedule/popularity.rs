@@ -0,0 +1,90 @@
+use rebuilderd_common::errors::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PkgstatsPage {
+    package_popularities: Vec<PkgstatsPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PkgstatsPackage {
+    name: String,
+    count: i64,
+}
+
+/// Parse the `by_inst` ranking of popcon, the score is the number of installations
+pub fn parse_popcon(text: &str) -> BTreeMap<String, i64> {
+    let mut packages = BTreeMap::new();
+    for line in text.lines() {
+        // the ranking is followed by a separator and the totals
+        if line.starts_with('-') {
+            break;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace().skip(1);
+        let (Some(name), Some(inst)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if let Ok(inst) = inst.parse() {
+            packages.insert(name.to_string(), inst);
+        }
+    }
+    packages
+}
+
+/// Parse a page of the pkgstats package api, eg. `https://pkgstats.archlinux.de/api/packages?limit=10000`
+pub fn parse_pkgstats(bytes: &[u8]) -> Result<BTreeMap<String, i64>> {
+    let page = serde_json::from_slice::<PkgstatsPage>(bytes)
+        .context("Failed to parse pkgstats response")?;
+    let packages = page
+        .package_popularities
+        .into_iter()
+        .map(|pkg| (pkg.name, pkg.count))
+        .collect();
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_popcon() {
+        let text = "#Format
+#
+#<name> is the package name;
+#rank name                            inst  vote   old recent no-files (maintainer)
+1     libacl1                         249315 232914  2190 14186    25 (Guillem Jover)
+2     debianutils                     249313 235137  1939 12212    25 (Helmut Grohne)
+3     not-a-number                    n/a     0     0     0     0 (Nobody)
+------------------------------------------------------------------------------------
+49861 Total                           9823125 6301624 1210484 1082391 1228626
+";
+        let packages = parse_popcon(text);
+        assert_eq!(
+            packages,
+            BTreeMap::from([
+                ("debianutils".to_string(), 249313),
+                ("libacl1".to_string(), 249315),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_pkgstats() {
+        let json = br#"{"total":2,"count":2,"limit":2,"offset":0,"query":null,"packagePopularities":[
+            {"name":"pacman","samples":20212,"count":20204,"popularity":99.96,"startMonth":202501,"endMonth":202501},
+            {"name":"bash","samples":20212,"count":20198,"popularity":99.93,"startMonth":202501,"endMonth":202501}
+        ]}"#;
+        let packages = parse_pkgstats(json).unwrap();
+        assert_eq!(
+            packages,
+            BTreeMap::from([("bash".to_string(), 20198), ("pacman".to_string(), 20204)])
+        );
+    }
+}
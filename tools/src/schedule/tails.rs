@@ -34,6 +34,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                 release: Some(release.clone()),
                 component: None,
                 architecture: architecture.clone(),
+                source: None,
                 packages: Vec::new(),
             };
 
@@ -72,6 +73,7 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                     version: version.to_string(),
                     architecture: architecture.clone(),
                     url: url.clone(),
+                    checksum: None,
                 };
 
                 if let Some(ref mut group) = group {
@@ -81,6 +83,8 @@ pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageRep
                         name: "tails".to_string(),
                         version: version.to_string(),
                         url: url.clone(), // use first artifact's url as the source URL for now
+                        payload: None,
+                        maintainer: None,
                         artifacts: vec![artifact],
                     };
 
@@ -0,0 +1,451 @@
+use crate::args::PkgsSync;
+use crate::decompress;
+use crate::schedule::{Pkg, fetch_url_or_path};
+use nom::bytes::complete::take_till;
+use rebuilderd_common::api::v1::{BinaryPackageReport, PackageReport, SourcePackageReport};
+use rebuilderd_common::errors::*;
+use rebuilderd_common::http;
+use std::collections::HashMap;
+use std::io::prelude::*;
+use tar::{Archive, EntryType};
+use xml::reader::{EventReader, XmlEvent};
+
+fn mirror_to_url(mut mirror: &str, repo: &str, file: &str) -> Result<String> {
+    let mut url = String::new();
+
+    loop {
+        let (s, txt) = take_till::<_, _, ()>(|c| c == '$')(mirror).unwrap();
+        url.push_str(txt);
+        if s.is_empty() {
+            break;
+        }
+        let (s, var) = take_till::<_, _, ()>(|c| c == '/')(s).unwrap();
+        match var {
+            "$repo" => url.push_str(repo),
+            _ => bail!("Unrecognized variable: {:?}", var),
+        }
+        mirror = s;
+    }
+
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+    url.push_str(file);
+
+    Ok(url)
+}
+
+/// The subset of property lists that is used by xbps repodata
+#[derive(Debug, PartialEq)]
+enum Plist {
+    Dict(HashMap<String, Plist>),
+    Array(Vec<Plist>),
+    String(String),
+    /// Integers, booleans, dates and data aren't needed to locate packages
+    Other,
+}
+
+impl Plist {
+    fn get(&self, key: &str) -> Option<&Plist> {
+        match self {
+            Plist::Dict(dict) => dict.get(key),
+            _ => None,
+        }
+    }
+
+    fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Plist::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+enum Element {
+    Start(String),
+    End,
+}
+
+/// Skip to the next opening or closing tag
+fn next_element<I>(events: &mut I) -> Result<Element>
+where
+    I: Iterator<Item = xml::reader::Result<XmlEvent>>,
+{
+    for event in events {
+        match event? {
+            XmlEvent::StartElement { name, .. } => return Ok(Element::Start(name.local_name)),
+            XmlEvent::EndElement { .. } => return Ok(Element::End),
+            XmlEvent::Characters(text) => bail!("Unexpected text in property list: {:?}", text),
+            _ => (),
+        }
+    }
+    bail!("Unexpected end of property list")
+}
+
+/// Read the text of a scalar element, up to its closing tag
+fn read_text<I>(events: &mut I) -> Result<String>
+where
+    I: Iterator<Item = xml::reader::Result<XmlEvent>>,
+{
+    let mut text = String::new();
+    for event in events {
+        match event? {
+            XmlEvent::Characters(s) | XmlEvent::CData(s) | XmlEvent::Whitespace(s) => {
+                text.push_str(&s)
+            }
+            XmlEvent::EndElement { .. } => return Ok(text),
+            XmlEvent::StartElement { name, .. } => {
+                bail!("Unexpected element in scalar value: {:?}", name.local_name)
+            }
+            _ => (),
+        }
+    }
+    bail!("Unexpected end of property list")
+}
+
+fn parse_value<I>(events: &mut I, tag: &str) -> Result<Plist>
+where
+    I: Iterator<Item = xml::reader::Result<XmlEvent>>,
+{
+    match tag {
+        "dict" => {
+            let mut dict = HashMap::new();
+            loop {
+                match next_element(events)? {
+                    Element::Start(tag) if tag == "key" => {
+                        let key = read_text(events)?;
+                        let Element::Start(tag) = next_element(events)? else {
+                            bail!("Missing value for key in property list: {:?}", key);
+                        };
+                        let value = parse_value(events, &tag)?;
+                        dict.insert(key, value);
+                    }
+                    Element::Start(tag) => bail!("Expected key in dict, found: {:?}", tag),
+                    Element::End => return Ok(Plist::Dict(dict)),
+                }
+            }
+        }
+        "array" => {
+            let mut array = Vec::new();
+            loop {
+                match next_element(events)? {
+                    Element::Start(tag) => array.push(parse_value(events, &tag)?),
+                    Element::End => return Ok(Plist::Array(array)),
+                }
+            }
+        }
+        "string" => Ok(Plist::String(read_text(events)?)),
+        _ => {
+            read_text(events)?;
+            Ok(Plist::Other)
+        }
+    }
+}
+
+fn parse_plist(bytes: &[u8]) -> Result<Plist> {
+    let mut events = EventReader::new(bytes).into_iter();
+
+    match next_element(&mut events)? {
+        Element::Start(tag) if tag == "plist" => (),
+        _ => bail!("Property list is missing the plist element"),
+    }
+    let Element::Start(tag) = next_element(&mut events)? else {
+        bail!("Property list is empty");
+    };
+    parse_value(&mut events, &tag)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VoidPkg {
+    pub name: String,
+    /// The name of the srcpkgs template the package is built from
+    pub template: String,
+    pub version: String,
+    pub architecture: String,
+    pub filename: String,
+    pub maintainer: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl Pkg for VoidPkg {
+    fn pkg_name(&self) -> &str {
+        &self.name
+    }
+
+    fn by_maintainer(&self, maintainers: &[String]) -> bool {
+        let Some(maintainer) = &self.maintainer else {
+            return false;
+        };
+        maintainers.iter().any(|m| maintainer.starts_with(m))
+    }
+}
+
+impl VoidPkg {
+    fn from_plist(name: &str, props: &Plist) -> Result<VoidPkg> {
+        // eg. bash-5.2.21_1, the name can contain dashes too
+        let pkgver = props
+            .get_str("pkgver")
+            .ok_or_else(|| anyhow!("Missing pkgver field for {:?}", name))?;
+        let version = pkgver
+            .strip_prefix(name)
+            .and_then(|v| v.strip_prefix('-'))
+            .ok_or_else(|| anyhow!("Invalid pkgver for {:?}: {:?}", name, pkgver))?;
+        let architecture = props
+            .get_str("architecture")
+            .ok_or_else(|| anyhow!("Missing architecture field for {:?}", name))?;
+
+        // eg. bash:0123abcd, subpackages carry the name of their parent template. Repos that were built without
+        // XBPS_USE_GIT_REVS don't have this field
+        let template = props
+            .get_str("source-revisions")
+            .and_then(|rev| rev.split_once(':'))
+            .map(|(template, _commit)| template)
+            .unwrap_or(name);
+
+        Ok(VoidPkg {
+            name: name.to_string(),
+            template: template.to_string(),
+            version: version.to_string(),
+            architecture: architecture.to_string(),
+            filename: format!("{pkgver}.{architecture}.xbps"),
+            maintainer: props.get_str("maintainer").map(String::from),
+            sha256: props.get_str("filename-sha256").map(String::from),
+        })
+    }
+}
+
+pub fn extract_pkgs_from_plist(bytes: &[u8]) -> Result<Vec<VoidPkg>> {
+    let Plist::Dict(index) = parse_plist(bytes)? else {
+        bail!("Repository index is not a dictionary");
+    };
+
+    let mut pkgs = index
+        .iter()
+        .map(|(name, props)| VoidPkg::from_plist(name, props))
+        .collect::<Result<Vec<_>>>()?;
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(pkgs)
+}
+
+/// The repodata is a compressed tar archive, the packages are listed in its `index.plist`
+pub fn extract_pkgs(bytes: &[u8]) -> Result<Vec<VoidPkg>> {
+    let comp = decompress::detect_compression(bytes);
+    let tar = decompress::stream(comp, bytes)?;
+    let mut archive = Archive::new(tar);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() == EntryType::Regular
+            && entry.path()?.to_str() == Some("index.plist")
+        {
+            let mut buf = Vec::new();
+            entry.read_to_end(&mut buf)?;
+            return extract_pkgs_from_plist(&buf);
+        }
+    }
+
+    bail!("Repository data is missing index.plist")
+}
+
+pub async fn sync(http: &http::Client, sync: &PkgsSync) -> Result<Vec<PackageReport>> {
+    let mut reports = Vec::new();
+    for arch in &sync.architectures {
+        for repo in &sync.components {
+            let index = mirror_to_url(&sync.source, repo, &format!("{arch}-repodata"))?;
+            let bytes = fetch_url_or_path(http, &index).await?;
+
+            let mut report = PackageReport {
+                distribution: "void".to_string(),
+                release: None,
+                component: Some(repo.clone()),
+                architecture: arch.clone(),
+                source: None,
+                packages: Vec::new(),
+            };
+
+            let mut templates: HashMap<_, SourcePackageReport> = HashMap::new();
+
+            info!("Parsing index ({} bytes)...", bytes.len());
+            for pkg in extract_pkgs(&bytes)? {
+                if !pkg.matches(sync) {
+                    continue;
+                }
+
+                let url = mirror_to_url(&sync.source, repo, &pkg.filename)?;
+                let artifact = BinaryPackageReport {
+                    name: pkg.name,
+                    version: pkg.version.clone(),
+                    architecture: pkg.architecture,
+                    url: url.clone(),
+                    checksum: pkg.sha256,
+                };
+
+                if let Some(group) = templates.get_mut(&pkg.template) {
+                    group.artifacts.push(artifact);
+                } else {
+                    let group = SourcePackageReport {
+                        name: pkg.template.clone(),
+                        version: pkg.version,
+                        url, // the rebuilder script reads the template and commit from the package
+                        payload: None,
+                        maintainer: None,
+                        artifacts: vec![artifact],
+                    };
+                    templates.insert(pkg.template, group);
+                }
+            }
+
+            report.packages = templates.into_values().collect();
+            reports.push(report);
+        }
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>bash</key>
+	<dict>
+		<key>architecture</key>
+		<string>x86_64</string>
+		<key>build-date</key>
+		<string>2024-01-08 20:27 UTC</string>
+		<key>filename-sha256</key>
+		<string>1f0a3ab9eb8e6ee5c8dcfc4da1fcd4b3e5b1cf7a0e20b62c2b7b4a5f4f9b9a3c</string>
+		<key>filename-size</key>
+		<integer>1150560</integer>
+		<key>installed_size</key>
+		<integer>6011904</integer>
+		<key>maintainer</key>
+		<string>Enno Boland &lt;gottox@voidlinux.org&gt;</string>
+		<key>pkgver</key>
+		<string>bash-5.2.21_1</string>
+		<key>preserve</key>
+		<true/>
+		<key>provides</key>
+		<array>
+			<string>sh-0_1</string>
+		</array>
+		<key>run_depends</key>
+		<array>
+			<string>glibc&gt;=2.36_1</string>
+			<string>readline&gt;=8.0_1</string>
+		</array>
+		<key>source-revisions</key>
+		<string>bash:95e5c3b5a2</string>
+	</dict>
+	<key>xbps-triggers</key>
+	<dict>
+		<key>architecture</key>
+		<string>noarch</string>
+		<key>pkgver</key>
+		<string>xbps-triggers-0.128_1</string>
+		<key>source-revisions</key>
+		<string>xbps-triggers:ab12cd34ef</string>
+	</dict>
+	<key>libgcc-devel</key>
+	<dict>
+		<key>architecture</key>
+		<string>x86_64</string>
+		<key>pkgver</key>
+		<string>libgcc-devel-13.2.0_1</string>
+		<key>source-revisions</key>
+		<string>gcc:0011223344</string>
+	</dict>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn test_mirror_to_url() {
+        let url = mirror_to_url(
+            "https://repo-default.voidlinux.org/$repo",
+            "current/nonfree",
+            "x86_64-repodata",
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://repo-default.voidlinux.org/current/nonfree/x86_64-repodata"
+        );
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let pkgs = extract_pkgs_from_plist(INDEX.as_bytes()).unwrap();
+        assert_eq!(
+            pkgs,
+            vec![
+                VoidPkg {
+                    name: "bash".to_string(),
+                    template: "bash".to_string(),
+                    version: "5.2.21_1".to_string(),
+                    architecture: "x86_64".to_string(),
+                    filename: "bash-5.2.21_1.x86_64.xbps".to_string(),
+                    maintainer: Some("Enno Boland <gottox@voidlinux.org>".to_string()),
+                    sha256: Some(
+                        "1f0a3ab9eb8e6ee5c8dcfc4da1fcd4b3e5b1cf7a0e20b62c2b7b4a5f4f9b9a3c"
+                            .to_string()
+                    ),
+                },
+                VoidPkg {
+                    name: "libgcc-devel".to_string(),
+                    template: "gcc".to_string(),
+                    version: "13.2.0_1".to_string(),
+                    architecture: "x86_64".to_string(),
+                    filename: "libgcc-devel-13.2.0_1.x86_64.xbps".to_string(),
+                    maintainer: None,
+                    sha256: None,
+                },
+                VoidPkg {
+                    name: "xbps-triggers".to_string(),
+                    template: "xbps-triggers".to_string(),
+                    version: "0.128_1".to_string(),
+                    architecture: "noarch".to_string(),
+                    filename: "xbps-triggers-0.128_1.noarch.xbps".to_string(),
+                    maintainer: None,
+                    sha256: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_index_invalid_pkgver() {
+        let index = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+	<key>bash</key>
+	<dict>
+		<key>architecture</key>
+		<string>x86_64</string>
+		<key>pkgver</key>
+		<string>zsh-5.9_1</string>
+	</dict>
+</dict>
+</plist>
+"#;
+        assert!(extract_pkgs_from_plist(index.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_plist_nested() {
+        let plist = parse_plist(
+            br#"<plist version="1.0"><dict><key>a</key><array><dict><key>b</key><string>c</string></dict><false/></array></dict></plist>"#,
+        )
+        .unwrap();
+        let Some(Plist::Array(array)) = plist.get("a") else {
+            panic!("Expected array: {plist:?}");
+        };
+        assert_eq!(array.len(), 2);
+        assert_eq!(array[0].get_str("b"), Some("c"));
+        assert_eq!(array[1], Plist::Other);
+    }
+}
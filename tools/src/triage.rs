@@ -0,0 +1,416 @@
+use crate::args::Triage;
+use crate::pager;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use rebuilderd_common::api::Client;
+use rebuilderd_common::api::v1::{
+    ArtifactStatus, BinaryPackage, BuildRestApi, BuildStatus, OriginFilter, PackageAnnotation,
+    PackageRestApi, Page, Priority, QueueJobRequest, QueueRestApi, RebuildArtifact,
+};
+use rebuilderd_common::errors::*;
+use std::collections::HashMap;
+
+const HELP: &str = "j/k: move  r: requeue  a: annotate  t: tag  l: log  q: quit";
+
+/// The source package and artifacts of a build, fetched the first time one of its packages is selected
+struct Details {
+    source_name: String,
+    artifacts: Vec<RebuildArtifact>,
+}
+
+enum Prompt {
+    Note(String),
+    Tag(String),
+}
+
+impl Prompt {
+    fn input(&mut self) -> &mut String {
+        match self {
+            Prompt::Note(input) | Prompt::Tag(input) => input,
+        }
+    }
+}
+
+struct App {
+    packages: Vec<BinaryPackage>,
+    list: ListState,
+    details: HashMap<i32, Details>,
+    prompt: Option<Prompt>,
+    message: String,
+}
+
+impl App {
+    fn new(packages: Vec<BinaryPackage>) -> App {
+        let mut list = ListState::default();
+        if !packages.is_empty() {
+            list.select(Some(0));
+        }
+        App {
+            packages,
+            list,
+            details: HashMap::new(),
+            prompt: None,
+            message: HELP.to_string(),
+        }
+    }
+
+    fn selected(&self) -> Option<&BinaryPackage> {
+        self.list.selected().and_then(|idx| self.packages.get(idx))
+    }
+
+    fn move_by(&mut self, offset: isize) {
+        if let Some(idx) = self.list.selected() {
+            let last = self.packages.len().saturating_sub(1);
+            let idx = idx.saturating_add_signed(offset).min(last);
+            self.list.select(Some(idx));
+        }
+    }
+
+    async fn load_details(&mut self, client: &Client) -> Result<()> {
+        let Some(build_id) = self.selected().and_then(|pkg| pkg.build_id) else {
+            return Ok(());
+        };
+        if self.details.contains_key(&build_id) {
+            return Ok(());
+        }
+
+        let build = client
+            .get_build(build_id)
+            .await
+            .context("Failed to fetch build")?;
+        let artifacts = client
+            .get_build_artifacts(build_id)
+            .await
+            .context("Failed to fetch build artifacts")?;
+        self.details.insert(
+            build_id,
+            Details {
+                source_name: build.name,
+                artifacts,
+            },
+        );
+        Ok(())
+    }
+
+    fn selected_details(&self) -> Option<(&BinaryPackage, &Details)> {
+        let pkg = self.selected()?;
+        let details = self.details.get(&pkg.build_id?)?;
+        Some((pkg, details))
+    }
+
+    async fn requeue(&mut self, client: &Client) -> Result<()> {
+        let Some((pkg, details)) = self.selected_details() else {
+            return Ok(());
+        };
+
+        let response = client
+            .request_rebuild(QueueJobRequest {
+                distribution: Some(pkg.distribution.clone()),
+                release: pkg.release.clone(),
+                component: pkg.component.clone(),
+                name: Some(details.source_name.clone()),
+                version: None,
+                architecture: Some(pkg.architecture.clone()),
+                status: Some(BuildStatus::Bad),
+                priority: Some(Priority::manual()),
+                built_with: None,
+                built_before: None,
+                binary_name: Some(pkg.name.clone()),
+                build_architecture: None,
+                reason: None,
+            })
+            .await?;
+
+        self.message = format!(
+            "Queued {} build inputs of {}, {} were already queued",
+            response.queued, details.source_name, response.skipped
+        );
+        if let Some(idx) = self.list.selected() {
+            self.packages[idx].rebuilding = true;
+        }
+        Ok(())
+    }
+
+    async fn submit(&mut self, client: &Client, prompt: Prompt) -> Result<()> {
+        let Some((pkg, details)) = self.selected_details() else {
+            return Ok(());
+        };
+        let distribution = pkg.distribution.clone();
+        let source_name = details.source_name.clone();
+        let build_id = pkg.build_id;
+
+        match prompt {
+            Prompt::Note(note) => {
+                let note = Some(note).filter(|note| !note.is_empty());
+                // the annotation is replaced as a whole, keep the bug url that's already recorded
+                client
+                    .annotate_package(
+                        &distribution,
+                        &source_name,
+                        &PackageAnnotation {
+                            bug_url: pkg.bug_url.clone(),
+                            note: note.clone(),
+                        },
+                    )
+                    .await?;
+                for pkg in &mut self.packages {
+                    if pkg.build_id == build_id {
+                        pkg.note.clone_from(&note);
+                    }
+                }
+                self.message = format!("Annotated {distribution}/{source_name}");
+            }
+            Prompt::Tag(tag) => {
+                if tag.is_empty() {
+                    return Ok(());
+                }
+                client
+                    .add_package_tag(&distribution, &source_name, &tag)
+                    .await?;
+                self.message = format!("Tagged {distribution}/{source_name} with {tag:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let items = self
+            .packages
+            .iter()
+            .map(|pkg| {
+                let mut line = format!("{} {}", pkg.name, pkg.version);
+                if pkg.rebuilding {
+                    line.push_str(" (rebuilding)");
+                }
+                ListItem::new(line)
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!("BAD packages ({})", self.packages.len())))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, left, &mut self.list);
+
+        let lines = match self.selected() {
+            Some(pkg) => {
+                let details = pkg.build_id.and_then(|id| self.details.get(&id));
+                package_lines(pkg, details)
+            }
+            None => vec![Line::from("No packages failed to reproduce")],
+        };
+        let detail = Paragraph::new(lines)
+            .block(Block::bordered().title("Details"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, right);
+
+        let status_line = match &self.prompt {
+            Some(Prompt::Note(input)) => format!("Note: {input}_"),
+            Some(Prompt::Tag(input)) => format!("Tag: {input}_"),
+            None => self.message.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+fn package_lines<'a>(pkg: &'a BinaryPackage, details: Option<&'a Details>) -> Vec<Line<'a>> {
+    let mut lines = vec![
+        Line::from(format!("{} {}", pkg.name, pkg.version).bold()),
+        Line::from(format!(
+            "{}, {}, {}, {}",
+            pkg.distribution,
+            pkg.release.as_deref().unwrap_or("<none>"),
+            pkg.component.as_deref().unwrap_or("<none>"),
+            pkg.architecture,
+        )),
+        Line::from(pkg.url.as_str()),
+    ];
+    if let Some(bug_url) = &pkg.bug_url {
+        lines.push(Line::from(format!("Bug: {bug_url}")));
+    }
+    if let Some(note) = &pkg.note {
+        lines.push(Line::from(format!("Note: {note}")));
+    }
+    lines.push(Line::default());
+
+    let Some(details) = details else {
+        return lines;
+    };
+    lines.push(Line::from(format!(
+        "Source package: {}",
+        details.source_name
+    )));
+
+    let artifact = details
+        .artifacts
+        .iter()
+        .find(|artifact| Some(artifact.id) == pkg.artifact_id);
+    match artifact.and_then(|artifact| artifact.diff_summary.as_ref()) {
+        Some(summary) => {
+            lines.push(Line::from(format!(
+                "Size: {} -> {} bytes ({:+})",
+                summary.original_size,
+                summary.rebuilt_size,
+                summary.size_delta()
+            )));
+            if let Some(offset) = summary.first_difference {
+                lines.push(Line::from(format!("First difference at byte {offset}")));
+            }
+            if summary.modulo_compression {
+                lines.push(Line::from(
+                    "Identical after decompression, only the compression differs",
+                ));
+            }
+            if !summary.differing_files.is_empty() {
+                lines.push(Line::from("Differing files:"));
+                for file in &summary.differing_files {
+                    lines.push(Line::from(format!("  {file}")));
+                }
+                if summary.truncated {
+                    lines.push(Line::from("  ..."));
+                }
+            }
+        }
+        None => lines.push(Line::from("No diff summary recorded")),
+    }
+    lines
+}
+
+async fn bad_packages(client: &Client, args: &Triage) -> Result<Vec<BinaryPackage>> {
+    let origin_filter = OriginFilter {
+        distribution: args.distro.clone(),
+        release: None,
+        component: args.suite.clone(),
+        architecture: args.architecture.clone(),
+    };
+
+    let mut page = Page {
+        limit: Some(1000),
+        before: None,
+        after: None,
+        sort: Some("name".to_string()),
+        direction: None,
+    };
+
+    let mut packages = Vec::new();
+    loop {
+        let results = client
+            .get_binary_packages(Some(&page), Some(&origin_filter), None)
+            .await
+            .context("Failed to fetch packages")?;
+
+        if let Some(last) = results.records.last() {
+            page.after = Some(last.id);
+        } else {
+            break;
+        }
+
+        packages.extend(
+            results
+                .records
+                .into_iter()
+                .filter(|pkg| pkg.status == Some(ArtifactStatus::Bad)),
+        );
+    }
+    Ok(packages)
+}
+
+/// Show the build log of the selected package in the pager, the terminal is handed over until the pager exits
+async fn show_log(terminal: &mut DefaultTerminal, app: &App, client: &Client) -> Result<()> {
+    let Some(build_id) = app.selected().and_then(|pkg| pkg.build_id) else {
+        return Ok(());
+    };
+    let log = client
+        .get_build_log(build_id)
+        .await
+        .context("Failed to fetch build log")?;
+
+    ratatui::restore();
+    let result = pager::write(log.as_bytes());
+    *terminal = ratatui::init();
+    result
+}
+
+async fn handle_key(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    client: &Client,
+    code: KeyCode,
+) -> Result<bool> {
+    if let Some(prompt) = &mut app.prompt {
+        match code {
+            KeyCode::Esc => app.prompt = None,
+            KeyCode::Enter => {
+                let prompt = app.prompt.take().unwrap();
+                app.submit(client, prompt).await?;
+            }
+            KeyCode::Backspace => {
+                prompt.input().pop();
+            }
+            KeyCode::Char(c) => prompt.input().push(c),
+            _ => (),
+        }
+        return Ok(false);
+    }
+
+    match code {
+        KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+        KeyCode::Char('j') | KeyCode::Down => app.move_by(1),
+        KeyCode::Char('k') | KeyCode::Up => app.move_by(-1),
+        KeyCode::PageDown => app.move_by(20),
+        KeyCode::PageUp => app.move_by(-20),
+        KeyCode::Char('r') => app.requeue(client).await?,
+        KeyCode::Char('a') => {
+            let note = app
+                .selected()
+                .and_then(|pkg| pkg.note.clone())
+                .unwrap_or_default();
+            app.prompt = Some(Prompt::Note(note));
+        }
+        KeyCode::Char('t') => app.prompt = Some(Prompt::Tag(String::new())),
+        KeyCode::Char('l') => show_log(terminal, app, client).await?,
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, client: &Client) -> Result<()> {
+    loop {
+        if let Err(err) = app.load_details(client).await {
+            app.message = format!("{err:#}");
+        }
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match handle_key(terminal, app, client, key.code).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => (),
+            // keep the session going, a failed request shouldn't discard the position in the list
+            Err(err) => app.message = format!("Error: {err:#}"),
+        }
+    }
+}
+
+pub async fn run(client: &Client, args: Triage) -> Result<()> {
+    let packages = bad_packages(client, &args).await?;
+    let mut app = App::new(packages);
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, client).await;
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,59 @@
+#!/bin/sh
+set -eux
+GPKG_PATH="$(realpath "$1")"
+GENTOO_REPO_URL="${GENTOO_REPO_URL:-https://github.com/gentoo-mirror/gentoo.git}"
+
+# setup temporary directory
+WORK_DIR=$(mktemp -d -t gentoo.XXXXXX)
+trap '{ rm -rf -- "$WORK_DIR"; }' EXIT
+cd "$WORK_DIR"
+
+# a gpkg is a plain tar, the settings of the build are recorded in its metadata archive
+mkdir gpkg
+tar -xf "$GPKG_PATH" -C gpkg
+METADATA=$(find gpkg -name 'metadata.tar*' ! -name '*.sig')
+tar -xf "$METADATA"
+meta() {
+    cat "metadata/$1" 2>/dev/null || true
+}
+CATEGORY=$(meta CATEGORY)
+PF=$(meta PF)
+BUILD_TIME=$(meta BUILD_TIME)
+
+if [ -z "$CATEGORY" ] || [ -z "$PF" ] || [ -z "$BUILD_TIME" ]; then
+    echo "Package metadata is incomplete, it's not a gpkg built by portage" >&2
+    exit 1
+fi
+
+echo "::rebuilderd-phase:: env-setup"
+# pin the ebuild repository to the last commit before the package was built
+git clone --filter=blob:none --no-checkout -- "$GENTOO_REPO_URL" gentoo
+COMMIT=$(git -C gentoo rev-list -n1 --first-parent --before="@$BUILD_TIME" HEAD)
+git -C gentoo checkout --detach "$COMMIT"
+
+export PORTAGE_REPOSITORIES="
+[DEFAULT]
+main-repo = gentoo
+
+[gentoo]
+location = $WORK_DIR/gentoo
+"
+export BINPKG_FORMAT=gpkg
+export FEATURES="-binpkg-signing"
+export SOURCE_DATE_EPOCH="$BUILD_TIME"
+CFLAGS=$(meta CFLAGS)
+CXXFLAGS=$(meta CXXFLAGS)
+LDFLAGS=$(meta LDFLAGS)
+CHOST=$(meta CHOST)
+USE=$(meta USE)
+export CFLAGS CXXFLAGS LDFLAGS CHOST USE
+
+# dependencies come from the binhost configured in binrepos.conf of this system
+emerge --oneshot --onlydeps --usepkg "=$CATEGORY/$PF"
+
+echo "::rebuilderd-phase:: build"
+PKGDIR="$WORK_DIR/binpkgs" emerge --oneshot --nodeps --buildpkgonly "=$CATEGORY/$PF"
+
+# the output needs the filename the package was downloaded with to be compared, the build id may differ
+find "$WORK_DIR/binpkgs" -name '*.gpkg.tar' -exec cp -v -- {} "$REBUILDERD_OUTDIR/$(basename "$GPKG_PATH")" \;
+ls -la "$REBUILDERD_OUTDIR"
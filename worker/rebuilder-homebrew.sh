@@ -0,0 +1,32 @@
+#!/bin/sh
+set -eux
+BOTTLE_PATH="$(realpath "$1")"
+HOMEBREW_CORE_URL="${HOMEBREW_CORE_URL:-https://github.com/Homebrew/homebrew-core.git}"
+
+# the receipt in the bottle records the formula and the homebrew-core commit it was built from
+RECEIPT=$(tar -tzf "$BOTTLE_PATH" | grep -m1 '^[^/]*/[^/]*/INSTALL_RECEIPT.json$')
+FORMULA="${RECEIPT%%/*}"
+COMMIT=$(tar -xzOf "$BOTTLE_PATH" "$RECEIPT" | plutil -extract source.tap_git_head raw -o - -)
+
+# setup temporary directory
+WORK_DIR=$(mktemp -d -t homebrew.XXXXXX)
+trap '{ brew uninstall --formula --ignore-dependencies "$FORMULA" || true; rm -rf -- "$WORK_DIR"; }' EXIT
+cd "$WORK_DIR"
+
+echo "::rebuilderd-phase:: env-setup"
+git clone --filter=blob:none --no-checkout -- "$HOMEBREW_CORE_URL" homebrew-core
+git -C homebrew-core checkout --detach "$COMMIT"
+FORMULA_PATH=$(git -C homebrew-core ls-files "Formula/*/$FORMULA.rb")
+
+# build from the formula of that commit instead of the metadata of the api
+export HOMEBREW_NO_INSTALL_FROM_API=1
+export HOMEBREW_NO_AUTO_UPDATE=1
+brew install --formula --only-dependencies "homebrew-core/$FORMULA_PATH"
+
+echo "::rebuilderd-phase:: build"
+brew install --formula --build-bottle "homebrew-core/$FORMULA_PATH"
+brew bottle --no-rebuild "$FORMULA"
+
+# the output needs the filename the bottle was downloaded with to be compared
+cp -v -- ./*.bottle*.tar.gz "$REBUILDERD_OUTDIR/$(basename "$BOTTLE_PATH")"
+ls -la "$REBUILDERD_OUTDIR"
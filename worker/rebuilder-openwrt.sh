@@ -0,0 +1,67 @@
+#!/bin/sh
+set -eux
+IPK_PATH="$(realpath "$1")"
+# https://downloads.openwrt.org/releases/<release>/packages/<arch>/<feed>/<file>.ipk
+RELEASE=$(echo "$REBUILDERD_INPUT_URL" | sed -nr 's|.*/releases/([^/]+)/packages/.*|\1|p')
+ARCH=$(echo "$REBUILDERD_INPUT_URL" | sed -nr 's|.*/packages/([^/]+)/[^/]+/[^/]+$|\1|p')
+MIRROR=$(echo "$REBUILDERD_INPUT_URL" | sed -nr 's|(.*)/releases/.*|\1|p')
+
+if [ -z "$RELEASE" ] || [ -z "$ARCH" ]; then
+    echo "Failed to detect OpenWrt release and architecture from: $REBUILDERD_INPUT_URL" >&2
+    exit 1
+fi
+
+# the sdk is published per target, pick one target for each package architecture.
+# this can be overridden with OPENWRT_TARGET if a worker needs a specific sdk.
+if [ -z "${OPENWRT_TARGET:-}" ]; then
+    case "$ARCH" in
+        mips_24kc) OPENWRT_TARGET=ath79/generic ;;
+        mipsel_24kc) OPENWRT_TARGET=ramips/mt7621 ;;
+        arm_cortex-a7_neon-vfpv4) OPENWRT_TARGET=ipq40xx/generic ;;
+        arm_cortex-a9_vfpv3-d16) OPENWRT_TARGET=mvebu/cortexa9 ;;
+        aarch64_cortex-a53) OPENWRT_TARGET=mediatek/filogic ;;
+        aarch64_generic) OPENWRT_TARGET=armsr/armv8 ;;
+        x86_64) OPENWRT_TARGET=x86/64 ;;
+        *)
+            echo "No OpenWrt target known for architecture: $ARCH" >&2
+            exit 1
+            ;;
+    esac
+fi
+
+# read the package metadata from the ipk
+CONTROL=$(tar -xzOf "$IPK_PATH" ./control.tar.gz | tar -xzOf - ./control)
+field() {
+    printf '%s\n' "$CONTROL" | sed -nr "s/^$1: (.*)/\1/p" | head -n1
+}
+# eg. feeds/base/package/network/services/dropbear
+PKG_DIR=$(basename "$(field Source)")
+SOURCE_DATE_EPOCH=$(field SourceDateEpoch)
+export SOURCE_DATE_EPOCH
+
+# setup temporary directory
+WORK_DIR=$(mktemp -d -t openwrt.XXXXXX)
+trap '{ rm -rf -- "$WORK_DIR"; }' EXIT
+cd "$WORK_DIR"
+
+# download and verify the sdk of this release
+echo "::rebuilderd-phase:: env-setup"
+TARGET_URL="${MIRROR}/releases/${RELEASE}/targets/${OPENWRT_TARGET}"
+curl -sSfO "${TARGET_URL}/sha256sums"
+SDK=$(grep -oE 'openwrt-sdk-[^ ]+\.tar\.(xz|zst)$' sha256sums | head -n1)
+curl -sSfO "${TARGET_URL}/${SDK}"
+grep -F "*${SDK}" sha256sums | sha256sum -c -
+mkdir sdk
+tar -xf "$SDK" -C sdk --strip-components=1
+cd sdk
+
+# the feeds are pinned to the commits of the release
+./scripts/feeds update -a
+./scripts/feeds install "$PKG_DIR"
+make defconfig
+echo "::rebuilderd-phase:: build"
+make "package/${PKG_DIR}/compile" V=s
+
+# collect build outputs
+find bin/packages -name '*.ipk' -exec cp -v -- {} "$REBUILDERD_OUTDIR" \;
+ls -la "$REBUILDERD_OUTDIR"
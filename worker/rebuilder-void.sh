@@ -0,0 +1,47 @@
+#!/bin/sh
+set -eux
+XBPS_PATH="$(realpath "$1")"
+VOID_PACKAGES_URL="${VOID_PACKAGES_URL:-https://github.com/void-linux/void-packages.git}"
+
+# read the package metadata from the xbps, it's a zstd compressed tar
+PROPS=$(tar --zstd -xOf "$XBPS_PATH" ./props.plist)
+prop() {
+    printf '%s\n' "$PROPS" | sed -n "/<key>$1<\/key>/{n;s|.*<string>\(.*\)</string>.*|\1|p;q}"
+}
+# eg. bash:95e5c3b5a2, subpackages point to the template they are built from
+SOURCE_REVISION=$(prop source-revisions)
+TEMPLATE="${SOURCE_REVISION%%:*}"
+COMMIT="${SOURCE_REVISION#*:}"
+ARCH=$(prop architecture)
+
+if [ -z "$SOURCE_REVISION" ] || [ "$TEMPLATE" = "$SOURCE_REVISION" ]; then
+    echo "Package has no source-revisions, it wasn't built with XBPS_USE_GIT_REVS" >&2
+    exit 1
+fi
+
+# setup temporary directory
+WORK_DIR=$(mktemp -d -t void.XXXXXX)
+trap '{ rm -rf -- "$WORK_DIR"; }' EXIT
+cd "$WORK_DIR"
+
+echo "::rebuilderd-phase:: env-setup"
+git clone --filter=blob:none --no-checkout -- "$VOID_PACKAGES_URL" void-packages
+cd void-packages
+git checkout --detach "$COMMIT"
+# the official builders record the commit in the package, do the same so props.plist matches
+echo XBPS_USE_GIT_REVS=yes >> etc/conf
+
+# noarch packages are built with the native masterdir
+if [ "$ARCH" = "noarch" ]; then
+    ./xbps-src binary-bootstrap
+    echo "::rebuilderd-phase:: build"
+    ./xbps-src pkg "$TEMPLATE"
+else
+    ./xbps-src -A "$ARCH" binary-bootstrap
+    echo "::rebuilderd-phase:: build"
+    ./xbps-src -A "$ARCH" pkg "$TEMPLATE"
+fi
+
+# collect build outputs, this includes all subpackages of the template
+find hostdir/binpkgs -name '*.xbps' -exec cp -v -- {} "$REBUILDERD_OUTDIR" \;
+ls -la "$REBUILDERD_OUTDIR"
@@ -27,14 +27,24 @@ pub enum SubCommand {
     Diffoscope(Diffoscope),
     /// Load and print a config
     CheckConfig,
+    /// Verify the configured backends and print the environment fingerprint
+    SelfTest,
 }
 
 #[derive(Debug, Parser)]
 pub struct Build {
-    /// Selects the right build profile from the configuration
-    pub distro: String,
+    /// Selects the right build profile from the configuration, may be omitted if only one backend is configured
+    pub distro: Option<String>,
     /// The pre-built artifact that should be reproduced
-    pub artifact_url: String,
+    #[arg(required_unless_present = "local")]
+    pub artifact_url: Option<String>,
+    /// Reproduce the artifact at this url without a daemon, print the verdict and a diff and exit non-zero unless it
+    /// reproduced
+    #[arg(long, value_name = "URL", conflicts_with = "artifact_url")]
+    pub local: Option<String>,
+    /// Verify the downloaded artifact against this sha256 checksum
+    #[arg(long)]
+    pub checksum: Option<String>,
     /// Pass a different input file to the rebuilder backend
     #[arg(long)]
     pub input_url: Option<String>,
@@ -49,6 +59,9 @@ pub struct Build {
 #[derive(Debug, Parser)]
 pub struct Connect {
     pub endpoint: Option<String>,
+    /// Connect even if the startup self-test fails
+    #[arg(long)]
+    pub skip_selftest: bool,
 }
 
 #[derive(Debug, Parser)]
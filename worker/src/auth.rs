@@ -1,14 +1,19 @@
 use data_encoding::BASE64;
 use in_toto::crypto::{KeyType, PrivateKey, SignatureScheme};
 use rebuilderd_common::api::Client;
+use rebuilderd_common::auth::WorkerIdentity;
 use rebuilderd_common::config::ConfigFile;
 use rebuilderd_common::errors::*;
-use rebuilderd_common::utils;
+use rebuilderd_common::http::TlsConfig;
 use std::fs;
 use std::io::ErrorKind;
 use std::path::Path;
 
+/// The key file of workers that predate identity files, relative to the working directory
+const LEGACY_KEY_FILE: &str = "rebuilder.v2.key";
+
 pub struct Profile {
+    pub uuid: String,
     pub pubkey: String,
     pub privkey: PrivateKey,
 }
@@ -18,10 +23,14 @@ impl Profile {
         &self,
         config: ConfigFile,
         endpoint: String,
+        tls: &TlsConfig,
         signup_secret: Option<String>,
         auth_cookie: Option<String>,
     ) -> Result<Client> {
         let mut client = Client::new(config, Some(endpoint))?;
+        client
+            .tls(tls)
+            .context("Failed to setup tls for rebuilderd endpoint")?;
         client.worker_key(self.pubkey.clone());
         if let Some(signup_secret) = signup_secret {
             client.signup_secret(signup_secret);
@@ -32,24 +41,56 @@ impl Profile {
     }
 }
 
-#[inline]
-pub fn load() -> Result<Profile> {
+pub fn load(path: &Path) -> Result<Profile> {
     match fs::remove_file("rebuilder.key") {
         Ok(_) => info!("Deleted old v1 worker key"),
         Err(err) if err.kind() == ErrorKind::NotFound => (),
         Err(err) => warn!("Failed to delete old v1 worker key: {:#}", err),
     }
 
-    load_key("rebuilder.v2.key")
+    let identity = if let Some(identity) = WorkerIdentity::load(path)? {
+        identity
+    } else {
+        create_identity(path)?
+    };
+
+    let privkey = PrivateKey::from_pkcs8(&identity.pkcs8()?, SignatureScheme::Ed25519)?;
+    let pubkey = BASE64.encode(privkey.public().as_bytes());
+
+    Ok(Profile {
+        uuid: identity.uuid,
+        pubkey,
+        privkey,
+    })
 }
 
-fn load_key<P: AsRef<Path>>(path: P) -> Result<Profile> {
-    let privkey = utils::load_or_create(path.as_ref(), || {
-        PrivateKey::new(KeyType::Ed25519).map_err(Error::from)
-    })?;
+/// Generate the identity on the first run. The key of a worker that predates identity files is moved into it, so
+/// the daemon keeps recognizing the worker.
+fn create_identity(path: &Path) -> Result<WorkerIdentity> {
+    let legacy_key = match fs::read(LEGACY_KEY_FILE) {
+        Ok(pkcs8) => Some(pkcs8),
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => {
+            return Err(err).with_context(|| anyhow!("Failed to read {LEGACY_KEY_FILE:?}"));
+        }
+    };
+
+    let pkcs8 = if let Some(pkcs8) = &legacy_key {
+        pkcs8.clone()
+    } else {
+        PrivateKey::new(KeyType::Ed25519)?
+    };
 
-    let privkey = PrivateKey::from_pkcs8(&privkey, SignatureScheme::Ed25519)?;
-    let pubkey = BASE64.encode(privkey.public().as_bytes());
+    let identity = WorkerIdentity::new(&pkcs8);
+    identity.save(path)?;
+    info!("Created worker identity {} at {path:?}", identity.uuid);
+
+    if legacy_key.is_some() {
+        info!("Moved worker key from {LEGACY_KEY_FILE:?} into the worker identity");
+        if let Err(err) = fs::remove_file(LEGACY_KEY_FILE) {
+            warn!("Failed to delete {LEGACY_KEY_FILE:?}: {err:#}");
+        }
+    }
 
-    Ok(Profile { pubkey, privkey })
+    Ok(identity)
 }
@@ -0,0 +1,138 @@
+//! The directory builds run in.
+//!
+//! Every job gets its own subdirectory with the downloaded inputs, the build output and a private `TMPDIR` for the
+//! rebuilder script, so nothing is shared between builds and everything is removed once the job is done. The
+//! directory can be backed by a tmpfs, which is mounted when the worker starts.
+use crate::config::Build;
+use nix::mount::{MsFlags, mount};
+use nix::sys::statfs::{TMPFS_MAGIC, statfs};
+use rebuilderd_common::errors::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A job directory and the subdirectories that are handed to the rebuilder script
+pub struct JobDir {
+    dir: TempDir,
+}
+
+impl JobDir {
+    pub fn create(build: &Build, job_id: Option<i32>) -> Result<JobDir> {
+        let prefix = match job_id {
+            Some(id) => format!("job-{id}-"),
+            None => "build-".to_string(),
+        };
+        let build_dir = build.build_dir();
+        let dir = tempfile::Builder::new()
+            .prefix(&prefix)
+            .tempdir_in(&build_dir)
+            .with_context(|| anyhow!("Failed to create job directory in {build_dir:?}"))?;
+
+        let job_dir = JobDir { dir };
+        for path in [job_dir.inputs(), job_dir.out(), job_dir.tmp()] {
+            fs::create_dir(&path).with_context(|| anyhow!("Failed to create {path:?}"))?;
+        }
+        Ok(job_dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn inputs(&self) -> PathBuf {
+        self.path().join("inputs")
+    }
+
+    pub fn out(&self) -> PathBuf {
+        self.path().join("out")
+    }
+
+    /// Scratch space of the rebuilder script, passed as `TMPDIR`
+    pub fn tmp(&self) -> PathBuf {
+        self.path().join("tmp")
+    }
+}
+
+fn is_tmpfs(path: &Path) -> Result<bool> {
+    let stat = statfs(path).with_context(|| anyhow!("Failed to stat filesystem of {path:?}"))?;
+    Ok(stat.filesystem_type() == TMPFS_MAGIC)
+}
+
+/// Create the build directory and mount the tmpfs, if one is configured
+pub fn prepare(build: &Build) -> Result<()> {
+    let build_dir = build.build_dir();
+    fs::create_dir_all(&build_dir)
+        .with_context(|| anyhow!("Failed to create build directory {build_dir:?}"))?;
+
+    let Some(size) = &build.tmpfs_size else {
+        return Ok(());
+    };
+    if build.build_dir.is_none() {
+        bail!(
+            "A tmpfs can only be mounted on a configured build_dir, not the system temp directory"
+        );
+    }
+
+    // the mount outlives the worker, a restarted worker keeps using it
+    if is_tmpfs(&build_dir)? {
+        info!("Build directory {build_dir:?} is already a tmpfs");
+        return Ok(());
+    }
+
+    info!("Mounting tmpfs with size {size:?} on {build_dir:?}");
+    mount(
+        Some("tmpfs"),
+        &build_dir,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(format!("size={size},mode=0755").as_str()),
+    )
+    .with_context(|| {
+        anyhow!(
+            "Failed to mount tmpfs on {build_dir:?}, this requires root. Alternatively mount it before the worker starts, eg. with TemporaryFileSystem= in the systemd unit"
+        )
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_dir_layout() {
+        let build_dir = tempfile::tempdir().unwrap();
+        let build = Build {
+            build_dir: Some(build_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let job_dir = JobDir::create(&build, Some(1234)).unwrap();
+        assert!(job_dir.path().starts_with(build_dir.path()));
+        assert!(
+            job_dir
+                .path()
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("job-1234-")
+        );
+        assert!(job_dir.inputs().is_dir());
+        assert!(job_dir.out().is_dir());
+        assert!(job_dir.tmp().is_dir());
+
+        let path = job_dir.path().to_path_buf();
+        drop(job_dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn tmpfs_needs_build_dir() {
+        let build = Build {
+            tmpfs_size: Some("1G".to_string()),
+            ..Default::default()
+        };
+        assert!(prepare(&build).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::config;
+use data_encoding::HEXLOWER;
+use rebuilderd_common::errors::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use tokio::sync::OwnedMutexGuard;
+
+/// Downloads of the same file wait for each other, this is shared by all downloaders of the process, eg. the one of
+/// the current build and the one prefetching the inputs of the next job
+static LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Mutex::default);
+
+/// What is known about a cached download, stored as json next to the data
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Set once the download finished, until then the data is resumed with a range request
+    pub complete: bool,
+    pub sha256: Option<String>,
+}
+
+impl CacheEntry {
+    /// Range requests are only safe if the server can tell whether the file changed in the meantime
+    pub fn validator(&self) -> Option<&str> {
+        self.etag.as_deref().or(self.last_modified.as_deref())
+    }
+}
+
+/// Downloads of the low-bandwidth mode, files are only fetched again if the mirror reports they changed and
+/// interrupted downloads continue where they stopped
+#[derive(Debug, Clone)]
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_bytes: Option<u64>,
+}
+
+impl DownloadCache {
+    pub fn from_config(config: &config::Download) -> Result<Option<Self>> {
+        let Some(dir) = &config.cache_dir else {
+            return Ok(None);
+        };
+        fs::create_dir_all(dir)
+            .with_context(|| anyhow!("Failed to create download cache directory: {dir:?}"))?;
+        Ok(Some(DownloadCache {
+            dir: dir.clone(),
+            max_bytes: config.cache_max_bytes,
+        }))
+    }
+
+    fn key(url: &str) -> String {
+        HEXLOWER.encode(&Sha256::digest(url.as_bytes()))
+    }
+
+    pub fn data_path(&self, url: &str) -> PathBuf {
+        self.dir.join(Self::key(url))
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", Self::key(url)))
+    }
+
+    pub async fn lock(&self, url: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(self.data_path(url)).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+
+    /// Entries that can't be read are treated like a cache miss
+    pub fn lookup(&self, url: &str) -> Option<CacheEntry> {
+        let buf = fs::read(self.entry_path(url)).ok()?;
+        let entry = serde_json::from_slice::<CacheEntry>(&buf).ok()?;
+        (entry.url == url && self.data_path(url).exists()).then_some(entry)
+    }
+
+    pub fn store(&self, entry: &CacheEntry) -> Result<()> {
+        let json = serde_json::to_vec(entry)?;
+        fs::write(self.entry_path(&entry.url), json)
+            .context("Failed to write download cache entry")?;
+        Ok(())
+    }
+
+    pub fn remove(&self, url: &str) {
+        let _ = fs::remove_file(self.entry_path(url));
+        let _ = fs::remove_file(self.data_path(url));
+    }
+
+    /// Eviction removes the least recently used files first
+    pub fn touch(&self, url: &str) -> Result<()> {
+        let file = fs::File::options().write(true).open(self.data_path(url))?;
+        file.set_modified(SystemTime::now())?;
+        Ok(())
+    }
+
+    /// Copy a cached file into the inputs of a build. The build may modify its inputs, so they aren't hard linked.
+    pub async fn checkout(&self, url: &str, target: &Path) -> Result<()> {
+        tokio::fs::copy(self.data_path(url), target)
+            .await
+            .with_context(|| anyhow!("Failed to copy cached download to {target:?}"))?;
+        Ok(())
+    }
+
+    /// Remove the least recently used files until the cache fits into its size limit. Files that are currently
+    /// downloaded are left alone.
+    pub fn evict(&self) -> Result<()> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+
+        let busy = LOCKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|(_, lock)| Arc::strong_count(lock) > 1)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        let mut files = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some() {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            total += metadata.len();
+            files.push((metadata.modified()?, metadata.len(), path));
+        }
+        files.sort();
+
+        for (_, len, path) in files {
+            if total <= max_bytes {
+                break;
+            }
+            if busy.contains(&path) {
+                continue;
+            }
+            debug!("Evicting {path:?} from download cache");
+            fs::remove_file(&path)?;
+            let _ = fs::remove_file(path.with_extension("json"));
+            total -= len;
+        }
+
+        Ok(())
+    }
+}
+
+/// The offset a `Content-Range` header of a partial response starts at, eg. `bytes 1024-2047/2048`
+pub fn content_range_start(value: &str) -> Option<u64> {
+    let (start, _) = value.strip_prefix("bytes ")?.split_once('-')?;
+    start.parse().ok()
+}
+
+pub async fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(HEXLOWER.encode(&hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn cache(dir: &Path, max_bytes: Option<u64>) -> DownloadCache {
+        DownloadCache::from_config(&config::Download {
+            cache_dir: Some(dir.to_path_buf()),
+            cache_max_bytes: max_bytes,
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    fn put(cache: &DownloadCache, url: &str, data: &[u8]) {
+        fs::write(cache.data_path(url), data).unwrap();
+        cache
+            .store(&CacheEntry {
+                url: url.to_string(),
+                complete: true,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), None);
+        assert_eq!(cache.lookup("https://example.com/a.pkg"), None);
+
+        put(&cache, "https://example.com/a.pkg", b"abc");
+        let entry = cache.lookup("https://example.com/a.pkg").unwrap();
+        assert!(entry.complete);
+        assert_eq!(cache.lookup("https://example.com/b.pkg"), None);
+
+        cache.remove("https://example.com/a.pkg");
+        assert_eq!(cache.lookup("https://example.com/a.pkg"), None);
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), Some(10));
+
+        let now = SystemTime::now();
+        for (i, url) in [
+            "https://example.com/a",
+            "https://example.com/b",
+            "https://example.com/c",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            put(&cache, url, b"12345");
+            fs::File::options()
+                .write(true)
+                .open(cache.data_path(url))
+                .unwrap()
+                .set_modified(now - Duration::from_secs(100 - i as u64))
+                .unwrap();
+        }
+        cache.touch("https://example.com/a").unwrap();
+
+        cache.evict().unwrap();
+        assert!(cache.lookup("https://example.com/a").is_some());
+        assert_eq!(cache.lookup("https://example.com/b"), None);
+        assert!(cache.lookup("https://example.com/c").is_some());
+        assert!(!cache.entry_path("https://example.com/b").exists());
+    }
+
+    #[test]
+    fn test_content_range_start() {
+        assert_eq!(content_range_start("bytes 1024-2047/2048"), Some(1024));
+        assert_eq!(content_range_start("bytes 0-9/*"), Some(0));
+        assert_eq!(content_range_start("bytes */2048"), None);
+    }
+}
@@ -0,0 +1,453 @@
+use crate::compression;
+use crate::config::{self, Compare, CompareCommand};
+use crate::diffoscope;
+use crate::normalize::{Normalizer, normalize_file};
+use crate::proc;
+use async_trait::async_trait;
+use rebuilderd_common::errors::*;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// Output of an external compare command beyond this is discarded
+const COMMAND_OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// The verdict on a rebuilt artifact
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Comparison {
+    pub identical: bool,
+    /// Set if diffoscope ran as part of the comparison, so it doesn't need to run a second time
+    pub diffoscope: Option<String>,
+    /// Only the decompressed contents are identical, the outer compression differs
+    pub modulo_compression: bool,
+}
+
+/// Decides whether a rebuilt artifact is equivalent to the published one
+#[async_trait]
+pub trait Comparator: Send + Sync {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison>;
+}
+
+/// The artifacts need to be bit-for-bit identical
+pub struct Bytes;
+
+#[async_trait]
+impl Comparator for Bytes {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison> {
+        Ok(Comparison {
+            identical: compare_files(artifact, output).await?,
+            diffoscope: None,
+            modulo_compression: false,
+        })
+    }
+}
+
+/// Both artifacts are normalized into a scratch directory before they're compared bit-for-bit, for formats that embed
+/// signatures or other data that can't be reproduced
+pub struct NormalizedArchive {
+    pub normalizers: Vec<Normalizer>,
+    pub dir: PathBuf,
+}
+
+#[async_trait]
+impl Comparator for NormalizedArchive {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison> {
+        fs::create_dir_all(&self.dir).context("Failed to create normalized/ temp dir")?;
+        let a = self.dir.join("artifact");
+        let b = self.dir.join("output");
+
+        info!("Normalizing artifacts with {:?}", self.normalizers);
+        normalize_file(&self.normalizers, artifact, &a)
+            .await
+            .context("Failed to normalize original artifact")?;
+        normalize_file(&self.normalizers, output, &b)
+            .await
+            .context("Failed to normalize rebuilt artifact")?;
+
+        Bytes.compare(&a, &b).await
+    }
+}
+
+/// The artifacts are accepted if diffoscope, with the configured arguments, finds no differences
+pub struct Diffoscope {
+    pub settings: config::Diffoscope,
+}
+
+#[async_trait]
+impl Comparator for Diffoscope {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison> {
+        info!("Checking artifacts with diffoscope");
+        let (identical, output) = diffoscope::run(artifact, output, &self.settings)
+            .await
+            .context("Failed to run diffoscope")?;
+        Ok(Comparison {
+            identical,
+            diffoscope: Some(output),
+            modulo_compression: false,
+        })
+    }
+}
+
+/// The decompressed contents of the artifacts need to be identical, for packages that are only compressed with a
+/// different level or implementation than the published one
+pub struct Decompressed;
+
+#[async_trait]
+impl Comparator for Decompressed {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison> {
+        let identical = compression::same_contents(artifact, output)
+            .await
+            .context("Failed to compare decompressed artifacts")?
+            .unwrap_or(false);
+        if identical {
+            info!("Decompressed artifacts are identical, reproducible modulo compression");
+        }
+        Ok(Comparison {
+            identical,
+            diffoscope: None,
+            modulo_compression: identical,
+        })
+    }
+}
+
+/// An external tool is started with the published and the rebuilt artifact as last arguments, the artifacts are
+/// accepted if it exits successfully
+pub struct External {
+    pub command: CompareCommand,
+}
+
+#[async_trait]
+impl Comparator for External {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison> {
+        let mut args = self
+            .command
+            .args
+            .iter()
+            .map(OsString::from)
+            .collect::<Vec<_>>();
+        args.push(artifact.into());
+        args.push(output.into());
+
+        let opts = proc::Options {
+            timeout: Duration::from_secs(self.command.timeout.unwrap_or(3600)), // 1h
+            max_timeout: None,
+            size_limit: Some(COMMAND_OUTPUT_LIMIT),
+            kill_at_size_limit: false,
+            passthrough: false,
+            envs: HashMap::new(),
+            progress: None,
+            isolate_network: false,
+            single_cpu: false,
+        };
+
+        let mut log = Vec::new();
+        let identical = proc::run(&self.command.path, &args, opts, &mut log)
+            .await
+            .with_context(|| anyhow!("Failed to run compare command {:?}", self.command.path))?;
+        debug!(
+            "Compare command {:?} finished: {}",
+            self.command.path,
+            String::from_utf8_lossy(&log)
+        );
+
+        Ok(Comparison {
+            identical,
+            diffoscope: None,
+            modulo_compression: false,
+        })
+    }
+}
+
+/// Asks each comparator in turn until one of them accepts the artifacts, the cheap ones should go first
+pub struct Chain(Vec<Box<dyn Comparator>>);
+
+#[async_trait]
+impl Comparator for Chain {
+    async fn compare(&self, artifact: &Path, output: &Path) -> Result<Comparison> {
+        let mut verdict = Comparison::default();
+        for comparator in &self.0 {
+            let comparison = comparator.compare(artifact, output).await?;
+            if comparison.identical {
+                return Ok(comparison);
+            }
+            verdict.diffoscope = comparison.diffoscope.or(verdict.diffoscope);
+        }
+        Ok(verdict)
+    }
+}
+
+/// The artifacts are compared bit-for-bit first, after normalization if the backend configures it. Artifacts that
+/// differ get a second chance with their decompressed contents, if enabled, and the configured comparison.
+pub fn for_backend(
+    backend: &config::Backend,
+    diffoscope: &config::Diffoscope,
+    normalized_dir: &Path,
+) -> Result<Chain> {
+    let mut chain: Vec<Box<dyn Comparator>> = if backend.normalize.is_empty() {
+        vec![Box::new(Bytes)]
+    } else {
+        vec![Box::new(NormalizedArchive {
+            normalizers: backend.normalize.clone(),
+            dir: normalized_dir.to_path_buf(),
+        })]
+    };
+
+    if backend.compare_decompressed {
+        chain.push(Box::new(Decompressed));
+    }
+
+    match backend.compare {
+        Compare::Exact => (),
+        Compare::Diffoscope => chain.push(Box::new(Diffoscope {
+            settings: diffoscope.clone(),
+        })),
+        Compare::Command => {
+            let command = backend.compare_command.clone().context(
+                "The backend compares with a command, but no compare_command is configured",
+            )?;
+            chain.push(Box::new(External { command }));
+        }
+    }
+
+    Ok(Chain(chain))
+}
+
+pub async fn compare_files(a: &Path, b: &Path) -> Result<bool> {
+    let mut buf1 = [0u8; 4096];
+    let mut buf2 = [0u8; 4096];
+
+    info!("Comparing {:?} with {:?}", a, b);
+    let mut f1 = File::open(a)
+        .await
+        .with_context(|| anyhow!("Failed to open {:?}", a))?;
+    let mut f2 = File::open(b)
+        .await
+        .with_context(|| anyhow!("Failed to open {:?}", b))?;
+
+    let mut pos = 0;
+    loop {
+        // read up to 4k bytes from the first file
+        let n = f1.read_buf(&mut &mut buf1[..]).await?;
+
+        // check if the first file is end-of-file
+        if n == 0 {
+            debug!("First file is at end-of-file");
+
+            // check if other file is eof too
+            let n = f2.read_buf(&mut &mut buf2[..]).await?;
+            if n > 0 {
+                info!("Files are not identical, {:?} is longer", b);
+                return Ok(false);
+            } else {
+                return Ok(true);
+            }
+        }
+
+        // check the same chunk in the other file
+        match f2.read_exact(&mut buf2[..n]).await {
+            Ok(n) => n,
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                info!("Files are not identical, {:?} is shorter", b);
+                return Ok(false);
+            }
+            err => err?,
+        };
+
+        if buf1[..n] != buf2[..n] {
+            // get the exact position
+            // this can't panic because we've already checked the slices are not equal
+            let pos = pos
+                + buf1[..n]
+                    .iter()
+                    .zip(buf2[..n].iter())
+                    .position(|(a, b)| a != b)
+                    .unwrap();
+            info!("Files {:?} and {:?} differ at position {}", a, b, pos);
+
+            return Ok(false);
+        }
+
+        // advance the number of bytes that are equal
+        pos += n;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn compare_files_equal() {
+        let equal = compare_files(Path::new("src/main.rs"), Path::new("src/main.rs"))
+            .await
+            .unwrap();
+        assert!(equal);
+    }
+
+    #[tokio::test]
+    async fn compare_files_not_equal1() {
+        let equal = compare_files(Path::new("src/main.rs"), Path::new("Cargo.toml"))
+            .await
+            .unwrap();
+        assert!(!equal);
+    }
+
+    #[tokio::test]
+    async fn compare_files_not_equal2() {
+        let equal = compare_files(Path::new("Cargo.toml"), Path::new("src/main.rs"))
+            .await
+            .unwrap();
+        assert!(!equal);
+    }
+
+    #[tokio::test]
+    async fn compare_large_files_equal() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), [0u8; 4096 * 100]).unwrap();
+        fs::write(dir.path().join("b"), [0u8; 4096 * 100]).unwrap();
+        let equal = compare_files(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(equal);
+    }
+
+    #[tokio::test]
+    async fn compare_large_files_not_equal() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), [0u8; 4096 * 100]).unwrap();
+        fs::write(dir.path().join("b"), [1u8; 4096 * 100]).unwrap();
+        let equal = compare_files(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(!equal);
+    }
+
+    #[tokio::test]
+    async fn external_command_decides() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "foo").unwrap();
+        fs::write(dir.path().join("b"), "bar").unwrap();
+
+        let accept = External {
+            command: CompareCommand {
+                path: PathBuf::from("true"),
+                ..Default::default()
+            },
+        };
+        let comparison = accept
+            .compare(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(comparison.identical);
+
+        let reject = External {
+            command: CompareCommand {
+                path: PathBuf::from("false"),
+                ..Default::default()
+            },
+        };
+        let comparison = reject
+            .compare(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(!comparison.identical);
+    }
+
+    #[tokio::test]
+    async fn chain_falls_back_to_configured_comparison() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "foo").unwrap();
+        fs::write(dir.path().join("b"), "bar").unwrap();
+
+        let backend = config::Backend {
+            compare: Compare::Command,
+            compare_command: Some(CompareCommand {
+                path: PathBuf::from("true"),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let chain = for_backend(
+            &backend,
+            &config::Diffoscope::default(),
+            &dir.path().join("n"),
+        )
+        .unwrap();
+        let comparison = chain
+            .compare(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(comparison.identical);
+
+        let exact = for_backend(
+            &config::Backend::default(),
+            &config::Diffoscope::default(),
+            &dir.path().join("n"),
+        )
+        .unwrap();
+        let comparison = exact
+            .compare(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(!comparison.identical);
+    }
+
+    #[tokio::test]
+    async fn recompressed_artifacts_are_accepted_if_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = b"foo".repeat(1000);
+        fs::write(
+            dir.path().join("a"),
+            zstd::encode_all(&data[..], 1).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("b"),
+            zstd::encode_all(&data[..], 19).unwrap(),
+        )
+        .unwrap();
+
+        let exact = for_backend(
+            &config::Backend::default(),
+            &config::Diffoscope::default(),
+            &dir.path().join("n"),
+        )
+        .unwrap();
+        let comparison = exact
+            .compare(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert_eq!(comparison, Comparison::default());
+
+        let backend = config::Backend {
+            compare_decompressed: true,
+            ..Default::default()
+        };
+        let chain = for_backend(
+            &backend,
+            &config::Diffoscope::default(),
+            &dir.path().join("n"),
+        )
+        .unwrap();
+        let comparison = chain
+            .compare(&dir.path().join("a"), &dir.path().join("b"))
+            .await
+            .unwrap();
+        assert!(comparison.identical);
+        assert!(comparison.modulo_compression);
+    }
+
+    #[test]
+    fn command_comparison_needs_a_command() {
+        let backend = config::Backend {
+            compare: Compare::Command,
+            ..Default::default()
+        };
+        assert!(for_backend(&backend, &config::Diffoscope::default(), Path::new("n")).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use flate2::read::GzDecoder;
+use rebuilderd_common::errors::*;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The outer compression of an artifact, eg. the `.zst` of a `.pkg.tar.zst`. It's detected from the content, so the
+/// filename doesn't matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if bytes.starts_with(XZ_MAGIC) {
+            Some(Compression::Xz)
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub fn detect_file(path: &Path) -> Result<Option<Self>> {
+        let mut file = File::open(path).with_context(|| anyhow!("Failed to open {:?}", path))?;
+        let mut magic = Vec::new();
+        file.by_ref().take(8).read_to_end(&mut magic)?;
+        Ok(Self::detect(&magic))
+    }
+
+    pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        let reader: Box<dyn Read + 'a> = match self {
+            Compression::Gzip => Box::new(GzDecoder::new(reader)),
+            Compression::Xz => Box::new(XzDecoder::new(reader)),
+            Compression::Zstd => Box::new(ZstdDecoder::new(reader)?),
+        };
+        Ok(reader)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Decompress the bytes if they use a known compression, reading at most `limit` bytes of decompressed data
+pub fn decompress(bytes: &[u8], limit: u64) -> Option<Vec<u8>> {
+    let compression = Compression::detect(bytes)?;
+    let mut out = Vec::new();
+    compression
+        .decoder(bytes)
+        .ok()?
+        .take(limit)
+        .read_to_end(&mut out)
+        .ok()?;
+    Some(out)
+}
+
+fn read_chunk<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(n)
+}
+
+fn same_contents_blocking(a: &Path, b: &Path) -> Result<Option<bool>> {
+    let (Some(compression_a), Some(compression_b)) =
+        (Compression::detect_file(a)?, Compression::detect_file(b)?)
+    else {
+        return Ok(None);
+    };
+    info!("Comparing {compression_a} compressed {a:?} with {compression_b} compressed {b:?}");
+
+    let mut a = compression_a.decoder(BufReader::new(File::open(a)?))?;
+    let mut b = compression_b.decoder(BufReader::new(File::open(b)?))?;
+
+    let mut buf1 = [0u8; 4096];
+    let mut buf2 = [0u8; 4096];
+    loop {
+        let n1 = read_chunk(&mut a, &mut buf1).context("Failed to decompress original artifact")?;
+        let n2 = read_chunk(&mut b, &mut buf2).context("Failed to decompress rebuilt artifact")?;
+        if buf1[..n1] != buf2[..n2] {
+            return Ok(Some(false));
+        }
+        if n1 == 0 {
+            return Ok(Some(true));
+        }
+    }
+}
+
+/// Whether both files are compressed and their decompressed contents are identical. `None` if one of them doesn't
+/// use a known compression.
+pub async fn same_contents(a: &Path, b: &Path) -> Result<Option<bool>> {
+    let a = PathBuf::from(a);
+    let b = PathBuf::from(b);
+    tokio::task::spawn_blocking(move || same_contents_blocking(&a, &b)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression as GzLevel;
+    use flate2::write::GzEncoder;
+    use std::fs;
+    use std::io::Write;
+
+    fn gzip(data: &[u8], level: u32) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), GzLevel::new(level));
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn write_pair(a: &[u8], b: &[u8]) -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let pa = dir.path().join("a");
+        let pb = dir.path().join("b");
+        fs::write(&pa, a).unwrap();
+        fs::write(&pb, b).unwrap();
+        (dir, pa, pb)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Compression::detect(&gzip(b"abc", 6)),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(&zstd::encode_all(&b"abc"[..], 3).unwrap()),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::detect(b"\xfd7zXZ\x00\x00\x04"),
+            Some(Compression::Xz)
+        );
+        assert_eq!(Compression::detect(b"!<arch>\n"), None);
+        assert_eq!(Compression::detect(b""), None);
+    }
+
+    #[test]
+    fn test_decompress() {
+        let data = b"hello world".repeat(100);
+        assert_eq!(decompress(&gzip(&data, 6), u64::MAX), Some(data.clone()));
+        assert_eq!(
+            decompress(&zstd::encode_all(&data[..], 19).unwrap(), 5),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(decompress(&data, u64::MAX), None);
+    }
+
+    #[tokio::test]
+    async fn test_same_contents_different_level() {
+        let data = b"hello world".repeat(1000);
+        let a = zstd::encode_all(&data[..], 1).unwrap();
+        let b = zstd::encode_all(&data[..], 19).unwrap();
+        assert_ne!(a, b);
+        let (_dir, a, b) = write_pair(&a, &b);
+        assert_eq!(same_contents(&a, &b).await.unwrap(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_same_contents_different_compression() {
+        let data = b"hello world".repeat(1000);
+        let (_dir, a, b) = write_pair(&gzip(&data, 9), &zstd::encode_all(&data[..], 3).unwrap());
+        assert_eq!(same_contents(&a, &b).await.unwrap(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_different_contents() {
+        let (_dir, a, b) = write_pair(&gzip(b"hello world", 1), &gzip(b"hello world!", 9));
+        assert_eq!(same_contents(&a, &b).await.unwrap(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed() {
+        let (_dir, a, b) = write_pair(&gzip(b"hello world", 1), b"hello world");
+        assert_eq!(same_contents(&a, &b).await.unwrap(), None);
+    }
+}
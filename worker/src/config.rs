@@ -1,31 +1,148 @@
 use crate::args::Args;
+use crate::normalize::Normalizer;
 use rebuilderd_common::errors::*;
+use rebuilderd_common::http::{OutboundConfig, TlsConfig};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Relative to the working directory of the worker, unless configured otherwise
+const DEFAULT_IDENTITY_FILE: &str = "rebuilder.identity";
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ConfigFile {
     pub endpoint: Option<String>,
     pub signup_secret: Option<String>,
+    /// The uuid and key of the worker, generated on the first run
+    pub identity_file: Option<PathBuf>,
+    /// Build reports are kept here until the daemon accepted them, so they survive the daemon being unreachable
+    pub spool_dir: Option<PathBuf>,
+    /// Verification of the daemon's certificate, downloads of build inputs are not affected
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// How downloads of build inputs identify themselves to mirrors
+    #[serde(default)]
+    pub outbound: OutboundConfig,
     #[serde(default)]
     pub build: Build,
     #[serde(default)]
+    pub download: Download,
+    #[serde(default)]
     pub diffoscope: Diffoscope,
     #[serde(default, rename = "backend")]
     pub backends: HashMap<String, Backend>,
+    /// Register every `rebuilder-<distro>.sh` in this directory as backend, unless it's configured explicitly
+    pub backend_dir: Option<PathBuf>,
+    /// Commands that are run after each build, in order
+    #[serde(default, rename = "hook")]
+    pub hooks: Vec<Hook>,
     #[serde(default)]
     pub supported_architectures: Vec<String>,
+    /// Architectures this worker has cross toolchains for, it can verify packages for them are cross-compiled
+    /// reproducibly
+    #[serde(default)]
+    pub cross_architectures: Vec<String>,
+    /// Preferred mirrors for artifact downloads, keyed by distribution
+    #[serde(default)]
+    pub mirrors: HashMap<String, String>,
     pub idle_delay: Option<u64>,
+    #[serde(default)]
+    pub metrics: Metrics,
+}
+
+impl ConfigFile {
+    pub fn identity_file(&self) -> &Path {
+        self.identity_file
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_IDENTITY_FILE))
+    }
+
+    /// The architectures to ask for work for, defaults to the native architecture. On macOS the Homebrew bottle tag of
+    /// the running os version is added too, eg. `arm64_sonoma`.
+    pub fn supported_architectures(&self) -> Vec<String> {
+        if !self.supported_architectures.is_empty() {
+            return self.supported_architectures.clone();
+        }
+
+        let mut architectures = vec![std::env::consts::ARCH.to_string()];
+        if cfg!(target_os = "macos")
+            && let Some(tag) = macos_version()
+                .and_then(|version| homebrew_bottle_tag(std::env::consts::ARCH, &version))
+        {
+            architectures.push(tag);
+        }
+        architectures
+    }
+
+    /// Pick the backend for a distribution, if none is given there has to be exactly one backend configured
+    pub fn select_backend(&self, distro: Option<&str>) -> Result<&Backend> {
+        if let Some(distro) = distro {
+            return self
+                .backends
+                .get(distro)
+                .with_context(|| anyhow!("No backend configured for {distro:?}"));
+        }
+
+        let mut backends = self.backends.values();
+        match (backends.next(), backends.next()) {
+            (Some(backend), None) => Ok(backend),
+            (None, _) => bail!("No backend configured in config file"),
+            (Some(_), Some(_)) => {
+                let mut names = self.backends.keys().map(String::as_str).collect::<Vec<_>>();
+                names.sort();
+                bail!(
+                    "Multiple backends configured, select one of: {}",
+                    names.join(", ")
+                )
+            }
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Build {
     pub timeout: Option<u64>,
+    /// Rebuilder scripts may print `::rebuilderd-extend:: <seconds>` to get more time, up to this many seconds in total
+    pub max_timeout: Option<u64>,
     pub max_bytes: Option<usize>,
     #[serde(default)]
     pub silent: bool,
+    /// Keep the output of builds that were rejected by a required hook in this directory
+    pub quarantine_dir: Option<PathBuf>,
+    /// Run rebuilder scripts without network access, only the inputs that were fetched beforehand are available
+    #[serde(default)]
+    pub offline: bool,
+    /// Fail builds whose inputs and original packages add up to more than this many bytes
+    pub max_download_bytes: Option<u64>,
+    /// Claim the next job while a build is running and download its inputs in the meantime
+    #[serde(default)]
+    pub prefetch_next_job: bool,
+    /// Inputs of the next job are only kept if they fit into this many bytes, defaults to `max_download_bytes`
+    pub prefetch_max_bytes: Option<u64>,
+    /// Every job gets its own directory in here, defaults to the system temp directory
+    pub build_dir: Option<PathBuf>,
+    /// Mount a tmpfs of this size on `build_dir` when the worker starts, eg. "16G"
+    pub tmpfs_size: Option<String>,
+}
+
+impl Build {
+    pub fn build_dir(&self) -> PathBuf {
+        self.build_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+}
+
+/// Low-bandwidth mode, for workers on metered or slow connections
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Download {
+    /// Keep downloads in this directory. Files are only fetched again if the mirror reports they changed, and
+    /// interrupted downloads are resumed with range requests.
+    pub cache_dir: Option<PathBuf>,
+    /// Remove the least recently used files once the cache grows beyond this many bytes
+    pub cache_max_bytes: Option<u64>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -38,13 +155,160 @@ pub struct Diffoscope {
     pub max_bytes: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Backend {
     pub path: PathBuf,
+    /// Tools the rebuilder script needs, checked by the self-test on startup
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Normalization passes applied to both artifacts before comparing them
+    #[serde(default)]
+    pub normalize: Vec<Normalizer>,
+    #[serde(default)]
+    pub compare: Compare,
+    /// The external tool used with `compare = "command"`
+    pub compare_command: Option<CompareCommand>,
+    /// Accept artifacts whose decompressed contents are identical, eg. if only the zstd level of a package differs.
+    /// They're reported as reproducible modulo compression.
+    #[serde(default)]
+    pub compare_decompressed: bool,
+    /// Overrides the timeout of the [build] section for this backend
+    pub timeout: Option<u64>,
+    /// Overrides the log limit of the [build] section for this backend
+    pub max_bytes: Option<usize>,
+    /// Overrides the offline setting of the [build] section for this backend
+    pub offline: Option<bool>,
+    /// Runs with network access before an offline build, to download the dependencies into the inputs directory
+    pub prefetch: Option<PathBuf>,
+    /// Regular expression the filenames of the original packages have to match, defaults to the naming scheme of the
+    /// distribution the backend is named after
+    pub artifact_filename: Option<String>,
+}
+
+impl Backend {
+    pub fn is_offline(&self, build: &Build) -> bool {
+        self.offline.unwrap_or(build.offline)
+    }
+
+    pub fn artifact_filename(&self) -> Result<Option<Regex>> {
+        let Some(pattern) = &self.artifact_filename else {
+            return Ok(None);
+        };
+        let regex = Regex::new(pattern)
+            .with_context(|| anyhow!("Invalid artifact_filename pattern: {pattern:?}"))?;
+        Ok(Some(regex))
+    }
+}
+
+/// How the packages of a distribution are named, an url that doesn't end like this is rejected before downloading it.
+/// The names are matched as they appear in the url, so they may be percent-encoded.
+fn default_artifact_filename(distro: &str) -> Option<&'static str> {
+    match distro {
+        "archlinux" => Some(
+            r"^[a-zA-Z0-9@_+][a-zA-Z0-9@._+-]*-[^-/]+-[^-/]+-[a-z0-9_]+\.pkg\.tar(\.[a-z0-9]+)?$",
+        ),
+        "debian" => Some(r"^[a-z0-9][a-z0-9.+%-]*_[a-zA-Z0-9.+~%-]+_[a-z0-9-]+\.u?deb$"),
+        _ => None,
+    }
+}
+
+/// How the rebuilt artifact is compared with the published one
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compare {
+    /// The artifacts need to be bit-for-bit identical, after normalization
+    #[default]
+    Exact,
+    /// Artifacts that differ are still accepted if diffoscope, with the configured arguments, finds no differences
+    Diffoscope,
+    /// Artifacts that differ are still accepted if the `compare_command` of the backend exits successfully
+    Command,
+}
+
+/// Started with the published and the rebuilt artifact as last arguments
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompareCommand {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub timeout: Option<u64>,
+}
+
+/// Push build counts and durations to a prometheus pushgateway, for workers that can't be scraped
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Metrics {
+    pub pushgateway: Option<String>,
+    /// The `job` label of the pushed metrics, defaults to `rebuilderd-worker`
+    pub job: Option<String>,
+    /// Further labels that tell workers apart, `instance` defaults to the name of the worker
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Metrics {
+    fn validate(&self) -> Result<()> {
+        for name in self.labels.keys() {
+            let valid = name
+                .chars()
+                .enumerate()
+                .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+            if name.is_empty() || !valid || name.starts_with("__") {
+                bail!("Invalid metrics label name: {name:?}");
+            }
+            if name == "job" {
+                bail!("The job label of metrics is configured with `job`, not in `labels`");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub timeout: Option<u64>,
+    /// Reject the build if the hook fails, instead of only logging a warning
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn macos_version() -> Option<String> {
+    let output = Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    Some(version.trim().to_string())
+}
+
+/// The tag Homebrew publishes bottles for this architecture and macOS version with, eg. `arm64_sonoma`
+fn homebrew_bottle_tag(arch: &str, macos_version: &str) -> Option<String> {
+    let major = macos_version.split('.').next()?;
+    let codename = match major {
+        "11" => "big_sur",
+        "12" => "monterey",
+        "13" => "ventura",
+        "14" => "sonoma",
+        "15" => "sequoia",
+        "26" => "tahoe",
+        _ => return None,
+    };
+    match arch {
+        "aarch64" => Some(format!("arm64_{codename}")),
+        "x86_64" => Some(codename.to_string()),
+        _ => None,
+    }
 }
 
 pub fn load(args: &Args) -> Result<ConfigFile> {
-    let path = if let Some(path) = args.config.as_ref() {
+    load_from(args.config.as_deref(), &args.backends)
+}
+
+/// The config file with the backends given on the command line added, also used to reload the config
+pub fn load_from(path: Option<&Path>, extra_backends: &[String]) -> Result<ConfigFile> {
+    let path = if let Some(path) = path {
         Some(path.to_owned())
     } else {
         let path = PathBuf::from("/etc/rebuilderd-worker.conf");
@@ -68,15 +332,246 @@ pub fn load(args: &Args) -> Result<ConfigFile> {
         ConfigFile::default()
     };
 
-    for backend in &args.backends {
+    if let Some(dir) = &conf.backend_dir {
+        for (name, path) in discover_backends(dir)? {
+            if let Entry::Vacant(entry) = conf.backends.entry(name) {
+                debug!("Discovered backend {:?}: {:?}", entry.key(), path);
+                entry.insert(Backend {
+                    path,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    for backend in extra_backends {
         debug!("Adding to list of supported backends: {:?}", backend);
         let (key, path) = backend.split_once('=').ok_or_else(|| {
             anyhow!("Invalid argument, expected format is --backend distro=/path/to/script")
         })?;
 
-        conf.backends
-            .insert(key.into(), Backend { path: path.into() });
+        conf.backends.insert(
+            key.into(),
+            Backend {
+                path: path.into(),
+                ..Default::default()
+            },
+        );
+    }
+
+    for (name, backend) in &mut conf.backends {
+        if backend.artifact_filename.is_none() {
+            backend.artifact_filename = default_artifact_filename(name).map(String::from);
+        }
+        backend
+            .artifact_filename()
+            .with_context(|| anyhow!("Invalid config for backend {name:?}"))?;
     }
+    conf.metrics.validate()?;
 
     Ok(conf)
 }
+
+/// Find rebuilder scripts named after the distribution they rebuild, eg. `rebuilder-archlinux.sh`
+fn discover_backends(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut backends = Vec::new();
+    let entries =
+        fs::read_dir(dir).with_context(|| anyhow!("Failed to read backend dir {:?}", dir))?;
+
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("rebuilder-"))
+            .and_then(|name| name.strip_suffix(".sh"))
+        else {
+            continue;
+        };
+
+        if name.is_empty() || !path.is_file() {
+            continue;
+        }
+
+        backends.push((name.to_string(), path.clone()));
+    }
+
+    backends.sort();
+    Ok(backends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_homebrew_bottle_tag() {
+        assert_eq!(
+            homebrew_bottle_tag("aarch64", "14.6.1").as_deref(),
+            Some("arm64_sonoma")
+        );
+        assert_eq!(
+            homebrew_bottle_tag("x86_64", "13.0").as_deref(),
+            Some("ventura")
+        );
+        assert_eq!(homebrew_bottle_tag("aarch64", "10.15.7"), None);
+        assert_eq!(homebrew_bottle_tag("riscv64", "15.0"), None);
+    }
+
+    #[test]
+    fn test_discover_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "rebuilder-archlinux.sh",
+            "rebuilder-debian.sh",
+            "rebuilder-.sh",
+            "README.md",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("rebuilder-dir.sh")).unwrap();
+
+        let backends = discover_backends(dir.path()).unwrap();
+        assert_eq!(
+            backends,
+            vec![
+                (
+                    "archlinux".to_string(),
+                    dir.path().join("rebuilder-archlinux.sh")
+                ),
+                ("debian".to_string(), dir.path().join("rebuilder-debian.sh")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_artifact_filename() {
+        let archlinux = Regex::new(default_artifact_filename("archlinux").unwrap()).unwrap();
+        for filename in [
+            "zstd-1.5.6-1-x86_64.pkg.tar.zst",
+            "gcc-libs-14.1.1+r1+g43b730b9134-1-x86_64.pkg.tar.zst",
+            "iptables-1:1.8.10-2-x86_64.pkg.tar.zst",
+            "python-setuptools-1:69.5.1-1-any.pkg.tar.xz",
+        ] {
+            assert!(archlinux.is_match(filename), "{filename:?}");
+        }
+        for filename in [
+            "zstd.pkg.tar.zst",
+            "zstd-1.5.6-1-x86_64.tar.zst",
+            "zstd_1.5.6-1_amd64.deb",
+        ] {
+            assert!(!archlinux.is_match(filename), "{filename:?}");
+        }
+
+        let debian = Regex::new(default_artifact_filename("debian").unwrap()).unwrap();
+        for filename in [
+            "curl_8.5.0-2_amd64.deb",
+            "libstdc++6_14.2.0-4_amd64.deb",
+            "tzdata_2024a-0%2Bdeb12u1_all.deb",
+            "di-utils_1.149_hurd-i386.udeb",
+        ] {
+            assert!(debian.is_match(filename), "{filename:?}");
+        }
+        for filename in [
+            "curl.deb",
+            "curl_8.5.0-2_amd64.deb.exe",
+            "Curl_8.5.0-2_amd64.deb",
+        ] {
+            assert!(!debian.is_match(filename), "{filename:?}");
+        }
+
+        assert_eq!(default_artifact_filename("tails"), None);
+    }
+
+    #[test]
+    fn test_validate_metrics_labels() {
+        let mut metrics = Metrics::default();
+        metrics
+            .labels
+            .insert("site_2".to_string(), "office".to_string());
+        assert!(metrics.validate().is_ok());
+
+        for name in ["", "2site", "site-name", "__name__", "job"] {
+            let mut metrics = Metrics::default();
+            metrics.labels.insert(name.to_string(), "x".to_string());
+            assert!(metrics.validate().is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_backend() {
+        let config = toml::from_str::<ConfigFile>(
+            r#"
+            [backend."tails"]
+            path = "/usr/libexec/rebuilderd/rebuilder-tails.sh"
+            compare = "diffoscope"
+            timeout = 172800
+        "#,
+        )
+        .unwrap();
+        let backend = &config.backends["tails"];
+        assert_eq!(backend.compare, Compare::Diffoscope);
+        assert_eq!(backend.timeout, Some(172800));
+        assert_eq!(backend.max_bytes, None);
+    }
+
+    #[test]
+    fn test_select_backend() {
+        let mut config = ConfigFile::default();
+        assert!(config.select_backend(None).is_err());
+
+        config.backends.insert(
+            "archlinux".to_string(),
+            Backend {
+                path: PathBuf::from("rebuilder-archlinux.sh"),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            config.select_backend(None).unwrap().path,
+            PathBuf::from("rebuilder-archlinux.sh")
+        );
+        assert!(config.select_backend(Some("debian")).is_err());
+
+        config.backends.insert(
+            "debian".to_string(),
+            Backend {
+                path: PathBuf::from("rebuilder-debian.sh"),
+                ..Default::default()
+            },
+        );
+        assert!(config.select_backend(None).is_err());
+        assert_eq!(
+            config.select_backend(Some("debian")).unwrap().path,
+            PathBuf::from("rebuilder-debian.sh")
+        );
+    }
+
+    #[test]
+    fn test_parse_offline() {
+        let config = toml::from_str::<ConfigFile>(
+            r#"
+            [build]
+            offline = true
+
+            [backend."archlinux"]
+            path = "/usr/libexec/rebuilderd/rebuilder-archlinux.sh"
+            prefetch = "/usr/libexec/rebuilderd/prefetch-archlinux.sh"
+
+            [backend."tails"]
+            path = "/usr/libexec/rebuilderd/rebuilder-tails.sh"
+            offline = false
+        "#,
+        )
+        .unwrap();
+        let archlinux = &config.backends["archlinux"];
+        assert!(archlinux.is_offline(&config.build));
+        assert_eq!(
+            archlinux.prefetch,
+            Some(PathBuf::from(
+                "/usr/libexec/rebuilderd/prefetch-archlinux.sh"
+            ))
+        );
+        assert!(!config.backends["tails"].is_offline(&config.build));
+    }
+}
@@ -7,6 +7,12 @@ use std::path::Path;
 use std::time::Duration;
 
 pub async fn diffoscope(a: &Path, b: &Path, settings: &config::Diffoscope) -> Result<String> {
+    let (_, output) = run(a, b, settings).await?;
+    Ok(output)
+}
+
+/// Run diffoscope and also report if it found the files to be identical
+pub async fn run(a: &Path, b: &Path, settings: &config::Diffoscope) -> Result<(bool, String)> {
     let mut args = settings.args.iter().map(OsString::from).collect::<Vec<_>>();
     let timeout = settings.timeout.unwrap_or(3600); // 1h
 
@@ -17,16 +23,20 @@ pub async fn diffoscope(a: &Path, b: &Path, settings: &config::Diffoscope) -> Re
 
     let opts = proc::Options {
         timeout: Duration::from_secs(timeout + 600), // give diffoscope 10 minutes to finish
+        max_timeout: None,
         size_limit: settings.max_bytes,
         kill_at_size_limit: true,
         passthrough: false,
         envs: HashMap::new(),
+        progress: None,
+        isolate_network: false,
+        single_cpu: false,
     };
     let bin = Path::new("diffoscope");
 
     let mut output = Vec::new();
-    proc::run(bin, &args, opts, &mut output).await?;
+    let identical = proc::run(bin, &args, opts, &mut output).await?;
     let output = String::from_utf8_lossy(&output);
 
-    Ok(output.into_owned())
+    Ok((identical, output.into_owned()))
 }
@@ -1,16 +1,81 @@
+use crate::cache::{self, CacheEntry, DownloadCache};
+use data_encoding::HEXLOWER;
 use futures_util::StreamExt;
 use rebuilderd_common::errors::*;
-use rebuilderd_common::http;
+use rebuilderd_common::http::{self, HostLimits, OutboundConfig, StatusCode};
+use sha2::{Digest, Sha256};
+use std::fmt;
 use std::path::{Path, PathBuf};
-use tokio::fs::File;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
 use url::Url;
 
-pub async fn download(url_str: &str, path: &Path) -> Result<PathBuf> {
+/// Homebrew publishes bottles on ghcr.io, which requires a token even for public packages. This is the anonymous token
+/// that brew itself sends.
+const GHCR_ANONYMOUS_TOKEN: &str = "Bearer QQ==";
+/// Longer names are rejected by most filesystems
+const MAX_FILENAME_LEN: usize = 255;
+
+/// Attached as context to errors while fetching the inputs of a build. These say nothing about whether the package is
+/// reproducible, so they are reported as DOWNLOAD_FAILED instead of FAIL.
+#[derive(Debug)]
+pub struct DownloadFailed {
+    pub what: &'static str,
+    pub url: String,
+}
+
+impl fmt::Display for DownloadFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to download {} from {:?}", self.what, self.url)
+    }
+}
+
+/// The inputs of a build are larger than the configured limit. Downloading them again won't help, so unlike
+/// [`DownloadFailed`] this is reported as FAIL.
+#[derive(Debug)]
+pub struct DownloadTooLarge {
+    pub max_bytes: u64,
+}
+
+impl fmt::Display for DownloadTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Inputs of the build exceed the download limit of {} bytes",
+            self.max_bytes
+        )
+    }
+}
+
+impl std::error::Error for DownloadTooLarge {}
+
+/// The filename of an input can't be used in the job directory, or doesn't look like a package of the distribution.
+/// Asking the mirror again won't change the url, so this is reported as FAIL too.
+#[derive(Debug)]
+pub struct RejectedFilename {
+    pub filename: String,
+    pub reason: String,
+}
+
+impl fmt::Display for RejectedFilename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rejected filename {:?}: {}", self.filename, self.reason)
+    }
+}
+
+impl std::error::Error for RejectedFilename {}
+
+/// The last path segment of the url, as the file is named in the job directory
+pub fn filename(url_str: &str) -> Result<String> {
     let url = url_str
         .parse::<Url>()
         .context("Failed to parse input as url")?;
+    filename_of(&url)
+}
 
+fn filename_of(url: &Url) -> Result<String> {
     let filename = url
         .path_segments()
         .ok_or_else(|| format_err!("Url doesn't seem to have a path"))?
@@ -21,28 +86,397 @@ pub async fn download(url_str: &str, path: &Path) -> Result<PathBuf> {
         bail!("Filename detected from url is empty");
     }
 
-    let target = path.join(&filename);
+    let reason = if filename.len() > MAX_FILENAME_LEN {
+        Some(format!("longer than {MAX_FILENAME_LEN} bytes"))
+    } else if filename.starts_with('.') {
+        Some("hidden file or reference to a directory".to_string())
+    } else if filename
+        .chars()
+        .any(|c| c == '/' || c == '\\' || c.is_control())
+    {
+        Some("contains a path separator or control character".to_string())
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        return Err(RejectedFilename { filename, reason }.into());
+    }
 
-    info!("Downloading {:?} to {:?}", url_str, target);
-    let client = http::client()?;
-    let mut stream = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .bytes_stream();
+    Ok(filename)
+}
 
-    let mut f = File::create(&target)
+/// The directory is resolved before the filename is appended, a file that is already there as symlink isn't followed
+async fn target_path(dir: &Path, filename: &str) -> Result<PathBuf> {
+    let dir = tokio::fs::canonicalize(dir)
         .await
-        .context("Failed to create output file")?;
+        .with_context(|| anyhow!("Failed to resolve download directory {dir:?}"))?;
+    let target = dir.join(filename);
+    if target.parent() != Some(dir.as_path()) {
+        return Err(RejectedFilename {
+            filename: filename.to_string(),
+            reason: format!("escapes the download directory {dir:?}"),
+        }
+        .into());
+    }
+
+    if let Ok(metadata) = tokio::fs::symlink_metadata(&target).await
+        && metadata.is_symlink()
+    {
+        return Err(RejectedFilename {
+            filename: filename.to_string(),
+            reason: "a symlink is in place of the file".to_string(),
+        }
+        .into());
+    }
+
+    Ok(target)
+}
+
+/// Fetches the inputs of a build, identifying as configured and taking turns with other downloads from the same host
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    client: http::Client,
+    limits: HostLimits,
+    /// Shared between clones, so all downloads of a build count towards the same limit
+    downloaded: Arc<AtomicU64>,
+    max_bytes: Option<u64>,
+    cache: Option<DownloadCache>,
+}
+
+impl Downloader {
+    pub fn new(outbound: &OutboundConfig) -> Result<Self> {
+        Ok(Downloader {
+            client: http::raw_client(outbound)?,
+            limits: outbound.host_limits(),
+            downloaded: Arc::default(),
+            max_bytes: None,
+            cache: None,
+        })
+    }
+
+    /// Keep downloads in this cache, to fetch only what changed since the last time
+    pub fn cache(mut self, cache: Option<DownloadCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Abort downloads once the inputs of the build exceed this size in total
+    pub fn max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The number of bytes received so far, including downloads that were aborted
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.downloaded.load(Ordering::Relaxed)
+    }
 
-    let mut bytes = 0;
-    while let Some(item) = stream.next().await {
-        let item = item?;
-        f.write_all(&item).await?;
-        bytes += item.len();
+    fn account(&self, bytes: u64) -> Result<()> {
+        let total = self.downloaded.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if let Some(max_bytes) = self.max_bytes
+            && total > max_bytes
+        {
+            return Err(DownloadTooLarge { max_bytes }.into());
+        }
+        Ok(())
     }
-    info!("Downloaded {} bytes", bytes);
 
-    Ok(PathBuf::from(filename))
+    /// Download a file into the given directory. If a sha256 checksum is given, the bytes are verified exactly as they
+    /// were published, the response body is never transparently decompressed.
+    pub async fn download(
+        &self,
+        url_str: &str,
+        path: &Path,
+        checksum: Option<&str>,
+    ) -> Result<PathBuf> {
+        let url = url_str
+            .parse::<Url>()
+            .context("Failed to parse input as url")?;
+
+        let filename = filename_of(&url)?;
+        let target = target_path(path, &filename).await?;
+
+        if let Some(cache) = &self.cache {
+            self.download_cached(cache, &url, &filename, &target, checksum)
+                .await?;
+            return Ok(PathBuf::from(filename));
+        }
+
+        let _permit = self.limits.acquire(&url).await?;
+        info!("Downloading {:?} to {:?}", url_str, target);
+        let response = self.request(&url).send().await?.error_for_status()?;
+        self.check_announced_length(&response)?;
+
+        let content_encoding = content_encoding(&response);
+        let mut stream = response.bytes_stream();
+
+        let mut f = File::create(&target)
+            .await
+            .context("Failed to create output file")?;
+
+        let mut hasher = Sha256::new();
+        let mut bytes = 0;
+        while let Some(item) = stream.next().await {
+            let item = item?;
+            self.account(item.len() as u64)?;
+            f.write_all(&item).await?;
+            hasher.update(&item);
+            bytes += item.len();
+        }
+        f.flush().await?;
+        info!("Downloaded {} bytes", bytes);
+
+        if let Some(expected) = checksum {
+            let actual = HEXLOWER.encode(&hasher.finalize());
+            verify_checksum(&filename, expected, &actual, content_encoding.as_deref())?;
+        }
+
+        Ok(PathBuf::from(filename))
+    }
+
+    fn request(&self, url: &Url) -> http::RequestBuilder {
+        let request = self.client.get(url.clone());
+        if url.host_str() == Some("ghcr.io") {
+            request.header("Authorization", GHCR_ANONYMOUS_TOKEN)
+        } else {
+            request
+        }
+    }
+
+    /// Don't start a download that is announced to be too large, the limit is enforced while streaming either way
+    fn check_announced_length(&self, response: &http::Response) -> Result<()> {
+        if let Some(max_bytes) = self.max_bytes
+            && let Some(length) = response.content_length()
+            && self.downloaded_bytes() + length > max_bytes
+        {
+            return Err(DownloadTooLarge { max_bytes }.into());
+        }
+        Ok(())
+    }
+
+    /// Download through the cache of the low-bandwidth mode. A cached file is reused without asking the mirror if it
+    /// matches the expected checksum, otherwise the mirror is asked whether it changed. Interrupted downloads are
+    /// resumed where they stopped.
+    async fn download_cached(
+        &self,
+        cache: &DownloadCache,
+        url: &Url,
+        filename: &str,
+        target: &Path,
+        checksum: Option<&str>,
+    ) -> Result<()> {
+        let url_str = url.as_str();
+        let _lock = cache.lock(url_str).await;
+        let data_path = cache.data_path(url_str);
+        let entry = cache.lookup(url_str);
+
+        if let Some(entry) = &entry
+            && entry.complete
+            && let (Some(expected), Some(actual)) = (checksum, &entry.sha256)
+            && actual.eq_ignore_ascii_case(expected)
+        {
+            info!("Using cached {filename:?}, it matches the expected checksum");
+            cache.touch(url_str)?;
+            return cache.checkout(url_str, target).await;
+        }
+
+        let _permit = self.limits.acquire(url).await?;
+        let mut request = self.request(url);
+        let mut resume_from = 0;
+        if let Some(entry) = &entry {
+            if entry.complete {
+                if let Some(etag) = &entry.etag {
+                    request = request.header("If-None-Match", etag);
+                }
+                if let Some(last_modified) = &entry.last_modified {
+                    request = request.header("If-Modified-Since", last_modified);
+                }
+            } else if let Some(validator) = entry.validator() {
+                resume_from = tokio::fs::metadata(&data_path)
+                    .await
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+                if resume_from > 0 {
+                    request = request
+                        .header("Range", format!("bytes={resume_from}-"))
+                        .header("If-Range", validator);
+                }
+            }
+        }
+
+        info!("Downloading {:?} to {:?}", url_str, target);
+        let response = request.send().await?.error_for_status()?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let Some(entry) = entry.filter(|entry| entry.complete) else {
+                bail!("Mirror reported {filename:?} as not modified, but it isn't cached");
+            };
+            info!("Using cached {filename:?}, it didn't change on the mirror");
+            cache.touch(url_str)?;
+            cache.checkout(url_str, target).await?;
+            if let (Some(expected), Some(actual)) = (checksum, &entry.sha256) {
+                verify_checksum(filename, expected, actual, None)?;
+            }
+            return Ok(());
+        }
+
+        let resume_from = if response.status() == StatusCode::PARTIAL_CONTENT {
+            let start = response
+                .headers()
+                .get("content-range")
+                .and_then(|value| value.to_str().ok())
+                .and_then(cache::content_range_start);
+            if start != Some(resume_from) {
+                cache.remove(url_str);
+                bail!(
+                    "Mirror sent an unexpected range of {filename:?}, expected bytes from {resume_from}"
+                );
+            }
+            info!("Resuming download of {filename:?} after {resume_from} bytes");
+            resume_from
+        } else {
+            0
+        };
+        self.check_announced_length(&response)?;
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let mut entry = CacheEntry {
+            url: url_str.to_string(),
+            etag: header("etag"),
+            last_modified: header("last-modified"),
+            complete: false,
+            sha256: None,
+        };
+        cache.store(&entry)?;
+
+        let content_encoding = content_encoding(&response);
+        let mut f = if resume_from > 0 {
+            OpenOptions::new().append(true).open(&data_path).await
+        } else {
+            File::create(&data_path).await
+        }
+        .context("Failed to open cached download")?;
+
+        let mut stream = response.bytes_stream();
+        let mut bytes = 0;
+        while let Some(item) = stream.next().await {
+            let item = item?;
+            self.account(item.len() as u64)?;
+            f.write_all(&item).await?;
+            bytes += item.len();
+        }
+        f.flush().await?;
+        info!("Downloaded {bytes} bytes, {resume_from} bytes were already cached");
+
+        let actual = cache::sha256_file(&data_path).await?;
+        entry.complete = true;
+        entry.sha256 = Some(actual.clone());
+        cache.store(&entry)?;
+        cache.checkout(url_str, target).await?;
+
+        if let Some(expected) = checksum
+            && let Err(err) =
+                verify_checksum(filename, expected, &actual, content_encoding.as_deref())
+        {
+            // don't resume from a corrupted file next time
+            cache.remove(url_str);
+            return Err(err);
+        }
+
+        let cache = cache.clone();
+        if let Err(err) = tokio::task::spawn_blocking(move || cache.evict()).await? {
+            warn!("Failed to evict files from download cache: {err:#}");
+        }
+
+        Ok(())
+    }
+}
+
+fn content_encoding(response: &http::Response) -> Option<String> {
+    response
+        .headers()
+        .get("content-encoding")
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+fn verify_checksum(
+    filename: &str,
+    expected: &str,
+    actual: &str,
+    content_encoding: Option<&str>,
+) -> Result<()> {
+    if !actual.eq_ignore_ascii_case(expected) {
+        if let Some(encoding) = content_encoding {
+            bail!(
+                "Checksum mismatch for {filename:?} (served with Content-Encoding: {encoding}), expected sha256 {expected}, got {actual}"
+            );
+        }
+        bail!("Checksum mismatch for {filename:?}, expected sha256 {expected}, got {actual}");
+    }
+    info!("Verified sha256 checksum of {:?}", filename);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_limit_is_shared_between_clones() {
+        let downloader = Downloader::new(&OutboundConfig::default())
+            .unwrap()
+            .max_bytes(Some(100));
+        let other = downloader.clone();
+
+        downloader.account(60).unwrap();
+        let err = other.account(60).unwrap_err();
+        assert!(err.downcast_ref::<DownloadTooLarge>().is_some());
+        assert_eq!(downloader.downloaded_bytes(), 120);
+    }
+
+    #[test]
+    fn test_download_without_limit() {
+        let downloader = Downloader::new(&OutboundConfig::default()).unwrap();
+        downloader.account(u32::MAX as u64).unwrap();
+        assert_eq!(downloader.downloaded_bytes(), u32::MAX as u64);
+    }
+
+    #[test]
+    fn test_filename() {
+        assert_eq!(
+            filename("https://deb.debian.org/debian/pool/main/c/curl/curl_8.5.0-2_amd64.deb")
+                .unwrap(),
+            "curl_8.5.0-2_amd64.deb"
+        );
+        // dot segments are already resolved by the url parser
+        assert_eq!(
+            filename("https://example.com/pool/../../etc/passwd").unwrap(),
+            "passwd"
+        );
+        assert!(filename("https://example.com/pool/").is_err());
+        assert!(filename("https://example.com/pool/%2e%2e").is_err());
+
+        let err = filename("https://example.com/pool/.hidden").unwrap_err();
+        assert!(err.downcast_ref::<RejectedFilename>().is_some());
+        let err = filename(&format!("https://example.com/{}.deb", "a".repeat(256))).unwrap_err();
+        assert!(err.downcast_ref::<RejectedFilename>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_target_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = target_path(dir.path(), "foo.deb").await.unwrap();
+        assert_eq!(target, dir.path().canonicalize().unwrap().join("foo.deb"));
+
+        std::os::unix::fs::symlink("/etc/passwd", dir.path().join("passwd")).unwrap();
+        let err = target_path(dir.path(), "passwd").await.unwrap_err();
+        assert!(err.downcast_ref::<RejectedFilename>().is_some());
+    }
 }
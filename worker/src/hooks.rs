@@ -0,0 +1,185 @@
+use crate::config;
+use crate::proc;
+use rebuilderd_common::errors::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const HOOK_OUTPUT_LIMIT: usize = 1024 * 1024; // 1MB
+
+/// What a post-build hook gets to see, passed as environment variables
+pub struct HookEnv<'a> {
+    pub input: &'a Path,
+    pub inputs_dir: &'a Path,
+    pub out_dir: &'a Path,
+    pub build_log: &'a Path,
+    pub status: &'a str,
+}
+
+impl HookEnv<'_> {
+    fn envs(&self) -> Result<HashMap<String, String>> {
+        let mut envs = HashMap::new();
+        for (key, path) in [
+            ("REBUILDERD_INPUT", self.input),
+            ("REBUILDERD_INPUTS_DIR", self.inputs_dir),
+            ("REBUILDERD_OUTDIR", self.out_dir),
+            ("REBUILDERD_BUILD_LOG", self.build_log),
+        ] {
+            let path = path
+                .to_str()
+                .with_context(|| anyhow!("Path contains invalid characters: {:?}", path))?;
+            envs.insert(key.to_string(), path.to_string());
+        }
+        envs.insert("REBUILDERD_STATUS".to_string(), self.status.to_string());
+        Ok(envs)
+    }
+}
+
+/// Run the configured hooks in order, their output is appended to the build log. Hooks that aren't required only log
+/// a warning if they fail, a failing required hook rejects the build.
+pub async fn run_all(hooks: &[config::Hook], env: &HookEnv<'_>, log: &mut Vec<u8>) -> Result<()> {
+    let envs = env.envs()?;
+
+    for hook in hooks {
+        let opts = proc::Options {
+            timeout: Duration::from_secs(hook.timeout.unwrap_or(600)), // 10min
+            max_timeout: None,
+            size_limit: Some(HOOK_OUTPUT_LIMIT),
+            kill_at_size_limit: false,
+            passthrough: false,
+            envs: envs.clone(),
+            progress: None,
+            isolate_network: false,
+            single_cpu: false,
+        };
+
+        let mut output = Vec::new();
+        let success = proc::run(&hook.path, &hook.args, opts, &mut output)
+            .await
+            .with_context(|| anyhow!("Failed to run post-build hook {:?}", hook.path))?;
+
+        log.extend(format!("\n\nrebuilderd: post-build hook {:?}\n", hook.path).as_bytes());
+        log.extend(&output);
+
+        if success {
+            continue;
+        }
+
+        if hook.required {
+            bail!("Post-build hook {:?} rejected the build", hook.path);
+        } else {
+            warn!("Post-build hook {:?} exited with an error", hook.path);
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)
+                .with_context(|| anyhow!("Failed to copy {:?} to {:?}", path, target))?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep the output and log of a rejected build for inspection, instead of deleting it with the temporary directory
+pub fn quarantine(quarantine_dir: &Path, out_dir: &Path, build_log: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(quarantine_dir)
+        .with_context(|| anyhow!("Failed to create quarantine directory {:?}", quarantine_dir))?;
+    let dir = tempfile::Builder::new()
+        .prefix("rebuilderd-")
+        .tempdir_in(quarantine_dir)?
+        .keep();
+
+    copy_dir(out_dir, &dir.join("out"))?;
+    fs::copy(build_log, dir.join("build.log")).context("Failed to copy build log")?;
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(script: &str, required: bool) -> config::Hook {
+        config::Hook {
+            path: PathBuf::from("sh"),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout: None,
+            required,
+        }
+    }
+
+    async fn run_in_tmp(hooks: &[config::Hook]) -> (Result<()>, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let build_log = dir.path().join("build.log");
+        fs::write(&build_log, "hello world\n").unwrap();
+
+        let env = HookEnv {
+            input: &dir.path().join("input"),
+            inputs_dir: dir.path(),
+            out_dir: dir.path(),
+            build_log: &build_log,
+            status: "GOOD",
+        };
+
+        let mut log = Vec::new();
+        let res = run_all(hooks, &env, &mut log).await;
+        (res, String::from_utf8(log).unwrap())
+    }
+
+    #[tokio::test]
+    async fn hook_sees_build_environment() {
+        let (res, log) = run_in_tmp(&[hook(
+            r#"echo "$REBUILDERD_STATUS"; cat "$REBUILDERD_BUILD_LOG""#,
+            true,
+        )])
+        .await;
+        res.unwrap();
+        assert!(log.ends_with("GOOD\nhello world\n"));
+    }
+
+    #[tokio::test]
+    async fn failing_optional_hook_is_ignored() {
+        let (res, _) = run_in_tmp(&[hook("exit 1", false), hook("echo second", true)]).await;
+        res.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failing_required_hook_rejects_build() {
+        let (res, log) = run_in_tmp(&[hook("echo rejected; exit 1", true)]).await;
+        assert!(res.is_err());
+        assert!(log.ends_with("rejected\n"));
+    }
+
+    #[test]
+    fn quarantine_keeps_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("out");
+        fs::create_dir_all(out_dir.join("sub")).unwrap();
+        fs::write(out_dir.join("sub/foo.pkg"), "foo").unwrap();
+        let build_log = dir.path().join("build.log");
+        fs::write(&build_log, "log").unwrap();
+
+        let quarantined = quarantine(&dir.path().join("quarantine"), &out_dir, &build_log).unwrap();
+        assert!(quarantined.starts_with(dir.path().join("quarantine")));
+        assert_eq!(
+            fs::read_to_string(quarantined.join("out/sub/foo.pkg")).unwrap(),
+            "foo"
+        );
+        assert_eq!(
+            fs::read_to_string(quarantined.join("build.log")).unwrap(),
+            "log"
+        );
+    }
+}
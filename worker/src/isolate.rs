@@ -0,0 +1,78 @@
+//! Run rebuilder scripts without network access.
+//!
+//! The script is started in a new network namespace, which only has a loopback interface that isn't configured. A
+//! worker that isn't running as root also needs a user namespace for this, the current user and group are mapped
+//! into it so files in the build directory keep their owner.
+use nix::fcntl::{OFlag, open};
+use nix::sched::{CloneFlags, unshare};
+use nix::sys::stat::Mode;
+use nix::unistd::{Gid, Uid, write};
+use rebuilderd_common::errors::*;
+use std::ffi::CStr;
+use std::io;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[derive(Debug, Clone)]
+struct NetworkIsolation {
+    privileged: bool,
+    uid_map: String,
+    gid_map: String,
+}
+
+impl NetworkIsolation {
+    fn new() -> Self {
+        let uid = Uid::current();
+        let gid = Gid::current();
+        NetworkIsolation {
+            privileged: uid.is_root(),
+            uid_map: format!("{uid} {uid} 1\n"),
+            gid_map: format!("{gid} {gid} 1\n"),
+        }
+    }
+
+    /// Runs between fork and exec, so this must not allocate
+    fn enter(&self) -> io::Result<()> {
+        if self.privileged {
+            unshare(CloneFlags::CLONE_NEWNET)?;
+        } else {
+            unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNET)?;
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", self.uid_map.as_bytes())?;
+            write_file(c"/proc/self/gid_map", self.gid_map.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+    let fd = open(path, OFlag::O_WRONLY | OFlag::O_CLOEXEC, Mode::empty())?;
+    write(&fd, data)?;
+    Ok(())
+}
+
+/// Start the command in a network namespace without connectivity
+pub fn apply(cmd: &mut Command) {
+    let isolation = NetworkIsolation::new();
+    unsafe {
+        cmd.pre_exec(move || isolation.enter());
+    }
+}
+
+/// Verify namespaces can be created on this machine, eg. unprivileged user namespaces may be disabled
+pub async fn check() -> Result<()> {
+    let mut cmd = Command::new("true");
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    apply(&mut cmd);
+
+    let status = cmd
+        .status()
+        .await
+        .context("Failed to start a process without network access")?;
+    if !status.success() {
+        bail!("Process without network access exited with {status}");
+    }
+    Ok(())
+}
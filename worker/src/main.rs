@@ -1,7 +1,14 @@
 #![recursion_limit = "256"]
 
 use crate::args::{Args, SubCommand};
+use crate::cache::DownloadCache;
+use crate::download::{DownloadFailed, DownloadTooLarge, Downloader, RejectedFilename};
+use crate::heartbeat::HeartBeat;
+use crate::metrics::{Metrics, Pusher};
+use crate::progress::Progress;
 use crate::rebuild::Context;
+use crate::reload::ReloadRequest;
+use crate::spool::Spool;
 use async_trait::async_trait;
 use chrono::Utc;
 use clap::Parser;
@@ -9,33 +16,56 @@ use env_logger::Env;
 use in_toto::crypto::PrivateKey;
 use rebuilderd_common::api::Client;
 use rebuilderd_common::api::v1::{
-    ArtifactStatus, BuildRestApi, BuildStatus, JobAssignment, PopQueuedJobRequest, QueueRestApi,
-    QueuedJobArtifact, RebuildReport, RegisterWorkerRequest, WorkerRestApi,
+    ArtifactStatus, BuildRestApi, BuildStatus, JobAborted, JobAssignment, JobPayload,
+    PingJobRequest, PingJobResponse, PopQueuedJobRequest, QueueRestApi, QueuedJobArtifact,
+    QueuedJobWithArtifacts, RebuildArtifactReport, RebuildReport, RegisterWorkerRequest,
+    WorkerRestApi,
 };
 use rebuilderd_common::auth::find_auth_cookie;
 use rebuilderd_common::config::*;
 use rebuilderd_common::errors::Context as _;
 use rebuilderd_common::errors::*;
-use rebuilderd_common::utils::zstd_compress;
+use rebuilderd_common::utils::{zstd_compress, zstd_decompress};
 use std::fs;
 use std::io::{self, Write};
-use std::path::Path;
-use std::time::Duration;
-use tokio::time;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::{select, time};
 
 pub mod args;
 pub mod auth;
+pub mod builddir;
+pub mod cache;
+pub mod compare;
+pub mod compression;
 pub mod config;
 pub mod diffoscope;
 pub mod download;
 pub mod heartbeat;
+pub mod hooks;
+pub mod isolate;
+pub mod metrics;
+pub mod normalize;
 pub mod proc;
+pub mod progress;
 pub mod rebuild;
+pub mod reload;
+pub mod selftest;
 pub mod setup;
+pub mod spool;
+pub mod summary;
+
+/// Warn if the local clock differs from the one of the daemon by more than this many seconds
+const CLOCK_SKEW_WARNING: i64 = 30;
 
 pub struct HttpHeartBeat<'a> {
     client: &'a Client,
     queue_id: i32,
+    /// Set once the daemon reported the worker as drained, so it's only logged once per job
+    draining: AtomicBool,
+    progress: Progress,
 }
 
 #[async_trait]
@@ -45,124 +75,542 @@ impl heartbeat::HeartBeat for HttpHeartBeat<'_> {
     }
 
     async fn ping(&self) -> Result<()> {
-        if let Err(err) = self.client.ping_job(self.queue_id).await {
-            warn!("Failed to ping: {}", err);
+        let request = PingJobRequest {
+            phase: self.progress.get(),
+            extend: self.progress.take_extension(),
+        };
+        match self
+            .client
+            .ping_job_with_phase(self.queue_id, request)
+            .await
+        {
+            Ok(PingJobResponse { draining }) => {
+                if draining && !self.draining.swap(true, Ordering::Relaxed) {
+                    info!(
+                        "Worker was drained, finishing the current job but not accepting new ones"
+                    );
+                }
+                Ok(())
+            }
+            // stops the build, dropping it kills the running process
+            Err(err) if err.downcast_ref::<JobAborted>().is_some() => Err(err),
+            Err(err) => {
+                warn!("Failed to ping: {}", err);
+                Ok(())
+            }
         }
-        Ok(())
     }
 }
 
-async fn rebuild(client: &Client, privkey: &PrivateKey, config: &config::ConfigFile) -> Result<()> {
-    info!("Requesting work from rebuilderd...");
-    let supported_backends = config.backends.keys().map(String::from).collect::<Vec<_>>();
+fn pop_request(config: &config::ConfigFile) -> PopQueuedJobRequest {
+    PopQueuedJobRequest {
+        supported_backends: config.backends.keys().map(String::from).collect(),
+        architecture: std::env::consts::ARCH.to_string(),
+        supported_architectures: config.supported_architectures(),
+        mirrors: config.mirrors.clone(),
+        cross_architectures: config.cross_architectures.clone(),
+    }
+}
 
-    // default to our native architecture if the user hasn't specified any explicit architectures
-    let supported_architectures = if config.supported_architectures.is_empty() {
-        vec![std::env::consts::ARCH.to_string()]
-    } else {
-        config.supported_architectures.clone()
+/// The timestamps that are reported are taken from the daemon, but the build itself still runs with the local clock
+fn check_clock_skew(client: &Client) {
+    let Some(skew) = client.server_clock().skew() else {
+        return;
     };
+    if skew.num_seconds().abs() > CLOCK_SKEW_WARNING {
+        let direction = if skew.num_seconds() > 0 {
+            "ahead of"
+        } else {
+            "behind"
+        };
+        warn!(
+            "Local clock is {}s {direction} the clock of rebuilderd, check the time synchronization of this system",
+            skew.num_seconds().abs()
+        );
+    }
+}
+
+/// A job that was claimed while the previous build was still running
+struct Claimed {
+    job: Box<QueuedJobWithArtifacts>,
+    inputs: Option<rebuild::Inputs>,
+    /// Received while prefetching, even if the inputs had to be discarded
+    prefetched_bytes: u64,
+}
 
-    match client
-        .request_work(PopQueuedJobRequest {
-            supported_backends,
-            architecture: std::env::consts::ARCH.to_string(),
-            supported_architectures,
-        })
-        .await?
-    {
-        JobAssignment::Nothing => {
-            let idle_delay = config.idle_delay.unwrap_or(IDLE_DELAY);
-            info!("No pending tasks, sleeping for {}s...", idle_delay);
-            time::sleep(Duration::from_secs(idle_delay)).await;
+/// Downloads through the local cache, if one is configured
+fn downloader(config: &config::ConfigFile) -> Result<Downloader> {
+    let cache = DownloadCache::from_config(&config.download)?;
+    Ok(Downloader::new(&config.outbound)?.cache(cache))
+}
+
+/// Claim the next job and download its inputs. If they don't fit into the prefetch budget, the job is kept anyway
+/// and its inputs are downloaded once the build starts.
+async fn prefetch_next_job(client: &Client, config: &config::ConfigFile) -> Option<Claimed> {
+    let job = match client.request_work(pop_request(config)).await {
+        Ok(JobAssignment::Rebuild(job)) => job,
+        Ok(JobAssignment::Nothing) => return None,
+        Err(err) => {
+            warn!("Failed to request next job for prefetching: {err:#}");
+            return None;
         }
-        JobAssignment::Rebuild(rb) => {
-            info!("Starting rebuild of {:?} {:?}", rb.job.name, rb.job.version);
-
-            let backend = config
-                .backends
-                .get(&rb.job.distribution)
-                .cloned()
-                .ok_or_else(|| anyhow!("No backend for {:?} configured", rb.job.distribution))?;
-
-            let ctx = Context {
-                artifacts: rb.artifacts.clone(),
-                input_url: Some(rb.job.url.clone()),
-                backend,
-                build: config.build.clone(),
-                diffoscope: config.diffoscope.clone(),
-                privkey,
-            };
+    };
+    info!(
+        "Prefetching inputs of next job {:?} {:?}",
+        job.job.name, job.job.version
+    );
+
+    let progress = Progress::default();
+    progress.set("fetch");
+    let hb = HttpHeartBeat {
+        client,
+        queue_id: job.job.id,
+        progress,
+        draining: AtomicBool::new(false),
+    };
 
-            let hb = HttpHeartBeat {
-                client,
-                queue_id: rb.job.id,
-            };
+    let budget = config
+        .build
+        .prefetch_max_bytes
+        .or(config.build.max_download_bytes);
+    let downloader = match downloader(config) {
+        Ok(downloader) => downloader.max_bytes(budget),
+        Err(err) => {
+            warn!("Failed to setup downloader for prefetching: {err:#}");
+            return Some(Claimed {
+                job,
+                inputs: None,
+                prefetched_bytes: 0,
+            });
+        }
+    };
+
+    // the patterns were checked when the config was loaded
+    let artifact_filename = config
+        .backends
+        .get(&job.job.distribution)
+        .and_then(|backend| backend.artifact_filename().ok().flatten());
+
+    let inputs = {
+        let payload = job.input_payload();
+        let mut fetch = pin!(rebuild::fetch_inputs(
+            &config.build,
+            Some(job.job.id),
+            &job.artifacts,
+            Some(&payload),
+            artifact_filename.as_ref(),
+            &downloader
+        ));
+        loop {
+            select! {
+                res = &mut fetch => break res,
+                _ = time::sleep(hb.interval()) => {
+                    let _ = hb.ping().await;
+                }
+            }
+        }
+    };
 
-            let mut log = Vec::new();
+    let inputs = match inputs {
+        Ok(inputs) => Some(inputs),
+        Err(err) => {
+            warn!("Failed to prefetch inputs, fetching them again once the build starts: {err:#}");
+            None
+        }
+    };
 
-            let (overall_status, rebuilds) =
-                match rebuild::rebuild_with_heartbeat(&ctx, &mut log, &hb).await {
-                    Ok(res) => {
-                        let overall_status = if res.iter().all(|r| r.status == ArtifactStatus::Good)
-                        {
-                            BuildStatus::Good
-                        } else {
-                            BuildStatus::Bad
-                        };
+    Some(Claimed {
+        job,
+        inputs,
+        prefetched_bytes: downloader.downloaded_bytes(),
+    })
+}
 
-                        (overall_status, res)
-                    }
+/// Run the build while the next job is claimed and its inputs are downloaded. The claim is kept alive until the build
+/// is done, if the daemon didn't hear from us within the ping deadline the job may have been handed to another worker
+/// and is dropped.
+async fn build_with_prefetch<F>(
+    client: &Client,
+    config: &config::ConfigFile,
+    build: F,
+) -> (Result<Vec<RebuildArtifactReport>>, Option<Claimed>)
+where
+    F: Future<Output = Result<Vec<RebuildArtifactReport>>>,
+{
+    let mut build = pin!(build);
+    let mut prefetch = pin!(prefetch_next_job(client, config));
+    let mut prefetch_done = false;
+    let mut next = None::<Claimed>;
+    let mut last_ping = Instant::now();
+
+    let res = loop {
+        select! {
+            res = &mut build => break res,
+            claimed = &mut prefetch, if !prefetch_done => {
+                prefetch_done = true;
+                next = claimed;
+                last_ping = Instant::now();
+            }
+            _ = time::sleep(Duration::from_secs(PING_INTERVAL)), if next.is_some() => {
+                let id = next.as_ref().map(|claimed| claimed.job.job.id).unwrap_or_default();
+                let request = PingJobRequest {
+                    phase: Some("waiting".to_string()),
+                    extend: None,
+                };
+                match client.ping_job_with_phase(id, request).await {
+                    Ok(_) => last_ping = Instant::now(),
                     Err(err) => {
-                        error!(
-                            "Unexpected error while rebuilding package package: {:#}",
-                            err
-                        );
-
-                        let msg = format!(
-                            "rebuilderd: unexpected error while rebuilding package: {:#}\n",
-                            err
-                        );
-
-                        if !log.is_empty() {
-                            log.extend(b"\n\n");
+                        warn!("Failed to ping prefetched job: {err:#}");
+                        if last_ping.elapsed() > Duration::from_secs(PING_DEADLINE as u64) {
+                            warn!("Lost the claim on the prefetched job, discarding it");
+                            next = None;
                         }
-
-                        log.extend(msg.as_bytes());
-                        (BuildStatus::Fail, vec![]) // TODO: good or bad idea? no artifact results from failed builds
                     }
-                };
+                }
+            }
+        }
+    };
+
+    if !prefetch_done {
+        next = prefetch.await;
+    }
+
+    (res, next)
+}
 
-            let utf8_sanitized_log = String::from_utf8_lossy(&log).into_owned();
-            let encoded_log = zstd_compress(utf8_sanitized_log.as_bytes())
-                .await
-                .map_err(Error::from)?;
-
-            let report = RebuildReport {
-                queue_id: rb.job.id,
-                built_at: Utc::now().naive_utc(),
-                build_log: encoded_log,
-                status: overall_status,
-                artifacts: rebuilds,
+async fn rebuild(
+    client: &Client,
+    privkey: &PrivateKey,
+    config: &config::ConfigFile,
+    spool: Option<&Spool>,
+    next: &mut Option<Claimed>,
+    prefetch: bool,
+    metrics: &mut Metrics,
+) -> Result<()> {
+    // results of earlier builds are delivered before we take on more work
+    if let Some(spool) = spool {
+        spool.replay(client).await?;
+    }
+
+    let claimed = if let Some(claimed) = next.take() {
+        claimed
+    } else {
+        info!("Requesting work from rebuilderd...");
+        let assignment = client.request_work(pop_request(config)).await?;
+        check_clock_skew(client);
+        match assignment {
+            JobAssignment::Nothing => {
+                let idle_delay = config.idle_delay.unwrap_or(IDLE_DELAY);
+                info!("No pending tasks, sleeping for {}s...", idle_delay);
+                time::sleep(Duration::from_secs(idle_delay)).await;
+                return Ok(());
+            }
+            JobAssignment::Rebuild(job) => Claimed {
+                job,
+                inputs: None,
+                prefetched_bytes: 0,
+            },
+        }
+    };
+
+    let rb = claimed.job;
+    info!("Starting rebuild of {:?} {:?}", rb.job.name, rb.job.version);
+
+    let backend = config
+        .backends
+        .get(&rb.job.distribution)
+        .cloned()
+        .ok_or_else(|| anyhow!("No backend for {:?} configured", rb.job.distribution))?;
+
+    let ctx = Context {
+        job_id: Some(rb.job.id),
+        artifacts: rb.artifacts.clone(),
+        payload: Some(rb.input_payload()),
+        cross_target: rb
+            .job
+            .build_architecture
+            .as_ref()
+            .map(|_| rb.job.architecture.clone()),
+        variation: rb.job.variation,
+        upload_artifacts: rb.upload_artifacts,
+        backend,
+        build: config.build.clone(),
+        diffoscope: config.diffoscope.clone(),
+        hooks: config.hooks.clone(),
+        privkey,
+        progress: Progress::default(),
+        downloader: downloader(config)?.max_bytes(config.build.max_download_bytes),
+    };
+
+    let hb = HttpHeartBeat {
+        client,
+        queue_id: rb.job.id,
+        progress: ctx.progress.clone(),
+        draining: AtomicBool::new(false),
+    };
+
+    let mut log = Vec::new();
+
+    let started = Instant::now();
+    let build = rebuild::rebuild_with_heartbeat(&ctx, claimed.inputs, &mut log, &hb);
+    let res = if prefetch {
+        let (res, prefetched) = build_with_prefetch(client, config, build).await;
+        *next = prefetched;
+        res
+    } else {
+        build.await
+    };
+
+    let (overall_status, rebuilds) = match res {
+        Err(err) if err.downcast_ref::<JobAborted>().is_some() => {
+            warn!("{err:#}, discarding the build");
+            return Ok(());
+        }
+        Ok(res) => {
+            let overall_status = if res.iter().all(|r| r.status == ArtifactStatus::Good) {
+                BuildStatus::Good
+            } else {
+                BuildStatus::Bad
             };
 
-            info!("Sending build report to rebuilderd...");
-            client
-                .submit_build_report(report)
-                .await
-                .context("Failed to report build to rebuilderd")?;
+            (overall_status, res)
+        }
+        Err(err) if err.downcast_ref::<RejectedFilename>().is_some() => {
+            error!("Refusing to build package: {:#}", err);
+
+            let msg = format!("rebuilderd: {:#}\n", err);
+
+            if !log.is_empty() {
+                log.extend(b"\n\n");
+            }
+
+            log.extend(msg.as_bytes());
+            (BuildStatus::Fail, vec![])
+        }
+        Err(err) if err.downcast_ref::<DownloadTooLarge>().is_some() => {
+            error!("Inputs of package are too large: {:#}", err);
+
+            let msg = format!("rebuilderd: {:#}\n", err);
+
+            if !log.is_empty() {
+                log.extend(b"\n\n");
+            }
+
+            log.extend(msg.as_bytes());
+            (BuildStatus::Fail, vec![])
+        }
+        Err(err) if err.downcast_ref::<DownloadFailed>().is_some() => {
+            error!("Failed to download inputs of package: {:#}", err);
+
+            let msg = format!("rebuilderd: failed to download inputs: {:#}\n", err);
+
+            if !log.is_empty() {
+                log.extend(b"\n\n");
+            }
+
+            log.extend(msg.as_bytes());
+            (BuildStatus::DownloadFailed, vec![])
         }
+        Err(err) => {
+            error!(
+                "Unexpected error while rebuilding package package: {:#}",
+                err
+            );
+
+            let msg = format!(
+                "rebuilderd: unexpected error while rebuilding package: {:#}\n",
+                err
+            );
+
+            if !log.is_empty() {
+                log.extend(b"\n\n");
+            }
+
+            log.extend(msg.as_bytes());
+            (BuildStatus::Fail, vec![]) // TODO: good or bad idea? no artifact results from failed builds
+        }
+    };
+
+    let downloaded_bytes = ctx.downloader.downloaded_bytes() + claimed.prefetched_bytes;
+    metrics.record_build(
+        &rb.job.distribution,
+        &overall_status,
+        started.elapsed(),
+        downloaded_bytes,
+    );
+
+    let utf8_sanitized_log = String::from_utf8_lossy(&log).into_owned();
+    let encoded_log = zstd_compress(utf8_sanitized_log.as_bytes())
+        .await
+        .map_err(Error::from)?;
+
+    let report = RebuildReport {
+        queue_id: rb.job.id,
+        // the daemon compares this with when the job was started, both have to come from its clock
+        built_at: client
+            .server_clock()
+            .now()
+            .unwrap_or_else(Utc::now)
+            .naive_utc(),
+        build_log: encoded_log,
+        status: overall_status,
+        artifacts: rebuilds,
+        downloaded_bytes: Some(downloaded_bytes),
+    };
+
+    if let Some(spool) = spool {
+        let path = spool.store(&report).await?;
+        debug!("Stored build report in spool: {path:?}");
+        if let Err(err) = spool.replay(client).await {
+            warn!("{err:#}");
+        }
+        return Ok(());
     }
+
+    info!("Sending build report to rebuilderd...");
+    client
+        .submit_build_report(report)
+        .await
+        .context("Failed to report build to rebuilderd")?;
+    Ok(())
+}
+
+/// Connects to the daemon on startup, and again with the new config after a reload
+struct Connector {
+    name: String,
+    /// Given on the command line, takes precedence over the configured endpoint
+    endpoint: Option<String>,
+    skip_selftest: bool,
+    profile: auth::Profile,
+    cookie: Option<String>,
+    config_path: Option<PathBuf>,
+    backends: Vec<String>,
+}
+
+impl Connector {
+    fn endpoint(&self, config: &config::ConfigFile) -> Result<String> {
+        self.endpoint
+            .clone()
+            .or_else(|| config.endpoint.clone())
+            .ok_or_else(|| format_err!("No endpoint configured"))
+    }
+
+    fn load(&self) -> Result<config::ConfigFile> {
+        config::load_from(self.config_path.as_deref(), &self.backends)
+            .context("Failed to load config file")
+    }
+
+    /// Registering again also updates the environment fingerprint, eg. after a backend was added
+    async fn connect(&self, config: &config::ConfigFile) -> Result<Client> {
+        let system_config = rebuilderd_common::config::load(None::<String>)
+            .context("Failed to load system config")?;
+        let endpoint = self.endpoint(config)?;
+
+        builddir::prepare(&config.build).context("Failed to prepare build directory")?;
+
+        let environment = match selftest::run(config).await {
+            Ok(environment) => Some(environment),
+            Err(err) if self.skip_selftest => {
+                warn!("Ignoring failed self-test: {err:#}");
+                None
+            }
+            Err(err) => return Err(err),
+        };
+
+        let client = self.profile.new_client(
+            system_config,
+            endpoint,
+            &config.tls,
+            config.signup_secret.clone(),
+            self.cookie.clone(),
+        )?;
+
+        client
+            .register_worker(RegisterWorkerRequest {
+                name: self.name.clone(),
+                environment,
+                uuid: Some(self.profile.uuid.clone()),
+            })
+            .await
+            .context("Failed to register worker with rebuilderd daemon")?;
+
+        Ok(client)
+    }
+}
+
+/// Switch to a reloaded config between builds. A job that was already claimed from the previous endpoint is built and
+/// reported there first. If the new config doesn't work, the worker keeps going with the previous one.
+async fn reload_config(
+    connector: &Connector,
+    config: &mut config::ConfigFile,
+    client: &mut Client,
+    next: &Option<Claimed>,
+    reload: &ReloadRequest,
+) -> Result<()> {
+    info!("Reloading config");
+    let new_config = connector.load()?;
+
+    if next.is_some() && connector.endpoint(&new_config)? != connector.endpoint(config)? {
+        info!("Endpoint changed, reloading after the already claimed job is done");
+        reload.postpone();
+        return Ok(());
+    }
+
+    *client = connector.connect(&new_config).await?;
+    *config = new_config;
+    info!("Reloaded config");
     Ok(())
 }
 
 async fn run_worker_loop(
-    client: &Client,
-    privkey: &PrivateKey,
-    config: &config::ConfigFile,
+    connector: &Connector,
+    mut config: config::ConfigFile,
+    mut client: Client,
+    reload: &ReloadRequest,
 ) -> Result<()> {
+    let mut next = None;
+    let mut metrics = Metrics::default();
+    let mut pusher = Pusher::from_config(&config.metrics, &config.outbound, &connector.name)?;
     loop {
-        if let Err(err) = rebuild(client, privkey, config).await {
+        if reload.take() {
+            if let Err(err) =
+                reload_config(connector, &mut config, &mut client, &next, reload).await
+            {
+                error!("Failed to reload config, keeping the previous one: {err:#}");
+            }
+            match Pusher::from_config(&config.metrics, &config.outbound, &connector.name) {
+                Ok(new_pusher) => pusher = new_pusher,
+                Err(err) => {
+                    error!("Failed to setup pushing metrics, keeping the previous setup: {err:#}")
+                }
+            }
+        }
+
+        // a pending reload may switch to another endpoint, don't claim more work from this one in the meantime
+        let prefetch = config.build.prefetch_next_job && !reload.is_pending();
+        let spool = config.spool_dir.clone().map(Spool::new);
+        let res = rebuild(
+            &client,
+            &connector.profile.privkey,
+            &config,
+            spool.as_ref(),
+            &mut next,
+            prefetch,
+            &mut metrics,
+        )
+        .await;
+        if res.is_err() {
+            metrics.record_error();
+        }
+
+        if let Some(pusher) = &pusher
+            && metrics.take_changed()
+            && let Err(err) = pusher.push(&metrics).await
+        {
+            warn!("{err:#}");
+        }
+
+        if let Err(err) = res {
             error!(
                 "Unexpected error, sleeping for {}s: {:#}",
                 API_ERROR_DELAY, err
@@ -183,6 +631,102 @@ async fn run_worker_loop(
     }
 }
 
+/// The filename of an artifact url, used to name the artifact in one-shot builds
+fn artifact_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() => name,
+        _ => "anonymous",
+    }
+}
+
+/// Rebuild a single artifact without a daemon, this is mostly useful while developing rebuilder scripts
+async fn build_locally(
+    build: args::Build,
+    config: config::ConfigFile,
+    privkey: &PrivateKey,
+) -> Result<()> {
+    let local = build.local.is_some();
+    let artifact_url = build
+        .local
+        .or(build.artifact_url)
+        .context("No artifact url given")?;
+
+    let backend = if let Some(script_location) = build.script_location {
+        config::Backend {
+            path: script_location,
+            ..Default::default()
+        }
+    } else {
+        config.select_backend(build.distro.as_deref())?.clone()
+    };
+
+    let downloader = downloader(&config)?.max_bytes(config.build.max_download_bytes);
+
+    // the diff is the most useful output while iterating on a script, the configured diffoscope settings still apply
+    let diffoscope = config::Diffoscope {
+        enabled: build.gen_diffoscope || local,
+        ..config.diffoscope
+    };
+
+    let mut log = Vec::new();
+
+    let res = rebuild::rebuild(
+        &Context {
+            job_id: None,
+            artifacts: vec![QueuedJobArtifact {
+                name: artifact_name(&artifact_url).to_string(),
+                version: "0.0.0".to_string(),
+                architecture: "amd64".to_string(),
+                url: artifact_url,
+                checksum: build.checksum,
+            }],
+            payload: build.input_url.map(|url| JobPayload::Generic { url }),
+            cross_target: None,
+            variation: None,
+            upload_artifacts: false,
+            backend,
+            build: config.build,
+            diffoscope,
+            hooks: config.hooks,
+            privkey,
+            progress: Progress::default(),
+            downloader,
+        },
+        None,
+        &mut log,
+    )
+    .await?;
+
+    let mut stdout = io::stdout();
+    let mut reproduced = true;
+    for res in res {
+        trace!("rebuild result object {:?}", res);
+
+        if res.status == ArtifactStatus::Good {
+            info!("Package verified successfully");
+        } else {
+            error!("Package failed to verify");
+            reproduced = false;
+        }
+
+        if local {
+            writeln!(stdout, "{} {}", res.status.as_str(), res.name)?;
+        }
+
+        if let Some(diffoscope) = res.diffoscope {
+            let diffoscope = zstd_decompress(&diffoscope).await?;
+            stdout.write_all(&diffoscope)?;
+        }
+    }
+
+    if local && !reproduced {
+        bail!("Artifact did not reproduce");
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -205,88 +749,27 @@ async fn main() -> Result<()> {
     if let Some(name) = &args.name {
         setup::run(name).context("Failed to setup worker")?;
     }
-    let profile = auth::load()?;
+    let profile = auth::load(config.identity_file()).context("Failed to load worker identity")?;
 
     match args.subcommand {
         SubCommand::Connect(connect) => {
-            let system_config = rebuilderd_common::config::load(None::<String>)
-                .context("Failed to load system config")?;
-            let endpoint = if let Some(endpoint) = connect.endpoint {
-                endpoint
-            } else {
-                config
-                    .endpoint
-                    .clone()
-                    .ok_or_else(|| format_err!("No endpoint configured"))?
-            };
-
-            let client = profile.new_client(
-                system_config,
-                endpoint,
-                config.signup_secret.clone(),
+            // listen before connecting, a SIGHUP must not terminate the worker once it runs builds
+            let reload = ReloadRequest::listen()?;
+            let connector = Connector {
+                name: args.name.unwrap_or("worker".to_string()),
+                endpoint: connect.endpoint,
+                skip_selftest: connect.skip_selftest,
+                profile,
                 cookie,
-            )?;
-
-            client
-                .register_worker(RegisterWorkerRequest {
-                    name: args.name.unwrap_or("worker".to_string()),
-                })
-                .await
-                .context("Failed to register worker with rebuilderd daemon")?;
-
-            run_worker_loop(&client, &profile.privkey, &config).await?;
+                config_path: args.config,
+                backends: args.backends,
+            };
+            let client = connector.connect(&config).await?;
+            run_worker_loop(&connector, config, client, &reload).await?;
         }
-        // this is only really for debugging
         SubCommand::Build(build) => {
-            let backend = if let Some(script_location) = build.script_location {
-                config::Backend {
-                    path: script_location,
-                }
-            } else {
-                config
-                    .backends
-                    .get(&build.distro)
-                    .cloned()
-                    .ok_or_else(|| anyhow!("No backend configured in config file"))?
-            };
-
-            let diffoscope = config::Diffoscope {
-                enabled: build.gen_diffoscope,
-                ..Default::default()
-            };
-
-            let mut log = Vec::new();
-
-            let res = rebuild::rebuild(
-                &Context {
-                    artifacts: vec![QueuedJobArtifact {
-                        name: "anonymous".to_string(),
-                        version: "0.0.0".to_string(),
-                        architecture: "amd64".to_string(),
-                        url: build.artifact_url,
-                    }],
-                    input_url: build.input_url,
-                    backend,
-                    build: config.build,
-                    diffoscope,
-                    privkey: &profile.privkey,
-                },
-                &mut log,
-            )
-            .await?;
-
-            for res in res {
-                trace!("rebuild result object {:?}", res);
-
-                if res.status == ArtifactStatus::Good {
-                    info!("Package verified successfully");
-                } else {
-                    error!("Package failed to verify");
-                    if let Some(diffoscope) = res.diffoscope {
-                        io::stdout().write_all(&diffoscope).ok();
-                    }
-                }
-            }
+            builddir::prepare(&config.build).context("Failed to prepare build directory")?;
+            build_locally(build, config, &profile.privkey).await?;
         }
         SubCommand::Diffoscope(diffoscope) => {
             let output =
@@ -297,6 +780,11 @@ async fn main() -> Result<()> {
             let json = serde_json::to_string_pretty(&config)?;
             println!("{}", json);
         }
+        SubCommand::SelfTest => {
+            let environment = selftest::run(&config).await?;
+            let json = serde_json::to_string_pretty(&environment)?;
+            println!("{}", json);
+        }
     }
 
     Ok(())
@@ -0,0 +1,290 @@
+use crate::config;
+use chrono::{DateTime, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use rebuilderd_common::api::v1::BuildStatus;
+use rebuilderd_common::errors::*;
+use rebuilderd_common::http::{self, OutboundConfig};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+use url::Url;
+
+const DEFAULT_JOB: &str = "rebuilderd-worker";
+
+/// Totals since the worker started. The pushgateway keeps the last values until the next push, prometheus notices
+/// counters that start over after a restart like with any other target.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Keyed by distribution and status
+    builds: BTreeMap<(String, String), u64>,
+    /// The sum of the durations and the number of builds, keyed by distribution
+    durations: BTreeMap<String, (f64, u64)>,
+    downloaded_bytes: u64,
+    errors: u64,
+    last_build: Option<DateTime<Utc>>,
+    changed: bool,
+}
+
+impl Metrics {
+    pub fn record_build(
+        &mut self,
+        distribution: &str,
+        status: &BuildStatus,
+        duration: Duration,
+        downloaded_bytes: u64,
+    ) {
+        *self
+            .builds
+            .entry((distribution.to_string(), status.as_str().to_string()))
+            .or_default() += 1;
+        let (sum, count) = self.durations.entry(distribution.to_string()).or_default();
+        *sum += duration.as_secs_f64();
+        *count += 1;
+        self.downloaded_bytes += downloaded_bytes;
+        self.last_build = Some(Utc::now());
+        self.changed = true;
+    }
+
+    /// The worker couldn't talk to rebuilderd or deliver a build report
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+        self.changed = true;
+    }
+
+    /// Whether anything was recorded since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// The prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "rebuilderd_worker_builds_total",
+            "counter",
+            "Builds finished by this worker",
+        );
+        for ((distribution, status), count) in &self.builds {
+            let _ = writeln!(
+                out,
+                "rebuilderd_worker_builds_total{{distribution=\"{}\",status=\"{}\"}} {count}",
+                escape(distribution),
+                escape(status),
+            );
+        }
+
+        header(
+            &mut out,
+            "rebuilderd_worker_build_duration_seconds",
+            "summary",
+            "Time spent on builds, including the download of their inputs",
+        );
+        for (distribution, (sum, count)) in &self.durations {
+            let distribution = escape(distribution);
+            let _ = writeln!(
+                out,
+                "rebuilderd_worker_build_duration_seconds_sum{{distribution=\"{distribution}\"}} {sum}"
+            );
+            let _ = writeln!(
+                out,
+                "rebuilderd_worker_build_duration_seconds_count{{distribution=\"{distribution}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "rebuilderd_worker_downloaded_bytes_total",
+            "counter",
+            "Bytes downloaded for builds",
+        );
+        let _ = writeln!(
+            out,
+            "rebuilderd_worker_downloaded_bytes_total {}",
+            self.downloaded_bytes
+        );
+
+        header(
+            &mut out,
+            "rebuilderd_worker_errors_total",
+            "counter",
+            "Failures to talk to rebuilderd or to deliver a build report",
+        );
+        let _ = writeln!(out, "rebuilderd_worker_errors_total {}", self.errors);
+
+        if let Some(last_build) = self.last_build {
+            header(
+                &mut out,
+                "rebuilderd_worker_last_build_timestamp_seconds",
+                "gauge",
+                "When the last build finished",
+            );
+            let _ = writeln!(
+                out,
+                "rebuilderd_worker_last_build_timestamp_seconds {}",
+                last_build.timestamp()
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Label values are base64 encoded in the url, so they may contain slashes
+fn push_label(url: &mut Url, name: &str, value: &str) -> Result<()> {
+    let value = if value.is_empty() {
+        "=".to_string()
+    } else {
+        BASE64URL_NOPAD.encode(value.as_bytes())
+    };
+    url.path_segments_mut()
+        .map_err(|_| format_err!("Pushgateway url can't be a base"))?
+        .pop_if_empty()
+        .push(&format!("{name}@base64"))
+        .push(&value);
+    Ok(())
+}
+
+/// Sends the metrics to a prometheus pushgateway, for workers behind NAT that can't be scraped
+pub struct Pusher {
+    client: http::Client,
+    url: Url,
+}
+
+impl Pusher {
+    /// `None` if no pushgateway is configured. The worker name is used as `instance` label, unless one is configured.
+    pub fn from_config(
+        config: &config::Metrics,
+        outbound: &OutboundConfig,
+        worker_name: &str,
+    ) -> Result<Option<Self>> {
+        let Some(pushgateway) = &config.pushgateway else {
+            return Ok(None);
+        };
+
+        let mut url = pushgateway
+            .parse::<Url>()
+            .with_context(|| anyhow!("Invalid pushgateway url: {pushgateway:?}"))?;
+        url.path_segments_mut()
+            .map_err(|_| format_err!("Pushgateway url can't be a base"))?
+            .pop_if_empty()
+            .push("metrics");
+        push_label(
+            &mut url,
+            "job",
+            config.job.as_deref().unwrap_or(DEFAULT_JOB),
+        )?;
+        if !config.labels.contains_key("instance") {
+            push_label(&mut url, "instance", worker_name)?;
+        }
+        for (name, value) in &config.labels {
+            push_label(&mut url, name, value)?;
+        }
+
+        let client = http::client(outbound)?;
+        Ok(Some(Pusher { client, url }))
+    }
+
+    /// Replaces all metrics of this worker on the pushgateway
+    pub async fn push(&self, metrics: &Metrics) -> Result<()> {
+        let res = self
+            .client
+            .put(self.url.clone())
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(metrics.render())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        res.with_context(|| anyhow!("Failed to push metrics to {:?}", self.url.as_str()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+        assert!(!metrics.take_changed());
+
+        metrics.record_build(
+            "archlinux",
+            &BuildStatus::Good,
+            Duration::from_secs(60),
+            100,
+        );
+        metrics.record_build("archlinux", &BuildStatus::Good, Duration::from_secs(30), 50);
+        metrics.record_build(
+            "debian",
+            &BuildStatus::DownloadFailed,
+            Duration::from_secs(1),
+            0,
+        );
+        metrics.record_error();
+        assert!(metrics.take_changed());
+        assert!(!metrics.take_changed());
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "rebuilderd_worker_builds_total{distribution=\"archlinux\",status=\"GOOD\"} 2\n"
+        ));
+        assert!(text.contains(
+            "rebuilderd_worker_builds_total{distribution=\"debian\",status=\"DOWNLOAD_FAILED\"} 1\n"
+        ));
+        assert!(text.contains(
+            "rebuilderd_worker_build_duration_seconds_sum{distribution=\"archlinux\"} 90\n"
+        ));
+        assert!(text.contains(
+            "rebuilderd_worker_build_duration_seconds_count{distribution=\"archlinux\"} 2\n"
+        ));
+        assert!(text.contains("rebuilderd_worker_downloaded_bytes_total 150\n"));
+        assert!(text.contains("rebuilderd_worker_errors_total 1\n"));
+        assert!(text.contains("# TYPE rebuilderd_worker_last_build_timestamp_seconds gauge\n"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn test_pushgateway_url() {
+        let config = config::Metrics {
+            pushgateway: Some("https://pushgateway.example.com/".to_string()),
+            job: None,
+            labels: BTreeMap::from([
+                ("site".to_string(), "office/basement".to_string()),
+                ("empty".to_string(), String::new()),
+            ]),
+        };
+        let pusher = Pusher::from_config(&config, &OutboundConfig::default(), "worker-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            pusher.url.as_str(),
+            "https://pushgateway.example.com/metrics/job@base64/cmVidWlsZGVyZC13b3JrZXI/instance@base64/d29ya2VyLTE/empty@base64/=/site@base64/b2ZmaWNlL2Jhc2VtZW50"
+        );
+
+        let config = config::Metrics::default();
+        assert!(
+            Pusher::from_config(&config, &OutboundConfig::default(), "worker-1")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,506 @@
+use flate2::read::GzDecoder;
+use rebuilderd_common::errors::*;
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder, EntryType, Header};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+const RPM_LEAD_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const RPM_LEAD_SIZE: usize = 96;
+const RPM_HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01];
+
+const ZIP_EOCD_MAGIC: &[u8] = &[0x50, 0x4b, 0x05, 0x06];
+const ZIP_EOCD_SIZE: usize = 22;
+const APK_SIG_BLOCK_MAGIC: &[u8] = b"APK Sig Block 42";
+
+/// The default cellars of Homebrew on Apple Silicon, Intel and Linux
+const HOMEBREW_CELLARS: &[&[u8]] = &[
+    b"/opt/homebrew/Cellar",
+    b"/usr/local/Cellar",
+    b"/home/linuxbrew/.linuxbrew/Cellar",
+];
+const HOMEBREW_CELLAR_PLACEHOLDER: &[u8] = b"@@HOMEBREW_CELLAR@@";
+
+/// A normalization pass that is applied to both the published and the rebuilt
+/// artifact before they are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Normalizer {
+    /// Remove the signature header of an rpm, the main header and payload are kept
+    RpmSignature,
+    /// Remove the signing block of an Android apk and move the central directory offset accordingly
+    ApkSigningBlock,
+    /// Rewrite a zip file with sorted entries, fixed timestamps and no compression
+    Zip,
+    /// Unpack a Homebrew bottle and write it as plain tar with sorted entries, fixed metadata and the cellar paths
+    /// replaced by a placeholder
+    HomebrewBottle,
+    /// Remove the signatures and the signed Manifest of a Gentoo gpkg, the other members are kept in their order
+    GpkgSignature,
+}
+
+impl Normalizer {
+    pub fn apply(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Normalizer::RpmSignature => strip_rpm_signature(bytes),
+            Normalizer::ApkSigningBlock => strip_apk_signing_block(bytes),
+            Normalizer::Zip => repack_zip(&bytes),
+            Normalizer::HomebrewBottle => repack_bottle(&bytes),
+            Normalizer::GpkgSignature => strip_gpkg_signature(&bytes),
+        }
+    }
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    let buf = bytes.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(buf.try_into().ok()?))
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    let buf = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(buf.try_into().ok()?))
+}
+
+fn read_u64_le(bytes: &[u8], offset: usize) -> Option<u64> {
+    let buf = bytes.get(offset..offset + 8)?;
+    Some(u64::from_le_bytes(buf.try_into().ok()?))
+}
+
+fn strip_rpm_signature(mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if !bytes.starts_with(RPM_LEAD_MAGIC) {
+        bail!("File is not an rpm, lead magic is missing");
+    }
+    let header = bytes
+        .get(RPM_LEAD_SIZE..)
+        .filter(|h| h.starts_with(RPM_HEADER_MAGIC))
+        .context("Rpm is missing signature header")?;
+
+    let index_len = read_u32_be(header, 8).context("Truncated rpm signature header")? as usize;
+    let store_len = read_u32_be(header, 12).context("Truncated rpm signature header")? as usize;
+    // the signature header is padded to a multiple of 8 bytes
+    let len = (16 + index_len * 16 + store_len).next_multiple_of(8);
+    if header.len() < len {
+        bail!("Truncated rpm signature header");
+    }
+
+    bytes.drain(RPM_LEAD_SIZE..RPM_LEAD_SIZE + len);
+    Ok(bytes)
+}
+
+fn strip_apk_signing_block(mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let eocd = bytes
+        .len()
+        .checked_sub(ZIP_EOCD_SIZE)
+        .and_then(|end| {
+            // the comment at the end of the zip is at most 64k
+            let start = end.saturating_sub(u16::MAX as usize);
+            (start..=end)
+                .rev()
+                .find(|&pos| bytes[pos..].starts_with(ZIP_EOCD_MAGIC))
+        })
+        .context("File is not a zip, end of central directory is missing")?;
+    let cd_offset = read_u32_le(&bytes, eocd + 16).context("Truncated zip")? as usize;
+
+    let Some(magic_offset) = cd_offset.checked_sub(APK_SIG_BLOCK_MAGIC.len()) else {
+        return Ok(bytes);
+    };
+    if bytes.get(magic_offset..cd_offset) != Some(APK_SIG_BLOCK_MAGIC) {
+        debug!("No apk signing block found, nothing to normalize");
+        return Ok(bytes);
+    }
+
+    let size = read_u64_le(&bytes, magic_offset.saturating_sub(8))
+        .context("Truncated apk signing block")? as usize;
+    let start = cd_offset
+        .checked_sub(size + 8)
+        .context("Invalid apk signing block size")?;
+    if read_u64_le(&bytes, start) != Some(size as u64) {
+        bail!("Apk signing block sizes don't match");
+    }
+
+    // signatures of different keys differ in length, zeroing the block in place is not enough
+    bytes.drain(start..cd_offset);
+    let eocd = eocd - (cd_offset - start);
+    bytes[eocd + 16..eocd + 20].copy_from_slice(&(start as u32).to_le_bytes());
+    Ok(bytes)
+}
+
+fn repack_zip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).context("Failed to read zip")?;
+
+    let mut names = archive.file_names().map(String::from).collect::<Vec<_>>();
+    names.sort();
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in names {
+        let mut file = archive.by_name(&name)?;
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::default());
+        if let Some(mode) = file.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+
+        if file.is_dir() {
+            writer.add_directory(name, options)?;
+        } else {
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+            writer.start_file(name, options)?;
+            writer.write_all(&buf)?;
+        }
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+fn replace_all(haystack: &[u8], needle: &[u8], replacement: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+    while let Some(pos) = rest
+        .windows(needle.len())
+        .position(|window| window == needle)
+    {
+        out.extend(&rest[..pos]);
+        out.extend(replacement);
+        rest = &rest[pos + needle.len()..];
+    }
+    out.extend(rest);
+    out
+}
+
+fn replace_cellars(bytes: &[u8]) -> Vec<u8> {
+    HOMEBREW_CELLARS
+        .iter()
+        .fold(bytes.to_vec(), |bytes, cellar| {
+            replace_all(&bytes, cellar, HOMEBREW_CELLAR_PLACEHOLDER)
+        })
+}
+
+fn repack_bottle(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = Archive::new(GzDecoder::new(bytes));
+
+    let mut entries = Vec::new();
+    for entry in archive.entries().context("Failed to read bottle")? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let entry_type = entry.header().entry_type();
+        let mode = entry.header().mode()?;
+        let link = entry
+            .link_name_bytes()
+            .map(|link| replace_cellars(&link))
+            .map(|link| PathBuf::from(String::from_utf8_lossy(&link).into_owned()));
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        entries.push((path, entry_type, mode, link, replace_cellars(&data)));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut builder = Builder::new(Vec::new());
+    for (path, entry_type, mode, link, data) in entries {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_size(data.len() as u64);
+
+        match (entry_type, link) {
+            (EntryType::Symlink | EntryType::Link, Some(link)) => {
+                builder.append_link(&mut header, &path, &link)?
+            }
+            _ => builder.append_data(&mut header, &path, data.as_slice())?,
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// The members of a gpkg that only exist to verify the others: the detached signatures and the Manifest with the
+/// checksums of every member, which is clearsigned if signing is enabled
+fn is_gpkg_signature(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "sig")
+        || path.file_name().is_some_and(|name| name == "Manifest")
+}
+
+fn strip_gpkg_signature(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = Archive::new(bytes);
+
+    let mut is_gpkg = false;
+    let mut builder = Builder::new(Vec::new());
+    for entry in archive.entries().context("Failed to read gpkg")? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path.file_name().is_some_and(|name| name == "gpkg-1") {
+            is_gpkg = true;
+        }
+        if is_gpkg_signature(&path) {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+
+        // the timestamps of the members are the time the package was built
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry.header().entry_type());
+        header.set_mode(entry.header().mode()?);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, &path, data.as_slice())?;
+    }
+
+    if !is_gpkg {
+        bail!("File is not a gpkg, gpkg-1 is missing");
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Copy `src` to `dest`, applying the normalizers in order
+pub async fn normalize_file(normalizers: &[Normalizer], src: &Path, dest: &Path) -> Result<()> {
+    let bytes = tokio::fs::read(src)
+        .await
+        .with_context(|| anyhow!("Failed to read {:?}", src))?;
+
+    let normalizers = normalizers.to_vec();
+    let bytes = tokio::task::spawn_blocking(move || {
+        normalizers
+            .iter()
+            .try_fold(bytes, |bytes, normalizer| normalizer.apply(bytes))
+    })
+    .await??;
+
+    tokio::fs::write(dest, bytes)
+        .await
+        .with_context(|| anyhow!("Failed to write {:?}", dest))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpm(signature: &[u8]) -> Vec<u8> {
+        let mut bytes = RPM_LEAD_MAGIC.to_vec();
+        bytes.resize(RPM_LEAD_SIZE, 0);
+        // signature header with one index entry
+        bytes.extend(RPM_HEADER_MAGIC);
+        bytes.extend([0; 4]);
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend((signature.len() as u32).to_be_bytes());
+        bytes.extend([0; 16]);
+        bytes.extend(signature);
+        while !bytes.len().is_multiple_of(8) {
+            bytes.push(0);
+        }
+        bytes.extend(RPM_HEADER_MAGIC);
+        bytes.extend(b"main header and payload");
+        bytes
+    }
+
+    fn apk(signature: &[u8]) -> Vec<u8> {
+        let mut bytes = b"local file entries".to_vec();
+        let size = (signature.len() + 8 + APK_SIG_BLOCK_MAGIC.len()) as u64;
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(signature);
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(APK_SIG_BLOCK_MAGIC);
+        let cd_offset = bytes.len() as u32;
+        bytes.extend(b"central directory");
+        bytes.extend(ZIP_EOCD_MAGIC);
+        bytes.extend([0; 12]);
+        bytes.extend(cd_offset.to_le_bytes());
+        bytes.extend([0; 2]);
+        bytes
+    }
+
+    #[test]
+    fn strip_rpm_signatures() {
+        let a = strip_rpm_signature(rpm(b"signature by key a")).unwrap();
+        let b = strip_rpm_signature(rpm(b"other signature")).unwrap();
+        assert_eq!(a, b);
+        assert!(a.ends_with(b"main header and payload"));
+    }
+
+    #[test]
+    fn strip_rpm_signature_rejects_other_files() {
+        assert!(strip_rpm_signature(b"hello world".to_vec()).is_err());
+    }
+
+    #[test]
+    fn strip_apk_signing_blocks() {
+        let a = strip_apk_signing_block(apk(b"signature by key a")).unwrap();
+        let b = strip_apk_signing_block(apk(b"signature by key b")).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn strip_apk_signing_blocks_of_different_lengths() {
+        let a = strip_apk_signing_block(apk(b"short signature")).unwrap();
+        let b =
+            strip_apk_signing_block(apk(b"a much longer signature by a different key")).unwrap();
+        assert_eq!(a, b);
+
+        // the central directory now follows the local file entries directly
+        let eocd = a.len() - ZIP_EOCD_SIZE;
+        let cd_offset = read_u32_le(&a, eocd + 16).unwrap() as usize;
+        assert_eq!(cd_offset, b"local file entries".len());
+        assert!(a[cd_offset..].starts_with(b"central directory"));
+    }
+
+    #[test]
+    fn strip_apk_signing_block_keeps_unsigned_zips() {
+        let mut bytes = b"local file entries".to_vec();
+        let cd_offset = bytes.len() as u32;
+        bytes.extend(b"central directory");
+        bytes.extend(ZIP_EOCD_MAGIC);
+        bytes.extend([0; 12]);
+        bytes.extend(cd_offset.to_le_bytes());
+        bytes.extend([0; 2]);
+        assert_eq!(strip_apk_signing_block(bytes.clone()).unwrap(), bytes);
+    }
+
+    fn bottle(mtime: u64, cellar: &str) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, data) in [
+            (
+                "wget/1.24.5/bin/wget",
+                format!("binary linked against {cellar}/openssl@3/3.5.0"),
+            ),
+            ("wget/1.24.5/README", "hello".to_string()),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_size(data.len() as u64);
+            builder
+                .append_data(&mut header, path, data.as_bytes())
+                .unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&tar).unwrap();
+        gz.finish().unwrap()
+    }
+
+    #[test]
+    fn repack_bottle_normalizes_cellar() {
+        let a = repack_bottle(&bottle(1700000000, "/opt/homebrew/Cellar")).unwrap();
+        let b = repack_bottle(&bottle(1710000000, "/usr/local/Cellar")).unwrap();
+        assert_eq!(a, b);
+
+        let mut archive = Archive::new(a.as_slice());
+        let mut entries = archive.entries().unwrap();
+        let first = entries.next().unwrap().unwrap();
+        assert_eq!(first.path().unwrap().to_str(), Some("wget/1.24.5/README"));
+        let mut second = entries.next().unwrap().unwrap();
+        let mut data = String::new();
+        second.read_to_string(&mut data).unwrap();
+        assert_eq!(
+            data,
+            "binary linked against @@HOMEBREW_CELLAR@@/openssl@3/3.5.0"
+        );
+    }
+
+    #[test]
+    fn repack_bottle_rejects_other_files() {
+        assert!(repack_bottle(b"hello world").is_err());
+    }
+
+    fn gpkg(mtime: u64, signature: &str) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, data) in [
+            ("zstd-1.5.5-r1-1/gpkg-1", "gpkg-1".to_string()),
+            ("zstd-1.5.5-r1-1/metadata.tar.zst", "metadata".to_string()),
+            (
+                "zstd-1.5.5-r1-1/metadata.tar.zst.sig",
+                signature.to_string(),
+            ),
+            ("zstd-1.5.5-r1-1/image.tar.zst", "image".to_string()),
+            ("zstd-1.5.5-r1-1/image.tar.zst.sig", signature.to_string()),
+            (
+                "zstd-1.5.5-r1-1/Manifest",
+                format!("DATA image.tar.zst\n{signature}"),
+            ),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_size(data.len() as u64);
+            builder
+                .append_data(&mut header, path, data.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn strip_gpkg_signature_removes_signatures() {
+        let a = strip_gpkg_signature(&gpkg(1717000000, "signed by key a")).unwrap();
+        let b = strip_gpkg_signature(&gpkg(1717500000, "signed by key b")).unwrap();
+        assert_eq!(a, b);
+
+        let mut archive = Archive::new(a.as_slice());
+        let paths = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "zstd-1.5.5-r1-1/gpkg-1",
+                "zstd-1.5.5-r1-1/metadata.tar.zst",
+                "zstd-1.5.5-r1-1/image.tar.zst",
+            ]
+        );
+    }
+
+    #[test]
+    fn strip_gpkg_signature_rejects_other_tars() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(5);
+        builder
+            .append_data(&mut header, "hello.txt", b"hello".as_slice())
+            .unwrap();
+        assert!(strip_gpkg_signature(&builder.into_inner().unwrap()).is_err());
+    }
+
+    #[test]
+    fn repack_zip_is_deterministic() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .last_modified_time(DateTime::from_date_and_time(2020, 1, 2, 3, 4, 5).unwrap());
+        writer.start_file("b.txt", options).unwrap();
+        writer.write_all(b"world").unwrap();
+        writer.start_file("a.txt", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        let a = writer.finish().unwrap().into_inner();
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::default());
+        writer.start_file("a.txt", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        writer.start_file("b.txt", options).unwrap();
+        writer.write_all(b"world").unwrap();
+        let b = writer.finish().unwrap().into_inner();
+
+        assert_eq!(repack_zip(&a).unwrap(), repack_zip(&b).unwrap());
+    }
+}
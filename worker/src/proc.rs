@@ -1,4 +1,7 @@
+use crate::isolate;
+use crate::progress::{PhaseParser, Progress};
 use futures_util::FutureExt;
+use nix::sched::{CpuSet, sched_getaffinity, sched_setaffinity};
 use nix::sys::signal::{self, Signal};
 use nix::unistd::Pid;
 use rebuilderd_common::errors::*;
@@ -18,15 +21,24 @@ const SIGKILL_DELAY: u64 = 10;
 
 pub struct Options {
     pub timeout: Duration,
+    /// Scripts may ask for more time with extend markers, but never beyond this limit
+    pub max_timeout: Option<Duration>,
     pub size_limit: Option<usize>,
     pub kill_at_size_limit: bool,
     pub passthrough: bool,
     pub envs: HashMap<String, String>,
+    /// Track phase markers printed to stdout
+    pub progress: Option<Progress>,
+    /// Run the process in a network namespace without connectivity
+    pub isolate_network: bool,
+    /// Restrict the process to a single cpu, everything it starts inherits this
+    pub single_cpu: bool,
 }
 
 pub struct Capture<'a> {
     output: &'a mut Vec<u8>,
     timeout: Duration,
+    max_timeout: Option<Duration>,
     size_limit: Option<usize>,
     kill_at_size_limit: bool,
     start: Instant,
@@ -39,6 +51,7 @@ pub fn capture(output: &mut Vec<u8>, opts: Options) -> Capture<'_> {
     Capture {
         output,
         timeout: opts.timeout,
+        max_timeout: opts.max_timeout,
         size_limit: opts.size_limit,
         kill_at_size_limit: opts.kill_at_size_limit,
         start,
@@ -74,6 +87,31 @@ impl Capture<'_> {
         Ok(())
     }
 
+    /// Move the timeout to the given number of seconds from now, returns false if that's not allowed
+    pub fn extend(&mut self, seconds: u64) -> bool {
+        let Some(max_timeout) = self.max_timeout else {
+            warn!(
+                "Ignoring request to extend the timeout by {seconds}s, no max_timeout configured"
+            );
+            return false;
+        };
+        if self.sigterm_sent.is_some() {
+            return false;
+        }
+
+        let timeout = self
+            .start
+            .elapsed()
+            .saturating_add(Duration::from_secs(seconds))
+            .min(max_timeout);
+        if timeout <= self.timeout {
+            return false;
+        }
+        info!("Extending timeout to {}s", timeout.as_secs());
+        self.timeout = timeout;
+        true
+    }
+
     fn kill(pid: u32, signal: Signal) -> Result<()> {
         // convert 1234 to -1234 to kill grand-children too
         let pid = -(pid as i32);
@@ -134,6 +172,47 @@ impl Capture<'_> {
     }
 }
 
+/// Kills the process group of the child if the build is dropped before the child exited, eg. because an admin aborted
+/// the job. Without this the child would keep running in the background.
+struct KillOnDrop {
+    pid: Option<u32>,
+}
+
+impl KillOnDrop {
+    fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid
+            && let Err(err) = Capture::kill(pid, Signal::SIGKILL)
+        {
+            warn!("Failed to kill child(pid={pid}): {err:#}");
+        }
+    }
+}
+
+/// Picks the first cpu this process may run on, a cpuset of the service manager may exclude the first one of the
+/// system
+fn pin_to_single_cpu(cmd: &mut Command) -> Result<()> {
+    let allowed = sched_getaffinity(Pid::from_raw(0))?;
+    let cpu = (0..CpuSet::count())
+        .find(|cpu| allowed.is_set(*cpu).unwrap_or(false))
+        .context("Process is not allowed to run on any cpu")?;
+    let mut single = CpuSet::new();
+    single.set(cpu)?;
+
+    unsafe {
+        cmd.pre_exec(move || {
+            sched_setaffinity(Pid::from_raw(0), &single)?;
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
 pub async fn run<I, S>(bin: &Path, args: I, opts: Options, log: &mut Vec<u8>) -> Result<bool>
 where
     I: IntoIterator<Item = S> + fmt::Debug,
@@ -158,7 +237,15 @@ where
         });
     }
 
+    if opts.isolate_network {
+        isolate::apply(&mut cmd);
+    }
+    if opts.single_cpu {
+        pin_to_single_cpu(&mut cmd)?;
+    }
+
     let mut child = cmd.spawn()?;
+    let mut guard = KillOnDrop { pid: child.id() };
 
     let mut child_stdout = child.stdout.take().unwrap();
     let mut child_stderr = child.stderr.take().unwrap();
@@ -169,6 +256,8 @@ where
     let mut stdout = tokio::io::stdout();
     let mut stderr = tokio::io::stderr();
     let passthrough = opts.passthrough;
+    let progress = opts.progress.clone();
+    let mut phases = PhaseParser::default();
 
     let mut stdout_open = true;
     let mut stderr_open = true;
@@ -187,6 +276,12 @@ where
                         stdout_open = false;
                     } else {
                         cap.push_bytes(&mut child, &buf_stdout[..n]).await?;
+                        if let Some(progress) = &progress
+                            && let Some(seconds) = phases.push(&buf_stdout[..n], progress)
+                            && cap.extend(seconds)
+                        {
+                            progress.request_extension(seconds);
+                        }
                         if passthrough {
                             stdout.write_all(&buf_stdout[..n]).await?;
                         }
@@ -211,6 +306,7 @@ where
                 status = child.wait().fuse() => {
                     let status = status?;
                     info!("{:?} exited with exit={}, captured {} bytes", bin, status, log.len());
+                    guard.disarm();
                     break status.success();
                 }
                 _ = time::sleep(remaining).fuse() => continue,
@@ -241,10 +337,14 @@ mod tests {
             "/bin/echo hello world",
             Options {
                 timeout: Duration::from_secs(600),
+                max_timeout: None,
                 size_limit: None,
                 kill_at_size_limit: false,
                 passthrough: false,
                 envs: HashMap::new(),
+                progress: None,
+                isolate_network: false,
+                single_cpu: false,
             },
         )
         .await
@@ -263,10 +363,14 @@ mod tests {
         ",
             Options {
                 timeout: Duration::from_secs(600),
+                max_timeout: None,
                 size_limit: Some(50),
                 kill_at_size_limit: false,
                 passthrough: false,
                 envs: HashMap::new(),
+                progress: None,
+                isolate_network: false,
+                single_cpu: false,
             },
         )
         .await
@@ -289,10 +393,14 @@ mod tests {
         ",
             Options {
                 timeout: Duration::from_secs(600),
+                max_timeout: None,
                 size_limit: Some(50),
                 kill_at_size_limit: true,
                 passthrough: false,
                 envs: HashMap::new(),
+                progress: None,
+                isolate_network: false,
+                single_cpu: false,
             },
         )
         .await
@@ -306,6 +414,37 @@ mod tests {
         assert!(duration < Duration::from_secs(5));
     }
 
+    #[tokio::test]
+    async fn dropped_build_is_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alive");
+        let cmd = format!("while true; do /bin/echo x >> {path:?}; sleep 0.1; done");
+        let build = script(
+            &cmd,
+            Options {
+                timeout: Duration::from_secs(600),
+                max_timeout: None,
+                size_limit: None,
+                kill_at_size_limit: false,
+                passthrough: false,
+                envs: HashMap::new(),
+                progress: None,
+                isolate_network: false,
+                single_cpu: false,
+            },
+        );
+        assert!(
+            time::timeout(Duration::from_millis(500), build)
+                .await
+                .is_err()
+        );
+
+        time::sleep(Duration::from_millis(300)).await;
+        let size = std::fs::metadata(&path).unwrap().len();
+        time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+    }
+
     #[tokio::test]
     async fn timeout() {
         let (success, output, duration) = script(
@@ -317,10 +456,14 @@ mod tests {
         ",
             Options {
                 timeout: Duration::from_millis(1500),
+                max_timeout: None,
                 size_limit: None,
                 kill_at_size_limit: false,
                 passthrough: false,
                 envs: HashMap::new(),
+                progress: None,
+                isolate_network: false,
+                single_cpu: false,
             },
         )
         .await
@@ -345,10 +488,14 @@ mod tests {
         ",
             Options {
                 timeout: Duration::from_millis(1500),
+                max_timeout: None,
                 size_limit: Some(50),
                 kill_at_size_limit: false,
                 passthrough: false,
                 envs: HashMap::new(),
+                progress: None,
+                isolate_network: false,
+                single_cpu: false,
             },
         )
         .await
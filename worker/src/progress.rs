@@ -0,0 +1,164 @@
+use rebuilderd_common::errors::*;
+use std::sync::{Arc, Mutex};
+
+/// Rebuilder scripts announce a new phase by printing a line like `::rebuilderd-phase:: build` to stdout
+pub const PHASE_MARKER: &str = "::rebuilderd-phase::";
+/// Rebuilder scripts ask for more time by printing a line like `::rebuilderd-extend:: 3600`, in seconds from now
+pub const EXTEND_MARKER: &str = "::rebuilderd-extend::";
+/// Phase names are free-form but kept short since they are sent with every ping
+const MAX_PHASE_LEN: usize = 64;
+/// Give up on lines that are unreasonably long, they can't be a phase marker anyway
+const MAX_LINE_LEN: usize = 4096;
+
+/// The current phase of a rebuild, shared between the build and the heartbeat
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    phase: Arc<Mutex<Option<String>>>,
+    extension: Arc<Mutex<Option<u64>>>,
+}
+
+impl Progress {
+    pub fn set(&self, phase: &str) {
+        debug!("Entering phase {:?}", phase);
+        *self.phase.lock().unwrap() = Some(phase.to_string());
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.phase.lock().unwrap().clone()
+    }
+
+    /// Remember an extension that was granted locally, so the next ping asks the daemon for it too
+    pub fn request_extension(&self, seconds: u64) {
+        debug!("Requesting an extension of the deadline by {seconds}s");
+        *self.extension.lock().unwrap() = Some(seconds);
+    }
+
+    pub fn take_extension(&self) -> Option<u64> {
+        self.extension.lock().unwrap().take()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Marker<'a> {
+    Phase(&'a str),
+    Extend(u64),
+}
+
+/// Scans process output for phase and extend markers, output may be split at arbitrary positions
+#[derive(Debug, Default)]
+pub struct PhaseParser {
+    partial: Vec<u8>,
+    overflow: bool,
+}
+
+impl PhaseParser {
+    /// Phases are recorded right away, the last extension that was asked for is returned to the caller
+    pub fn push(&mut self, mut bytes: &[u8], progress: &Progress) -> Option<u64> {
+        let mut extension = None;
+        while let Some(pos) = bytes.iter().position(|b| *b == b'\n') {
+            if !self.overflow {
+                self.partial.extend(&bytes[..pos]);
+                match parse_line(&self.partial) {
+                    Some(Marker::Phase(phase)) => progress.set(phase),
+                    Some(Marker::Extend(seconds)) => extension = Some(seconds),
+                    None => (),
+                }
+            }
+            self.partial.clear();
+            self.overflow = false;
+            bytes = &bytes[pos + 1..];
+        }
+
+        if !self.overflow {
+            self.partial.extend(bytes);
+            if self.partial.len() > MAX_LINE_LEN {
+                self.partial.clear();
+                self.overflow = true;
+            }
+        }
+
+        extension
+    }
+}
+
+fn parse_line(line: &[u8]) -> Option<Marker<'_>> {
+    let line = std::str::from_utf8(line).ok()?.trim_end();
+    if let Some(seconds) = line.strip_prefix(EXTEND_MARKER) {
+        return seconds.trim().parse().ok().map(Marker::Extend);
+    }
+
+    let phase = line.strip_prefix(PHASE_MARKER)?.trim();
+    if phase.is_empty() || phase.len() > MAX_PHASE_LEN {
+        None
+    } else {
+        Some(Marker::Phase(phase))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_phase_reported() {
+        let progress = Progress::default();
+        let mut parser = PhaseParser::default();
+        parser.push(b"hello world\nsome more output\n", &progress);
+        assert_eq!(progress.get(), None);
+    }
+
+    #[test]
+    fn detect_phases() {
+        let progress = Progress::default();
+        let mut parser = PhaseParser::default();
+        parser.push(b"::rebuilderd-phase:: fetch\ndownloading...\n", &progress);
+        assert_eq!(progress.get().as_deref(), Some("fetch"));
+        parser.push(b"::rebuilderd-phase:: build\r\n", &progress);
+        assert_eq!(progress.get().as_deref(), Some("build"));
+    }
+
+    #[test]
+    fn marker_split_across_reads() {
+        let progress = Progress::default();
+        let mut parser = PhaseParser::default();
+        parser.push(b"output\n::rebuilderd-", &progress);
+        assert_eq!(progress.get(), None);
+        parser.push(b"phase:: env-setup\n", &progress);
+        assert_eq!(progress.get().as_deref(), Some("env-setup"));
+    }
+
+    #[test]
+    fn marker_must_start_the_line() {
+        let progress = Progress::default();
+        let mut parser = PhaseParser::default();
+        parser.push(b"+ echo ::rebuilderd-phase:: build\n", &progress);
+        assert_eq!(progress.get(), None);
+    }
+
+    #[test]
+    fn ignore_overlong_lines() {
+        let progress = Progress::default();
+        let mut parser = PhaseParser::default();
+        parser.push(&[b'a'; MAX_LINE_LEN + 1], &progress);
+        parser.push(b"::rebuilderd-phase:: build\n", &progress);
+        assert_eq!(progress.get(), None);
+        parser.push(b"::rebuilderd-phase:: build\n", &progress);
+        assert_eq!(progress.get().as_deref(), Some("build"));
+    }
+
+    #[test]
+    fn detect_extensions() {
+        let progress = Progress::default();
+        let mut parser = PhaseParser::default();
+        let extension = parser.push(
+            b"::rebuilderd-extend:: 600\n::rebuilderd-extend:: 3600\n",
+            &progress,
+        );
+        assert_eq!(extension, Some(3600));
+        assert_eq!(progress.get(), None);
+        assert_eq!(
+            parser.push(b"::rebuilderd-extend:: soon\n", &progress),
+            None
+        );
+    }
+}
@@ -1,31 +1,55 @@
+use crate::builddir::JobDir;
+use crate::compare::{self, Comparator, Comparison};
 use crate::config;
 use crate::diffoscope::diffoscope;
-use crate::download::download;
+use crate::download::{self, DownloadFailed, Downloader, RejectedFilename};
 use crate::heartbeat::HeartBeat;
+use crate::hooks::{self, HookEnv};
 use crate::proc;
+use crate::progress::Progress;
+use crate::summary;
+use data_encoding::HEXLOWER;
+use futures::future::try_join_all;
 use in_toto::crypto::PrivateKey;
 use in_toto::runlib::in_toto_run;
-use rebuilderd_common::api::v1::{ArtifactStatus, QueuedJobArtifact, RebuildArtifactReport};
+use rebuilderd_common::api::v1::{
+    ArtifactStatus, BisectVariation, JobPayload, QueuedJobArtifact, RebuildArtifactReport,
+};
 use rebuilderd_common::errors::Context as _;
 use rebuilderd_common::errors::*;
 use rebuilderd_common::utils::zstd_compress;
+use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
-use std::io::ErrorKind;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
 use tokio::select;
 use tokio::time;
 
+/// Differs from the usual C, C.UTF-8 and en_US locales in the decimal separator and the format of dates
+const VARIED_LOCALE: &str = "fr_CH.UTF-8";
+
 pub struct Context<'a> {
+    /// Names the job directory, local builds have no job id
+    pub job_id: Option<i32>,
     pub artifacts: Vec<QueuedJobArtifact>,
-    pub input_url: Option<String>,
+    /// The build input and auxiliary files, the first artifact is used as build input if there is none
+    pub payload: Option<JobPayload>,
+    /// The architecture to cross-compile for, the build runs on the native architecture of the worker
+    pub cross_target: Option<String>,
+    /// The build is a step of a bisect and runs with this change to its environment
+    pub variation: Option<BisectVariation>,
+    /// Attach GOOD artifacts to the report, the daemon hashes them itself instead of trusting the reported checksum
+    pub upload_artifacts: bool,
     pub backend: config::Backend,
     pub build: config::Build,
     pub diffoscope: config::Diffoscope,
+    pub hooks: Vec<config::Hook>,
     pub privkey: &'a PrivateKey,
+    pub progress: Progress,
+    pub downloader: Downloader,
 }
 
 fn path_to_string(path: &Path) -> Result<String> {
@@ -35,72 +59,22 @@ fn path_to_string(path: &Path) -> Result<String> {
     Ok(s.to_string())
 }
 
-pub async fn compare_files(a: &Path, b: &Path) -> Result<bool> {
-    let mut buf1 = [0u8; 4096];
-    let mut buf2 = [0u8; 4096];
-
-    info!("Comparing {:?} with {:?}", a, b);
-    let mut f1 = File::open(a)
-        .await
-        .with_context(|| anyhow!("Failed to open {:?}", a))?;
-    let mut f2 = File::open(b)
-        .await
-        .with_context(|| anyhow!("Failed to open {:?}", b))?;
-
-    let mut pos = 0;
-    loop {
-        // read up to 4k bytes from the first file
-        let n = f1.read_buf(&mut &mut buf1[..]).await?;
-
-        // check if the first file is end-of-file
-        if n == 0 {
-            debug!("First file is at end-of-file");
-
-            // check if other file is eof too
-            let n = f2.read_buf(&mut &mut buf2[..]).await?;
-            if n > 0 {
-                info!("Files are not identical, {:?} is longer", b);
-                return Ok(false);
-            } else {
-                return Ok(true);
-            }
-        }
-
-        // check the same chunk in the other file
-        match f2.read_exact(&mut buf2[..n]).await {
-            Ok(n) => n,
-            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
-                info!("Files are not identical, {:?} is shorter", b);
-                return Ok(false);
-            }
-            err => err?,
-        };
-
-        if buf1[..n] != buf2[..n] {
-            // get the exact position
-            // this can't panic because we've already checked the slices are not equal
-            let pos = pos
-                + buf1[..n]
-                    .iter()
-                    .zip(buf2[..n].iter())
-                    .position(|(a, b)| a != b)
-                    .unwrap();
-            info!("Files {:?} and {:?} differ at position {}", a, b, pos);
-
-            return Ok(false);
-        }
-
-        // advance the number of bytes that are equal
-        pos += n;
-    }
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        fs::File::open(path).with_context(|| anyhow!("Failed to open artifact: {path:?}"))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| anyhow!("Failed to read artifact: {path:?}"))?;
+    Ok(HEXLOWER.encode(&hasher.finalize()))
 }
 
 pub async fn rebuild_with_heartbeat(
     ctx: &Context<'_>,
+    inputs: Option<Inputs>,
     log: &mut Vec<u8>,
     hb: &dyn HeartBeat,
 ) -> Result<Vec<RebuildArtifactReport>> {
-    let mut rebuild = Box::pin(rebuild(ctx, log));
+    let mut rebuild = Box::pin(rebuild(ctx, inputs, log));
     loop {
         select! {
             res = &mut rebuild => {
@@ -111,52 +85,171 @@ pub async fn rebuild_with_heartbeat(
     }
 }
 
-pub async fn rebuild(ctx: &Context<'_>, log: &mut Vec<u8>) -> Result<Vec<RebuildArtifactReport>> {
-    // setup
-    let tmp = tempfile::Builder::new().prefix("rebuilderd").tempdir()?;
+/// The downloaded inputs of a job, fetched right before the build or while the previous build was still running
+pub struct Inputs {
+    dir: JobDir,
+    artifacts: Vec<(QueuedJobArtifact, PathBuf)>,
+    input_url: String,
+    input_filename: PathBuf,
+}
 
-    let inputs_dir = tmp.path().join("inputs");
-    fs::create_dir(&inputs_dir).context("Failed to create inputs/ temp dir")?;
+/// Download the original packages and the build input into a new job directory. The original packages have to be named
+/// like packages of the distribution, if the backend knows how they are named.
+pub async fn fetch_inputs(
+    build: &config::Build,
+    job_id: Option<i32>,
+    artifacts: &[QueuedJobArtifact],
+    payload: Option<&JobPayload>,
+    artifact_filename: Option<&Regex>,
+    downloader: &Downloader,
+) -> Result<Inputs> {
+    if let Some(pattern) = artifact_filename {
+        for artifact in artifacts {
+            let filename = download::filename(&artifact.url)?;
+            if !pattern.is_match(&filename) {
+                return Err(RejectedFilename {
+                    filename,
+                    reason: format!("doesn't match the naming scheme {:?}", pattern.as_str()),
+                }
+                .into());
+            }
+        }
+    }
 
-    let out_dir = tmp.path().join("out");
-    fs::create_dir(&out_dir).context("Failed to create out/ temp dir")?;
+    let dir = JobDir::create(build, job_id)?;
+    let inputs_dir = dir.inputs();
 
-    // download
-    let mut artifacts = Vec::new();
-    for artifact in &ctx.artifacts {
-        let artifact_filename = download(&artifact.url, &inputs_dir)
+    // the downloader limits how many of these hit the same mirror at once
+    let artifacts = try_join_all(artifacts.iter().map(|artifact| async {
+        let artifact_filename = downloader
+            .download(&artifact.url, &inputs_dir, artifact.checksum.as_deref())
             .await
-            .with_context(|| {
-                anyhow!(
-                    "Failed to download original package from {:?}",
-                    artifact.url
-                )
+            .with_context(|| DownloadFailed {
+                what: "original package",
+                url: artifact.url.clone(),
             })?;
-        let artifact_path = inputs_dir.join(&artifact_filename);
-        artifacts.push((artifact.clone(), artifact_filename, artifact_path));
+        Ok::<_, Error>((artifact.clone(), artifact_filename))
+    }))
+    .await?;
+
+    // eg. the source package description, the rebuilder script expects them next to the build input
+    if let Some(payload) = payload {
+        let path = inputs_dir.as_path();
+        try_join_all(payload.auxiliary_urls().into_iter().map(|url| async move {
+            downloader
+                .download(url, path, None)
+                .await
+                .with_context(|| DownloadFailed {
+                    what: "auxiliary input",
+                    url: url.to_string(),
+                })
+        }))
+        .await?;
     }
 
-    let input_filename = if let Some(input_url) = &ctx.input_url {
-        download(input_url, &inputs_dir)
+    let (input_url, input_filename) = if let Some(input_url) = payload.map(JobPayload::input_url) {
+        let filename = downloader
+            .download(input_url, &inputs_dir, None)
             .await
-            .with_context(|| anyhow!("Failed to download build input from {:?}", input_url))?
+            .with_context(|| DownloadFailed {
+                what: "build input",
+                url: input_url.to_string(),
+            })?;
+        (input_url.to_string(), filename)
     } else {
-        artifacts
+        let (artifact, filename) = artifacts
             .first()
-            .context("Failed to use first artifact as build input")?
-            .1
-            .to_owned()
+            .context("Failed to use first artifact as build input")?;
+        (artifact.url.clone(), filename.to_owned())
     };
+
+    Ok(Inputs {
+        dir,
+        artifacts,
+        input_url,
+        input_filename,
+    })
+}
+
+pub async fn rebuild(
+    ctx: &Context<'_>,
+    inputs: Option<Inputs>,
+    log: &mut Vec<u8>,
+) -> Result<Vec<RebuildArtifactReport>> {
+    // download, unless the inputs have been prefetched
+    let inputs = if let Some(inputs) = inputs {
+        inputs
+    } else {
+        ctx.progress.set("fetch");
+        fetch_inputs(
+            &ctx.build,
+            ctx.job_id,
+            &ctx.artifacts,
+            ctx.payload.as_ref(),
+            ctx.backend.artifact_filename()?.as_ref(),
+            &ctx.downloader,
+        )
+        .await?
+    };
+    // the job directory is removed once this goes out of scope
+    let Inputs {
+        dir: job_dir,
+        artifacts,
+        input_url,
+        input_filename,
+    } = inputs;
+    let inputs_dir = job_dir.inputs();
+    let out_dir = job_dir.out();
+    let normalized_dir = job_dir.path().join("normalized");
+
+    let artifacts = artifacts
+        .into_iter()
+        .map(|(artifact, filename)| {
+            let path = inputs_dir.join(&filename);
+            (artifact, filename, path)
+        })
+        .collect::<Vec<_>>();
     let input_path = inputs_dir.join(&input_filename);
 
-    // rebuild
-    verify(ctx, log, &out_dir, &input_path).await?;
+    let offline = ctx.backend.is_offline(&ctx.build);
+    if offline && let Some(prefetch) = &ctx.backend.prefetch {
+        ctx.progress.set("prefetch");
+        let opts = script_options(ctx, &job_dir, &input_url, false)?;
+        if !proc::run(prefetch, &[&input_path], opts, log).await? {
+            bail!("Prefetch script {prefetch:?} failed");
+        }
+    }
+
+    // rebuild, the rebuilder script may report more detailed phases from here on
+    ctx.progress.set("build");
+    if offline {
+        log.extend(b"rebuilderd: running build without network access\n");
+    }
+    let opts = script_options(ctx, &job_dir, &input_url, offline)?;
+    proc::run(ctx.backend.path.as_ref(), &[&input_path], opts, log).await?;
 
     // process results
+    ctx.progress.set("compare");
+    let comparator = compare::for_backend(&ctx.backend, &ctx.diffoscope, &normalized_dir)?;
     let mut results = Vec::new();
     for (artifact, artifact_filename, artifact_path) in artifacts {
         let output_path = out_dir.join(&artifact_filename);
 
+        let Comparison {
+            identical,
+            diffoscope: diff,
+            modulo_compression,
+        } = if output_path.exists() {
+            comparator.compare(&artifact_path, &output_path).await?
+        } else {
+            Comparison::default()
+        };
+        let checksum = if output_path.exists() {
+            Some(sha256_file(&output_path)?)
+        } else {
+            None
+        };
+
         let result = if !output_path.exists() {
             info!(
                 "No output artifact found, marking as BAD: {:?}",
@@ -168,8 +261,11 @@ pub async fn rebuild(ctx: &Context<'_>, log: &mut Vec<u8>) -> Result<Vec<Rebuild
                 diffoscope: None,
                 attestation: None,
                 status: ArtifactStatus::Bad,
+                diff_summary: None,
+                checksum: None,
+                content: None,
             }
-        } else if compare_files(&artifact_path, &output_path).await? {
+        } else if identical {
             info!(
                 "Output artifacts is identical, marking as GOOD: {:?}",
                 output_path
@@ -180,8 +276,30 @@ pub async fn rebuild(ctx: &Context<'_>, log: &mut Vec<u8>) -> Result<Vec<Rebuild
                 diffoscope: None,
                 attestation: None,
                 status: ArtifactStatus::Good,
+                diff_summary: None,
+                checksum,
+                content: None,
             };
 
+            if ctx.upload_artifacts {
+                let content = fs::read(&output_path)
+                    .with_context(|| anyhow!("Failed to read artifact: {output_path:?}"))?;
+                res.content = Some(zstd_compress(&content).await.map_err(Error::from)?);
+            }
+
+            if modulo_compression {
+                log.extend(
+                    format!(
+                        "rebuilderd: {artifact_filename:?} is reproducible modulo compression\n"
+                    )
+                    .as_bytes(),
+                );
+                match summary::summarize(&artifact_path, &output_path).await {
+                    Ok(summary) => res.diff_summary = Some(summary),
+                    Err(err) => warn!("Failed to summarize differences: {err:#}"),
+                }
+            }
+
             info!("Generating signed link");
             match in_toto_run(
                 &format!("rebuild {}", artifact_filename.to_str().unwrap()),
@@ -224,14 +342,30 @@ pub async fn rebuild(ctx: &Context<'_>, log: &mut Vec<u8>) -> Result<Vec<Rebuild
                 diffoscope: None,
                 attestation: None,
                 status: ArtifactStatus::Bad,
+                diff_summary: None,
+                checksum,
+                content: None,
             };
 
-            // generate diffoscope diff if enabled
-            if ctx.diffoscope.enabled {
+            // a summary is cheap, so it's included even if diffoscope is disabled
+            match summary::summarize(&artifact_path, &output_path).await {
+                Ok(summary) => res.diff_summary = Some(summary),
+                Err(err) => warn!("Failed to summarize differences: {err:#}"),
+            }
+
+            // generate diffoscope diff if enabled, unless the comparison already did
+            let diff = if let Some(diff) = diff {
+                Some(diff)
+            } else if ctx.diffoscope.enabled {
                 let diff = diffoscope(&artifact_path, &output_path, &ctx.diffoscope)
                     .await
                     .context("Failed to run diffoscope")?;
+                Some(diff)
+            } else {
+                None
+            };
 
+            if let Some(diff) = diff {
                 let encoded_diffoscope =
                     zstd_compress(diff.as_bytes()).await.map_err(Error::from)?;
 
@@ -244,81 +378,93 @@ pub async fn rebuild(ctx: &Context<'_>, log: &mut Vec<u8>) -> Result<Vec<Rebuild
         results.push(result);
     }
 
-    Ok(results)
-}
-
-async fn verify(
-    ctx: &Context<'_>,
-    log: &mut Vec<u8>,
-    out_dir: &Path,
-    input_path: &Path,
-) -> Result<()> {
-    let bin = &ctx.backend.path;
-    let timeout = ctx.build.timeout.unwrap_or(3600 * 24); // 24h
-
-    let mut envs = HashMap::new();
-    envs.insert("REBUILDERD_OUTDIR".into(), path_to_string(out_dir)?);
+    if !ctx.hooks.is_empty() {
+        ctx.progress.set("hooks");
+        let status = if results.iter().all(|r| r.status == ArtifactStatus::Good) {
+            "GOOD"
+        } else {
+            "BAD"
+        };
 
-    let opts = proc::Options {
-        timeout: Duration::from_secs(timeout),
-        size_limit: ctx.build.max_bytes,
-        kill_at_size_limit: false,
-        passthrough: !ctx.build.silent,
-        envs,
-    };
+        let build_log = job_dir.path().join("build.log");
+        fs::write(&build_log, &log[..]).context("Failed to write build log for hooks")?;
 
-    proc::run(bin.as_ref(), &[input_path], opts, log).await?;
+        let env = HookEnv {
+            input: &input_path,
+            inputs_dir: &inputs_dir,
+            out_dir: &out_dir,
+            build_log: &build_log,
+            status,
+        };
+        if let Err(err) = hooks::run_all(&ctx.hooks, &env, log).await {
+            if let Some(quarantine_dir) = &ctx.build.quarantine_dir {
+                let path = hooks::quarantine(quarantine_dir, &out_dir, &build_log)
+                    .context("Failed to quarantine build output")?;
+                warn!("Build output has been quarantined in {path:?}");
+                log.extend(
+                    format!("\nrebuilderd: build output quarantined in {path:?}\n").as_bytes(),
+                );
+            }
+            return Err(err);
+        }
+    }
 
-    Ok(())
+    Ok(results)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The environment rebuilder and prefetch scripts are started with
+fn script_options(
+    ctx: &Context<'_>,
+    job_dir: &JobDir,
+    input_url: &str,
+    isolate_network: bool,
+) -> Result<proc::Options> {
+    let timeout = ctx
+        .backend
+        .timeout
+        .or(ctx.build.timeout)
+        .unwrap_or(3600 * 24); // 24h
 
-    #[tokio::test]
-    async fn compare_files_equal() {
-        let equal = compare_files(Path::new("src/main.rs"), Path::new("src/main.rs"))
-            .await
-            .unwrap();
-        assert!(equal);
+    let mut envs = HashMap::new();
+    envs.insert(
+        "REBUILDERD_INPUTS_DIR".into(),
+        path_to_string(&job_dir.inputs())?,
+    );
+    envs.insert("REBUILDERD_OUTDIR".into(), path_to_string(&job_dir.out())?);
+    // keep scratch files of the script in the job directory, they are cleaned up along with it
+    envs.insert("TMPDIR".into(), path_to_string(&job_dir.tmp())?);
+    // some backends need to know where the input was published, eg. to locate a matching sdk
+    envs.insert("REBUILDERD_INPUT_URL".into(), input_url.to_string());
+    if let Some(payload) = &ctx.payload {
+        envs.insert("REBUILDERD_PAYLOAD".into(), serde_json::to_string(payload)?);
+        if let Some(commit) = payload.packaging_commit() {
+            envs.insert("REBUILDERD_PACKAGING_COMMIT".into(), commit.to_string());
+        }
     }
-
-    #[tokio::test]
-    async fn compare_files_not_equal1() {
-        let equal = compare_files(Path::new("src/main.rs"), Path::new("Cargo.toml"))
-            .await
-            .unwrap();
-        assert!(!equal);
+    if isolate_network {
+        envs.insert("REBUILDERD_OFFLINE".into(), "1".to_string());
     }
-
-    #[tokio::test]
-    async fn compare_files_not_equal2() {
-        let equal = compare_files(Path::new("Cargo.toml"), Path::new("src/main.rs"))
-            .await
-            .unwrap();
-        assert!(!equal);
+    if let Some(cross_target) = &ctx.cross_target {
+        envs.insert("REBUILDERD_CROSS_TARGET".into(), cross_target.clone());
     }
-
-    #[tokio::test]
-    async fn compare_large_files_equal() {
-        let dir = tempfile::tempdir().unwrap();
-        fs::write(dir.path().join("a"), [0u8; 4096 * 100]).unwrap();
-        fs::write(dir.path().join("b"), [0u8; 4096 * 100]).unwrap();
-        let equal = compare_files(&dir.path().join("a"), &dir.path().join("b"))
-            .await
-            .unwrap();
-        assert!(equal);
+    // the worker can only change the locale and the cpus itself, the script takes care of the other variations
+    if let Some(variation) = ctx.variation {
+        envs.insert("REBUILDERD_VARIATION".into(), variation.to_string());
+        if variation == BisectVariation::Locale {
+            envs.insert("LANG".into(), VARIED_LOCALE.to_string());
+            envs.insert("LC_ALL".into(), VARIED_LOCALE.to_string());
+        }
     }
 
-    #[tokio::test]
-    async fn compare_large_files_not_equal() {
-        let dir = tempfile::tempdir().unwrap();
-        fs::write(dir.path().join("a"), [0u8; 4096 * 100]).unwrap();
-        fs::write(dir.path().join("b"), [1u8; 4096 * 100]).unwrap();
-        let equal = compare_files(&dir.path().join("a"), &dir.path().join("b"))
-            .await
-            .unwrap();
-        assert!(!equal);
-    }
+    Ok(proc::Options {
+        timeout: Duration::from_secs(timeout),
+        max_timeout: ctx.build.max_timeout.map(Duration::from_secs),
+        size_limit: ctx.backend.max_bytes.or(ctx.build.max_bytes),
+        kill_at_size_limit: false,
+        passthrough: !ctx.build.silent,
+        envs,
+        progress: Some(ctx.progress.clone()),
+        isolate_network,
+        single_cpu: ctx.variation == Some(BisectVariation::SingleCpu),
+    })
 }
@@ -0,0 +1,37 @@
+use rebuilderd_common::errors::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::signal::unix::{SignalKind, signal};
+
+/// Set by SIGHUP, the config is reloaded once the running build is done
+#[derive(Debug, Clone, Default)]
+pub struct ReloadRequest(Arc<AtomicBool>);
+
+impl ReloadRequest {
+    /// Handling SIGHUP also keeps it from terminating the worker, which would kill the running build
+    pub fn listen() -> Result<Self> {
+        let mut hangup = signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+        let request = ReloadRequest::default();
+        let flag = request.0.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading the config once the current build is done");
+                flag.store(true, Ordering::SeqCst);
+            }
+        });
+        Ok(request)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+
+    /// The reload can't be applied yet, try again after the next build
+    pub fn postpone(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
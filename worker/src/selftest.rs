@@ -0,0 +1,162 @@
+use crate::config;
+use crate::isolate;
+use data_encoding::HEXLOWER;
+use rebuilderd_common::api::v1::WorkerEnvironment;
+use rebuilderd_common::errors::*;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::fs;
+use tokio::process::Command;
+use tokio::time;
+
+const VERSION_TIMEOUT: u64 = 30;
+
+fn find_in_path(tool: &str) -> Option<PathBuf> {
+    if tool.contains('/') {
+        let path = PathBuf::from(tool);
+        return path.is_file().then_some(path);
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(tool))
+        .find(|path| path.is_file())
+}
+
+async fn tool_version(path: &Path) -> Option<String> {
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = time::timeout(Duration::from_secs(VERSION_TIMEOUT), output)
+        .await
+        .ok()?
+        .ok()?;
+
+    // some tools print their version to stderr
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    let text = String::from_utf8_lossy(&text);
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+async fn check_script(path: &Path) -> Result<String> {
+    let metadata = fs::metadata(path)
+        .await
+        .with_context(|| anyhow!("Rebuilder script does not exist: {path:?}"))?;
+    if !metadata.is_file() {
+        bail!("Rebuilder script is not a file: {path:?}");
+    }
+    if metadata.permissions().mode() & 0o111 == 0 {
+        bail!("Rebuilder script is not executable: {path:?}");
+    }
+
+    let content = fs::read(path)
+        .await
+        .with_context(|| anyhow!("Failed to read rebuilder script: {path:?}"))?;
+    Ok(HEXLOWER.encode(&Sha256::digest(&content)))
+}
+
+async fn kernel_release() -> Option<String> {
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .await
+        .ok()?;
+    Some(release.trim().to_string())
+}
+
+/// Verify the configured backends can actually run on this machine and collect a fingerprint of the environment.
+///
+/// All problems are collected and reported at once, so they can be fixed in one go.
+pub async fn run(config: &config::ConfigFile) -> Result<WorkerEnvironment> {
+    let mut problems = Vec::new();
+    let mut scripts = BTreeMap::new();
+    let mut tools = BTreeMap::new();
+
+    let mut required_tools = Vec::new();
+    if config.diffoscope.enabled
+        || config
+            .backends
+            .values()
+            .any(|backend| backend.compare == config::Compare::Diffoscope)
+    {
+        required_tools.push("diffoscope".to_string());
+    }
+
+    for (name, backend) in &config.backends {
+        match check_script(&backend.path).await {
+            Ok(digest) => {
+                scripts.insert(name.clone(), digest);
+            }
+            Err(err) => problems.push(format!("backend {name:?}: {err:#}")),
+        }
+        if let Some(prefetch) = &backend.prefetch
+            && let Err(err) = check_script(prefetch).await
+        {
+            problems.push(format!("backend {name:?}: {err:#}"));
+        }
+        if backend.compare == config::Compare::Command {
+            match &backend.compare_command {
+                Some(command) => {
+                    if let Err(err) = check_script(&command.path).await {
+                        problems.push(format!("backend {name:?}: {err:#}"));
+                    }
+                }
+                None => problems.push(format!(
+                    "backend {name:?}: compare = \"command\" needs a compare_command"
+                )),
+            }
+        }
+        required_tools.extend(backend.requires.iter().cloned());
+    }
+
+    if config
+        .backends
+        .values()
+        .any(|backend| backend.is_offline(&config.build))
+        && let Err(err) = isolate::check().await
+    {
+        problems.push(format!("offline builds are not supported: {err:#}"));
+    }
+
+    required_tools.sort();
+    required_tools.dedup();
+
+    for tool in required_tools {
+        let Some(path) = find_in_path(&tool) else {
+            problems.push(format!("required tool not found in PATH: {tool:?}"));
+            continue;
+        };
+
+        let version = tool_version(&path)
+            .await
+            .unwrap_or_else(|| "unknown".to_string());
+        debug!("Found {tool:?} at {path:?}: {version}");
+        tools.insert(tool, version);
+    }
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("Self-test failed: {problem}");
+        }
+        bail!("Worker self-test failed with {} problem(s)", problems.len());
+    }
+
+    Ok(WorkerEnvironment {
+        worker_version: env!("CARGO_PKG_VERSION").to_string(),
+        kernel: kernel_release().await,
+        scripts,
+        tools,
+    })
+}
@@ -0,0 +1,181 @@
+use rebuilderd_common::api::v1::{BuildRestApi, RebuildReport};
+use rebuilderd_common::api::{Client, is_unreachable};
+use rebuilderd_common::errors::*;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Build reports are written to disk before they're sent to the daemon, and only removed once the daemon accepted
+/// them. If the daemon is unreachable, eg. because it's being restarted, the reports stay here until it's back.
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    pub fn new(dir: PathBuf) -> Self {
+        Spool { dir }
+    }
+
+    pub async fn store(&self, report: &RebuildReport) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| anyhow!("Failed to create spool directory: {:?}", self.dir))?;
+
+        // sorting by name submits the reports in the order they were built
+        let name = format!(
+            "{}-{}",
+            report.built_at.format("%Y%m%d%H%M%S"),
+            report.queue_id
+        );
+        let path = self.dir.join(format!("{name}.json"));
+        let tmp = self.dir.join(format!(".{name}.tmp"));
+
+        let json = serde_json::to_vec(report)?;
+        fs::write(&tmp, json)
+            .await
+            .with_context(|| anyhow!("Failed to write build report to spool: {tmp:?}"))?;
+        fs::rename(&tmp, &path).await?;
+
+        Ok(path)
+    }
+
+    /// Reports that weren't accepted by the daemon yet, oldest first
+    pub async fn pending(&self) -> Result<Vec<PathBuf>> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(Error::from(err)
+                    .context(format!("Failed to read spool directory: {:?}", self.dir)));
+            }
+        };
+
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    /// Submit the spooled reports until the daemon can't be reached anymore. Reports the daemon rejected are renamed
+    /// to `*.rejected`, they're kept for inspection but not submitted again.
+    pub async fn replay(&self, client: &Client) -> Result<()> {
+        for path in self.pending().await? {
+            let report =
+                match fs::read(&path).await.map_err(Error::from).and_then(|buf| {
+                    serde_json::from_slice::<RebuildReport>(&buf).map_err(Error::from)
+                }) {
+                    Ok(report) => report,
+                    Err(err) => {
+                        error!("Failed to read spooled build report {path:?}: {err:#}");
+                        reject(&path).await?;
+                        continue;
+                    }
+                };
+
+            info!(
+                "Sending build report of job #{} to rebuilderd...",
+                report.queue_id
+            );
+            match client.submit_build_report(report).await {
+                Ok(()) => fs::remove_file(&path).await?,
+                Err(err) if is_unreachable(&err) => {
+                    return Err(err.context(format!(
+                        "Failed to reach rebuilderd, keeping build report in spool: {path:?}"
+                    )));
+                }
+                Err(err) => {
+                    error!("Build report {path:?} was rejected by rebuilderd: {err:#}");
+                    reject(&path).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn reject(path: &Path) -> Result<()> {
+    fs::rename(path, path.with_extension("rejected"))
+        .await
+        .with_context(|| anyhow!("Failed to move rejected build report aside: {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use rebuilderd_common::api::v1::BuildStatus;
+
+    fn report(queue_id: i32, built_at: &str) -> RebuildReport {
+        RebuildReport {
+            queue_id,
+            built_at: NaiveDateTime::parse_from_str(built_at, "%Y-%m-%d %H:%M:%S").unwrap(),
+            build_log: b"build log".to_vec(),
+            status: BuildStatus::Good,
+            artifacts: vec![],
+            downloaded_bytes: Some(1024),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_oldest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::new(dir.path().join("spool"));
+
+        assert!(spool.pending().await.unwrap().is_empty());
+
+        spool
+            .store(&report(7, "2026-10-16 12:00:00"))
+            .await
+            .unwrap();
+        spool
+            .store(&report(3, "2026-10-16 09:30:00"))
+            .await
+            .unwrap();
+        spool
+            .store(&report(5, "2026-10-17 01:00:00"))
+            .await
+            .unwrap();
+
+        let pending = spool.pending().await.unwrap();
+        let names = pending
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "20261016093000-3.json",
+                "20261016120000-7.json",
+                "20261017010000-5.json"
+            ]
+        );
+
+        let buf = std::fs::read(&pending[0]).unwrap();
+        let stored = serde_json::from_slice::<RebuildReport>(&buf).unwrap();
+        assert_eq!(stored.queue_id, 3);
+        assert_eq!(stored.build_log, b"build log");
+        assert_eq!(stored.downloaded_bytes, Some(1024));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_is_not_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::new(dir.path().to_path_buf());
+
+        let path = spool
+            .store(&report(1, "2026-10-16 12:00:00"))
+            .await
+            .unwrap();
+        reject(&path).await.unwrap();
+
+        assert!(spool.pending().await.unwrap().is_empty());
+        assert!(dir.path().join("20261016120000-1.rejected").exists());
+    }
+}
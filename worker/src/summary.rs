@@ -0,0 +1,351 @@
+use crate::compression;
+use rebuilderd_common::api::v1::ArtifactDiffSummary;
+use rebuilderd_common::errors::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use zip::ZipArchive;
+
+/// Larger artifacts are only compared byte-by-byte, listing their files would need all of them in memory
+const MAX_ARCHIVE_SIZE: u64 = 256 * 1024 * 1024;
+/// The summary is meant to be glanced at, the full picture is in the diffoscope output
+const MAX_DIFFERING_FILES: usize = 100;
+/// Archives in archives are opened up to this depth, eg. the `data.tar.gz` of a `.deb`
+const MAX_DEPTH: usize = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    /// Permissions, ownership and timestamps, so a file that only differs in its mtime is listed too
+    metadata: Vec<u8>,
+    content: Vec<u8>,
+}
+
+type Entries = BTreeMap<String, Entry>;
+
+fn first_difference<A: Read, B: Read>(a: A, b: B) -> Result<Option<u64>> {
+    let mut a = BufReader::new(a).bytes();
+    let mut b = BufReader::new(b).bytes();
+
+    let mut pos = 0;
+    loop {
+        match (a.next().transpose()?, b.next().transpose()?) {
+            (None, None) => return Ok(None),
+            (Some(a), Some(b)) if a == b => pos += 1,
+            _ => return Ok(Some(pos)),
+        }
+    }
+}
+
+fn is_tar(bytes: &[u8]) -> bool {
+    bytes.get(257..262) == Some(b"ustar")
+}
+
+fn read_tar<R: Read>(reader: R) -> Result<Entries> {
+    let mut archive = Archive::new(reader);
+    let mut entries = Entries::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        // the checksum covers the other fields, it would make every modified file look like a metadata change
+        let mut header = entry.header().clone();
+        header.as_mut_bytes()[148..156].fill(0);
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        entries.insert(
+            path,
+            Entry {
+                metadata: header.as_bytes().to_vec(),
+                content,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+fn read_zip(bytes: &[u8]) -> Result<Entries> {
+    let mut zip = ZipArchive::new(Cursor::new(bytes))?;
+    let mut entries = Entries::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let metadata = format!("{:?} {:?}", file.last_modified(), file.unix_mode());
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        entries.insert(
+            file.name().to_string(),
+            Entry {
+                metadata: metadata.into_bytes(),
+                content,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+/// The members of an `ar` archive, the container format of `.deb` files
+fn read_ar(bytes: &[u8]) -> Result<Entries> {
+    let mut entries = Entries::new();
+    let mut pos = 8;
+    while pos < bytes.len() {
+        let header = bytes
+            .get(pos..pos + 60)
+            .context("Truncated ar member header")?;
+        let name = String::from_utf8_lossy(&header[..16])
+            .trim_end()
+            .trim_end_matches('/')
+            .to_string();
+        let size = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse::<usize>()
+            .context("Invalid ar member size")?;
+        let start = pos + 60;
+        let content = bytes
+            .get(start..start + size)
+            .context("Truncated ar member")?;
+        entries.insert(
+            name,
+            Entry {
+                metadata: header[16..48].to_vec(),
+                content: content.to_vec(),
+            },
+        );
+        // members are aligned to two bytes
+        pos = start + size + size % 2;
+    }
+    Ok(entries)
+}
+
+/// The files of an archive, `None` if this isn't a format that can be opened
+fn read_archive(bytes: &[u8]) -> Option<Entries> {
+    if bytes.starts_with(b"PK\x03\x04") {
+        read_zip(bytes).ok()
+    } else if bytes.starts_with(b"!<arch>\n") {
+        read_ar(bytes).ok()
+    } else if let Some(tar) = compression::decompress(bytes, MAX_ARCHIVE_SIZE) {
+        is_tar(&tar).then(|| read_tar(&tar[..]).ok()).flatten()
+    } else if is_tar(bytes) {
+        read_tar(bytes).ok()
+    } else {
+        None
+    }
+}
+
+fn differing_files(a: &Entries, b: &Entries, prefix: &str, depth: usize, out: &mut Vec<String>) {
+    let paths = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+    for path in paths {
+        let (a, b) = match (a.get(path), b.get(path)) {
+            (Some(a), Some(b)) if a == b => continue,
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                out.push(format!("{prefix}{path}"));
+                continue;
+            }
+        };
+
+        if a.metadata == b.metadata
+            && depth < MAX_DEPTH
+            && let (Some(a), Some(b)) = (read_archive(&a.content), read_archive(&b.content))
+        {
+            let listed = out.len();
+            differing_files(&a, &b, &format!("{prefix}{path}/"), depth + 1, out);
+            // eg. only the compression of a nested archive differs
+            if out.len() > listed {
+                continue;
+            }
+        }
+
+        out.push(format!("{prefix}{path}"));
+    }
+}
+
+fn summarize_files(original: &Path, rebuilt: &Path) -> Result<ArtifactDiffSummary> {
+    let mut summary = ArtifactDiffSummary {
+        original_size: fs::metadata(original)?.len(),
+        rebuilt_size: fs::metadata(rebuilt)?.len(),
+        first_difference: first_difference(File::open(original)?, File::open(rebuilt)?)?,
+        ..Default::default()
+    };
+
+    if summary.first_difference.is_none()
+        || summary.original_size > MAX_ARCHIVE_SIZE
+        || summary.rebuilt_size > MAX_ARCHIVE_SIZE
+    {
+        return Ok(summary);
+    }
+
+    let original = fs::read(original)?;
+    let rebuilt = fs::read(rebuilt)?;
+    let archives = match (
+        compression::decompress(&original, MAX_ARCHIVE_SIZE),
+        compression::decompress(&rebuilt, MAX_ARCHIVE_SIZE),
+    ) {
+        // the decompressed contents are cut off at the limit, that's not enough to tell they're identical
+        (Some(a), Some(b)) if a == b && (a.len() as u64) < MAX_ARCHIVE_SIZE => {
+            summary.modulo_compression = true;
+            return Ok(summary);
+        }
+        (Some(a), Some(b)) => (read_archive(&a), read_archive(&b)),
+        _ => (read_archive(&original), read_archive(&rebuilt)),
+    };
+    if let (Some(a), Some(b)) = archives {
+        differing_files(&a, &b, "", 1, &mut summary.differing_files);
+    }
+
+    if summary.differing_files.len() > MAX_DIFFERING_FILES {
+        summary.differing_files.truncate(MAX_DIFFERING_FILES);
+        summary.truncated = true;
+    }
+
+    Ok(summary)
+}
+
+/// Describe how the rebuilt artifact differs from the published one, without running diffoscope
+pub async fn summarize(original: &Path, rebuilt: &Path) -> Result<ArtifactDiffSummary> {
+    let original = PathBuf::from(original);
+    let rebuilt = PathBuf::from(rebuilt);
+    tokio::task::spawn_blocking(move || summarize_files(&original, &rebuilt)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{DateTime, ZipWriter};
+
+    fn zip(files: &[(&str, &[u8], u16)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content, year) in files {
+            let options = SimpleFileOptions::default()
+                .last_modified_time(DateTime::from_date_and_time(*year, 1, 1, 0, 0, 0).unwrap());
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn summarize_bytes(a: &[u8], b: &[u8]) -> ArtifactDiffSummary {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original");
+        let rebuilt = dir.path().join("rebuilt");
+        fs::write(&original, a).unwrap();
+        fs::write(&rebuilt, b).unwrap();
+        summarize_files(&original, &rebuilt).unwrap()
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference(&b"abc"[..], &b"abc"[..]).unwrap(), None);
+        assert_eq!(first_difference(&b"abc"[..], &b"abd"[..]).unwrap(), Some(2));
+        assert_eq!(first_difference(&b"ab"[..], &b"abc"[..]).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_summarize_zip() {
+        let original = zip(&[
+            ("foo/__init__.py", b"", 2020),
+            ("foo/__pycache__/bar.pyc", b"\x00\x01", 2020),
+            ("foo/bar.py", b"print()", 2020),
+        ]);
+        let rebuilt = zip(&[
+            ("foo/__init__.py", b"", 2020),
+            ("foo/__pycache__/bar.pyc", b"\x00\x01", 2024),
+            ("foo/bar.py", b"print()", 2020),
+        ]);
+
+        let summary = summarize_bytes(&original, &rebuilt);
+        assert_eq!(summary.size_delta(), 0);
+        assert!(summary.first_difference.is_some());
+        assert_eq!(summary.differing_files, vec!["foo/__pycache__/bar.pyc"]);
+        assert!(!summary.truncated);
+    }
+
+    #[test]
+    fn test_summarize_not_an_archive() {
+        let summary = summarize_bytes(b"hello world", b"hello rebuilderd");
+        assert_eq!(
+            summary,
+            ArtifactDiffSummary {
+                original_size: 11,
+                rebuilt_size: 16,
+                first_difference: Some(6),
+                differing_files: vec![],
+                truncated: false,
+                modulo_compression: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_ar() {
+        let mut ar = b"!<arch>\n".to_vec();
+        ar.extend(
+            format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                "debian-binary", 0, 0, 0, 100644, 4
+            )
+            .as_bytes(),
+        );
+        ar.extend(b"2.0\n");
+        ar.extend(
+            format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                "data.tar.xz/", 0, 0, 0, 100644, 3
+            )
+            .as_bytes(),
+        );
+        ar.extend(b"abc\n");
+
+        let entries = read_ar(&ar).unwrap();
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            vec!["data.tar.xz", "debian-binary"]
+        );
+        assert_eq!(entries["data.tar.xz"].content, b"abc");
+    }
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_summarize_zstd_package() {
+        let original = tar(&[(".PKGINFO", b"pkgname = foo"), ("usr/bin/foo", b"\x7fELF1")]);
+        let rebuilt = tar(&[(".PKGINFO", b"pkgname = foo"), ("usr/bin/foo", b"\x7fELF2")]);
+
+        let summary = summarize_bytes(
+            &zstd::encode_all(&original[..], 19).unwrap(),
+            &zstd::encode_all(&rebuilt[..], 19).unwrap(),
+        );
+        assert_eq!(summary.differing_files, vec!["usr/bin/foo"]);
+        assert!(!summary.modulo_compression);
+    }
+
+    #[test]
+    fn test_summarize_modulo_compression() {
+        let package = tar(&[(".PKGINFO", b"pkgname = foo"), ("usr/bin/foo", b"\x7fELF")]);
+
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gzip.write_all(&package).unwrap();
+
+        let summary = summarize_bytes(
+            &zstd::encode_all(&package[..], 19).unwrap(),
+            &gzip.finish().unwrap(),
+        );
+        assert!(summary.first_difference.is_some());
+        assert!(summary.differing_files.is_empty());
+        assert!(summary.modulo_compression);
+    }
+}